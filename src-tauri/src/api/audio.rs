@@ -0,0 +1,196 @@
+use reqwest::multipart;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+use super::http;
+use super::limits;
+use crate::file_system::paths::long_path;
+use crate::media::commands::extract_video_frame;
+use crate::media::{ffmpeg, formats, probe};
+
+/// Frames sampled from a video captioned together with its sound
+const SAMPLED_FRAMES: usize = 4;
+
+/// Model transcribing the sound of videos for APIs that only take images
+const TRANSCRIPTION_MODEL: &str = "whisper-1";
+
+/// Instructions added to the prompt of a video sent as frames and a transcript
+pub(crate) const FRAMES_PROMPT: &str = "The images are frames sampled in order from a \
+single video. Describe the video as a whole rather than each frame.";
+
+/// Instructions added to the prompt of a video captioned together with its sound
+pub(crate) const AUDIO_PROMPT: &str = "Take the video's sound into account as well as its \
+picture: describe speech, music and other notable sounds where they matter to what \
+happens.";
+
+/// What an image-only API is sent of a video captioned with its sound
+pub(crate) struct VideoContext {
+    /// Frames as JPEG data URLs, in order
+    pub frames: Vec<String>,
+    /// What is said in the video; None when it has no sound or nothing is said
+    pub transcript: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Transcription {
+    text: String,
+}
+
+/// Whether a video has a sound track; assumed when ffprobe is missing
+fn has_audio(path: &Path) -> bool {
+    if !ffmpeg::capabilities().ffprobe {
+        return true;
+    }
+    Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("a")
+        .arg("-show_entries")
+        .arg("stream=index")
+        .arg("-of")
+        .arg("csv=p=0")
+        .arg(path)
+        .output()
+        .is_ok_and(|output| output.status.success() && !output.stdout.trim_ascii().is_empty())
+}
+
+/// Extract the sound of a video as small mono AAC, which transcription APIs accept
+fn extract_audio(path: &Path, target: &Path) -> Result<(), String> {
+    let output = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-i")
+        .arg(path)
+        .arg("-vn")
+        .arg("-ac")
+        .arg("1")
+        .arg("-ar")
+        .arg("16000")
+        .arg("-c:a")
+        .arg("aac")
+        .arg("-b:a")
+        .arg("48k")
+        .arg(target)
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to extract the sound of the video: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+/// Transcription endpoint of the OpenAI-compatible API serving a chat completions
+/// endpoint
+fn transcription_url(api_url: &str) -> Result<String, String> {
+    api_url
+        .trim_end_matches('/')
+        .strip_suffix("/chat/completions")
+        .map(|base| format!("{}/audio/transcriptions", base))
+        .ok_or_else(|| {
+            format!(
+                "Can't find the transcription endpoint of {}; it should end in /chat/completions",
+                api_url
+            )
+        })
+}
+
+/// Transcribe what is said in a video with the Whisper endpoint of an OpenAI-compatible
+/// API
+async fn transcribe(api_url: &str, api_key: &str, video: PathBuf) -> Result<String, String> {
+    let url = transcription_url(api_url)?;
+    let audio = tempfile::Builder::new()
+        .suffix(".m4a")
+        .tempfile()
+        .map_err(|e| format!("Failed to create temporary file: {}", e))?;
+    let target = audio.path().to_path_buf();
+    tauri::async_runtime::spawn_blocking(move || extract_audio(&video, &target))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let limits = limits::current().openai;
+    let bytes = fs::read(audio.path()).map_err(|e| format!("Failed to read sound: {}", e))?;
+    limits.check_upload(bytes.len() as u64)?;
+    debug!("Transcribing {} bytes of sound", bytes.len());
+
+    let file = multipart::Part::bytes(bytes)
+        .file_name("audio.m4a")
+        .mime_str("audio/mp4")
+        .map_err(|e| e.to_string())?;
+    let form = multipart::Form::new()
+        .text("model", TRANSCRIPTION_MODEL)
+        .text("response_format", "json")
+        .part("file", file);
+    let response = http::client()
+        .post(&url)
+        .timeout(limits.timeout())
+        .header("Authorization", format!("Bearer {}", api_key))
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| format!("Transcription request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(format!(
+            "Transcription failed with status {}: {}",
+            status, error_text
+        ));
+    }
+    let body = http::read_limited(response, limits.max_response_bytes()).await?;
+    let transcription: Transcription = serde_json::from_slice(&body)
+        .map_err(|e| format!("Failed to parse transcription: {}", e))?;
+    Ok(transcription.text.trim().to_string())
+}
+
+/// Frames spread evenly over a video, as data URLs
+async fn sample_frames(path: &Path) -> Result<Vec<String>, String> {
+    let duration = probe::probe_media(path, "video")
+        .duration
+        .filter(|duration| *duration > 0.0);
+    let times: Vec<Option<f64>> = match duration {
+        Some(duration) => (0..SAMPLED_FRAMES)
+            .map(|i| Some(duration * (i as f64 + 0.5) / SAMPLED_FRAMES as f64))
+            .collect(),
+        None => vec![None],
+    };
+
+    let mut frames = Vec::with_capacity(times.len());
+    for time in times {
+        frames.push(extract_video_frame(path.to_string_lossy().to_string(), time).await?);
+    }
+    Ok(frames)
+}
+
+/// Gather the frames and the transcript an image-only API captions a video from, or
+/// None for other files
+///
+/// The transcript comes from the Whisper endpoint next to `api_url`, with the same key.
+pub(crate) async fn video_context(
+    api_url: &str,
+    api_key: &str,
+    media_path: &str,
+) -> Result<Option<VideoContext>, String> {
+    let path = long_path(Path::new(media_path));
+    if formats::media_type(&path) != Some("video") {
+        return Ok(None);
+    }
+    ffmpeg::require("captioning videos with their sound")?;
+
+    let frames = sample_frames(&path).await?;
+    let transcript = if has_audio(&path) {
+        Some(transcribe(api_url, api_key, path).await?).filter(|text| !text.is_empty())
+    } else {
+        None
+    };
+    Ok(Some(VideoContext { frames, transcript }))
+}
@@ -0,0 +1,137 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::jobs::manager::Job;
+use crate::project;
+use crate::project::settings::{self, BatchSettings};
+
+/// Most files a batch captions at the same time, whatever the project asks for
+const MAX_PARALLELISM: usize = 16;
+
+/// Time each rate-limited key (a profile or a project) may send its next request at
+static NEXT_REQUEST: Lazy<Mutex<HashMap<String, Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Wait until `key` may send another request under a rate of `requests_per_minute`
+///
+/// Each call reserves the next free slot, so concurrent requests sharing a key share
+/// its rate. Returns right away without a limit.
+pub async fn throttle(key: &str, requests_per_minute: Option<u32>) {
+    let Some(rate) = requests_per_minute.filter(|&rate| rate > 0) else {
+        return;
+    };
+    let interval = Duration::from_secs(60) / rate;
+
+    let wait = {
+        let Ok(mut next) = NEXT_REQUEST.lock() else {
+            return;
+        };
+        let now = Instant::now();
+        let slot = next
+            .get(key)
+            .copied()
+            .filter(|slot| *slot > now)
+            .unwrap_or(now);
+        next.insert(key.to_string(), slot + interval);
+        slot - now
+    };
+
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Batch settings of the project the first file belongs to, or the defaults
+pub fn settings_for(paths: &[String]) -> (String, BatchSettings) {
+    let root = paths
+        .first()
+        .map(|path| project::project_root_for(Path::new(path)))
+        .unwrap_or_default();
+    let batch = settings::load_settings(&root)
+        .map(|settings| settings.batch)
+        .unwrap_or_else(|e| {
+            warn!("Using the default batch settings: {}", e);
+            BatchSettings::default()
+        });
+    (root.to_string_lossy().to_string(), batch)
+}
+
+/// Caption files with the batch settings of their project: `parallelism` files at a
+/// time, within the project's rate limit, trying failed files again up to `retries`
+/// times with a growing delay
+///
+/// `caption` is called with a path and its 1-based position in the batch. Each file is
+/// recorded with the job as it finishes, failures as `Error: ...`. Once the job is
+/// cancelled no further file is started; the results of the files started are
+/// returned in the order of `paths`.
+pub async fn caption_all<F, Fut>(job: &Job, paths: Vec<String>, caption: F) -> Vec<(String, String)>
+where
+    F: Fn(String, usize) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<String, String>> + Send + 'static,
+{
+    let (project, settings) = settings_for(&paths);
+    let rate_key = format!("project:{}", project);
+    let parallelism = settings.parallelism.clamp(1, MAX_PARALLELISM);
+    let slots = Arc::new(Semaphore::new(parallelism));
+    let caption = Arc::new(caption);
+    let total = paths.len() as u64;
+    let done = Arc::new(AtomicU64::new(0));
+    job.progress(0, total, None);
+
+    let mut running = Vec::new();
+    for (index, path) in paths.into_iter().enumerate() {
+        if job.is_cancelled() {
+            break;
+        }
+        let Ok(slot) = slots.clone().acquire_owned().await else {
+            break;
+        };
+        if job.is_cancelled() {
+            break;
+        }
+
+        let job = job.clone();
+        let caption = caption.clone();
+        let rate_key = rate_key.clone();
+        let done = done.clone();
+        let settings = settings.clone();
+        running.push(tauri::async_runtime::spawn(async move {
+            let mut attempt = 0;
+            let result = loop {
+                throttle(&rate_key, settings.requests_per_minute).await;
+                match caption(path.clone(), index + 1).await {
+                    Ok(caption) => break caption,
+                    Err(e) if attempt < settings.retries && !job.is_cancelled() => {
+                        warn!("Retrying {} after an error: {}", path, e);
+                        tokio::time::sleep(Duration::from_secs(2u64.pow(attempt.min(6)))).await;
+                        attempt += 1;
+                    }
+                    Err(e) => break format!("Error: {}", e),
+                }
+            };
+            drop(slot);
+
+            job.complete_item(&path, &result);
+            let finished = done.fetch_add(1, Ordering::SeqCst) + 1;
+            job.progress(finished, total, Some(path.clone()));
+            (path, result)
+        }));
+    }
+
+    let mut results = Vec::new();
+    for task in running {
+        match task.await {
+            Ok(result) => results.push(result),
+            Err(e) => warn!("A caption request failed to finish: {}", e),
+        }
+    }
+    job.progress(results.len() as u64, total, None);
+    results
+}
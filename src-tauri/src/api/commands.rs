@@ -1,9 +1,26 @@
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
 use std::time::Duration;
 use std::path::Path;
+use std::sync::Arc;
 use serde_json;
+use tauri::AppHandle;
+use tracing::{debug, info, instrument, trace, warn};
+
+use super::audio::{self, VideoContext};
+use super::batch::caption_all;
+use super::http::{self, GEMINI_POLL_TIMEOUT, GEMINI_UPLOAD_TIMEOUT};
+use super::limits::{self, LimitSettings};
+use super::profiles::{self, ProfileProvider, ProviderProfile};
+use super::script;
+use super::stream::{self, CaptionStream};
+use crate::captions::{alt_text, batch, postprocess};
+use crate::file_system::commands::{scan_media_files, validate_project_directory};
+use crate::file_system::paths::long_path;
+use crate::jobs::journal;
+use crate::jobs::manager::{self as jobs, JobKind};
+use crate::media::{animation, formats};
 
 // OpenAI API request structure
 #[derive(Serialize)]
@@ -12,6 +29,12 @@ struct OpenAIRequest {
     messages: Vec<Message>,
     max_tokens: u32,
     temperature: f32,
+    /// Structured output format, for models that support it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+    /// Send the reply as server-sent events while it is generated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -52,8 +75,30 @@ struct ChoiceMessage {
     content: String,
 }
 
+// OpenAI streamed response structure
+#[derive(Deserialize, Debug)]
+struct OpenAIStreamChunk {
+    choices: Vec<StreamChoice>,
+}
+
+#[derive(Deserialize, Debug)]
+struct StreamChoice {
+    delta: StreamDelta,
+}
+
+#[derive(Deserialize, Debug)]
+struct StreamDelta {
+    content: Option<String>,
+}
+
 /// Generate a caption for an image or video frame using OpenAI's API
+///
+/// The caption is post-processed with the tag dictionary and trigger rules of the
+/// file's project. With `include_audio`, a video is sent as frames sampled over its
+/// whole length together with a transcript of its sound from the API's Whisper
+/// endpoint, instead of as a single frame.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn generate_caption(
     api_url: String,
     api_key: String,
@@ -63,16 +108,109 @@ pub async fn generate_caption(
     image_detail: String,
     use_detail_parameter: bool,
     video_frame_url: Option<String>,
+    include_audio: Option<bool>,
 ) -> Result<String, String> {
-    // Use provided video frame if available, otherwise create from image path
-    let image_data_url = match video_frame_url {
-        Some(url) => url,
-        None => match create_data_url_from_image(&image_path).await {
-            Ok(url) => url,
-            Err(e) => return Err(format!("Failed to create data URL: {}", e)),
-        },
-    };
+    let video = video_with_audio(&api_url, &api_key, &image_path, include_audio).await?;
+    let request = caption_request(
+        prompt,
+        &image_path,
+        model,
+        image_detail,
+        use_detail_parameter,
+        video_frame_url,
+        video,
+    )
+    .await?;
+
+    let caption = send_openai_request(&api_url, &api_key, &request).await?;
+    Ok(postprocess::apply(Path::new(&image_path), caption))
+}
+
+/// Generate a caption like `generate_caption`, streaming it as it is written
+///
+/// The caption received so far is sent in `caption-stream` events tagged with
+/// `stream_id`; the post-processed caption is returned once the stream ends.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn stream_caption(
+    app: AppHandle,
+    stream_id: String,
+    api_url: String,
+    api_key: String,
+    prompt: String,
+    image_path: String,
+    model: String,
+    image_detail: String,
+    use_detail_parameter: bool,
+    video_frame_url: Option<String>,
+    include_audio: Option<bool>,
+) -> Result<String, String> {
+    let video = video_with_audio(&api_url, &api_key, &image_path, include_audio).await?;
+    let mut request = caption_request(
+        prompt,
+        &image_path,
+        model,
+        image_detail,
+        use_detail_parameter,
+        video_frame_url,
+        video,
+    )
+    .await?;
+    request.stream = Some(true);
+
+    let limits = limits::current().openai;
+    let response =
+        post_openai_request(&api_url, &api_key, &request, limits.stream_timeout()).await?;
+    let stream = CaptionStream::new(app, stream_id);
+    let mut caption = String::new();
+    stream::read_events(response, limits.max_response_bytes(), |data| {
+        let Ok(chunk) = serde_json::from_str::<OpenAIStreamChunk>(data) else {
+            return;
+        };
+        let delta = chunk
+            .choices
+            .first()
+            .and_then(|c| c.delta.content.as_deref());
+        if let Some(delta) = delta.filter(|d| !d.is_empty()) {
+            caption.push_str(delta);
+            stream.emit(&caption);
+        }
+    })
+    .await?;
+
+    if caption.is_empty() {
+        return Err("No caption generated".to_string());
+    }
+    Ok(postprocess::apply(Path::new(&image_path), caption))
+}
 
+/// The frames and transcript of a video to caption with its sound, if `include_audio`
+/// is set and the file is a video
+async fn video_with_audio(
+    api_url: &str,
+    api_key: &str,
+    media_path: &str,
+    include_audio: Option<bool>,
+) -> Result<Option<VideoContext>, String> {
+    if !include_audio.unwrap_or(false) {
+        return Ok(None);
+    }
+    audio::video_context(api_url, api_key, media_path).await
+}
+
+/// Build the OpenAI request captioning an image, or a frame of a video
+///
+/// With a `video` context the video is described from its sampled frames and the
+/// transcript of its sound instead.
+async fn caption_request(
+    prompt: String,
+    image_path: &str,
+    model: String,
+    image_detail: String,
+    use_detail_parameter: bool,
+    video_frame_url: Option<String>,
+    video: Option<VideoContext>,
+) -> Result<OpenAIRequest, String> {
     // Set detail parameter if enabled
     let detail = if use_detail_parameter {
         Some(image_detail)
@@ -80,36 +218,131 @@ pub async fn generate_caption(
         None
     };
 
+    let mut content = vec![MessageContent::Text { text: prompt }];
+    let image_urls = match video {
+        Some(video) => {
+            let transcript = match video.transcript {
+                Some(transcript) => format!("Transcript of the sound:\n{}", transcript),
+                None => "Nothing is said in the video.".to_string(),
+            };
+            content.push(MessageContent::Text {
+                text: format!(
+                    "{}\n{}\n\n{}",
+                    audio::FRAMES_PROMPT,
+                    audio::AUDIO_PROMPT,
+                    transcript
+                ),
+            });
+            video.frames
+        }
+        // Use provided video frame if available, otherwise create from image path
+        None => match video_frame_url {
+            Some(url) => vec![url],
+            None => match create_data_url_from_image(image_path).await {
+                Ok(url) => vec![url],
+                Err(e) => return Err(format!("Failed to create data URL: {}", e)),
+            },
+        },
+    };
+    limits::current()
+        .openai
+        .check_upload(image_urls.iter().map(|url| url.len() as u64).sum())?;
+    content.extend(image_urls.into_iter().map(|url| MessageContent::Image {
+        image_url: ImageUrl {
+            url,
+            detail: detail.clone(),
+        },
+    }));
+
     // Create the API request
-    let request = OpenAIRequest {
+    Ok(OpenAIRequest {
         model,
         messages: vec![Message {
             role: "user".to_string(),
-            content: vec![
-                MessageContent::Text { text: prompt },
-                MessageContent::Image {
-                    image_url: ImageUrl {
-                        url: image_data_url,
-                        detail,
-                    },
-                },
-            ],
+            content,
         }],
         max_tokens: 300,
         temperature: 0.7,
+        response_format: None,
+        stream: None,
+    })
+}
+
+/// Instructions of the grammar pass over existing captions
+const GRAMMAR_PROMPT: &str = "Fix the spelling and grammar mistakes in the image caption below. \
+Keep its wording, meaning and style otherwise. Reply with only the corrected caption, or \
+with the caption unchanged if it has no mistakes.";
+
+/// Ask an OpenAI-compatible model to correct the spelling and grammar of a caption
+pub(crate) async fn correct_caption(
+    api_url: &str,
+    api_key: &str,
+    model: &str,
+    caption: &str,
+) -> Result<String, String> {
+    let request = OpenAIRequest {
+        model: model.to_string(),
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: vec![MessageContent::Text {
+                text: format!("{}\n\n{}", GRAMMAR_PROMPT, caption),
+            }],
+        }],
+        max_tokens: 600,
+        temperature: 0.0,
+        response_format: None,
+        stream: None,
     };
 
-    // Send the request to OpenAI
-    let client = Client::builder()
-        .timeout(Duration::from_secs(30))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let corrected = send_openai_request(api_url, api_key, &request).await?;
+    Ok(corrected.trim().trim_matches('"').trim().to_string())
+}
 
-    let response = client
-        .post(&api_url)
+/// Send a chat completion request to an OpenAI-compatible API and return the text of
+/// the first choice
+async fn send_openai_request(
+    api_url: &str,
+    api_key: &str,
+    request: &OpenAIRequest,
+) -> Result<String, String> {
+    let limits = limits::current().openai;
+    let response = post_openai_request(api_url, api_key, request, limits.timeout()).await?;
+
+    // Capture response info before parsing
+    let status = response.status();
+    let headers = response.headers().clone();
+    
+    // Parse the response
+    let body = http::read_limited(response, limits.max_response_bytes()).await?;
+    let response_body: OpenAIResponse = serde_json::from_slice(&body)
+        .map_err(|e| {
+            warn!(%status, ?headers, "Failed to parse OpenAI API response as JSON: {}", e);
+            "Failed to parse API response. This might be a network/encoding issue, the API returned non-JSON data, or there's a server error.".to_string()
+        })?;
+
+    // Extract the caption
+    if let Some(choice) = response_body.choices.first() {
+        Ok(choice.message.content.clone())
+    } else {
+        Err("No caption generated".to_string())
+    }
+}
+
+/// Send a request to an OpenAI-compatible API, turning error statuses into errors
+#[instrument(skip_all, fields(url = %api_url))]
+async fn post_openai_request(
+    api_url: &str,
+    api_key: &str,
+    request: &OpenAIRequest,
+    timeout: Duration,
+) -> Result<reqwest::Response, String> {
+    // Send the request to OpenAI
+    let response = http::client()
+        .post(api_url)
+        .timeout(timeout)
         .header("Content-Type", "application/json")
         .header("Authorization", format!("Bearer {}", api_key))
-        .json(&request)
+        .json(request)
         .send()
         .await
         .map_err(|e| format!("API request failed: {}", e))?;
@@ -133,27 +366,7 @@ pub async fn generate_caption(
         ));
     }
 
-    // Capture response info before parsing
-    let status = response.status();
-    let headers = response.headers().clone();
-    
-    // Parse the response
-    let response_body: OpenAIResponse = response
-        .json()
-        .await
-        .map_err(|e| {
-            println!("Failed to parse OpenAI API response as JSON: {}", e);
-            println!("Response status was: {}", status);
-            println!("Response headers: {:?}", headers);
-            "Failed to parse API response. This might be a network/encoding issue, the API returned non-JSON data, or there's a server error.".to_string()
-        })?;
-
-    // Extract the caption
-    if let Some(choice) = response_body.choices.first() {
-        Ok(choice.message.content.clone())
-    } else {
-        Err("No caption generated".to_string())
-    }
+    Ok(response)
 }
 
 /// Create a data URL from an image file
@@ -163,15 +376,15 @@ async fn create_data_url_from_image(path: &str) -> Result<String, Box<dyn Error>
     use std::io::Cursor;
     use std::path::Path;
 
-    let path = Path::new(path);
+    let path = &long_path(Path::new(path));
 
     // Check if the file exists
     if !path.exists() {
         return Err(format!("File not found: {}", path.display()).into());
     }
 
-    // Read the image file
-    let img = image::open(path)?;
+    // Read the image file; animations are captioned from their middle frame
+    let img = animation::representative_frame(path)?;
 
     // Convert to JPEG format with reasonable quality
     let mut buffer = Vec::new();
@@ -185,9 +398,32 @@ async fn create_data_url_from_image(path: &str) -> Result<String, Box<dyn Error>
     Ok(format!("data:image/jpeg;base64,{}", base64_string))
 }
 
+/// Save the frame an animation is captioned from to a temporary JPEG
+fn write_representative_frame(path: &Path) -> Result<tempfile::NamedTempFile, String> {
+    let frame = animation::representative_frame(path)
+        .map_err(|e| format!("Failed to read animation: {}", e))?;
+    let file = tempfile::Builder::new()
+        .suffix(".jpg")
+        .tempfile()
+        .map_err(|e| format!("Failed to create temporary file: {}", e))?;
+    frame
+        .to_rgb8()
+        .save_with_format(file.path(), image::ImageFormat::Jpeg)
+        .map_err(|e| format!("Failed to write animation frame: {}", e))?;
+    Ok(file)
+}
+
 /// Generate captions for multiple images and videos
+///
+/// Runs as a captioning job, paced by the batch settings of the files' project
+/// (parallelism, rate limit and retries); cancelling it stops after the files being
+/// captioned and returns the captions generated so far. The job is resumable: if the app exits mid-batch,
+/// the captions already generated are listed by `list_interrupted_jobs`.
+/// `include_audio` works as in `generate_caption`.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn generate_captions(
+    app: AppHandle,
     api_url: String,
     api_key: String,
     prompt: String,
@@ -195,55 +431,105 @@ pub async fn generate_captions(
     model: String,
     image_detail: String,
     use_detail_parameter: bool,
+    include_audio: Option<bool>,
 ) -> Result<Vec<(String, String)>, String> {
-    let mut results = Vec::new();
-
-    for path in image_paths {
-        // Check if the file is a video
-        let path_obj = std::path::Path::new(&path);
-        let is_video = if let Some(ext) = path_obj.extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
-            ["mp4", "webm", "mov", "avi"].contains(&ext_str.as_str())
-        } else {
-            false
-        };
+    let description = format!("Caption {} files", image_paths.len());
 
-        // For videos, extract the first frame
-        let video_frame_url = if is_video {
-            match super::super::media::commands::extract_video_frame(path.clone(), None).await {
-                Ok(frame) => Some(frame),
-                Err(e) => {
-                    eprintln!("Failed to extract video frame: {}", e);
-                    None
-                }
+    jobs::run_async(&app, JobKind::Captioning, description, |job| async move {
+        // Everything but the API key, which is never written to disk
+        let params = serde_json::json!({
+            "provider": "openai",
+            "api_url": api_url,
+            "prompt": prompt,
+            "model": model,
+            "image_detail": image_detail,
+            "use_detail_parameter": use_detail_parameter,
+            "include_audio": include_audio,
+        });
+        job.make_resumable(params, &image_paths);
+
+        let results = caption_all(&job, image_paths, move |path, _| {
+            let api_url = api_url.clone();
+            let api_key = api_key.clone();
+            let prompt = prompt.clone();
+            let model = model.clone();
+            let image_detail = image_detail.clone();
+            async move {
+                let video_frame_url = first_video_frame(&path).await;
+                generate_caption(
+                    api_url,
+                    api_key,
+                    prompt,
+                    path,
+                    model,
+                    image_detail,
+                    use_detail_parameter,
+                    video_frame_url,
+                    include_audio,
+                )
+                .await
             }
-        } else {
-            None
-        };
+        })
+        .await;
+        Ok(results)
+    })
+    .await
+}
 
-        // Generate caption
-        match generate_caption(
-            api_url.clone(),
-            api_key.clone(),
-            prompt.clone(),
-            path.clone(),
-            model.clone(),
-            image_detail.clone(),
-            use_detail_parameter,
-            video_frame_url,
-        )
-        .await
-        {
-            Ok(caption) => results.push((path, caption)),
-            Err(e) => results.push((path, format!("Error: {}", e))),
-        }
+/// Caption one file with an OpenAI-compatible API, returning the error text on failure
+pub(crate) async fn generate_captions_item(
+    api_url: &str,
+    api_key: &str,
+    prompt: &str,
+    path: &str,
+    model: &str,
+    image_detail: &str,
+    use_detail_parameter: bool,
+) -> String {
+    let video_frame_url = first_video_frame(path).await;
+
+    // Generate caption
+    match generate_caption(
+        api_url.to_string(),
+        api_key.to_string(),
+        prompt.to_string(),
+        path.to_string(),
+        model.to_string(),
+        image_detail.to_string(),
+        use_detail_parameter,
+        video_frame_url,
+        None,
+    )
+    .await
+    {
+        Ok(caption) => caption,
+        Err(e) => format!("Error: {}", e),
     }
+}
 
-    Ok(results)
+/// The first frame of a video as a data URL, for APIs that only take images; `None`
+/// for other files
+async fn first_video_frame(path: &str) -> Option<String> {
+    // Check if the file is a video
+    let path_obj = std::path::Path::new(path);
+    if formats::media_type(path_obj) != Some("video") {
+        return None;
+    }
+
+    match super::super::media::commands::extract_video_frame(path.to_string(), None).await {
+        Ok(frame) => Some(frame),
+        Err(e) => {
+            warn!("Failed to extract video frame: {}", e);
+            None
+        }
+    }
 }
 
 // Gemini API structures
 
+/// Model Gemini requests use unless a provider profile names another
+const GEMINI_DEFAULT_MODEL: &str = "gemini-2.0-flash";
+
 // Gemini file upload response
 #[derive(Deserialize, Debug)]
 struct GeminiFileResponse {
@@ -262,6 +548,36 @@ struct GeminiFileInfo {
     state: String,
 }
 
+// Gemini file list response, one page at a time
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GeminiFileList {
+    #[serde(default)]
+    files: Vec<GeminiListedFile>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GeminiListedFile {
+    name: String,
+    #[serde(default)]
+    display_name: String,
+}
+
+/// Result of deleting stale uploads from the Gemini Files API
+#[derive(Debug, Default, Serialize)]
+pub struct GeminiCleanup {
+    /// Uploads of this app that were deleted
+    pub deleted: usize,
+    /// Uploads of this app that are newer than the cutoff
+    pub kept: usize,
+    /// Files not uploaded by this app, which are left alone
+    pub foreign: usize,
+    /// Uploads that could not be deleted
+    pub failed: usize,
+}
+
 // Gemini API request structure
 #[derive(Serialize)]
 struct GeminiRequest {
@@ -319,8 +635,10 @@ struct GeminiResponseSchema {
 }
 
 #[derive(Serialize)]
-struct GeminiProperties {
-    caption: GeminiCaption,
+#[serde(untagged)]
+enum GeminiProperties {
+    Caption { caption: GeminiCaption },
+    AltText { alt_text: GeminiCaption },
 }
 
 #[derive(Serialize)]
@@ -329,22 +647,152 @@ struct GeminiCaption {
     caption_type: String,
 }
 
+/// Structured output asked of Gemini
+#[derive(Clone, Copy)]
+enum GeminiOutput {
+    Caption,
+    AltText,
+}
+
+impl GeminiOutput {
+    /// Field of the response holding the text
+    fn field(self) -> &'static str {
+        match self {
+            GeminiOutput::Caption => "caption",
+            GeminiOutput::AltText => "alt_text",
+        }
+    }
+
+    fn properties(self) -> GeminiProperties {
+        let text = GeminiCaption {
+            caption_type: "string".to_string(),
+        };
+        match self {
+            GeminiOutput::Caption => GeminiProperties::Caption { caption: text },
+            GeminiOutput::AltText => GeminiProperties::AltText { alt_text: text },
+        }
+    }
+}
+
 // Gemini API response structure
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 struct GeminiResponse {
+    #[serde(default)]
     candidates: Vec<GeminiCandidate>,
+    /// Present when the prompt itself was blocked, in which case there are no candidates
+    prompt_feedback: Option<GeminiPromptFeedback>,
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 struct GeminiCandidate {
+    /// Missing when the candidate was blocked before any text was generated
+    #[serde(default)]
     content: GeminiCandidateContent,
+    finish_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<GeminiSafetyRating>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 struct GeminiCandidateContent {
+    #[serde(default)]
     parts: Vec<GeminiResponsePart>,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GeminiPromptFeedback {
+    block_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<GeminiSafetyRating>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeminiSafetyRating {
+    category: String,
+    #[serde(default)]
+    probability: String,
+    #[serde(default)]
+    blocked: bool,
+}
+
+/// Finish reasons of a candidate that was cut off by Gemini's content filters
+const GEMINI_BLOCK_REASONS: &[&str] = &[
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+    "IMAGE_SAFETY",
+];
+
+/// Readable names of the categories a response was blocked for, like `sexually
+/// explicit` for `HARM_CATEGORY_SEXUALLY_EXPLICIT`
+///
+/// Ratings flagged as blocked are named; without any, those rated a medium or high
+/// probability are.
+fn blocked_categories(ratings: &[GeminiSafetyRating]) -> Vec<String> {
+    let name = |rating: &GeminiSafetyRating| {
+        rating
+            .category
+            .strip_prefix("HARM_CATEGORY_")
+            .unwrap_or(&rating.category)
+            .to_lowercase()
+            .replace('_', " ")
+    };
+
+    let blocked: Vec<String> = ratings.iter().filter(|r| r.blocked).map(name).collect();
+    if !blocked.is_empty() {
+        return blocked;
+    }
+    ratings
+        .iter()
+        .filter(|r| matches!(r.probability.as_str(), "MEDIUM" | "HIGH"))
+        .map(name)
+        .collect()
+}
+
+/// Error describing why Gemini blocked a request, naming the safety categories
+fn gemini_block_error(reason: &str, ratings: &[GeminiSafetyRating]) -> String {
+    let categories = blocked_categories(ratings);
+    let detail = if categories.is_empty() {
+        String::new()
+    } else {
+        format!(" for {}", categories.join(", "))
+    };
+    format!(
+        "Blocked by Gemini ({}){}. Adjust the safety settings or the prompt and try again.",
+        reason, detail
+    )
+}
+
+/// Error for a response that was blocked or stopped without text, if it was
+///
+/// A blocked prompt is reported through the prompt feedback; a blocked or truncated
+/// reply through the finish reason of its candidate.
+fn gemini_finish_error(response: &GeminiResponse) -> Option<String> {
+    if let Some(feedback) = &response.prompt_feedback {
+        if let Some(reason) = &feedback.block_reason {
+            return Some(gemini_block_error(reason, &feedback.safety_ratings));
+        }
+    }
+
+    let candidate = response.candidates.first()?;
+    let reason = candidate.finish_reason.as_deref()?;
+    if GEMINI_BLOCK_REASONS.contains(&reason) {
+        return Some(gemini_block_error(reason, &candidate.safety_ratings));
+    }
+    if reason != "STOP" && candidate.content.parts.is_empty() {
+        return Some(format!(
+            "No caption generated: Gemini stopped with reason {}",
+            reason
+        ));
+    }
+    None
+}
+
 #[derive(Deserialize, Debug)]
 struct GeminiResponsePart {
     text: String,
@@ -356,60 +804,60 @@ async fn wait_for_file_active(
     file_name: &str,
     max_attempts: usize,
 ) -> Result<bool, Box<dyn Error>> {
-    let client = Client::new();
-    
     // Get file endpoint
     let url = format!(
         "https://generativelanguage.googleapis.com/v1beta/files/{}?key={}",
         file_name, api_key
     );
     
-    println!("Waiting for file {} to become active...", file_name);
+    debug!("Waiting for file {} to become active...", file_name);
     
     // Poll the file state with backoff
     for attempt in 0..max_attempts {
         // Send request to check file state
-        let response = client
+        let response = http::client()
             .get(&url)
+            .timeout(GEMINI_POLL_TIMEOUT)
             .send()
             .await?;
         
         if response.status().is_success() {
             let file_info: GeminiFileInfo = response.json().await?;
             
-            println!("File state check attempt {}: state = {}", attempt + 1, file_info.state);
+            debug!("File state check attempt {}: state = {}", attempt + 1, file_info.state);
             
             // If file is active, we're good to go
             if file_info.state == "ACTIVE" {
-                println!("File is now ACTIVE and ready to use");
+                debug!("File is now ACTIVE and ready to use");
                 return Ok(true);
             }
             
             // If file failed, no point in waiting
             if file_info.state != "PROCESSING" {
-                println!("File is in {} state, not ACTIVE or PROCESSING", file_info.state);
+                warn!("File is in {} state, not ACTIVE or PROCESSING", file_info.state);
                 return Err(format!("File is in {} state, not ACTIVE", file_info.state).into());
             }
             
             // Wait with exponential backoff (start with 5s, then 10s, 20s, etc.)
             let wait_time = Duration::from_secs(5u64.saturating_pow(attempt as u32));
-            println!("File still processing, waiting for {} seconds before next check", 5u64.saturating_pow(attempt as u32));
+            debug!("File still processing, waiting for {} seconds before next check", 5u64.saturating_pow(attempt as u32));
             tokio::time::sleep(wait_time).await;
         } else {
             // If we can't get file info, return error
             let status = response.status();
             let error_text = response.text().await?;
-            println!("Failed to get file status: {} - {}", status, error_text);
+            warn!("Failed to get file status: {} - {}", status, error_text);
             return Err(format!("Failed to get file status: {} - {}", status, error_text).into());
         }
     }
     
     // Exhausted all attempts
-    println!("Exhausted all {} attempts waiting for file to become active", max_attempts);
+    warn!("Exhausted all {} attempts waiting for file to become active", max_attempts);
     Err("File did not become ACTIVE after maximum wait time".into())
 }
 
 /// Upload a file to Gemini's API and wait for it to be ready
+#[instrument(skip_all, fields(file = %file_path))]
 async fn upload_file_to_gemini(
     api_key: &str,
     file_path: &str,
@@ -418,10 +866,16 @@ async fn upload_file_to_gemini(
     use reqwest::multipart;
     use std::time::{SystemTime, UNIX_EPOCH};
     
-    println!("Starting file upload for: {}", file_path);
+    debug!("Starting file upload for: {}", file_path);
     
-    let file_bytes = tokio::fs::read(file_path).await?;
-    println!("Read {} bytes from file", file_bytes.len());
+    let limits = limits::current().gemini;
+    let file_size = tokio::fs::metadata(long_path(Path::new(file_path)))
+        .await?
+        .len();
+    limits.check_upload(file_size)?;
+    
+    let file_bytes = tokio::fs::read(long_path(Path::new(file_path))).await?;
+    debug!("Read {} bytes from file", file_bytes.len());
     
     let file_name = Path::new(file_path)
         .file_name()
@@ -435,9 +889,8 @@ async fn upload_file_to_gemini(
         .as_millis();
     
     let unique_name = format!("{}_{}", file_name, timestamp);
-    println!("Using unique name for upload: {}", unique_name);
+    debug!("Using unique name for upload: {}", unique_name);
     
-    let client = Client::new();
     let url = format!(
         "https://generativelanguage.googleapis.com/upload/v1beta/files?key={}",
         api_key
@@ -446,7 +899,7 @@ async fn upload_file_to_gemini(
     // Create the file metadata part with unique name
     let metadata_json = format!("{{\"file\": {{\"display_name\": \"{}\"}}}}", unique_name);
     
-    println!("Uploading file with MIME type: {}", mime_type);
+    debug!("Uploading file with MIME type: {}", mime_type);
     
     // Create multipart form with metadata and file
     let form = multipart::Form::new()
@@ -459,9 +912,10 @@ async fn upload_file_to_gemini(
         );
     
     // Send the request
-    println!("Sending upload request to Gemini API...");
-    let response = client
+    debug!("Sending upload request to Gemini API...");
+    let response = http::client()
         .post(&url)
+        .timeout(GEMINI_UPLOAD_TIMEOUT.max(limits.timeout()))
         .multipart(form)
         .send()
         .await?;
@@ -469,34 +923,151 @@ async fn upload_file_to_gemini(
     if !response.status().is_success() {
         let status = response.status();
         let error_text = response.text().await?;
-        println!("Upload failed with status {}: {}", status, error_text);
+        warn!("Upload failed with status {}: {}", status, error_text);
         return Err(format!("Upload failed with status {}: {}", status, error_text).into());
     }
     
-    println!("Upload successful, parsing response...");
+    debug!("Upload successful, parsing response...");
     let file_response: GeminiFileResponse = response.json().await?;
-    println!("File uploaded with name: {} and URI: {}", file_response.file.name, file_response.file.uri);
+    debug!("File uploaded with name: {} and URI: {}", file_response.file.name, file_response.file.uri);
     
     // Extract file name from URI (typically the last part after the slash)
     let file_id = file_response.file.name.split('/').last()
         .ok_or("Invalid file name format")?;
-    println!("Extracted file ID: {}", file_id);
+    debug!("Extracted file ID: {}", file_id);
     
     // Wait for file to become active (max 10 attempts with exponential backoff)
-    println!("Waiting for file to become active...");
+    debug!("Waiting for file to become active...");
     match wait_for_file_active(api_key, file_id, 10).await {
         Ok(_) => {
-            println!("File is active and ready to use");
+            debug!("File is active and ready to use");
             Ok(file_response.file.uri)
         },
         Err(e) => {
-            println!("File activation failed: {}", e);
+            warn!("File activation failed: {}", e);
             Err(format!("File uploaded but not ready for use: {}", e).into())
         },
     }
 }
 
+/// Upload time of a file uploaded by [`upload_file_to_gemini`], in milliseconds since
+/// the Unix epoch, read from the `<file name>_<timestamp>` display name it is given
+fn gemini_upload_timestamp(display_name: &str) -> Option<u128> {
+    let (_, timestamp) = display_name.rsplit_once('_')?;
+    // Millisecond timestamps have 13 digits until the year 2286
+    if timestamp.len() != 13 || !timestamp.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    timestamp.parse().ok()
+}
+
+/// Delete files this app uploaded to the Gemini Files API that are older than
+/// `max_age_minutes` (one hour by default)
+///
+/// Uploads count against the Files API quota until Gemini expires them. Files are
+/// recognized by the timestamp in their display name; anything else is left alone.
+#[tauri::command]
+pub async fn cleanup_gemini_files(
+    api_key: String,
+    max_age_minutes: Option<u64>,
+) -> Result<GeminiCleanup, String> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let max_age = Duration::from_secs(max_age_minutes.unwrap_or(60) * 60);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let cutoff = now.saturating_sub(max_age.as_millis());
+
+    // Collect every page before deleting, so deletions don't shift the pages
+    let mut stale = Vec::new();
+    let mut report = GeminiCleanup::default();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut request = http::client()
+            .get("https://generativelanguage.googleapis.com/v1beta/files")
+            .query(&[("key", api_key.as_str()), ("pageSize", "100")])
+            .timeout(GEMINI_POLL_TIMEOUT);
+        if let Some(token) = &page_token {
+            request = request.query(&[("pageToken", token.as_str())]);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to list Gemini files: {}", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!(
+                "Failed to list Gemini files: {} - {}",
+                status, error_text
+            ));
+        }
+        let page: GeminiFileList = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Gemini file list: {}", e))?;
+
+        for file in page.files {
+            match gemini_upload_timestamp(&file.display_name) {
+                Some(timestamp) if timestamp < cutoff => stale.push(file.name),
+                Some(_) => report.kept += 1,
+                None => report.foreign += 1,
+            }
+        }
+
+        match page.next_page_token.filter(|token| !token.is_empty()) {
+            Some(token) => page_token = Some(token),
+            None => break,
+        }
+    }
+
+    for name in stale {
+        let url = format!("https://generativelanguage.googleapis.com/v1beta/{}", name);
+        let result = http::client()
+            .delete(&url)
+            .query(&[("key", api_key.as_str())])
+            .timeout(GEMINI_POLL_TIMEOUT)
+            .send()
+            .await;
+        match result {
+            Ok(response) if response.status().is_success() => report.deleted += 1,
+            Ok(response) => {
+                warn!("Failed to delete Gemini file {}: {}", name, response.status());
+                report.failed += 1;
+            }
+            Err(e) => {
+                warn!("Failed to delete Gemini file {}: {}", name, e);
+                report.failed += 1;
+            }
+        }
+    }
+
+    info!(
+        "Deleted {} stale Gemini uploads ({} failed)",
+        report.deleted, report.failed
+    );
+    Ok(report)
+}
+
+/// The prompt of a Gemini caption, asking to describe the sound of a video as well
+/// when `include_audio` is set
+///
+/// Gemini gets the whole video, sound included, so it only needs to be told to listen.
+fn gemini_prompt(prompt: String, media_path: &str, include_audio: Option<bool>) -> String {
+    let is_video = formats::media_type(Path::new(media_path)) == Some("video");
+    if include_audio.unwrap_or(false) && is_video {
+        format!("{}\n\n{}", prompt, audio::AUDIO_PROMPT)
+    } else {
+        prompt
+    }
+}
+
 /// Generate a caption for a video or image using Google's Gemini API
+///
+/// With `include_audio`, a video is described from its sound as well as its picture.
 #[tauri::command]
 pub async fn generate_gemini_caption(
     api_key: String,
@@ -504,6 +1075,65 @@ pub async fn generate_gemini_caption(
     media_path: String,
     system_instruction: Option<String>,
     temperature: Option<f32>,
+    include_audio: Option<bool>,
+) -> Result<String, String> {
+    let caption = generate_gemini_with_retry(
+        api_key,
+        gemini_prompt(prompt, &media_path, include_audio),
+        media_path.clone(),
+        system_instruction,
+        temperature,
+        None,
+        GeminiOutput::Caption,
+        None,
+    )
+    .await?;
+    Ok(postprocess::apply(Path::new(&media_path), caption))
+}
+
+/// Generate a caption like `generate_gemini_caption`, streaming it as it is written
+///
+/// The caption received so far is sent in `caption-stream` events tagged with
+/// `stream_id`; the post-processed caption is returned once the stream ends.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn stream_gemini_caption(
+    app: AppHandle,
+    stream_id: String,
+    api_key: String,
+    prompt: String,
+    media_path: String,
+    system_instruction: Option<String>,
+    temperature: Option<f32>,
+    include_audio: Option<bool>,
+) -> Result<String, String> {
+    let stream = CaptionStream::new(app, stream_id);
+    let caption = generate_gemini_with_retry(
+        api_key,
+        gemini_prompt(prompt, &media_path, include_audio),
+        media_path.clone(),
+        system_instruction,
+        temperature,
+        None,
+        GeminiOutput::Caption,
+        Some(&stream),
+    )
+    .await?;
+    Ok(postprocess::apply(Path::new(&media_path), caption))
+}
+
+/// Generate text for a media file with Gemini, retrying once when the uploaded file
+/// needs to be uploaded again
+#[allow(clippy::too_many_arguments)]
+async fn generate_gemini_with_retry(
+    api_key: String,
+    prompt: String,
+    media_path: String,
+    system_instruction: Option<String>,
+    temperature: Option<f32>,
+    model: Option<String>,
+    output: GeminiOutput,
+    stream: Option<&CaptionStream>,
 ) -> Result<String, String> {
     // Try the operation with one automatic retry for file state errors
     match generate_gemini_caption_internal(
@@ -512,6 +1142,9 @@ pub async fn generate_gemini_caption(
         media_path.clone(),
         system_instruction.clone(),
         temperature,
+        model.clone(),
+        output,
+        stream,
         false, // Not a retry yet
     ).await {
         Ok(caption) => Ok(caption),
@@ -524,6 +1157,9 @@ pub async fn generate_gemini_caption(
                     media_path,
                     system_instruction,
                     temperature,
+                    model,
+                    output,
+                    stream,
                     true, // This is a retry
                 ).await
             } else {
@@ -535,17 +1171,24 @@ pub async fn generate_gemini_caption(
 }
 
 /// Internal implementation of Gemini caption generation with retry flag
+///
+/// With a `stream`, the reply is streamed and the text received so far forwarded to it.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip_all, fields(media = %media_path, retry = is_retry))]
 async fn generate_gemini_caption_internal(
     api_key: String,
     prompt: String,
     media_path: String,
     system_instruction: Option<String>,
     temperature: Option<f32>,
+    model: Option<String>,
+    output: GeminiOutput,
+    stream: Option<&CaptionStream>,
     is_retry: bool,
 ) -> Result<String, String> {
-    println!("Starting Gemini caption generation for: {}", media_path);
+    info!("Starting Gemini caption generation for: {}", media_path);
     if is_retry {
-        println!("This is a retry attempt");
+        debug!("This is a retry attempt");
     }
     
     // Determine mime type from file extension
@@ -556,28 +1199,31 @@ async fn generate_gemini_caption_internal(
         .unwrap_or("")
         .to_lowercase();
     
-    let mime_type = match extension.as_str() {
-        "jpg" | "jpeg" => "image/jpeg",
-        "png" => "image/png",
-        "webp" => "image/webp",
-        "gif" => "image/gif",
-        "mp4" => "video/mp4",
-        "mov" => "video/quicktime",
-        "avi" => "video/x-msvideo",
-        "webm" => "video/webm",
-        _ => return Err(format!("Unsupported file type: {}", extension)),
+    let mime_type = formats::mime_type(path)
+        .ok_or_else(|| format!("Unsupported file type: {}", extension))?;
+    debug!("Detected MIME type: {}", mime_type);
+
+    // Gemini reads animated GIFs and WebPs as still images, so send the frame that
+    // stands for the animation instead of whichever one it would pick
+    let frame = if animation::is_animated(path) {
+        Some(write_representative_frame(path)?)
+    } else {
+        None
+    };
+    let (upload_path, mime_type) = match &frame {
+        Some(frame) => (frame.path().to_string_lossy().to_string(), "image/jpeg"),
+        None => (media_path.clone(), mime_type),
     };
-    println!("Detected MIME type: {}", mime_type);
     
     // Upload the file to Gemini
-    println!("Uploading file to Gemini API...");
-    let file_uri = match upload_file_to_gemini(&api_key, &media_path, mime_type).await {
+    debug!("Uploading file to Gemini API...");
+    let file_uri = match upload_file_to_gemini(&api_key, &upload_path, mime_type).await {
         Ok(uri) => {
-            println!("File uploaded successfully with URI: {}", uri);
+            debug!("File uploaded successfully with URI: {}", uri);
             uri
         },
         Err(e) => {
-            println!("File upload failed: {}", e);
+            warn!("File upload failed: {}", e);
             return Err(format!("Failed to upload file: {}", e));
         },
     };
@@ -623,60 +1269,62 @@ async fn generate_gemini_caption_internal(
             response_mime_type: "application/json".to_string(),
             response_schema: GeminiResponseSchema {
                 schema_type: "object".to_string(),
-                properties: GeminiProperties {
-                    caption: GeminiCaption {
-                        caption_type: "string".to_string(),
-                    },
-                },
+                properties: output.properties(),
             },
         },
     };
     
     // Send the request to Gemini
-    println!("Creating HTTP client for Gemini API request...");
-    let client = Client::builder()
-        .timeout(Duration::from_secs(120)) // Longer timeout for video processing
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-    
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:generateContent?key={}",
-        api_key
-    );
+    let model = model.as_deref().unwrap_or(GEMINI_DEFAULT_MODEL);
+    let url = match stream {
+        Some(_) => format!(
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?alt=sse&key={}",
+            model, api_key
+        ),
+        None => format!(
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+            model, api_key
+        ),
+    };
     
-    println!("Sending caption generation request to Gemini API...");
-    let response = client
+    debug!("Sending caption generation request to Gemini API...");
+    let limits = limits::current().gemini;
+    let response = http::client()
         .post(&url)
+        .timeout(match stream {
+            Some(_) => limits.stream_timeout(),
+            None => limits.timeout(),
+        })
         .header("Content-Type", "application/json")
         .json(&request)
         .send()
         .await
         .map_err(|e| {
-            println!("API request failed: {}", e);
+            warn!("API request failed: {}", e);
             format!("API request failed: {}", e)
         })?;
     
     // Check if the request was successful
     if !response.status().is_success() {
         let status = response.status();
-        println!("Received error status code: {}", status);
+        warn!("Received error status code: {}", status);
         
         let error_text = response
             .text()
             .await
             .unwrap_or_else(|_| "Unknown error".to_string());
         
-        println!("Error response body: {}", error_text);
+        debug!("Error response body: {}", error_text);
         
         // Check for specific Gemini error about file state
         if error_text.contains("not in an ACTIVE state") {
-            println!("Detected 'not in an ACTIVE state' error");
+            debug!("Detected 'not in an ACTIVE state' error");
             if is_retry {
                 // If this is already a retry, give up
-                println!("This was already a retry attempt, giving up");
+                warn!("This was already a retry attempt, giving up");
                 return Err("Failed to process file after retry. Please try again later.".to_string());
             } else {
-                println!("Will retry with a fresh upload");
+                debug!("Will retry with a fresh upload");
                 return Err("The file needs to be re-uploaded. Please try again.".to_string());
             }
         }
@@ -687,75 +1335,802 @@ async fn generate_gemini_caption_internal(
         ));
     }
     
-    println!("Received successful response from Gemini API");
+    debug!("Received successful response from Gemini API");
     
-    // Parse the response
-    println!("Parsing JSON response...");
-    let response_body: GeminiResponse = response
-        .json()
-        .await
-        .map_err(|e| {
-            println!("Failed to parse Gemini API response as JSON: {}", e);
-            format!("Failed to parse API response: {}. This might be a network/encoding issue or the API returned non-JSON data.", e)
-        })?;
+    let text = match stream {
+        Some(stream) => {
+            read_gemini_stream(response, limits.max_response_bytes(), stream, output).await?
+        }
+        None => {
+            // Parse the response
+            debug!("Parsing JSON response...");
+            let body = http::read_limited(response, limits.max_response_bytes()).await?;
+            let response_body: GeminiResponse = serde_json::from_slice(&body)
+                .map_err(|e| {
+                    warn!("Failed to parse Gemini API response as JSON: {}", e);
+                    format!("Failed to parse API response: {}. This might be a network/encoding issue or the API returned non-JSON data.", e)
+                })?;
+            
+            debug!("Extracting caption from response...");
+            if let Some(error) = gemini_finish_error(&response_body) {
+                warn!("{}", error);
+                return Err(error);
+            }
+            let Some(candidate) = response_body.candidates.first() else {
+                warn!("No candidates found in response");
+                return Err("No caption generated".to_string());
+            };
+            let Some(part) = candidate.content.parts.first() else {
+                warn!("No parts found in response");
+                return Err("No caption generated".to_string());
+            };
+            part.text.clone()
+        }
+    };
     
     // Extract the caption (JSON parsing)
-    println!("Extracting caption from response...");
-    if let Some(candidate) = response_body.candidates.first() {
-        if let Some(part) = candidate.content.parts.first() {
-            // Try to parse the JSON response to extract just the caption
-            println!("Received text response: {}", part.text);
-            match serde_json::from_str::<serde_json::Value>(&part.text) {
-                Ok(json) => {
-                    println!("Successfully parsed JSON response");
-                    if let Some(caption) = json.get("caption").and_then(|c| c.as_str()) {
-                        println!("Extracted caption: {}", caption);
-                        return Ok(caption.to_string());
-                    } else {
-                        println!("No 'caption' field found in JSON, returning full text");
-                        return Ok(part.text.clone()); // Return full text if can't extract caption
-                    }
-                },
-                Err(e) => {
-                    println!("Response is not valid JSON ({}), returning as plain text", e);
-                    return Ok(part.text.clone()); // Not valid JSON, return as is
-                }
+    trace!("Received text response: {}", text);
+    match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(json) => {
+            debug!("Successfully parsed JSON response");
+            if let Some(caption) = json.get(output.field()).and_then(|c| c.as_str()) {
+                debug!("Extracted caption: {}", caption);
+                Ok(caption.to_string())
+            } else {
+                debug!("No '{}' field found in JSON, returning full text", output.field());
+                Ok(text) // Return full text if can't extract caption
             }
-        } else {
-            println!("No parts found in response");
+        },
+        Err(e) => {
+            debug!("Response is not valid JSON ({}), returning as plain text", e);
+            Ok(text) // Not valid JSON, return as is
         }
-    } else {
-        println!("No candidates found in response");
     }
-    
-    Err("No caption generated".to_string())
+}
+
+/// Read a streamed Gemini reply, forwarding the caption received so far to `stream`,
+/// and return the whole text
+async fn read_gemini_stream(
+    response: reqwest::Response,
+    max_bytes: usize,
+    stream: &CaptionStream,
+    output: GeminiOutput,
+) -> Result<String, String> {
+    let mut text = String::new();
+    let mut finish_error = None;
+    stream::read_events(response, max_bytes, |data| {
+        // Chunks without text, like the final one with the finish reason, are skipped
+        let Ok(chunk) = serde_json::from_str::<GeminiResponse>(data) else {
+            return;
+        };
+        if let Some(error) = gemini_finish_error(&chunk) {
+            finish_error = Some(error);
+        }
+        let Some(part) = chunk
+            .candidates
+            .first()
+            .and_then(|candidate| candidate.content.parts.first())
+        else {
+            return;
+        };
+        text.push_str(&part.text);
+
+        // The caption is the value of a field of the structured output
+        if let Some(caption) = stream::partial_json_string(&text, output.field()) {
+            stream.emit(&caption);
+        }
+    })
+    .await?;
+
+    // A reply cut off by a filter is reported even if part of it arrived
+    if let Some(error) = finish_error {
+        warn!("{}", error);
+        return Err(error);
+    }
+    if text.trim().is_empty() {
+        return Err("No caption generated".to_string());
+    }
+    Ok(text)
 }
 
 /// Generate captions for multiple media files using Gemini
+///
+/// Runs as a resumable captioning job, like `generate_captions`.
 #[tauri::command]
 pub async fn generate_gemini_captions(
+    app: AppHandle,
     api_key: String,
     prompt: String,
     media_paths: Vec<String>,
     system_instruction: Option<String>,
     temperature: Option<f32>,
+    include_audio: Option<bool>,
 ) -> Result<Vec<(String, String)>, String> {
-    let mut results = Vec::new();
-
-    for path in media_paths {
-        match generate_gemini_caption(
-            api_key.clone(),
-            prompt.clone(),
-            path.clone(),
-            system_instruction.clone(),
-            temperature,
-        )
-        .await
-        {
-            Ok(caption) => results.push((path, caption)),
-            Err(e) => results.push((path, format!("Error: {}", e))),
+    let description = format!("Caption {} files with Gemini", media_paths.len());
+
+    jobs::run_async(&app, JobKind::Captioning, description, |job| async move {
+        let params = serde_json::json!({
+            "provider": "gemini",
+            "prompt": prompt,
+            "system_instruction": system_instruction,
+            "temperature": temperature,
+            "include_audio": include_audio,
+        });
+        job.make_resumable(params, &media_paths);
+
+        let results = caption_all(&job, media_paths, move |path, _| {
+            generate_gemini_caption(
+                api_key.clone(),
+                prompt.clone(),
+                path,
+                system_instruction.clone(),
+                temperature,
+                include_audio,
+            )
+        })
+        .await;
+        Ok(results)
+    })
+    .await
+}
+
+/// Generate a caption for a media file with a user-provided script (see
+/// [`script::run`] for the protocol)
+#[tauri::command]
+pub async fn generate_script_caption(
+    command: String,
+    args: Vec<String>,
+    prompt: String,
+    media_path: String,
+    timeout_secs: Option<u64>,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        script::run(&command, &args, &prompt, &media_path, timeout_secs, None)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Generate captions for multiple media files with a user-provided script
+///
+/// Runs as a resumable captioning job, like `generate_captions`; cancelling it kills
+/// the script working on the current file.
+#[tauri::command]
+pub async fn generate_script_captions(
+    app: AppHandle,
+    command: String,
+    args: Vec<String>,
+    prompt: String,
+    media_paths: Vec<String>,
+    timeout_secs: Option<u64>,
+) -> Result<Vec<(String, String)>, String> {
+    let description = format!("Caption {} files with a script", media_paths.len());
+
+    jobs::run(&app, JobKind::Captioning, description, move |job| {
+        let params = serde_json::json!({
+            "provider": "script",
+            "command": command,
+            "args": args,
+            "prompt": prompt,
+            "timeout_secs": timeout_secs,
+        });
+        job.make_resumable(params, &media_paths);
+
+        let total = media_paths.len() as u64;
+        let mut results = Vec::new();
+
+        for (index, path) in media_paths.into_iter().enumerate() {
+            if job.is_cancelled() {
+                break;
+            }
+            job.progress(index as u64, total, Some(path.clone()));
+
+            let caption = match script::run(
+                &command,
+                &args,
+                &prompt,
+                &path,
+                timeout_secs,
+                Some(job.cancel_flag()),
+            ) {
+                Ok(caption) => caption,
+                Err(e) if e == jobs::CANCELLED => break,
+                Err(e) => format!("Error: {}", e),
+            };
+            job.complete_item(&path, &caption);
+            results.push((path, caption));
+        }
+
+        job.progress(results.len() as u64, total, None);
+        Ok(results)
+    })
+    .await
+}
+
+/// OpenAI structured output format of alt text: `{ "alt_text": "..." }`
+fn alt_text_response_format() -> serde_json::Value {
+    serde_json::json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": "alt_text",
+            "strict": true,
+            "schema": {
+                "type": "object",
+                "properties": { "alt_text": { "type": "string" } },
+                "required": ["alt_text"],
+                "additionalProperties": false,
+            },
+        },
+    })
+}
+
+/// Generate web alt text for an image or video frame using OpenAI's API
+///
+/// Unlike captions for training, alt text is concise and factual and skips openings
+/// like "image of"; it is cut to `max_length` characters (default 125). The tag
+/// dictionary and trigger rules of the project don't apply to it.
+#[tauri::command]
+pub async fn generate_alt_text(
+    api_url: String,
+    api_key: String,
+    image_path: String,
+    model: String,
+    max_length: Option<usize>,
+    video_frame_url: Option<String>,
+) -> Result<String, String> {
+    let max_length = max_length.unwrap_or(alt_text::DEFAULT_MAX_LENGTH);
+    let image_data_url = match video_frame_url {
+        Some(url) => url,
+        None => create_data_url_from_image(&image_path)
+            .await
+            .map_err(|e| format!("Failed to create data URL: {}", e))?,
+    };
+
+    let request = OpenAIRequest {
+        model,
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: vec![
+                MessageContent::Text {
+                    text: alt_text::prompt(max_length),
+                },
+                MessageContent::Image {
+                    image_url: ImageUrl {
+                        url: image_data_url,
+                        detail: None,
+                    },
+                },
+            ],
+        }],
+        max_tokens: 300,
+        temperature: 0.2,
+        response_format: Some(alt_text_response_format()),
+        stream: None,
+    };
+
+    let response = send_openai_request(&api_url, &api_key, &request).await?;
+    Ok(alt_text::clean(
+        &alt_text::parse_response(&response),
+        max_length,
+    ))
+}
+
+/// Generate web alt text for a video or image using Google's Gemini API, like
+/// `generate_alt_text`
+#[tauri::command]
+pub async fn generate_gemini_alt_text(
+    api_key: String,
+    media_path: String,
+    max_length: Option<usize>,
+) -> Result<String, String> {
+    let max_length = max_length.unwrap_or(alt_text::DEFAULT_MAX_LENGTH);
+    let text = generate_gemini_with_retry(
+        api_key,
+        alt_text::prompt(max_length),
+        media_path,
+        None,
+        Some(0.2),
+        None,
+        GeminiOutput::AltText,
+        None,
+    )
+    .await?;
+    Ok(alt_text::clean(&text, max_length))
+}
+
+/// Generate web alt text for multiple media files using OpenAI
+///
+/// Runs as a resumable captioning job, like `generate_captions`.
+#[tauri::command]
+pub async fn generate_alt_texts(
+    app: AppHandle,
+    api_url: String,
+    api_key: String,
+    image_paths: Vec<String>,
+    model: String,
+    max_length: Option<usize>,
+) -> Result<Vec<(String, String)>, String> {
+    let description = format!("Write alt text for {} files", image_paths.len());
+
+    jobs::run_async(&app, JobKind::Captioning, description, |job| async move {
+        let params = serde_json::json!({
+            "provider": "openai",
+            "mode": "alt_text",
+            "api_url": api_url,
+            "model": model,
+            "max_length": max_length,
+        });
+        job.make_resumable(params, &image_paths);
+
+        let total = image_paths.len() as u64;
+        let mut results = Vec::new();
+
+        for (index, path) in image_paths.into_iter().enumerate() {
+            if job.is_cancelled() {
+                break;
+            }
+            job.progress(index as u64, total, Some(path.clone()));
+
+            let video_frame_url = first_video_frame(&path).await;
+            let text = match generate_alt_text(
+                api_url.clone(),
+                api_key.clone(),
+                path.clone(),
+                model.clone(),
+                max_length,
+                video_frame_url,
+            )
+            .await
+            {
+                Ok(text) => text,
+                Err(e) => format!("Error: {}", e),
+            };
+            job.complete_item(&path, &text);
+            results.push((path, text));
+        }
+
+        job.progress(results.len() as u64, total, None);
+        Ok(results)
+    })
+    .await
+}
+
+/// Generate web alt text for multiple media files using Gemini
+///
+/// Runs as a resumable captioning job, like `generate_captions`.
+#[tauri::command]
+pub async fn generate_gemini_alt_texts(
+    app: AppHandle,
+    api_key: String,
+    media_paths: Vec<String>,
+    max_length: Option<usize>,
+) -> Result<Vec<(String, String)>, String> {
+    let description = format!("Write alt text for {} files with Gemini", media_paths.len());
+
+    jobs::run_async(&app, JobKind::Captioning, description, |job| async move {
+        let params = serde_json::json!({
+            "provider": "gemini",
+            "mode": "alt_text",
+            "max_length": max_length,
+        });
+        job.make_resumable(params, &media_paths);
+
+        let total = media_paths.len() as u64;
+        let mut results = Vec::new();
+
+        for (index, path) in media_paths.into_iter().enumerate() {
+            if job.is_cancelled() {
+                break;
+            }
+            job.progress(index as u64, total, Some(path.clone()));
+
+            let text =
+                match generate_gemini_alt_text(api_key.clone(), path.clone(), max_length).await {
+                    Ok(text) => text,
+                    Err(e) => format!("Error: {}", e),
+                };
+            job.complete_item(&path, &text);
+            results.push((path, text));
+        }
+
+        job.progress(results.len() as u64, total, None);
+        Ok(results)
+    })
+    .await
+}
+
+/// Get the time, upload and reply limits of the requests sent to each provider
+#[tauri::command]
+pub async fn get_provider_limits() -> Result<LimitSettings, String> {
+    Ok(limits::current())
+}
+
+/// Change the limits of the requests sent to each provider, e.g. a longer timeout for
+/// a slow self-hosted server
+#[tauri::command]
+pub async fn update_provider_limits(app: AppHandle, settings: LimitSettings) -> Result<(), String> {
+    limits::save(&app, settings)
+}
+
+/// List the saved provider profiles
+#[tauri::command]
+pub async fn list_provider_profiles(app: AppHandle) -> Result<Vec<ProviderProfile>, String> {
+    Ok(profiles::list(&app))
+}
+
+/// Save a provider profile, adding it if it has no id yet; returns the saved profile
+#[tauri::command]
+pub async fn save_provider_profile(
+    app: AppHandle,
+    profile: ProviderProfile,
+) -> Result<ProviderProfile, String> {
+    profiles::save(&app, profile)
+}
+
+/// Delete a saved provider profile
+#[tauri::command]
+pub async fn delete_provider_profile(app: AppHandle, id: String) -> Result<(), String> {
+    profiles::delete(&app, &id)
+}
+
+/// Caption one file with a provider profile, waiting for its rate limit first
+///
+/// `index` is the 1-based position of the file in its batch, for the `{index}`
+/// placeholder of the prompt.
+async fn caption_with_profile_item(
+    profile: &ProviderProfile,
+    api_key: &str,
+    prompt: &str,
+    media_path: &str,
+    index: usize,
+) -> Result<String, String> {
+    let prompt = batch::fill_template(prompt, Path::new(media_path), index);
+    profiles::throttle(profile).await;
+
+    match profile.provider {
+        ProfileProvider::Openai => {
+            let video_frame_url = first_video_frame(media_path).await;
+            let video = video_with_audio(
+                &profile.api_url,
+                api_key,
+                media_path,
+                Some(profile.include_audio),
+            )
+            .await?;
+            let mut request = caption_request(
+                prompt,
+                media_path,
+                profile.model.clone(),
+                profile.image_detail.clone(),
+                profile.use_detail_parameter,
+                video_frame_url,
+                video,
+            )
+            .await?;
+            if let Some(temperature) = profile.temperature {
+                request.temperature = temperature;
+            }
+
+            let caption = send_openai_request(&profile.api_url, api_key, &request).await?;
+            Ok(postprocess::apply(Path::new(media_path), caption))
+        }
+        ProfileProvider::Gemini => {
+            let model = Some(profile.model.trim())
+                .filter(|model| !model.is_empty())
+                .map(str::to_string);
+            let caption = generate_gemini_with_retry(
+                api_key.to_string(),
+                gemini_prompt(prompt, media_path, Some(profile.include_audio)),
+                media_path.to_string(),
+                profile.system_instruction.clone(),
+                profile.temperature,
+                model,
+                GeminiOutput::Caption,
+                None,
+            )
+            .await?;
+            Ok(postprocess::apply(Path::new(media_path), caption))
+        }
+        ProfileProvider::Script => {
+            let command = profile.script_command.clone();
+            let args = profile.script_args.clone();
+            let timeout_secs = profile.script_timeout_secs;
+            let media_path = media_path.to_string();
+            tauri::async_runtime::spawn_blocking(move || {
+                script::run(&command, &args, &prompt, &media_path, timeout_secs, None)
+            })
+            .await
+            .map_err(|e| e.to_string())?
         }
     }
+}
+
+/// Generate a caption for a media file with a saved provider profile
+///
+/// The profile supplies the endpoint, key, model and other settings; `prompt`
+/// replaces its default prompt when given.
+#[tauri::command]
+pub async fn caption_with_profile(
+    app: AppHandle,
+    profile_id: String,
+    media_path: String,
+    prompt: Option<String>,
+) -> Result<String, String> {
+    let profile = profiles::get(&app, &profile_id)?;
+    let api_key = profile.resolve_api_key()?;
+    let prompt = prompt.unwrap_or_else(|| profile.prompt.clone());
+    caption_with_profile_item(&profile, &api_key, &prompt, &media_path, 1).await
+}
 
-    Ok(results)
+/// Generate captions for multiple media files with a saved provider profile
+///
+/// Runs as a resumable captioning job, like `generate_captions`, spacing requests out
+/// to the rate limit of the profile as well as that of the project.
+#[tauri::command]
+pub async fn caption_files_with_profile(
+    app: AppHandle,
+    profile_id: String,
+    media_paths: Vec<String>,
+    prompt: Option<String>,
+) -> Result<Vec<(String, String)>, String> {
+    let profile = profiles::get(&app, &profile_id)?;
+    let api_key = profile.resolve_api_key()?;
+    let prompt = prompt.unwrap_or_else(|| profile.prompt.clone());
+    let description = format!("Caption {} files with {}", media_paths.len(), profile.name);
+
+    jobs::run_async(&app, JobKind::Captioning, description, |job| async move {
+        // The profile keeps the API key, so only its id is written to disk
+        let params = serde_json::json!({
+            "provider": "profile",
+            "profile_id": profile.id,
+            "prompt": prompt,
+        });
+        job.make_resumable(params, &media_paths);
+
+        let results = caption_all(&job, media_paths, move |path, index| {
+            let profile = profile.clone();
+            let api_key = api_key.clone();
+            let prompt = prompt.clone();
+            async move {
+                caption_with_profile_item(&profile, &api_key, &prompt, &path, index).await
+            }
+        })
+        .await;
+        Ok(results)
+    })
+    .await
+}
+
+/// Captions of a sample of a project's uncaptioned files, to approve before
+/// captioning the rest
+#[derive(Debug, Serialize)]
+pub struct CaptionSample {
+    /// Sampled files and their captions, or `Error: ...` for failed requests
+    pub results: Vec<(String, String)>,
+    /// The other uncaptioned files, to caption once the sample is approved
+    pub remaining: Vec<String>,
+    /// Seed the sample was drawn with
+    pub seed: u64,
+}
+
+/// Caption a random sample of a project's uncaptioned files with a provider profile,
+/// without saving the captions
+///
+/// A guard rail against spending a whole batch on a bad prompt: the frontend shows
+/// the sample for approval, then saves it and captions `remaining` with
+/// `caption_files_with_profile`. Runs as a captioning job.
+#[tauri::command]
+pub async fn sample_uncaptioned_files(
+    app: AppHandle,
+    directory: String,
+    profile_id: String,
+    sample_size: usize,
+    prompt: Option<String>,
+    seed: Option<u64>,
+) -> Result<CaptionSample, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let profile = profiles::get(&app, &profile_id)?;
+    let api_key = profile.resolve_api_key()?;
+    let prompt = prompt.unwrap_or_else(|| profile.prompt.clone());
+    if sample_size == 0 {
+        return Err("The sample needs at least one file".to_string());
+    }
+
+    // Drawn seeds stay within what a JavaScript number holds exactly
+    let seed = seed.unwrap_or_else(|| u64::from(fastrand::u32(..)));
+    let mut uncaptioned: Vec<String> = scan_media_files(&dir_path)?
+        .into_iter()
+        .filter(|file| !file.has_caption)
+        .map(|file| file.path)
+        .collect();
+    if uncaptioned.is_empty() {
+        return Err("Every file already has a caption".to_string());
+    }
+    fastrand::Rng::with_seed(seed).shuffle(&mut uncaptioned);
+    let mut remaining = uncaptioned.split_off(sample_size.min(uncaptioned.len()));
+    let mut sample = uncaptioned;
+    sample.sort();
+    remaining.sort();
+
+    let description = format!(
+        "Caption a sample of {} files with {}",
+        sample.len(),
+        profile.name
+    );
+    jobs::run_async(&app, JobKind::Captioning, description, |job| async move {
+        let results = caption_all(&job, sample, move |path, index| {
+            let profile = profile.clone();
+            let api_key = api_key.clone();
+            let prompt = prompt.clone();
+            async move {
+                caption_with_profile_item(&profile, &api_key, &prompt, &path, index).await
+            }
+        })
+        .await;
+        Ok(CaptionSample {
+            results,
+            remaining,
+            seed,
+        })
+    })
+    .await
+}
+
+/// Arguments of a caption job, as recorded in its journal
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RecordedCaptionJob {
+    provider: String,
+    /// `alt_text` for alt text jobs
+    mode: Option<String>,
+    api_url: String,
+    prompt: String,
+    model: String,
+    image_detail: String,
+    use_detail_parameter: bool,
+    system_instruction: Option<String>,
+    temperature: Option<f32>,
+    include_audio: Option<bool>,
+    max_length: Option<usize>,
+    command: String,
+    args: Vec<String>,
+    timeout_secs: Option<u64>,
+    profile_id: String,
+    /// Never recorded, so given again to retry
+    #[serde(skip)]
+    api_key: String,
+    #[serde(skip)]
+    profile: Option<ProviderProfile>,
+}
+
+impl RecordedCaptionJob {
+    /// Caption one file the way the recorded job did
+    async fn run(&self, path: String, index: usize) -> Result<String, String> {
+        let alt_text = self.mode.as_deref() == Some("alt_text");
+        match self.provider.as_str() {
+            "openai" => {
+                let video_frame_url = first_video_frame(&path).await;
+                if alt_text {
+                    generate_alt_text(
+                        self.api_url.clone(),
+                        self.api_key.clone(),
+                        path,
+                        self.model.clone(),
+                        self.max_length,
+                        video_frame_url,
+                    )
+                    .await
+                } else {
+                    generate_caption(
+                        self.api_url.clone(),
+                        self.api_key.clone(),
+                        self.prompt.clone(),
+                        path,
+                        self.model.clone(),
+                        self.image_detail.clone(),
+                        self.use_detail_parameter,
+                        video_frame_url,
+                        self.include_audio,
+                    )
+                    .await
+                }
+            }
+            "gemini" if alt_text => {
+                generate_gemini_alt_text(self.api_key.clone(), path, self.max_length).await
+            }
+            "gemini" => {
+                generate_gemini_caption(
+                    self.api_key.clone(),
+                    self.prompt.clone(),
+                    path,
+                    self.system_instruction.clone(),
+                    self.temperature,
+                    self.include_audio,
+                )
+                .await
+            }
+            "script" => {
+                let command = self.command.clone();
+                let args = self.args.clone();
+                let prompt = self.prompt.clone();
+                let timeout_secs = self.timeout_secs;
+                tauri::async_runtime::spawn_blocking(move || {
+                    script::run(&command, &args, &prompt, &path, timeout_secs, None)
+                })
+                .await
+                .map_err(|e| e.to_string())?
+            }
+            "profile" => match &self.profile {
+                Some(profile) => {
+                    caption_with_profile_item(profile, &self.api_key, &self.prompt, &path, index)
+                        .await
+                }
+                None => Err(format!("Provider profile {} is not loaded", self.profile_id)),
+            },
+            provider => Err(format!("Jobs of provider {} can't be retried", provider)),
+        }
+    }
+}
+
+/// Caption the failed files of a finished batch again, merging the new results into
+/// the job's record
+///
+/// The job's arguments come from its journal, which is kept for finished jobs with
+/// failed files (see `list_failed_jobs`). OpenAI and Gemini jobs need `api_key` again,
+/// since keys are never recorded. Runs as a captioning job paced by the project's
+/// batch settings. Returns every result of the original job, the retried files with
+/// their new result.
+#[tauri::command]
+pub async fn retry_failed(
+    app: AppHandle,
+    job_id: String,
+    api_key: Option<String>,
+) -> Result<Vec<(String, String)>, String> {
+    let record = journal::read_finished(&app, &job_id)?;
+    let failed = record.failed();
+    if failed.is_empty() {
+        return Err(format!("Job {} has no failed files", job_id));
+    }
+
+    let mut recorded: RecordedCaptionJob = serde_json::from_value(record.params.clone())
+        .map_err(|e| format!("Job {} can't be retried: {}", job_id, e))?;
+    match recorded.provider.as_str() {
+        "openai" | "gemini" => {
+            recorded.api_key = api_key.filter(|key| !key.is_empty()).ok_or_else(|| {
+                format!("Retrying job {} needs the API key, which isn't recorded", job_id)
+            })?;
+        }
+        "profile" => {
+            let profile = profiles::get(&app, &recorded.profile_id)?;
+            recorded.api_key = profile.resolve_api_key()?;
+            recorded.profile = Some(profile);
+        }
+        _ => {}
+    }
+
+    // Files keep their position in the original batch, for the {index} placeholder
+    let positions: HashMap<String, usize> = record
+        .completed
+        .iter()
+        .enumerate()
+        .map(|(position, (item, _))| (item.clone(), position + 1))
+        .collect();
+    let recorded = Arc::new(recorded);
+    let description = format!("Retry {} failed files of {}", failed.len(), record.description);
+
+    let handle = app.clone();
+    jobs::run_async(&app, JobKind::Captioning, description, |job| async move {
+        let results = caption_all(&job, failed, move |path, index| {
+            let recorded = recorded.clone();
+            let index = positions.get(&path).copied().unwrap_or(index);
+            async move { recorded.run(path, index).await }
+        })
+        .await;
+
+        for (path, result) in &results {
+            journal::record_retry(&handle, &job_id, path, result)?;
+        }
+        Ok(journal::read_finished(&handle, &job_id)?.completed)
+    })
+    .await
 }
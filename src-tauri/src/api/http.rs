@@ -0,0 +1,59 @@
+use once_cell::sync::Lazy;
+use reqwest::{Client, Response};
+use std::time::Duration;
+
+/// Time limit of a Gemini file upload, which sends the whole video
+pub const GEMINI_UPLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Time limit of a Gemini file state check
+pub const GEMINI_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Shortest time limit of a streamed request, which lasts as long as the whole reply takes
+pub const STREAM_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Time limit of establishing a connection, whatever the provider
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long an unused connection is kept open for the next request
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// HTTP client shared by every provider request, so batches reuse connections
+/// rather than opening a new one per caption
+///
+/// Time limits differ per provider, so they are set on each request.
+static CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+});
+
+/// The shared HTTP client
+pub fn client() -> &'static Client {
+    &CLIENT
+}
+
+/// Read the body of a response, failing once it grows past `max_bytes`
+pub async fn read_limited(mut response: Response, max_bytes: usize) -> Result<Vec<u8>, String> {
+    let too_large = || format!("The response is larger than {} KB", max_bytes / 1024);
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_bytes as u64)
+    {
+        return Err(too_large());
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read the response: {}", e))?
+    {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
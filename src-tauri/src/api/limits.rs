@@ -0,0 +1,151 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use super::http::STREAM_TIMEOUT;
+use crate::file_system::atomic;
+
+/// File in the app data directory holding the provider limits
+const SETTINGS_FILE: &str = "spacecat-provider-limits.json";
+
+/// Time, size and reply limits of the requests sent to one provider
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ProviderLimits {
+    /// Time limit of a caption request in seconds; streamed requests get at least
+    /// two minutes, since they last as long as the whole reply takes
+    pub timeout_secs: u64,
+    /// Largest media sent in one request, in megabytes
+    pub max_upload_mb: u64,
+    /// Largest reply accepted, in kilobytes
+    pub max_response_kb: u64,
+}
+
+impl ProviderLimits {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    pub fn stream_timeout(&self) -> Duration {
+        self.timeout().max(STREAM_TIMEOUT)
+    }
+
+    pub fn max_response_bytes(&self) -> usize {
+        (self.max_response_kb as usize).saturating_mul(1024)
+    }
+
+    /// Reject media larger than the upload limit
+    pub fn check_upload(&self, size: u64) -> Result<(), String> {
+        let max = self.max_upload_mb.saturating_mul(1024 * 1024);
+        if size > max {
+            return Err(format!(
+                "The file is {:.1} MB, more than the {} MB upload limit",
+                size as f64 / (1024.0 * 1024.0),
+                self.max_upload_mb
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate(&self, provider: &str) -> Result<(), String> {
+        if self.timeout_secs == 0 || self.max_upload_mb == 0 || self.max_response_kb == 0 {
+            return Err(format!("The {} limits must be greater than zero", provider));
+        }
+        Ok(())
+    }
+}
+
+fn default_openai() -> ProviderLimits {
+    ProviderLimits {
+        timeout_secs: 30,
+        // OpenAI accepts images up to 20 MB
+        max_upload_mb: 20,
+        max_response_kb: 1024,
+    }
+}
+
+fn default_gemini() -> ProviderLimits {
+    ProviderLimits {
+        // Longer for video processing
+        timeout_secs: 120,
+        // The Files API accepts files up to 2 GB
+        max_upload_mb: 2048,
+        max_response_kb: 1024,
+    }
+}
+
+/// Limits of the requests sent to each provider
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LimitSettings {
+    /// Limits of OpenAI-compatible APIs, including self-hosted servers
+    #[serde(default = "default_openai")]
+    pub openai: ProviderLimits,
+    #[serde(default = "default_gemini")]
+    pub gemini: ProviderLimits,
+}
+
+impl Default for LimitSettings {
+    fn default() -> Self {
+        Self {
+            openai: default_openai(),
+            gemini: default_gemini(),
+        }
+    }
+}
+
+static SETTINGS: Lazy<RwLock<LimitSettings>> = Lazy::new(|| RwLock::new(LimitSettings::default()));
+
+/// The provider limits in use
+pub fn current() -> LimitSettings {
+    SETTINGS
+        .read()
+        .map(|settings| *settings)
+        .unwrap_or_default()
+}
+
+fn apply(settings: LimitSettings) {
+    if let Ok(mut current) = SETTINGS.write() {
+        *current = settings;
+    }
+}
+
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join(SETTINGS_FILE))
+}
+
+/// Apply the saved provider limits, if any, at startup
+pub fn load(app: &AppHandle) {
+    let path = match settings_path(app) {
+        Ok(path) if path.is_file() => path,
+        _ => return,
+    };
+
+    match fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+    {
+        Ok(settings) => apply(settings),
+        Err(e) => tracing::warn!("Invalid provider limits, using defaults: {}", e),
+    }
+}
+
+/// Apply provider limits and save them for the next start
+pub fn save(app: &AppHandle, settings: LimitSettings) -> Result<(), String> {
+    settings.openai.validate("OpenAI")?;
+    settings.gemini.validate("Gemini")?;
+
+    let path = settings_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    atomic::write_atomic(&path, &content, false).map_err(|e| e.to_string())?;
+
+    apply(settings);
+    Ok(())
+}
@@ -1 +1,8 @@
+pub mod audio;
+pub mod batch;
 pub mod commands;
+pub mod http;
+pub mod limits;
+pub mod profiles;
+pub mod script;
+pub mod stream;
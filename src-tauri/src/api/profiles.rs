@@ -0,0 +1,192 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use super::batch;
+use crate::file_system::atomic;
+
+/// File in the app data directory holding the provider profiles
+const SETTINGS_FILE: &str = "spacecat-provider-profiles.json";
+
+/// Service a profile captions with
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileProvider {
+    /// An OpenAI-compatible chat completions API
+    Openai,
+    Gemini,
+    /// A user-provided caption script
+    Script,
+}
+
+/// Where the API key of a profile comes from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyRef {
+    /// The key itself
+    Value(String),
+    /// Name of an environment variable holding the key
+    Env(String),
+}
+
+fn default_image_detail() -> String {
+    "auto".to_string()
+}
+
+/// A named provider configuration that caption commands can refer to by id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderProfile {
+    /// Assigned when the profile is first saved
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub provider: ProfileProvider,
+    /// Endpoint of OpenAI-compatible APIs
+    #[serde(default)]
+    pub api_url: String,
+    #[serde(default)]
+    pub api_key: Option<ApiKeyRef>,
+    /// Model name; Gemini uses its default model when empty
+    #[serde(default)]
+    pub model: String,
+    /// Default prompt, a template with the placeholders of propagated captions like
+    /// `{name}` and `{stem}` filled in per file
+    pub prompt: String,
+    #[serde(default)]
+    pub system_instruction: Option<String>,
+    #[serde(default = "default_image_detail")]
+    pub image_detail: String,
+    #[serde(default)]
+    pub use_detail_parameter: bool,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Caption videos from their sound as well as their picture: Gemini is told to
+    /// listen, other APIs get sampled frames with a Whisper transcript
+    #[serde(default)]
+    pub include_audio: bool,
+    /// Requests are spaced out to stay under this rate, across all commands using the
+    /// profile
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub script_command: String,
+    #[serde(default)]
+    pub script_args: Vec<String>,
+    #[serde(default)]
+    pub script_timeout_secs: Option<u64>,
+}
+
+impl ProviderProfile {
+    /// The API key of the profile, read from the environment if it refers to a variable
+    ///
+    /// Scripts need no key, so they get an empty one.
+    pub fn resolve_api_key(&self) -> Result<String, String> {
+        match &self.api_key {
+            Some(ApiKeyRef::Value(key)) => Ok(key.clone()),
+            Some(ApiKeyRef::Env(variable)) => std::env::var(variable)
+                .map_err(|_| format!("Environment variable {} is not set", variable)),
+            None if self.provider == ProfileProvider::Script => Ok(String::new()),
+            None => Err(format!("Profile {} has no API key", self.name)),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Profiles need a name".to_string());
+        }
+        match self.provider {
+            ProfileProvider::Openai if self.api_url.trim().is_empty() => {
+                Err(format!("Profile {} has no API URL", self.name))
+            }
+            ProfileProvider::Openai if self.model.trim().is_empty() => {
+                Err(format!("Profile {} has no model", self.name))
+            }
+            ProfileProvider::Script if self.script_command.trim().is_empty() => {
+                Err(format!("Profile {} has no script command", self.name))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join(SETTINGS_FILE))
+}
+
+/// The saved provider profiles, in the order they were created
+pub fn list(app: &AppHandle) -> Vec<ProviderProfile> {
+    let path = match settings_path(app) {
+        Ok(path) if path.is_file() => path,
+        _ => return Vec::new(),
+    };
+
+    fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            warn!("Invalid provider profiles, ignoring them: {}", e);
+            Vec::new()
+        })
+}
+
+/// Look up a saved profile by id
+pub fn get(app: &AppHandle, id: &str) -> Result<ProviderProfile, String> {
+    list(app)
+        .into_iter()
+        .find(|profile| profile.id == id)
+        .ok_or_else(|| format!("No provider profile with id {}", id))
+}
+
+fn write(app: &AppHandle, profiles: &[ProviderProfile]) -> Result<(), String> {
+    let path = settings_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(profiles).map_err(|e| e.to_string())?;
+    atomic::write_atomic(&path, &content, false).map_err(|e| e.to_string())
+}
+
+/// Save a profile, replacing the one with the same id or adding it with a new id
+pub fn save(app: &AppHandle, mut profile: ProviderProfile) -> Result<ProviderProfile, String> {
+    profile.validate()?;
+    profile.name = profile.name.trim().to_string();
+
+    let mut profiles = list(app);
+    match profiles
+        .iter_mut()
+        .find(|p| !profile.id.is_empty() && p.id == profile.id)
+    {
+        Some(existing) => *existing = profile.clone(),
+        None => {
+            profile.id = Local::now().format("%Y%m%d%H%M%S%3f").to_string();
+            profiles.push(profile.clone());
+        }
+    }
+
+    write(app, &profiles)?;
+    Ok(profile)
+}
+
+/// Delete a saved profile
+pub fn delete(app: &AppHandle, id: &str) -> Result<(), String> {
+    let mut profiles = list(app);
+    let count = profiles.len();
+    profiles.retain(|profile| profile.id != id);
+    if profiles.len() == count {
+        return Err(format!("No provider profile with id {}", id));
+    }
+    write(app, &profiles)
+}
+
+/// Wait until a profile may send another request under its rate limit
+///
+/// Concurrent batches using the same profile share its rate.
+pub async fn throttle(profile: &ProviderProfile) {
+    let key = format!("profile:{}", profile.id);
+    batch::throttle(&key, profile.requests_per_minute).await;
+}
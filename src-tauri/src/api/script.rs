@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::captions::postprocess;
+use crate::jobs::manager::CANCELLED;
+use crate::media::formats;
+
+/// Time a script gets per file unless the caller sets another limit
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+
+/// Lines of the script's stderr included in errors
+const STDERR_LINES: usize = 10;
+
+/// Request written as JSON to the script's stdin
+#[derive(Serialize)]
+struct ScriptRequest<'a> {
+    media_path: &'a str,
+    /// "image" or "video"
+    media_type: &'a str,
+    prompt: &'a str,
+}
+
+/// Reply read from the last line of the script's stdout
+#[derive(Deserialize)]
+struct ScriptReply {
+    caption: Option<String>,
+    error: Option<String>,
+}
+
+/// Read a pipe to the end on its own thread, so the script never blocks on a full pipe
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        String::from_utf8_lossy(&output).to_string()
+    })
+}
+
+/// The last lines of the script's stderr, to explain a failure
+fn stderr_tail(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.lines().filter(|l| !l.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(STDERR_LINES)..].join("\n")
+}
+
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Caption a media file with a user-provided script
+///
+/// `command` is started with `args` and gets a JSON request on stdin with the
+/// `media_path`, its `media_type` and the `prompt`. It answers with a JSON object on
+/// the last line of stdout, `{"caption": "..."}` or `{"error": "..."}`; anything it
+/// prints before that, like model loading logs, is ignored. Scripts that run longer
+/// than the timeout, or whose job is cancelled, are killed. The caption is
+/// post-processed like those of the API providers.
+pub fn run(
+    command: &str,
+    args: &[String],
+    prompt: &str,
+    media_path: &str,
+    timeout_secs: Option<u64>,
+    cancel: Option<&AtomicBool>,
+) -> Result<String, String> {
+    if command.trim().is_empty() {
+        return Err("No caption script configured".to_string());
+    }
+    let media_type = formats::media_type(Path::new(media_path)).unwrap_or("image");
+    let request = serde_json::to_string(&ScriptRequest {
+        media_path,
+        media_type,
+        prompt,
+    })
+    .map_err(|e| e.to_string())?;
+
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", command, e))?;
+
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    if let Some(mut stdin) = child.stdin.take() {
+        // A script that doesn't read its request may close stdin early
+        if let Err(e) = writeln!(stdin, "{}", request) {
+            warn!("Failed to send the request to {}: {}", command, e);
+        }
+    }
+
+    let timeout = Duration::from_secs(timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {}
+            Err(e) => {
+                kill(&mut child);
+                return Err(format!("Failed to wait for {}: {}", command, e));
+            }
+        }
+        if cancel.is_some_and(|flag| flag.load(Ordering::SeqCst)) {
+            kill(&mut child);
+            return Err(CANCELLED.to_string());
+        }
+        if started.elapsed() >= timeout {
+            kill(&mut child);
+            return Err(format!(
+                "Caption script timed out after {} seconds",
+                timeout.as_secs()
+            ));
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        return Err(format!(
+            "Caption script failed ({}): {}",
+            status,
+            stderr_tail(&stderr)
+        ));
+    }
+
+    let last_line = stdout
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .ok_or_else(|| "Caption script printed nothing".to_string())?;
+    let reply: ScriptReply = serde_json::from_str(last_line.trim())
+        .map_err(|e| format!("Caption script printed invalid JSON ({}): {}", e, last_line))?;
+
+    match (reply.caption, reply.error) {
+        (_, Some(error)) => Err(error),
+        (Some(caption), None) => Ok(postprocess::apply(
+            Path::new(media_path),
+            caption.trim().to_string(),
+        )),
+        (None, None) => Err("Caption script returned no caption".to_string()),
+    }
+}
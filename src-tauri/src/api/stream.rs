@@ -0,0 +1,128 @@
+use reqwest::Response;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+/// Event carrying the text of a caption being streamed so far
+pub const CAPTION_STREAM_EVENT: &str = "caption-stream";
+
+/// Payload of [`CAPTION_STREAM_EVENT`]
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptionChunk {
+    /// Identifies the request, as given by the frontend
+    pub stream_id: String,
+    /// The caption received so far, not just the latest chunk
+    pub text: String,
+}
+
+/// Forwards the partial text of a streamed caption to the frontend
+pub struct CaptionStream {
+    app: AppHandle,
+    stream_id: String,
+}
+
+impl CaptionStream {
+    pub fn new(app: AppHandle, stream_id: String) -> Self {
+        Self { app, stream_id }
+    }
+
+    pub fn emit(&self, text: &str) {
+        let chunk = CaptionChunk {
+            stream_id: self.stream_id.clone(),
+            text: text.to_string(),
+        };
+        let _ = self.app.emit(CAPTION_STREAM_EVENT, chunk);
+    }
+}
+
+/// Read a server-sent events response, passing the data of each event to `on_data`
+/// until the stream ends or sends `[DONE]`
+///
+/// Fails once more than `max_bytes` have been received.
+pub async fn read_events(
+    mut response: Response,
+    max_bytes: usize,
+    mut on_data: impl FnMut(&str),
+) -> Result<(), String> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut data = String::new();
+    let mut received = 0;
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read the streamed response: {}", e))?
+    {
+        received += chunk.len();
+        if received > max_bytes {
+            return Err(format!(
+                "The streamed response is larger than {} KB",
+                max_bytes / 1024
+            ));
+        }
+        buffer.extend_from_slice(&chunk);
+
+        // Only whole lines are decoded, so multi-byte characters split across chunks
+        // stay intact
+        while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+
+            if line.is_empty() {
+                // A blank line ends an event
+                if data == "[DONE]" {
+                    return Ok(());
+                }
+                if !data.is_empty() {
+                    on_data(&data);
+                }
+                data.clear();
+            } else if let Some(value) = line.strip_prefix("data:") {
+                if !data.is_empty() {
+                    data.push('\n');
+                }
+                data.push_str(value.strip_prefix(' ').unwrap_or(value));
+            }
+        }
+    }
+
+    if !data.is_empty() && data != "[DONE]" {
+        on_data(&data);
+    }
+    Ok(())
+}
+
+/// The value of a string field of a JSON object that may still be incomplete, e.g.
+/// `{"caption": "A cat sit` gives `A cat sit`
+///
+/// Returns `None` until the field's value has started.
+pub fn partial_json_string(json: &str, field: &str) -> Option<String> {
+    let key = format!("\"{}\"", field);
+    let after_key = &json[json.find(&key)? + key.len()..];
+    let value = after_key.trim_start().strip_prefix(':')?.trim_start();
+    let mut chars = value.strip_prefix('"')?.chars();
+
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some('r') => text.push('\r'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        Some(c) => text.push(c),
+                        // Incomplete escape at the end of what arrived so far
+                        None => break,
+                    }
+                }
+                Some(other) => text.push(other),
+                None => break,
+            },
+            c => text.push(c),
+        }
+    }
+    Some(text)
+}
@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use super::store::{self, BackupInfo};
+use crate::file_system::commands::validate_project_directory;
+use crate::project::settings;
+
+/// Get the directory that holds the backups of all projects
+pub(crate) fn backups_root(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join("spacecat-backups"))
+}
+
+/// Back up a project's captions before a batch operation, if its settings ask for it
+///
+/// A failed backup aborts the operation rather than running it without a safety net.
+pub(crate) fn backup_before_batch(
+    app: &AppHandle,
+    project: &Path,
+    reason: &str,
+) -> Result<(), String> {
+    let backup = settings::load_settings(project)?.backup;
+    if !backup.before_batch {
+        return Ok(());
+    }
+
+    let root = backups_root(app)?;
+    store::create_backup(&root, project, backup.include_media, reason)
+        .map_err(|e| format!("Backup before {} failed: {}", reason, e))?;
+    store::prune_backups(&root, project, backup.keep)?;
+
+    Ok(())
+}
+
+/// Back up a project now
+///
+/// Captions and settings are always included; media only with `include_media` set
+/// (defaults to the project's backup settings).
+#[tauri::command]
+pub async fn create_project_backup(
+    app: AppHandle,
+    project: String,
+    include_media: Option<bool>,
+) -> Result<BackupInfo, String> {
+    let project_path = validate_project_directory(&app, &project)?;
+    let include_media =
+        include_media.unwrap_or(settings::load_settings(&project_path)?.backup.include_media);
+
+    store::create_backup(&backups_root(&app)?, &project_path, include_media, "manual")
+}
+
+/// List the backups of a project, most recent first
+#[tauri::command]
+pub async fn list_project_backups(
+    app: AppHandle,
+    project: String,
+) -> Result<Vec<BackupInfo>, String> {
+    let project_path = validate_project_directory(&app, &project)?;
+    store::list_backups(&backups_root(&app)?, &project_path)
+}
+
+/// Restore a backup into its project, returning the number of files restored
+///
+/// The restore is recorded in the undo journal, so it can be reverted.
+#[tauri::command]
+pub async fn restore_project_backup(
+    app: AppHandle,
+    project: String,
+    id: String,
+) -> Result<usize, String> {
+    let project_path = validate_project_directory(&app, &project)?;
+    store::restore_backup(&backups_root(&app)?, &project_path, &id)
+}
+
+/// Delete old backups of a project, keeping the `keep` most recent (defaults to the
+/// project's backup settings), and return how many were deleted
+#[tauri::command]
+pub async fn prune_project_backups(
+    app: AppHandle,
+    project: String,
+    keep: Option<usize>,
+) -> Result<usize, String> {
+    let project_path = validate_project_directory(&app, &project)?;
+    let keep = keep.unwrap_or(settings::load_settings(&project_path)?.backup.keep);
+
+    store::prune_backups(&backups_root(&app)?, &project_path, keep)
+}
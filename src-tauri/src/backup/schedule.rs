@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use tauri::AppHandle;
+use tracing::{info, warn};
+
+use super::commands::backups_root;
+use super::store;
+use crate::file_system::commands::working_root;
+use crate::project::settings;
+
+/// How often the scheduler looks for projects that are due for a backup
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Start the background thread that runs scheduled backups
+pub fn start(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(CHECK_INTERVAL);
+        if let Err(e) = run_due_backups(&app) {
+            warn!("Scheduled backup check failed: {}", e);
+        }
+    });
+}
+
+/// Back up every project whose backup interval has passed since its last backup
+fn run_due_backups(app: &AppHandle) -> Result<(), String> {
+    let working_dir = working_root(app)?;
+    if !working_dir.is_dir() {
+        return Ok(());
+    }
+
+    let root = backups_root(app)?;
+    let entries = fs::read_dir(&working_dir).map_err(|e| e.to_string())?;
+    for entry in entries.flatten() {
+        let project = entry.path();
+        if project.is_dir() {
+            if let Err(e) = backup_if_due(&root, &project) {
+                warn!("Scheduled backup of {} failed: {}", project.display(), e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn backup_if_due(root: &Path, project: &Path) -> Result<(), String> {
+    let backup = settings::load_settings(project)?.backup;
+    let interval_hours = match backup.interval_hours {
+        Some(hours) if hours > 0 => hours,
+        _ => return Ok(()),
+    };
+
+    let due = store::seconds_since_last_backup(root, project)
+        .map(|elapsed| elapsed >= i64::from(interval_hours) * 3600)
+        .unwrap_or(true);
+    if !due {
+        return Ok(());
+    }
+
+    let info = store::create_backup(root, project, backup.include_media, "scheduled")?;
+    info!(
+        "Backed up {} ({} files)",
+        project.display(),
+        info.file_count
+    );
+    store::prune_backups(root, project, backup.keep)?;
+
+    Ok(())
+}
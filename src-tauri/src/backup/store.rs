@@ -0,0 +1,227 @@
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+use crate::captions::format::{CaptionStore, MANIFEST_FILE};
+use crate::file_system::atomic;
+use crate::file_system::commands::scan_media_files;
+use crate::project::PROJECT_FILE;
+use crate::undo::journal::{Operation, OperationKind};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A snapshot of a project stored as `<id>.zip` with its details in `<id>.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub id: String,
+    pub project: String,
+    pub created: String,
+    /// Why the backup was made ("manual", "scheduled", or the batch operation)
+    pub reason: String,
+    pub include_media: bool,
+    pub file_count: usize,
+    pub size_bytes: u64,
+}
+
+/// Directory holding the backups of one project
+pub fn project_backup_dir(root: &Path, project: &Path) -> Result<PathBuf, String> {
+    let name = project
+        .file_name()
+        .ok_or_else(|| "Invalid project directory".to_string())?;
+    Ok(root.join(name))
+}
+
+/// Files of a project that go into a backup, relative to the project
+///
+/// Captions (in whichever format the project uses) and the project settings are always
+/// included; media only when requested.
+fn backup_files(project: &Path, include_media: bool) -> Result<BTreeSet<PathBuf>, String> {
+    let store = CaptionStore::open(project);
+    let mut files = BTreeSet::new();
+
+    for name in [PROJECT_FILE, MANIFEST_FILE] {
+        if project.join(name).is_file() {
+            files.insert(PathBuf::from(name));
+        }
+    }
+
+    for file in scan_media_files(project)? {
+        let media = PathBuf::from(&file.path);
+        let caption = store.caption_path(&media);
+        if caption.is_file() {
+            if let Ok(relative) = caption.strip_prefix(project) {
+                files.insert(relative.to_path_buf());
+            }
+        }
+        if include_media {
+            files.insert(PathBuf::from(&file.relative_path));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Snapshot a project into a new timestamped archive under `root`
+pub fn create_backup(
+    root: &Path,
+    project: &Path,
+    include_media: bool,
+    reason: &str,
+) -> Result<BackupInfo, String> {
+    let dir = project_backup_dir(root, project)?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backup directory: {}", e))?;
+
+    let files = backup_files(project, include_media)?;
+    let id = Local::now().format("%Y%m%d%H%M%S%3f").to_string();
+    let zip_path = dir.join(format!("{}.zip", id));
+
+    let file =
+        fs::File::create(&zip_path).map_err(|e| format!("Failed to create backup: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut buffer = Vec::new();
+
+    let result = (|| {
+        for relative in &files {
+            let name = relative.to_string_lossy().replace('\\', "/");
+            zip.start_file(name, options)
+                .map_err(|e| format!("Failed to add file to backup: {}", e))?;
+
+            buffer.clear();
+            fs::File::open(project.join(relative))
+                .and_then(|mut f| f.read_to_end(&mut buffer))
+                .map_err(|e| format!("Failed to read {}: {}", relative.display(), e))?;
+            zip.write_all(&buffer).map_err(|e| e.to_string())?;
+        }
+        zip.finish()
+            .map_err(|e| format!("Failed to finalize backup: {}", e))?;
+        Ok::<(), String>(())
+    })();
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&zip_path);
+        return Err(e);
+    }
+
+    let info = BackupInfo {
+        id: id.clone(),
+        project: project.to_string_lossy().to_string(),
+        created: Local::now().format(TIMESTAMP_FORMAT).to_string(),
+        reason: reason.to_string(),
+        include_media,
+        file_count: files.len(),
+        size_bytes: fs::metadata(&zip_path).map(|m| m.len()).unwrap_or(0),
+    };
+
+    let content = serde_json::to_string_pretty(&info)
+        .map_err(|e| format!("Failed to serialize backup details: {}", e))?;
+    atomic::write_atomic(&dir.join(format!("{}.json", id)), content, false)
+        .map_err(|e| format!("Failed to write backup details: {}", e))?;
+
+    Ok(info)
+}
+
+/// List the backups of a project, most recent first
+pub fn list_backups(root: &Path, project: &Path) -> Result<Vec<BackupInfo>, String> {
+    let dir = project_backup_dir(root, project)?;
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups: Vec<BackupInfo> = fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read backup directory: {}", e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map(|e| e == "json").unwrap_or(false))
+        .filter(|path| path.with_extension("zip").is_file())
+        .filter_map(|path| {
+            let content = fs::read_to_string(&path).ok()?;
+            serde_json::from_str(&content).ok()
+        })
+        .collect();
+
+    // IDs are timestamps, so they sort chronologically
+    backups.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(backups)
+}
+
+/// Time since the most recent backup of a project, in seconds
+pub fn seconds_since_last_backup(root: &Path, project: &Path) -> Option<i64> {
+    let latest = list_backups(root, project).ok()?.into_iter().next()?;
+    let created = NaiveDateTime::parse_from_str(&latest.created, TIMESTAMP_FORMAT).ok()?;
+    Some((Local::now().naive_local() - created).num_seconds())
+}
+
+/// Restore the files of a backup into its project as one undoable operation
+///
+/// Files in the backup replace their current versions; files created since the backup
+/// was made are left alone. Returns the number of files restored.
+pub fn restore_backup(root: &Path, project: &Path, id: &str) -> Result<usize, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid backup id: {}", id));
+    }
+
+    let zip_path = project_backup_dir(root, project)?.join(format!("{}.zip", id));
+    let file = fs::File::open(&zip_path).map_err(|e| format!("Backup not found: {}", e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("Invalid backup: {}", e))?;
+
+    let mut operation = Operation::begin(
+        project,
+        OperationKind::Restore,
+        format!("Restore backup {}", id),
+    )?;
+
+    let mut restored = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read backup: {}", e))?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        let relative = entry
+            .enclosed_name()
+            .map(|p| p.to_path_buf())
+            .ok_or_else(|| format!("Backup entry has an unsafe path: {}", entry.name()))?;
+        let target = project.join(&relative);
+
+        let mut content = Vec::new();
+        entry
+            .read_to_end(&mut content)
+            .map_err(|e| format!("Failed to read {} from backup: {}", relative.display(), e))?;
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        operation.snapshot(&target)?;
+        atomic::write_atomic(&target, content, false)
+            .map_err(|e| format!("Failed to restore {}: {}", relative.display(), e))?;
+        restored += 1;
+    }
+
+    operation.commit()?;
+
+    Ok(restored)
+}
+
+/// Delete all but the `keep` most recent backups of a project, returning how many
+/// were removed
+pub fn prune_backups(root: &Path, project: &Path, keep: usize) -> Result<usize, String> {
+    let dir = project_backup_dir(root, project)?;
+    let backups = list_backups(root, project)?;
+
+    let mut removed = 0;
+    for backup in backups.iter().skip(keep) {
+        fs::remove_file(dir.join(format!("{}.zip", backup.id)))
+            .map_err(|e| format!("Failed to delete backup {}: {}", backup.id, e))?;
+        let _ = fs::remove_file(dir.join(format!("{}.json", backup.id)));
+        removed += 1;
+    }
+
+    Ok(removed)
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use super::search::read_all_captions;
+use crate::file_system::atomic;
+
+/// Default length cap of alt text, which screen readers handle best when short
+pub const DEFAULT_MAX_LENGTH: usize = 125;
+
+/// File an alt text export is written to
+pub const CSV_FILE: &str = "alt-text.csv";
+
+/// Openings that only restate that the file is an image, dropped from alt text
+const REDUNDANT_OPENINGS: &[&str] = &[
+    "this is an image of",
+    "this is a picture of",
+    "this is a photo of",
+    "this image shows",
+    "this picture shows",
+    "this photo shows",
+    "the image shows",
+    "the picture shows",
+    "the photo shows",
+    "an image of",
+    "a picture of",
+    "a photograph of",
+    "a photo of",
+    "image of",
+    "picture of",
+    "photograph of",
+    "photo of",
+];
+
+/// Instructions for writing the alt text of a media file
+pub fn prompt(max_length: usize) -> String {
+    format!(
+        "Write alt text for this image for a web page. Describe only what is visible and \
+         what matters to someone who can't see it: the subject, the action and any \
+         legible text. Be concise and factual, with no opinions or guesses. Don't start \
+         with \"image of\", \"picture of\" or \"photo of\", and don't mention that it is \
+         an image. Use at most {} characters. Return the alt text in the alt_text field.",
+        max_length
+    )
+}
+
+/// The alt text of a structured response, or the whole response when it isn't one
+pub fn parse_response(response: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(response)
+        .ok()
+        .and_then(|json| json.get("alt_text")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| response.to_string())
+}
+
+/// Tidy generated alt text: drop quotes and redundant openings, capitalize it and cap
+/// its length
+///
+/// Text over `max_length` characters is cut at the last sentence that fits, or else
+/// at the last whole word.
+pub fn clean(text: &str, max_length: usize) -> String {
+    let mut text = text
+        .trim()
+        .trim_matches(|c: char| c == '"' || c == '\'' || c.is_whitespace())
+        .to_string();
+
+    while let Some(opening) = REDUNDANT_OPENINGS.iter().find(|opening| {
+        text.get(..opening.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(opening))
+    }) {
+        text = text[opening.len()..]
+            .trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+            .to_string();
+    }
+
+    let mut chars = text.chars();
+    let mut text = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+        None => return text,
+    };
+
+    if text.chars().count() > max_length {
+        let cut: String = text.chars().take(max_length).collect();
+        text = match cut.rfind(['.', '!', '?']) {
+            Some(end) if end > 0 => cut[..=end].to_string(),
+            _ => match cut.rfind(char::is_whitespace) {
+                Some(end) => cut[..end].to_string(),
+                None => cut,
+            },
+        };
+        text = text
+            .trim_end_matches(|c: char| c == ',' || c == ';' || c == ':' || c.is_whitespace())
+            .to_string();
+    }
+
+    text
+}
+
+/// Quote a CSV field when it contains separators, quotes or line breaks
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Write the captions of a project as alt text to an [`CSV_FILE`] in `destination`,
+/// with a `file,alt_text` row per captioned file
+///
+/// Returns the number of rows written.
+pub fn write_csv(directory: &Path, destination: &Path) -> Result<usize, String> {
+    let mut captions = read_all_captions(directory)?;
+    captions.sort_by(|a, b| a.0.relative_path.cmp(&b.0.relative_path));
+
+    let mut content = String::from("file,alt_text\n");
+    let mut rows = 0;
+    for (file, caption) in captions {
+        let caption = caption.split_whitespace().collect::<Vec<_>>().join(" ");
+        if caption.is_empty() {
+            continue;
+        }
+        content.push_str(&format!(
+            "{},{}\n",
+            csv_field(&file.relative_path),
+            csv_field(&caption)
+        ));
+        rows += 1;
+    }
+
+    atomic::write_atomic(&destination.join(CSV_FILE), content, false)
+        .map_err(|e| format!("Failed to write {}: {}", CSV_FILE, e))?;
+
+    Ok(rows)
+}
@@ -0,0 +1,371 @@
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::warn;
+
+use super::encoding;
+use super::format::{self, CaptionStore};
+use super::git;
+use super::history;
+use super::search::read_all_captions;
+use crate::file_system::commands::scan_media_files;
+use crate::undo::journal::{Operation, OperationKind};
+
+/// A caption that a batch edit changes
+#[derive(Debug, Serialize)]
+pub struct CaptionChange {
+    pub path: String,
+    pub name: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// Result of a batch edit; when `applied` is false nothing was written (dry run)
+#[derive(Debug, Serialize)]
+pub struct BatchEditSummary {
+    pub changes: Vec<CaptionChange>,
+    pub total_replacements: usize,
+    pub applied: bool,
+}
+
+/// Compute the captions changed by a find-and-replace over a project
+pub fn plan_replace(
+    directory: &Path,
+    find: &str,
+    replace: &str,
+    regex: bool,
+    case_sensitive: bool,
+) -> Result<(Vec<CaptionChange>, usize), String> {
+    let pattern = if regex {
+        find.to_string()
+    } else {
+        regex::escape(find)
+    };
+    let pattern = RegexBuilder::new(&pattern)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {}", e))?;
+
+    let mut changes = Vec::new();
+    let mut total = 0;
+
+    for (file, caption) in read_all_captions(directory)? {
+        let count = pattern.find_iter(&caption).count();
+        if count == 0 {
+            continue;
+        }
+
+        // Literal replacements must not expand `$1`-style references
+        let after = if regex {
+            pattern.replace_all(&caption, replace).to_string()
+        } else {
+            pattern.replace_all(&caption, NoExpand(replace)).to_string()
+        };
+
+        if after != caption {
+            total += count;
+            changes.push(CaptionChange {
+                path: file.path,
+                name: file.name,
+                before: caption,
+                after,
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok((changes, total))
+}
+
+/// The text to add with surrounding separators removed, used to detect duplicates
+fn core_text(text: &str) -> &str {
+    text.trim_matches(|c: char| c == ',' || c.is_whitespace())
+}
+
+/// Compute the captions changed by adding text before and/or after them
+///
+/// Text already present in a caption (ignoring case and separators) isn't added again.
+/// When `files` is given only those media files are modified; media without a caption
+/// are skipped.
+pub fn plan_modify(
+    directory: &Path,
+    prepend: Option<&str>,
+    append: Option<&str>,
+    files: Option<&[String]>,
+) -> Result<Vec<CaptionChange>, String> {
+    let prepend = prepend.filter(|p| !core_text(p).is_empty());
+    let append = append.filter(|a| !core_text(a).is_empty());
+    let selected: Option<HashSet<&str>> = files.map(|f| f.iter().map(|p| p.as_str()).collect());
+
+    let mut changes = Vec::new();
+
+    for (file, caption) in read_all_captions(directory)? {
+        if let Some(selected) = &selected {
+            if !selected.contains(file.path.as_str()) {
+                continue;
+            }
+        }
+
+        let lower = caption.to_lowercase();
+        let mut after = caption.clone();
+
+        if let Some(prefix) = prepend {
+            if !lower.contains(&core_text(prefix).to_lowercase()) {
+                after = if after.trim().is_empty() {
+                    core_text(prefix).to_string()
+                } else {
+                    format!("{}{}", prefix, after)
+                };
+            }
+        }
+
+        if let Some(suffix) = append {
+            if !lower.contains(&core_text(suffix).to_lowercase()) {
+                after = if after.trim().is_empty() {
+                    core_text(suffix).to_string()
+                } else {
+                    format!("{}{}", after, suffix)
+                };
+            }
+        }
+
+        if after != caption {
+            changes.push(CaptionChange {
+                path: file.path,
+                name: file.name,
+                before: caption,
+                after,
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(changes)
+}
+
+/// Fill in the placeholders of a propagated caption for one target file
+///
+/// `{name}` is the file name, `{stem}` the name without extension, `{index}` the
+/// 1-based position in the selection and `{frame}` the number the stem ends with (e.g.
+/// `0042` for `clip_0042.png`, empty if there is none).
+pub(crate) fn fill_template(template: &str, media: &Path, index: usize) -> String {
+    let name = media
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = media
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let frame_start = stem
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let frame = &stem[frame_start..];
+
+    template
+        .replace("{name}", &name)
+        .replace("{stem}", &stem)
+        .replace("{index}", &index.to_string())
+        .replace("{frame}", frame)
+}
+
+/// Compute the captions changed by copying one file's caption to other files
+///
+/// With `template` set, placeholders in the source caption are filled in per target
+/// (see [`fill_template`]). Targets that already have a caption are only changed with
+/// `overwrite` set.
+pub fn plan_propagate(
+    directory: &Path,
+    source: &Path,
+    targets: &[String],
+    template: bool,
+    overwrite: bool,
+) -> Result<Vec<CaptionChange>, String> {
+    let mut store = CaptionStore::open(directory);
+    let caption = store
+        .read(source)?
+        .filter(|c| !c.trim().is_empty())
+        .ok_or_else(|| format!("{} has no caption to copy", source.display()))?;
+
+    let mut changes = Vec::new();
+
+    for (i, target) in targets.iter().enumerate() {
+        let media = Path::new(target);
+        if media == source {
+            continue;
+        }
+        if !media.is_file() {
+            return Err(format!("File does not exist: {}", target));
+        }
+
+        let before = store.read(media)?.unwrap_or_default();
+        if !before.trim().is_empty() && !overwrite {
+            continue;
+        }
+
+        let after = if template {
+            fill_template(&caption, media, i + 1)
+        } else {
+            caption.clone()
+        };
+
+        if after != before {
+            changes.push(CaptionChange {
+                path: target.clone(),
+                name: media
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                before,
+                after,
+            });
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Where a caption merge reads one of its parts from
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeSource {
+    /// The project's caption
+    Caption,
+    /// The caption in a language, e.g. `image.en.txt`
+    Language(String),
+    /// A sidecar with this extension next to the media, e.g. `tags` for `image.tags`
+    /// or `wd14.txt` for `image.wd14.txt`
+    Sidecar(String),
+}
+
+impl MergeSource {
+    fn read(&self, directory: &Path, media: &Path) -> Result<Option<String>, String> {
+        match self {
+            MergeSource::Caption => CaptionStore::open(directory).read(media),
+            MergeSource::Language(language) => {
+                format::validate_language(language)?;
+                CaptionStore::open(directory)
+                    .with_language(Some(language))
+                    .read(media)
+            }
+            MergeSource::Sidecar(extension) => {
+                let path = media.with_extension(extension.trim_start_matches('.'));
+                if !path.is_file() {
+                    return Ok(None);
+                }
+                encoding::read_caption_text(&path)
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// Compute the captions changed by merging two caption sources, e.g. tagger output
+/// and a prose caption, into each file's caption
+///
+/// `template` places the parts with `{tags}` and `{prose}`. When a file has only one
+/// part, the separators around the missing one are dropped; files with neither are
+/// skipped. When `files` is given only those media files are merged.
+pub fn plan_merge(
+    directory: &Path,
+    tags: &MergeSource,
+    prose: &MergeSource,
+    template: &str,
+    files: Option<&[String]>,
+) -> Result<Vec<CaptionChange>, String> {
+    if !template.contains("{tags}") && !template.contains("{prose}") {
+        return Err("The template needs a {tags} or {prose} placeholder".to_string());
+    }
+    let selected: Option<HashSet<&str>> = files.map(|f| f.iter().map(|p| p.as_str()).collect());
+    // Separators left behind by an empty part, e.g. ", , " or a leading ", "
+    let separators = Regex::new(r"(\s*,\s*){2,}").map_err(|e| e.to_string())?;
+    let mut store = CaptionStore::open(directory);
+
+    let mut changes = Vec::new();
+
+    for file in scan_media_files(directory)? {
+        if let Some(selected) = &selected {
+            if !selected.contains(file.path.as_str()) {
+                continue;
+            }
+        }
+
+        let media = Path::new(&file.path);
+        let tags_text = tags.read(directory, media)?.unwrap_or_default();
+        let prose_text = prose.read(directory, media)?.unwrap_or_default();
+        if tags_text.trim().is_empty() && prose_text.trim().is_empty() {
+            continue;
+        }
+
+        let merged = template
+            .replace("{tags}", core_text(&tags_text))
+            .replace("{prose}", core_text(&prose_text));
+        let after = core_text(&separators.replace_all(&merged, ", ")).to_string();
+
+        let before = store.read(media)?.unwrap_or_default();
+        if after != before {
+            changes.push(CaptionChange {
+                path: file.path,
+                name: file.name,
+                before,
+                after,
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(changes)
+}
+
+/// Write a set of caption changes as a single undoable operation
+pub fn apply_changes(
+    directory: &Path,
+    changes: &[CaptionChange],
+    description: String,
+) -> Result<(), String> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut store = CaptionStore::open(directory);
+    let mut operation = Operation::begin(
+        directory,
+        OperationKind::CaptionOverwrite,
+        description.clone(),
+    )?;
+
+    if store.format().is_manifest() {
+        operation.snapshot(&store.caption_path(directory))?;
+    } else {
+        for change in changes {
+            operation.snapshot(&store.caption_path(Path::new(&change.path)))?;
+        }
+    }
+
+    for change in changes {
+        let media = Path::new(&change.path);
+        // New captions have no previous version to keep
+        if change.before.is_empty() {
+            store.write(media, &change.after)?;
+            continue;
+        }
+        if let Err(e) = history::record_version(media, &change.before) {
+            warn!(
+                "Failed to record caption history for {}: {}",
+                change.path, e
+            );
+        }
+        store.write(media, &change.after)?;
+    }
+
+    operation.commit()?;
+    git::commit_if_enabled(directory, &description);
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use super::batch::CaptionChange;
+use super::search::read_all_captions;
+use super::taxonomy::TagDictionary;
+use crate::jobs::manager::Job;
+
+/// Tags this short keep their underscores, since they're emoticons like `^_^` or `o_o`
+const EMOTICON_MAX_LEN: usize = 3;
+
+/// A tag of the imported captions that the project's tag dictionary doesn't know
+#[derive(Debug, Serialize)]
+pub struct UnknownTag {
+    pub tag: String,
+    /// Number of captions containing it
+    pub files: usize,
+}
+
+/// Result of normalizing the tags of an imported booru-style dataset
+#[derive(Debug, Serialize)]
+pub struct BooruImport {
+    pub changes: Vec<CaptionChange>,
+    pub applied: bool,
+    /// Tags neither aliased, implied nor implying anything, most frequent first
+    pub unknown_tags: Vec<UnknownTag>,
+}
+
+/// Split a booru-style caption into its raw tags
+///
+/// Tags are comma-separated, one per line, or, in captions with neither, separated by
+/// spaces when they use underscores for theirs (`1girl long_hair blue_eyes`).
+fn split_raw(caption: &str) -> Vec<&str> {
+    let parts: Vec<&str> = if caption.contains(',') {
+        caption.split(',').collect()
+    } else if caption.trim().contains('\n') {
+        caption.lines().collect()
+    } else if caption.contains('_') {
+        caption.split_whitespace().collect()
+    } else {
+        vec![caption]
+    };
+
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Normalize a single booru tag: underscores become spaces and whitespace is collapsed
+fn normalize_tag(tag: &str) -> String {
+    let tag = if tag.chars().count() > EMOTICON_MAX_LEN {
+        tag.replace('_', " ")
+    } else {
+        tag.to_string()
+    };
+    tag.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Normalize a booru-style caption into a comma-separated tag list
+///
+/// Separators and underscores are normalized, duplicate tags removed and the tag
+/// dictionary applied.
+pub fn normalize_caption(caption: &str, dictionary: &TagDictionary) -> String {
+    let mut seen = HashSet::new();
+    let tags: Vec<String> = split_raw(caption)
+        .into_iter()
+        .map(normalize_tag)
+        .filter(|tag| seen.insert(tag.to_lowercase()))
+        .collect();
+
+    dictionary.apply(&tags.join(", "))
+}
+
+/// Compute the captions changed by normalizing a project's booru-style tags, along
+/// with the tags its dictionary doesn't know
+pub fn plan_import(
+    job: &Job,
+    directory: &Path,
+    dictionary: &TagDictionary,
+) -> Result<(Vec<CaptionChange>, Vec<UnknownTag>), String> {
+    let captions = read_all_captions(directory)?;
+    let total = captions.len() as u64;
+    let known = dictionary.known_tags();
+
+    let mut changes = Vec::new();
+    let mut unknown: HashMap<String, usize> = HashMap::new();
+
+    for (index, (file, caption)) in captions.into_iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some(file.name.clone()));
+
+        let after = normalize_caption(&caption, dictionary);
+        let tags: HashSet<String> = after
+            .split(',')
+            .map(|t| t.trim().to_lowercase())
+            .filter(|t| !t.is_empty() && !known.contains(t))
+            .collect();
+        for tag in tags {
+            *unknown.entry(tag).or_default() += 1;
+        }
+
+        if after != caption {
+            changes.push(CaptionChange {
+                path: file.path,
+                name: file.name,
+                before: caption,
+                after,
+            });
+        }
+    }
+    job.progress(total, total, None);
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut unknown_tags: Vec<UnknownTag> = unknown
+        .into_iter()
+        .map(|(tag, files)| UnknownTag { tag, files })
+        .collect();
+    unknown_tags.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| a.tag.cmp(&b.tag)));
+
+    Ok((changes, unknown_tags))
+}
@@ -0,0 +1,935 @@
+use std::path::Path;
+use tauri::AppHandle;
+use tracing::warn;
+
+use super::alt_text;
+use super::batch::{self, BatchEditSummary, MergeSource};
+use super::booru::{self, BooruImport};
+use super::diff::{self, CaptionDiff, CaptionMerge};
+use super::encoding;
+use super::format::{self, CaptionFormat, CaptionStore, ConversionSummary};
+use super::git::{self, CaptionCommit, CaptionFileDiff};
+use super::history::{self, CaptionVersion};
+use super::import::{self as caption_import, CaptionConflict, CaptionImport};
+use super::modified;
+use super::orphans::{self, OrphanAction, OrphanCleanup, OrphanReport};
+use super::search::{self, CaptionMatch};
+use super::spelling::{self, CaptionIssue, GrammarModel, IssueKind, LintReport};
+use super::stats::{self, CaptionStats, TagFrequency};
+use super::taxonomy::{self, TagDictionary};
+use super::trigger;
+use crate::api::commands::correct_caption;
+use crate::backup::commands::backup_before_batch;
+use crate::file_system::commands::scan_media_files;
+use crate::file_system::commands::{validate_media_file, validate_project_directory};
+use crate::jobs::manager::{self as jobs, Job, JobKind};
+use crate::media::metadata;
+use crate::project::{settings, PROJECT_FILE};
+use crate::undo::journal::{self, Operation, OperationKind};
+
+/// Open the caption store of a media file's project for a language, if one is given
+fn store_for_language(media: &Path, language: Option<&str>) -> Result<CaptionStore, String> {
+    if let Some(language) = language {
+        format::validate_language(language)?;
+    }
+    Ok(CaptionStore::for_media(media).with_language(language))
+}
+
+/// Read the caption of a media file in its project's caption format
+///
+/// Returns an empty string if the file has no caption yet. With a `language` the
+/// caption in that language (`image.en.txt`) is read instead of the primary one.
+#[tauri::command]
+pub async fn read_media_caption(
+    media_path: String,
+    language: Option<String>,
+) -> Result<String, String> {
+    let media = Path::new(&media_path);
+    let mut store = store_for_language(media, language.as_deref())?;
+
+    Ok(store.read(media)?.unwrap_or_default())
+}
+
+/// Write the caption of a media file in its project's caption format
+///
+/// The write is atomic; `sync` additionally flushes it to disk before returning. With
+/// a `language` the caption in that language is written instead of the primary one.
+/// With `expected`, the caption as it was last read, a caption changed on disk since is
+/// not overwritten; the error holds it as it is now (see [`modified::CaptionModified`]).
+#[tauri::command]
+pub async fn write_media_caption(
+    media_path: String,
+    content: String,
+    sync: Option<bool>,
+    language: Option<String>,
+    expected: Option<String>,
+) -> Result<(), String> {
+    let media = Path::new(&media_path);
+    settings::ensure_writable(media)?;
+    let mut store =
+        store_for_language(media, language.as_deref())?.with_sync(sync.unwrap_or(false));
+    let content = encoding::normalize_line_endings(&content);
+    let caption_path = store.caption_path(media);
+    // Sidecars in a language keep their own history; the manifest's is per media file
+    let history_path = match (language.is_some(), store.format().extension()) {
+        (true, Some(_)) => caption_path.clone(),
+        _ => media.to_path_buf(),
+    };
+
+    // Record the previous caption so an accidental overwrite can be undone
+    let mut operation = None;
+    let previous = store.read(media)?;
+    modified::ensure_unmodified(
+        &caption_path,
+        expected.as_deref(),
+        previous.as_deref(),
+        &content,
+    )?;
+    if previous.is_some()
+        && previous.as_deref() != Some(content.as_str())
+        && !journal::recently_recorded_caption(&caption_path)
+    {
+        let name = media
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut op = Operation::begin(
+            &caption_path,
+            OperationKind::CaptionOverwrite,
+            format!("Edit caption {}", name),
+        )?;
+        op.snapshot(&caption_path)?;
+        operation = Some(op);
+    }
+
+    if let Some(previous) = previous.as_deref().filter(|p| *p != content) {
+        if let Err(e) = history::record_version(&history_path, previous) {
+            warn!("Failed to record caption history for {}: {}", media_path, e);
+        }
+    }
+
+    store.write(media, &content)?;
+
+    if let Some(op) = operation {
+        op.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Convert all captions of a project to another sidecar format and make it the
+/// project's caption format
+#[tauri::command]
+pub async fn convert_caption_format(
+    app: AppHandle,
+    directory: String,
+    target_format: CaptionFormat,
+    remove_old: bool,
+) -> Result<ConversionSummary, String> {
+    let dir_path = Path::new(&directory);
+    if !dir_path.is_dir() {
+        return Err(format!("Directory does not exist: {}", directory));
+    }
+
+    backup_before_batch(&app, dir_path, "caption format conversion")?;
+
+    let source = CaptionStore::open(dir_path);
+    let destination = CaptionStore::with_format(dir_path, target_format);
+
+    // Snapshot every file the conversion may create or remove
+    let mut operation = Operation::begin(
+        dir_path,
+        OperationKind::CaptionOverwrite,
+        format!("Convert captions to {:?}", target_format),
+    )?;
+    operation.snapshot(&dir_path.join(PROJECT_FILE))?;
+    for file in scan_media_files(dir_path)? {
+        let media = Path::new(&file.path);
+        operation.snapshot(&source.caption_path(media))?;
+        operation.snapshot(&destination.caption_path(media))?;
+    }
+
+    let summary = format::convert_directory(dir_path, target_format, remove_old)?;
+    operation.commit()?;
+    git::commit_if_enabled(
+        dir_path,
+        &format!("Convert captions to {:?}", target_format),
+    );
+
+    Ok(summary)
+}
+
+/// Get the previous versions of a caption, most recent first
+///
+/// Accepts either a media file or its caption file.
+#[tauri::command]
+pub async fn get_caption_history(
+    app: AppHandle,
+    path: String,
+) -> Result<Vec<CaptionVersion>, String> {
+    let path = validate_media_file(&app, &path)?;
+    history::get_versions(&path)
+}
+
+/// Restore a previous version of a caption and return its content
+///
+/// The caption being replaced is kept as a new version, so a restore can be reverted.
+#[tauri::command]
+pub async fn restore_caption_version(
+    app: AppHandle,
+    path: String,
+    version: u32,
+) -> Result<String, String> {
+    let path = validate_media_file(&app, &path)?;
+    let target = history::history_target(&path);
+
+    let mut operation = Operation::begin(
+        &target,
+        OperationKind::CaptionOverwrite,
+        format!("Restore caption version {}", version),
+    )?;
+    operation.snapshot(&CaptionStore::for_media(&target).caption_path(&target))?;
+
+    let content = history::restore_version(&target, version)?;
+    operation.commit()?;
+
+    Ok(content)
+}
+
+/// Keep a project's caption files in a git repository from now on
+///
+/// The repository lives in the project's metadata directory, so the dataset itself is
+/// left untouched. The current captions become the first commit (returned, unless the
+/// project was already tracked and nothing changed), and every batch operation commits
+/// afterwards.
+#[tauri::command]
+pub async fn enable_caption_git(
+    app: AppHandle,
+    directory: String,
+) -> Result<Option<CaptionCommit>, String> {
+    let project = validate_project_directory(&app, &directory)?;
+
+    let mut project_settings = settings::load_settings(&project)?;
+    project_settings.caption_git = true;
+    settings::save_settings(&project, &project_settings)?;
+
+    git::commit(&project, "Track captions")
+}
+
+/// Commit the current captions of a project, e.g. after editing captions by hand;
+/// returns None if nothing changed
+#[tauri::command]
+pub async fn commit_captions(
+    app: AppHandle,
+    directory: String,
+    message: String,
+) -> Result<Option<CaptionCommit>, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let message = match message.trim() {
+        "" => "Edit captions",
+        message => message,
+    };
+
+    git::commit(&project, message)
+}
+
+/// List the caption commits of a project, most recent first (default: the last 100)
+#[tauri::command]
+pub async fn get_caption_git_log(
+    app: AppHandle,
+    directory: String,
+    limit: Option<usize>,
+) -> Result<Vec<CaptionCommit>, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    git::log(&project, limit.unwrap_or(100))
+}
+
+/// Get the caption changes of a commit, or the uncommitted ones when no commit is given
+#[tauri::command]
+pub async fn get_caption_git_diff(
+    app: AppHandle,
+    directory: String,
+    commit: Option<String>,
+) -> Result<Vec<CaptionFileDiff>, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    git::diff(&project, commit.as_deref())
+}
+
+/// Undo the caption changes of a commit, keeping later changes, and commit the result
+///
+/// Fails without changing anything when later edits conflict with the revert. The
+/// rewrite is recorded in the undo journal.
+#[tauri::command]
+pub async fn revert_caption_commit(
+    app: AppHandle,
+    directory: String,
+    commit: String,
+) -> Result<Option<CaptionCommit>, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    git::revert(&project, &commit)
+}
+
+/// Search the captions of every media file in a project
+///
+/// Plain queries match literally; with `regex` set the query is a regular expression.
+/// Matching is case-insensitive either way.
+#[tauri::command]
+pub async fn search_captions(
+    app: AppHandle,
+    directory: String,
+    query: String,
+    regex: bool,
+) -> Result<Vec<CaptionMatch>, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let pattern = search::build_pattern(&query, regex)?;
+    search::search_directory(&dir_path, &pattern)
+}
+
+/// Find and replace text across all captions of a project
+///
+/// With `dry_run` set the affected captions are returned without writing anything, so
+/// the changes can be previewed first. Applied changes are a single undoable operation.
+#[tauri::command]
+pub async fn replace_in_captions(
+    app: AppHandle,
+    directory: String,
+    find: String,
+    replace: String,
+    regex: bool,
+    case_sensitive: bool,
+    dry_run: bool,
+) -> Result<BatchEditSummary, String> {
+    let dir_path = Path::new(&directory);
+    if !dir_path.is_dir() {
+        return Err(format!("Directory does not exist: {}", directory));
+    }
+
+    if find.is_empty() {
+        return Err("Search text cannot be empty".to_string());
+    }
+
+    let (changes, total_replacements) =
+        batch::plan_replace(dir_path, &find, &replace, regex, case_sensitive)?;
+
+    if !dry_run && !changes.is_empty() {
+        backup_before_batch(&app, dir_path, "find and replace")?;
+        batch::apply_changes(
+            dir_path,
+            &changes,
+            format!("Replace \"{}\" in {} captions", find, changes.len()),
+        )?;
+    }
+
+    Ok(BatchEditSummary {
+        changes,
+        total_replacements,
+        applied: !dry_run,
+    })
+}
+
+/// Add text before and/or after the captions of a project, or of selected files
+///
+/// Use it to add a trigger word or a style suffix to a whole dataset. The text is added
+/// as given, so include any separator (e.g. `"ohwx, "`); captions that already contain
+/// it are left alone.
+#[tauri::command]
+pub async fn modify_captions(
+    app: AppHandle,
+    directory: String,
+    prepend: Option<String>,
+    append: Option<String>,
+    files: Option<Vec<String>>,
+) -> Result<BatchEditSummary, String> {
+    let dir_path = Path::new(&directory);
+    if !dir_path.is_dir() {
+        return Err(format!("Directory does not exist: {}", directory));
+    }
+
+    let changes = batch::plan_modify(
+        dir_path,
+        prepend.as_deref(),
+        append.as_deref(),
+        files.as_deref(),
+    )?;
+
+    if !changes.is_empty() {
+        backup_before_batch(&app, dir_path, "caption modification")?;
+    }
+    batch::apply_changes(
+        dir_path,
+        &changes,
+        format!("Modify {} captions", changes.len()),
+    )?;
+
+    Ok(BatchEditSummary {
+        total_replacements: changes.len(),
+        changes,
+        applied: true,
+    })
+}
+
+/// Copy the caption of one media file to a selection of others
+///
+/// Useful for burst shots and frame sequences. With `template` set, `{name}`, `{stem}`,
+/// `{index}` and `{frame}` in the caption are filled in per file. Files that already
+/// have a caption are left alone unless `overwrite` is set. With `dry_run` set the
+/// changes are only returned.
+#[tauri::command]
+pub async fn propagate_caption(
+    app: AppHandle,
+    directory: String,
+    source: String,
+    targets: Vec<String>,
+    template: bool,
+    overwrite: bool,
+    dry_run: bool,
+) -> Result<BatchEditSummary, String> {
+    let dir_path = Path::new(&directory);
+    if !dir_path.is_dir() {
+        return Err(format!("Directory does not exist: {}", directory));
+    }
+
+    let changes =
+        batch::plan_propagate(dir_path, Path::new(&source), &targets, template, overwrite)?;
+
+    if !dry_run && !changes.is_empty() {
+        backup_before_batch(&app, dir_path, "caption propagation")?;
+        let name = Path::new(&source)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        batch::apply_changes(
+            dir_path,
+            &changes,
+            format!("Copy caption of {} to {} files", name, changes.len()),
+        )?;
+    }
+
+    Ok(BatchEditSummary {
+        total_replacements: changes.len(),
+        changes,
+        applied: !dry_run,
+    })
+}
+
+/// Merge two caption sources into the captions of a project, or of selected files
+///
+/// Combines e.g. tagger output kept in `image.tags` with the prose caption, placing
+/// them with a template like `"{tags}, {prose}"` (the default). The merged captions
+/// replace the files' captions as a single undoable operation; with `dry_run` set the
+/// changes are only returned.
+#[tauri::command]
+pub async fn merge_captions(
+    app: AppHandle,
+    directory: String,
+    tags: MergeSource,
+    prose: MergeSource,
+    template: Option<String>,
+    files: Option<Vec<String>>,
+    dry_run: bool,
+) -> Result<BatchEditSummary, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let template = template.unwrap_or_else(|| "{tags}, {prose}".to_string());
+
+    let changes = batch::plan_merge(&dir_path, &tags, &prose, &template, files.as_deref())?;
+
+    if !dry_run && !changes.is_empty() {
+        backup_before_batch(&app, &dir_path, "caption merge")?;
+        batch::apply_changes(
+            &dir_path,
+            &changes,
+            format!("Merge {} captions", changes.len()),
+        )?;
+    }
+
+    Ok(BatchEditSummary {
+        total_replacements: changes.len(),
+        changes,
+        applied: !dry_run,
+    })
+}
+
+/// Seed captions from the descriptions and keywords already embedded in a project's
+/// images (XMP, IPTC or EXIF)
+///
+/// Lets previously annotated libraries start from their existing descriptions. Files
+/// that already have a caption are left alone unless `overwrite` is set. With `dry_run`
+/// set the changes are only returned, e.g. to offer seeding after an import.
+#[tauri::command]
+pub async fn seed_captions_from_metadata(
+    app: AppHandle,
+    directory: String,
+    include_keywords: bool,
+    overwrite: bool,
+    dry_run: bool,
+) -> Result<BatchEditSummary, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let description = format!("Read embedded captions in {}", directory);
+
+    let project = dir_path.clone();
+    let changes = jobs::run(&app, JobKind::Import, description, move |job| {
+        metadata::plan_seed(job, &project, include_keywords, overwrite)
+    })
+    .await?;
+
+    if !dry_run && !changes.is_empty() {
+        backup_before_batch(&app, &dir_path, "caption seeding")?;
+        batch::apply_changes(
+            &dir_path,
+            &changes,
+            format!("Seed {} captions from image metadata", changes.len()),
+        )?;
+    }
+
+    Ok(BatchEditSummary {
+        total_replacements: changes.len(),
+        changes,
+        applied: !dry_run,
+    })
+}
+
+/// Find the captions of a project left without a media file, e.g. after media was
+/// deleted or renamed outside the app, and the media files without a caption
+///
+/// Each orphaned caption comes with the uncaptioned media file it most likely
+/// belonged to, when its name is a close match.
+#[tauri::command]
+pub async fn find_orphan_captions(
+    app: AppHandle,
+    directory: String,
+) -> Result<OrphanReport, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    orphans::find_orphans(&dir_path)
+}
+
+/// Delete, archive or re-pair the orphaned captions of a project, as one undoable
+/// operation
+///
+/// `captions` are the file names of the orphans to handle, by default all of them.
+/// Re-pairing renames each caption after its suggested media file.
+#[tauri::command]
+pub async fn clean_up_orphan_captions(
+    app: AppHandle,
+    directory: String,
+    action: OrphanAction,
+    captions: Option<Vec<String>>,
+) -> Result<OrphanCleanup, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    orphans::clean_up_orphans(&dir_path, action, captions.as_deref())
+}
+
+/// Get caption statistics of a project to gauge how ready the dataset is
+#[tauri::command]
+pub async fn get_caption_stats(app: AppHandle, directory: String) -> Result<CaptionStats, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    stats::collect_stats(&dir_path)
+}
+
+/// Get how often each comma-separated tag occurs across a project's captions
+#[tauri::command]
+pub async fn get_tag_frequencies(
+    app: AppHandle,
+    directory: String,
+) -> Result<Vec<TagFrequency>, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    stats::tag_frequencies(&dir_path)
+}
+
+/// Get the tag aliases and implications of a project
+#[tauri::command]
+pub async fn get_tag_dictionary(
+    app: AppHandle,
+    directory: String,
+) -> Result<TagDictionary, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    taxonomy::load(&dir_path)
+}
+
+/// Replace the tag aliases and implications of a project
+///
+/// They are applied to every caption generated for the project from then on; use
+/// `normalize_tags` to apply them to the existing captions. Returns the dictionary as
+/// stored, with its tags trimmed and empty entries dropped.
+#[tauri::command]
+pub async fn save_tag_dictionary(
+    app: AppHandle,
+    directory: String,
+    dictionary: TagDictionary,
+) -> Result<TagDictionary, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    taxonomy::save(&dir_path, dictionary)
+}
+
+/// Apply a project's tag dictionary to all of its captions
+///
+/// Aliases are replaced, implied tags added and duplicate tags removed. With `dry_run`
+/// set the changes are only returned. Applied changes are a single undoable operation.
+#[tauri::command]
+pub async fn normalize_tags(
+    app: AppHandle,
+    directory: String,
+    dry_run: bool,
+) -> Result<BatchEditSummary, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let dictionary = taxonomy::load(&dir_path)?;
+    if dictionary.is_empty() {
+        return Err("The project has no tag aliases or implications".to_string());
+    }
+
+    let changes = taxonomy::plan_normalize(&dir_path, &dictionary)?;
+
+    if !dry_run && !changes.is_empty() {
+        backup_before_batch(&app, &dir_path, "tag normalization")?;
+        batch::apply_changes(
+            &dir_path,
+            &changes,
+            format!("Normalize tags in {} captions", changes.len()),
+        )?;
+    }
+
+    Ok(BatchEditSummary {
+        total_replacements: changes.len(),
+        changes,
+        applied: !dry_run,
+    })
+}
+
+/// Check the spelling of all captions of a project, optionally fixing what it can
+///
+/// Words are checked against `dictionary` (a word list or Hunspell `.dic`; by default
+/// the system word list, if there is one), the project's word list and the words its
+/// captions use often. With `grammar` a language model also corrects every caption
+/// that isn't a tag list. The suggested corrections are returned as changes, and with
+/// `fix` set they are written as a single undoable operation. Runs as a job.
+#[tauri::command]
+pub async fn lint_captions(
+    app: AppHandle,
+    directory: String,
+    dictionary: Option<String>,
+    grammar: Option<GrammarModel>,
+    fix: bool,
+) -> Result<LintReport, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    // The grammar pass is rate limited like captioning; spell checking reads like indexing
+    let kind = if grammar.is_some() {
+        JobKind::Captioning
+    } else {
+        JobKind::Indexing
+    };
+    let description = format!("Check captions in {}", directory);
+
+    let project = dir_path.clone();
+    let mut report = jobs::run_async(&app, kind, description, |job| async move {
+        let checking = job.clone();
+        let mut report = tauri::async_runtime::spawn_blocking(move || {
+            spelling::check_directory(&checking, &project, dictionary.as_deref().map(Path::new))
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        if let Some(grammar) = grammar {
+            check_grammar(&job, &mut report, &grammar).await?;
+        }
+        report.files.retain(|lint| !lint.issues.is_empty());
+        Ok(report)
+    })
+    .await?;
+
+    report.changes = spelling::plan_fixes(&report.files);
+    if fix && !report.changes.is_empty() {
+        backup_before_batch(&app, &dir_path, "spelling fixes")?;
+        batch::apply_changes(
+            &dir_path,
+            &report.changes,
+            format!("Fix spelling in {} captions", report.changes.len()),
+        )?;
+    }
+    report.applied = fix;
+
+    Ok(report)
+}
+
+/// Add a language model's correction to the issues of every prose caption
+async fn check_grammar(
+    job: &Job,
+    report: &mut LintReport,
+    grammar: &GrammarModel,
+) -> Result<(), String> {
+    job.progress(
+        0,
+        report.files.len() as u64,
+        Some("Checking grammar".to_string()),
+    );
+    for lint in &mut report.files {
+        job.check_cancelled()?;
+        if !spelling::is_tag_list(&lint.caption) {
+            let corrected = correct_caption(
+                &grammar.api_url,
+                &grammar.api_key,
+                &grammar.model,
+                &lint.caption,
+            )
+            .await?;
+            if !corrected.is_empty() && corrected != lint.caption.trim() {
+                lint.issues.push(CaptionIssue {
+                    kind: IssueKind::Grammar,
+                    start: 0,
+                    end: lint.caption.len(),
+                    text: lint.caption.clone(),
+                    suggestion: Some(corrected),
+                });
+            }
+        }
+        job.advance(1);
+    }
+    Ok(())
+}
+
+/// Add words to the list the spell checker accepts for a project, e.g. names it
+/// flagged; returns the whole list
+#[tauri::command]
+pub async fn add_spelling_words(
+    app: AppHandle,
+    directory: String,
+    words: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    spelling::add_project_words(&dir_path, &words)
+}
+
+/// Normalize the tags of a dataset imported with booru-style `.txt` captions
+///
+/// Tags separated by commas, lines or (with underscores) spaces become comma-separated
+/// tags with spaces, duplicates are removed and the project's tag dictionary is applied,
+/// so datasets from different sources end up with consistent labels. The tags the
+/// dictionary doesn't know are reported, e.g. to add aliases for them. With `dry_run`
+/// set the changes are only returned.
+#[tauri::command]
+pub async fn import_booru_tags(
+    app: AppHandle,
+    directory: String,
+    dry_run: bool,
+) -> Result<BooruImport, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let dictionary = taxonomy::load(&dir_path)?;
+    let description = format!("Normalize booru tags in {}", directory);
+
+    let project = dir_path.clone();
+    let (changes, unknown_tags) = jobs::run(&app, JobKind::Import, description, move |job| {
+        booru::plan_import(job, &project, &dictionary)
+    })
+    .await?;
+
+    if !dry_run && !changes.is_empty() {
+        backup_before_batch(&app, &dir_path, "booru tag import")?;
+        batch::apply_changes(
+            &dir_path,
+            &changes,
+            format!("Normalize booru tags in {} captions", changes.len()),
+        )?;
+    }
+
+    Ok(BooruImport {
+        changes,
+        applied: !dry_run,
+        unknown_tags,
+    })
+}
+
+/// Import the caption files of a folder or ZIP archive into a project's captions, e.g.
+/// captions a collaborator reviewed in a captions-only export
+///
+/// Captions match media files by relative path, or by file name; `policy` decides
+/// about media files that already have a caption (by default they keep it). Captions
+/// matching no media file are reported. The captions replace the files' captions as a
+/// single undoable operation; with `dry_run` set the changes are only returned.
+#[tauri::command]
+pub async fn import_caption_files(
+    app: AppHandle,
+    directory: String,
+    source: String,
+    policy: Option<CaptionConflict>,
+    dry_run: bool,
+) -> Result<CaptionImport, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let source_path = Path::new(&source).to_path_buf();
+    if !source_path.exists() {
+        return Err(format!("Source does not exist: {}", source));
+    }
+    let description = format!("Read captions from {}", source);
+
+    let project = dir_path.clone();
+    let mut import = jobs::run(&app, JobKind::Import, description, move |job| {
+        caption_import::plan_import(job, &project, &source_path, policy.unwrap_or_default())
+    })
+    .await?;
+
+    if !dry_run && !import.changes.is_empty() {
+        backup_before_batch(&app, &dir_path, "caption import")?;
+        batch::apply_changes(
+            &dir_path,
+            &import.changes,
+            format!("Import {} captions", import.changes.len()),
+        )?;
+    }
+    import.applied = !dry_run;
+    Ok(import)
+}
+
+/// Compare the captions of a project with those of another project, or of a folder or
+/// ZIP archive of caption files, e.g. before merging a collaborator's work
+#[tauri::command]
+pub async fn diff_captions(
+    app: AppHandle,
+    directory: String,
+    other: String,
+) -> Result<CaptionDiff, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let other_path = Path::new(&other).to_path_buf();
+    if !other_path.exists() {
+        return Err(format!("Source does not exist: {}", other));
+    }
+    let description = format!("Compare captions of {} with {}", directory, other);
+
+    jobs::run(&app, JobKind::Import, description, move |job| {
+        diff::diff_captions(job, &dir_path, &other_path)
+    })
+    .await
+}
+
+/// Merge the captions of another project, or of a folder or ZIP archive of caption
+/// files, into a project with a three-way merge
+///
+/// `base` is the caption set both sides started from, e.g. a captions-only export
+/// made before the dataset was split between two people. Captions changed on both
+/// sides get both versions between `<<<<<<< ours` and `>>>>>>> theirs` markers to be
+/// resolved by hand. The captions replace the files' captions as a single undoable
+/// operation; with `dry_run` set the changes are only returned.
+#[tauri::command]
+pub async fn merge_caption_sets(
+    app: AppHandle,
+    directory: String,
+    theirs: String,
+    base: Option<String>,
+    dry_run: bool,
+) -> Result<CaptionMerge, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    for source in std::iter::once(&theirs).chain(base.as_ref()) {
+        if !Path::new(source).exists() {
+            return Err(format!("Source does not exist: {}", source));
+        }
+    }
+    let description = format!("Merge captions of {} into {}", theirs, directory);
+
+    let project = dir_path.clone();
+    let mut merge = jobs::run(&app, JobKind::Import, description, move |job| {
+        diff::plan_three_way(
+            job,
+            &project,
+            Path::new(&theirs),
+            base.as_deref().map(Path::new),
+        )
+    })
+    .await?;
+
+    if !dry_run && !merge.changes.is_empty() {
+        backup_before_batch(&app, &dir_path, "caption merge")?;
+        batch::apply_changes(
+            &dir_path,
+            &merge.changes,
+            format!(
+                "Merge {} captions from another caption set",
+                merge.changes.len()
+            ),
+        )?;
+    }
+    merge.applied = !dry_run;
+    Ok(merge)
+}
+
+/// List the languages that have captions in a project, e.g. `["en", "ja"]`
+#[tauri::command]
+pub async fn list_caption_languages(directory: String) -> Result<Vec<String>, String> {
+    let dir_path = Path::new(&directory);
+    if !dir_path.is_dir() {
+        return Err(format!("Directory does not exist: {}", directory));
+    }
+
+    format::languages(dir_path)
+}
+
+/// Re-apply a project's trigger rules to all of its captions
+///
+/// Generated captions follow the rules already; use this after changing them
+/// mid-project. Tokens in `previous_tokens`, e.g. an old trigger word, are replaced by
+/// the current one first. With `dry_run` set the changes are only returned. Applied
+/// changes are a single undoable operation.
+#[tauri::command]
+pub async fn reapply_trigger_rules(
+    app: AppHandle,
+    directory: String,
+    previous_tokens: Option<Vec<String>>,
+    dry_run: bool,
+) -> Result<BatchEditSummary, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let project_settings = settings::load_settings(&dir_path)?;
+
+    let changes = trigger::plan_reapply(
+        &dir_path,
+        &project_settings,
+        previous_tokens.as_deref().unwrap_or_default(),
+    )?;
+
+    if !dry_run && !changes.is_empty() {
+        backup_before_batch(&app, &dir_path, "trigger rules")?;
+        batch::apply_changes(
+            &dir_path,
+            &changes,
+            format!("Apply trigger rules to {} captions", changes.len()),
+        )?;
+    }
+
+    Ok(BatchEditSummary {
+        total_replacements: changes.len(),
+        changes,
+        applied: !dry_run,
+    })
+}
+
+/// Export the captions of a project as web alt text to an `alt-text.csv`
+///
+/// The file is written to `destination`, or to the project directory by default, with
+/// a `file,alt_text` row per captioned file. Returns the path of the written file.
+#[tauri::command]
+pub async fn export_alt_text(
+    app: AppHandle,
+    directory: String,
+    destination: Option<String>,
+) -> Result<String, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let destination = match destination {
+        Some(destination) => {
+            let destination = Path::new(&destination).to_path_buf();
+            if !destination.is_dir() {
+                return Err(format!(
+                    "Directory does not exist: {}",
+                    destination.display()
+                ));
+            }
+            destination
+        }
+        None => dir_path.clone(),
+    };
+
+    alt_text::write_csv(&dir_path, &destination)?;
+
+    Ok(destination
+        .join(alt_text::CSV_FILE)
+        .to_string_lossy()
+        .to_string())
+}
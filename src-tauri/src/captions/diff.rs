@@ -0,0 +1,324 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::batch::CaptionChange;
+use super::encoding;
+use super::format::CaptionStore;
+use super::import::{read_caption_set, MediaIndex};
+use crate::file_system::commands::{scan_media_files, MediaFile};
+use crate::jobs::manager::Job;
+
+/// Opens the project's side of a merge conflict written into a caption
+const MARKER_OURS: &str = "<<<<<<< ours";
+/// Separates the two sides of a merge conflict
+const MARKER_SEPARATOR: &str = "=======";
+/// Closes the other side of a merge conflict
+const MARKER_THEIRS: &str = ">>>>>>> theirs";
+
+/// How the captions of a media file differ between a project and another caption set
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffStatus {
+    /// Only the project has a caption
+    OnlyOurs,
+    /// Only the other caption set has one
+    OnlyTheirs,
+    Changed,
+}
+
+/// A media file whose caption differs
+#[derive(Debug, Serialize)]
+pub struct CaptionDiffEntry {
+    pub path: String,
+    pub name: String,
+    pub status: DiffStatus,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+/// Caption-level differences between a project and another caption set
+#[derive(Debug, Serialize)]
+pub struct CaptionDiff {
+    pub entries: Vec<CaptionDiffEntry>,
+    /// Media files with the same caption on both sides
+    pub unchanged: usize,
+    /// Captions of the other set that matched no media file of the project, or several
+    pub unmatched: Vec<String>,
+}
+
+/// Result of merging another caption set into a project; when `applied` is false
+/// nothing was written (dry run)
+#[derive(Debug, Serialize)]
+pub struct CaptionMerge {
+    pub changes: Vec<CaptionChange>,
+    pub applied: bool,
+    /// Media files whose caption now holds conflict markers, to be resolved by hand
+    pub conflicts: Vec<String>,
+    pub unmatched: Vec<String>,
+}
+
+/// Captions of a caption set by the path of the project media file they belong to
+fn captions_by_media(
+    media_index: &MediaIndex,
+    source: &Path,
+    unmatched: &mut Vec<String>,
+) -> Result<HashMap<String, String>, String> {
+    let mut captions = HashMap::new();
+    for caption in read_caption_set(source)? {
+        let text = encoding::normalize_line_endings(caption.text.trim());
+        match media_index.find(&caption.key) {
+            // A second caption for the same media file, e.g. both a `.txt` and a `.json`
+            Some(file) if !captions.contains_key(&file.path) => {
+                captions.insert(file.path.clone(), text);
+            }
+            _ => unmatched.push(caption.path),
+        }
+    }
+    Ok(captions)
+}
+
+/// A project's caption of a media file, trimmed, or None if it has none
+fn read_ours(store: &mut CaptionStore, file: &MediaFile) -> Result<Option<String>, String> {
+    Ok(store
+        .read(Path::new(&file.path))?
+        .map(|caption| caption.trim().to_string())
+        .filter(|caption| !caption.is_empty()))
+}
+
+/// Compare the captions of a project with those of another project, or of a folder or
+/// ZIP archive of caption files
+///
+/// The other captions match media files like an import does, by relative path or by
+/// file name.
+pub fn diff_captions(job: &Job, project: &Path, other: &Path) -> Result<CaptionDiff, String> {
+    let media = scan_media_files(project)?;
+    let media_index = MediaIndex::new(&media);
+    let mut unmatched = Vec::new();
+    let theirs = captions_by_media(&media_index, other, &mut unmatched)?;
+
+    let mut store = CaptionStore::open(project);
+    let mut entries = Vec::new();
+    let mut unchanged = 0;
+    let total = media.len() as u64;
+    for (index, file) in media.iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some(file.name.clone()));
+
+        let ours = read_ours(&mut store, file)?;
+        let theirs = theirs
+            .get(&file.path)
+            .filter(|caption| !caption.is_empty())
+            .cloned();
+        let status = match (&ours, &theirs) {
+            (None, None) => continue,
+            (Some(ours), Some(theirs)) if ours == theirs => {
+                unchanged += 1;
+                continue;
+            }
+            (Some(_), None) => DiffStatus::OnlyOurs,
+            (None, Some(_)) => DiffStatus::OnlyTheirs,
+            (Some(_), Some(_)) => DiffStatus::Changed,
+        };
+        entries.push(CaptionDiffEntry {
+            path: file.path.clone(),
+            name: file.name.clone(),
+            status,
+            ours,
+            theirs,
+        });
+    }
+
+    job.progress(total, total, None);
+    Ok(CaptionDiff {
+        entries,
+        unchanged,
+        unmatched,
+    })
+}
+
+/// Both sides of a conflict, between markers like those of a git merge
+fn conflict_text(ours: &str, theirs: &str) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        MARKER_OURS, ours, MARKER_SEPARATOR, theirs, MARKER_THEIRS
+    )
+}
+
+/// Check whether a caption still holds unresolved conflict markers
+fn has_conflict_markers(caption: &str) -> bool {
+    caption.lines().any(|line| line == MARKER_OURS) && caption.contains(MARKER_THEIRS)
+}
+
+/// Outcome of the three-way merge of one caption
+#[derive(Debug, PartialEq, Eq)]
+enum MergedCaption {
+    /// The project's caption stays as it is
+    Keep,
+    /// The other side's caption replaces it
+    Take(String),
+    /// Both sides between conflict markers, to be resolved by hand
+    Conflict(String),
+}
+
+/// Merge the other side's caption of a media file into the project's
+///
+/// Captions are compared trimmed, and an empty caption counts as none.
+fn merge_caption(ours: Option<&str>, theirs: &str, base: Option<&str>) -> MergedCaption {
+    let ours = ours.map(str::trim).filter(|caption| !caption.is_empty());
+    let theirs = theirs.trim();
+    let base = base.map(str::trim).filter(|caption| !caption.is_empty());
+
+    match ours {
+        _ if theirs.is_empty() => MergedCaption::Keep,
+        Some(ours) if ours == theirs => MergedCaption::Keep,
+        // Only our side changed it
+        _ if base == Some(theirs) => MergedCaption::Keep,
+        // Only their side changed it
+        ours if ours == base => MergedCaption::Take(theirs.to_string()),
+        // Already merged, with the conflict left for review
+        Some(ours) if has_conflict_markers(ours) => MergedCaption::Keep,
+        Some(ours) => MergedCaption::Conflict(conflict_text(ours, theirs)),
+        None => MergedCaption::Take(theirs.to_string()),
+    }
+}
+
+/// Compute the captions changed by a three-way merge of another caption set into a
+/// project, e.g. after two people each labeled half of a copy of the dataset
+///
+/// `base` is the caption set both sides started from, such as a captions-only export
+/// made before the work was split. A caption only one side changed takes that side's
+/// version; a caption both sides changed differently gets both versions between
+/// conflict markers. Without a base, captions only the other side has are taken and
+/// any other difference is a conflict. Captions the other side removed are kept.
+pub fn plan_three_way(
+    job: &Job,
+    project: &Path,
+    theirs: &Path,
+    base: Option<&Path>,
+) -> Result<CaptionMerge, String> {
+    let media = scan_media_files(project)?;
+    let media_index = MediaIndex::new(&media);
+    let mut unmatched = Vec::new();
+    let theirs = captions_by_media(&media_index, theirs, &mut unmatched)?;
+    let base = match base {
+        Some(base) => captions_by_media(&media_index, base, &mut Vec::new())?,
+        None => HashMap::new(),
+    };
+
+    let mut store = CaptionStore::open(project);
+    let mut changes = Vec::new();
+    let mut conflicts = Vec::new();
+    let total = media.len() as u64;
+    for (index, file) in media.iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some(file.name.clone()));
+
+        let Some(their_caption) = theirs.get(&file.path) else {
+            continue;
+        };
+        let before = store.read(Path::new(&file.path))?.unwrap_or_default();
+        let base_caption = base.get(&file.path).map(String::as_str);
+
+        let after = match merge_caption(Some(&before), their_caption, base_caption) {
+            MergedCaption::Keep => continue,
+            MergedCaption::Take(caption) => caption,
+            MergedCaption::Conflict(caption) => {
+                conflicts.push(file.name.clone());
+                caption
+            }
+        };
+        changes.push(CaptionChange {
+            path: file.path.clone(),
+            name: file.name.clone(),
+            before,
+            after,
+        });
+    }
+
+    job.progress(total, total, None);
+    Ok(CaptionMerge {
+        changes,
+        applied: false,
+        conflicts,
+        unmatched,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_edits_made_on_one_side_only() {
+        // Each side edited a different caption
+        assert_eq!(
+            merge_caption(Some("a cat"), "a black cat", Some("a cat")),
+            MergedCaption::Take("a black cat".to_string())
+        );
+        assert_eq!(
+            merge_caption(Some("a dog on grass"), "a dog", Some("a dog")),
+            MergedCaption::Keep
+        );
+    }
+
+    #[test]
+    fn keeps_identical_edits() {
+        assert_eq!(
+            merge_caption(Some("a black cat"), "a black cat", Some("a cat")),
+            MergedCaption::Keep
+        );
+    }
+
+    #[test]
+    fn marks_edits_made_on_both_sides_as_conflicts() {
+        let merged = merge_caption(Some("a black cat"), "a white cat", Some("a cat"));
+        assert_eq!(
+            merged,
+            MergedCaption::Conflict(
+                "<<<<<<< ours\na black cat\n=======\na white cat\n>>>>>>> theirs".to_string()
+            )
+        );
+
+        // Merging again leaves the conflict for review
+        let MergedCaption::Conflict(caption) = merged else {
+            unreachable!()
+        };
+        assert_eq!(
+            merge_caption(Some(&caption), "a white cat", Some("a cat")),
+            MergedCaption::Keep
+        );
+    }
+
+    #[test]
+    fn merges_without_a_base() {
+        assert_eq!(
+            merge_caption(None, "a cat", None),
+            MergedCaption::Take("a cat".to_string())
+        );
+        assert_eq!(
+            merge_caption(Some(""), "a cat", Some("")),
+            MergedCaption::Take("a cat".to_string())
+        );
+        assert!(matches!(
+            merge_caption(Some("a dog"), "a cat", None),
+            MergedCaption::Conflict(_)
+        ));
+    }
+
+    #[test]
+    fn ignores_trailing_newlines() {
+        assert_eq!(
+            merge_caption(Some("a cat\n"), "a cat", None),
+            MergedCaption::Keep
+        );
+        assert_eq!(
+            merge_caption(Some("a cat"), "a dog\n", Some("a cat\n")),
+            MergedCaption::Take("a dog".to_string())
+        );
+        assert_eq!(
+            merge_caption(Some("a cat"), "\n", None),
+            MergedCaption::Keep
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Guess whether BOM-less text is UTF-16
+///
+/// UTF-16 text from Notepad is mostly ASCII, so every other byte is zero. Such text
+/// is also valid UTF-8, which is why this has to be checked first.
+fn guess_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let pairs = bytes.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+
+    let even_zeros = bytes.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_zeros = bytes.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+
+    if odd_zeros * 2 > pairs && even_zeros * 10 < pairs {
+        return Some(UTF_16LE);
+    }
+    if even_zeros * 2 > pairs && odd_zeros * 10 < pairs {
+        return Some(UTF_16BE);
+    }
+    None
+}
+
+/// Decode caption bytes to a string, whatever editor saved them
+///
+/// Handles UTF-8 with or without a BOM, UTF-16 (LE/BE, with or without a BOM) and
+/// Latin-1/Windows-1252. The BOM is never part of the result.
+pub fn decode_caption(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+        return text.into_owned();
+    }
+
+    if let Some(encoding) = guess_utf16(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(bytes);
+        return text.into_owned();
+    }
+
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+
+    // Anything else is treated as Windows-1252, a superset of Latin-1
+    let (text, _) = WINDOWS_1252.decode_without_bom_handling(bytes);
+    text.into_owned()
+}
+
+/// Read a caption file as UTF-8 text, transcoding it if necessary
+pub fn read_caption_text(path: &Path) -> io::Result<String> {
+    fs::read(path).map(|bytes| decode_caption(&bytes))
+}
+
+/// Normalize Windows (`\r\n`) and classic Mac (`\r`) line endings to `\n`
+pub fn normalize_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decodes_utf16le_without_bom() {
+        let bytes = utf16("a cat, sitting", false);
+        assert_eq!(decode_caption(&bytes), "a cat, sitting");
+    }
+
+    #[test]
+    fn decodes_utf16be_without_bom() {
+        let bytes = utf16("a cat, sitting", true);
+        assert_eq!(decode_caption(&bytes), "a cat, sitting");
+    }
+
+    #[test]
+    fn decodes_utf8_and_latin1() {
+        assert_eq!(decode_caption("café".as_bytes()), "café");
+        assert_eq!(decode_caption(b"caf\xe9"), "café");
+    }
+
+    #[test]
+    fn strips_bom() {
+        assert_eq!(decode_caption(b"\xef\xbb\xbfcat"), "cat");
+    }
+}
@@ -0,0 +1,683 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::encoding;
+use crate::file_system::commands::{scan_media_files, MediaFile};
+use crate::file_system::{atomic, paths};
+use crate::project::{project_root_for, settings, PROJECT_FILE};
+
+/// Name of the unified caption manifest stored in the project root
+pub const MANIFEST_FILE: &str = "captions.json";
+
+/// Marks the JSON sidecar of a media file in the project root whose plain sidecar name
+/// is taken by the project settings or a manifest: `project.png` keeps its caption in
+/// `project.caption.json`
+const RESERVED_MARKER: &str = "caption";
+
+/// How captions are stored next to the media of a project
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionFormat {
+    /// `image.txt` plain text sidecars (default)
+    #[default]
+    Txt,
+    /// `image.caption` plain text sidecars
+    Caption,
+    /// `image.json` sidecars with a `caption` field
+    Json,
+    /// A single `captions.json` mapping file names to captions
+    Manifest,
+    /// A single `captions.jsonl` with a `{"file_name", "caption"}` object per line
+    Jsonl,
+}
+
+/// One line of a JSONL caption manifest
+#[derive(Serialize, Deserialize)]
+struct ManifestLine {
+    file_name: String,
+    caption: String,
+}
+
+impl CaptionFormat {
+    /// Every caption format
+    pub const ALL: [CaptionFormat; 5] = [
+        CaptionFormat::Txt,
+        CaptionFormat::Caption,
+        CaptionFormat::Json,
+        CaptionFormat::Manifest,
+        CaptionFormat::Jsonl,
+    ];
+
+    /// Extension of the per-file sidecar, or None for the unified manifest
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            CaptionFormat::Txt => Some("txt"),
+            CaptionFormat::Caption => Some("caption"),
+            CaptionFormat::Json => Some("json"),
+            CaptionFormat::Manifest | CaptionFormat::Jsonl => None,
+        }
+    }
+
+    /// Whether captions are kept in a single manifest rather than in sidecars
+    pub fn is_manifest(self) -> bool {
+        self.extension().is_none()
+    }
+
+    /// Name of the manifest holding the captions of a language, or None for sidecars
+    pub fn manifest_file(self, language: Option<&str>) -> Option<String> {
+        let extension = match self {
+            CaptionFormat::Manifest => "json",
+            CaptionFormat::Jsonl => "jsonl",
+            _ => return None,
+        };
+        Some(match language {
+            Some(language) => format!("captions.{}.{}", language, extension),
+            None => format!("captions.{}", extension),
+        })
+    }
+}
+
+/// Check whether a file name is a caption manifest: `captions.json`, `captions.jsonl`
+/// or one of their languages like `captions.en.json`
+pub fn is_manifest_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let Some(rest) = name.strip_prefix("captions.") else {
+        return false;
+    };
+    match rest
+        .strip_suffix(".jsonl")
+        .or_else(|| rest.strip_suffix(".json"))
+    {
+        Some(language) => validate_language(language).is_ok(),
+        None => rest == "json" || rest == "jsonl",
+    }
+}
+
+/// Check whether a file could hold captions in any format: a sidecar or a manifest
+pub fn is_caption_file(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    CaptionFormat::ALL
+        .iter()
+        .any(|format| format.extension() == Some(extension.as_str()))
+        || is_manifest_name(&name)
+}
+
+fn is_jsonl(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case("jsonl"))
+}
+
+/// Parse a caption manifest: a JSON object mapping file names to captions, or for a
+/// `.jsonl` manifest one `{"file_name", "caption"}` object per line
+pub fn parse_manifest(path: &Path, content: &str) -> Result<BTreeMap<String, String>, String> {
+    if !is_jsonl(path) {
+        return serde_json::from_str(content).map_err(|e| e.to_string());
+    }
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str::<ManifestLine>(line)
+                .map(|entry| (entry.file_name, entry.caption))
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+/// Serialize a caption manifest in the format its file name calls for
+pub fn serialize_manifest(
+    path: &Path,
+    manifest: &BTreeMap<String, String>,
+) -> Result<String, String> {
+    if !is_jsonl(path) {
+        return serde_json::to_string_pretty(manifest).map_err(|e| e.to_string());
+    }
+
+    let mut content = String::new();
+    for (file_name, caption) in manifest {
+        let line = ManifestLine {
+            file_name: file_name.clone(),
+            caption: caption.clone(),
+        };
+        content.push_str(&serde_json::to_string(&line).map_err(|e| e.to_string())?);
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+/// Check a caption language code: a two-letter language, optionally followed by
+/// subtags like a region or script (`en`, `ja`, `pt-BR`, `zh-Hans`)
+pub fn validate_language(language: &str) -> Result<(), String> {
+    let mut parts = language.split('-');
+    let primary = parts.next().unwrap_or_default();
+    let valid = primary.len() == 2
+        && primary.chars().all(|c| c.is_ascii_lowercase())
+        && parts
+            .all(|p| (2..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid caption language: {}", language))
+    }
+}
+
+/// Result of converting a project's captions to another format
+#[derive(Debug, Serialize)]
+pub struct ConversionSummary {
+    pub converted: usize,
+    pub skipped: usize,
+}
+
+/// Reads and writes captions of one project in its configured format
+///
+/// The manifest is loaded lazily and written back on every change, so a store can be
+/// kept around for a whole batch operation. All writes are atomic.
+pub struct CaptionStore {
+    project: PathBuf,
+    format: CaptionFormat,
+    manifest: Option<BTreeMap<String, String>>,
+    sync: bool,
+    /// Language of the captions read and written, None for the primary captions
+    language: Option<String>,
+}
+
+impl CaptionStore {
+    /// Open the caption store of a project using the format from its settings
+    pub fn open(project: &Path) -> Self {
+        let format = settings::load_settings(project)
+            .map(|s| s.caption_format)
+            .unwrap_or_default();
+        Self::with_format(project, format)
+    }
+
+    /// Open the caption store of a project with an explicit format
+    pub fn with_format(project: &Path, format: CaptionFormat) -> Self {
+        Self {
+            project: project.to_path_buf(),
+            format,
+            manifest: None,
+            sync: false,
+            language: None,
+        }
+    }
+
+    /// Flush every caption write to disk before returning
+    pub fn with_sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Read and write the captions of a language (`image.en.txt`, `captions.en.json`)
+    /// instead of the primary ones; the code must pass [`validate_language`]
+    pub fn with_language(mut self, language: Option<&str>) -> Self {
+        if self.language.as_deref() != language {
+            self.language = language.map(str::to_string);
+            self.manifest = None;
+        }
+        self
+    }
+
+    /// Open the caption store of the project that owns a media file
+    pub fn for_media(media: &Path) -> Self {
+        Self::open(&project_root_for(media))
+    }
+
+    pub fn format(&self) -> CaptionFormat {
+        self.format
+    }
+
+    /// Path of the file holding the caption of a media file
+    ///
+    /// A sidecar never takes the name of the project settings or a manifest; see
+    /// [`RESERVED_MARKER`].
+    pub fn caption_path(&self, media: &Path) -> PathBuf {
+        let Some(ext) = self.format.extension() else {
+            return self.manifest_path();
+        };
+        let suffix = match &self.language {
+            Some(language) => format!("{}.{}", language, ext),
+            None => ext.to_string(),
+        };
+
+        let path = media.with_extension(&suffix);
+        if self.is_reserved(&path) {
+            media.with_extension(format!("{}.{}", RESERVED_MARKER, suffix))
+        } else {
+            path
+        }
+    }
+
+    /// Check whether a path is the project settings or one of the manifests
+    fn is_reserved(&self, path: &Path) -> bool {
+        let in_root = path.parent().is_some_and(|parent| {
+            paths::display_path(parent) == paths::display_path(&self.project)
+        });
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        in_root && (name.eq_ignore_ascii_case(PROJECT_FILE) || is_manifest_name(&name))
+    }
+
+    /// Sidecar holding the caption of a media file, or None when captions are kept in a
+    /// manifest
+    pub fn sidecar_path(&self, media: &Path) -> Option<PathBuf> {
+        self.format.extension().map(|_| self.caption_path(media))
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        let name = self
+            .format
+            .manifest_file(self.language.as_deref())
+            .unwrap_or_else(|| MANIFEST_FILE.to_string());
+        self.project.join(name)
+    }
+
+    /// Key of a media file in the manifest: its path relative to the project
+    fn manifest_key(&self, media: &Path) -> String {
+        // Compare without extended-length prefixes, which either path may carry
+        let media = PathBuf::from(paths::display_path(media));
+        let project = PathBuf::from(paths::display_path(&self.project));
+
+        media
+            .strip_prefix(&project)
+            .unwrap_or(&media)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    fn manifest(&mut self) -> Result<&mut BTreeMap<String, String>, String> {
+        if self.manifest.is_none() {
+            let path = self.manifest_path();
+            let manifest = if path.exists() {
+                let content = encoding::read_caption_text(&path)
+                    .map_err(|e| format!("Failed to read caption manifest: {}", e))?;
+                parse_manifest(&path, &content)
+                    .map_err(|e| format!("Failed to parse caption manifest: {}", e))?
+            } else {
+                BTreeMap::new()
+            };
+            self.manifest = Some(manifest);
+        }
+
+        Ok(self.manifest.get_or_insert_with(BTreeMap::new))
+    }
+
+    fn save_manifest(&mut self) -> Result<(), String> {
+        let path = self.manifest_path();
+        let content = serialize_manifest(&path, self.manifest()?)
+            .map_err(|e| format!("Failed to serialize caption manifest: {}", e))?;
+        atomic::write_atomic(&path, content, self.sync)
+            .map_err(|e| format!("Failed to write caption manifest: {}", e))
+    }
+
+    /// Check whether a media file has a caption
+    pub fn has_caption(&mut self, media: &Path) -> bool {
+        match self.format {
+            CaptionFormat::Manifest | CaptionFormat::Jsonl => {
+                let key = self.manifest_key(media);
+                self.manifest()
+                    .map(|m| m.contains_key(&key))
+                    .unwrap_or(false)
+            }
+            _ => self.caption_path(media).exists(),
+        }
+    }
+
+    /// Read the caption of a media file, or None if it has none
+    pub fn read(&mut self, media: &Path) -> Result<Option<String>, String> {
+        match self.format {
+            CaptionFormat::Txt | CaptionFormat::Caption => {
+                let path = self.caption_path(media);
+                if !path.exists() {
+                    return Ok(None);
+                }
+                encoding::read_caption_text(&path)
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            }
+            CaptionFormat::Json => {
+                let path = self.caption_path(media);
+                if !path.exists() {
+                    return Ok(None);
+                }
+                let content = encoding::read_caption_text(&path).map_err(|e| e.to_string())?;
+                let value: serde_json::Value = serde_json::from_str(&content)
+                    .map_err(|e| format!("Failed to parse caption file: {}", e))?;
+                Ok(value
+                    .get("caption")
+                    .and_then(|c| c.as_str())
+                    .map(|c| c.to_string()))
+            }
+            CaptionFormat::Manifest | CaptionFormat::Jsonl => {
+                let key = self.manifest_key(media);
+                Ok(self.manifest()?.get(&key).cloned())
+            }
+        }
+    }
+
+    /// Write the caption of a media file, normalizing its line endings
+    pub fn write(&mut self, media: &Path, content: &str) -> Result<(), String> {
+        let content = &encoding::normalize_line_endings(content);
+
+        match self.format {
+            CaptionFormat::Txt | CaptionFormat::Caption => {
+                atomic::write_atomic(&self.caption_path(media), content, self.sync)
+                    .map_err(|e| e.to_string())
+            }
+            CaptionFormat::Json => {
+                let path = self.caption_path(media);
+
+                // Keep any other fields a pipeline stored in the sidecar
+                let mut value = encoding::read_caption_text(&path)
+                    .ok()
+                    .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok())
+                    .filter(|v| v.is_object())
+                    .unwrap_or_else(|| serde_json::json!({}));
+                value["caption"] = serde_json::Value::String(content.to_string());
+
+                let content = serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?;
+                atomic::write_atomic(&path, content, self.sync).map_err(|e| e.to_string())
+            }
+            CaptionFormat::Manifest | CaptionFormat::Jsonl => {
+                let key = self.manifest_key(media);
+                self.manifest()?.insert(key, content.to_string());
+                self.save_manifest()
+            }
+        }
+    }
+
+    /// Remove the caption of a media file
+    pub fn remove(&mut self, media: &Path) -> Result<(), String> {
+        match self.format {
+            CaptionFormat::Manifest | CaptionFormat::Jsonl => {
+                let key = self.manifest_key(media);
+                if self.manifest()?.remove(&key).is_some() {
+                    self.save_manifest()?;
+                }
+                Ok(())
+            }
+            _ => {
+                let path = self.caption_path(media);
+                if path.exists() {
+                    fs::remove_file(path).map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Languages that have captions in a project, e.g. `["en", "ja"]`
+///
+/// Found from the `image.<language>.<ext>` sidecars next to the media, or the
+/// `captions.<language>.json(l)` manifests in the project root.
+pub fn languages(project: &Path) -> Result<Vec<String>, String> {
+    let mut languages = BTreeSet::new();
+    let add = |path: &Path, languages: &mut BTreeSet<String>| {
+        if let Some(language) = path.extension().map(|l| l.to_string_lossy().to_string()) {
+            if validate_language(&language).is_ok() {
+                languages.insert(language);
+            }
+        }
+    };
+
+    match CaptionStore::open(project).format().extension() {
+        None => {
+            for entry in fs::read_dir(project).map_err(|e| e.to_string())?.flatten() {
+                let path = entry.path();
+                let name = entry.file_name().to_string_lossy().to_string();
+                if is_manifest_name(&name) {
+                    add(&path.with_extension(""), &mut languages);
+                }
+            }
+        }
+        Some(ext) => {
+            let files = scan_media_files(project)?;
+            let stems: HashSet<PathBuf> = files
+                .iter()
+                .map(|f| Path::new(&f.path).with_extension(""))
+                .collect();
+            let dirs: HashSet<&Path> = files
+                .iter()
+                .filter_map(|f| Path::new(&f.path).parent())
+                .collect();
+
+            for dir in dirs {
+                for entry in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+                    let path = entry.path();
+                    if path.extension().is_some_and(|e| e == ext) {
+                        // image.en.txt belongs to image.png, captions.caption.en.json
+                        // to captions.png
+                        let tagged = path.with_extension("");
+                        let owner = tagged.with_extension("");
+                        let marked = owner.extension().is_some_and(|e| e == RESERVED_MARKER);
+                        if stems.contains(&owner)
+                            || (marked && stems.contains(&owner.with_extension("")))
+                        {
+                            add(&tagged, &mut languages);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(languages.into_iter().collect())
+}
+
+/// Make the captions of one language the primary captions of a project, e.g. in an
+/// export staging copy
+///
+/// Files without a caption in that language end up uncaptioned, and the captions of
+/// every other language are removed, so the result holds a single language.
+pub fn promote_language(directory: &Path, language: &str) -> Result<(), String> {
+    validate_language(language)?;
+    let files = scan_media_files(directory)?;
+    let mut primary = CaptionStore::open(directory);
+
+    let mut source = CaptionStore::open(directory).with_language(Some(language));
+    for file in &files {
+        let media = Path::new(&file.path);
+        match source.read(media)? {
+            Some(caption) => primary.write(media, &caption)?,
+            None => primary.remove(media)?,
+        }
+    }
+
+    for other in languages(directory)? {
+        let mut store = CaptionStore::open(directory).with_language(Some(&other));
+        remove_all(&mut store, &files)?;
+    }
+
+    Ok(())
+}
+
+/// Drop the captions of media files from the manifests of a project, in every
+/// language, e.g. for files left out of an export staging copy
+///
+/// Paths are relative to the project. Does nothing when captions are kept in sidecars.
+pub fn remove_from_manifests(directory: &Path, media: &HashSet<PathBuf>) -> Result<(), String> {
+    if !CaptionStore::open(directory).format().is_manifest() {
+        return Ok(());
+    }
+
+    let languages = languages(directory)?;
+    let languages = std::iter::once(None).chain(languages.iter().map(|l| Some(l.as_str())));
+    for language in languages {
+        let mut store = CaptionStore::open(directory).with_language(language);
+        if !store.manifest_path().exists() {
+            continue;
+        }
+
+        let keys: HashSet<String> = media
+            .iter()
+            .map(|file| store.manifest_key(&directory.join(file)))
+            .collect();
+        let manifest = store.manifest()?;
+        let count = manifest.len();
+        manifest.retain(|key, _| !keys.contains(key));
+        if manifest.len() != count {
+            store.save_manifest()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove every caption of a store: its sidecars, or its manifest
+fn remove_all(store: &mut CaptionStore, files: &[MediaFile]) -> Result<(), String> {
+    if store.format().is_manifest() {
+        let manifest = store.manifest_path();
+        if manifest.exists() {
+            fs::remove_file(manifest).map_err(|e| e.to_string())?;
+        }
+    } else {
+        for file in files {
+            store.remove(Path::new(&file.path))?;
+        }
+    }
+    Ok(())
+}
+
+/// Convert every caption of a project directory to another format
+///
+/// The captions of every language are converted along with the primary ones. When
+/// `remove_old` is set the previous sidecars (or manifests) are deleted once all
+/// captions have been written in the new format.
+pub fn convert_directory(
+    directory: &Path,
+    target: CaptionFormat,
+    remove_old: bool,
+) -> Result<ConversionSummary, String> {
+    let mut summary = ConversionSummary {
+        converted: 0,
+        skipped: 0,
+    };
+
+    if CaptionStore::open(directory).format() == target {
+        return Ok(summary);
+    }
+
+    let files = scan_media_files(directory)?;
+    let languages = languages(directory)?;
+    let languages = std::iter::once(None).chain(languages.iter().map(|l| Some(l.as_str())));
+
+    for language in languages {
+        let mut source = CaptionStore::open(directory).with_language(language);
+        let mut destination = CaptionStore::with_format(directory, target).with_language(language);
+
+        for file in &files {
+            let media = Path::new(&file.path);
+            match source.read(media)? {
+                Some(caption) => {
+                    destination.write(media, &caption)?;
+                    summary.converted += 1;
+                }
+                // Only missing primary captions count, translations are optional
+                None if language.is_none() => summary.skipped += 1,
+                None => {}
+            }
+        }
+
+        if remove_old {
+            remove_all(&mut source, &files)?;
+        }
+    }
+
+    let mut project_settings = settings::load_settings(directory)?;
+    project_settings.caption_format = target;
+    settings::save_settings(directory, &project_settings)?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_sidecars_leave_project_files_alone() {
+        let project = tempfile::tempdir().unwrap();
+        let store = CaptionStore::with_format(project.path(), CaptionFormat::Json);
+
+        let media = project.path().join("project.png");
+        assert_eq!(
+            store.caption_path(&media),
+            project.path().join("project.caption.json")
+        );
+        let media = project.path().join("captions.png");
+        assert_eq!(
+            store.caption_path(&media),
+            project.path().join("captions.caption.json")
+        );
+        let store = store.with_language(Some("en"));
+        assert_eq!(
+            store.caption_path(&media),
+            project.path().join("captions.caption.en.json")
+        );
+
+        // Only the project root holds the project files
+        let media = project.path().join("nested").join("project.png");
+        assert_eq!(
+            CaptionStore::with_format(project.path(), CaptionFormat::Json).caption_path(&media),
+            project.path().join("nested").join("project.json")
+        );
+    }
+
+    #[test]
+    fn jsonl_manifest_round_trips() {
+        let project = tempfile::tempdir().unwrap();
+        let media = project.path().join("cat.png");
+        let mut store = CaptionStore::with_format(project.path(), CaptionFormat::Jsonl);
+        store.write(&media, "a cat\non a mat").unwrap();
+
+        let content = fs::read_to_string(project.path().join("captions.jsonl")).unwrap();
+        assert_eq!(
+            content,
+            "{\"file_name\":\"cat.png\",\"caption\":\"a cat\\non a mat\"}\n"
+        );
+
+        let mut store = CaptionStore::with_format(project.path(), CaptionFormat::Jsonl);
+        assert_eq!(
+            store.read(&media).unwrap().as_deref(),
+            Some("a cat\non a mat")
+        );
+    }
+
+    #[test]
+    fn recognizes_manifest_names() {
+        assert!(is_manifest_name("captions.json"));
+        assert!(is_manifest_name("captions.jsonl"));
+        assert!(is_manifest_name("Captions.pt-BR.json"));
+        assert!(!is_manifest_name("captions.caption.json"));
+        assert!(!is_manifest_name("captions.txt"));
+    }
+
+    #[test]
+    fn recognizes_caption_files_of_every_format() {
+        for name in [
+            "a.txt",
+            "a.caption",
+            "a.JSON",
+            "captions.jsonl",
+            "captions.en.jsonl",
+        ] {
+            assert!(is_caption_file(Path::new(name)), "{}", name);
+        }
+        assert!(!is_caption_file(Path::new("a.png")));
+        assert!(!is_caption_file(Path::new("metadata.jsonl")));
+    }
+}
@@ -0,0 +1,329 @@
+use chrono::{Local, TimeZone};
+use git2::{Commit, Delta, DiffFormat, Oid, Repository, Signature, Sort, Tree};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use super::format::CaptionStore;
+use crate::file_system::atomic;
+use crate::file_system::commands::scan_media_files;
+use crate::project::{metadata_dir, settings, METADATA_DIR};
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Bare repository in the project's metadata directory; the project is its work tree
+const REPO_DIR: &str = "captions.git";
+
+/// A commit of a project's captions
+#[derive(Debug, Serialize)]
+pub struct CaptionCommit {
+    pub id: String,
+    pub summary: String,
+    pub timestamp: String,
+    pub files_changed: usize,
+}
+
+/// Changes to one caption file, as a unified diff
+#[derive(Debug, Serialize)]
+pub struct CaptionFileDiff {
+    pub path: String,
+    /// "added", "modified" or "deleted"
+    pub status: String,
+    pub patch: String,
+}
+
+fn git_error(e: git2::Error) -> String {
+    format!("Caption history error: {}", e.message())
+}
+
+fn signature() -> Result<Signature<'static>, String> {
+    Signature::now("SpaceCat", "spacecat@localhost").map_err(git_error)
+}
+
+/// Check whether a project keeps its captions in git
+pub fn is_initialized(project: &Path) -> bool {
+    project.join(METADATA_DIR).join(REPO_DIR).is_dir()
+}
+
+/// Open the caption repository of a project, creating it if needed
+///
+/// The repository is bare so nothing is added to the project itself; the project is
+/// set as its work tree on every open.
+fn open(project: &Path) -> Result<Repository, String> {
+    let dir = metadata_dir(project)?.join(REPO_DIR);
+    let repo = if dir.is_dir() {
+        Repository::open_bare(&dir).map_err(git_error)?
+    } else {
+        Repository::init_bare(&dir).map_err(git_error)?
+    };
+    repo.set_workdir(project, false).map_err(git_error)?;
+    Ok(repo)
+}
+
+/// Caption files of a project, relative to it with forward slashes
+fn caption_files(project: &Path) -> Result<Vec<String>, String> {
+    let store = CaptionStore::open(project);
+    if let Some(manifest) = store.format().manifest_file(None) {
+        let exists = project.join(&manifest).is_file();
+        return Ok(exists.then_some(manifest).into_iter().collect());
+    }
+
+    let mut files: Vec<String> = scan_media_files(project)?
+        .iter()
+        .map(|file| store.caption_path(Path::new(&file.path)))
+        .filter(|caption| caption.is_file())
+        .filter_map(|caption| {
+            caption
+                .strip_prefix(project)
+                .ok()
+                .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Tree of the captions as they are on disk now
+fn current_tree(repo: &Repository, project: &Path) -> Result<Oid, String> {
+    let mut index = repo.index().map_err(git_error)?;
+    index.clear().map_err(git_error)?;
+    for file in caption_files(project)? {
+        index.add_path(Path::new(&file)).map_err(git_error)?;
+    }
+    index.write().map_err(git_error)?;
+    index.write_tree().map_err(git_error)
+}
+
+fn head_commit(repo: &Repository) -> Option<Commit<'_>> {
+    repo.head().ok()?.peel_to_commit().ok()
+}
+
+fn parent_tree<'a>(commit: &Commit<'a>) -> Option<Tree<'a>> {
+    commit.parent(0).ok()?.tree().ok()
+}
+
+fn summary(commit: &Commit) -> String {
+    String::from_utf8_lossy(commit.summary_bytes().unwrap_or_default()).to_string()
+}
+
+fn describe(repo: &Repository, commit: &Commit) -> Result<CaptionCommit, String> {
+    let tree = commit.tree().map_err(git_error)?;
+    let diff = repo
+        .diff_tree_to_tree(parent_tree(commit).as_ref(), Some(&tree), None)
+        .map_err(git_error)?;
+    let timestamp = Local
+        .timestamp_opt(commit.time().seconds(), 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+
+    Ok(CaptionCommit {
+        id: commit.id().to_string(),
+        summary: summary(commit),
+        timestamp,
+        files_changed: diff.deltas().len(),
+    })
+}
+
+/// Commit the current captions of a project, creating its repository on first use;
+/// returns None if nothing changed since the last commit
+pub fn commit(project: &Path, message: &str) -> Result<Option<CaptionCommit>, String> {
+    let repo = open(project)?;
+    let tree = repo
+        .find_tree(current_tree(&repo, project)?)
+        .map_err(git_error)?;
+    let parent = head_commit(&repo);
+    if parent.as_ref().map(|p| p.tree_id()) == Some(tree.id()) {
+        return Ok(None);
+    }
+
+    let signature = signature()?;
+    let parents: Vec<&Commit> = parent.iter().collect();
+    let id = repo
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .map_err(git_error)?;
+    let commit = repo.find_commit(id).map_err(git_error)?;
+
+    describe(&repo, &commit).map(Some)
+}
+
+/// Commit the captions after a batch operation, if the project keeps them in git
+///
+/// The operation already happened, so a failed commit is only logged.
+pub fn commit_if_enabled(project: &Path, message: &str) {
+    let enabled = settings::load_settings(project)
+        .map(|s| s.caption_git)
+        .unwrap_or(false);
+    if !enabled {
+        return;
+    }
+
+    if let Err(e) = commit(project, message) {
+        warn!("Failed to commit captions of {}: {}", project.display(), e);
+    }
+}
+
+/// List the caption commits of a project, most recent first
+pub fn log(project: &Path, limit: usize) -> Result<Vec<CaptionCommit>, String> {
+    if !is_initialized(project) {
+        return Ok(Vec::new());
+    }
+
+    let repo = open(project)?;
+    if head_commit(&repo).is_none() {
+        return Ok(Vec::new());
+    }
+
+    let mut walk = repo.revwalk().map_err(git_error)?;
+    walk.push_head().map_err(git_error)?;
+    walk.set_sorting(Sort::TIME).map_err(git_error)?;
+
+    walk.take(limit)
+        .map(|id| {
+            let commit = repo
+                .find_commit(id.map_err(git_error)?)
+                .map_err(git_error)?;
+            describe(&repo, &commit)
+        })
+        .collect()
+}
+
+fn status_name(status: Delta) -> &'static str {
+    match status {
+        Delta::Added => "added",
+        Delta::Deleted => "deleted",
+        _ => "modified",
+    }
+}
+
+/// Diff the captions changed by a commit, or the uncommitted changes when `commit` is
+/// None
+pub fn diff(project: &Path, commit: Option<&str>) -> Result<Vec<CaptionFileDiff>, String> {
+    let repo = open(project)?;
+    let (old, new) = match commit {
+        Some(id) => {
+            let commit = repo
+                .find_commit(Oid::from_str(id).map_err(git_error)?)
+                .map_err(git_error)?;
+            (parent_tree(&commit), commit.tree().map_err(git_error)?)
+        }
+        None => {
+            let current = repo
+                .find_tree(current_tree(&repo, project)?)
+                .map_err(git_error)?;
+            let head = head_commit(&repo).and_then(|c| c.tree().ok());
+            (head, current)
+        }
+    };
+
+    let diff = repo
+        .diff_tree_to_tree(old.as_ref(), Some(&new), None)
+        .map_err(git_error)?;
+    let mut files: Vec<CaptionFileDiff> = Vec::new();
+    diff.print(DiffFormat::Patch, |delta, _hunk, line| {
+        let path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if files.last().map(|f| f.path != path).unwrap_or(true) {
+            files.push(CaptionFileDiff {
+                path,
+                status: status_name(delta.status()).to_string(),
+                patch: String::new(),
+            });
+        }
+
+        if let Some(file) = files.last_mut() {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                file.patch.push(line.origin());
+            }
+            file.patch
+                .push_str(&String::from_utf8_lossy(line.content()));
+        }
+        true
+    })
+    .map_err(git_error)?;
+
+    Ok(files)
+}
+
+/// Undo the caption changes of a commit and commit the result
+///
+/// Later changes to the same captions are kept; if they conflict with the revert,
+/// nothing is changed. Caption files are rewritten as one undoable operation.
+pub fn revert(project: &Path, id: &str) -> Result<Option<CaptionCommit>, String> {
+    let repo = open(project)?;
+    let target = repo
+        .find_commit(Oid::from_str(id).map_err(git_error)?)
+        .map_err(git_error)?;
+    let head = head_commit(&repo).ok_or_else(|| "The caption history is empty".to_string())?;
+
+    // Uncommitted edits would be silently overwritten otherwise
+    commit(project, "Save captions before revert")?;
+    let head = head_commit(&repo).unwrap_or(head);
+
+    let mut reverted = repo
+        .revert_commit(&target, &head, 0, None)
+        .map_err(git_error)?;
+    if reverted.has_conflicts() {
+        return Err(
+            "The captions changed since this commit; it can't be reverted cleanly".to_string(),
+        );
+    }
+    let tree = repo
+        .find_tree(reverted.write_tree_to(&repo).map_err(git_error)?)
+        .map_err(git_error)?;
+    let diff = repo
+        .diff_tree_to_tree(Some(&head.tree().map_err(git_error)?), Some(&tree), None)
+        .map_err(git_error)?;
+
+    let summary = summary(&target);
+    let mut operation = Operation::begin(
+        project,
+        OperationKind::CaptionOverwrite,
+        format!("Revert \"{}\"", summary),
+    )?;
+    let changes: Vec<(String, Option<Oid>)> = diff
+        .deltas()
+        .filter_map(|delta| {
+            let path = delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())?;
+            let blob = (delta.status() != Delta::Deleted).then(|| delta.new_file().id());
+            Some((path.to_string_lossy().to_string(), blob))
+        })
+        .collect();
+    for (path, _) in &changes {
+        operation.snapshot(&project.join(path))?;
+    }
+
+    for (path, blob) in &changes {
+        let file = project.join(path);
+        match blob {
+            Some(blob) => {
+                let blob = repo.find_blob(*blob).map_err(git_error)?;
+                atomic::write_atomic(&file, blob.content(), false)
+                    .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            }
+            None => {
+                if file.exists() {
+                    fs::remove_file(&file)
+                        .map_err(|e| format!("Failed to delete {}: {}", path, e))?;
+                }
+            }
+        }
+    }
+    operation.commit()?;
+
+    commit(project, &format!("Revert \"{}\"", summary))
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::format::CaptionStore;
+use crate::file_system::atomic;
+use crate::project::{metadata_dir, project_root_for, settings};
+
+const HISTORY_DIR: &str = "history";
+
+/// Number of versions kept per caption unless the project overrides it
+pub const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+/// A previous version of a caption
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptionVersion {
+    pub version: u32,
+    pub timestamp: String,
+    pub content: String,
+}
+
+/// All stored versions of one caption
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CaptionHistory {
+    path: String,
+    versions: Vec<CaptionVersion>,
+}
+
+/// Resolve the file a caption's history is kept for
+///
+/// Media paths map to their sidecar in sidecar-based projects, so the history is the
+/// same whether a caption was written through its media file or its caption file.
+/// Captions stored in the manifest are tracked by their media file.
+pub fn history_target(path: &Path) -> PathBuf {
+    let store = CaptionStore::for_media(path);
+    match store.format().extension() {
+        Some(ext) if path.extension().map(|e| e != ext).unwrap_or(true) => store.caption_path(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Location of the history file for a caption
+fn history_file(target: &Path) -> Result<PathBuf, String> {
+    let project = project_root_for(target);
+    let relative = target
+        .strip_prefix(&project)
+        .unwrap_or(target)
+        .to_string_lossy()
+        .replace(['/', '\\'], "__");
+
+    Ok(metadata_dir(&project)?
+        .join(HISTORY_DIR)
+        .join(format!("{}.json", relative)))
+}
+
+fn load_history(file: &Path) -> Result<CaptionHistory, String> {
+    if !file.exists() {
+        return Ok(CaptionHistory::default());
+    }
+
+    let content =
+        fs::read_to_string(file).map_err(|e| format!("Failed to read caption history: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse caption history: {}", e))
+}
+
+fn save_history(file: &Path, history: &CaptionHistory) -> Result<(), String> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let content = serde_json::to_string_pretty(history)
+        .map_err(|e| format!("Failed to serialize caption history: {}", e))?;
+    atomic::write_atomic(file, content, false)
+        .map_err(|e| format!("Failed to write caption history: {}", e))
+}
+
+/// Store the content a caption had before it was overwritten
+pub fn record_version(path: &Path, previous: &str) -> Result<(), String> {
+    let target = history_target(path);
+    let project = project_root_for(&target);
+    let limit = settings::load_settings(&project)
+        .ok()
+        .and_then(|s| s.caption_history_limit)
+        .unwrap_or(DEFAULT_HISTORY_LIMIT);
+
+    if limit == 0 {
+        return Ok(());
+    }
+
+    let file = history_file(&target)?;
+    let mut history = load_history(&file)?;
+
+    // Saving the same text twice in a row doesn't need a new version
+    if history.versions.last().map(|v| v.content.as_str()) == Some(previous) {
+        return Ok(());
+    }
+
+    let version = history.versions.last().map(|v| v.version + 1).unwrap_or(1);
+    history.path = target.to_string_lossy().to_string();
+    history.versions.push(CaptionVersion {
+        version,
+        timestamp: settings::now_timestamp(),
+        content: previous.to_string(),
+    });
+
+    if history.versions.len() > limit {
+        let excess = history.versions.len() - limit;
+        history.versions.drain(..excess);
+    }
+
+    save_history(&file, &history)
+}
+
+/// Get the stored versions of a caption, most recent first
+pub fn get_versions(path: &Path) -> Result<Vec<CaptionVersion>, String> {
+    let target = history_target(path);
+    let mut versions = load_history(&history_file(&target)?)?.versions;
+    versions.reverse();
+    Ok(versions)
+}
+
+/// Restore a stored version of a caption, keeping the current text as a new version
+pub fn restore_version(path: &Path, version: u32) -> Result<String, String> {
+    let target = history_target(path);
+    let history = load_history(&history_file(&target)?)?;
+    let content = history
+        .versions
+        .iter()
+        .find(|v| v.version == version)
+        .map(|v| v.content.clone())
+        .ok_or_else(|| format!("Caption version {} not found", version))?;
+
+    // Sidecar paths map onto themselves, so the store can write either kind of target
+    let mut store = CaptionStore::for_media(&target);
+    if let Some(current) = store.read(&target)? {
+        record_version(&target, &current)?;
+    }
+    store.write(&target, &content)?;
+
+    Ok(content)
+}
@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use zip::ZipArchive;
+
+use super::batch::CaptionChange;
+use super::encoding;
+use super::format::{self, CaptionStore};
+use crate::file_system::commands::{scan_media_files, MediaFile};
+use crate::jobs::manager::Job;
+use crate::project::{METADATA_DIR, PROJECT_FILE};
+
+/// Extensions of the caption sidecars an import picks up
+const CAPTION_EXTENSIONS: [&str; 4] = ["txt", "caption", "json", "jsonl"];
+
+/// What to do with an imported caption when the media file already has one
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionConflict {
+    /// Keep the project's caption (default)
+    #[default]
+    Skip,
+    /// Replace the project's caption; the replaced caption can be restored with undo
+    Overwrite,
+    /// Add the imported caption after the project's, unless it already contains it
+    Append,
+}
+
+/// Result of importing caption files into a project; when `applied` is false nothing
+/// was written (dry run)
+#[derive(Debug, Serialize)]
+pub struct CaptionImport {
+    pub changes: Vec<CaptionChange>,
+    pub applied: bool,
+    /// Media files that kept their caption because of the conflict policy
+    pub skipped: Vec<String>,
+    /// Imported captions that matched no media file of the project, or several
+    pub unmatched: Vec<String>,
+}
+
+/// An imported caption, keyed by its relative path without the extension
+pub(crate) struct ImportedCaption {
+    /// Where it was read from, for reports
+    pub path: String,
+    pub key: String,
+    pub text: String,
+}
+
+/// Turn a file of the import source into captions: a sidecar becomes one, a caption
+/// manifest one per entry
+fn parse_entry(relative: &str, bytes: &[u8], captions: &mut Vec<ImportedCaption>) {
+    let path = Path::new(relative);
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if name == PROJECT_FILE || !CAPTION_EXTENSIONS.contains(&extension.as_str()) {
+        return;
+    }
+    let text = encoding::decode_caption(bytes);
+
+    // A JSONL file can only be a manifest
+    if name == format::MANIFEST_FILE || extension == "jsonl" {
+        let Ok(manifest) = format::parse_manifest(path, &text) else {
+            return;
+        };
+        let dir = relative.strip_suffix(&name).unwrap_or_default();
+        for (media, caption) in manifest {
+            let media = format!("{}{}", dir, media);
+            captions.push(ImportedCaption {
+                key: strip_extension(&media),
+                path: format!("{} ({})", relative, media),
+                text: caption,
+            });
+        }
+        return;
+    }
+
+    let text = if extension == "json" {
+        let caption = serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .and_then(|value| value.get("caption")?.as_str().map(str::to_string));
+        match caption {
+            Some(caption) => caption,
+            // Any other JSON file isn't a caption
+            None => return,
+        }
+    } else {
+        text
+    };
+    captions.push(ImportedCaption {
+        path: relative.to_string(),
+        key: strip_extension(relative),
+        text,
+    });
+}
+
+fn strip_extension(relative: &str) -> String {
+    Path::new(relative)
+        .with_extension("")
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Read the caption files of a folder and its subfolders, app data aside
+fn read_folder(root: &Path, dir: &Path, captions: &mut Vec<ImportedCaption>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        if path.is_dir() {
+            if entry.file_name() != METADATA_DIR {
+                read_folder(root, &path, captions)?;
+            }
+            continue;
+        }
+
+        let relative = path
+            .strip_prefix(root)
+            .map_err(|_| "Failed to create relative path".to_string())?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let bytes = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", relative, e))?;
+        parse_entry(&relative, &bytes, captions);
+    }
+    Ok(())
+}
+
+/// Read the caption files of a ZIP archive
+fn read_archive(source: &Path, captions: &mut Vec<ImportedCaption>) -> Result<(), String> {
+    let file = fs::File::open(source).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("Not a valid ZIP archive: {}", e))?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read archive: {}", e))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let Some(relative) = entry.enclosed_name() else {
+            continue;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        if relative.split('/').any(|part| part == METADATA_DIR) {
+            continue;
+        }
+
+        let mut bytes = Vec::new();
+        entry
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Failed to read {}: {}", relative, e))?;
+        parse_entry(&relative, &bytes, captions);
+    }
+    Ok(())
+}
+
+/// Read the captions of a folder or ZIP archive: caption sidecars in any caption format
+/// and caption manifests, whatever the format of the project they go to
+pub(crate) fn read_caption_set(source: &Path) -> Result<Vec<ImportedCaption>, String> {
+    let mut captions = Vec::new();
+    if source.is_dir() {
+        read_folder(source, source, &mut captions)?;
+    } else {
+        read_archive(source, &mut captions)?;
+    }
+    if captions.is_empty() {
+        return Err(format!("No caption files found in {}", source.display()));
+    }
+    Ok(captions)
+}
+
+/// The media files of a project by relative path and by file name, both without their
+/// extension, to find the media file an imported caption belongs to
+pub(crate) struct MediaIndex<'a> {
+    by_path: HashMap<String, Vec<&'a MediaFile>>,
+    by_name: HashMap<String, Vec<&'a MediaFile>>,
+}
+
+impl<'a> MediaIndex<'a> {
+    pub fn new(media: &'a [MediaFile]) -> Self {
+        let mut by_path: HashMap<String, Vec<&MediaFile>> = HashMap::new();
+        let mut by_name: HashMap<String, Vec<&MediaFile>> = HashMap::new();
+        for file in media {
+            by_path
+                .entry(strip_extension(&file.relative_path))
+                .or_default()
+                .push(file);
+            by_name
+                .entry(strip_extension(&file.name))
+                .or_default()
+                .push(file);
+        }
+        Self { by_path, by_name }
+    }
+
+    /// The one media file an imported caption belongs to: the one at its relative
+    /// path, or else the only one with its file name, for sources packed inside another
+    /// folder
+    pub fn find(&self, key: &str) -> Option<&'a MediaFile> {
+        if let Some(files) = self.by_path.get(key) {
+            return match files.as_slice() {
+                [file] => Some(*file),
+                _ => None,
+            };
+        }
+
+        let name = key.rsplit('/').next().unwrap_or(key);
+        match self.by_name.get(name).map(Vec::as_slice) {
+            Some([file]) => Some(*file),
+            _ => None,
+        }
+    }
+}
+
+/// Compute the captions changed by importing the caption files of a folder or ZIP
+/// archive, e.g. captions reviewed by a collaborator
+///
+/// Sidecars in any caption format and caption manifests are read, whatever the
+/// project's format. Captions match media files by relative path, or by file name when
+/// only one media file has it; `policy` decides about media files that already have a
+/// caption. Nothing is written; the import comes back with `applied` unset.
+pub fn plan_import(
+    job: &Job,
+    project: &Path,
+    source: &Path,
+    policy: CaptionConflict,
+) -> Result<CaptionImport, String> {
+    let captions = read_caption_set(source)?;
+    let media = scan_media_files(project)?;
+    let media_index = MediaIndex::new(&media);
+
+    let mut store = CaptionStore::open(project);
+    let mut imported = HashSet::new();
+    let mut changes = Vec::new();
+    let mut skipped = Vec::new();
+    let mut unmatched = Vec::new();
+    let total = captions.len() as u64;
+    for (index, caption) in captions.iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some(caption.path.clone()));
+
+        // A second caption for the same media file, e.g. both a `.txt` and a `.json`
+        let file = match media_index.find(&caption.key) {
+            Some(file) if imported.insert(file.path.as_str()) => file,
+            _ => {
+                unmatched.push(caption.path.clone());
+                continue;
+            }
+        };
+        let text = encoding::normalize_line_endings(caption.text.trim());
+        if text.is_empty() {
+            continue;
+        }
+
+        let before = store.read(Path::new(&file.path))?.unwrap_or_default();
+        if before.trim() == text {
+            continue;
+        }
+        let after = if before.trim().is_empty() {
+            text
+        } else {
+            match policy {
+                CaptionConflict::Skip => {
+                    skipped.push(file.name.clone());
+                    continue;
+                }
+                CaptionConflict::Overwrite => text,
+                CaptionConflict::Append if before.contains(&text) => continue,
+                CaptionConflict::Append => format!("{}, {}", before.trim_end(), text),
+            }
+        };
+        changes.push(CaptionChange {
+            path: file.path.clone(),
+            name: file.name.clone(),
+            before,
+            after,
+        });
+    }
+
+    job.progress(total, total, None);
+    Ok(CaptionImport {
+        changes,
+        applied: false,
+        skipped,
+        unmatched,
+    })
+}
@@ -0,0 +1,18 @@
+pub mod alt_text;
+pub mod batch;
+pub mod booru;
+pub mod commands;
+pub mod diff;
+pub mod encoding;
+pub mod format;
+pub mod git;
+pub mod history;
+pub mod import;
+pub mod modified;
+pub mod orphans;
+pub mod postprocess;
+pub mod search;
+pub mod spelling;
+pub mod stats;
+pub mod taxonomy;
+pub mod trigger;
@@ -0,0 +1,50 @@
+use serde::Serialize;
+use std::path::Path;
+
+use super::encoding::normalize_line_endings;
+
+/// Error of a caption write refused because the caption changed on disk since the
+/// frontend read it, e.g. in a text editor, returned to the frontend as JSON with the
+/// caption as it is now so the edits aren't lost
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename = "caption_modified")]
+pub struct CaptionModified {
+    pub path: String,
+    /// The caption on disk, empty if it was deleted
+    pub current: String,
+    pub message: String,
+}
+
+/// Refuse to write `content` over a caption that is no longer the `expected` one the
+/// frontend last read
+///
+/// Without an expectation nothing is checked. A caption already changed to `content`
+/// isn't a conflict.
+pub fn ensure_unmodified(
+    path: &Path,
+    expected: Option<&str>,
+    current: Option<&str>,
+    content: &str,
+) -> Result<(), String> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let current = normalize_line_endings(current.unwrap_or_default());
+    if current == normalize_line_endings(expected) || current == content {
+        return Ok(());
+    }
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string());
+    let modified = CaptionModified {
+        path: path.display().to_string(),
+        message: format!(
+            "The caption of {} was modified outside the app since it was opened",
+            name
+        ),
+        current,
+    };
+    Err(serde_json::to_string(&modified).unwrap_or(modified.message))
+}
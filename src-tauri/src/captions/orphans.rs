@@ -0,0 +1,265 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::format::{self, CaptionStore};
+use super::spelling::edit_distance;
+use crate::file_system::commands::scan_media_files;
+use crate::project::{metadata_dir, PROJECT_FILE};
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Directory of the project metadata orphaned captions are archived to
+const ARCHIVE_DIR: &str = "orphaned-captions";
+
+/// A caption sidecar without a media file
+#[derive(Debug, Serialize)]
+pub struct OrphanCaption {
+    pub path: String,
+    pub name: String,
+    /// Uncaptioned media file whose name is close enough to be the caption's, if
+    /// exactly one is
+    pub suggested_media: Option<String>,
+}
+
+/// Captions and media of a project that lost their pair
+#[derive(Debug, Serialize)]
+pub struct OrphanReport {
+    pub orphan_captions: Vec<OrphanCaption>,
+    pub uncaptioned_media: Vec<String>,
+}
+
+/// What is done with orphaned captions
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanAction {
+    Delete,
+    /// Move into the project metadata, where exports don't pick them up
+    Archive,
+    /// Rename to the suggested media file's caption
+    Repair,
+}
+
+/// Outcome of dealing with orphaned captions
+#[derive(Debug, Default, Serialize)]
+pub struct OrphanCleanup {
+    pub deleted: Vec<String>,
+    pub archived: Vec<String>,
+    /// Captions given to a media file, as `caption -> media`
+    pub repaired: Vec<String>,
+    /// Captions without a suggested media file, which can't be re-paired
+    pub skipped: Vec<String>,
+}
+
+/// File name without its extension and without a caption language (`image.en.txt`)
+fn caption_base(path: &Path) -> Option<(String, Option<String>)> {
+    let stem = path.file_stem()?.to_string_lossy().to_string();
+    match stem.rsplit_once('.') {
+        Some((base, language)) if format::validate_language(language).is_ok() => {
+            Some((base.to_string(), Some(language.to_string())))
+        }
+        _ => Some((stem, None)),
+    }
+}
+
+/// A file stem reduced to what survives renames: lowercase letters and digits
+fn normalize(stem: &str) -> String {
+    stem.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The one uncaptioned media file whose name is closest to a caption's, if it is close
+/// enough and no other is as close
+fn suggest_media<'a>(base: &str, uncaptioned: &'a [(String, String)]) -> Option<&'a str> {
+    let base = normalize(base);
+    let limit = (base.chars().count() / 4).max(2);
+
+    let mut scored: Vec<(usize, &str)> = uncaptioned
+        .iter()
+        .filter_map(|(stem, name)| {
+            let stem = normalize(stem);
+            let distance = if stem.contains(&base) || base.contains(&stem) {
+                Some(stem.chars().count().abs_diff(base.chars().count()).min(1))
+            } else {
+                edit_distance(&base, &stem, limit)
+            };
+            distance.map(|distance| (distance, name.as_str()))
+        })
+        .collect();
+    scored.sort();
+
+    match scored.as_slice() {
+        [(best, name), rest @ ..] if rest.first().is_none_or(|(next, _)| next > best) => {
+            Some(*name)
+        }
+        _ => None,
+    }
+}
+
+/// Find the caption sidecars in a project's folder without a media file, and the media
+/// files without a caption
+///
+/// Projects keeping their captions in a manifest have no sidecars to orphan. Orphans
+/// get the uncaptioned media file they most likely belonged to suggested, for media
+/// renamed outside the app.
+pub fn find_orphans(project: &Path) -> Result<OrphanReport, String> {
+    let mut store = CaptionStore::open(project);
+    let media = scan_media_files(project)?;
+
+    let media_stems: HashSet<String> = media
+        .iter()
+        .filter_map(|file| {
+            Some(
+                Path::new(&file.name)
+                    .file_stem()?
+                    .to_string_lossy()
+                    .to_string(),
+            )
+        })
+        .collect();
+    let sidecars: HashSet<PathBuf> = media
+        .iter()
+        .filter_map(|file| store.sidecar_path(Path::new(&file.path)))
+        .collect();
+    let uncaptioned: Vec<(String, String)> = media
+        .iter()
+        .filter(|file| !store.has_caption(Path::new(&file.path)))
+        .filter_map(|file| {
+            let stem = Path::new(&file.name)
+                .file_stem()?
+                .to_string_lossy()
+                .to_string();
+            Some((stem, file.name.clone()))
+        })
+        .collect();
+
+    let mut orphan_captions = Vec::new();
+    if let Some(extension) = store.format().extension() {
+        for entry in fs::read_dir(project).map_err(|e| e.to_string())?.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_caption = path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case(extension));
+            if !is_caption
+                || name == PROJECT_FILE
+                || format::is_manifest_name(&name)
+                || sidecars.contains(&path)
+            {
+                continue;
+            }
+
+            let Some((base, _)) = caption_base(&path) else {
+                continue;
+            };
+            // `image.de.txt` may also be the caption of `image.de.png`
+            let stem = path.with_extension("");
+            let stem = stem.file_name().unwrap_or_default().to_string_lossy();
+            if media_stems.contains(&base) || media_stems.contains(stem.as_ref()) {
+                continue;
+            }
+            orphan_captions.push(OrphanCaption {
+                path: path.to_string_lossy().to_string(),
+                suggested_media: suggest_media(&base, &uncaptioned).map(str::to_string),
+                name,
+            });
+        }
+    }
+    orphan_captions.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(OrphanReport {
+        orphan_captions,
+        uncaptioned_media: uncaptioned.into_iter().map(|(_, name)| name).collect(),
+    })
+}
+
+/// Delete, archive or re-pair a project's orphaned captions, as one undoable operation
+///
+/// `names` limits the cleanup to some of the orphans; by default all are handled.
+/// Re-pairing gives each caption to its suggested media file, under the same caption
+/// language, and skips the rest.
+pub fn clean_up_orphans(
+    project: &Path,
+    action: OrphanAction,
+    names: Option<&[String]>,
+) -> Result<OrphanCleanup, String> {
+    let report = find_orphans(project)?;
+    let wanted: Option<HashSet<&str>> =
+        names.map(|names| names.iter().map(String::as_str).collect());
+    let orphans: Vec<OrphanCaption> = report
+        .orphan_captions
+        .into_iter()
+        .filter(|orphan| {
+            wanted
+                .as_ref()
+                .is_none_or(|wanted| wanted.contains(orphan.name.as_str()))
+        })
+        .collect();
+
+    let mut cleanup = OrphanCleanup::default();
+    if orphans.is_empty() {
+        return Ok(cleanup);
+    }
+
+    let (kind, description) = match action {
+        OrphanAction::Delete => (OperationKind::Delete, "Delete"),
+        OrphanAction::Archive => (OperationKind::Delete, "Archive"),
+        OrphanAction::Repair => (OperationKind::Rename, "Re-pair"),
+    };
+    let mut operation = Operation::begin(
+        project,
+        kind,
+        format!("{} {} orphaned captions", description, orphans.len()),
+    )?;
+    let archive_dir = metadata_dir(project)?
+        .join(ARCHIVE_DIR)
+        .join(Local::now().format("%Y%m%d_%H%M%S").to_string());
+
+    for orphan in orphans {
+        let path = PathBuf::from(&orphan.path);
+        match action {
+            OrphanAction::Delete => {
+                operation.snapshot_and_remove(&path)?;
+                cleanup.deleted.push(orphan.name);
+            }
+            OrphanAction::Archive => {
+                fs::create_dir_all(&archive_dir)
+                    .map_err(|e| format!("Failed to create caption archive: {}", e))?;
+                fs::copy(&path, archive_dir.join(&orphan.name))
+                    .map_err(|e| format!("Failed to archive {}: {}", orphan.name, e))?;
+                operation.snapshot_and_remove(&path)?;
+                cleanup.archived.push(orphan.name);
+            }
+            OrphanAction::Repair => {
+                let Some(media) = orphan.suggested_media else {
+                    cleanup.skipped.push(orphan.name);
+                    continue;
+                };
+                let language = caption_base(&path).and_then(|(_, language)| language);
+                let target = CaptionStore::open(project)
+                    .with_language(language.as_deref())
+                    .caption_path(&project.join(&media));
+                // Two orphans may suggest the same media file; the first one gets it
+                if target.exists() {
+                    cleanup.skipped.push(orphan.name);
+                    continue;
+                }
+
+                operation.snapshot(&target)?;
+                operation.snapshot(&path)?;
+                fs::rename(&path, &target)
+                    .map_err(|e| format!("Failed to re-pair {}: {}", orphan.name, e))?;
+                cleanup
+                    .repaired
+                    .push(format!("{} -> {}", orphan.name, media));
+            }
+        }
+    }
+
+    operation.commit()?;
+    Ok(cleanup)
+}
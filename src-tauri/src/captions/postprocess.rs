@@ -0,0 +1,29 @@
+use std::path::Path;
+use tracing::warn;
+
+use super::{taxonomy, trigger};
+use crate::project::{project_root_for, settings};
+
+/// Post-process a generated caption with the rules of the media file's project: its tag
+/// dictionary, then its trigger rules
+///
+/// Rules that can't be read are only logged, so generation never fails on them.
+pub fn apply(media_path: &Path, caption: String) -> String {
+    let project = project_root_for(media_path);
+
+    let caption = match taxonomy::load(&project) {
+        Ok(dictionary) => dictionary.apply(&caption),
+        Err(e) => {
+            warn!("Not applying the tag dictionary: {}", e);
+            caption
+        }
+    };
+
+    match settings::load_settings(&project).and_then(|s| trigger::apply(&s, caption.clone())) {
+        Ok(caption) => caption,
+        Err(e) => {
+            warn!("Not applying the trigger rules: {}", e);
+            caption
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::path::Path;
+use tracing::warn;
+
+use super::format::CaptionStore;
+use crate::file_system::commands::{scan_media_files, MediaFile};
+use crate::jobs::pool;
+use crate::project::index;
+
+/// Characters of context shown on each side of a match
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Maximum number of snippets returned per file
+const MAX_SNIPPETS: usize = 3;
+
+/// A piece of a snippet, highlighted when it is part of the match
+#[derive(Debug, Serialize)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlight: bool,
+}
+
+/// A media file whose caption matches a search
+#[derive(Debug, Serialize)]
+pub struct CaptionMatch {
+    pub file: MediaFile,
+    pub match_count: usize,
+    pub snippets: Vec<Vec<SnippetPart>>,
+}
+
+/// Build the search pattern; plain queries match literally, both ignore case
+pub fn build_pattern(query: &str, regex: bool) -> Result<Regex, String> {
+    let pattern = if regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {}", e))
+}
+
+/// Byte offset of the char boundary `chars` characters before `index`
+fn back_chars(text: &str, index: usize, chars: usize) -> usize {
+    text[..index]
+        .char_indices()
+        .rev()
+        .nth(chars.saturating_sub(1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// Byte offset of the char boundary `chars` characters after `index`
+fn forward_chars(text: &str, index: usize, chars: usize) -> usize {
+    text[index..]
+        .char_indices()
+        .nth(chars)
+        .map(|(i, _)| index + i)
+        .unwrap_or(text.len())
+}
+
+/// Cut a snippet around a match, split into plain and highlighted parts
+fn snippet(caption: &str, start: usize, end: usize) -> Vec<SnippetPart> {
+    let from = back_chars(caption, start, SNIPPET_CONTEXT);
+    let to = forward_chars(caption, end, SNIPPET_CONTEXT);
+
+    let mut parts = Vec::new();
+    let before = &caption[from..start];
+    if !before.is_empty() {
+        let prefix = if from > 0 { "…" } else { "" };
+        parts.push(SnippetPart {
+            text: format!("{}{}", prefix, before),
+            highlight: false,
+        });
+    }
+    parts.push(SnippetPart {
+        text: caption[start..end].to_string(),
+        highlight: true,
+    });
+    let after = &caption[end..to];
+    if !after.is_empty() {
+        let suffix = if to < caption.len() { "…" } else { "" };
+        parts.push(SnippetPart {
+            text: format!("{}{}", after, suffix),
+            highlight: false,
+        });
+    }
+
+    parts
+}
+
+/// Read the captions of all media files in a project
+///
+/// Captions come from the project index, which only reads those that changed. Without
+/// a usable index, sidecars are read in parallel and the manifest is loaded once.
+pub fn read_all_captions(directory: &Path) -> Result<Vec<(MediaFile, String)>, String> {
+    match index::captions(directory) {
+        Ok(captions) => return Ok(captions),
+        Err(e) => warn!("Reading captions without the project index: {}", e),
+    }
+
+    let files = scan_media_files(directory)?;
+    let format = CaptionStore::open(directory).format();
+
+    if format.is_manifest() {
+        let mut store = CaptionStore::with_format(directory, format);
+        let mut captions = Vec::new();
+        for file in files {
+            if let Some(caption) = store.read(Path::new(&file.path))? {
+                captions.push((file, caption));
+            }
+        }
+        return Ok(captions);
+    }
+
+    Ok(pool::install(|| {
+        files
+            .into_par_iter()
+            .filter_map(|file| {
+                let mut store = CaptionStore::with_format(directory, format);
+                match store.read(Path::new(&file.path)) {
+                    Ok(Some(caption)) => Some((file, caption)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }))
+}
+
+/// Find every media file of a project whose caption matches `pattern`
+pub fn search_directory(directory: &Path, pattern: &Regex) -> Result<Vec<CaptionMatch>, String> {
+    let captions = read_all_captions(directory)?;
+
+    let mut matches: Vec<CaptionMatch> = pool::install(|| {
+        captions
+            .into_par_iter()
+            .filter_map(|(file, caption)| {
+                let found: Vec<_> = pattern
+                    .find_iter(&caption)
+                    .filter(|m| !m.as_str().is_empty())
+                    .map(|m| (m.start(), m.end()))
+                    .collect();
+                if found.is_empty() {
+                    return None;
+                }
+
+                let snippets = found
+                    .iter()
+                    .take(MAX_SNIPPETS)
+                    .map(|&(start, end)| snippet(&caption, start, end))
+                    .collect();
+
+                Some(CaptionMatch {
+                    file,
+                    match_count: found.len(),
+                    snippets,
+                })
+            })
+            .collect()
+    });
+
+    matches.sort_by(|a, b| a.file.name.cmp(&b.file.name));
+
+    Ok(matches)
+}
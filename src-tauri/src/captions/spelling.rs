@@ -0,0 +1,486 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use super::batch::CaptionChange;
+use super::search::read_all_captions;
+use super::stats::split_tags;
+use super::taxonomy;
+use crate::file_system::atomic;
+use crate::file_system::commands::MediaFile;
+use crate::jobs::manager::Job;
+use crate::project::{metadata_dir, settings, METADATA_DIR};
+
+/// Name of the file in a project's metadata directory listing words the spell checker
+/// accepts, one per line
+const WORDS_FILE: &str = "words.txt";
+
+/// Word lists looked for when no dictionary is given: plain lists first, then
+/// Hunspell dictionaries, whose affix flags are ignored
+const SYSTEM_DICTIONARIES: &[&str] = &[
+    "/usr/share/dict/words",
+    "/usr/share/hunspell/en_US.dic",
+    "/usr/share/myspell/en_US.dic",
+    "/usr/share/myspell/dicts/en_US.dic",
+];
+
+/// Words used in at least this many captions are taken as the dataset's vocabulary
+/// (names, styles, trigger words) rather than typos
+const VOCABULARY_MIN_CAPTIONS: usize = 3;
+
+/// Words shorter than this aren't checked
+const MIN_WORD_LENGTH: usize = 3;
+
+/// Endings stripped from a word to find it in dictionaries without inflections
+const SUFFIXES: &[&str] = &["'s", "s", "es", "ed", "d", "ing", "ly", "er", "est"];
+
+/// What a caption issue is about
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    Spelling,
+    /// The same word twice in a row, e.g. "the the"
+    RepeatedWord,
+    /// Found by the optional language model pass, covering the whole caption
+    Grammar,
+}
+
+/// A problem found in a caption
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptionIssue {
+    pub kind: IssueKind,
+    /// Byte range of the flagged text in the caption
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    /// Replacement for the flagged text, used when fixing; None if there is no good guess
+    pub suggestion: Option<String>,
+}
+
+/// The issues found in one caption
+#[derive(Debug, Serialize)]
+pub struct CaptionLint {
+    pub path: String,
+    pub name: String,
+    pub caption: String,
+    pub issues: Vec<CaptionIssue>,
+}
+
+/// Result of checking the captions of a project
+#[derive(Debug, Default, Serialize)]
+pub struct LintReport {
+    /// Captions with issues, by file name
+    pub files: Vec<CaptionLint>,
+    /// Number of captions checked
+    pub checked: usize,
+    /// Words in the dictionary used; 0 when none was found and only the project's own
+    /// vocabulary was checked against
+    pub dictionary_words: usize,
+    /// Captions changed by fixing the issues that have a suggestion
+    pub changes: Vec<CaptionChange>,
+    /// Whether the changes were written
+    pub applied: bool,
+}
+
+/// OpenAI-compatible model used for the optional grammar pass
+#[derive(Debug, Deserialize)]
+pub struct GrammarModel {
+    pub api_url: String,
+    pub api_key: String,
+    pub model: String,
+}
+
+/// Checks words against a dictionary and the vocabulary of a project
+pub struct SpellChecker {
+    /// Lowercase words accepted as spelled correctly
+    known: HashSet<String>,
+    /// Dictionary words by first letter and length, where suggestions are looked for
+    buckets: HashMap<(char, usize), Vec<String>>,
+    /// Number of captions each lowercase word appears in
+    usage: HashMap<String, usize>,
+    dictionary_words: usize,
+}
+
+/// Split text into words with their byte ranges; apostrophes inside words are kept
+fn words(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let next_is_letter = chars.peek().is_some_and(|(_, n)| n.is_alphabetic());
+        let in_word = c.is_alphabetic() || (c == '\'' && start.is_some() && next_is_letter);
+        match (in_word, start) {
+            (true, None) => start = Some(index),
+            (false, Some(s)) => {
+                words.push((s, index, &text[s..index]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, text.len(), &text[s..]));
+    }
+
+    words
+}
+
+/// Edit distance with adjacent transpositions counting as one edit, giving up once it
+/// exceeds `limit`
+pub fn edit_distance(a: &str, b: &str, limit: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+
+    let mut previous: Vec<usize> = Vec::new();
+    let mut current: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let before = std::mem::replace(&mut previous, current.clone());
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(before[j - 2] + 1);
+            }
+            current[j] = distance;
+        }
+        if current.iter().min().is_some_and(|&d| d > limit) {
+            return None;
+        }
+    }
+
+    Some(current[b.len()]).filter(|&d| d <= limit)
+}
+
+/// Words of a dictionary file, from a plain list or a Hunspell `.dic`
+fn read_dictionary(path: &Path) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read dictionary {}: {}", path.display(), e))?;
+    let hunspell = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("dic"));
+
+    Ok(content
+        .lines()
+        // A Hunspell dictionary starts with its word count
+        .skip(usize::from(hunspell))
+        .map(|line| line.split('/').next().unwrap_or_default().trim())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect())
+}
+
+/// Words a project accepts beyond the dictionary
+pub fn project_words(project: &Path) -> Result<Vec<String>, String> {
+    let path = project.join(METADATA_DIR).join(WORDS_FILE);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read word list: {}", e))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Add words to the list a project accepts, returning the whole list
+pub fn add_project_words(project: &Path, words: &[String]) -> Result<Vec<String>, String> {
+    let mut list = project_words(project)?;
+    let mut seen: HashSet<String> = list.iter().map(|w| w.to_lowercase()).collect();
+    for word in words.iter().map(|w| w.trim()) {
+        if !word.is_empty() && seen.insert(word.to_lowercase()) {
+            list.push(word.to_string());
+        }
+    }
+    list.sort_by_key(|w| w.to_lowercase());
+
+    let mut content = list.join("\n");
+    content.push('\n');
+    atomic::write_atomic(&metadata_dir(project)?.join(WORDS_FILE), content, false)
+        .map_err(|e| format!("Failed to write word list: {}", e))?;
+    Ok(list)
+}
+
+impl SpellChecker {
+    /// Build a checker for a project's captions
+    ///
+    /// Words come from `dictionary` (or the first system word list found), the
+    /// project's word list, trigger word and tag dictionary, and the words its captions
+    /// use often.
+    pub fn load(
+        project: &Path,
+        dictionary: Option<&Path>,
+        captions: &[(MediaFile, String)],
+    ) -> Result<Self, String> {
+        let dictionary = match dictionary {
+            Some(path) => read_dictionary(path)?,
+            None => SYSTEM_DICTIONARIES
+                .iter()
+                .map(Path::new)
+                .find(|path| path.is_file())
+                .map(read_dictionary)
+                .transpose()?
+                .unwrap_or_default(),
+        };
+
+        let mut usage: HashMap<String, usize> = HashMap::new();
+        for (_, caption) in captions {
+            let unique: HashSet<String> = words(caption)
+                .into_iter()
+                .map(|(_, _, word)| word.to_lowercase())
+                .collect();
+            for word in unique {
+                *usage.entry(word).or_default() += 1;
+            }
+        }
+
+        let mut known: HashSet<String> = dictionary.iter().cloned().collect();
+        let mut buckets: HashMap<(char, usize), Vec<String>> = HashMap::new();
+        for word in dictionary.iter().chain(
+            usage
+                .iter()
+                .filter(|(_, &count)| count >= VOCABULARY_MIN_CAPTIONS)
+                .map(|(word, _)| word),
+        ) {
+            if let Some(first) = word.chars().next() {
+                buckets
+                    .entry((first, word.chars().count()))
+                    .or_default()
+                    .push(word.clone());
+            }
+        }
+        known.extend(
+            usage
+                .iter()
+                .filter(|(_, &count)| count >= VOCABULARY_MIN_CAPTIONS)
+                .map(|(word, _)| word.clone()),
+        );
+
+        // Project-specific words: accepted, but never suggested for other words
+        let settings = settings::load_settings(project)?;
+        let tags = taxonomy::load(project)?.known_tags();
+        let extra = project_words(project)?
+            .into_iter()
+            .chain(settings.trigger_word)
+            .chain(tags);
+        for text in extra {
+            known.extend(words(&text).into_iter().map(|(_, _, w)| w.to_lowercase()));
+        }
+
+        Ok(Self {
+            known,
+            buckets,
+            usage,
+            dictionary_words: dictionary.len(),
+        })
+    }
+
+    pub fn dictionary_words(&self) -> usize {
+        self.dictionary_words
+    }
+
+    fn is_known(&self, word: &str) -> bool {
+        if self.known.contains(word) {
+            return true;
+        }
+        // Inflections missing from dictionaries that only list stems
+        SUFFIXES.iter().any(|suffix| {
+            word.strip_suffix(suffix).is_some_and(|stem| {
+                stem.chars().count() >= MIN_WORD_LENGTH && self.known.contains(stem)
+            })
+        })
+    }
+
+    /// The closest known word, preferring words the project uses more often
+    fn suggest(&self, word: &str) -> Option<String> {
+        let first = word.chars().next()?;
+        let length = word.chars().count();
+        let limit = if length <= 4 { 1 } else { 2 };
+
+        (length.saturating_sub(limit)..=length + limit)
+            .filter_map(|l| self.buckets.get(&(first, l)))
+            .flatten()
+            .filter_map(|candidate| {
+                edit_distance(word, candidate, limit).map(|distance| (distance, candidate))
+            })
+            .min_by(|(a, x), (b, y)| {
+                let usage = |w: &String| self.usage.get(w).copied().unwrap_or(0);
+                a.cmp(b).then_with(|| usage(y).cmp(&usage(x)))
+            })
+            .map(|(_, candidate)| candidate.clone())
+    }
+
+    /// Find misspelled and repeated words in a caption
+    ///
+    /// Without a dictionary, a word is only flagged when it is rare in the project and
+    /// close to a word the project uses often, since anything else may be a name.
+    pub fn check(&self, caption: &str) -> Vec<CaptionIssue> {
+        let mut issues = Vec::new();
+        let words = words(caption);
+
+        for (index, &(start, end, word)) in words.iter().enumerate() {
+            let lower = word.to_lowercase();
+
+            // Only whitespace between the words, so "red, red" in tags isn't flagged
+            if let Some(&(previous_start, previous_end, previous)) =
+                index.checked_sub(1).map(|i| &words[i])
+            {
+                let between = &caption[previous_end..start];
+                if previous.to_lowercase() == lower
+                    && !between.is_empty()
+                    && between.chars().all(char::is_whitespace)
+                {
+                    issues.push(CaptionIssue {
+                        kind: IssueKind::RepeatedWord,
+                        start: previous_start,
+                        end,
+                        text: caption[previous_start..end].to_string(),
+                        suggestion: Some(previous.to_string()),
+                    });
+                    continue;
+                }
+            }
+
+            if word.chars().count() < MIN_WORD_LENGTH || self.is_known(&lower) {
+                continue;
+            }
+            // Acronyms and camel-case names
+            if word.chars().skip(1).any(char::is_uppercase) {
+                continue;
+            }
+
+            let suggestion = self.suggest(&lower).map(|s| match_case(word, &s));
+            if self.dictionary_words == 0 && suggestion.is_none() {
+                continue;
+            }
+
+            issues.push(CaptionIssue {
+                kind: IssueKind::Spelling,
+                start,
+                end,
+                text: word.to_string(),
+                suggestion,
+            });
+        }
+
+        issues
+    }
+}
+
+/// Capitalize a suggestion like the word it replaces
+fn match_case(word: &str, suggestion: &str) -> String {
+    if word.chars().next().is_some_and(char::is_uppercase) {
+        let mut chars = suggestion.chars();
+        chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        suggestion.to_string()
+    }
+}
+
+/// Apply the suggestions of a caption's issues
+///
+/// A language model correction replaces the whole caption, since it fixes spelling
+/// too; otherwise each suggested replacement is made, skipping overlapping ones.
+pub fn fix(caption: &str, issues: &[CaptionIssue]) -> String {
+    if let Some(corrected) = issues
+        .iter()
+        .find(|issue| issue.kind == IssueKind::Grammar)
+        .and_then(|issue| issue.suggestion.as_ref())
+    {
+        return corrected.clone();
+    }
+
+    let mut fixes: Vec<&CaptionIssue> = issues
+        .iter()
+        .filter(|issue| issue.suggestion.is_some())
+        .collect();
+    fixes.sort_by_key(|issue| issue.start);
+
+    let mut fixed = String::with_capacity(caption.len());
+    let mut position = 0;
+    for issue in fixes {
+        if issue.start < position {
+            continue;
+        }
+        fixed.push_str(&caption[position..issue.start]);
+        fixed.push_str(issue.suggestion.as_deref().unwrap_or_default());
+        position = issue.end;
+    }
+    fixed.push_str(&caption[position..]);
+    fixed
+}
+
+/// The caption changes fixing every issue that has a suggestion
+pub fn plan_fixes(files: &[CaptionLint]) -> Vec<CaptionChange> {
+    files
+        .iter()
+        .filter_map(|lint| {
+            let after = fix(&lint.caption, &lint.issues);
+            (after != lint.caption).then(|| CaptionChange {
+                path: lint.path.clone(),
+                name: lint.name.clone(),
+                before: lint.caption.clone(),
+                after,
+            })
+        })
+        .collect()
+}
+
+/// Check the spelling of every caption of a project
+///
+/// All captions are listed in the report, including those without issues, so a
+/// grammar pass can add to them.
+pub fn check_directory(
+    job: &Job,
+    project: &Path,
+    dictionary: Option<&Path>,
+) -> Result<LintReport, String> {
+    let mut captions = read_all_captions(project)?;
+    captions.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+    let checker = SpellChecker::load(project, dictionary, &captions)?;
+
+    job.progress(
+        0,
+        captions.len() as u64,
+        Some("Checking spelling".to_string()),
+    );
+    let mut files = Vec::with_capacity(captions.len());
+    for (file, caption) in captions {
+        job.check_cancelled()?;
+        let issues = checker.check(&caption);
+        files.push(CaptionLint {
+            path: file.path,
+            name: file.name,
+            caption,
+            issues,
+        });
+        job.advance(1);
+    }
+
+    Ok(LintReport {
+        checked: files.len(),
+        files,
+        dictionary_words: checker.dictionary_words(),
+        ..Default::default()
+    })
+}
+
+/// Whether a caption is a list of tags rather than prose, for the grammar pass
+pub fn is_tag_list(caption: &str) -> bool {
+    let tags: Vec<&str> = split_tags(caption).collect();
+    tags.len() > 2 && tags.iter().all(|tag| tag.split_whitespace().count() <= 4)
+}
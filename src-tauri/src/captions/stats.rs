@@ -0,0 +1,229 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tracing::warn;
+
+use super::format::{self, CaptionStore};
+use super::search::read_all_captions;
+use crate::file_system::commands::scan_media_files;
+use crate::project::index::{self, TagCount};
+
+/// Tokens available to a caption in CLIP's 77-token context (start and end excluded)
+pub const CLIP_TOKEN_LIMIT: usize = 75;
+
+/// Summary of a set of lengths
+#[derive(Debug, Default, Serialize)]
+pub struct LengthStats {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    pub median: usize,
+    pub p90: usize,
+}
+
+impl LengthStats {
+    fn from_values(mut values: Vec<usize>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+
+        values.sort_unstable();
+        let percentile = |p: usize| values[(values.len() - 1) * p / 100];
+
+        Self {
+            min: values[0],
+            max: values[values.len() - 1],
+            mean: values.iter().sum::<usize>() as f64 / values.len() as f64,
+            median: percentile(50),
+            p90: percentile(90),
+        }
+    }
+}
+
+/// Caption statistics of a project
+#[derive(Debug, Serialize)]
+pub struct CaptionStats {
+    pub total_files: usize,
+    pub captioned: usize,
+    pub uncaptioned: usize,
+    /// Media whose caption exists but is blank
+    pub empty_captions: Vec<String>,
+    pub chars: LengthStats,
+    pub words: LengthStats,
+    /// Estimated CLIP tokens (see [`estimate_clip_tokens`])
+    pub clip_tokens: LengthStats,
+    /// Captions that probably exceed CLIP's context and will be truncated
+    pub over_token_limit: usize,
+    /// Average number of comma-separated tags per caption
+    pub average_tags: f64,
+    /// Coverage of the captions in other languages
+    pub languages: Vec<LanguageCoverage>,
+}
+
+/// How many media files have a caption in a language
+#[derive(Debug, Serialize)]
+pub struct LanguageCoverage {
+    pub language: String,
+    pub captioned: usize,
+    /// Share of all media files (0-1)
+    pub ratio: f64,
+}
+
+/// Estimate the number of CLIP BPE tokens of a caption
+///
+/// This approximates the tokenizer without its vocabulary: every punctuation mark is
+/// a token, and words split into roughly one token per five characters. Common words
+/// are a single token, so the estimate is close for natural captions.
+pub fn estimate_clip_tokens(caption: &str, pattern: &Regex) -> usize {
+    pattern
+        .find_iter(&caption.to_lowercase())
+        .map(|m| {
+            let chars = m.as_str().chars().count();
+            if m.as_str().chars().all(char::is_alphanumeric) {
+                chars.div_ceil(5).max(1)
+            } else {
+                1
+            }
+        })
+        .sum()
+}
+
+/// How often a tag occurs across a project
+#[derive(Debug, Serialize)]
+pub struct TagFrequency {
+    pub tag: String,
+    /// Total number of occurrences
+    pub count: usize,
+    /// Number of captions containing the tag
+    pub files: usize,
+    /// Share of captioned files containing the tag (0-1)
+    pub ratio: f64,
+}
+
+/// Split a comma-separated caption into tags
+pub fn split_tags(caption: &str) -> impl Iterator<Item = &str> {
+    caption.split(',').map(str::trim).filter(|t| !t.is_empty())
+}
+
+/// Count the tags of a comma-separated caption
+pub fn count_tags(caption: &str) -> usize {
+    split_tags(caption).count()
+}
+
+/// Aggregate tag frequencies over all captions of a project, most frequent first
+///
+/// Tags are compared case-insensitively and reported in lowercase. They are counted
+/// by the project index, or from the captions themselves if it can't be used.
+pub fn tag_frequencies(directory: &Path) -> Result<Vec<TagFrequency>, String> {
+    let (counts, captioned) = match index::tag_counts(directory) {
+        Ok(counts) => counts,
+        Err(e) => {
+            warn!("Counting tags without the project index: {}", e);
+            count_tags_in_captions(directory)?
+        }
+    };
+
+    let captioned = captioned.max(1) as f64;
+    let mut frequencies: Vec<TagFrequency> = counts
+        .into_iter()
+        .map(|count| TagFrequency {
+            ratio: count.files as f64 / captioned,
+            tag: count.tag,
+            count: count.count,
+            files: count.files,
+        })
+        .collect();
+
+    frequencies.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+
+    Ok(frequencies)
+}
+
+/// Count every tag by reading all captions, along with the number of captioned files
+fn count_tags_in_captions(directory: &Path) -> Result<(Vec<TagCount>, usize), String> {
+    let captions = read_all_captions(directory)?;
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+
+    for (_, caption) in &captions {
+        let mut seen = HashSet::new();
+        for tag in split_tags(caption) {
+            let tag = tag.to_lowercase();
+            let entry = counts.entry(tag.clone()).or_default();
+            entry.0 += 1;
+            if seen.insert(tag) {
+                entry.1 += 1;
+            }
+        }
+    }
+
+    let counts = counts
+        .into_iter()
+        .map(|(tag, (count, files))| TagCount { tag, count, files })
+        .collect();
+
+    Ok((counts, captions.len()))
+}
+
+/// Compute caption statistics for a project directory
+pub fn collect_stats(directory: &Path) -> Result<CaptionStats, String> {
+    let files = scan_media_files(directory)?;
+    let total_files = files.len();
+    let captions = read_all_captions(directory)?;
+    let token_pattern =
+        Regex::new(r"[[:alnum:]]+|[^[:alnum:][:space:]]").map_err(|e| e.to_string())?;
+
+    let mut empty_captions = Vec::new();
+    let mut chars = Vec::new();
+    let mut words = Vec::new();
+    let mut tokens = Vec::new();
+    let mut tag_total = 0;
+
+    for (file, caption) in &captions {
+        let caption = caption.trim();
+        if caption.is_empty() {
+            empty_captions.push(file.path.clone());
+            continue;
+        }
+
+        chars.push(caption.chars().count());
+        words.push(caption.split_whitespace().count());
+        tokens.push(estimate_clip_tokens(caption, &token_pattern));
+        tag_total += count_tags(caption);
+    }
+
+    let non_empty = chars.len();
+    let over_token_limit = tokens.iter().filter(|&&t| t > CLIP_TOKEN_LIMIT).count();
+    empty_captions.sort();
+
+    let mut languages = Vec::new();
+    for language in format::languages(directory)? {
+        let mut store = CaptionStore::open(directory).with_language(Some(&language));
+        let captioned = files
+            .iter()
+            .filter(|f| store.has_caption(Path::new(&f.path)))
+            .count();
+        languages.push(LanguageCoverage {
+            language,
+            captioned,
+            ratio: captioned as f64 / total_files.max(1) as f64,
+        });
+    }
+
+    Ok(CaptionStats {
+        total_files,
+        captioned: captions.len(),
+        uncaptioned: total_files.saturating_sub(captions.len()),
+        empty_captions,
+        chars: LengthStats::from_values(chars),
+        words: LengthStats::from_values(words),
+        clip_tokens: LengthStats::from_values(tokens),
+        over_token_limit,
+        average_tags: if non_empty > 0 {
+            tag_total as f64 / non_empty as f64
+        } else {
+            0.0
+        },
+        languages,
+    })
+}
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use super::batch::CaptionChange;
+use super::search::read_all_captions;
+use super::stats::split_tags;
+use crate::file_system::atomic;
+use crate::project::{metadata_dir, METADATA_DIR};
+
+/// Name of the tag dictionary file in a project's metadata directory
+const DICTIONARY_FILE: &str = "tags.json";
+
+/// Tag aliases and implications of a project
+///
+/// Tags are matched case-insensitively, as whole comma-separated segments of a caption.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagDictionary {
+    /// Tags replaced by their preferred form, e.g. "girl" → "woman"
+    pub aliases: BTreeMap<String, String>,
+    /// Tags added along with a tag, e.g. "golden retriever" → ["dog"]
+    pub implications: BTreeMap<String, Vec<String>>,
+}
+
+impl TagDictionary {
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty() && self.implications.is_empty()
+    }
+
+    /// Trim the entries, lowercase the tags they match and drop empty ones
+    fn cleaned(self) -> Self {
+        let aliases = self
+            .aliases
+            .into_iter()
+            .map(|(from, to)| (from.trim().to_lowercase(), to.trim().to_string()))
+            .filter(|(from, to)| !from.is_empty() && !to.is_empty() && *from != to.to_lowercase())
+            .collect();
+        let implications = self
+            .implications
+            .into_iter()
+            .map(|(tag, implied)| {
+                let implied: Vec<String> = implied
+                    .iter()
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+                (tag.trim().to_lowercase(), implied)
+            })
+            .filter(|(tag, implied)| !tag.is_empty() && !implied.is_empty())
+            .collect();
+
+        Self {
+            aliases,
+            implications,
+        }
+    }
+
+    /// Every tag the dictionary mentions, in lowercase
+    pub fn known_tags(&self) -> HashSet<String> {
+        let aliases = self
+            .aliases
+            .iter()
+            .flat_map(|(from, to)| [from.clone(), to.to_lowercase()]);
+        let implications = self.implications.iter().flat_map(|(tag, implied)| {
+            std::iter::once(tag.clone()).chain(implied.iter().map(|t| t.to_lowercase()))
+        });
+        aliases.chain(implications).collect()
+    }
+
+    /// Resolve a tag through the aliases, following chains like "girl" → "woman" →
+    /// "adult woman"
+    pub fn canonical(&self, tag: &str) -> String {
+        let mut current = tag.to_string();
+        let mut visited = HashSet::new();
+        while let Some(target) = self.aliases.get(&current.to_lowercase()) {
+            if !visited.insert(current.to_lowercase()) {
+                break;
+            }
+            current = target.clone();
+        }
+        current
+    }
+
+    /// Normalize the tags of a caption
+    ///
+    /// Aliases are replaced, the tags implied by any tag (transitively) are appended
+    /// and duplicates are removed. Captions without a matching tag are returned as is,
+    /// so prose captions keep their punctuation.
+    pub fn apply(&self, caption: &str) -> String {
+        if self.is_empty() {
+            return caption.to_string();
+        }
+
+        let original: Vec<&str> = split_tags(caption).collect();
+        let mut tags: Vec<String> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut add = |tag: String, tags: &mut Vec<String>| {
+            if seen.insert(tag.to_lowercase()) {
+                tags.push(tag);
+            }
+        };
+
+        for tag in &original {
+            add(self.canonical(tag), &mut tags);
+        }
+        // Implied tags are queued behind the others, so their own implications apply
+        let mut next = 0;
+        while next < tags.len() {
+            if let Some(implied) = self.implications.get(&tags[next].to_lowercase()) {
+                for tag in implied {
+                    add(self.canonical(tag), &mut tags);
+                }
+            }
+            next += 1;
+        }
+
+        if tags.len() == original.len() && tags.iter().zip(&original).all(|(a, b)| a == b) {
+            caption.to_string()
+        } else {
+            tags.join(", ")
+        }
+    }
+}
+
+/// Load the tag dictionary of a project, or an empty one if it has none
+pub fn load(project: &Path) -> Result<TagDictionary, String> {
+    let path = project.join(METADATA_DIR).join(DICTIONARY_FILE);
+    if !path.is_file() {
+        return Ok(TagDictionary::default());
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read tag dictionary: {}", e))?;
+    serde_json::from_str::<TagDictionary>(&content)
+        .map(TagDictionary::cleaned)
+        .map_err(|e| format!("Failed to parse tag dictionary: {}", e))
+}
+
+/// Save the tag dictionary of a project, returning it as stored
+pub fn save(project: &Path, dictionary: TagDictionary) -> Result<TagDictionary, String> {
+    let dictionary = dictionary.cleaned();
+    let content = serde_json::to_string_pretty(&dictionary)
+        .map_err(|e| format!("Failed to serialize tag dictionary: {}", e))?;
+    atomic::write_atomic(
+        &metadata_dir(project)?.join(DICTIONARY_FILE),
+        content,
+        false,
+    )
+    .map_err(|e| format!("Failed to write tag dictionary: {}", e))?;
+    Ok(dictionary)
+}
+
+/// Compute the captions changed by applying a project's tag dictionary to them
+pub fn plan_normalize(
+    directory: &Path,
+    dictionary: &TagDictionary,
+) -> Result<Vec<CaptionChange>, String> {
+    let mut changes: Vec<CaptionChange> = read_all_captions(directory)?
+        .into_iter()
+        .filter_map(|(file, caption)| {
+            let after = dictionary.apply(&caption);
+            (after != caption).then_some(CaptionChange {
+                path: file.path,
+                name: file.name,
+                before: caption,
+                after,
+            })
+        })
+        .collect();
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(changes)
+}
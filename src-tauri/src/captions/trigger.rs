@@ -0,0 +1,212 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::batch::CaptionChange;
+use super::search::read_all_captions;
+use crate::project::settings::ProjectSettings;
+
+/// Where the trigger token goes in a caption
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerPlacement {
+    /// Before everything else (default)
+    #[default]
+    First,
+    /// After the leading style tags, e.g. `photo, film grain, ohwx woman, ...`
+    AfterStyleTags,
+    /// In place of the first subject noun, e.g. `a photo of ohwx woman ...`; captions
+    /// without one get the token first
+    ReplaceSubject,
+}
+
+/// How a project's trigger token is injected into its captions
+///
+/// The token is the project's trigger word followed by the class token, e.g.
+/// `ohwx woman`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriggerRules {
+    pub class_token: Option<String>,
+    pub placement: TriggerPlacement,
+    /// Tags the token follows with [`TriggerPlacement::AfterStyleTags`]
+    pub style_tags: Vec<String>,
+    /// Nouns the token replaces with [`TriggerPlacement::ReplaceSubject`], like
+    /// "a woman" or "the person"; longer phrases are tried first
+    pub subject_nouns: Vec<String>,
+}
+
+/// Trigger rules of a project along with the token they inject
+pub struct TriggerInjection<'a> {
+    rules: &'a TriggerRules,
+    token: String,
+}
+
+/// Match a phrase as a whole, case-insensitively
+fn phrase_pattern(phrase: &str) -> Result<Regex, String> {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let start = if is_word(phrase.chars().next()) {
+        r"\b"
+    } else {
+        ""
+    };
+    let end = if is_word(phrase.chars().last()) {
+        r"\b"
+    } else {
+        ""
+    };
+    RegexBuilder::new(&format!("{}{}{}", start, regex::escape(phrase), end))
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Collapse the separators a removed phrase leaves behind
+fn tidy(caption: &str) -> String {
+    let separators = Regex::new(r"(\s*,\s*){2,}").expect("valid separator pattern");
+    let spaces = Regex::new(r" {2,}").expect("valid whitespace pattern");
+    let caption = separators.replace_all(caption, ", ");
+    let caption = spaces.replace_all(&caption, " ");
+    caption
+        .trim_matches(|c: char| c == ',' || c.is_whitespace())
+        .to_string()
+}
+
+impl<'a> TriggerInjection<'a> {
+    /// The injection configured in a project's settings, if any
+    pub fn from_settings(settings: &'a ProjectSettings) -> Option<Self> {
+        let rules = settings.trigger_rules.as_ref()?;
+        let token = [
+            settings.trigger_word.as_deref(),
+            rules.class_token.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+        (!token.is_empty()).then_some(Self { rules, token })
+    }
+
+    /// Inject the token into a caption, leaving captions that already follow the rules
+    /// as they are
+    ///
+    /// Occurrences of `previous` tokens, from before the rules changed, are replaced by
+    /// the current token first.
+    pub fn apply(&self, caption: &str, previous: &[String]) -> Result<String, String> {
+        let mut caption = caption.to_string();
+        for old in previous.iter().map(|t| t.trim()) {
+            if !old.is_empty() && !old.eq_ignore_ascii_case(&self.token) {
+                caption = phrase_pattern(old)?
+                    .replace_all(&caption, regex::NoExpand(&self.token))
+                    .to_string();
+            }
+        }
+        if caption.trim().is_empty() {
+            return Ok(caption);
+        }
+
+        let token_pattern = phrase_pattern(&self.token)?;
+        match self.rules.placement {
+            TriggerPlacement::First => {
+                if token_pattern
+                    .find(caption.trim_start())
+                    .is_some_and(|m| m.start() == 0)
+                {
+                    return Ok(caption);
+                }
+                Ok(self.insert(&caption, 0))
+            }
+            TriggerPlacement::AfterStyleTags => {
+                let tags: Vec<&str> = caption.split(',').map(str::trim).collect();
+                let styles = tags
+                    .iter()
+                    .take_while(|tag| {
+                        self.rules
+                            .style_tags
+                            .iter()
+                            .any(|style| style.trim().eq_ignore_ascii_case(tag))
+                    })
+                    .count();
+                if tags
+                    .get(styles)
+                    .is_some_and(|tag| tag.eq_ignore_ascii_case(&self.token))
+                {
+                    return Ok(caption);
+                }
+                Ok(self.insert(&caption, styles))
+            }
+            TriggerPlacement::ReplaceSubject => {
+                if token_pattern.is_match(&caption) {
+                    return Ok(caption);
+                }
+                let mut nouns: Vec<&str> = self
+                    .rules
+                    .subject_nouns
+                    .iter()
+                    .map(|n| n.trim())
+                    .filter(|n| !n.is_empty())
+                    .collect();
+                nouns.sort_by_key(|n| std::cmp::Reverse(n.len()));
+                for noun in nouns {
+                    let pattern = phrase_pattern(noun)?;
+                    if pattern.is_match(&caption) {
+                        return Ok(pattern
+                            .replacen(&caption, 1, regex::NoExpand(&self.token))
+                            .to_string());
+                    }
+                }
+                Ok(self.insert(&caption, 0))
+            }
+        }
+    }
+
+    /// Insert the token as the tag at `position`, dropping it from anywhere else
+    fn insert(&self, caption: &str, position: usize) -> String {
+        let mut tags: Vec<&str> = caption
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.eq_ignore_ascii_case(&self.token))
+            .collect();
+        tags.insert(position.min(tags.len()), &self.token);
+        tidy(&tags.join(", "))
+    }
+}
+
+/// Apply a project's trigger rules to a caption, if it has any
+pub fn apply(settings: &ProjectSettings, caption: String) -> Result<String, String> {
+    match TriggerInjection::from_settings(settings) {
+        Some(injection) => injection.apply(&caption, &[]),
+        None => Ok(caption),
+    }
+}
+
+/// Compute the captions changed by re-applying a project's trigger rules, e.g. after
+/// they changed mid-project
+pub fn plan_reapply(
+    directory: &Path,
+    settings: &ProjectSettings,
+    previous: &[String],
+) -> Result<Vec<CaptionChange>, String> {
+    let injection = TriggerInjection::from_settings(settings)
+        .ok_or_else(|| "The project has no trigger word or trigger rules".to_string())?;
+
+    let mut changes = Vec::new();
+    for (file, caption) in read_all_captions(directory)? {
+        let after = injection.apply(&caption, previous)?;
+        if after != caption {
+            changes.push(CaptionChange {
+                path: file.path,
+                name: file.name,
+                before: caption,
+                after,
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(changes)
+}
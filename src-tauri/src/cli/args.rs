@@ -0,0 +1,86 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// Arguments of a subcommand: positional values, `--name value` options and `--name`
+/// flags
+#[derive(Debug, Default)]
+pub struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    flags: HashSet<String>,
+}
+
+impl Args {
+    /// Parse the arguments after the subcommand, rejecting options not in `options`
+    /// or `flags` (names without the leading dashes)
+    pub fn parse(args: &[String], options: &[&str], flags: &[&str]) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                parsed.positional.push(arg.clone());
+                continue;
+            };
+
+            // Both `--name value` and `--name=value` are accepted
+            let (name, inline) = match name.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (name, None),
+            };
+            if flags.contains(&name) && inline.is_none() {
+                parsed.flags.insert(name.to_string());
+            } else if options.contains(&name) {
+                let value = match inline {
+                    Some(value) => value,
+                    None => args
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("Missing value for --{}", name))?,
+                };
+                parsed.options.insert(name.to_string(), value);
+            } else {
+                return Err(format!("Unknown option --{}", name));
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// The positional argument at `index`, described by `name` in the error if missing
+    pub fn positional(&self, index: usize, name: &str) -> Result<&str, String> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("Missing {}", name))
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    pub fn required(&self, name: &str) -> Result<&str, String> {
+        self.value(name)
+            .ok_or_else(|| format!("Missing required option --{}", name))
+    }
+
+    /// Parse the value of an option, if it was given
+    pub fn optional<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.value(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for --{}: {}", name, value))
+            })
+            .transpose()
+    }
+
+    /// Parse the value of an option, or return `default` if it wasn't given
+    pub fn parsed<T: FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+        Ok(self.optional(name)?.unwrap_or(default))
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+}
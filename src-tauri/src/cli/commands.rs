@@ -0,0 +1,371 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::args::Args;
+use crate::api::commands::{generate_captions_item, generate_gemini_caption};
+use crate::api::script;
+use crate::captions::format::CaptionStore;
+use crate::captions::git as caption_git;
+use crate::captions::{encoding, history};
+use crate::file_system::commands::MediaFile;
+use crate::file_system::copy::{copy_file, ImportMode};
+use crate::jobs::manager::{self as jobs, Job, JobKind};
+use crate::media::commands::thumbnail_bytes;
+use crate::project::kohya::{self, KohyaOptions};
+use crate::project::{index, lock, settings};
+use crate::remote::commands::{dataset_metadata, METADATA_FILE};
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Environment variable read for the API key when `--api-key` isn't given, so it stays
+/// out of the shell history
+const API_KEY_VAR: &str = "SPACECAT_API_KEY";
+
+// Same defaults as the app's settings
+const DEFAULT_PROMPT: &str = "Describe this image in detail:";
+const DEFAULT_API_URL: &str = "https://api.openai.com/v1/chat/completions";
+const DEFAULT_MODEL: &str = "gpt-4o-2024-05-13";
+const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+
+/// Captions generated before they are written, so an interrupted run keeps most of them
+const WRITE_BATCH: usize = 25;
+
+/// Captioning service and its settings
+enum Provider {
+    OpenAi {
+        api_url: String,
+        model: String,
+        /// Image detail sent with requests, when set
+        detail: Option<String>,
+    },
+    Gemini {
+        system_instruction: Option<String>,
+        temperature: Option<f32>,
+    },
+    /// A user-provided script, started with its arguments for every file
+    Script { command: String, args: Vec<String> },
+}
+
+impl Provider {
+    fn from_args(args: &Args) -> Result<Self, String> {
+        match args.value("provider").unwrap_or("openai") {
+            "openai" => Ok(Provider::OpenAi {
+                api_url: args.value("api-url").unwrap_or(DEFAULT_API_URL).to_string(),
+                model: args.value("model").unwrap_or(DEFAULT_MODEL).to_string(),
+                detail: args.value("detail").map(str::to_string),
+            }),
+            "gemini" => Ok(Provider::Gemini {
+                system_instruction: args.value("system-instruction").map(str::to_string),
+                temperature: args
+                    .value("temperature")
+                    .map(|t| t.parse().map_err(|_| format!("Invalid temperature: {}", t)))
+                    .transpose()?,
+            }),
+            "script" => Ok(Provider::Script {
+                command: args.required("script")?.to_string(),
+                args: args
+                    .value("script-args")
+                    .map(|a| a.split_whitespace().map(str::to_string).collect())
+                    .unwrap_or_default(),
+            }),
+            other => Err(format!(
+                "Unknown provider: {} (openai, gemini or script)",
+                other
+            )),
+        }
+    }
+
+    async fn caption(&self, api_key: &str, prompt: &str, path: &str) -> Result<String, String> {
+        match self {
+            Provider::OpenAi {
+                api_url,
+                model,
+                detail,
+            } => {
+                let caption = generate_captions_item(
+                    api_url,
+                    api_key,
+                    prompt,
+                    path,
+                    model,
+                    detail.as_deref().unwrap_or("auto"),
+                    detail.is_some(),
+                )
+                .await;
+                match caption.strip_prefix("Error: ") {
+                    Some(error) => Err(error.to_string()),
+                    None => Ok(caption),
+                }
+            }
+            Provider::Gemini {
+                system_instruction,
+                temperature,
+            } => {
+                generate_gemini_caption(
+                    api_key.to_string(),
+                    prompt.to_string(),
+                    path.to_string(),
+                    system_instruction.clone(),
+                    *temperature,
+                    None,
+                )
+                .await
+            }
+            Provider::Script { command, args } => {
+                let (command, args) = (command.clone(), args.clone());
+                let (prompt, path) = (prompt.to_string(), path.to_string());
+                tauri::async_runtime::spawn_blocking(move || {
+                    script::run(&command, &args, &prompt, &path, None, None)
+                })
+                .await
+                .map_err(|e| e.to_string())?
+            }
+        }
+    }
+
+    fn needs_api_key(&self) -> bool {
+        !matches!(self, Provider::Script { .. })
+    }
+}
+
+/// The project directory given as the first argument
+fn project_directory(args: &Args) -> Result<PathBuf, String> {
+    let path = Path::new(args.positional(0, "project directory")?);
+    if !path.is_dir() {
+        return Err(format!("Not a directory: {}", path.display()));
+    }
+    path.canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))
+}
+
+/// The output directory of `--output`, which must lie outside the project
+fn output_directory(args: &Args, project: &Path) -> Result<PathBuf, String> {
+    let output = PathBuf::from(args.required("output")?);
+    fs::create_dir_all(&output)
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let output = output
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", output.display(), e))?;
+
+    if output.starts_with(project) {
+        return Err("The output directory can't be inside the project".to_string());
+    }
+    Ok(output)
+}
+
+/// Run a job to its end, recording its outcome
+fn finish<T>(job: Job, result: Result<T, String>) -> Result<T, String> {
+    job.finish(&result);
+    result
+}
+
+/// Caption the media files of a project, skipping captioned ones unless `--overwrite`
+///
+/// Captions are written in batches, each an undoable operation, so running an
+/// interrupted batch again only captions what is left. Files whose request failed are
+/// reported and keep their caption.
+pub async fn caption(args: &Args) -> Result<(), String> {
+    let project = project_directory(args)?;
+    // Held until the process exits, so the app doesn't write captions in between
+    lock::acquire(&project, "command line run", None)?;
+    let provider = Provider::from_args(args)?;
+    let api_key = match args.value("api-key") {
+        Some(key) => key.to_string(),
+        None if !provider.needs_api_key() => String::new(),
+        None => env::var(API_KEY_VAR)
+            .map_err(|_| format!("Missing API key: pass --api-key or set {}", API_KEY_VAR))?,
+    };
+    let prompt = args.value("prompt").unwrap_or(DEFAULT_PROMPT);
+    let overwrite = args.flag("overwrite");
+
+    let files: Vec<MediaFile> = index::list_files(&project)?
+        .into_iter()
+        .filter(|file| overwrite || !file.has_caption)
+        .collect();
+    if files.is_empty() {
+        println!("No files to caption");
+        return Ok(());
+    }
+
+    let job = jobs::detached(
+        JobKind::Captioning,
+        format!("Caption {} files", files.len()),
+    );
+    let total = files.len() as u64;
+    let mut pending = Vec::new();
+    let mut captioned = 0;
+    let mut failed = 0;
+    for (index, file) in files.iter().enumerate() {
+        job.progress(index as u64, total, Some(file.relative_path.clone()));
+        match provider.caption(&api_key, prompt, &file.path).await {
+            Ok(caption) => pending.push((PathBuf::from(&file.path), caption)),
+            Err(e) => {
+                eprintln!("Failed to caption {}: {}", file.relative_path, e);
+                failed += 1;
+            }
+        }
+
+        if pending.len() >= WRITE_BATCH {
+            if let Err(e) = write_captions(&project, &pending) {
+                return finish(job, Err(e));
+            }
+            captioned += pending.len();
+            pending.clear();
+        }
+    }
+    job.progress(total, total, None);
+
+    let result = write_captions(&project, &pending);
+    finish(job, result)?;
+    captioned += pending.len();
+    println!("Captioned {} files, {} failed", captioned, failed);
+    Ok(())
+}
+
+/// Write generated captions as a single undoable operation
+fn write_captions(project: &Path, captions: &[(PathBuf, String)]) -> Result<(), String> {
+    if captions.is_empty() {
+        return Ok(());
+    }
+
+    let description = format!("Caption {} files from the command line", captions.len());
+    let mut store = CaptionStore::open(project);
+    let mut operation = Operation::begin(
+        project,
+        OperationKind::CaptionOverwrite,
+        description.clone(),
+    )?;
+    for (media, _) in captions {
+        operation.snapshot(&store.caption_path(media))?;
+    }
+
+    for (media, caption) in captions {
+        let caption = encoding::normalize_line_endings(caption);
+        if let Some(previous) = store.read(media)?.filter(|p| *p != caption) {
+            if let Err(e) = history::record_version(media, &previous) {
+                eprintln!(
+                    "Failed to record caption history for {}: {}",
+                    media.display(),
+                    e
+                );
+            }
+        }
+        store.write(media, &caption)?;
+    }
+
+    operation.commit()?;
+    caption_git::commit_if_enabled(project, &description);
+    Ok(())
+}
+
+/// Export a project as a training dataset, in kohya's folder layout or as a Hugging
+/// Face `imagefolder` dataset
+pub fn export(args: &Args) -> Result<(), String> {
+    let project = project_directory(args)?;
+    let format = args.required("format")?;
+    if format != "kohya" && format != "hf" {
+        return Err(format!("Unknown export format: {} (kohya or hf)", format));
+    }
+    let output = output_directory(args, &project)?;
+
+    let job = jobs::detached(JobKind::Export, format!("Export {}", project.display()));
+    let result = match format {
+        "kohya" => export_kohya(&job, args, &project, &output),
+        _ => export_imagefolder(&job, &project, &output),
+    };
+    let exported = finish(job, result)?;
+    settings::record_export(&project, &output.to_string_lossy());
+    println!("Exported {} files to {}", exported, output.display());
+    Ok(())
+}
+
+/// Export for kohya's sd-scripts, with a dataset config derived from the dataset and
+/// optionally a training config
+fn export_kohya(job: &Job, args: &Args, project: &Path, output: &Path) -> Result<usize, String> {
+    let options = KohyaOptions {
+        repeats: args.optional("repeats")?,
+        concept: args.value("concept").map(str::to_string),
+        resolution: args.optional("resolution")?,
+        training_config: args.flag("training-config"),
+        base_model: args.value("base-model").map(str::to_string),
+    };
+
+    let export = kohya::export(job, project, output, &options)?;
+    println!("Dataset config: {}", export.dataset_config);
+    if let Some(training_config) = &export.training_config {
+        println!("Training config: {}", training_config);
+    }
+    Ok(export.files)
+}
+
+/// Copy the media files to `output` at their relative paths, with a `metadata.jsonl`
+/// holding the captions
+fn export_imagefolder(job: &Job, project: &Path, output: &Path) -> Result<usize, String> {
+    let media = index::list_files(project)?;
+
+    let total = media.len() as u64;
+    for (index, file) in media.iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some(file.relative_path.clone()));
+
+        let target = output.join(&file.relative_path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        copy_file(ImportMode::Copy, Path::new(&file.path), &target)?;
+    }
+
+    let metadata = output.join(METADATA_FILE);
+    fs::write(&metadata, dataset_metadata(project, &media)?)
+        .map_err(|e| format!("Failed to write {}: {}", metadata.display(), e))?;
+
+    job.progress(total, total, None);
+    Ok(media.len())
+}
+
+/// Write a JPEG thumbnail of every media file of a project into `--output`, at the
+/// file's relative path with `.jpg` appended
+pub async fn thumbnails(args: &Args) -> Result<(), String> {
+    let project = project_directory(args)?;
+    let output = output_directory(args, &project)?;
+    let max_size: u32 = args.parsed("size", DEFAULT_THUMBNAIL_SIZE)?;
+
+    let media = index::list_files(&project)?;
+    let job = jobs::detached(
+        JobKind::Thumbnails,
+        format!("Generate thumbnails for {}", project.display()),
+    );
+
+    let total = media.len() as u64;
+    let mut generated = 0;
+    for (index, file) in media.iter().enumerate() {
+        job.progress(index as u64, total, Some(file.relative_path.clone()));
+
+        let target = output.join(format!("{}.jpg", file.relative_path));
+        match write_thumbnail(&file.path, &target, max_size).await {
+            Ok(()) => generated += 1,
+            Err(e) => eprintln!("Failed to generate thumbnail for {}: {}", file.path, e),
+        }
+    }
+    job.progress(total, total, None);
+
+    finish(job, Ok(()))?;
+    println!(
+        "Generated {} of {} thumbnails in {}",
+        generated,
+        media.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+async fn write_thumbnail(path: &str, target: &Path, max_size: u32) -> Result<(), String> {
+    let bytes = thumbnail_bytes(path, max_size).await?;
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    fs::write(target, &bytes).map_err(|e| format!("Failed to write {}: {}", target.display(), e))
+}
@@ -0,0 +1,112 @@
+//! Headless mode, running the core operations from the command line without the GUI
+//!
+//! Started with `spacecat-caption --headless <command> ...`; progress goes to stderr
+//! and the outcome to stdout, and the exit code is non-zero on failure. Release builds
+//! on Windows have no console of their own, so redirect the output there to see it.
+
+pub mod args;
+pub mod commands;
+
+use args::Args;
+
+const USAGE: &str = "\
+Usage: spacecat-caption --headless <command> <project directory> [options]
+
+Commands:
+  caption       Caption the media files that have no caption yet
+      --provider <openai|gemini|script>
+                                   Captioning service (default: openai)
+      --api-key <key>              API key (default: $SPACECAT_API_KEY)
+      --prompt <text>              Caption prompt
+      --model <name>               OpenAI model
+      --api-url <url>              OpenAI-compatible endpoint
+      --detail <low|high|auto>     OpenAI image detail
+      --system-instruction <text>  Gemini system instruction
+      --temperature <value>        Gemini temperature
+      --script <command>           Caption script (see the README for its protocol)
+      --script-args <args>         Arguments of the script, separated by spaces
+      --overwrite                  Caption files that already have a caption too
+
+  export        Export the project as a training dataset
+      --format <kohya|hf>          kohya folder layout or Hugging Face imagefolder
+      --output <dir>               Directory to export into
+      --repeats <n>                kohya repeat count (default: derived from the file count)
+      --concept <name>             kohya concept name (default: trigger word or project name)
+      --resolution <pixels>        kohya training resolution (default: derived from the images)
+      --training-config            Also write a kohya LoRA training config
+      --base-model <path>          Base model named in the training config
+
+  thumbnails    Write JPEG thumbnails of the media files
+      --output <dir>               Directory to write them into
+      --size <pixels>              Maximum width and height (default: 256)
+
+Warnings and errors are logged to stderr; set SPACECAT_LOG to error, warn, info, debug
+or trace to change how much.
+";
+
+/// Run a headless command, given the arguments after `--headless`; returns the exit code
+pub fn run(args: &[String]) -> i32 {
+    crate::logging::filter::init_headless();
+
+    let Some((command, rest)) = args.split_first() else {
+        eprint!("{}", USAGE);
+        return 2;
+    };
+
+    let parsed = match command.as_str() {
+        "caption" => Args::parse(
+            rest,
+            &[
+                "provider",
+                "api-key",
+                "prompt",
+                "model",
+                "api-url",
+                "detail",
+                "system-instruction",
+                "temperature",
+                "script",
+                "script-args",
+            ],
+            &["overwrite"],
+        ),
+        "export" => Args::parse(
+            rest,
+            &[
+                "format",
+                "output",
+                "repeats",
+                "concept",
+                "resolution",
+                "base-model",
+            ],
+            &["training-config"],
+        ),
+        "thumbnails" => Args::parse(rest, &["output", "size"], &[]),
+        "help" | "--help" | "-h" => {
+            print!("{}", USAGE);
+            return 0;
+        }
+        other => Err(format!("Unknown command: {}", other)),
+    };
+    let args = match parsed {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+
+    let result = match command.as_str() {
+        "caption" => tauri::async_runtime::block_on(commands::caption(&args)),
+        "export" => commands::export(&args),
+        _ => tauri::async_runtime::block_on(commands::thumbnails(&args)),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::path::Path;
+use tauri::AppHandle;
+
+use super::store::{self, Experiment, ExperimentProvider, ExperimentSample, PromptVariant};
+use crate::api::commands::{generate_captions_item, generate_gemini_caption};
+use crate::file_system::commands::{scan_media_files, validate_project_directory};
+use crate::file_system::paths;
+use crate::jobs::manager::{self as jobs, JobKind};
+use crate::project::settings;
+
+/// Caption a random sample of a project's files with each of several prompts
+///
+/// Draws `sample_size` files (all of them if the project has fewer), using `seed` if
+/// given so a sample can be drawn again, and captions each with every variant. Runs as
+/// a captioning job; cancelling it keeps the captions generated so far. Nothing is
+/// written to the captions themselves; the results are saved as an experiment of the
+/// project and returned.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_experiment(
+    app: AppHandle,
+    directory: String,
+    name: String,
+    variants: Vec<PromptVariant>,
+    sample_size: usize,
+    seed: Option<u64>,
+    provider: ExperimentProvider,
+    api_key: String,
+) -> Result<Experiment, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    if variants.len() < 2 {
+        return Err("An experiment needs at least two prompt variants".to_string());
+    }
+    if variants.iter().any(|v| v.prompt.trim().is_empty()) {
+        return Err("Prompt variants cannot be empty".to_string());
+    }
+    if sample_size == 0 {
+        return Err("The sample needs at least one file".to_string());
+    }
+
+    // Drawn seeds stay within what a JavaScript number holds exactly
+    let seed = seed.unwrap_or_else(|| u64::from(fastrand::u32(..)));
+    let mut files = scan_media_files(&dir_path)?;
+    fastrand::Rng::with_seed(seed).shuffle(&mut files);
+    files.truncate(sample_size);
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+
+    let mut experiment = Experiment {
+        id: store::new_id(),
+        name: name.trim().to_string(),
+        created: settings::now_timestamp(),
+        provider,
+        variants,
+        seed,
+        samples: files
+            .iter()
+            .map(|file| ExperimentSample {
+                path: file.path.clone(),
+                relative_path: file.relative_path.clone(),
+                captions: Vec::new(),
+            })
+            .collect(),
+        incomplete: false,
+    };
+
+    let description = format!(
+        "Compare {} prompts on {} files",
+        experiment.variants.len(),
+        experiment.samples.len()
+    );
+    let experiment = jobs::run_async(&app, JobKind::Captioning, description, |job| async move {
+        let total = (experiment.samples.len() * experiment.variants.len()) as u64;
+        let mut done = 0;
+
+        'samples: for sample in &mut experiment.samples {
+            for variant in &experiment.variants {
+                if job.is_cancelled() {
+                    break 'samples;
+                }
+                job.progress(
+                    done,
+                    total,
+                    Some(format!("{}: {}", variant.name, sample.relative_path)),
+                );
+
+                let caption = match &experiment.provider {
+                    ExperimentProvider::Openai {
+                        api_url,
+                        model,
+                        image_detail,
+                        use_detail_parameter,
+                    } => {
+                        generate_captions_item(
+                            api_url,
+                            &api_key,
+                            &variant.prompt,
+                            &sample.path,
+                            model,
+                            image_detail,
+                            *use_detail_parameter,
+                        )
+                        .await
+                    }
+                    ExperimentProvider::Gemini {
+                        system_instruction,
+                        temperature,
+                    } => generate_gemini_caption(
+                        api_key.clone(),
+                        variant.prompt.clone(),
+                        sample.path.clone(),
+                        system_instruction.clone(),
+                        *temperature,
+                        None,
+                    )
+                    .await
+                    .unwrap_or_else(|e| format!("Error: {}", e)),
+                };
+                sample.captions.push(caption);
+                done += 1;
+            }
+        }
+
+        experiment.incomplete = done < total;
+        job.progress(done, total, None);
+        Ok(experiment)
+    })
+    .await?;
+
+    store::save(&dir_path, &experiment)?;
+    Ok(experiment)
+}
+
+/// List the prompt experiments of a project, newest first
+#[tauri::command]
+pub async fn list_experiments(
+    app: AppHandle,
+    directory: String,
+) -> Result<Vec<Experiment>, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    store::list(&dir_path)
+}
+
+/// Delete a prompt experiment of a project
+#[tauri::command]
+pub async fn delete_experiment(
+    app: AppHandle,
+    directory: String,
+    id: String,
+) -> Result<(), String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    store::delete(&dir_path, &id)
+}
+
+/// Export a prompt experiment as a CSV comparison sheet, with a row per sampled file
+/// and a column per variant
+///
+/// The file is written to `destination`, or to the project directory by default.
+/// Returns the path of the written file.
+#[tauri::command]
+pub async fn export_experiment(
+    app: AppHandle,
+    directory: String,
+    id: String,
+    destination: Option<String>,
+) -> Result<String, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let destination = match destination {
+        Some(destination) => {
+            let destination = Path::new(&destination).to_path_buf();
+            if !destination.is_dir() {
+                return Err(format!(
+                    "Directory does not exist: {}",
+                    destination.display()
+                ));
+            }
+            destination
+        }
+        None => dir_path.clone(),
+    };
+
+    let experiment = store::load(&dir_path, &id)?;
+    let path = store::write_csv(&experiment, &destination)?;
+    Ok(paths::display_path(&path))
+}
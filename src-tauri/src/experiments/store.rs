@@ -0,0 +1,164 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::captions::alt_text::csv_field;
+use crate::file_system::atomic;
+use crate::project::{metadata_dir, METADATA_DIR};
+
+/// Directory in a project's metadata directory holding one JSON file per experiment
+const EXPERIMENTS_DIR: &str = "experiments";
+
+/// A prompt compared in an experiment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptVariant {
+    pub name: String,
+    pub prompt: String,
+}
+
+/// Provider and model the variants are captioned with; the API key is never stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum ExperimentProvider {
+    Openai {
+        api_url: String,
+        model: String,
+        image_detail: String,
+        use_detail_parameter: bool,
+    },
+    Gemini {
+        system_instruction: Option<String>,
+        temperature: Option<f32>,
+    },
+}
+
+/// The captions of one sampled file, in the order of the variants
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentSample {
+    pub path: String,
+    pub relative_path: String,
+    /// Generated captions, or `Error: ...` for failed requests; shorter than the
+    /// variants when the experiment was cancelled
+    pub captions: Vec<String>,
+}
+
+/// Prompt variants captioned side by side on a sample of a project's files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Experiment {
+    pub id: String,
+    pub name: String,
+    pub created: String,
+    pub provider: ExperimentProvider,
+    pub variants: Vec<PromptVariant>,
+    /// Seed the sample was drawn with, so it can be drawn again
+    pub seed: u64,
+    pub samples: Vec<ExperimentSample>,
+    /// Whether the run was cancelled before every sample was captioned
+    #[serde(default)]
+    pub incomplete: bool,
+}
+
+/// Identifier of a new experiment, from the current time
+pub fn new_id() -> String {
+    Local::now().format("%Y%m%d%H%M%S%3f").to_string()
+}
+
+/// Path of an experiment's file, rejecting ids that aren't plain names
+fn experiment_path(project: &Path, id: &str) -> Result<PathBuf, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid experiment id: {}", id));
+    }
+    Ok(project
+        .join(METADATA_DIR)
+        .join(EXPERIMENTS_DIR)
+        .join(format!("{}.json", id)))
+}
+
+/// Save an experiment, replacing any earlier version of it
+pub fn save(project: &Path, experiment: &Experiment) -> Result<(), String> {
+    let dir = metadata_dir(project)?.join(EXPERIMENTS_DIR);
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create experiments directory: {}", e))?;
+
+    let content = serde_json::to_string_pretty(experiment)
+        .map_err(|e| format!("Failed to serialize experiment: {}", e))?;
+    atomic::write_atomic(&experiment_path(project, &experiment.id)?, content, false)
+        .map_err(|e| format!("Failed to write experiment: {}", e))
+}
+
+/// Load one experiment of a project
+pub fn load(project: &Path, id: &str) -> Result<Experiment, String> {
+    let path = experiment_path(project, id)?;
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read experiment {}: {}", id, e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse experiment {}: {}", id, e))
+}
+
+/// All experiments of a project, newest first
+///
+/// Files that can't be read are skipped.
+pub fn list(project: &Path) -> Result<Vec<Experiment>, String> {
+    let dir = project.join(METADATA_DIR).join(EXPERIMENTS_DIR);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let entries =
+        fs::read_dir(&dir).map_err(|e| format!("Failed to read experiments directory: {}", e))?;
+    let mut experiments: Vec<Experiment> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let id = name.strip_suffix(".json")?;
+            load(project, id).ok()
+        })
+        .collect();
+    experiments.sort_by(|a, b| b.id.cmp(&a.id));
+
+    Ok(experiments)
+}
+
+/// Delete an experiment of a project
+pub fn delete(project: &Path, id: &str) -> Result<(), String> {
+    let path = experiment_path(project, id)?;
+    fs::remove_file(&path).map_err(|e| format!("Failed to delete experiment {}: {}", id, e))
+}
+
+/// Write an experiment as a comparison sheet: a row per sampled file with a column of
+/// captions per variant, plus a column of caption lengths per variant
+///
+/// Returns the path of the written file.
+pub fn write_csv(experiment: &Experiment, destination: &Path) -> Result<PathBuf, String> {
+    let mut header = vec!["file".to_string()];
+    header.extend(experiment.variants.iter().map(|v| csv_field(&v.name)));
+    header.extend(
+        experiment
+            .variants
+            .iter()
+            .map(|v| csv_field(&format!("{} (words)", v.name))),
+    );
+
+    let mut content = header.join(",");
+    content.push('\n');
+    for sample in &experiment.samples {
+        let captions = (0..experiment.variants.len()).map(|i| {
+            sample
+                .captions
+                .get(i)
+                .map(String::as_str)
+                .unwrap_or_default()
+        });
+
+        let mut row = vec![csv_field(&sample.relative_path)];
+        row.extend(captions.clone().map(csv_field));
+        row.extend(captions.map(|c| c.split_whitespace().count().to_string()));
+        content.push_str(&row.join(","));
+        content.push('\n');
+    }
+
+    let path = destination.join(format!("experiment-{}.csv", experiment.id));
+    atomic::write_atomic(&path, content, false)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
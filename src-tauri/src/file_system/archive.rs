@@ -0,0 +1,432 @@
+use chrono::Local;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use super::commands::{scan_media_files, MediaFile};
+use super::space;
+use crate::captions::format::{self as caption_format, CaptionFormat, CaptionStore, MANIFEST_FILE};
+use crate::jobs::manager::Job;
+use crate::project::{settings as project_settings, METADATA_DIR, PROJECT_FILE};
+
+/// Prefix of the file names given to exported archives
+const EXPORT_PREFIX: &str = "spacecat_export_";
+
+/// Prefix of the file names given to archives of captions only
+const CAPTIONS_PREFIX: &str = "spacecat_captions_";
+
+/// An archive of a project's captions, without the media
+#[derive(Debug, Serialize)]
+pub struct CaptionExport {
+    pub path: String,
+    /// Number of captions in the archive
+    pub captions: usize,
+}
+
+/// Outcome of restoring a project from an exported archive
+#[derive(Debug, Serialize)]
+pub struct ArchiveImport {
+    /// Path of the recreated project
+    pub project: String,
+    pub name: String,
+    pub media_count: usize,
+    /// Whether the archive carried the project's settings
+    pub has_project_file: bool,
+    /// Media files without a caption
+    pub uncaptioned: Vec<String>,
+    /// Caption sidecars without a media file
+    pub orphan_captions: Vec<String>,
+}
+
+/// Project name for an archive, dropping the prefix and timestamp added on export
+fn archive_name(zip_path: &Path) -> String {
+    let stem = zip_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = stem.strip_prefix(EXPORT_PREFIX).unwrap_or(&stem);
+
+    // Exports end in `_YYYYMMDD_HHMMSS`
+    let parts: Vec<&str> = name.rsplitn(3, '_').collect();
+    let is_timestamp = parts.len() == 3
+        && parts[0].len() == 6
+        && parts[1].len() == 8
+        && parts[..2]
+            .iter()
+            .all(|p| p.chars().all(|c| c.is_ascii_digit()));
+
+    let name = if is_timestamp { parts[2] } else { name };
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() {
+        "Imported project".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// A free project directory for `name`: "name", "name 2", ...
+pub(crate) fn unique_project_dir(working_dir: &Path, name: &str) -> PathBuf {
+    let mut counter = 1;
+    loop {
+        let candidate = if counter == 1 {
+            working_dir.join(name)
+        } else {
+            working_dir.join(format!("{} {}", name, counter))
+        };
+
+        if !candidate.exists() {
+            return candidate;
+        }
+
+        counter += 1;
+    }
+}
+
+/// Paths of the archive's entries, checked to stay inside the extraction directory
+///
+/// Archives made by zipping the project folder itself (instead of exporting it) have
+/// everything under a single top-level directory, which is stripped. App-managed state
+/// is left out: undo trusts the paths its journal names, so an archive could otherwise
+/// plant a journal pointing anywhere.
+fn entry_paths(archive: &mut ZipArchive<fs::File>) -> Result<Vec<Option<PathBuf>>, String> {
+    let mut paths = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read archive: {}", e))?;
+        let path = entry
+            .enclosed_name()
+            .map(|p| p.to_path_buf())
+            .ok_or_else(|| format!("Archive entry has an unsafe path: {}", entry.name()))?;
+        paths.push((path, entry.is_dir()));
+    }
+
+    let mut top_level = HashSet::new();
+    let mut files_at_root = false;
+    for (path, is_dir) in &paths {
+        let mut components = path.components();
+        if let Some(first) = components.next() {
+            top_level.insert(first.as_os_str().to_os_string());
+            if !is_dir && components.next().is_none() {
+                files_at_root = true;
+            }
+        }
+    }
+    let wrapper = if !files_at_root && top_level.len() == 1 {
+        top_level.into_iter().next().map(PathBuf::from)
+    } else {
+        None
+    };
+
+    Ok(paths
+        .into_iter()
+        .map(|(path, is_dir)| {
+            let path = match &wrapper {
+                Some(wrapper) => path.strip_prefix(wrapper).ok()?.to_path_buf(),
+                None => path,
+            };
+            // Directories are created as their files are extracted
+            if is_dir || path.as_os_str().is_empty() || path.starts_with(METADATA_DIR) {
+                None
+            } else {
+                Some(path)
+            }
+        })
+        .collect())
+}
+
+/// Extract an archive's files into `target`
+fn extract(
+    archive: &mut ZipArchive<fs::File>,
+    paths: &[Option<PathBuf>],
+    target: &Path,
+) -> Result<(), String> {
+    for (i, relative) in paths.iter().enumerate() {
+        let relative = match relative {
+            Some(relative) => relative,
+            None => continue,
+        };
+
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read archive: {}", e))?;
+        let destination = target.join(relative);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let mut file = fs::File::create(&destination)
+            .map_err(|e| format!("Failed to create {}: {}", relative.display(), e))?;
+        io::copy(&mut entry, &mut file)
+            .map_err(|e| format!("Failed to extract {}: {}", relative.display(), e))?;
+    }
+
+    Ok(())
+}
+
+/// Caption sidecars in the project root that don't belong to any media file
+fn orphan_captions(project: &Path, media: &[MediaFile]) -> Vec<String> {
+    let store = CaptionStore::open(project);
+    let extension = match store.format().extension() {
+        Some(extension) => extension,
+        None => return Vec::new(),
+    };
+
+    let sidecars: HashSet<String> = media
+        .iter()
+        .filter_map(|file| store.sidecar_path(Path::new(&file.path)))
+        .filter_map(|sidecar| Some(sidecar.file_name()?.to_string_lossy().to_string()))
+        .collect();
+
+    let mut orphans: Vec<String> = fs::read_dir(project)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .map(|e| e.to_string_lossy().eq_ignore_ascii_case(extension))
+                    .unwrap_or(false)
+        })
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            if name == PROJECT_FILE || name == MANIFEST_FILE {
+                return None;
+            }
+            (!sidecars.contains(&name)).then_some(name)
+        })
+        .collect();
+    orphans.sort();
+    orphans
+}
+
+/// Recreate a project under `working_dir` from an archive made by export
+///
+/// The archive must contain at least one media file. Its `project.json` is kept (with
+/// the name updated to the new project directory) when present. If anything fails the
+/// partially restored project is removed again.
+pub fn import_archive(zip_path: &Path, working_dir: &Path) -> Result<ArchiveImport, String> {
+    let file = fs::File::open(zip_path).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("Not a valid ZIP archive: {}", e))?;
+
+    let paths = entry_paths(&mut archive)?;
+    if paths.iter().all(|p| p.is_none()) {
+        return Err("The archive is empty".to_string());
+    }
+
+    let mut uncompressed = 0;
+    for i in 0..archive.len() {
+        if let Ok(entry) = archive.by_index(i) {
+            uncompressed += entry.size();
+        }
+    }
+
+    fs::create_dir_all(working_dir).map_err(|e| e.to_string())?;
+    space::ensure_space(working_dir, uncompressed)?;
+
+    let project = unique_project_dir(working_dir, &archive_name(zip_path));
+    fs::create_dir_all(&project).map_err(|e| e.to_string())?;
+
+    let result = extract(&mut archive, &paths, &project).and_then(|_| inspect(&project));
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&project);
+    }
+    result
+}
+
+/// Validate a freshly extracted project and report its missing caption pairs
+fn inspect(project: &Path) -> Result<ArchiveImport, String> {
+    let media = scan_media_files(project)?;
+    if media.is_empty() {
+        return Err("The archive contains no media files".to_string());
+    }
+
+    let has_project_file = project.join(PROJECT_FILE).is_file();
+    let mut settings = project_settings::load_settings(project)?;
+    settings.name = project
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if settings.created.is_none() {
+        settings.created = Some(project_settings::now_timestamp());
+    }
+    project_settings::save_settings(project, &settings)?;
+
+    let uncaptioned = media
+        .iter()
+        .filter(|m| !m.has_caption)
+        .map(|m| m.name.clone())
+        .collect();
+
+    Ok(ArchiveImport {
+        project: project.to_string_lossy().to_string(),
+        name: settings.name,
+        media_count: media.len(),
+        has_project_file,
+        uncaptioned,
+        orphan_captions: orphan_captions(project, &media),
+    })
+}
+
+fn zip_error(e: impl std::fmt::Display) -> String {
+    format!("Failed to write caption archive: {}", e)
+}
+
+/// Write the captions of `media` into a new ZIP archive, returning how many there were
+///
+/// They are read from `store` and named and formatted the way `target` would store them.
+fn write_caption_zip(
+    job: &Job,
+    zip_path: &Path,
+    media: &[MediaFile],
+    store: &mut CaptionStore,
+    target: &CaptionStore,
+) -> Result<usize, String> {
+    let file = fs::File::create(zip_path).map_err(zip_error)?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let total = media.len() as u64;
+    let mut manifest = BTreeMap::new();
+    let mut count = 0;
+    for (index, file) in media.iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some(file.relative_path.clone()));
+
+        let Some(caption) = store.read(Path::new(&file.path))? else {
+            continue;
+        };
+        let relative = file.relative_path.replace('\\', "/");
+        match target.sidecar_path(Path::new(&file.path)) {
+            Some(sidecar) => {
+                let content = match target.format() {
+                    CaptionFormat::Json => {
+                        serde_json::to_string_pretty(&serde_json::json!({ "caption": caption }))
+                            .map_err(zip_error)?
+                    }
+                    _ => caption,
+                };
+                let name = Path::new(&relative)
+                    .with_file_name(sidecar.file_name().unwrap_or_default())
+                    .to_string_lossy()
+                    .to_string();
+                zip.start_file(name, options).map_err(zip_error)?;
+                zip.write_all(content.as_bytes()).map_err(zip_error)?;
+            }
+            None => {
+                manifest.insert(relative, caption);
+            }
+        }
+        count += 1;
+    }
+
+    if !manifest.is_empty() {
+        let name = target
+            .format()
+            .manifest_file(None)
+            .unwrap_or_else(|| MANIFEST_FILE.to_string());
+        let content = caption_format::serialize_manifest(Path::new(&name), &manifest)?;
+        zip.start_file(name, options).map_err(zip_error)?;
+        zip.write_all(content.as_bytes()).map_err(zip_error)?;
+    }
+    zip.finish().map_err(zip_error)?;
+
+    job.progress(total, total, None);
+    Ok(count)
+}
+
+/// Package the captions of a project, without its media, into a ZIP archive in
+/// `destination`
+///
+/// Sidecars keep the relative paths of their media, so the archive can be unpacked
+/// over an earlier export. Captions are written in `format` (by default the project's)
+/// and with a `language` its captions become the primary ones. Media files in
+/// `excluded` are left out. A failed or cancelled export removes the archive again.
+pub fn export_captions(
+    job: &Job,
+    project: &Path,
+    destination: &Path,
+    format: Option<CaptionFormat>,
+    language: Option<&str>,
+    excluded: &HashSet<PathBuf>,
+) -> Result<CaptionExport, String> {
+    let media: Vec<MediaFile> = scan_media_files(project)?
+        .into_iter()
+        .filter(|file| !excluded.contains(Path::new(&file.relative_path)))
+        .collect();
+    let store = CaptionStore::open(project);
+    let format = format.unwrap_or(store.format());
+    let mut store = store.with_language(language);
+
+    let name = project
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let timestamp = Local::now().format("%Y%m%d_%H%M%S");
+    let zip_path = destination.join(format!("{}{}_{}.zip", CAPTIONS_PREFIX, name, timestamp));
+
+    let target = CaptionStore::with_format(project, format);
+    match write_caption_zip(job, &zip_path, &media, &mut store, &target) {
+        Ok(captions) => Ok(CaptionExport {
+            path: zip_path.to_string_lossy().to_string(),
+            captions,
+        }),
+        Err(e) => {
+            let _ = fs::remove_file(&zip_path);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_archive(path: &Path, entries: &[&str]) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+        for name in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(b"[]").unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn extracted(entries: &[&str]) -> (tempfile::TempDir, Vec<Option<PathBuf>>) {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("import.zip");
+        write_archive(&zip_path, entries);
+
+        let mut archive = ZipArchive::new(fs::File::open(&zip_path).unwrap()).unwrap();
+        let paths = entry_paths(&mut archive).unwrap();
+        extract(&mut archive, &paths, &dir.path().join("project")).unwrap();
+        (dir, paths)
+    }
+
+    #[test]
+    fn skips_project_metadata() {
+        let (dir, paths) = extracted(&[
+            "photo.png",
+            ".spacecat/journal.json",
+            ".spacecat/undo/1/photo.png",
+        ]);
+        assert_eq!(paths, vec![Some(PathBuf::from("photo.png")), None, None]);
+
+        let project = dir.path().join("project");
+        assert!(project.join("photo.png").is_file());
+        assert!(!project.join(METADATA_DIR).exists());
+    }
+
+    #[test]
+    fn skips_project_metadata_below_a_wrapper_directory() {
+        let (dir, paths) = extracted(&["dataset/photo.png", "dataset/.spacecat/journal.json"]);
+        assert_eq!(paths, vec![Some(PathBuf::from("photo.png")), None]);
+        assert!(!dir.path().join("project").join(METADATA_DIR).exists());
+    }
+}
@@ -0,0 +1,60 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Distinguishes temp files of concurrent writes to the same target
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Hidden temp file next to the target, so the final rename stays on one volume
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "file".to_string());
+    let counter = TEMP_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+    path.with_file_name(format!(".{}.{}-{}.tmp", name, std::process::id(), counter))
+}
+
+/// Write a file atomically: the target holds either its old or its new contents,
+/// never a truncated mix, even if the app crashes mid-write
+///
+/// The data goes to a temp file in the same directory which is then renamed over the
+/// target, keeping the target's permissions. With `sync` set the data (and on Unix the
+/// directory entry) is flushed to disk before returning.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>, sync: bool) -> io::Result<()> {
+    let temp = temp_path(path);
+
+    let result = (|| {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)?;
+        file.write_all(contents.as_ref())?;
+        if sync {
+            file.sync_all()?;
+        }
+        drop(file);
+
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+
+        fs::rename(&temp, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        return result;
+    }
+
+    #[cfg(unix)]
+    if sync {
+        if let Some(parent) = path.parent() {
+            fs::File::open(parent)?.sync_all()?;
+        }
+    }
+
+    Ok(())
+}
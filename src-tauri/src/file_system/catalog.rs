@@ -0,0 +1,233 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use super::archive::unique_project_dir;
+use super::copy::{self, ImportMode};
+use super::ignore_rules::IgnoreRules;
+use super::merge::{collect_files, RenamedFile};
+use super::space;
+use crate::captions::format::CaptionStore;
+use crate::jobs::manager::Job;
+use crate::media::formats;
+use crate::media::metadata::{self, SidecarText};
+use crate::project::attribution::Attribution;
+use crate::project::{index, settings as project_settings};
+
+/// Outcome of importing the exported folders of a photo catalog
+#[derive(Debug, Serialize)]
+pub struct CatalogImport {
+    /// Path of the new project
+    pub project: String,
+    pub name: String,
+    pub media_count: usize,
+    /// Files given an initial caption from their metadata
+    pub captioned: usize,
+    /// Files whose name was already taken by a file of another folder, from their
+    /// path in the export to their name in the project
+    pub renamed: Vec<RenamedFile>,
+}
+
+/// The XMP sidecar of an exported file: `IMG_1.xmp` as Lightroom and Capture One write
+/// it, or `IMG_1.jpg.xmp` as darktable does
+fn find_sidecar(media: &Path) -> Option<PathBuf> {
+    let mut with_extension = media.as_os_str().to_os_string();
+    with_extension.push(".xmp");
+    [
+        media.with_extension("xmp"),
+        media.with_extension("XMP"),
+        PathBuf::from(with_extension),
+    ]
+    .into_iter()
+    .find(|path| path.is_file())
+}
+
+/// Name of a file in the flattened project: its own name, or prefixed with its folders
+/// (then numbered) if another folder already had a file of that name
+fn flat_name(relative: &Path, taken: &mut HashSet<String>) -> String {
+    let name = relative
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if taken.insert(name.to_lowercase()) {
+        return name;
+    }
+
+    let folders: Vec<String> = relative
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.components())
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    let prefixed = format!("{}_{}", folders.join("_"), name);
+    if taken.insert(prefixed.to_lowercase()) {
+        return prefixed;
+    }
+
+    let path = Path::new(&prefixed);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut counter = 2;
+    loop {
+        let candidate = format!("{}_{}{}", stem, counter, extension);
+        if taken.insert(candidate.to_lowercase()) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// Metadata of an exported file: its XMP sidecar if there is one, otherwise what is
+/// embedded in the file itself
+fn read_text(media: &Path) -> SidecarText {
+    if let Some(sidecar) = find_sidecar(media) {
+        match metadata::read_xmp_sidecar(&sidecar) {
+            Ok(text) => return text,
+            Err(e) => warn!("Skipping sidecar {}: {}", sidecar.display(), e),
+        }
+    }
+
+    match metadata::read_embedded(media) {
+        Ok(embedded) => SidecarText {
+            description: embedded.description,
+            keywords: embedded.keywords,
+            ..Default::default()
+        },
+        Err(e) => {
+            warn!("Failed to read the metadata of {}: {}", media.display(), e);
+            SidecarText::default()
+        }
+    }
+}
+
+/// Create a project under `working_dir` from the folders a photo catalog (Lightroom,
+/// Capture One) exported
+///
+/// Media files of every subfolder are brought into the project root. Titles,
+/// descriptions and, with `include_keywords`, keywords from the XMP sidecars (or the
+/// files' own metadata) become initial captions; each file's original path, creator
+/// and rights are kept as its attribution. The job's progress advances by one per
+/// file. If anything fails the partially created project is removed again.
+pub fn import_catalog(
+    job: &Job,
+    source: &Path,
+    working_dir: &Path,
+    mode: ImportMode,
+    include_keywords: bool,
+) -> Result<CatalogImport, String> {
+    let mut relative_paths = Vec::new();
+    collect_files(
+        source,
+        source,
+        &IgnoreRules::load(source),
+        &mut relative_paths,
+    )?;
+    relative_paths.retain(|relative| formats::media_type(relative).is_some());
+    relative_paths.sort();
+    if relative_paths.is_empty() {
+        return Err("The folder contains no media files".to_string());
+    }
+
+    let name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().trim().to_string())
+        .filter(|n| !n.is_empty() && !n.starts_with('.'))
+        .unwrap_or_else(|| "Imported catalog".to_string());
+    fs::create_dir_all(working_dir).map_err(|e| e.to_string())?;
+    space::ensure_space(
+        working_dir,
+        copy::required_space(source, mode, &HashSet::new())?,
+    )?;
+
+    let project = unique_project_dir(working_dir, &name);
+    fs::create_dir_all(&project).map_err(|e| e.to_string())?;
+
+    let result = import_files(
+        job,
+        source,
+        &project,
+        &relative_paths,
+        mode,
+        include_keywords,
+    );
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&project);
+    }
+    result
+}
+
+fn import_files(
+    job: &Job,
+    source: &Path,
+    project: &Path,
+    relative_paths: &[PathBuf],
+    mode: ImportMode,
+    include_keywords: bool,
+) -> Result<CatalogImport, String> {
+    let mut settings = project_settings::load_settings(project)?;
+    settings.name = project
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    settings.created = Some(project_settings::now_timestamp());
+    project_settings::save_settings(project, &settings)?;
+
+    let mut store = CaptionStore::open(project);
+    let mut taken = HashSet::new();
+    let mut captioned = 0;
+    let mut renamed = Vec::new();
+    let mut provenance = Vec::new();
+
+    job.progress(
+        0,
+        relative_paths.len() as u64,
+        Some("Importing catalog export".to_string()),
+    );
+    for relative in relative_paths {
+        job.check_cancelled()?;
+        let original = source.join(relative);
+        let flat = flat_name(relative, &mut taken);
+        let target = project.join(&flat);
+        copy::copy_file(mode, &original, &target)?;
+
+        if relative.file_name() != Some(flat.as_ref()) {
+            renamed.push(RenamedFile {
+                from: relative.to_string_lossy().replace('\\', "/"),
+                to: flat.clone(),
+            });
+        }
+
+        let text = read_text(&original);
+        if let Some(caption) = text.embedded().caption(include_keywords) {
+            store.write(&target, &caption)?;
+            captioned += 1;
+        }
+
+        provenance.push((
+            flat,
+            Attribution {
+                license: text.rights,
+                source: Some(original.to_string_lossy().to_string()),
+                creator: text.creator,
+            },
+        ));
+        job.advance(1);
+    }
+    index::set_file_attributions(project, &provenance)?;
+
+    Ok(CatalogImport {
+        project: project.to_string_lossy().to_string(),
+        name: settings.name,
+        media_count: relative_paths.len(),
+        captioned,
+        renamed,
+    })
+}
@@ -1,17 +1,55 @@
 use chrono::{DateTime, Local, Utc};
 use fs_extra::dir::{get_size, CopyOptions};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs;
 use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use tempfile::tempdir;
 use tauri::AppHandle;
-use tauri::Manager;
+use tauri::{Emitter, Manager, WebviewWindow};
 use tauri_plugin_dialog::DialogExt;
 use tauri_plugin_fs::FsExt;
 use tauri_plugin_opener::OpenerExt;
+use tracing::{debug, error, info, warn};
 use zip::{write::FileOptions, ZipWriter};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::project::marks::{FileMark, MarkFilter};
+use crate::project::{
+    index as project_index, lock as project_lock, project_root_for, settings as project_settings,
+    METADATA_DIR, PROJECT_FILE,
+};
+use super::archive::{self, ArchiveImport, CaptionExport};
+use super::atomic;
+use super::catalog::{self, CatalogImport};
+use super::copy::{self, ImportMode};
+use super::paths;
+use super::dedup::{self, DedupMode, DedupReport};
+use super::ignore_rules::IgnoreRules;
+use super::in_use;
+use super::leftovers::{self, CleanupReport};
+use super::location::{self, WorkingRootInfo, WorkingRootMove};
+use super::merge::{self, ConflictPolicy, MergeReport};
+use super::naming::{self, ExportNaming};
+use super::sync::{self, SyncReport};
+use super::watch;
+use super::space;
+use crate::captions::format::{self as caption_format, CaptionFormat, CaptionStore};
+use crate::captions::encoding as caption_encoding;
+use crate::captions::git as caption_git;
+use crate::captions::history as caption_history;
+use crate::captions::modified as caption_modified;
+use crate::jobs::manager::{self as jobs, Job, JobKind};
+use crate::jobs::pool;
+use crate::media::{formats, metadata, probe};
+use crate::semantic::subjects::SubjectCount;
+use crate::undo::journal::{self, Operation, OperationKind};
+
+/// Number of caption characters included in a media file listing
+const CAPTION_PREVIEW_CHARS: usize = 120;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MediaFile {
     pub id: String,
     pub name: String,
@@ -19,6 +57,23 @@ pub struct MediaFile {
     pub relative_path: String,
     pub file_type: String,
     pub has_caption: bool,
+    pub size: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Duration in seconds (videos and animated images)
+    pub duration: Option<f64>,
+    /// Number of frames (animated GIFs and WebPs only)
+    #[serde(default)]
+    pub frame_count: Option<u32>,
+    pub modified: Option<String>,
+    /// Start of the caption, filled in when listing a directory
+    pub caption_preview: Option<String>,
+    /// Rating, flag and favorite bit, filled in when listing a directory
+    #[serde(default)]
+    pub mark: Option<FileMark>,
+    /// Faces and persons found by subject detection, filled in when listing a directory
+    #[serde(default)]
+    pub subjects: Option<SubjectCount>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -44,10 +99,51 @@ pub async fn select_directory(app: AppHandle) -> Result<String, String> {
 }
 
 /// Duplicate a directory to create a working copy
+///
+/// Runs as an import job, reporting its progress through job events as well as
+/// `duplicate-progress` events; `cancel_duplicate_directory` (or cancelling the job)
+/// stops the copy and removes the partial working copy. `mode` selects a space-saving
+/// import (hard links, reflinks or links to the originals) instead of a full copy.
+/// `dedup` looks for byte-identical files first, reporting them and optionally
+/// importing only one copy; the report is kept with the project. An import cut short
+/// by the app exiting is listed by `list_interrupted_jobs`, to be started again.
 #[tauri::command]
-pub async fn duplicate_directory(source: String, destination: String) -> Result<String, String> {
+pub async fn duplicate_directory(
+    app: AppHandle,
+    source: String,
+    destination: String,
+    mode: Option<ImportMode>,
+    dedup: Option<DedupMode>,
+) -> Result<String, String> {
+    let description = format!("Import {}", source);
+    let events = app.clone();
+
+    jobs::run(&app, JobKind::Import, description, move |job| {
+        let params = serde_json::json!({
+            "source": source,
+            "destination": destination,
+            "mode": mode,
+            "dedup": dedup,
+        });
+        job.make_resumable(params, &[]);
+        duplicate_directory_job(job, &events, &source, &destination, mode, dedup)
+    })
+    .await
+}
+
+fn duplicate_directory_job(
+    job: &Job,
+    app: &AppHandle,
+    source: &str,
+    destination: &str,
+    mode: Option<ImportMode>,
+    dedup: Option<DedupMode>,
+) -> Result<String, String> {
+    let mode = mode.unwrap_or_default();
+    let dedup_mode = dedup.unwrap_or_default();
+
     // Create the destination directory if it doesn't exist
-    let dest_path = Path::new(&destination);
+    let dest_path = Path::new(destination);
     if !dest_path.exists() {
         fs::create_dir_all(dest_path).map_err(|e| e.to_string())?;
     } else {
@@ -58,10 +154,10 @@ pub async fn duplicate_directory(source: String, destination: String) -> Result<
     }
 
     // Debug: Print source and destination
-    println!("Duplicating directory from {} to {}", source, destination);
+    info!("Duplicating directory from {} to {}", source, destination);
 
     // Get the source directory name
-    let source_path = Path::new(&source);
+    let source_path = Path::new(source);
     let source_name = source_path
         .file_name()
         .ok_or_else(|| "Invalid source directory".to_string())?;
@@ -69,34 +165,259 @@ pub async fn duplicate_directory(source: String, destination: String) -> Result<
     // Create the full destination path including the source directory name
     let full_dest_path = dest_path.join(source_name);
 
-    // Copy options
-    let options = CopyOptions::new().overwrite(true).copy_inside(true);
+    // Find exact duplicates before copying so skipped files are never written
+    job.progress(0, 0, Some("Looking for duplicates".to_string()));
+    let report = match dedup::find_duplicates(source_path, dedup_mode, job.cancel_flag()) {
+        Ok(report) => report,
+        Err(e) if e == copy::CANCELLED => return Err("Import cancelled".to_string()),
+        Err(e) => return Err(e),
+    };
+    if !report.groups.is_empty() {
+        info!(
+            "Found {} duplicate files ({} bytes)",
+            report.duplicate_files, report.duplicate_bytes
+        );
+    }
+
+    // Only emit when the percentage changes to avoid flooding the frontend
+    let mut last_percent = None;
+    let skipped = report.skipped_paths();
+    space::ensure_space(dest_path, copy::required_space(source_path, mode, &skipped)?)?;
+
+    let result = copy::copy_tree(
+        source_path,
+        &full_dest_path,
+        mode,
+        &skipped,
+        job.cancel_flag(),
+        |progress| {
+            let percent = (progress.copied_bytes * 100)
+                .checked_div(progress.total_bytes)
+                .unwrap_or(100);
+
+            if last_percent != Some(percent) {
+                last_percent = Some(percent);
+                job.progress(
+                    progress.copied_bytes,
+                    progress.total_bytes,
+                    Some(progress.current_file.clone()),
+                );
+                let _ = app.emit("duplicate-progress", progress.clone());
+            }
+        },
+    );
 
     // Copy the directory
-    match fs_extra::dir::copy(&source, &destination, &options) {
+    match result {
         Ok(_) => {
-            println!("Successfully copied directory to {}", destination);
+            debug!("Successfully copied directory to {}", destination);
+
+            // Remember where the project came from so it travels with the project
+            if let Err(e) = project_settings::record_source_directory(&full_dest_path, source, mode)
+            {
+                warn!("Failed to write project settings: {}", e);
+            }
+
+            if dedup_mode != DedupMode::Off {
+                if let Err(e) = dedup::save_report(&full_dest_path, &report) {
+                    warn!("Failed to write dedup report: {}", e);
+                }
+            }
 
             // Return the full destination path where files were copied
             let result_path = full_dest_path.to_string_lossy().to_string();
-            println!("Using working directory: {}", result_path);
+            debug!("Using working directory: {}", result_path);
 
             Ok(result_path)
         }
         Err(e) => {
-            println!("Error copying directory: {}", e);
-            Err(e.to_string())
+            error!("Error copying directory: {}", e);
+
+            // Don't leave a half-copied project behind
+            let _ = fs::remove_dir_all(&full_dest_path);
+
+            if e == copy::CANCELLED {
+                Err("Import cancelled".to_string())
+            } else {
+                Err(e)
+            }
         }
     }
 }
 
+/// Get the duplicate report written when a project was imported, if any
+#[tauri::command]
+pub async fn get_dedup_report(app: AppHandle, path: String) -> Result<Option<DedupReport>, String> {
+    let project = validate_project_directory(&app, &path)?;
+    dedup::load_report(&project)
+}
+
+/// Import the files of a folder into an existing project, keeping what's already there
+///
+/// `policy` decides what happens to files whose name is taken: skip them, overwrite
+/// the project's copy or import them under a new name. The merge can be undone.
+#[tauri::command]
+pub async fn merge_import_directory(
+    app: AppHandle,
+    source: String,
+    project: String,
+    policy: Option<ConflictPolicy>,
+    mode: Option<ImportMode>,
+) -> Result<MergeReport, String> {
+    let project_path = validate_project_directory(&app, &project)?;
+    let source_path = Path::new(&source);
+    if !source_path.is_dir() {
+        return Err(format!("Directory does not exist: {}", source));
+    }
+
+    if project_path.starts_with(source_path) || source_path.starts_with(&project_path) {
+        return Err("Cannot import a project into itself".to_string());
+    }
+
+    let mode = mode.unwrap_or_default();
+    space::ensure_space(
+        &project_path,
+        copy::required_space(source_path, mode, &HashSet::new())?,
+    )?;
+
+    let policy = policy.unwrap_or_default();
+    let report = merge::merge_into_project(source_path, &project_path, policy, mode)?;
+    info!(
+        "Merged {} into {}: {} added, {} renamed, {} overwritten, {} skipped",
+        source,
+        project,
+        report.added.len(),
+        report.renamed.len(),
+        report.overwritten.len(),
+        report.skipped.len()
+    );
+
+    Ok(report)
+}
+
+/// Compare a project with the folder it was imported from
+///
+/// Reports files added, modified or removed in the source since the last sync; with
+/// `apply` set the changes are brought into the project as one undoable operation.
+/// Files changed on both sides are reported as conflicts and left alone.
+#[tauri::command]
+pub async fn sync_project(app: AppHandle, path: String, apply: bool) -> Result<SyncReport, String> {
+    let project = validate_project_directory(&app, &path)?;
+    let settings = project_settings::load_settings(&project)?;
+
+    let source = settings
+        .source_directory
+        .ok_or_else(|| "Project has no source folder to sync with".to_string())?;
+    let source_path = Path::new(&source);
+    if !source_path.is_dir() {
+        return Err(format!("Source folder no longer exists: {}", source));
+    }
+
+    sync::sync_with_source(&project, source_path, settings.import_mode, apply)
+}
+
+/// Remove or restore the backup and temporary files that failed crops and trims left
+/// in a project
+///
+/// Runs for every project at startup too. Refused while a crop or trim is running,
+/// since its files would look like leftovers.
+#[tauri::command]
+pub async fn clean_up_edit_leftovers(
+    app: AppHandle,
+    path: String,
+) -> Result<CleanupReport, String> {
+    let project = validate_project_directory(&app, &path)?;
+    let editing = jobs::list()
+        .iter()
+        .any(|job| job.finished.is_none() && matches!(job.kind, JobKind::Trim | JobKind::Crop));
+    if editing {
+        return Err("Wait for the running crops and trims to finish".to_string());
+    }
+
+    Ok(leftovers::clean_project(&project))
+}
+
+/// Recreate a project from a ZIP archive made by export
+///
+/// The project gets the archive's name without the export timestamp (numbered if that
+/// name is taken). Media without captions and captions without media are reported.
+#[tauri::command]
+pub async fn import_export_archive(app: AppHandle, zip_path: String) -> Result<ArchiveImport, String> {
+    let zip = Path::new(&zip_path);
+    if !zip.is_file() {
+        return Err(format!("Archive does not exist: {}", zip_path));
+    }
+
+    archive::import_archive(zip, &working_root(&app)?)
+}
+
+/// Create a project from the folders a photo catalog (Lightroom, Capture One) exported
+///
+/// Subfolders are flattened into the project. Titles, descriptions and, unless
+/// `include_keywords` is false, keywords from the XMP sidecars become initial captions,
+/// and each file's original path is kept as its attribution source.
+#[tauri::command]
+pub async fn import_catalog_export(
+    app: AppHandle,
+    source: String,
+    mode: Option<ImportMode>,
+    include_keywords: Option<bool>,
+) -> Result<CatalogImport, String> {
+    let source_path = PathBuf::from(&source);
+    if !source_path.is_dir() {
+        return Err(format!("Directory does not exist: {}", source));
+    }
+
+    let working_dir = working_root(&app)?;
+    if source_path.starts_with(&working_dir) {
+        return Err("Cannot import a folder of the working directory".to_string());
+    }
+
+    let description = format!("Import catalog export {}", source);
+    jobs::run(&app, JobKind::Import, description, move |job| {
+        catalog::import_catalog(
+            job,
+            &source_path,
+            &working_dir,
+            mode.unwrap_or_default(),
+            include_keywords.unwrap_or(true),
+        )
+    })
+    .await
+}
+
+/// Cancel the running directory duplication
+#[tauri::command]
+pub fn cancel_duplicate_directory() -> Result<(), String> {
+    jobs::cancel_kind(JobKind::Import);
+    Ok(())
+}
+
 /// Register a directory as an asset scope for direct media access
+///
+/// Registering happens whenever a project is opened, so the project is also locked
+/// against changes from other app windows and command line runs until the window
+/// opens another one. Fails if another window or process holds it.
 #[tauri::command]
-pub async fn register_working_directory(app: AppHandle, path: String) -> Result<(), String> {
+pub async fn register_working_directory(
+    app: AppHandle,
+    window: WebviewWindow,
+    path: String,
+) -> Result<(), String> {
+    let project = Path::new(&path);
+    project_lock::acquire(project, "app window", Some(window.label()))?;
+    project_lock::release_others(window.label(), Some(project));
+
     // The correct method is fs_scope() with allow_directory
     // Based on the documentation, allow_directory takes a path and a boolean for recursive
-    match app.fs_scope().allow_directory(Path::new(&path), false) {
-        Ok(_) => Ok(()),
+    match app.fs_scope().allow_directory(project, false) {
+        Ok(_) => {
+            // Registering happens whenever a project is opened
+            if let Err(e) = project_settings::touch_last_opened(project) {
+                warn!("Failed to update project settings: {}", e);
+            }
+            Ok(())
+        }
         Err(e) => Err(e.to_string()),
     }
 }
@@ -104,24 +425,118 @@ pub async fn register_working_directory(app: AppHandle, path: String) -> Result<
 /// Read a caption file
 #[tauri::command]
 pub async fn read_caption_file(path: String) -> Result<String, String> {
-    match fs::read_to_string(path) {
+    match caption_encoding::read_caption_text(&paths::long_path(Path::new(&path))) {
         Ok(content) => Ok(content),
         Err(e) => Err(e.to_string()),
     }
 }
 
 /// Write content to a caption file
+///
+/// The write is atomic; `sync` additionally flushes it to disk before returning. With
+/// `expected`, the content as it was last read, a file changed on disk since, e.g. in a
+/// text editor, is not overwritten; the error holds the file as it is now.
+#[tauri::command]
+pub async fn write_caption_file(
+    path: String,
+    content: String,
+    sync: Option<bool>,
+    expected: Option<String>,
+) -> Result<(), String> {
+    let caption_path = &paths::long_path(Path::new(&path));
+    project_settings::ensure_writable(caption_path)?;
+    let content = caption_encoding::normalize_line_endings(&content);
+    if expected.is_some() {
+        let current = caption_encoding::read_caption_text(caption_path).ok();
+        caption_modified::ensure_unmodified(
+            caption_path,
+            expected.as_deref(),
+            current.as_deref(),
+            &content,
+        )?;
+    }
+
+    // Record the previous caption so an accidental overwrite can be undone
+    let mut operation = None;
+    if caption_path.is_file() && !journal::recently_recorded_caption(caption_path) {
+        let previous = caption_encoding::read_caption_text(caption_path).unwrap_or_default();
+        if previous != content {
+            let name = caption_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let mut op = Operation::begin(
+                caption_path,
+                OperationKind::CaptionOverwrite,
+                format!("Edit caption {}", name),
+            )?;
+            op.snapshot(caption_path)?;
+            operation = Some(op);
+        }
+    }
+
+    write_caption(caption_path, &content, sync.unwrap_or(false))?;
+
+    if let Some(op) = operation {
+        op.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Write several caption files as a single undoable operation
 #[tauri::command]
-pub async fn write_caption_file(path: String, content: String) -> Result<(), String> {
+pub async fn write_caption_files(captions: Vec<(String, String)>) -> Result<(), String> {
+    let first = match captions.first() {
+        Some((path, _)) => PathBuf::from(path),
+        None => return Ok(()),
+    };
+
+    let mut operation = Operation::begin(
+        &first,
+        OperationKind::CaptionOverwrite,
+        format!("Write {} captions", captions.len()),
+    )?;
+
+    for (path, _) in &captions {
+        operation.snapshot(Path::new(path))?;
+    }
+
+    for (path, content) in &captions {
+        write_caption(Path::new(path), content, false)?;
+    }
+
+    operation.commit()?;
+    caption_git::commit_if_enabled(
+        &project_root_for(&first),
+        &format!("Write {} captions", captions.len()),
+    );
+
+    Ok(())
+}
+
+/// Helper function to write a caption file, creating its directory if needed
+fn write_caption(path: &Path, content: &str, sync: bool) -> Result<(), String> {
     // Ensure the directory exists
-    if let Some(parent) = Path::new(&path).parent() {
+    if let Some(parent) = path.parent() {
         if !parent.exists() {
             fs::create_dir_all(parent).map_err(|e| e.to_string())?;
         }
     }
 
+    let content = &caption_encoding::normalize_line_endings(content);
+
+    // Keep the text being replaced in the caption's version history
+    if let Ok(previous) = caption_encoding::read_caption_text(path) {
+        if previous != *content {
+            if let Err(e) = caption_history::record_version(path, &previous) {
+                warn!("Failed to record caption history for {}: {}", path.display(), e);
+            }
+        }
+    }
+
     // Write the file
-    match fs::write(path, content) {
+    match atomic::write_atomic(path, content, sync) {
         Ok(_) => Ok(()),
         Err(e) => Err(e.to_string()),
     }
@@ -140,62 +555,283 @@ pub async fn select_export_directory(app: AppHandle) -> Result<String, String> {
 }
 
 /// Export the working directory to a specified destination
+///
+/// Runs as an export job that can be cancelled; a cancelled export removes what it
+/// had written so far. An export cut short by the app exiting is listed by
+/// `list_interrupted_jobs`, to be started again. With a `filter` only the files whose
+/// marks match are exported, e.g. the favorites or those rated 4 stars and up. Captions
+/// in every language are exported unless `caption_language` picks one, whose captions
+/// then become the exported captions. `naming` renames the exported media, with their
+/// caption sidecars, and records the old names in a `renamed.json` at the export root.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn export_directory(
+    app: AppHandle,
     source_dir: String,
     destination_dir: String,
     as_zip: bool,
+    caption_format: Option<CaptionFormat>,
+    embed_captions: Option<bool>,
+    filter: Option<MarkFilter>,
+    caption_language: Option<String>,
+    naming: Option<ExportNaming>,
+) -> Result<String, String> {
+    let description = format!("Export {}", source_dir);
+    let embed_captions = embed_captions.unwrap_or(false);
+    let project = PathBuf::from(&source_dir);
+
+    let exported = jobs::run(&app, JobKind::Export, description, move |job| {
+        let params = serde_json::json!({
+            "source_dir": source_dir,
+            "destination_dir": destination_dir,
+            "as_zip": as_zip,
+            "caption_format": caption_format,
+            "embed_captions": embed_captions,
+            "filter": filter,
+            "caption_language": caption_language,
+            "naming": naming,
+        });
+        job.make_resumable(params, &[]);
+        export_directory_job(
+            job,
+            &source_dir,
+            &destination_dir,
+            as_zip,
+            caption_format,
+            embed_captions,
+            filter.as_ref(),
+            caption_language.as_deref(),
+            naming.unwrap_or_default(),
+        )
+    })
+    .await?;
+    project_settings::record_export(&project, &exported);
+    Ok(exported)
+}
+
+/// Export only the captions of a project, as a small ZIP archive, e.g. to update a
+/// dataset already copied to a training machine
+///
+/// Runs as an export job. Caption sidecars keep the relative paths of their media;
+/// `caption_format`, `filter` and `caption_language` work as in `export_directory`.
+#[tauri::command]
+pub async fn export_caption_files(
+    app: AppHandle,
+    source_dir: String,
+    destination_dir: String,
+    caption_format: Option<CaptionFormat>,
+    filter: Option<MarkFilter>,
+    caption_language: Option<String>,
+) -> Result<CaptionExport, String> {
+    let project = validate_project_directory(&app, &source_dir)?;
+    let destination = PathBuf::from(&destination_dir);
+    if !destination.is_dir() {
+        return Err(format!("Directory does not exist: {}", destination_dir));
+    }
+    if let Some(language) = &caption_language {
+        caption_format::validate_language(language)?;
+    }
+
+    let description = format!("Export captions of {}", source_dir);
+    let source = project.clone();
+    let exported = jobs::run(&app, JobKind::Export, description, move |job| {
+        let excluded = match &filter {
+            Some(filter) => excluded_by_filter(&source, filter)?,
+            None => HashSet::new(),
+        };
+        archive::export_captions(
+            job,
+            &source,
+            &destination,
+            caption_format,
+            caption_language.as_deref(),
+            &excluded,
+        )
+    })
+    .await?;
+    project_settings::record_export(&project, &exported.path);
+    Ok(exported)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn export_directory_job(
+    job: &Job,
+    source_dir: &str,
+    destination_dir: &str,
+    as_zip: bool,
+    caption_format: Option<CaptionFormat>,
+    embed_captions: bool,
+    filter: Option<&MarkFilter>,
+    caption_language: Option<&str>,
+    naming: ExportNaming,
 ) -> Result<String, String> {
     // Generate a timestamp for the export directory/file name
     let timestamp = Local::now().format("%Y%m%d_%H%M%S").to_string();
-    let source_path = Path::new(&source_dir);
+    let project_path = Path::new(source_dir);
 
     // Get the source directory name to use as part of the export name
-    let source_name = source_path
+    let source_name = project_path
         .file_name()
         .ok_or_else(|| "Invalid source directory".to_string())?
         .to_string_lossy();
 
+    // Convert captions, pick a caption language, embed them in the images, leave out
+    // filtered files or rename files in a staging copy when requested, leaving the
+    // project untouched
+    let convert_to =
+        caption_format.filter(|&format| format != CaptionStore::open(project_path).format());
+    let excluded = match filter {
+        Some(filter) => excluded_by_filter(project_path, filter)?,
+        None => HashSet::new(),
+    };
+    let mut _staging = None;
+    let source_path = if convert_to.is_some()
+        || embed_captions
+        || !excluded.is_empty()
+        || caption_language.is_some()
+        || naming != ExportNaming::Original
+    {
+        let staging =
+            tempdir().map_err(|e| format!("Failed to create staging directory: {}", e))?;
+        space::ensure_space(staging.path(), space::dataset_size(project_path))?;
+        let staged = staging.path().join(source_name.as_ref());
+        job.progress(0, 0, Some("Staging files".to_string()));
+        copy::copy_tree(
+            project_path,
+            &staged,
+            ImportMode::Copy,
+            &excluded,
+            job.cancel_flag(),
+            |_| {},
+        )?;
+        if !excluded.is_empty() {
+            caption_format::remove_from_manifests(&staged, &excluded)?;
+        }
+        if let Some(language) = caption_language {
+            job.progress(0, 0, Some(format!("Picking {} captions", language)));
+            caption_format::promote_language(&staged, language)?;
+        }
+        if embed_captions {
+            let summary = metadata::embed_directory(job, &staged, None)?;
+            for (file, error) in &summary.failed {
+                warn!("Failed to embed the caption of {}: {}", file, error);
+            }
+        }
+        if let Some(format) = convert_to {
+            job.progress(0, 0, Some("Converting captions".to_string()));
+            caption_format::convert_directory(&staged, format, true)?;
+        }
+        if naming != ExportNaming::Original {
+            job.progress(0, 0, Some("Renaming files".to_string()));
+            naming::rename_files(job, &staged, naming)?;
+        }
+        _staging = Some(staging);
+        staged
+    } else {
+        project_path.to_path_buf()
+    };
+    let source_dir = source_path.to_string_lossy().to_string();
+
     // Create the export name using the timestamp
     let export_name = format!("spacecat_export_{}_{}", source_name, timestamp);
 
     // Create the full destination path
-    let dest_path = Path::new(&destination_dir);
+    let dest_path = Path::new(destination_dir);
+
+    // Media barely compresses, so a ZIP needs about as much space as a copy
+    let total_bytes = space::dataset_size(&source_path);
+    space::ensure_space(dest_path, total_bytes)?;
+    job.progress(0, total_bytes, None);
 
     if as_zip {
         // Export as a ZIP file
         let zip_filename = format!("{}.zip", export_name);
         let zip_path = dest_path.join(&zip_filename);
 
-        println!("Exporting to ZIP file: {}", zip_path.display());
+        info!("Exporting to ZIP file: {}", zip_path.display());
 
-        // Create the ZIP file
-        zip_directory(&source_dir, &zip_path.to_string_lossy())
-            .map_err(|e| format!("Failed to create ZIP file: {}", e))?;
+        // Create the ZIP file, removing it again if that fails or is cancelled
+        if let Err(e) = zip_directory(&source_dir, &zip_path.to_string_lossy(), job) {
+            let _ = fs::remove_file(&zip_path);
+            if e == jobs::CANCELLED {
+                return Err(e);
+            }
+            return Err(format!("Failed to create ZIP file: {}", e));
+        }
 
         Ok(zip_path.to_string_lossy().to_string())
     } else {
         // Export as a directory
         let export_dir = dest_path.join(&export_name);
 
-        println!("Exporting to directory: {}", export_dir.display());
+        info!("Exporting to directory: {}", export_dir.display());
 
         // Create the destination directory
         fs::create_dir_all(&export_dir).map_err(|e| e.to_string())?;
 
-        // Copy options
-        let options = CopyOptions::new().overwrite(true).copy_inside(true);
-
-        // Copy the directory contents
-        fs_extra::dir::copy(&source_dir, &export_dir, &options)
-            .map_err(|e| format!("Failed to copy directory: {}", e))?;
+        // Copy the directory contents, leaving out ignored files
+        let mut last_percent = None;
+        let result = copy::copy_tree(
+            &source_path,
+            &export_dir.join(source_name.as_ref()),
+            ImportMode::Copy,
+            &HashSet::new(),
+            job.cancel_flag(),
+            |progress| {
+                let percent = (progress.copied_bytes * 100)
+                    .checked_div(progress.total_bytes)
+                    .unwrap_or(100);
+                if last_percent != Some(percent) {
+                    last_percent = Some(percent);
+                    job.progress(
+                        progress.copied_bytes,
+                        progress.total_bytes,
+                        Some(progress.current_file.clone()),
+                    );
+                }
+            },
+        );
+        if let Err(e) = result {
+            let _ = fs::remove_dir_all(&export_dir);
+            if e == copy::CANCELLED {
+                return Err(jobs::CANCELLED.to_string());
+            }
+            return Err(format!("Failed to copy directory: {}", e));
+        }
 
         Ok(export_dir.to_string_lossy().to_string())
     }
 }
 
-/// Helper function to create a ZIP file from a directory
-fn zip_directory(src_dir: &str, zip_path: &str) -> Result<(), String> {
+/// Relative paths of the media files that don't match a filter, and of their
+/// caption sidecars in every language
+fn excluded_by_filter(project: &Path, filter: &MarkFilter) -> Result<HashSet<PathBuf>, String> {
+    let languages = caption_format::languages(project)?;
+    let stores: Vec<CaptionStore> = std::iter::once(None)
+        .chain(languages.iter().map(|l| Some(l.as_str())))
+        .map(|language| CaptionStore::open(project).with_language(language))
+        .collect();
+    let mut excluded = HashSet::new();
+
+    for file in project_index::list_files(project)? {
+        if filter.matches(file.mark.as_ref(), file.subjects.as_ref()) {
+            continue;
+        }
+        let media = PathBuf::from(&file.relative_path);
+        for store in &stores {
+            if let Some(sidecar) = store.sidecar_path(&media) {
+                excluded.insert(sidecar);
+            }
+        }
+        excluded.insert(media);
+    }
+
+    Ok(excluded)
+}
+
+/// Helper function to create a ZIP file from a directory, advancing the job's
+/// progress by the size of each file added
+pub(crate) fn zip_directory(src_dir: &str, zip_path: &str, job: &Job) -> Result<(), String> {
     let src_path = Path::new(src_dir);
     if !src_path.exists() || !src_path.is_dir() {
         return Err(format!("Source directory does not exist: {}", src_dir));
@@ -217,14 +853,27 @@ fn zip_directory(src_dir: &str, zip_path: &str) -> Result<(), String> {
     fn add_directory_to_zip(
         path: &Path,
         src_path: &Path,
+        rules: &IgnoreRules,
         zip: &mut ZipWriter<fs::File>,
         options: &FileOptions,
         buffer: &mut Vec<u8>,
+        job: &Job,
     ) -> Result<(), String> {
         for entry in fs::read_dir(path).map_err(|e| e.to_string())? {
+            job.check_cancelled()?;
+
             let entry = entry.map_err(|e| e.to_string())?;
             let path = entry.path();
 
+            // Skip app-managed state (undo backups, journal), ignored files and what
+            // failed edits left behind
+            if entry.file_name() == METADATA_DIR
+                || rules.is_ignored(&path, path.is_dir())
+                || leftovers::is_edit_leftover(&path)
+            {
+                continue;
+            }
+
             // Create a relative path for the ZIP file
             let name = path
                 .strip_prefix(src_path)
@@ -238,7 +887,7 @@ fn zip_directory(src_dir: &str, zip_path: &str) -> Result<(), String> {
                     .map_err(|e| format!("Failed to add directory to ZIP: {}", e))?;
 
                 // Recursively add contents
-                add_directory_to_zip(&path, src_path, zip, options, buffer)?;
+                add_directory_to_zip(&path, src_path, rules, zip, options, buffer, job)?;
             } else {
                 // Add file to ZIP
                 zip.start_file(name.to_string(), *options)
@@ -249,13 +898,23 @@ fn zip_directory(src_dir: &str, zip_path: &str) -> Result<(), String> {
                 buffer.clear();
                 file.read_to_end(buffer).map_err(|e| e.to_string())?;
                 zip.write_all(buffer).map_err(|e| e.to_string())?;
+                job.advance(buffer.len() as u64);
             }
         }
         Ok(())
     }
 
     // Start adding files to the ZIP
-    add_directory_to_zip(src_path, src_path, &mut zip, &options, &mut buffer)?;
+    let rules = IgnoreRules::load(src_path);
+    add_directory_to_zip(
+        src_path,
+        src_path,
+        &rules,
+        &mut zip,
+        &options,
+        &mut buffer,
+        job,
+    )?;
 
     // Finalize the ZIP file
     zip.finish()
@@ -264,7 +923,7 @@ fn zip_directory(src_dir: &str, zip_path: &str) -> Result<(), String> {
     Ok(())
 }
 
-/// List all project directories in the app data directory
+/// List all project directories in the working directory
 #[tauri::command]
 pub async fn list_project_directories(app: AppHandle) -> Result<Vec<ProjectDirectory>, String> {
     let mut project_dirs = Vec::new();
@@ -277,8 +936,8 @@ pub async fn list_project_directories(app: AppHandle) -> Result<Vec<ProjectDirec
         fs::create_dir_all(&app_data_dir).map_err(|e| e.to_string())?;
     }
 
-    // Specifically look for the 'spacecat-working' directory
-    let working_dir = app_data_dir.join("spacecat-working");
+    // The working directory may have been moved out of the app data directory
+    let working_dir = working_root(&app)?;
 
     // If the working directory doesn't exist, create it
     if !working_dir.exists() {
@@ -290,7 +949,7 @@ pub async fn list_project_directories(app: AppHandle) -> Result<Vec<ProjectDirec
     let entries = match fs::read_dir(&working_dir) {
         Ok(entries) => entries,
         Err(e) => {
-            println!("Error reading working directory: {}", e);
+            error!("Error reading working directory: {}", e);
             return Err(format!("Failed to read working directory: {}", e));
         }
     };
@@ -362,10 +1021,47 @@ pub async fn list_project_directories(app: AppHandle) -> Result<Vec<ProjectDirec
     Ok(project_dirs)
 }
 
-/// Delete a project directory
+/// Get the directory that holds all project working copies
+pub(crate) fn working_root(app: &AppHandle) -> Result<PathBuf, String> {
+    location::working_root(app)
+}
+
+/// Get the directory that holds all project working copies, and its free space
 #[tauri::command]
-pub async fn delete_project_directory(app: AppHandle, path: String) -> Result<(), String> {
-    let dir_path = Path::new(&path);
+pub async fn get_working_root(app: AppHandle) -> Result<WorkingRootInfo, String> {
+    location::info(&app)
+}
+
+/// Move the working directory, with every project, into another directory (e.g. on a
+/// larger drive)
+///
+/// The projects end up in a `spacecat-working` directory inside `destination`. Refused
+/// while other jobs run, since they work on paths inside the current working directory.
+#[tauri::command]
+pub async fn move_working_root(
+    app: AppHandle,
+    destination: String,
+) -> Result<WorkingRootMove, String> {
+    if jobs::list().iter().any(|job| job.finished.is_none()) {
+        return Err(
+            "Wait for the running jobs to finish before moving the working directory".to_string(),
+        );
+    }
+
+    // Open lock files would keep the old projects from being removed on Windows
+    project_lock::release_all();
+
+    let description = format!("Move working directory to {}", destination);
+    let events = app.clone();
+    jobs::run(&app, JobKind::Import, description, move |job| {
+        location::move_root(job, &events, Path::new(&destination))
+    })
+    .await
+}
+
+/// Validate that a path is an existing directory inside the working directory
+pub(crate) fn validate_project_directory(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let dir_path = Path::new(path);
 
     // Validate the path is a directory
     if !dir_path.exists() {
@@ -377,16 +1073,72 @@ pub async fn delete_project_directory(app: AppHandle, path: String) -> Result<()
     }
 
     // Additional security check - ensure it's within the working directory
-    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-    let working_dir = app_data_dir.join("spacecat-working");
+    let working_dir = working_root(app)?;
 
-    if !dir_path.starts_with(&working_dir) {
+    if !dir_path.starts_with(&working_dir)
+        || dir_path.components().any(|c| c == Component::ParentDir)
+    {
         return Err(format!(
             "Security error: Directory is outside the working directory: {}",
             path
         ));
     }
 
+    Ok(dir_path.to_path_buf())
+}
+
+/// Ensure a path is a file inside the working directory
+pub(crate) fn validate_media_file(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let file_path = Path::new(path);
+
+    if !file_path.is_file() {
+        return Err(format!("File does not exist: {}", path));
+    }
+
+    let working_dir = working_root(app)?;
+
+    if !file_path.starts_with(&working_dir)
+        || file_path.components().any(|c| c == Component::ParentDir)
+    {
+        return Err(format!(
+            "Security error: File is outside the working directory: {}",
+            path
+        ));
+    }
+
+    Ok(file_path.to_path_buf())
+}
+
+/// Validate a user-provided project name
+fn validate_project_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+
+    if name.is_empty() {
+        return Err("Project name cannot be empty".to_string());
+    }
+
+    if name == "." || name == ".." || name.starts_with('.') {
+        return Err(format!("Invalid project name: {}", name));
+    }
+
+    if name
+        .chars()
+        .any(|c| matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control())
+    {
+        return Err(format!("Project name contains invalid characters: {}", name));
+    }
+
+    Ok(name)
+}
+
+/// Delete a project directory
+#[tauri::command]
+pub async fn delete_project_directory(app: AppHandle, path: String) -> Result<(), String> {
+    let dir_path = validate_project_directory(&app, &path)?;
+    project_settings::ensure_writable(&dir_path)?;
+    // An open lock file can't be deleted on Windows
+    project_lock::release(&dir_path);
+
     // Delete the directory
     match fs::remove_dir_all(dir_path) {
         Ok(_) => Ok(()),
@@ -397,38 +1149,172 @@ pub async fn delete_project_directory(app: AppHandle, path: String) -> Result<()
 /// Open a project directory in the system's file explorer
 #[tauri::command]
 pub async fn open_project_directory(app: AppHandle, path: String) -> Result<(), String> {
-    let dir_path = Path::new(&path);
+    validate_project_directory(&app, &path)?;
 
-    // Validate the path is a directory
-    if !dir_path.exists() {
-        return Err(format!("Directory does not exist: {}", path));
-    }
+    // Use the opener plugin to open the directory
+    app.opener()
+        .open_path(path, None::<&str>)
+        .map_err(|e| format!("Failed to open directory: {}", e))
+}
 
-    if !dir_path.is_dir() {
-        return Err(format!("Path is not a directory: {}", path));
+/// Turn a project into a hot folder, or back into a plain one
+///
+/// While enabled, media files without a caption that are dropped into the project are
+/// announced with a `hot-folder-files` event once they're completely copied, so they can
+/// be captioned right away.
+#[tauri::command]
+pub async fn set_hot_folder(app: AppHandle, path: String, enabled: bool) -> Result<(), String> {
+    let project = validate_project_directory(&app, &path)?;
+    if enabled {
+        watch::start(app, &project)
+    } else {
+        watch::stop(&project);
+        Ok(())
     }
+}
 
-    // Additional security check - ensure it's within the working directory
-    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
-    let working_dir = app_data_dir.join("spacecat-working");
+/// List the projects that are hot folders
+#[tauri::command]
+pub async fn get_hot_folders() -> Result<Vec<String>, String> {
+    Ok(watch::watched())
+}
 
-    if !dir_path.starts_with(&working_dir) {
-        return Err(format!(
-            "Security error: Directory is outside the working directory: {}",
-            path
-        ));
-    }
+/// Show a media file selected in the system's file explorer (Explorer, Finder)
+#[tauri::command]
+pub async fn reveal_media_file(app: AppHandle, path: String) -> Result<(), String> {
+    let file_path = validate_media_file(&app, &path)?;
+
+    app.opener()
+        .reveal_item_in_dir(file_path)
+        .map_err(|e| format!("Failed to reveal file: {}", e))
+}
+
+/// Open a media file in the application the system associates with its type
+#[tauri::command]
+pub async fn open_with_default_app(app: AppHandle, path: String) -> Result<(), String> {
+    validate_media_file(&app, &path)?;
 
-    // Use the opener plugin to open the directory
     app.opener()
         .open_path(path, None::<&str>)
-        .map_err(|e| format!("Failed to open directory: {}", e))
+        .map_err(|e| format!("Failed to open file: {}", e))
+}
+
+/// Rename a project directory, returning its new path
+#[tauri::command]
+pub async fn rename_project(
+    app: AppHandle,
+    window: WebviewWindow,
+    path: String,
+    new_name: String,
+) -> Result<String, String> {
+    let dir_path = validate_project_directory(&app, &path)?;
+    project_settings::ensure_writable(&dir_path)?;
+    let new_name = validate_project_name(&new_name)?;
+
+    let new_path = working_root(&app)?.join(new_name);
+    if new_path == dir_path {
+        return Ok(path);
+    }
+
+    if new_path.exists() {
+        return Err(format!("A project named \"{}\" already exists", new_name));
+    }
+
+    // Opening the project again locks it under its new path
+    project_lock::release(&dir_path);
+
+    if let Err(e) = in_use::retry(|| fs::rename(&dir_path, &new_path)) {
+        // The project stays open under its old path
+        if let Err(lock_error) =
+            project_lock::acquire(&dir_path, "app window", Some(window.label()))
+        {
+            warn!(
+                "Failed to lock {} again: {}",
+                dir_path.display(),
+                lock_error
+            );
+        }
+        return Err(in_use::error_message(e, "rename", &dir_path, |e| {
+            format!("Failed to rename project: {}", e)
+        }));
+    }
+
+    // Undo snapshots name files by absolute path
+    if let Err(e) = journal::relocate(&new_path, &dir_path) {
+        warn!(
+            "Failed to update the operation journal of {}: {}",
+            new_path.display(),
+            e
+        );
+    }
+
+    // Keep the name in project.json in sync with the directory
+    if new_path.join(PROJECT_FILE).exists() {
+        let mut settings = project_settings::load_settings(&new_path)?;
+        settings.name = new_name.to_string();
+        project_settings::save_settings(&new_path, &settings)?;
+    }
+
+    Ok(new_path.to_string_lossy().to_string())
+}
+
+/// Clone a project directory under a new unique name, returning the new path
+#[tauri::command]
+pub async fn clone_project(app: AppHandle, path: String) -> Result<String, String> {
+    let dir_path = validate_project_directory(&app, &path)?;
+    let working_dir = working_root(&app)?;
+
+    let name = dir_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| "Invalid project directory".to_string())?;
+
+    // Find a free name: "name copy", "name copy 2", ...
+    let mut counter = 1;
+    let new_path = loop {
+        let candidate = if counter == 1 {
+            format!("{} copy", name)
+        } else {
+            format!("{} copy {}", name, counter)
+        };
+
+        let candidate_path = working_dir.join(&candidate);
+        if !candidate_path.exists() {
+            break candidate_path;
+        }
+
+        counter += 1;
+    };
+
+    fs::create_dir_all(&new_path).map_err(|e| e.to_string())?;
+
+    let options = CopyOptions::new().overwrite(true).content_only(true);
+    if let Err(e) = fs_extra::dir::copy(&dir_path, &new_path, &options) {
+        let _ = fs::remove_dir_all(&new_path);
+        return Err(format!("Failed to clone project: {}", e));
+    }
+
+    // Undo history refers to files of the original project
+    let copied_metadata = new_path.join(METADATA_DIR);
+    if copied_metadata.exists() {
+        let _ = fs::remove_dir_all(copied_metadata);
+    }
+
+    let mut settings = project_settings::load_settings(&new_path)?;
+    settings.name = new_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    settings.created = Some(project_settings::now_timestamp());
+    project_settings::save_settings(&new_path, &settings)?;
+
+    Ok(new_path.to_string_lossy().to_string())
 }
 
 /// Duplicate a media file and its associated caption file
 #[tauri::command]
 pub async fn duplicate_media_file(path: String) -> Result<MediaFile, String> {
-    let file_path = Path::new(&path);
+    let file_path = &paths::long_path(Path::new(&path));
     
     // Validate the file exists
     if !file_path.exists() {
@@ -444,14 +1330,15 @@ pub async fn duplicate_media_file(path: String) -> Result<MediaFile, String> {
     let file_stem = file_path.file_stem().ok_or_else(|| "Invalid file name".to_string())?;
     let file_ext = file_path.extension().ok_or_else(|| "Invalid file extension".to_string())?;
     
-    // Check if a caption file exists
-    let caption_path = file_path.with_extension("txt");
-    let has_caption = caption_path.exists();
+    // Check if a caption exists in the project's caption format
+    let mut store = CaptionStore::for_media(file_path);
+    let caption = store.read(file_path)?;
+    let has_caption = caption.is_some();
+    let uses_sidecars = store.format().extension().is_some();
     
     // Generate a new unique filename with suffix
     let mut counter = 1;
     let mut new_file_path: PathBuf;
-    let mut new_caption_path: PathBuf;
     
     loop {
         let new_name = format!("{}_{}.{}", file_stem.to_string_lossy(), counter, file_ext.to_string_lossy());
@@ -459,10 +1346,9 @@ pub async fn duplicate_media_file(path: String) -> Result<MediaFile, String> {
         
         // Check if the new file already exists
         if !new_file_path.exists() {
-            // If we have a caption, also check if the new caption file would exist
-            if has_caption {
-                new_caption_path = new_file_path.with_extension("txt");
-                if !new_caption_path.exists() {
+            // If we have a caption sidecar, also check if the new sidecar would exist
+            if has_caption && uses_sidecars {
+                if !store.caption_path(&new_file_path).exists() {
                     break; // Both file paths are available
                 }
             } else {
@@ -477,28 +1363,19 @@ pub async fn duplicate_media_file(path: String) -> Result<MediaFile, String> {
     // Copy the media file
     fs::copy(file_path, &new_file_path).map_err(|e| format!("Failed to copy media file: {}", e))?;
     
-    // Copy the caption file if it exists
-    let new_has_caption = if has_caption {
-        let new_caption_path = new_file_path.with_extension("txt");
-        fs::copy(&caption_path, &new_caption_path).map_err(|e| format!("Failed to copy caption file: {}", e))?;
-        true
-    } else {
-        false
+    // Copy the caption if it exists
+    let new_has_caption = match &caption {
+        Some(caption) => {
+            store
+                .write(&new_file_path, caption)
+                .map_err(|e| format!("Failed to copy caption file: {}", e))?;
+            true
+        }
+        None => false,
     };
     
     // Determine file type
-    let file_type = if let Some(ext) = file_path.extension() {
-        let ext_str = ext.to_string_lossy().to_lowercase();
-        if ["jpg", "jpeg", "png", "gif", "webp"].contains(&ext_str.as_str()) {
-            "image"
-        } else if ["mp4", "webm", "mov", "avi"].contains(&ext_str.as_str()) {
-            "video"
-        } else {
-            "unknown"
-        }
-    } else {
-        "unknown"
-    };
+    let file_type = formats::media_type(file_path).unwrap_or("unknown");
     
     // Get the new file name
     let new_name = new_file_path
@@ -507,7 +1384,7 @@ pub async fn duplicate_media_file(path: String) -> Result<MediaFile, String> {
         .unwrap_or_default();
     
     // Get the new path as a string
-    let new_path_str = new_file_path.to_string_lossy().to_string();
+    let new_path_str = paths::display_path(&new_file_path);
     
     // Get the relative path from the directory
     let dir_path = file_dir.to_path_buf();
@@ -519,6 +1396,9 @@ pub async fn duplicate_media_file(path: String) -> Result<MediaFile, String> {
     // Create a unique ID
     let id = format!("{}-{}", file_type, new_name);
     
+    let (size, modified) = file_details(&new_file_path);
+    let info = probe::probe_media(&new_file_path, file_type);
+
     // Return the new MediaFile
     Ok(MediaFile {
         id,
@@ -527,13 +1407,22 @@ pub async fn duplicate_media_file(path: String) -> Result<MediaFile, String> {
         relative_path,
         file_type: file_type.to_string(),
         has_caption: new_has_caption,
+        size,
+        width: info.width,
+        height: info.height,
+        duration: info.duration,
+        frame_count: info.frame_count,
+        modified,
+        caption_preview: caption.as_deref().map(caption_preview),
+        mark: None,
+        subjects: None,
     })
 }
 
 /// Delete a media file and its associated caption file
 #[tauri::command]
 pub async fn delete_media_file(path: String) -> Result<(), String> {
-    let file_path = Path::new(&path);
+    let file_path = &paths::long_path(Path::new(&path));
 
     // Validate the file exists
     if !file_path.exists() {
@@ -545,21 +1434,40 @@ pub async fn delete_media_file(path: String) -> Result<(), String> {
     }
 
     // Try to find the associated caption file
-    let caption_path = file_path.with_extension("txt");
+    let mut store = CaptionStore::for_media(file_path);
+    let caption_path = store.caption_path(file_path);
 
-    // Delete the media file
-    match fs::remove_file(file_path) {
+    let file_name = file_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut operation = Operation::begin(
+        file_path,
+        OperationKind::Delete,
+        format!("Delete {}", file_name),
+    )?;
+
+    // Delete the media file by moving it into the undo area
+    match operation.snapshot_and_remove(file_path) {
         Ok(_) => {
-            println!("Successfully deleted media file: {}", path);
-
-            // Try to delete the caption file if it exists
-            if caption_path.exists() {
-                match fs::remove_file(&caption_path) {
-                    Ok(_) => println!(
+            debug!("Successfully deleted media file: {}", path);
+
+            // Try to delete the caption if it exists
+            if store.format().is_manifest() {
+                // Captions live in the shared manifest, so back it up and drop the entry
+                if let Err(e) = operation
+                    .snapshot(&caption_path)
+                    .and_then(|_| store.remove(file_path))
+                {
+                    warn!("Failed to remove caption from manifest: {}", e);
+                }
+            } else if caption_path.exists() {
+                match operation.snapshot_and_remove(&caption_path) {
+                    Ok(_) => debug!(
                         "Successfully deleted caption file: {}",
                         caption_path.display()
                     ),
-                    Err(e) => println!(
+                    Err(e) => warn!(
                         "Warning: Failed to delete caption file {}: {}",
                         caption_path.display(),
                         e
@@ -567,16 +1475,359 @@ pub async fn delete_media_file(path: String) -> Result<(), String> {
                 }
             }
 
+            operation.commit()?;
+
             Ok(())
         }
         Err(e) => Err(format!("Failed to delete media file: {}", e)),
     }
 }
 
+/// Result of deleting several media files at once
+#[derive(Debug, Serialize)]
+pub struct DeleteSummary {
+    pub deleted: usize,
+    /// Captions deleted along with the media, counting each language
+    pub captions: usize,
+    /// Size of the deleted media files
+    pub bytes: u64,
+}
+
+/// Move a media file and its captions in every language into an operation's backups,
+/// returning the number of captions
+fn remove_with_captions(
+    operation: &mut Operation,
+    stores: &mut [CaptionStore],
+    media: &Path,
+) -> Result<usize, String> {
+    let mut captions = 0;
+    for store in stores.iter_mut() {
+        if !store.has_caption(media) {
+            continue;
+        }
+        let caption_path = store.caption_path(media);
+        if store.format().is_manifest() {
+            // Captions live in the shared manifest, so back it up and drop the entry
+            operation.snapshot(&caption_path)?;
+            store.remove(media)?;
+        } else {
+            operation.snapshot_and_remove(&caption_path)?;
+        }
+        captions += 1;
+    }
+    operation.snapshot_and_remove(media)?;
+    Ok(captions)
+}
+
+/// Delete several media files of a project, with their captions, as a single operation
+///
+/// Every path must be a media file inside the working directory, all of the same
+/// project, or nothing is deleted. The files are moved to the project's undo area, so
+/// one undo brings them all back; if one of them can't be moved, those already moved
+/// are put back.
+#[tauri::command]
+pub async fn delete_media_files(
+    app: AppHandle,
+    paths: Vec<String>,
+) -> Result<DeleteSummary, String> {
+    if paths.is_empty() {
+        return Err("No files to delete".to_string());
+    }
+
+    // Validate every path before touching any file
+    let working_dir = working_root(&app)?;
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    for path in &paths {
+        let file_path = Path::new(path);
+        if !file_path.starts_with(&working_dir)
+            || file_path.components().any(|c| c == Component::ParentDir)
+        {
+            return Err(format!(
+                "Security error: File is outside the working directory: {}",
+                path
+            ));
+        }
+        if !file_path.is_file() {
+            return Err(format!("File does not exist: {}", path));
+        }
+        if formats::media_type(file_path).is_none() {
+            return Err(format!("Not a media file: {}", path));
+        }
+        if seen.insert(file_path.to_path_buf()) {
+            files.push(file_path.to_path_buf());
+        }
+    }
+
+    let project = project_root_for(&files[0]);
+    if let Some(other) = files.iter().find(|file| project_root_for(file) != project) {
+        return Err(format!(
+            "Files of different projects can't be deleted together: {}",
+            other.display()
+        ));
+    }
+
+    let languages = caption_format::languages(&project)?;
+    let mut stores: Vec<CaptionStore> = std::iter::once(None)
+        .chain(languages.iter().map(|language| Some(language.as_str())))
+        .map(|language| CaptionStore::open(&project).with_language(language))
+        .collect();
+
+    let mut operation = Operation::begin(
+        &project,
+        OperationKind::Delete,
+        format!("Delete {} files", files.len()),
+    )?;
+    let mut summary = DeleteSummary {
+        deleted: 0,
+        captions: 0,
+        bytes: 0,
+    };
+    for file in &files {
+        let file_path = &paths::long_path(file);
+        let size = file_path.metadata().map(|m| m.len()).unwrap_or(0);
+        match remove_with_captions(&mut operation, &mut stores, file_path) {
+            Ok(captions) => {
+                summary.deleted += 1;
+                summary.captions += captions;
+                summary.bytes += size;
+            }
+            Err(e) => {
+                operation.rollback().map_err(|restore| {
+                    format!("{} (restoring the files deleted so far failed: {})", e, restore)
+                })?;
+                return Err(format!("Nothing was deleted: {}", e));
+            }
+        }
+    }
+    operation.commit()?;
+
+    Ok(summary)
+}
+
+/// Rename a media file and its captions in every language within an operation
+fn rename_with_captions(
+    operation: &mut Operation,
+    stores: &mut [CaptionStore],
+    media: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    for store in stores.iter_mut() {
+        if !store.has_caption(media) {
+            continue;
+        }
+        if store.format().is_manifest() {
+            // Captions live in the shared manifest, so back it up and move the entry
+            operation.snapshot(&store.caption_path(media))?;
+            let caption = store.read(media)?.unwrap_or_default();
+            store.remove(media)?;
+            store.write(target, &caption)?;
+        } else {
+            operation.rename(&store.caption_path(media), &store.caption_path(target))?;
+        }
+    }
+    operation.rename(media, target)
+}
+
+/// Rename a media file of a project, with its captions in every language, as one
+/// undoable operation, returning its new path
+///
+/// `new_name` is the new file name, which must keep a media extension. Marks move
+/// along with the file.
+#[tauri::command]
+pub async fn rename_media_file(
+    app: AppHandle,
+    path: String,
+    new_name: String,
+) -> Result<String, String> {
+    let media = validate_media_file(&app, &path)?;
+    let new_name = new_name.trim();
+    if new_name.is_empty()
+        || new_name.starts_with('.')
+        || new_name.chars().any(|c| {
+            matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control()
+        })
+    {
+        return Err(format!("Invalid file name: {}", new_name));
+    }
+    let target = media.with_file_name(new_name);
+    if target == media {
+        return Ok(path);
+    }
+    if formats::media_type(&target).is_none() {
+        return Err(format!("Not a media file name: {}", new_name));
+    }
+    if target.exists() {
+        return Err(format!("A file named {} already exists", new_name));
+    }
+
+    let project = project_root_for(&media);
+    let languages = caption_format::languages(&project)?;
+    let mut stores: Vec<CaptionStore> = std::iter::once(None)
+        .chain(languages.iter().map(|language| Some(language.as_str())))
+        .map(|language| CaptionStore::open(&project).with_language(language))
+        .collect();
+    let old_name = media
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut operation = Operation::begin(
+        &project,
+        OperationKind::Rename,
+        format!("Rename {} to {}", old_name, new_name),
+    )?;
+
+    if let Err(e) = rename_with_captions(&mut operation, &mut stores, &media, &target) {
+        operation
+            .rollback()
+            .map_err(|restore| format!("{} (putting the files back failed: {})", e, restore))?;
+        return Err(e);
+    }
+    operation.commit()?;
+
+    let relative = |file: &Path| {
+        file.strip_prefix(&project)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let (old_relative, new_relative) = (relative(&media), relative(&target));
+    let moved_mark = project_index::marks(&project)
+        .map(|mut marks| marks.remove(&old_relative))
+        .and_then(|mark| match mark {
+            Some(mark) => {
+                project_index::update_marks(&project, &[new_relative], |m| *m = mark.clone())?;
+                project_index::update_marks(&project, &[old_relative], |m| *m = FileMark::default())
+            }
+            None => Ok(()),
+        });
+    if let Err(e) = moved_mark {
+        warn!("Failed to move the marks of {}: {}", old_name, e);
+    }
+
+    Ok(paths::display_path(&target))
+}
+
 /// List all media files in a directory
+///
+/// Unlike the internal scan, the listing includes dimensions, video durations, a
+/// caption preview and the files' marks. Image sizes come from file headers and video
+/// probes are cached. With a `filter` only the files whose marks match are listed, and
+/// with a `language` captions and previews are those in that language.
 #[tauri::command]
-pub async fn list_directory_files(directory: String) -> Result<Vec<MediaFile>, String> {
+pub async fn list_directory_files(
+    directory: String,
+    filter: Option<MarkFilter>,
+    language: Option<String>,
+) -> Result<Vec<MediaFile>, String> {
     let dir_path = Path::new(&directory);
+
+    // The project index only probes and reads what changed since the last listing
+    let mut files = match project_index::list_files(dir_path) {
+        Ok(files) => files,
+        Err(e) => {
+            warn!("Listing {} without the project index: {}", directory, e);
+            list_without_index(dir_path)?
+        }
+    };
+
+    if let Some(filter) = filter {
+        files.retain(|file| filter.matches(file.mark.as_ref(), file.subjects.as_ref()));
+    }
+
+    if let Some(language) = language {
+        preview_language(dir_path, &mut files, &language)?;
+    }
+
+    Ok(files)
+}
+
+/// List what changed in a directory since it was last listed: the added and changed
+/// files, and the relative paths of the removed ones
+///
+/// Unlike a full listing, nothing is sent back for unchanged files. Fails when the
+/// project index can't be used, in which case the directory should be listed again.
+#[tauri::command]
+pub async fn refresh_directory(
+    directory: String,
+    language: Option<String>,
+) -> Result<project_index::DirectoryChanges, String> {
+    let dir_path = Path::new(&directory);
+    let mut changes = project_index::changed_files(dir_path)?;
+
+    if let Some(language) = language {
+        preview_language(dir_path, &mut changes.added, &language)?;
+        preview_language(dir_path, &mut changes.changed, &language)?;
+    }
+
+    Ok(changes)
+}
+
+/// Show the captions of another language in listed files
+fn preview_language(
+    dir_path: &Path,
+    files: &mut [MediaFile],
+    language: &str,
+) -> Result<(), String> {
+    caption_format::validate_language(language)?;
+    let mut store = CaptionStore::open(dir_path).with_language(Some(language));
+    for file in files {
+        let caption = store.read(Path::new(&file.path)).unwrap_or(None);
+        file.has_caption = caption.is_some();
+        file.caption_preview = caption.as_deref().map(caption_preview);
+    }
+    Ok(())
+}
+
+/// List the media files of a directory by probing every one of them
+fn list_without_index(dir_path: &Path) -> Result<Vec<MediaFile>, String> {
+    let mut files = scan_media_files(dir_path)?;
+
+    pool::install(|| {
+        files.par_iter_mut().for_each(|file| {
+            let info = probe::probe_media(Path::new(&file.path), &file.file_type);
+            file.width = info.width;
+            file.height = info.height;
+            file.duration = info.duration;
+            file.frame_count = info.frame_count;
+        })
+    });
+
+    let mut store = CaptionStore::open(dir_path);
+    for file in files.iter_mut().filter(|f| f.has_caption) {
+        if let Ok(Some(caption)) = store.read(Path::new(&file.path)) {
+            file.caption_preview = Some(caption_preview(&caption));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Size and formatted modification time of a file
+fn file_details(path: &Path) -> (u64, Option<String>) {
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            let modified = metadata.modified().ok().map(|time| {
+                let datetime: DateTime<Utc> = time.into();
+                datetime.format("%Y-%m-%d %H:%M:%S").to_string()
+            });
+            (metadata.len(), modified)
+        }
+        Err(_) => (0, None),
+    }
+}
+
+/// First characters of a caption, for display in file listings
+pub(crate) fn caption_preview(caption: &str) -> String {
+    let caption = caption.trim();
+    match caption.char_indices().nth(CAPTION_PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", caption[..end].trim_end()),
+        None => caption.to_string(),
+    }
+}
+
+/// Scan a directory for media files (shared by listing and batch operations)
+pub(crate) fn scan_media_files(dir_path: &Path) -> Result<Vec<MediaFile>, String> {
+    let directory = dir_path.display();
     if !dir_path.exists() {
         return Err(format!("Directory does not exist: {}", directory));
     }
@@ -586,14 +1837,18 @@ pub async fn list_directory_files(directory: String) -> Result<Vec<MediaFile>, S
     }
 
     let mut media_files = Vec::new();
+    let mut store = CaptionStore::open(dir_path);
 
     // No debug logging
 
     // Check if the directory is empty
-    let entries = match fs::read_dir(dir_path) {
+    // Read through the extended-length form so long file paths can be opened on Windows
+    let read_path = paths::extended_path(dir_path);
+    let rules = IgnoreRules::load(&read_path);
+    let entries = match fs::read_dir(&read_path) {
         Ok(entries) => entries,
         Err(e) => {
-            println!("Error reading directory: {}", e);
+            error!("Error reading directory: {}", e);
             return Err(format!("Failed to read directory: {}", e));
         }
     };
@@ -608,23 +1863,25 @@ pub async fn list_directory_files(directory: String) -> Result<Vec<MediaFile>, S
                 continue;
             }
 
-            // No debug logging
+            if rules.is_ignored(&path, false) {
+                continue;
+            }
 
-            if let Some(extension) = path.extension() {
-                let ext = extension.to_string_lossy().to_lowercase();
+            // Backups and temporary files of a crop or trim that didn't finish
+            if leftovers::is_edit_leftover(&path) {
+                continue;
+            }
 
-                // No debug logging
+            // The frontend couldn't address a name that isn't valid Unicode
+            if !paths::is_unicode_name(&path) {
+                warn!("Skipping file with a non-Unicode name: {}", path.display());
+                continue;
+            }
 
-                // Check if it's a media file
-                let file_type = if ["jpg", "jpeg", "png", "gif", "webp"].contains(&ext.as_str()) {
-                    "image"
-                } else if ["mp4", "webm", "mov", "avi"].contains(&ext.as_str()) {
-                    "video"
-                } else {
-                    // Skip silently
-                    continue; // Skip non-media files
-                };
+            // No debug logging
 
+            // Check if it's a media file, by extension or else by its contents
+            if let Some(file_type) = formats::media_type(&path) {
                 // Get the file name
                 let name = path
                     .file_name()
@@ -632,17 +1889,18 @@ pub async fn list_directory_files(directory: String) -> Result<Vec<MediaFile>, S
                     .unwrap_or_default();
 
                 // Get the path as a string
-                let path_str = path.to_string_lossy().to_string();
+                let path_str = paths::display_path(&path);
 
                 // Get the relative path from the directory
                 let relative_path = path
-                    .strip_prefix(dir_path)
+                    .strip_prefix(&read_path)
                     .map(|p| p.to_string_lossy().to_string())
                     .unwrap_or_default();
 
-                // Check if a caption file exists
-                let caption_path = path.with_extension("txt");
-                let has_caption = caption_path.exists();
+                // Check if a caption exists in the project's caption format
+                let has_caption = store.has_caption(&path);
+
+                let (size, modified) = file_details(&path);
 
                 // Create a unique ID
                 let id = format!("{}-{}", file_type, name);
@@ -657,9 +1915,16 @@ pub async fn list_directory_files(directory: String) -> Result<Vec<MediaFile>, S
                     relative_path,
                     file_type: file_type.to_string(),
                     has_caption,
+                    size,
+                    width: None,
+                    height: None,
+                    duration: None,
+                    frame_count: None,
+                    modified,
+                    caption_preview: None,
+                    mark: None,
+                    subjects: None,
                 });
-            } else {
-                println!("Skipping file without extension: {}", path.display());
             }
         }
     }
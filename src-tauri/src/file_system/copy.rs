@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::debug;
+
+use super::ignore_rules::IgnoreRules;
+use super::leftovers;
+use super::{paths, space};
+use crate::captions::format as caption_format;
+use crate::project::METADATA_DIR;
+
+/// Size of the chunks copied between progress updates and cancellation checks
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Error message returned when a copy is cancelled
+pub const CANCELLED: &str = "Copy cancelled";
+
+/// How files are brought into a working copy
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Full byte-for-byte copy (default)
+    #[default]
+    Copy,
+    /// Hard link media files when source and project share a volume
+    Hardlink,
+    /// Copy-on-write clone (APFS clonefile, btrfs/XFS FICLONE, ReFS block cloning)
+    Reflink,
+    /// Symlink media files to the originals, which are left untouched
+    LinkInPlace,
+}
+
+/// Progress of a directory copy, emitted to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct CopyProgress {
+    pub copied_bytes: u64,
+    pub total_bytes: u64,
+    pub copied_files: usize,
+    pub total_files: usize,
+    pub current_file: String,
+}
+
+/// A file to copy, relative to the source root
+struct CopyEntry {
+    source: PathBuf,
+    relative: PathBuf,
+    size: u64,
+}
+
+/// Recursively collect all files (and directories) under a source directory, leaving
+/// out what its `.spacecatignore` excludes and the app-managed state (undo backups,
+/// journal, index) of a project
+fn collect_entries(
+    root: &Path,
+    dir: &Path,
+    rules: &IgnoreRules,
+    files: &mut Vec<CopyEntry>,
+    dirs: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        let relative = path
+            .strip_prefix(root)
+            .map_err(|_| "Failed to create relative path".to_string())?
+            .to_path_buf();
+
+        if relative.as_os_str() == METADATA_DIR
+            || rules.is_ignored(&relative, path.is_dir())
+            || leftovers::is_edit_leftover(&path)
+        {
+            continue;
+        }
+
+        if path.is_dir() {
+            dirs.push(relative);
+            collect_entries(root, &path, rules, files, dirs)?;
+        } else {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            files.push(CopyEntry {
+                source: path,
+                relative,
+                size,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Sidecars the app edits in place are always copied, never linked, so writes
+/// can't reach the originals: captions in every format, and XMP sidecars
+fn is_editable_sidecar(path: &Path) -> bool {
+    caption_format::is_caption_file(path)
+        || path
+            .extension()
+            .is_some_and(|ext| ext.to_string_lossy().eq_ignore_ascii_case("xmp"))
+}
+
+#[cfg(unix)]
+pub(crate) fn symlink_file(source: &Path, target: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(source, target)
+}
+
+#[cfg(windows)]
+pub(crate) fn symlink_file(source: &Path, target: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(source, target)
+}
+
+/// Try to link a file instead of copying it; returns false if the copy has to be made
+fn link_file(mode: ImportMode, source: &Path, target: &Path) -> bool {
+    if is_editable_sidecar(source) {
+        return false;
+    }
+
+    let result = match mode {
+        ImportMode::Copy => return false,
+        ImportMode::Hardlink => fs::hard_link(source, target),
+        ImportMode::Reflink => reflink_copy::reflink(source, target),
+        ImportMode::LinkInPlace => {
+            // Symlinks need an absolute target to stay valid
+            let absolute = fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf());
+            symlink_file(&absolute, target)
+        }
+    };
+
+    match result {
+        Ok(_) => true,
+        Err(e) => {
+            // Different volume, unsupported filesystem or missing privileges
+            debug!(
+                "Falling back to copy for {} ({:?} failed: {})",
+                source.display(),
+                mode,
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Bring a single file into a project, linking it when the mode allows and copying
+/// it otherwise
+pub fn copy_file(mode: ImportMode, source: &Path, target: &Path) -> Result<(), String> {
+    if link_file(mode, source, target) {
+        return Ok(());
+    }
+
+    fs::copy(source, target)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to copy {}: {}", source.display(), e))
+}
+
+/// Estimate the bytes a copy of `source` will write in the given mode
+///
+/// Linked imports only write the sidecars that are always copied; if linking fails
+/// for some files they fall back to copies, which the per-file check in
+/// [`copy_tree`] still catches.
+pub fn required_space(
+    source: &Path,
+    mode: ImportMode,
+    skip: &HashSet<PathBuf>,
+) -> Result<u64, String> {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    collect_entries(
+        source,
+        source,
+        &IgnoreRules::load(source),
+        &mut files,
+        &mut dirs,
+    )?;
+
+    Ok(files
+        .iter()
+        .filter(|f| !skip.contains(&f.relative))
+        .filter(|f| mode == ImportMode::Copy || is_editable_sidecar(&f.source))
+        .map(|f| f.size)
+        .sum())
+}
+
+/// Copy the contents of `source` into `destination`, reporting progress as it goes
+///
+/// Depending on `mode`, media files are linked or cloned instead of copied, falling
+/// back to a regular copy per file when that isn't possible. The copy checks `cancel`
+/// between chunks and stops with [`CANCELLED`] when it is set; the caller is
+/// responsible for cleaning up the partial copy. Files whose relative path is in
+/// `skip` are left out.
+pub fn copy_tree<F>(
+    source: &Path,
+    destination: &Path,
+    mode: ImportMode,
+    skip: &HashSet<PathBuf>,
+    cancel: &AtomicBool,
+    mut on_progress: F,
+) -> Result<u64, String>
+where
+    F: FnMut(&CopyProgress),
+{
+    // Nested files may exceed MAX_PATH on Windows even when the roots don't
+    let source = &paths::extended_path(source);
+    let destination = &paths::extended_path(destination);
+
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    collect_entries(
+        source,
+        source,
+        &IgnoreRules::load(source),
+        &mut files,
+        &mut dirs,
+    )?;
+    files.retain(|f| !skip.contains(&f.relative));
+
+    fs::create_dir_all(destination).map_err(|e| e.to_string())?;
+    for dir in &dirs {
+        fs::create_dir_all(destination.join(dir)).map_err(|e| e.to_string())?;
+    }
+
+    let mut progress = CopyProgress {
+        copied_bytes: 0,
+        total_bytes: files.iter().map(|f| f.size).sum(),
+        copied_files: 0,
+        total_files: files.len(),
+        current_file: String::new(),
+    };
+
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+
+    for file in &files {
+        progress.current_file = file.relative.to_string_lossy().to_string();
+        on_progress(&progress);
+
+        let target = destination.join(&file.relative);
+
+        if cancel.load(Ordering::SeqCst) {
+            return Err(CANCELLED.to_string());
+        }
+
+        if link_file(mode, &file.source, &target) {
+            progress.copied_bytes += file.size;
+            progress.copied_files += 1;
+            on_progress(&progress);
+            continue;
+        }
+
+        // Fail cleanly instead of running into a full disk halfway through the file
+        space::ensure_space(destination, file.size)?;
+
+        let mut reader = fs::File::open(&file.source)
+            .map_err(|e| format!("Failed to open {}: {}", file.source.display(), e))?;
+        let mut writer = fs::File::create(&target)
+            .map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+
+        loop {
+            if cancel.load(Ordering::SeqCst) {
+                return Err(CANCELLED.to_string());
+            }
+
+            let read = reader.read(&mut buffer).map_err(|e| e.to_string())?;
+            if read == 0 {
+                break;
+            }
+
+            writer
+                .write_all(&buffer[..read])
+                .map_err(|e| e.to_string())?;
+            progress.copied_bytes += read as u64;
+            on_progress(&progress);
+        }
+
+        // Match fs::copy, which keeps the permission bits of the original
+        if let Ok(metadata) = fs::metadata(&file.source) {
+            let _ = fs::set_permissions(&target, metadata.permissions());
+        }
+
+        progress.copied_files += 1;
+    }
+
+    on_progress(&progress);
+
+    Ok(progress.copied_bytes)
+}
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::atomic;
+use super::copy::CANCELLED;
+use super::ignore_rules::IgnoreRules;
+use crate::project::metadata_dir;
+
+/// Name of the dedup report written into a project's metadata directory
+pub const REPORT_FILE: &str = "dedup_report.json";
+
+/// What to do with byte-identical files found while importing
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupMode {
+    /// Don't look for duplicates (default)
+    #[default]
+    Off,
+    /// Import everything but write a report of the duplicates
+    Report,
+    /// Import only the first copy of each file and report the rest
+    Skip,
+}
+
+/// A set of byte-identical files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub hash: String,
+    pub size: u64,
+    /// The copy that is kept (first by path)
+    pub kept: String,
+    pub duplicates: Vec<String>,
+}
+
+/// Duplicates found in an import source, with paths relative to the source
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DedupReport {
+    pub source: String,
+    pub mode: DedupMode,
+    pub groups: Vec<DuplicateGroup>,
+    pub duplicate_files: usize,
+    pub duplicate_bytes: u64,
+}
+
+impl DedupReport {
+    /// Relative paths of the files to leave out of the import, including the caption
+    /// sidecars of skipped media
+    pub fn skipped_paths(&self) -> HashSet<PathBuf> {
+        let mut skipped = HashSet::new();
+        if self.mode != DedupMode::Skip {
+            return skipped;
+        }
+
+        for duplicate in self.groups.iter().flat_map(|g| &g.duplicates) {
+            let path = PathBuf::from(duplicate);
+            for ext in ["txt", "caption", "json"] {
+                skipped.insert(path.with_extension(ext));
+            }
+            skipped.insert(path);
+        }
+
+        skipped
+    }
+}
+
+/// Collect the files under a directory, relative to `root`, honoring its ignore rules
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    rules: &IgnoreRules,
+    files: &mut Vec<(PathBuf, u64)>,
+) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+
+        if rules.is_ignored(&path, path.is_dir()) {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_files(root, &path, rules, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            files.push((relative.to_path_buf(), size));
+        }
+    }
+
+    Ok(())
+}
+
+/// Hash a file with blake3, checking for cancellation between chunks
+pub(crate) fn hash_file(path: &Path, cancel: &AtomicBool) -> Result<String, String> {
+    let mut file =
+        fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+
+    loop {
+        if cancel.load(Ordering::SeqCst) {
+            return Err(CANCELLED.to_string());
+        }
+
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Find byte-identical files in an import source
+///
+/// Only files sharing a size with another file are hashed, so sources without
+/// duplicates are cheap to check. Empty files are never reported.
+pub fn find_duplicates(
+    source: &Path,
+    mode: DedupMode,
+    cancel: &AtomicBool,
+) -> Result<DedupReport, String> {
+    let mut report = DedupReport {
+        source: source.to_string_lossy().to_string(),
+        mode,
+        ..Default::default()
+    };
+
+    if mode == DedupMode::Off {
+        return Ok(report);
+    }
+
+    let mut files = Vec::new();
+    collect_files(source, source, &IgnoreRules::load(source), &mut files)?;
+    files.sort();
+
+    let mut by_size: BTreeMap<u64, Vec<PathBuf>> = BTreeMap::new();
+    for (path, size) in files {
+        if size > 0 {
+            by_size.entry(size).or_default().push(path);
+        }
+    }
+
+    for (size, paths) in by_size.into_iter().filter(|(_, p)| p.len() > 1) {
+        let mut by_hash: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for path in paths {
+            let hash = hash_file(&source.join(&path), cancel)?;
+            by_hash.entry(hash).or_default().push(path);
+        }
+
+        for (hash, mut paths) in by_hash.into_iter().filter(|(_, p)| p.len() > 1) {
+            let kept = paths.remove(0);
+            report.duplicate_files += paths.len();
+            report.duplicate_bytes += size * paths.len() as u64;
+            report.groups.push(DuplicateGroup {
+                hash,
+                size,
+                kept: kept.to_string_lossy().to_string(),
+                duplicates: paths
+                    .iter()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect(),
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+/// Write a dedup report into a project's metadata directory
+pub fn save_report(project: &Path, report: &DedupReport) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(report)
+        .map_err(|e| format!("Failed to serialize dedup report: {}", e))?;
+    atomic::write_atomic(&metadata_dir(project)?.join(REPORT_FILE), content, false)
+        .map_err(|e| format!("Failed to write dedup report: {}", e))
+}
+
+/// Load the dedup report written when a project was imported, if any
+pub fn load_report(project: &Path) -> Result<Option<DedupReport>, String> {
+    let path = metadata_dir(project)?.join(REPORT_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read dedup report: {}", e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse dedup report: {}", e))
+}
@@ -0,0 +1,62 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Name of the file listing the paths a folder's scans should skip
+pub const IGNORE_FILE: &str = ".spacecatignore";
+
+/// Gitignore-style rules from a folder's `.spacecatignore`
+///
+/// Rules apply to paths relative to the folder the file was loaded from, e.g.
+/// `.DS_Store`, `*.psd` or `samples/`. A folder without the file ignores nothing.
+pub struct IgnoreRules {
+    root: PathBuf,
+    matcher: Option<Gitignore>,
+}
+
+impl IgnoreRules {
+    /// Load the rules of a folder
+    pub fn load(root: &Path) -> Self {
+        let file = root.join(IGNORE_FILE);
+        let matcher = if file.is_file() {
+            let mut builder = GitignoreBuilder::new(root);
+            if let Some(e) = builder.add(&file) {
+                // Invalid lines are skipped; the remaining rules still apply
+                warn!("Problem in {}: {}", file.display(), e);
+            }
+            match builder.build() {
+                Ok(matcher) => Some(matcher),
+                Err(e) => {
+                    warn!("Ignoring invalid {}: {}", file.display(), e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        Self {
+            root: root.to_path_buf(),
+            matcher,
+        }
+    }
+
+    /// Check whether a path inside the folder (absolute or relative to it) is ignored,
+    /// either itself or through one of its parent directories
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let matcher = match &self.matcher {
+            Some(matcher) => matcher,
+            None => return false,
+        };
+
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        if relative.is_absolute() {
+            // Outside the folder the rules were loaded for
+            return false;
+        }
+
+        matcher
+            .matched_path_or_any_parents(relative, is_dir)
+            .is_ignore()
+    }
+}
@@ -0,0 +1,82 @@
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// Windows error of a file another process has open without sharing it
+const ERROR_SHARING_VIOLATION: i32 = 32;
+
+/// Windows error of a file region another process has locked
+const ERROR_LOCK_VIOLATION: i32 = 33;
+
+/// Attempts after the first before giving up on a file that is in use
+const RETRIES: u32 = 4;
+
+/// Wait before the first retry, growing with each further one
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Error of a file operation that failed because another program has the file open,
+/// returned to the frontend as JSON so it can ask the user to close that program
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename = "file_in_use")]
+pub struct FileInUse {
+    /// What was being done, e.g. `crop`
+    pub operation: String,
+    pub path: String,
+    pub message: String,
+}
+
+/// Whether an error means another program has the file open
+///
+/// Only Windows refuses to touch open files; elsewhere this is always false.
+pub fn is_in_use(error: &io::Error) -> bool {
+    cfg!(windows)
+        && matches!(
+            error.raw_os_error(),
+            Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)
+        )
+}
+
+/// Run a file operation, trying again for about two seconds while the file is in use
+///
+/// Virus scanners and indexers often hold a file that was just written for a moment.
+pub fn retry<T>(mut operation: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match operation() {
+            Err(e) if is_in_use(&e) && attempt < RETRIES => {
+                attempt += 1;
+                thread::sleep(RETRY_DELAY * attempt);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Turn the error of a file operation into the message returned to the frontend: a
+/// [`FileInUse`] as JSON if the file is in use, otherwise the message of `describe`
+pub fn error_message(
+    error: io::Error,
+    operation: &str,
+    path: &Path,
+    describe: impl FnOnce(io::Error) -> String,
+) -> String {
+    if !is_in_use(&error) {
+        return describe(error);
+    }
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string());
+    let in_use = FileInUse {
+        operation: operation.to_string(),
+        path: path.display().to_string(),
+        message: format!(
+            "{} is open in another program. Close it there and try again.",
+            name
+        ),
+    };
+    serde_json::to_string(&in_use).unwrap_or(in_use.message)
+}
@@ -0,0 +1,225 @@
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tracing::{info, warn};
+
+use super::location;
+use crate::project::METADATA_DIR;
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Suffix of the copy of a media file kept while it's cropped or trimmed
+pub const BACKUP_SUFFIX: &str = "_backup";
+
+/// Suffix of the file FFmpeg writes a cropped or trimmed video to before it replaces
+/// the original
+pub const TEMP_SUFFIX: &str = "_temp";
+
+/// Size of the chunks files are compared in
+const COMPARE_CHUNK: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LeftoverKind {
+    Backup,
+    Temp,
+}
+
+/// What a failed crop or trim left behind in a project
+#[derive(Debug, Default, Serialize)]
+pub struct CleanupReport {
+    /// Temporary files, and backups identical to their original, that were deleted
+    pub removed: Vec<String>,
+    /// Backups put back in place of an original that had gone missing
+    pub restored: Vec<String>,
+    /// Backups that differ from their original, moved into the undo journal so the
+    /// edit can still be undone
+    pub kept_for_undo: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+impl CleanupReport {
+    fn is_empty(&self) -> bool {
+        self.removed.is_empty()
+            && self.restored.is_empty()
+            && self.kept_for_undo.is_empty()
+            && self.failed.is_empty()
+    }
+}
+
+/// Whether a path is named like an edit's backup or temporary file, and the path of
+/// the media file it belongs to
+fn split_leftover(path: &Path) -> Option<(LeftoverKind, PathBuf)> {
+    let stem = path.file_stem()?.to_str()?;
+    let (kind, original_stem) = if let Some(stem) = stem.strip_suffix(BACKUP_SUFFIX) {
+        (LeftoverKind::Backup, stem)
+    } else if let Some(stem) = stem.strip_suffix(TEMP_SUFFIX) {
+        (LeftoverKind::Temp, stem)
+    } else {
+        return None;
+    };
+    if original_stem.is_empty() {
+        return None;
+    }
+
+    let mut name = OsString::from(original_stem);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    Some((kind, path.with_file_name(name)))
+}
+
+/// Check whether a file is a backup or temporary file left next to its media file by
+/// a crop or trim, which listings and exports leave out
+///
+/// A backup whose original is gone is the only copy left, so it isn't hidden until
+/// cleanup puts it back.
+pub fn is_edit_leftover(path: &Path) -> bool {
+    split_leftover(path).is_some_and(|(_, original)| original.is_file())
+}
+
+/// Compare two files byte by byte
+fn same_contents(a: &Path, b: &Path) -> Result<bool, String> {
+    let size = |path: &Path| {
+        fs::metadata(path)
+            .map(|m| m.len())
+            .map_err(|e| e.to_string())
+    };
+    if size(a)? != size(b)? {
+        return Ok(false);
+    }
+
+    let open = |path: &Path| {
+        fs::File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| e.to_string())
+    };
+    let (mut a, mut b) = (open(a)?, open(b)?);
+    let mut chunk_a = vec![0u8; COMPARE_CHUNK];
+    let mut chunk_b = vec![0u8; COMPARE_CHUNK];
+    loop {
+        let read = a.read(&mut chunk_a).map_err(|e| e.to_string())?;
+        if read == 0 {
+            return Ok(true);
+        }
+        b.read_exact(&mut chunk_b[..read])
+            .map_err(|e| e.to_string())?;
+        if chunk_a[..read] != chunk_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Files named like edit leftovers in a project and its subfolders, app data aside
+fn collect_leftovers(dir: &Path, leftovers: &mut Vec<(LeftoverKind, PathBuf, PathBuf)>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to read {}: {}", dir.display(), e);
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if entry.file_name() != METADATA_DIR {
+                collect_leftovers(&path, leftovers);
+            }
+        } else if let Some((kind, original)) = split_leftover(&path) {
+            leftovers.push((kind, path, original));
+        }
+    }
+}
+
+/// Deal with a backup: put it back if its original is missing, delete it if it's
+/// identical, and otherwise keep it as the undo state of the interrupted edit
+fn clean_backup(backup: &Path, original: &Path, report: &mut CleanupReport) -> Result<(), String> {
+    let name = backup.to_string_lossy().to_string();
+    if !original.exists() {
+        fs::rename(backup, original).map_err(|e| e.to_string())?;
+        report.restored.push(original.to_string_lossy().to_string());
+    } else if same_contents(backup, original)? {
+        fs::remove_file(backup).map_err(|e| e.to_string())?;
+        report.removed.push(name);
+    } else {
+        let file_name = original
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut operation = Operation::begin(
+            original,
+            OperationKind::MediaEdit,
+            format!("Interrupted edit of {}", file_name),
+        )?;
+        operation.adopt_backup(original, backup)?;
+        operation.commit()?;
+        report.kept_for_undo.push(name);
+    }
+    Ok(())
+}
+
+/// Remove or restore the backup and temporary files failed crops and trims left in a
+/// project
+///
+/// Backups are handled first, so a temporary file is only deleted once its original
+/// is back in place. Must not run while a crop or trim is working on the project.
+pub fn clean_project(project: &Path) -> CleanupReport {
+    let mut leftovers = Vec::new();
+    collect_leftovers(project, &mut leftovers);
+    leftovers.sort_by_key(|(kind, _, _)| *kind == LeftoverKind::Temp);
+
+    let mut report = CleanupReport::default();
+    for (kind, path, original) in leftovers {
+        let result = match kind {
+            LeftoverKind::Backup => clean_backup(&path, &original, &mut report),
+            // Without the original, the temporary file may be all that is left
+            LeftoverKind::Temp if !original.is_file() => continue,
+            LeftoverKind::Temp => fs::remove_file(&path)
+                .map(|_| report.removed.push(path.to_string_lossy().to_string()))
+                .map_err(|e| e.to_string()),
+        };
+        if let Err(e) = result {
+            warn!("Failed to clean up {}: {}", path.display(), e);
+            report.failed.push(path.to_string_lossy().to_string());
+        }
+    }
+    report
+}
+
+/// Clean up the leftovers of every project at startup, in the background, before any
+/// crop or trim can run
+pub fn clean_on_startup(app: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = match location::working_root(&app) {
+            Ok(root) => root,
+            Err(e) => {
+                warn!("Skipping leftover cleanup: {}", e);
+                return;
+            }
+        };
+        let projects = match fs::read_dir(&root) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        for project in projects.flatten().map(|entry| entry.path()) {
+            if !project.is_dir() {
+                continue;
+            }
+            let report = clean_project(&project);
+            if !report.is_empty() {
+                info!(
+                    "Cleaned up {}: {} removed, {} restored, {} kept for undo, {} failed",
+                    project.display(),
+                    report.removed.len(),
+                    report.restored.len(),
+                    report.kept_for_undo.len(),
+                    report.failed.len()
+                );
+            }
+        }
+    });
+}
@@ -0,0 +1,360 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use super::copy::symlink_file;
+use super::{atomic, space, watch};
+use crate::jobs::manager::Job;
+use crate::undo::journal as undo;
+
+/// File in the app data directory holding the location of the working directory
+const SETTINGS_FILE: &str = "spacecat-working-location.json";
+
+/// Name of the directory holding the project working copies
+pub const WORKING_DIR_NAME: &str = "spacecat-working";
+
+/// Where the project working copies are kept
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocationSettings {
+    /// Working directory chosen by the user; the default is inside the app data
+    /// directory, which often lives on a small system drive
+    pub working_root: Option<String>,
+}
+
+static SETTINGS: Lazy<RwLock<LocationSettings>> =
+    Lazy::new(|| RwLock::new(LocationSettings::default()));
+
+/// The working directory and the drive it is on
+#[derive(Debug, Serialize)]
+pub struct WorkingRootInfo {
+    pub path: String,
+    pub default_path: String,
+    pub is_default: bool,
+    /// Free space on the drive, if the platform reports it
+    pub available_bytes: Option<u64>,
+}
+
+/// Outcome of moving the working directory
+#[derive(Debug, Serialize)]
+pub struct WorkingRootMove {
+    pub path: String,
+    pub projects: usize,
+    pub bytes: u64,
+    /// Old project directories that couldn't be removed after copying, e.g. because a
+    /// file was in use
+    pub leftovers: Vec<String>,
+}
+
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join(SETTINGS_FILE))
+}
+
+/// Use the saved working directory, if any, from startup
+pub fn load(app: &AppHandle) {
+    let path = match settings_path(app) {
+        Ok(path) if path.is_file() => path,
+        _ => return,
+    };
+
+    match fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+    {
+        Ok(settings) => apply(settings),
+        Err(e) => warn!(
+            "Invalid working directory settings, using the default: {}",
+            e
+        ),
+    }
+}
+
+fn apply(settings: LocationSettings) {
+    if let Ok(mut current) = SETTINGS.write() {
+        *current = settings;
+    }
+}
+
+fn save(app: &AppHandle, settings: LocationSettings) -> Result<(), String> {
+    let path = settings_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    atomic::write_atomic(&path, &content, false).map_err(|e| e.to_string())?;
+
+    apply(settings);
+    Ok(())
+}
+
+/// The working directory inside the app data directory
+pub fn default_root(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join(WORKING_DIR_NAME))
+}
+
+/// The directory that holds all project working copies
+pub fn working_root(app: &AppHandle) -> Result<PathBuf, String> {
+    let configured = SETTINGS
+        .read()
+        .ok()
+        .and_then(|settings| settings.working_root.clone());
+    match configured {
+        Some(root) => Ok(PathBuf::from(root)),
+        None => default_root(app),
+    }
+}
+
+/// Describe the working directory in use
+pub fn info(app: &AppHandle) -> Result<WorkingRootInfo, String> {
+    let root = working_root(app)?;
+    let default = default_root(app)?;
+
+    Ok(WorkingRootInfo {
+        path: root.to_string_lossy().to_string(),
+        default_path: default.to_string_lossy().to_string(),
+        is_default: root == default,
+        available_bytes: space::available_space(&root).ok(),
+    })
+}
+
+/// How a file of a project is brought over to the new working directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    File,
+    /// Recreated with the same target, like the links of link-in-place imports
+    Symlink,
+    /// Linked to the same data again, so files shared with imported originals stay
+    /// shared when the new directory is on the same volume
+    HardLink,
+}
+
+/// A file to move, relative to the working directory
+struct MoveEntry {
+    relative: PathBuf,
+    size: u64,
+    kind: EntryKind,
+}
+
+#[cfg(unix)]
+fn is_hard_linked(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink() > 1
+}
+
+/// Link counts aren't available on stable Rust elsewhere, so those files are copied
+#[cfg(not(unix))]
+fn is_hard_linked(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Files under a directory, relative to it
+///
+/// Links are sized by the file they point to, in case they end up copied.
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<MoveEntry>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        if file_type.is_dir() {
+            collect_files(root, &path, files)?;
+            continue;
+        }
+
+        let relative = path
+            .strip_prefix(root)
+            .map_err(|_| "Failed to create relative path".to_string())?
+            .to_path_buf();
+        let (size, kind) = if file_type.is_symlink() {
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            (size, EntryKind::Symlink)
+        } else {
+            let metadata = entry.metadata().map_err(|e| e.to_string())?;
+            let kind = if is_hard_linked(&metadata) {
+                EntryKind::HardLink
+            } else {
+                EntryKind::File
+            };
+            (metadata.len(), kind)
+        };
+        files.push(MoveEntry {
+            relative,
+            size,
+            kind,
+        });
+    }
+    Ok(())
+}
+
+/// Bring one file over, linking it again when it was a link and copying it when
+/// that fails (different volume, missing privileges)
+fn move_entry(kind: EntryKind, source: &Path, target: &Path) -> std::io::Result<()> {
+    let linked = match kind {
+        EntryKind::File => false,
+        EntryKind::Symlink => fs::read_link(source)
+            .and_then(|link| symlink_file(&link, target))
+            .is_ok(),
+        EntryKind::HardLink => fs::hard_link(source, target).is_ok(),
+    };
+    if !linked {
+        fs::copy(source, target)?;
+    }
+    Ok(())
+}
+
+/// Copy every project into the new working directory, reporting progress by bytes
+///
+/// Symlinks and hard links stay links where the new directory allows it, so linked
+/// imports don't turn into full copies.
+fn copy_projects(
+    job: &Job,
+    old_root: &Path,
+    new_root: &Path,
+    projects: &[PathBuf],
+) -> Result<u64, String> {
+    let mut files = Vec::new();
+    for project in projects {
+        collect_files(old_root, project, &mut files)?;
+    }
+    let total: u64 = files.iter().map(|file| file.size).sum();
+    space::ensure_space(new_root, total)?;
+
+    for project in projects {
+        if let Ok(relative) = project.strip_prefix(old_root) {
+            fs::create_dir_all(new_root.join(relative)).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let mut copied = 0;
+    for file in &files {
+        let relative = &file.relative;
+        job.check_cancelled()?;
+        job.progress(
+            copied,
+            total,
+            Some(format!("Copying {}", relative.to_string_lossy())),
+        );
+
+        let target = new_root.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        move_entry(file.kind, &old_root.join(relative), &target)
+            .map_err(|e| format!("Failed to copy {}: {}", relative.display(), e))?;
+        copied += file.size;
+    }
+
+    job.progress(total, total, None);
+    Ok(total)
+}
+
+/// Move the working directory, with every project, into `destination`
+///
+/// The projects are copied into a `spacecat-working` directory inside `destination`,
+/// the app switches to it, and only then are the old copies removed, so a failed or
+/// cancelled move leaves the current working directory untouched. Choosing the app data
+/// directory moves the projects back to the default location.
+pub fn move_root(
+    job: &Job,
+    app: &AppHandle,
+    destination: &Path,
+) -> Result<WorkingRootMove, String> {
+    if !destination.is_absolute() || destination.components().any(|c| c == Component::ParentDir) {
+        return Err(format!("Invalid destination: {}", destination.display()));
+    }
+
+    let old_root = working_root(app)?;
+    let new_root = destination.join(WORKING_DIR_NAME);
+    if new_root == old_root {
+        return Err("The working directory is already there".to_string());
+    }
+    if new_root.starts_with(&old_root) || old_root.starts_with(&new_root) {
+        return Err(
+            "The new working directory can't contain or be inside the current one".to_string(),
+        );
+    }
+    let occupied = fs::read_dir(&new_root)
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false);
+    if occupied {
+        return Err(format!(
+            "{} already exists and is not empty",
+            new_root.display()
+        ));
+    }
+
+    let projects: Vec<PathBuf> = match fs::read_dir(&old_root) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+
+    fs::create_dir_all(&new_root)
+        .map_err(|e| format!("Failed to create {}: {}", new_root.display(), e))?;
+    let bytes = match copy_projects(job, &old_root, &new_root, &projects) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&new_root);
+            return Err(e);
+        }
+    };
+
+    // Undo snapshots name files by absolute path
+    for project in &projects {
+        if let Ok(relative) = project.strip_prefix(&old_root) {
+            if let Err(e) = undo::relocate(&new_root.join(relative), project) {
+                warn!(
+                    "Failed to update the operation journal of {}: {}",
+                    project.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    let hot_folders: Vec<PathBuf> = watch::watched()
+        .into_iter()
+        .map(PathBuf::from)
+        .filter(|project| project.starts_with(&old_root))
+        .collect();
+    for project in &hot_folders {
+        watch::stop(project);
+    }
+
+    let working_root =
+        (new_root != default_root(app)?).then(|| new_root.to_string_lossy().to_string());
+    save(app, LocationSettings { working_root })?;
+
+    for project in &hot_folders {
+        if let Ok(relative) = project.strip_prefix(&old_root) {
+            if let Err(e) = watch::start(app.clone(), &new_root.join(relative)) {
+                warn!("Failed to watch {}: {}", relative.display(), e);
+            }
+        }
+    }
+
+    let mut leftovers = Vec::new();
+    for project in &projects {
+        if let Err(e) = fs::remove_dir_all(project) {
+            warn!("Failed to remove {}: {}", project.display(), e);
+            leftovers.push(project.to_string_lossy().to_string());
+        }
+    }
+    // Only removed once empty
+    let _ = fs::remove_dir(&old_root);
+
+    Ok(WorkingRootMove {
+        path: new_root.to_string_lossy().to_string(),
+        projects: projects.len(),
+        bytes,
+        leftovers,
+    })
+}
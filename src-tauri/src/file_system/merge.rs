@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::copy::{self, ImportMode};
+use super::ignore_rules::IgnoreRules;
+use crate::project::{METADATA_DIR, PROJECT_FILE};
+use crate::undo::journal::{Operation, OperationKind};
+
+/// What to do when an imported file already exists in the project
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Keep the project's file and leave the imported one out (default)
+    #[default]
+    Skip,
+    /// Replace the project's file; the replaced file can be restored with undo
+    Overwrite,
+    /// Import under a new name with a numeric suffix
+    Rename,
+}
+
+/// A file imported under a different name because of a conflict
+#[derive(Debug, Serialize)]
+pub struct RenamedFile {
+    pub from: String,
+    pub to: String,
+}
+
+/// Outcome of merging a folder into a project, with paths relative to the project
+#[derive(Debug, Default, Serialize)]
+pub struct MergeReport {
+    pub added: Vec<String>,
+    pub skipped: Vec<String>,
+    pub overwritten: Vec<String>,
+    pub renamed: Vec<RenamedFile>,
+}
+
+/// Collect the files of an import source, relative to `root`
+///
+/// App metadata of a source that is itself a project is left out, as is anything its
+/// `.spacecatignore` excludes.
+pub(crate) fn collect_files(
+    root: &Path,
+    dir: &Path,
+    rules: &IgnoreRules,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        let name = entry.file_name();
+
+        if name == METADATA_DIR || (dir == root && name == PROJECT_FILE) {
+            continue;
+        }
+
+        if rules.is_ignored(&path, path.is_dir()) {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_files(root, &path, rules, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_path_buf());
+        }
+    }
+
+    Ok(())
+}
+
+/// Name of a file with a numeric suffix added to its stem
+fn with_suffix(path: &Path, counter: usize) -> PathBuf {
+    let mut name = path
+        .file_stem()
+        .map(|s| s.to_os_string())
+        .unwrap_or_default();
+    name.push(format!("_{}", counter));
+
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+
+    path.with_file_name(name)
+}
+
+/// Merge the files of `source` into an existing project without replacing it
+///
+/// A media file and its caption sidecars (files sharing the same stem) are handled as
+/// one unit, so a renamed image keeps its caption. The whole merge is recorded as a
+/// single undoable operation.
+pub fn merge_into_project(
+    source: &Path,
+    project: &Path,
+    policy: ConflictPolicy,
+    mode: ImportMode,
+) -> Result<MergeReport, String> {
+    let mut files = Vec::new();
+    collect_files(source, source, &IgnoreRules::load(source), &mut files)?;
+
+    // Group files by stem so media and sidecars move together
+    let mut groups: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for file in files {
+        groups
+            .entry(file.with_extension(""))
+            .or_default()
+            .push(file);
+    }
+
+    let mut report = MergeReport::default();
+    let mut operation = Operation::begin(
+        project,
+        OperationKind::Import,
+        format!("Import from {}", source.display()),
+    )?;
+
+    for group in groups.values() {
+        let conflict = group.iter().any(|f| project.join(f).exists());
+
+        let targets: Vec<PathBuf> = match (conflict, policy) {
+            (false, _) | (true, ConflictPolicy::Overwrite) => group.clone(),
+            (true, ConflictPolicy::Skip) => {
+                report
+                    .skipped
+                    .extend(group.iter().map(|f| f.to_string_lossy().to_string()));
+                continue;
+            }
+            (true, ConflictPolicy::Rename) => {
+                let mut counter = 1;
+                while group
+                    .iter()
+                    .any(|f| project.join(with_suffix(f, counter)).exists())
+                {
+                    counter += 1;
+                }
+                group.iter().map(|f| with_suffix(f, counter)).collect()
+            }
+        };
+
+        for (file, target) in group.iter().zip(&targets) {
+            let destination = project.join(target);
+            let existed = destination.exists();
+
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+
+            // Snapshot even new files, so undoing the import removes them again
+            operation.snapshot(&destination)?;
+            if existed {
+                fs::remove_file(&destination).map_err(|e| e.to_string())?;
+            }
+            copy::copy_file(mode, &source.join(file), &destination)?;
+
+            let from = file.to_string_lossy().to_string();
+            let to = target.to_string_lossy().to_string();
+            if existed {
+                report.overwritten.push(to);
+            } else if from != to {
+                report.renamed.push(RenamedFile { from, to });
+            } else {
+                report.added.push(to);
+            }
+        }
+    }
+
+    operation.commit()?;
+
+    Ok(report)
+}
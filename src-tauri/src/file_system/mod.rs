@@ -1 +1,16 @@
+pub mod archive;
+pub mod atomic;
+pub mod catalog;
 pub mod commands;
+pub mod copy;
+pub mod dedup;
+pub mod ignore_rules;
+pub mod in_use;
+pub mod leftovers;
+pub mod location;
+pub mod merge;
+pub mod naming;
+pub mod paths;
+pub mod space;
+pub mod sync;
+pub mod watch;
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::atomic;
+use super::commands::scan_media_files;
+use super::copy;
+use super::dedup;
+use super::in_use;
+use crate::captions::format::{self as caption_format, CaptionStore};
+use crate::jobs::manager::{self as jobs, Job};
+use crate::media::formats;
+
+/// File written at the root of a renamed export, mapping old to new relative paths
+pub const RENAME_MANIFEST: &str = "renamed.json";
+
+/// Hex digits of the content hash used as a file name
+const HASH_NAME_LEN: usize = 16;
+
+/// How the media files of an export are named
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportNaming {
+    /// Keep the original names (default)
+    #[default]
+    Original,
+    /// Number the files in name order: `0001.jpg`, `0002.jpg`, ...
+    Sequential,
+    /// Name files after their content hash, so identical names mean identical files
+    Hash,
+    /// The original names limited to ASCII letters, digits, `-` and `_`
+    Sanitized,
+}
+
+/// Limit a file stem to ASCII letters, digits, `-` and `_`, without spaces
+fn sanitize(stem: &str) -> String {
+    let mut sanitized = String::new();
+    for c in stem.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            sanitized.push(c);
+        } else if !sanitized.ends_with('_') {
+            sanitized.push('_');
+        }
+    }
+    let sanitized = sanitized.trim_matches('_');
+    if sanitized.is_empty() {
+        "file".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+/// A media file to rename, with the sidecars that share its stem
+struct Entry {
+    media: PathBuf,
+    relative_path: String,
+    stem: String,
+    /// Sidecar paths with the part of their name after the stem, e.g. `.en.txt`
+    sidecars: Vec<(PathBuf, String)>,
+    new_stem: String,
+}
+
+/// Rename the media files of an export staging copy, with their sidecars in lockstep
+///
+/// Captions in a manifest are moved to the new names, and a [`RENAME_MANIFEST`] maps
+/// the old relative paths to the new ones. Returns the number of renamed files.
+pub fn rename_files(job: &Job, directory: &Path, naming: ExportNaming) -> Result<usize, String> {
+    if naming == ExportNaming::Original {
+        return Ok(0);
+    }
+
+    let mut files = scan_media_files(directory)?;
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    let total = files.len() as u64;
+    let width = files.len().to_string().len().max(4);
+    let manifest_format = CaptionStore::open(directory).format().is_manifest();
+
+    let mut entries: Vec<Entry> = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some(file.relative_path.clone()));
+
+        let media = PathBuf::from(&file.path);
+        let stem = media
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let new_stem = match naming {
+            ExportNaming::Sequential => format!("{:0width$}", index + 1, width = width),
+            ExportNaming::Hash => match dedup::hash_file(&media, job.cancel_flag()) {
+                Ok(hash) => hash[..HASH_NAME_LEN.min(hash.len())].to_string(),
+                Err(e) if e == copy::CANCELLED => return Err(jobs::CANCELLED.to_string()),
+                Err(e) => return Err(e),
+            },
+            _ => sanitize(&stem),
+        };
+        entries.push(Entry {
+            media,
+            relative_path: file.relative_path.clone(),
+            stem,
+            sidecars: Vec::new(),
+            new_stem,
+        });
+    }
+
+    // Give every other file in the media folders to the media with the longest stem it
+    // starts with, so `a.v2.txt` goes with `a.v2.png` rather than `a.png`
+    let mut by_dir: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let dir = entry.media.parent().unwrap_or(directory).to_path_buf();
+        by_dir.entry(dir).or_default().push(index);
+    }
+    for (dir, indices) in &by_dir {
+        for item in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+            let path = item.path();
+            let name = item.file_name().to_string_lossy().to_string();
+            let is_manifest = manifest_format
+                && dir.as_path() == directory
+                && caption_format::is_manifest_name(&name);
+            if !path.is_file() || formats::media_type(&path).is_some() || is_manifest {
+                continue;
+            }
+            let owner = indices
+                .iter()
+                .filter(|&&i| name.starts_with(&format!("{}.", entries[i].stem)))
+                .max_by_key(|&&i| entries[i].stem.len());
+            if let Some(&owner) = owner {
+                let suffix = name[entries[owner].stem.len()..].to_string();
+                entries[owner].sidecars.push((path, suffix));
+            }
+        }
+    }
+
+    // Names stay unique per folder, with a counter for repeated stems
+    let mut taken: HashSet<(PathBuf, String)> = HashSet::new();
+    let mut renames: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut mapping: BTreeMap<String, String> = BTreeMap::new();
+    for entry in &entries {
+        let dir = entry.media.parent().unwrap_or(directory);
+        let extension = entry
+            .media
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+        let mut stem = entry.new_stem.clone();
+        let mut counter = 1;
+        while !taken.insert((dir.to_path_buf(), stem.to_lowercase())) {
+            counter += 1;
+            stem = format!("{}_{}", entry.new_stem, counter);
+        }
+
+        let file_name = format!("{}{}", stem, extension);
+        let renamed = match entry.relative_path.rsplit_once('/') {
+            Some((folder, _)) => format!("{}/{}", folder, file_name),
+            None => file_name.clone(),
+        };
+        mapping.insert(entry.relative_path.clone(), renamed);
+        renames.push((entry.media.clone(), dir.join(&file_name)));
+        for (sidecar, suffix) in &entry.sidecars {
+            renames.push((sidecar.clone(), dir.join(format!("{}{}", stem, suffix))));
+        }
+    }
+
+    // Move everything aside first, so new names can't collide with old ones
+    let parked: Vec<(PathBuf, PathBuf)> = renames
+        .into_iter()
+        .enumerate()
+        .map(|(index, (from, to))| {
+            let aside = from.with_file_name(format!(".spacecat-rename-{}", index));
+            in_use::retry(|| fs::rename(&from, &aside))
+                .map_err(|e| format!("Failed to rename {}: {}", from.display(), e))?;
+            Ok((aside, to))
+        })
+        .collect::<Result<_, String>>()?;
+    for (aside, to) in parked {
+        in_use::retry(|| fs::rename(&aside, &to))
+            .map_err(|e| format!("Failed to rename {}: {}", to.display(), e))?;
+    }
+
+    if manifest_format {
+        rename_manifest_keys(directory, &mapping)?;
+    }
+
+    let content = serde_json::to_string_pretty(&mapping).map_err(|e| e.to_string())?;
+    atomic::write_atomic(&directory.join(RENAME_MANIFEST), content, false)
+        .map_err(|e| format!("Failed to write {}: {}", RENAME_MANIFEST, e))?;
+
+    job.progress(total, total, None);
+    Ok(mapping.len())
+}
+
+/// Move the captions of the caption manifests (one per language) to the new names
+fn rename_manifest_keys(
+    directory: &Path,
+    mapping: &BTreeMap<String, String>,
+) -> Result<(), String> {
+    for item in fs::read_dir(directory)
+        .map_err(|e| e.to_string())?
+        .flatten()
+    {
+        let name = item.file_name().to_string_lossy().to_string();
+        if !caption_format::is_manifest_name(&name) {
+            continue;
+        }
+
+        let path = item.path();
+        let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let captions = caption_format::parse_manifest(&path, &content)
+            .map_err(|e| format!("Failed to parse {}: {}", name, e))?;
+        let renamed: BTreeMap<String, String> = captions
+            .into_iter()
+            .map(|(key, caption)| (mapping.get(&key).cloned().unwrap_or(key), caption))
+            .collect();
+
+        let content = caption_format::serialize_manifest(&path, &renamed)?;
+        atomic::write_atomic(&path, content, false)
+            .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+    }
+    Ok(())
+}
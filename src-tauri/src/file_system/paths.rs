@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+/// Length from which Windows paths need the extended-length prefix (MAX_PATH is 260,
+/// and directory APIs leave room for an 8.3 file name)
+#[cfg(windows)]
+const LONG_PATH_THRESHOLD: usize = 248;
+
+/// Convert a path to a form the OS file APIs accept regardless of its length
+///
+/// On Windows, absolute paths longer than `MAX_PATH` get the extended-length prefix
+/// (see [`extended_path`]). Everywhere else the path is returned unchanged.
+pub fn long_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    if path.as_os_str().len() >= LONG_PATH_THRESHOLD {
+        return extended_path(path);
+    }
+
+    path.to_path_buf()
+}
+
+/// Prefix an absolute Windows path with `\\?\` (or `\\?\UNC\`), lifting the `MAX_PATH`
+/// limit for it and everything below it
+///
+/// Use this for directory roots that are walked recursively, since their children may
+/// be long even when the root isn't. Extended-length paths skip normalization, so the
+/// path is made to use backslashes first and paths with `.` or `..` components are
+/// left as they are. Everywhere else the path is returned unchanged.
+#[cfg(windows)]
+pub fn extended_path(path: &Path) -> PathBuf {
+    use std::path::Component;
+
+    let raw = path.as_os_str().to_string_lossy();
+    if raw.starts_with(r"\\?\") || !path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    if path
+        .components()
+        .any(|c| matches!(c, Component::CurDir | Component::ParentDir))
+    {
+        return path.to_path_buf();
+    }
+
+    let normalized = raw.replace('/', "\\");
+    match normalized.strip_prefix(r"\\") {
+        Some(unc) => PathBuf::from(format!(r"\\?\UNC\{}", unc)),
+        None => PathBuf::from(format!(r"\\?\{}", normalized)),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn extended_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// Format a path for the frontend, removing any extended-length prefix
+pub fn display_path(path: &Path) -> String {
+    let raw = path.to_string_lossy();
+
+    if let Some(unc) = raw.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else if let Some(local) = raw.strip_prefix(r"\\?\") {
+        local.to_string()
+    } else {
+        raw.to_string()
+    }
+}
+
+/// Check that a file name can be passed to the frontend and back without loss
+///
+/// Names that aren't valid Unicode (possible on Linux) would come back as a different,
+/// non-existent path, so listings skip them.
+pub fn is_unicode_name(path: &Path) -> bool {
+    path.file_name()
+        .map(|n| n.to_str().is_some())
+        .unwrap_or(false)
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use crate::project::METADATA_DIR;
+
+/// Space left free on the target volume on top of what an operation needs
+const HEADROOM_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Format a byte count for error messages
+pub fn format_size(bytes: u64) -> String {
+    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+    const MB: f64 = 1024.0 * 1024.0;
+
+    let bytes = bytes as f64;
+    if bytes >= GB {
+        format!("{:.1} GB", bytes / GB)
+    } else {
+        format!("{:.1} MB", bytes / MB)
+    }
+}
+
+/// Available space on the volume that holds `path`
+///
+/// The path doesn't have to exist yet; its closest existing ancestor is checked.
+pub fn available_space(path: &Path) -> Result<u64, String> {
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| format!("No existing parent for {}", path.display()))?;
+
+    fs2::available_space(existing).map_err(|e| format!("Failed to check free disk space: {}", e))
+}
+
+/// Fail with a clear message when the volume holding `target` can't fit `required` bytes
+pub fn ensure_space(target: &Path, required: u64) -> Result<(), String> {
+    let available = match available_space(target) {
+        Ok(available) => available,
+        Err(e) => {
+            // Don't block the operation if the platform can't report free space
+            warn!("Skipping disk space check: {}", e);
+            return Ok(());
+        }
+    };
+
+    if required.saturating_add(HEADROOM_BYTES) > available {
+        return Err(format!(
+            "Not enough disk space on {}: need {}, have {}",
+            target.display(),
+            format_size(required),
+            format_size(available)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Total size of a project's files, excluding app metadata
+pub fn dataset_size(dir: &Path) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+
+    entries
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                if entry.file_name() == METADATA_DIR {
+                    0
+                } else {
+                    dataset_size(&path)
+                }
+            } else {
+                entry.metadata().map(|m| m.len()).unwrap_or(0)
+            }
+        })
+        .sum()
+}
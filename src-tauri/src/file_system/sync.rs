@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::time::UNIX_EPOCH;
+
+use super::atomic;
+use super::copy::{self, ImportMode};
+use super::dedup::hash_file;
+use super::ignore_rules::IgnoreRules;
+use super::merge::collect_files;
+use crate::project::metadata_dir;
+use crate::undo::journal::{Operation, OperationKind};
+
+/// State of the source folder at the last sync, stored in the project's metadata
+const SYNC_FILE: &str = "sync.json";
+
+/// A source file as it was when last synced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SyncEntry {
+    size: u64,
+    modified: u64,
+    hash: String,
+    /// The project's copy, so it's only hashed again once it changed
+    #[serde(default)]
+    target: Option<TargetEntry>,
+}
+
+/// A project file as it was when last synced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TargetEntry {
+    size: u64,
+    modified: u64,
+    hash: String,
+}
+
+/// Kind of difference between the source folder and the working copy
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncChangeKind {
+    /// New in the source, copied into the project
+    Added,
+    /// Changed in the source, replaces the project's copy
+    Modified,
+    /// Gone from the source, removed from the project
+    Removed,
+    /// Changed on both sides (or never synced and different); left alone
+    Conflict,
+}
+
+/// A difference found by a sync, with the path relative to the project
+#[derive(Debug, Serialize)]
+pub struct SyncChange {
+    pub path: String,
+    pub kind: SyncChangeKind,
+}
+
+/// Result of comparing a project with its source; when `applied` is false nothing
+/// was changed
+#[derive(Debug, Serialize)]
+pub struct SyncReport {
+    pub source: String,
+    pub changes: Vec<SyncChange>,
+    pub applied: bool,
+}
+
+fn sync_path(project: &Path) -> Result<PathBuf, String> {
+    Ok(metadata_dir(project)?.join(SYNC_FILE))
+}
+
+fn load_state(project: &Path) -> Result<BTreeMap<String, SyncEntry>, String> {
+    let path = sync_path(project)?;
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read sync state: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse sync state: {}", e))
+}
+
+fn save_state(project: &Path, state: &BTreeMap<String, SyncEntry>) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(state)
+        .map_err(|e| format!("Failed to serialize sync state: {}", e))?;
+    atomic::write_atomic(&sync_path(project)?, content, false)
+        .map_err(|e| format!("Failed to write sync state: {}", e))
+}
+
+/// Size and modification time (seconds since the epoch) of a file
+fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some((metadata.len(), modified))
+}
+
+fn hash(path: &Path) -> Result<String, String> {
+    hash_file(path, &AtomicBool::new(false))
+}
+
+/// State of a project file, reusing its hash from the last sync while its size and
+/// mtime are the same
+fn target_entry(target: &Path, known: Option<&SyncEntry>) -> Result<TargetEntry, String> {
+    let (size, modified) =
+        file_stamp(target).ok_or_else(|| format!("Failed to read {}", target.display()))?;
+    let hash = match known.and_then(|k| k.target.as_ref()) {
+        Some(t) if t.size == size && t.modified == modified => t.hash.clone(),
+        _ => hash(target)?,
+    };
+    Ok(TargetEntry {
+        size,
+        modified,
+        hash,
+    })
+}
+
+/// Compare a project with the folder it was imported from, optionally applying the
+/// differences
+///
+/// Files on both sides are compared by size and mtime first and only hashed when
+/// those changed. The state of the source after each sync is remembered, so files edited in the
+/// project (like captions) are reported as conflicts instead of being overwritten,
+/// and only files that came from the source are removed when they disappear there.
+pub fn sync_with_source(
+    project: &Path,
+    source: &Path,
+    mode: ImportMode,
+    apply: bool,
+) -> Result<SyncReport, String> {
+    let previous = load_state(project)?;
+    let mut state = BTreeMap::new();
+    let mut changes = Vec::new();
+
+    let mut files = Vec::new();
+    collect_files(source, source, &IgnoreRules::load(source), &mut files)?;
+    let source_files: BTreeSet<String> = files
+        .iter()
+        .map(|f| f.to_string_lossy().to_string())
+        .collect();
+
+    for relative in &source_files {
+        let source_file = source.join(relative);
+        let target = project.join(relative);
+        let (size, modified) = match file_stamp(&source_file) {
+            Some(stamp) => stamp,
+            None => continue,
+        };
+
+        let known = previous.get(relative);
+        let unchanged = known
+            .map(|k| k.size == size && k.modified == modified)
+            .unwrap_or(false);
+        let source_hash = match known {
+            Some(k) if unchanged => k.hash.clone(),
+            _ => hash(&source_file)?,
+        };
+
+        let (kind, target_state) = if !target.exists() {
+            // Files removed from the project on purpose stay removed
+            let kind = if known.is_some() {
+                None
+            } else {
+                Some(SyncChangeKind::Added)
+            };
+            (kind, None)
+        } else {
+            let current = target_entry(&target, known)?;
+            let kind = match known {
+                _ if current.hash == source_hash => None,
+                Some(k) if k.hash == source_hash => None,
+                Some(k) if k.hash == current.hash => Some(SyncChangeKind::Modified),
+                _ => Some(SyncChangeKind::Conflict),
+            };
+            (kind, Some(current))
+        };
+
+        let entry = if kind == Some(SyncChangeKind::Conflict) {
+            // Keep the source as it was at the last sync, so the conflict is reported
+            // until one side is changed back
+            known.cloned().map(|k| SyncEntry {
+                target: target_state,
+                ..k
+            })
+        } else {
+            Some(SyncEntry {
+                size,
+                modified,
+                hash: source_hash,
+                target: target_state,
+            })
+        };
+        if let Some(entry) = entry {
+            state.insert(relative.clone(), entry);
+        }
+
+        if let Some(kind) = kind {
+            changes.push(SyncChange {
+                path: relative.clone(),
+                kind,
+            });
+        }
+    }
+
+    for (relative, entry) in &previous {
+        if source_files.contains(relative) {
+            continue;
+        }
+
+        let target = project.join(relative);
+        if !target.exists() {
+            continue;
+        }
+
+        let kind = if target_entry(&target, Some(entry))?.hash == entry.hash {
+            SyncChangeKind::Removed
+        } else {
+            SyncChangeKind::Conflict
+        };
+        changes.push(SyncChange {
+            path: relative.clone(),
+            kind,
+        });
+    }
+
+    if apply {
+        apply_changes(project, source, &changes, mode)?;
+
+        // The copies replaced the project's files, so they're stamped again
+        for change in &changes {
+            if !matches!(
+                change.kind,
+                SyncChangeKind::Added | SyncChangeKind::Modified
+            ) {
+                continue;
+            }
+            if let Some(entry) = state.get_mut(&change.path) {
+                entry.target =
+                    file_stamp(&project.join(&change.path)).map(|(size, modified)| TargetEntry {
+                        size,
+                        modified,
+                        hash: entry.hash.clone(),
+                    });
+            }
+        }
+        save_state(project, &state)?;
+    }
+
+    Ok(SyncReport {
+        source: source.to_string_lossy().to_string(),
+        changes,
+        applied: apply,
+    })
+}
+
+/// Apply sync changes to a project as a single undoable operation
+fn apply_changes(
+    project: &Path,
+    source: &Path,
+    changes: &[SyncChange],
+    mode: ImportMode,
+) -> Result<(), String> {
+    let mut operation = Operation::begin(
+        project,
+        OperationKind::Import,
+        format!("Sync from {}", source.display()),
+    )?;
+
+    for change in changes {
+        let target = project.join(&change.path);
+        match change.kind {
+            SyncChangeKind::Added | SyncChangeKind::Modified => {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                operation.snapshot(&target)?;
+                if target.exists() {
+                    fs::remove_file(&target).map_err(|e| e.to_string())?;
+                }
+                copy::copy_file(mode, &source.join(&change.path), &target)?;
+            }
+            SyncChangeKind::Removed => operation.snapshot_and_remove(&target)?,
+            SyncChangeKind::Conflict => {}
+        }
+    }
+
+    operation.commit()?;
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter};
+use tracing::{debug, warn};
+
+use super::commands::{scan_media_files, MediaFile};
+use crate::media::probe;
+
+/// Event emitted when new files of a hot folder are ready to be captioned
+pub const HOT_FOLDER_EVENT: &str = "hot-folder-files";
+
+/// How often a hot folder is checked for new files
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a new file must stay unchanged before it counts as completely copied
+const QUIET_PERIOD: Duration = Duration::from_secs(3);
+
+/// New files of a hot folder, ready to be captioned
+#[derive(Debug, Clone, Serialize)]
+pub struct HotFolderFiles {
+    pub project: String,
+    pub files: Vec<MediaFile>,
+}
+
+/// Stop flags of the projects being watched
+static WATCHED: Lazy<Mutex<HashMap<PathBuf, Arc<AtomicBool>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A new file, waiting until it's no longer being written
+struct Pending {
+    file: MediaFile,
+    changed: Instant,
+}
+
+/// Size and modification time of a file, which change while it's being written
+type Stamp = (u64, Option<SystemTime>);
+
+/// Stamps of a project's media files by relative path
+///
+/// The watcher keeps its own rather than going through the project index, so the
+/// changes it sees aren't taken from a directory refresh of the open project.
+fn snapshot(project: &Path) -> Result<HashMap<String, (Stamp, MediaFile)>, String> {
+    Ok(scan_media_files(project)?
+        .into_iter()
+        .map(|file| {
+            let modified = fs::metadata(&file.path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            (file.relative_path.clone(), ((file.size, modified), file))
+        })
+        .collect())
+}
+
+/// Start watching a project as a hot folder: media files added to it from now on are
+/// announced with [`HOT_FOLDER_EVENT`] once they're completely copied
+pub fn start(app: AppHandle, project: &Path) -> Result<(), String> {
+    let mut watched = WATCHED.lock().map_err(|e| e.to_string())?;
+    if watched.contains_key(project) {
+        return Ok(());
+    }
+
+    // Take in what is already there, so only files added from now on are announced
+    let known = snapshot(project)?
+        .into_iter()
+        .map(|(relative_path, (stamp, _))| (relative_path, stamp))
+        .collect();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    watched.insert(project.to_path_buf(), stop.clone());
+    let project = project.to_path_buf();
+    thread::spawn(move || watch(&app, &project, known, &stop));
+    Ok(())
+}
+
+/// Stop watching a project, returning whether it was watched
+pub fn stop(project: &Path) -> bool {
+    let stop = WATCHED
+        .lock()
+        .ok()
+        .and_then(|mut watched| watched.remove(project));
+    match stop {
+        Some(stop) => {
+            stop.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+/// The projects being watched
+pub fn watched() -> Vec<String> {
+    WATCHED
+        .lock()
+        .map(|watched| {
+            watched
+                .keys()
+                .map(|project| project.to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn watch(app: &AppHandle, project: &Path, mut known: HashMap<String, Stamp>, stop: &AtomicBool) {
+    let mut pending: HashMap<String, Pending> = HashMap::new();
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if stop.load(Ordering::SeqCst) {
+            return;
+        }
+        if !project.is_dir() {
+            warn!(
+                "Hot folder {} is gone, no longer watching it",
+                project.display()
+            );
+            self::stop(project);
+            return;
+        }
+
+        let current = match snapshot(project) {
+            Ok(current) => current,
+            Err(e) => {
+                warn!("Failed to check hot folder {}: {}", project.display(), e);
+                continue;
+            }
+        };
+
+        // Files still being copied show up as changed on every check
+        let now = Instant::now();
+        known.retain(|relative_path, _| current.contains_key(relative_path));
+        pending.retain(|relative_path, _| current.contains_key(relative_path));
+        for (relative_path, (stamp, file)) in current {
+            match known.insert(relative_path.clone(), stamp) {
+                Some(previous) if previous == stamp => {}
+                Some(_) if !pending.contains_key(&relative_path) => {}
+                _ => {
+                    pending.insert(relative_path, Pending { file, changed: now });
+                }
+            }
+        }
+
+        let ready: Vec<String> = pending
+            .iter()
+            .filter(|(_, entry)| now.duration_since(entry.changed) >= QUIET_PERIOD)
+            .map(|(relative_path, _)| relative_path.clone())
+            .collect();
+        let mut files: Vec<MediaFile> = ready
+            .iter()
+            .filter_map(|relative_path| pending.remove(relative_path))
+            .map(|entry| entry.file)
+            // Files that arrived with a caption don't need one
+            .filter(|file| !file.has_caption)
+            .map(|mut file| {
+                let info = probe::probe_media(Path::new(&file.path), &file.file_type);
+                file.width = info.width;
+                file.height = info.height;
+                file.duration = info.duration;
+                file.frame_count = info.frame_count;
+                file
+            })
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        debug!(
+            "{} new files in hot folder {}",
+            files.len(),
+            project.display()
+        );
+        let _ = app.emit(
+            HOT_FOLDER_EVENT,
+            HotFolderFiles {
+                project: project.to_string_lossy().to_string(),
+                files,
+            },
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use tauri::AppHandle;
+
+use super::journal::{self, InterruptedJob};
+use super::manager::{self, JobInfo};
+use super::notify::{self, NotificationSettings};
+
+/// List queued, running and recently finished jobs, oldest first
+#[tauri::command]
+pub fn list_jobs() -> Result<Vec<JobInfo>, String> {
+    Ok(manager::list())
+}
+
+/// Cancel a queued or running job
+///
+/// Jobs stop at their next checkpoint and report themselves as cancelled.
+#[tauri::command]
+pub fn cancel_job(id: String) -> Result<(), String> {
+    if manager::cancel(&id) {
+        Ok(())
+    } else {
+        Err(format!("No active job with id {}", id))
+    }
+}
+
+/// List jobs that were interrupted by a crash or by quitting the app before they ended
+///
+/// Each comes with the parameters to start it again, the items it finished (with their
+/// results) and the items still to do, so the frontend can offer to resume it.
+#[tauri::command]
+pub async fn list_interrupted_jobs(app: AppHandle) -> Result<Vec<InterruptedJob>, String> {
+    let jobs = journal::list_interrupted(&app)?;
+    Ok(jobs
+        .into_iter()
+        .filter(|j| !manager::is_active(&j.id))
+        .collect())
+}
+
+/// Forget an interrupted job, after it was resumed or when it won't be
+#[tauri::command]
+pub async fn discard_interrupted_job(app: AppHandle, id: String) -> Result<(), String> {
+    if manager::is_active(&id) {
+        return Err(format!("Job {} is still running", id));
+    }
+
+    if journal::remove(&app, &id)? {
+        Ok(())
+    } else {
+        Err(format!("No interrupted job with id {}", id))
+    }
+}
+
+/// List finished caption jobs with failed files, whose failures `retry_failed` can
+/// caption again
+///
+/// Each comes with its latest result per file. Only the most recent such jobs are
+/// kept.
+#[tauri::command]
+pub async fn list_failed_jobs(app: AppHandle) -> Result<Vec<InterruptedJob>, String> {
+    journal::list_finished(&app)
+}
+
+/// Get how finished jobs are announced
+#[tauri::command]
+pub async fn get_notification_settings(app: AppHandle) -> Result<NotificationSettings, String> {
+    Ok(notify::load_settings(&app))
+}
+
+/// Change how finished jobs are announced: desktop notifications, a webhook, or both
+#[tauri::command]
+pub async fn update_notification_settings(
+    app: AppHandle,
+    settings: NotificationSettings,
+) -> Result<(), String> {
+    notify::save_settings(&app, &settings)
+}
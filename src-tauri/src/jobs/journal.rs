@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use super::manager::JobKind;
+use crate::file_system::atomic;
+
+/// Directory in the app data directory holding one journal file per running job
+const JOURNAL_DIR: &str = "spacecat-jobs";
+
+/// Directory inside [`JOURNAL_DIR`] keeping the journals of finished jobs with failed
+/// items, so the failures can be retried
+const FINISHED_DIR: &str = "finished";
+
+/// Journals of finished jobs kept; older ones are deleted
+const MAX_FINISHED: usize = 20;
+
+/// Start of the result of an item that failed
+pub const FAILED_PREFIX: &str = "Error: ";
+
+/// Serializes appends, so lines of concurrent updates never interleave
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+/// First line of a job journal: what the job does and how to run it again
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalHeader {
+    id: String,
+    kind: JobKind,
+    description: String,
+    params: serde_json::Value,
+    items: Vec<String>,
+    started: String,
+}
+
+/// Every further line: an item the job finished, with its result
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalItem {
+    item: String,
+    result: String,
+}
+
+/// A job that was still running when the app last exited or crashed, or a finished
+/// job with failed items
+#[derive(Debug, Clone, Serialize)]
+pub struct InterruptedJob {
+    pub id: String,
+    pub kind: JobKind,
+    pub description: String,
+    /// Arguments needed to run the job again; secrets like API keys are never recorded
+    pub params: serde_json::Value,
+    /// Items finished before the interruption, with their latest result
+    pub completed: Vec<(String, String)>,
+    /// Items that still have to be processed, in their original order
+    pub remaining: Vec<String>,
+    pub started: String,
+}
+
+fn journal_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join(JOURNAL_DIR))
+}
+
+fn journal_file(id: &str) -> Result<String, String> {
+    // Ids are generated by the job manager, but also arrive from the frontend
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid job id: {}", id));
+    }
+    Ok(format!("{}.jsonl", id))
+}
+
+fn journal_path(app: &AppHandle, id: &str) -> Result<PathBuf, String> {
+    Ok(journal_dir(app)?.join(journal_file(id)?))
+}
+
+fn finished_path(app: &AppHandle, id: &str) -> Result<PathBuf, String> {
+    Ok(journal_dir(app)?.join(FINISHED_DIR).join(journal_file(id)?))
+}
+
+impl InterruptedJob {
+    /// Items whose latest result is a failure
+    pub fn failed(&self) -> Vec<String> {
+        self.completed
+            .iter()
+            .filter(|(_, result)| result.starts_with(FAILED_PREFIX))
+            .map(|(item, _)| item.clone())
+            .collect()
+    }
+}
+
+/// Start the journal of a job, before it processes any item
+pub fn begin(
+    app: &AppHandle,
+    id: &str,
+    kind: JobKind,
+    description: &str,
+    params: serde_json::Value,
+    items: Vec<String>,
+) -> Result<(), String> {
+    let header = JournalHeader {
+        id: id.to_string(),
+        kind,
+        description: description.to_string(),
+        params,
+        items,
+        started: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    let mut line = serde_json::to_string(&header)
+        .map_err(|e| format!("Failed to serialize job journal: {}", e))?;
+    line.push('\n');
+
+    fs::create_dir_all(journal_dir(app)?)
+        .map_err(|e| format!("Failed to create job journal directory: {}", e))?;
+    atomic::write_atomic(&journal_path(app, id)?, line, true)
+        .map_err(|e| format!("Failed to write job journal: {}", e))
+}
+
+/// Record that a job finished an item
+///
+/// Appends a single line and syncs it, so the journal stays cheap to update for large
+/// batches and a crash loses at most the line being written.
+pub fn complete_item(app: &AppHandle, id: &str, item: &str, result: &str) -> Result<(), String> {
+    append(&journal_path(app, id)?, item, result)
+}
+
+/// Record the new result of an item of a finished job, replacing its earlier result
+pub fn record_retry(app: &AppHandle, id: &str, item: &str, result: &str) -> Result<(), String> {
+    append(&finished_path(app, id)?, item, result)
+}
+
+fn append(path: &Path, item: &str, result: &str) -> Result<(), String> {
+    let entry = JournalItem {
+        item: item.to_string(),
+        result: result.to_string(),
+    };
+    let mut line = serde_json::to_string(&entry)
+        .map_err(|e| format!("Failed to serialize job journal: {}", e))?;
+    line.push('\n');
+
+    let _lock = JOURNAL_LOCK.lock().map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open job journal: {}", e))?;
+    file.write_all(line.as_bytes())
+        .and_then(|_| file.sync_data())
+        .map_err(|e| format!("Failed to write job journal: {}", e))
+}
+
+/// Delete the journal of a job; returns false if there was none
+pub fn remove(app: &AppHandle, id: &str) -> Result<bool, String> {
+    let path = journal_path(app, id)?;
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    fs::remove_file(&path).map_err(|e| format!("Failed to delete job journal: {}", e))?;
+    Ok(true)
+}
+
+/// Keep the journal of a job that ended for retrying its failed items, or delete it if
+/// none failed
+pub fn finish(app: &AppHandle, id: &str) -> Result<(), String> {
+    let path = journal_path(app, id)?;
+    if !path.exists() {
+        return Ok(());
+    }
+    if read_journal(&path)?.failed().is_empty() {
+        return fs::remove_file(&path).map_err(|e| format!("Failed to delete job journal: {}", e));
+    }
+
+    let finished = finished_path(app, id)?;
+    if let Some(dir) = finished.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create job journal directory: {}", e))?;
+    }
+    fs::rename(&path, &finished).map_err(|e| format!("Failed to keep job journal: {}", e))?;
+
+    // Forget the oldest finished jobs
+    let mut jobs = list_finished(app)?;
+    let excess = jobs.len().saturating_sub(MAX_FINISHED);
+    for job in jobs.drain(..excess) {
+        let _ = fs::remove_file(finished_path(app, &job.id)?);
+    }
+    Ok(())
+}
+
+/// Read the journal of a finished job with failed items
+pub fn read_finished(app: &AppHandle, id: &str) -> Result<InterruptedJob, String> {
+    let path = finished_path(app, id)?;
+    if !path.is_file() {
+        return Err(format!("No finished job with failed items has id {}", id));
+    }
+    read_journal(&path)
+}
+
+/// Read one journal; a line cut off by a crash is ignored
+fn read_journal(path: &Path) -> Result<InterruptedJob, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut lines = content.lines();
+
+    let header: JournalHeader = lines
+        .next()
+        .ok_or_else(|| "Empty job journal".to_string())
+        .and_then(|line| serde_json::from_str(line).map_err(|e| e.to_string()))?;
+
+    // Retried items are listed again; their latest result counts
+    let mut completed: Vec<(String, String)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for entry in lines.filter_map(|line| serde_json::from_str::<JournalItem>(line).ok()) {
+        match positions.get(&entry.item) {
+            Some(&position) => completed[position].1 = entry.result,
+            None => {
+                positions.insert(entry.item.clone(), completed.len());
+                completed.push((entry.item, entry.result));
+            }
+        }
+    }
+
+    let remaining = header
+        .items
+        .iter()
+        .filter(|item| !positions.contains_key(item.as_str()))
+        .cloned()
+        .collect();
+
+    Ok(InterruptedJob {
+        id: header.id,
+        kind: header.kind,
+        description: header.description,
+        params: header.params,
+        completed,
+        remaining,
+        started: header.started,
+    })
+}
+
+/// List the journals left behind by jobs that never finished, oldest first
+///
+/// Jobs remove their journal when they end, so at startup every journal belongs to a
+/// job interrupted by a crash or by quitting the app. Journals that can't be read are
+/// skipped.
+pub fn list_interrupted(app: &AppHandle) -> Result<Vec<InterruptedJob>, String> {
+    read_journals(&journal_dir(app)?)
+}
+
+/// List the finished jobs whose failed items can be retried, oldest first
+pub fn list_finished(app: &AppHandle) -> Result<Vec<InterruptedJob>, String> {
+    read_journals(&journal_dir(app)?.join(FINISHED_DIR))
+}
+
+fn read_journals(dir: &Path) -> Result<Vec<InterruptedJob>, String> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut jobs = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        if path.extension().map(|e| e != "jsonl").unwrap_or(true) {
+            continue;
+        }
+
+        match read_journal(&path) {
+            Ok(job) => jobs.push(job),
+            Err(e) => warn!("Skipping job journal {}: {}", path.display(), e),
+        }
+    }
+
+    jobs.sort_by(|a, b| a.started.cmp(&b.started));
+    Ok(jobs)
+}
@@ -0,0 +1,467 @@
+use chrono::Local;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tracing::warn;
+
+use super::{journal, notify};
+
+/// Event emitted whenever a job is queued, makes progress or finishes
+pub const JOB_EVENT: &str = "job-progress";
+
+/// Error returned by jobs that stopped because they were cancelled
+pub const CANCELLED: &str = "Job cancelled";
+
+/// Finished jobs kept around for `list_jobs`
+const MAX_FINISHED_JOBS: usize = 50;
+
+/// Kind of long-running operation; each kind has its own concurrency limit
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Import,
+    Export,
+    Trim,
+    Crop,
+    Captioning,
+    Thumbnails,
+    Indexing,
+}
+
+impl JobKind {
+    /// Number of jobs of this kind that may run at the same time; the rest wait
+    fn max_concurrent(self) -> usize {
+        match self {
+            // Disk-bound, so running them side by side only slows each down
+            JobKind::Import | JobKind::Export => 1,
+            // ffmpeg already uses every core
+            JobKind::Trim | JobKind::Crop => 1,
+            // Captioning requests are rate limited by the providers
+            JobKind::Captioning => 1,
+            JobKind::Thumbnails => 2,
+            // Hashing reads whole files, like imports
+            JobKind::Indexing => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// How far a job has come; `total` is 0 while unknown
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JobProgress {
+    pub current: u64,
+    pub total: u64,
+    pub message: Option<String>,
+}
+
+/// State of a job, as listed and sent with every job event
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: JobKind,
+    pub description: String,
+    pub status: JobStatus,
+    pub progress: JobProgress,
+    pub error: Option<String>,
+    pub created: String,
+    pub finished: Option<String>,
+}
+
+struct JobEntry {
+    info: JobInfo,
+    cancel: Arc<AtomicBool>,
+}
+
+static JOBS: Lazy<Mutex<Vec<JobEntry>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Running jobs per kind, with a condition variable to wake queued jobs
+static SLOTS: Lazy<(Mutex<HashMap<JobKind, usize>>, Condvar)> =
+    Lazy::new(|| (Mutex::new(HashMap::new()), Condvar::new()));
+
+/// When detached jobs last printed their progress, to print at most once a second
+static LAST_PRINTED: Lazy<Mutex<Option<Instant>>> = Lazy::new(|| Mutex::new(None));
+
+fn now() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Handle given to a running job to report progress and check for cancellation
+#[derive(Clone)]
+pub struct Job {
+    id: String,
+    kind: JobKind,
+    description: String,
+    cancel: Arc<AtomicBool>,
+    /// Set once the job keeps a journal on disk to resume from
+    journaled: Arc<AtomicBool>,
+    /// None for detached jobs, which print their progress instead of emitting it
+    app: Option<AppHandle>,
+    /// When the job was queued, to tell how long it took
+    started: Instant,
+}
+
+impl Job {
+    /// Flag set when the job is cancelled, for code that polls an `AtomicBool`
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        &self.cancel
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    /// Return [`CANCELLED`] as an error once the job has been cancelled
+    pub fn check_cancelled(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err(CANCELLED.to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Report progress; callers should throttle frequent updates
+    pub fn progress(&self, current: u64, total: u64, message: Option<String>) {
+        self.update(|info| {
+            info.progress = JobProgress {
+                current,
+                total,
+                message,
+            }
+        });
+    }
+
+    /// Record the job on disk, so it can be resumed if the app exits before it ends
+    ///
+    /// `params` are the arguments needed to start the job again and `items` what it
+    /// works through; report each finished item with [`Job::complete_item`]. A job that
+    /// can't write its journal still runs, it just can't be resumed.
+    pub fn make_resumable(&self, params: serde_json::Value, items: &[String]) {
+        // Detached jobs run from the command line, where nothing resumes them
+        let Some(app) = &self.app else {
+            return;
+        };
+        let result = journal::begin(
+            app,
+            &self.id,
+            self.kind,
+            &self.description,
+            params,
+            items.to_vec(),
+        );
+        match result {
+            Ok(()) => self.journaled.store(true, Ordering::SeqCst),
+            Err(e) => warn!("Job {} can't be resumed: {}", self.id, e),
+        }
+    }
+
+    /// Record that an item of a resumable job is done, with its result
+    pub fn complete_item(&self, item: &str, result: &str) {
+        let Some(app) = &self.app else {
+            return;
+        };
+        if !self.journaled.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Err(e) = journal::complete_item(app, &self.id, item, result) {
+            warn!("Failed to record progress of job {}: {}", self.id, e);
+        }
+    }
+
+    /// Advance the progress by `amount`, keeping the total and message
+    pub fn advance(&self, amount: u64) {
+        self.update(|info| info.progress.current += amount);
+    }
+
+    fn update(&self, change: impl FnOnce(&mut JobInfo)) {
+        let info = match JOBS.lock() {
+            Ok(mut jobs) => match jobs.iter_mut().find(|j| j.info.id == self.id) {
+                Some(entry) => {
+                    change(&mut entry.info);
+                    entry.info.clone()
+                }
+                None => return,
+            },
+            Err(_) => return,
+        };
+
+        match &self.app {
+            Some(app) => {
+                let _ = app.emit(JOB_EVENT, info);
+            }
+            None => print_progress(&info),
+        }
+    }
+
+    /// Wait until a slot for this kind of job is free, or the job is cancelled
+    fn acquire_slot(&self) -> Result<SlotGuard, String> {
+        let (lock, available) = &*SLOTS;
+        let mut running = lock.lock().map_err(|e| e.to_string())?;
+
+        loop {
+            self.check_cancelled()?;
+
+            let count = running.entry(self.kind).or_insert(0);
+            if *count < self.kind.max_concurrent() {
+                *count += 1;
+                return Ok(SlotGuard(self.kind));
+            }
+
+            // Wake up now and then to notice cancellation of a queued job
+            running = available
+                .wait_timeout(running, Duration::from_millis(500))
+                .map_err(|e| e.to_string())?
+                .0;
+        }
+    }
+
+    /// Record how the job ended; [`run_async`] does this for the jobs it runs
+    pub fn finish<T>(&self, result: &Result<T, String>) {
+        let cancelled = self.is_cancelled();
+        self.update(|info| {
+            info.finished = Some(now());
+            match result {
+                // Jobs that stop early may still return what they finished
+                _ if cancelled => info.status = JobStatus::Cancelled,
+                Ok(_) => info.status = JobStatus::Completed,
+                Err(e) if e == CANCELLED => info.status = JobStatus::Cancelled,
+                Err(e) => {
+                    info.status = JobStatus::Failed;
+                    info.error = Some(e.clone());
+                }
+            }
+        });
+
+        if let Some(app) = &self.app {
+            if let Some(info) = list().into_iter().find(|j| j.id == self.id) {
+                notify::job_finished(app, &info, self.started.elapsed());
+            }
+        }
+
+        // A job that ended, however it ended, has nothing left to resume, but its
+        // failed items can still be retried
+        if let (Some(app), true) = (&self.app, self.journaled.load(Ordering::SeqCst)) {
+            if let Err(e) = journal::finish(app, &self.id) {
+                warn!("Failed to remove journal of job {}: {}", self.id, e);
+            }
+        }
+
+        // Forget the oldest finished jobs
+        if let Ok(mut jobs) = JOBS.lock() {
+            let finished = jobs.iter().filter(|j| j.info.finished.is_some()).count();
+            let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+            jobs.retain(|j| {
+                if excess > 0 && j.info.finished.is_some() {
+                    excess -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+}
+
+/// Frees a job's concurrency slot when it ends, even if it panicked
+struct SlotGuard(JobKind);
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        let (lock, available) = &*SLOTS;
+        if let Ok(mut running) = lock.lock() {
+            if let Some(count) = running.get_mut(&self.0) {
+                *count = count.saturating_sub(1);
+            }
+        }
+        available.notify_all();
+    }
+}
+
+/// Print the progress of a detached job to stderr, throttled except when it finishes
+fn print_progress(info: &JobInfo) {
+    let finished = info.finished.is_some();
+    if let Ok(mut last) = LAST_PRINTED.lock() {
+        let due = last.is_none_or(|time| time.elapsed() >= Duration::from_secs(1));
+        if !due && !finished {
+            return;
+        }
+        *last = Some(Instant::now());
+    }
+
+    let progress = &info.progress;
+    let mut line = match progress.total {
+        0 => format!("[{}] {}", progress.current, info.description),
+        total => format!("[{}/{}] {}", progress.current, total, info.description),
+    };
+    if let Some(message) = &progress.message {
+        line.push_str(&format!(": {}", message));
+    }
+    if finished {
+        line.push_str(&format!(" ({:?})", info.status).to_lowercase());
+    }
+    eprintln!("{}", line);
+}
+
+fn register(app: Option<&AppHandle>, kind: JobKind, description: String) -> Job {
+    // Prefixed with the start time so ids stay unique across restarts, for journals
+    let id = format!(
+        "{}-{}",
+        Local::now().format("%Y%m%d%H%M%S"),
+        NEXT_ID.fetch_add(1, Ordering::SeqCst)
+    );
+    let cancel = Arc::new(AtomicBool::new(false));
+    let info = JobInfo {
+        id: id.clone(),
+        kind,
+        description: description.clone(),
+        status: JobStatus::Queued,
+        progress: JobProgress::default(),
+        error: None,
+        created: now(),
+        finished: None,
+    };
+
+    if let Ok(mut jobs) = JOBS.lock() {
+        jobs.push(JobEntry {
+            info: info.clone(),
+            cancel: cancel.clone(),
+        });
+    }
+    if let Some(app) = app {
+        let _ = app.emit(JOB_EVENT, info);
+    }
+
+    Job {
+        id,
+        kind,
+        description,
+        cancel,
+        journaled: Arc::new(AtomicBool::new(false)),
+        app: app.cloned(),
+        started: Instant::now(),
+    }
+}
+
+/// Start a job outside the app, for the headless command line
+///
+/// The job runs right away without waiting for a slot, prints its progress to stderr
+/// and is never journaled; hand its outcome to [`Job::finish`].
+pub fn detached(kind: JobKind, description: impl Into<String>) -> Job {
+    let job = register(None, kind, description.into());
+    job.update(|info| info.status = JobStatus::Running);
+    job
+}
+
+/// Run an async operation as a job
+///
+/// The job is listed right away, waits for a free slot of its kind, and its outcome
+/// (completed, failed or cancelled) is recorded when the future resolves.
+pub async fn run_async<T, F, Fut>(
+    app: &AppHandle,
+    kind: JobKind,
+    description: impl Into<String>,
+    operation: F,
+) -> Result<T, String>
+where
+    F: FnOnce(Job) -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    let job = register(Some(app), kind, description.into());
+
+    let waiting = job.clone();
+    let slot = tauri::async_runtime::spawn_blocking(move || waiting.acquire_slot())
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|slot| slot);
+    let _slot = match slot {
+        Ok(slot) => slot,
+        Err(e) => {
+            let result = Err(e);
+            job.finish::<T>(&result);
+            return result;
+        }
+    };
+
+    job.update(|info| info.status = JobStatus::Running);
+    let result = operation(job.clone()).await;
+    job.finish(&result);
+
+    result
+}
+
+/// Run a blocking operation as a job on a worker thread (see [`run_async`])
+pub async fn run<T, F>(
+    app: &AppHandle,
+    kind: JobKind,
+    description: impl Into<String>,
+    operation: F,
+) -> Result<T, String>
+where
+    F: FnOnce(&Job) -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    run_async(app, kind, description, |job| async move {
+        tauri::async_runtime::spawn_blocking(move || operation(&job))
+            .await
+            .map_err(|e| e.to_string())?
+    })
+    .await
+}
+
+/// List all known jobs, oldest first
+pub fn list() -> Vec<JobInfo> {
+    JOBS.lock()
+        .map(|jobs| jobs.iter().map(|j| j.info.clone()).collect())
+        .unwrap_or_default()
+}
+
+/// The most recently started job of a kind
+pub fn latest(kind: JobKind) -> Option<JobInfo> {
+    list().into_iter().rev().find(|j| j.kind == kind)
+}
+
+/// Check whether a job is queued or running in this session
+pub fn is_active(id: &str) -> bool {
+    list().iter().any(|j| j.id == id && j.finished.is_none())
+}
+
+/// Ask a queued or running job to stop; returns false if it isn't active
+pub fn cancel(id: &str) -> bool {
+    let jobs = match JOBS.lock() {
+        Ok(jobs) => jobs,
+        Err(_) => return false,
+    };
+
+    match jobs.iter().find(|j| j.info.id == id) {
+        Some(entry) if entry.info.finished.is_none() => {
+            entry.cancel.store(true, Ordering::SeqCst);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Cancel every active job of a kind, returning how many were cancelled
+pub fn cancel_kind(kind: JobKind) -> usize {
+    let ids: Vec<String> = list()
+        .into_iter()
+        .filter(|j| j.kind == kind && j.finished.is_none())
+        .map(|j| j.id)
+        .collect();
+
+    ids.iter().filter(|id| cancel(id)).count()
+}
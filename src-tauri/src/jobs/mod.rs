@@ -0,0 +1,5 @@
+pub mod commands;
+pub mod journal;
+pub mod manager;
+pub mod notify;
+pub mod pool;
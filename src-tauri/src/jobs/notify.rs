@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+use tracing::warn;
+
+use super::manager::{JobInfo, JobStatus};
+use crate::file_system::atomic;
+
+/// File in the app data directory holding the notification settings
+const SETTINGS_FILE: &str = "spacecat-notifications.json";
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(15);
+
+fn default_desktop() -> bool {
+    true
+}
+
+fn default_min_duration() -> u64 {
+    30
+}
+
+/// How the user is told that a job finished
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSettings {
+    /// Show a desktop notification
+    #[serde(default = "default_desktop")]
+    pub desktop: bool,
+    /// URL a summary of each finished job is POSTed to as JSON, e.g. a Discord or
+    /// Slack webhook
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// Jobs that finish sooner aren't announced, since the user is likely still
+    /// watching them
+    #[serde(default = "default_min_duration")]
+    pub min_duration_secs: u64,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            desktop: default_desktop(),
+            webhook_url: None,
+            min_duration_secs: default_min_duration(),
+        }
+    }
+}
+
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join(SETTINGS_FILE))
+}
+
+/// Load the notification settings, or the defaults if none were saved
+pub fn load_settings(app: &AppHandle) -> NotificationSettings {
+    let path = match settings_path(app) {
+        Ok(path) if path.is_file() => path,
+        _ => return NotificationSettings::default(),
+    };
+
+    fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            warn!("Invalid notification settings, using defaults: {}", e);
+            NotificationSettings::default()
+        })
+}
+
+pub fn save_settings(app: &AppHandle, settings: &NotificationSettings) -> Result<(), String> {
+    if let Some(url) = &settings.webhook_url {
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(format!("Invalid webhook URL: {}", url));
+        }
+    }
+
+    let path = settings_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    atomic::write_atomic(&path, &content, false).map_err(|e| e.to_string())
+}
+
+/// Format a duration like "1h 05m", "3m 20s" or "42s"
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, _) => format!("{}h {:02}m", h, m),
+    }
+}
+
+/// Title and body of the notification for a finished job
+fn describe(info: &JobInfo, duration: Duration) -> (String, String) {
+    let kind = format!("{:?}", info.kind);
+    let progress = &info.progress;
+    let counts = match progress.total {
+        0 => String::new(),
+        total => format!(" ({}/{})", progress.current, total),
+    };
+
+    match &info.error {
+        Some(error) => (
+            format!("{} failed", kind),
+            format!("{}{}: {}", info.description, counts, error),
+        ),
+        None => (
+            format!("{} finished", kind),
+            format!(
+                "{}{} in {}",
+                info.description,
+                counts,
+                format_duration(duration)
+            ),
+        ),
+    }
+}
+
+/// Tell the user that a job finished, on the desktop and through the webhook
+///
+/// Cancelled jobs and jobs quicker than the configured minimum are skipped. The webhook
+/// is called in the background, and failures of either are only logged.
+pub fn job_finished(app: &AppHandle, info: &JobInfo, duration: Duration) {
+    let settings = load_settings(app);
+    if info.status == JobStatus::Cancelled || duration.as_secs() < settings.min_duration_secs {
+        return;
+    }
+
+    let (title, body) = describe(info, duration);
+    if settings.desktop {
+        if let Err(e) = app
+            .notification()
+            .builder()
+            .title(&title)
+            .body(&body)
+            .show()
+        {
+            warn!("Failed to show notification: {}", e);
+        }
+    }
+
+    if let Some(url) = settings.webhook_url {
+        let text = format!("{}: {}", title, body);
+        let payload = json!({
+            // What Slack (text) and Discord (content) show
+            "text": text,
+            "content": text,
+            "job_id": info.id,
+            "kind": info.kind,
+            "description": info.description,
+            "status": info.status,
+            "current": info.progress.current,
+            "total": info.progress.total,
+            "duration_secs": duration.as_secs(),
+            "error": info.error,
+            "finished": info.finished,
+        });
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = post_webhook(&url, &payload).await {
+                warn!("Job webhook failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn post_webhook(url: &str, payload: &serde_json::Value) -> Result<(), String> {
+    let client = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let response = client
+        .post(url)
+        .json(payload)
+        .send()
+        .await
+        // Webhook URLs carry their secret, so it's kept out of the logs
+        .map_err(|e| e.without_url().to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!("The webhook returned {}", response.status()));
+    }
+    Ok(())
+}
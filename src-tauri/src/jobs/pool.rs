@@ -0,0 +1,64 @@
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::num::NonZeroUsize;
+use std::thread;
+
+use super::manager::Job;
+
+/// Worker threads used when the number of CPU cores can't be determined
+const FALLBACK_WORKERS: usize = 4;
+
+/// Threads of dataset-wide work (hashing, probing, embedding), one per CPU core
+///
+/// Every feature runs its per-file work here rather than on threads of its own, so
+/// jobs running side by side share the cores instead of each claiming all of them.
+static POOL: Lazy<ThreadPool> = Lazy::new(|| {
+    ThreadPoolBuilder::new()
+        .num_threads(workers())
+        .thread_name(|index| format!("spacecat-worker-{}", index))
+        .build()
+        .expect("worker pool can be started")
+});
+
+/// Number of threads of the worker pool
+pub fn workers() -> usize {
+    thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(FALLBACK_WORKERS)
+}
+
+/// Run an operation on the worker pool, which also runs any parallel iterator inside it
+///
+/// Blocks until the operation returns, so callers hand out no more work than the pool
+/// has threads for.
+pub fn install<R: Send>(operation: impl FnOnce() -> R + Send) -> R {
+    POOL.install(operation)
+}
+
+/// Process the items of a job on the worker pool, advancing its progress by one per item
+///
+/// Workers take the next item as they free up. After the first error, or once the job
+/// is cancelled, no further item is started and the error (or [`super::manager::CANCELLED`])
+/// is returned.
+pub fn map<T, R>(
+    job: &Job,
+    items: Vec<T>,
+    operation: impl Fn(T) -> Result<R, String> + Sync + Send,
+) -> Result<Vec<R>, String>
+where
+    T: Send,
+    R: Send,
+{
+    install(|| {
+        items
+            .into_par_iter()
+            .map(|item| {
+                job.check_cancelled()?;
+                let result = operation(item)?;
+                job.advance(1);
+                Ok(result)
+            })
+            .collect()
+    })
+}
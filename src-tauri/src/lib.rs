@@ -1,56 +1,292 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 mod api;
+mod backup;
+mod captions;
+mod cli;
+mod experiments;
 mod file_system;
+mod jobs;
+mod logging;
 mod media;
+mod project;
+mod remote;
+mod semantic;
+mod undo;
 
 use file_system::commands::{
-    delete_media_file, delete_project_directory, duplicate_directory, duplicate_media_file, export_directory,
-    list_directory_files, list_project_directories, open_project_directory, read_caption_file,
-    register_working_directory, select_directory, select_export_directory, write_caption_file,
+    cancel_duplicate_directory, clean_up_edit_leftovers, clone_project, delete_media_file,
+    delete_media_files, delete_project_directory, duplicate_directory, duplicate_media_file,
+    export_caption_files, export_directory, get_dedup_report, get_hot_folders, get_working_root,
+    import_catalog_export, import_export_archive, list_directory_files, list_project_directories,
+    merge_import_directory, move_working_root, open_project_directory, open_with_default_app,
+    read_caption_file, refresh_directory, register_working_directory, rename_media_file,
+    rename_project, reveal_media_file, select_directory, select_export_directory, set_hot_folder, sync_project,
+    write_caption_file, write_caption_files,
 };
 
-use api::commands::{generate_caption, generate_captions, generate_gemini_caption, generate_gemini_captions};
-use media::commands::{crop_video, extract_video_frame, get_media_thumbnail, get_trim_progress, reset_trim_progress, save_cropped_image, trim_video};
+use api::commands::{
+    caption_files_with_profile, caption_with_profile, cleanup_gemini_files,
+    delete_provider_profile, generate_alt_text, generate_alt_texts, generate_caption,
+    generate_captions, generate_gemini_alt_text, generate_gemini_alt_texts,
+    generate_gemini_caption, generate_gemini_captions, generate_script_caption,
+    generate_script_captions, get_provider_limits, list_provider_profiles, retry_failed,
+    sample_uncaptioned_files, save_provider_profile, stream_caption, stream_gemini_caption,
+    update_provider_limits,
+};
+use backup::commands::{
+    create_project_backup, list_project_backups, prune_project_backups, restore_project_backup,
+};
+use captions::commands::{
+    add_spelling_words, clean_up_orphan_captions, commit_captions, convert_caption_format,
+    diff_captions, enable_caption_git, export_alt_text, find_orphan_captions, get_caption_git_diff,
+    get_caption_git_log, get_caption_history, get_caption_stats, get_tag_dictionary,
+    get_tag_frequencies, import_booru_tags, import_caption_files, lint_captions,
+    list_caption_languages, merge_caption_sets, merge_captions, modify_captions, normalize_tags,
+    propagate_caption, read_media_caption, reapply_trigger_rules, replace_in_captions,
+    restore_caption_version, revert_caption_commit, save_tag_dictionary, search_captions,
+    seed_captions_from_metadata, write_media_caption,
+};
+use experiments::commands::{
+    delete_experiment, export_experiment, list_experiments, run_experiment,
+};
+use jobs::commands::{
+    cancel_job, discard_interrupted_job, get_notification_settings, list_failed_jobs,
+    list_interrupted_jobs, list_jobs, update_notification_settings,
+};
+use logging::commands::{get_log_settings, set_log_level};
+use media::commands::{
+    audit_jpeg_quality, convert_animation_to_mp4, convert_images, convert_pdf_documents,
+    convert_raw_images, crop_to_aspect, crop_video, detect_image_borders, downscale_videos,
+    embed_caption_metadata, extract_keyframes, extract_video_frame, find_bursts,
+    get_crop_presets, get_dataset_colors, get_ffmpeg_capabilities, get_image_colors,
+    get_media_thumbnail, get_pdf_pages, get_trim_progress, pregenerate_thumbnails,
+    reset_trim_progress, save_cropped_image, tile_images, trim_image_borders, trim_video,
+    upscale_images,
+};
+use project::commands::{
+    export_kohya_dataset, generate_class_images, generate_report, get_attribution, get_file_marks,
+    get_project_overview, get_project_settings, refresh_project_index, set_file_attribution,
+    set_file_favorite, set_file_flag, set_file_rating, set_project_attribution,
+    update_project_settings,
+};
+use remote::commands::{export_to_s3, export_to_sftp, upload_to_hub};
+use semantic::commands::{
+    detect_subjects, detect_text_regions, get_clip_model_info, get_subject_model_info,
+    get_text_model_info, index_embeddings, score_caption_alignment, semantic_search,
+};
+use undo::commands::{list_operation_history, undo_last_operation};
+
+/// Run a command from the command line without starting the GUI, given the arguments
+/// after `--headless`; returns the process exit code
+pub fn run_headless(args: &[String]) -> i32 {
+    cli::run(args)
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
-        .plugin(tauri_plugin_log::Builder::default().build())
+        .plugin(logging::filter::builder().build())
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_dialog::init())
         // Add required plugins
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_persisted_scope::init())
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .register_asynchronous_uri_scheme_protocol(
+            media::protocol::SCHEME,
+            |ctx, request, responder| {
+                media::protocol::handle(ctx.app_handle().clone(), request, responder)
+            },
+        )
+        .setup(|app| {
+            logging::filter::load(app.handle());
+            file_system::location::load(app.handle());
+            api::limits::load(app.handle());
+            backup::schedule::start(app.handle().clone());
+            file_system::leftovers::clean_on_startup(app.handle().clone());
+            // Probe FFmpeg in the background so the first media command doesn't wait
+            tauri::async_runtime::spawn_blocking(media::ffmpeg::capabilities);
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            // Let other windows open the projects a closed window had open
+            if let tauri::WindowEvent::Destroyed = event {
+                project::lock::release_others(window.label(), None);
+            }
+        })
         // Register command handlers
         .invoke_handler(tauri::generate_handler![
             // File system commands
             select_directory,
             select_export_directory,
             duplicate_directory,
+            cancel_duplicate_directory,
+            get_dedup_report,
+            merge_import_directory,
+            sync_project,
+            clean_up_edit_leftovers,
+            import_export_archive,
+            import_catalog_export,
             register_working_directory,
             read_caption_file,
             write_caption_file,
+            write_caption_files,
             list_directory_files,
+            refresh_directory,
+            set_hot_folder,
+            get_hot_folders,
             export_directory,
+            export_caption_files,
             list_project_directories,
             delete_project_directory,
             open_project_directory,
+            get_working_root,
+            move_working_root,
+            reveal_media_file,
+            open_with_default_app,
+            rename_project,
+            clone_project,
             delete_media_file,
+            delete_media_files,
+            rename_media_file,
             duplicate_media_file,
+            // Caption commands
+            read_media_caption,
+            write_media_caption,
+            list_caption_languages,
+            convert_caption_format,
+            get_caption_history,
+            restore_caption_version,
+            search_captions,
+            replace_in_captions,
+            modify_captions,
+            propagate_caption,
+            merge_captions,
+            seed_captions_from_metadata,
+            get_caption_stats,
+            find_orphan_captions,
+            clean_up_orphan_captions,
+            get_tag_frequencies,
+            get_tag_dictionary,
+            save_tag_dictionary,
+            normalize_tags,
+            lint_captions,
+            add_spelling_words,
+            import_booru_tags,
+            import_caption_files,
+            diff_captions,
+            merge_caption_sets,
+            reapply_trigger_rules,
+            export_alt_text,
+            enable_caption_git,
+            commit_captions,
+            get_caption_git_log,
+            get_caption_git_diff,
+            revert_caption_commit,
             // Media commands
             get_media_thumbnail,
+            get_ffmpeg_capabilities,
             crop_video,
+            get_crop_presets,
+            crop_to_aspect,
+            detect_image_borders,
+            trim_image_borders,
             trim_video,
             save_cropped_image,
             reset_trim_progress,
             get_trim_progress,
             extract_video_frame,
+            extract_keyframes,
+            convert_images,
+            convert_animation_to_mp4,
+            convert_raw_images,
+            convert_pdf_documents,
+            get_pdf_pages,
+            upscale_images,
+            downscale_videos,
+            audit_jpeg_quality,
+            get_image_colors,
+            get_dataset_colors,
+            tile_images,
+            find_bursts,
+            pregenerate_thumbnails,
+            embed_caption_metadata,
+            // Project commands
+            get_project_settings,
+            update_project_settings,
+            get_project_overview,
+            refresh_project_index,
+            generate_report,
+            export_kohya_dataset,
+            generate_class_images,
+            get_file_marks,
+            set_file_rating,
+            set_file_flag,
+            set_file_favorite,
+            get_attribution,
+            set_project_attribution,
+            set_file_attribution,
+            // Undo commands
+            undo_last_operation,
+            list_operation_history,
+            // Job commands
+            list_jobs,
+            cancel_job,
+            list_interrupted_jobs,
+            discard_interrupted_job,
+            list_failed_jobs,
+            get_notification_settings,
+            update_notification_settings,
+            // Logging commands
+            get_log_settings,
+            set_log_level,
+            // Semantic search commands
+            get_clip_model_info,
+            index_embeddings,
+            semantic_search,
+            score_caption_alignment,
+            get_text_model_info,
+            detect_text_regions,
+            get_subject_model_info,
+            detect_subjects,
+            // Remote export commands
+            upload_to_hub,
+            export_to_s3,
+            export_to_sftp,
+            // Backup commands
+            create_project_backup,
+            list_project_backups,
+            restore_project_backup,
+            prune_project_backups,
             // API commands
             generate_caption,
             generate_captions,
             generate_gemini_caption,
             generate_gemini_captions,
+            generate_script_caption,
+            generate_script_captions,
+            stream_caption,
+            stream_gemini_caption,
+            generate_alt_text,
+            generate_alt_texts,
+            generate_gemini_alt_text,
+            generate_gemini_alt_texts,
+            cleanup_gemini_files,
+            get_provider_limits,
+            update_provider_limits,
+            list_provider_profiles,
+            save_provider_profile,
+            delete_provider_profile,
+            caption_with_profile,
+            caption_files_with_profile,
+            sample_uncaptioned_files,
+            retry_failed,
+            // Experiment commands
+            run_experiment,
+            list_experiments,
+            delete_experiment,
+            export_experiment,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,47 @@
+use tauri::AppHandle;
+
+use super::filter::{self, LogLevel, LogSettings};
+
+/// Get the log level of the app and the levels of single modules
+#[tauri::command]
+pub async fn get_log_settings() -> Result<LogSettings, String> {
+    Ok(filter::current())
+}
+
+/// Change how much is logged, from now on and after restarts
+///
+/// Without a module this sets the level of the whole app. With one (e.g. `media` or
+/// `api::commands`) it sets the level of that module and its submodules, or removes
+/// it when no level is given.
+#[tauri::command]
+pub async fn set_log_level(
+    app: AppHandle,
+    level: Option<LogLevel>,
+    module: Option<String>,
+) -> Result<LogSettings, String> {
+    let mut settings = filter::current();
+
+    match module.as_deref().map(str::trim) {
+        Some("") => return Err("Empty module name".to_string()),
+        Some(module) => {
+            let module = module
+                .strip_prefix("spacecat_caption_lib::")
+                .unwrap_or(module)
+                .to_string();
+            match level {
+                Some(level) => {
+                    settings.modules.insert(module, level);
+                }
+                None => {
+                    settings.modules.remove(&module);
+                }
+            }
+        }
+        None => {
+            settings.level = level.ok_or("A log level is required without a module")?;
+        }
+    }
+
+    filter::save(&app, settings.clone())?;
+    Ok(settings)
+}
@@ -0,0 +1,181 @@
+use log::{LevelFilter, Metadata};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tauri::{AppHandle, Manager};
+
+use crate::file_system::atomic;
+
+/// File in the app data directory holding the log levels
+const SETTINGS_FILE: &str = "spacecat-logging.json";
+
+/// Prefix of the log targets of this crate, left out of module names
+const CRATE_TARGET: &str = "spacecat_caption_lib::";
+
+/// Prefix of the log targets of messages from the frontend
+const WEBVIEW_TARGET: &str = "webview";
+
+/// How much is logged
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// Log levels of the app and of single modules
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// Level of the app's own modules without a level of their own
+    pub level: LogLevel,
+    /// Levels by module path, e.g. `media` or `api::commands`, which also apply to
+    /// their submodules; dependencies are named by their crate, e.g. `reqwest`
+    pub modules: BTreeMap<String, LogLevel>,
+}
+
+impl LogSettings {
+    /// Whether a record of a target (module path) at a level is logged
+    ///
+    /// The most specific module level applies. Dependencies without a level of their
+    /// own only log warnings and errors, since their debug output drowns the app's; the
+    /// frontend counts as part of the app.
+    fn enabled(&self, target: &str, level: log::Level) -> bool {
+        let (module, own) = match target.strip_prefix(CRATE_TARGET) {
+            Some(module) => (module, true),
+            None => (target, target.starts_with(WEBVIEW_TARGET)),
+        };
+
+        let specific = self
+            .modules
+            .iter()
+            .filter(|(name, _)| {
+                module == name.as_str()
+                    || module
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, level)| level.filter());
+
+        let filter = match specific {
+            Some(filter) => filter,
+            None if own => self.level.filter(),
+            None => self.level.filter().min(LevelFilter::Warn),
+        };
+        level <= filter
+    }
+}
+
+static SETTINGS: Lazy<RwLock<LogSettings>> = Lazy::new(|| RwLock::new(LogSettings::default()));
+
+/// Builder of the log plugin, passing records through the current log levels
+///
+/// The plugin itself lets everything through, so levels can change while running.
+pub fn builder() -> tauri_plugin_log::Builder {
+    tauri_plugin_log::Builder::default()
+        .level(LevelFilter::Trace)
+        .filter(|metadata: &Metadata| match SETTINGS.read() {
+            Ok(settings) => settings.enabled(metadata.target(), metadata.level()),
+            Err(_) => metadata.level() <= LevelFilter::Warn,
+        })
+}
+
+/// Logger of headless runs, which have no log plugin, writing to stderr
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Log to stderr in headless runs, at the level of the `SPACECAT_LOG` environment
+/// variable (default: warnings and errors)
+pub fn init_headless() {
+    let level = std::env::var("SPACECAT_LOG")
+        .ok()
+        .and_then(|level| level.trim().parse().ok())
+        .unwrap_or(LevelFilter::Warn);
+    if log::set_logger(&StderrLogger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// The log levels in use
+pub fn current() -> LogSettings {
+    SETTINGS
+        .read()
+        .map(|settings| settings.clone())
+        .unwrap_or_default()
+}
+
+fn apply(settings: LogSettings) {
+    if let Ok(mut current) = SETTINGS.write() {
+        *current = settings;
+    }
+}
+
+fn settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join(SETTINGS_FILE))
+}
+
+/// Apply the saved log levels, if any, at startup
+pub fn load(app: &AppHandle) {
+    let path = match settings_path(app) {
+        Ok(path) if path.is_file() => path,
+        _ => return,
+    };
+
+    match fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+    {
+        Ok(settings) => apply(settings),
+        Err(e) => tracing::warn!("Invalid log settings, using defaults: {}", e),
+    }
+}
+
+/// Apply log levels and save them for the next start
+pub fn save(app: &AppHandle, settings: LogSettings) -> Result<(), String> {
+    let path = settings_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    atomic::write_atomic(&path, &content, false).map_err(|e| e.to_string())?;
+
+    apply(settings);
+    Ok(())
+}
@@ -2,5 +2,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--headless") {
+        std::process::exit(spacecat_caption_lib::run_headless(&args[1..]));
+    }
+
     spacecat_caption_lib::run()
 }
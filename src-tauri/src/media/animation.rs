@@ -0,0 +1,132 @@
+use image::codecs::gif::GifDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::io::Reader as ImageReader;
+use image::{AnimationDecoder, DynamicImage, Frames, ImageFormat, ImageResult};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use super::formats;
+use crate::jobs::manager::Job;
+
+/// Frame delays below this many milliseconds are shown as [`DEFAULT_DELAY_MS`] by
+/// browsers, so they are counted that way too
+const MIN_DELAY_MS: f64 = 20.0;
+
+/// Delay browsers give frames without a usable one
+const DEFAULT_DELAY_MS: f64 = 100.0;
+
+/// Frame count and length of an animated GIF or WebP
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationInfo {
+    pub frame_count: u32,
+    /// Length of one loop in seconds
+    pub duration: f64,
+}
+
+/// Frames of a GIF or WebP, or `None` for other images and still WebPs
+///
+/// The format is detected from the contents, like [`formats::open_image`] does.
+fn frames(path: &Path) -> Option<Frames<'static>> {
+    let format = ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .format()?;
+    let reader = BufReader::new(File::open(path).ok()?);
+
+    match format {
+        ImageFormat::Gif => GifDecoder::new(reader).ok().map(|d| d.into_frames()),
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(reader).ok()?;
+            decoder.has_animation().then(|| decoder.into_frames())
+        }
+        _ => None,
+    }
+}
+
+/// Display time of a frame in milliseconds
+fn delay_ms(frame: &image::Frame) -> f64 {
+    let (numerator, denominator) = frame.delay().numer_denom_ms();
+    let delay = numerator as f64 / denominator.max(1) as f64;
+    if delay < MIN_DELAY_MS {
+        DEFAULT_DELAY_MS
+    } else {
+        delay
+    }
+}
+
+/// Count the frames of an animated GIF or WebP and add up their delays
+///
+/// Returns `None` for still images, including GIFs with a single frame.
+pub fn animation_info(path: &Path) -> Option<AnimationInfo> {
+    let mut frame_count = 0u32;
+    let mut duration_ms = 0.0;
+    for frame in frames(path)? {
+        let frame = frame.ok()?;
+        frame_count += 1;
+        duration_ms += delay_ms(&frame);
+    }
+
+    (frame_count > 1).then(|| AnimationInfo {
+        frame_count,
+        duration: duration_ms / 1000.0,
+    })
+}
+
+/// Whether an image is an animated GIF or WebP
+pub fn is_animated(path: &Path) -> bool {
+    frames(path).is_some_and(|mut frames| frames.nth(1).is_some())
+}
+
+/// Frame of an image that stands for the whole of it when captioning
+///
+/// For animations this is the middle frame, since the first one is often a blank or
+/// a title card; other images open as usual.
+pub fn representative_frame(path: &Path) -> ImageResult<DynamicImage> {
+    let Some(info) = animation_info(path) else {
+        return formats::open_image(path);
+    };
+
+    match frames(path).and_then(|mut frames| frames.nth(info.frame_count as usize / 2)) {
+        Some(frame) => Ok(DynamicImage::ImageRgba8(frame?.into_buffer())),
+        None => formats::open_image(path),
+    }
+}
+
+/// Write the frames of an animation to a directory as PNGs, along with an FFmpeg
+/// concat list giving each its delay
+///
+/// Returns the path of the list.
+pub fn write_frames(job: &Job, path: &Path, directory: &Path) -> Result<PathBuf, String> {
+    let frames = frames(path).ok_or_else(|| format!("Not an animation: {}", path.display()))?;
+
+    let mut list = String::from("ffconcat version 1.0\n");
+    let mut last = None;
+    for (index, frame) in frames.enumerate() {
+        job.check_cancelled()?;
+        let frame = frame.map_err(|e| format!("Failed to decode frame {}: {}", index, e))?;
+        let delay = delay_ms(&frame);
+
+        // Names are relative to the list, so the directory path needs no escaping
+        let frame_name = format!("frame_{:05}.png", index);
+        frame
+            .into_buffer()
+            .save_with_format(directory.join(&frame_name), ImageFormat::Png)
+            .map_err(|e| format!("Failed to write frame {}: {}", index, e))?;
+
+        let _ = writeln!(list, "file '{}'", frame_name);
+        let _ = writeln!(list, "duration {:.3}", delay / 1000.0);
+        last = Some(frame_name);
+    }
+
+    // The concat demuxer drops the duration of the last entry unless it is repeated
+    if let Some(last) = last {
+        let _ = writeln!(list, "file '{}'", last);
+    }
+
+    let list_path = directory.join("frames.txt");
+    fs::write(&list_path, list).map_err(|e| format!("Failed to write frame list: {}", e))?;
+    Ok(list_path)
+}
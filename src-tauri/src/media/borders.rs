@@ -0,0 +1,290 @@
+use image::{DynamicImage, GenericImageView, GrayImage};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use super::crop::{self, CropSummary};
+use super::formats;
+use crate::file_system::commands::scan_media_files;
+use crate::jobs::manager::Job;
+
+/// Largest difference in brightness from the edge color a border pixel may have
+const BORDER_TOLERANCE: u8 = 16;
+
+/// Share of the pixels of a border line that may stray from the edge color, for noise
+/// and compression artifacts
+const BORDER_OUTLIERS: f64 = 0.02;
+
+/// Borders thinner than this, in pixels, are ignored
+const MIN_BORDER: u32 = 2;
+
+/// Borders never take more than this share of a side, so flat images aren't trimmed
+/// away
+const MAX_BORDER_SHARE: f64 = 0.4;
+
+/// Bars darker than this count as letterboxing
+const LETTERBOX_BRIGHTNESS: f64 = 40.0;
+
+/// Width and height of the corner regions searched for watermarks, as shares of the
+/// image
+const CORNER_WIDTH: f64 = 0.25;
+const CORNER_HEIGHT: f64 = 0.15;
+
+/// Brightness step between neighboring pixels that counts as an edge
+const EDGE_THRESHOLD: i32 = 48;
+
+/// A corner is flagged when it has this many times the edge density of the image...
+const WATERMARK_RATIO: f64 = 2.5;
+
+/// ...and at least this share of its pixels are edges
+const MIN_WATERMARK_DENSITY: f64 = 0.06;
+
+/// Width of solid borders on each side of an image, in pixels
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct Borders {
+    pub top: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub right: u32,
+}
+
+impl Borders {
+    fn is_empty(&self) -> bool {
+        *self == Borders::default()
+    }
+}
+
+/// Corner of an image
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// An image with solid borders or a likely watermark
+#[derive(Debug, Serialize)]
+pub struct BorderReport {
+    pub path: String,
+    pub relative_path: String,
+    pub width: u32,
+    pub height: u32,
+    pub borders: Borders,
+    /// Dark bars on opposite sides, as left by letterboxed video frames
+    pub letterboxed: bool,
+    /// Corner with far more fine detail than the rest of the image, as text and logos
+    /// have
+    pub watermark: Option<Corner>,
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// Brightness of the pixel `index` along the line `depth` lines in from a side
+fn pixel(luma: &GrayImage, side: Side, depth: u32, index: u32) -> u8 {
+    let (width, height) = luma.dimensions();
+    let (x, y) = match side {
+        Side::Top => (index, depth),
+        Side::Bottom => (index, height - 1 - depth),
+        Side::Left => (depth, index),
+        Side::Right => (width - 1 - depth, index),
+    };
+    luma.get_pixel(x, y)[0]
+}
+
+/// Width of the solid border on a side, and its brightness
+fn border_depth(luma: &GrayImage, side: Side) -> (u32, f64) {
+    let (width, height) = luma.dimensions();
+    let (length, across) = match side {
+        Side::Top | Side::Bottom => (width, height),
+        Side::Left | Side::Right => (height, width),
+    };
+    let max_depth = (across as f64 * MAX_BORDER_SHARE) as u32;
+    let reference = (0..length)
+        .map(|i| f64::from(pixel(luma, side, 0, i)))
+        .sum::<f64>()
+        / length as f64;
+    let allowed = (length as f64 * BORDER_OUTLIERS) as usize;
+
+    let mut depth = 0;
+    while depth < max_depth {
+        let outliers = (0..length)
+            .filter(|&i| {
+                (f64::from(pixel(luma, side, depth, i)) - reference).abs()
+                    > f64::from(BORDER_TOLERANCE)
+            })
+            .count();
+        if outliers > allowed {
+            break;
+        }
+        depth += 1;
+    }
+
+    if depth < MIN_BORDER {
+        (0, reference)
+    } else {
+        (depth, reference)
+    }
+}
+
+/// Solid borders of an image, and whether they are letterboxing
+fn detect_borders(luma: &GrayImage) -> (Borders, bool) {
+    if luma.width() == 0 || luma.height() == 0 {
+        return (Borders::default(), false);
+    }
+    let (top, top_color) = border_depth(luma, Side::Top);
+    let (bottom, bottom_color) = border_depth(luma, Side::Bottom);
+    let (left, left_color) = border_depth(luma, Side::Left);
+    let (right, right_color) = border_depth(luma, Side::Right);
+
+    let dark_bars = |a: u32, a_color: f64, b: u32, b_color: f64| {
+        a > 0 && b > 0 && a_color.max(b_color) < LETTERBOX_BRIGHTNESS
+    };
+    let letterboxed = dark_bars(top, top_color, bottom, bottom_color)
+        || dark_bars(left, left_color, right, right_color);
+
+    let borders = Borders {
+        top,
+        bottom,
+        left,
+        right,
+    };
+    (borders, letterboxed)
+}
+
+/// Share of the pixels of a region that are edges
+fn edge_density(luma: &GrayImage, x: u32, y: u32, width: u32, height: u32) -> f64 {
+    let mut edges = 0u64;
+    let mut total = 0u64;
+    for py in y..(y + height).saturating_sub(1) {
+        for px in x..(x + width).saturating_sub(1) {
+            let here = i32::from(luma.get_pixel(px, py)[0]);
+            let right = i32::from(luma.get_pixel(px + 1, py)[0]);
+            let below = i32::from(luma.get_pixel(px, py + 1)[0]);
+            if (here - right).abs() + (here - below).abs() > EDGE_THRESHOLD {
+                edges += 1;
+            }
+            total += 1;
+        }
+    }
+    edges as f64 / total.max(1) as f64
+}
+
+/// The corner inside the borders most likely to hold a watermark, if any stands out
+///
+/// Watermarks are small, sharp text or logos, so a corner with far more edges than the
+/// image as a whole is flagged. Busy images rarely trip this, since their corners are
+/// as detailed as the rest.
+fn detect_watermark(luma: &GrayImage, borders: Borders) -> Option<Corner> {
+    let (width, height) = luma.dimensions();
+    let content_width = width.saturating_sub(borders.left + borders.right);
+    let content_height = height.saturating_sub(borders.top + borders.bottom);
+    let corner_width = (content_width as f64 * CORNER_WIDTH) as u32;
+    let corner_height = (content_height as f64 * CORNER_HEIGHT) as u32;
+    if corner_width < 8 || corner_height < 8 {
+        return None;
+    }
+
+    let overall = edge_density(
+        luma,
+        borders.left,
+        borders.top,
+        content_width,
+        content_height,
+    );
+    let right = borders.left + content_width - corner_width;
+    let bottom = borders.top + content_height - corner_height;
+    [
+        (Corner::TopLeft, borders.left, borders.top),
+        (Corner::TopRight, right, borders.top),
+        (Corner::BottomLeft, borders.left, bottom),
+        (Corner::BottomRight, right, bottom),
+    ]
+    .into_iter()
+    .map(|(corner, x, y)| {
+        let density = edge_density(luma, x, y, corner_width, corner_height);
+        (corner, density)
+    })
+    .filter(|&(_, density)| {
+        density >= MIN_WATERMARK_DENSITY && density >= overall * WATERMARK_RATIO
+    })
+    .max_by(|a, b| a.1.total_cmp(&b.1))
+    .map(|(corner, _)| corner)
+}
+
+/// Find the images of a project with solid borders, letterboxing or a likely corner
+/// watermark
+///
+/// Videos are skipped. Returns only the images with a finding.
+pub fn analyze_directory(job: &Job, directory: &Path) -> Result<Vec<BorderReport>, String> {
+    let images: Vec<_> = scan_media_files(directory)?
+        .into_iter()
+        .filter(|file| file.file_type == "image")
+        .collect();
+
+    let total = images.len() as u64;
+    let mut reports = Vec::new();
+    for (index, file) in images.into_iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some("Looking for borders".to_string()));
+
+        let image = match formats::open_image(Path::new(&file.path)) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!("Failed to analyze {}: {}", file.path, e);
+                continue;
+            }
+        };
+        let luma = image.to_luma8();
+        let (borders, letterboxed) = detect_borders(&luma);
+        let watermark = detect_watermark(&luma, borders);
+        if borders.is_empty() && watermark.is_none() {
+            continue;
+        }
+
+        let (width, height) = luma.dimensions();
+        reports.push(BorderReport {
+            path: file.path,
+            relative_path: file.relative_path,
+            width,
+            height,
+            borders,
+            letterboxed,
+            watermark,
+        });
+    }
+
+    job.progress(total, total, None);
+    Ok(reports)
+}
+
+/// An image without its solid borders, or `None` if it has none
+fn trim_image(image: &DynamicImage) -> Option<DynamicImage> {
+    let (borders, _) = detect_borders(&image.to_luma8());
+    if borders.is_empty() {
+        return None;
+    }
+
+    let (width, height) = image.dimensions();
+    let content_width = width.saturating_sub(borders.left + borders.right);
+    let content_height = height.saturating_sub(borders.top + borders.bottom);
+    if content_width == 0 || content_height == 0 {
+        return None;
+    }
+    Some(image.crop_imm(borders.left, borders.top, content_width, content_height))
+}
+
+/// Crop the solid borders and letterboxing off images, in place
+///
+/// Images without borders are skipped. The crops are recorded as one undoable
+/// operation.
+pub fn trim_borders(job: &Job, paths: &[PathBuf]) -> Result<CropSummary, String> {
+    crop::crop_in_place(job, paths, "Trim image borders".to_string(), trim_image)
+}
@@ -0,0 +1,300 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::path::Path;
+use tracing::warn;
+
+use super::{formats, metadata};
+use crate::file_system::commands::scan_media_files;
+use crate::jobs::manager::Job;
+
+/// Seconds between two shots of a burst when no window is given
+pub const DEFAULT_WINDOW_SECONDS: f64 = 2.0;
+
+/// Differing bits of the perceptual hashes of two near-identical images when no limit
+/// is given, out of 64
+pub const DEFAULT_MAX_DISTANCE: u32 = 6;
+
+/// Side of the image the perceptual hash is computed from
+const HASH_SOURCE: u32 = 32;
+
+/// Side of the low frequencies the hash keeps
+const HASH_SIZE: usize = 8;
+
+/// Images are scaled to fit this size before measuring sharpness, so images of
+/// different resolutions compare
+const SHARPNESS_SIZE: u32 = 512;
+
+/// Why files were grouped into a burst
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BurstReason {
+    /// Shot within the time window of each other
+    Time,
+    /// Near-identical perceptual hashes
+    Similar,
+    Both,
+}
+
+/// A file of a burst
+#[derive(Debug, Serialize)]
+pub struct BurstMember {
+    pub path: String,
+    pub relative_path: String,
+    /// When it was shot, from its EXIF data
+    pub captured: Option<String>,
+    /// Variance of the Laplacian of the image; higher is sharper
+    pub sharpness: f64,
+}
+
+/// Files shot in quick succession or near-identical, of which only the best is worth
+/// training on
+#[derive(Debug, Serialize)]
+pub struct Burst {
+    pub reason: BurstReason,
+    /// In order of capture, then of path
+    pub members: Vec<BurstMember>,
+    /// Path of the sharpest member, the one to keep
+    pub best: String,
+}
+
+/// What grouping needs of an image
+struct Shot {
+    path: String,
+    relative_path: String,
+    captured: Option<chrono::NaiveDateTime>,
+    hash: u64,
+    sharpness: f64,
+}
+
+/// Disjoint groups of indices, merged pairwise
+struct Groups {
+    parent: Vec<usize>,
+}
+
+impl Groups {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn root(&mut self, mut index: usize) -> usize {
+        while self.parent[index] != index {
+            self.parent[index] = self.parent[self.parent[index]];
+            index = self.parent[index];
+        }
+        index
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
+
+/// The 2D DCT-II of a square grayscale image, keeping only its top-left frequencies
+fn low_frequencies(pixels: &GrayImage) -> Vec<f64> {
+    let size = pixels.width() as usize;
+    let cosines: Vec<Vec<f64>> = (0..HASH_SIZE)
+        .map(|u| {
+            (0..size)
+                .map(|x| ((2 * x + 1) as f64 * u as f64 * PI / (2 * size) as f64).cos())
+                .collect()
+        })
+        .collect();
+
+    let mut frequencies = Vec::with_capacity(HASH_SIZE * HASH_SIZE);
+    for v in 0..HASH_SIZE {
+        for u in 0..HASH_SIZE {
+            let mut sum = 0.0;
+            for (y, row_cosine) in cosines[v].iter().enumerate() {
+                for (x, column_cosine) in cosines[u].iter().enumerate() {
+                    sum +=
+                        pixels.get_pixel(x as u32, y as u32)[0] as f64 * column_cosine * row_cosine;
+                }
+            }
+            frequencies.push(sum);
+        }
+    }
+    frequencies
+}
+
+/// Perceptual hash of an image: one bit per low frequency, set when above the median
+///
+/// Resizing, recompression and small shifts leave it almost unchanged, so the number
+/// of differing bits measures how alike two images look.
+fn perceptual_hash(image: &DynamicImage) -> u64 {
+    let small = image
+        .resize_exact(HASH_SOURCE, HASH_SOURCE, FilterType::Triangle)
+        .to_luma8();
+    let frequencies = low_frequencies(&small);
+
+    // The first frequency is the mean brightness, which says nothing about the content
+    let mut sorted: Vec<f64> = frequencies[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    frequencies
+        .iter()
+        .enumerate()
+        .filter(|(_, &frequency)| frequency > median)
+        .fold(0, |hash, (bit, _)| hash | (1 << bit))
+}
+
+/// Variance of the Laplacian of an image, a common measure of focus
+fn sharpness(image: &DynamicImage) -> f64 {
+    let luma = image
+        .resize(SHARPNESS_SIZE, SHARPNESS_SIZE, FilterType::Triangle)
+        .to_luma8();
+    let (width, height) = luma.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    let value = |x: u32, y: u32| luma.get_pixel(x, y)[0] as f64;
+    let mut sum = 0.0;
+    let mut squares = 0.0;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let laplacian = value(x - 1, y) + value(x + 1, y) + value(x, y - 1) + value(x, y + 1)
+                - 4.0 * value(x, y);
+            sum += laplacian;
+            squares += laplacian * laplacian;
+        }
+    }
+    let count = ((width - 2) * (height - 2)) as f64;
+    let mean = sum / count;
+    ((squares / count - mean * mean) * 100.0).round() / 100.0
+}
+
+/// Group the images of a project into bursts: shots taken within `window_seconds` (2
+/// by default) of each other by their EXIF capture time, or whose perceptual hashes
+/// differ by at most `max_distance` bits (6 by default)
+///
+/// Each burst names its sharpest member as the one to keep. Runs over every image,
+/// videos aside; images alone in their group aren't returned.
+pub fn find_bursts(
+    job: &Job,
+    directory: &Path,
+    window_seconds: Option<f64>,
+    max_distance: Option<u32>,
+) -> Result<Vec<Burst>, String> {
+    let window = window_seconds.unwrap_or(DEFAULT_WINDOW_SECONDS).max(0.0);
+    let max_distance = max_distance.unwrap_or(DEFAULT_MAX_DISTANCE).min(32);
+    let images: Vec<_> = scan_media_files(directory)?
+        .into_iter()
+        .filter(|file| file.file_type == "image")
+        .collect();
+
+    let total = images.len() as u64;
+    let mut shots = Vec::new();
+    for (index, file) in images.into_iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some("Comparing images".to_string()));
+
+        let path = Path::new(&file.path);
+        let image = match formats::open_image(path) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!("Failed to analyze {}: {}", file.path, e);
+                continue;
+            }
+        };
+        shots.push(Shot {
+            captured: metadata::read_capture_time(path),
+            hash: perceptual_hash(&image),
+            sharpness: sharpness(&image),
+            path: file.path,
+            relative_path: file.relative_path,
+        });
+    }
+    job.progress(total, total, None);
+
+    let mut by_time = Groups::new(shots.len());
+    let mut timed: Vec<usize> = (0..shots.len())
+        .filter(|&i| shots[i].captured.is_some())
+        .collect();
+    timed.sort_by_key(|&i| shots[i].captured);
+    for pair in timed.windows(2) {
+        let (Some(a), Some(b)) = (shots[pair[0]].captured, shots[pair[1]].captured) else {
+            continue;
+        };
+        if (b - a).num_milliseconds() as f64 <= window * 1000.0 {
+            by_time.join(pair[0], pair[1]);
+        }
+    }
+
+    let mut by_hash = Groups::new(shots.len());
+    for a in 0..shots.len() {
+        for b in a + 1..shots.len() {
+            if (shots[a].hash ^ shots[b].hash).count_ones() <= max_distance {
+                by_hash.join(a, b);
+            }
+        }
+    }
+
+    // Either kind of likeness puts files in the same burst
+    let mut groups = Groups::new(shots.len());
+    for index in 0..shots.len() {
+        let time_root = by_time.root(index);
+        let hash_root = by_hash.root(index);
+        groups.join(time_root, index);
+        groups.join(hash_root, index);
+    }
+    let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for index in 0..shots.len() {
+        members.entry(groups.root(index)).or_default().push(index);
+    }
+
+    let mut bursts = Vec::new();
+    for mut indices in members.into_values().filter(|indices| indices.len() > 1) {
+        indices.sort_by(|&a, &b| {
+            shots[a]
+                .captured
+                .cmp(&shots[b].captured)
+                .then_with(|| shots[a].path.cmp(&shots[b].path))
+        });
+        let timed = indices
+            .windows(2)
+            .any(|pair| by_time.root(pair[0]) == by_time.root(pair[1]));
+        let similar = indices
+            .windows(2)
+            .any(|pair| by_hash.root(pair[0]) == by_hash.root(pair[1]));
+        let reason = match (timed, similar) {
+            (true, false) => BurstReason::Time,
+            (false, true) => BurstReason::Similar,
+            _ => BurstReason::Both,
+        };
+        let best = indices
+            .iter()
+            .copied()
+            .max_by(|&a, &b| shots[a].sharpness.total_cmp(&shots[b].sharpness))
+            .map(|index| shots[index].path.clone())
+            .unwrap_or_default();
+
+        bursts.push(Burst {
+            reason,
+            members: indices
+                .iter()
+                .map(|&index| {
+                    let shot = &shots[index];
+                    BurstMember {
+                        path: shot.path.clone(),
+                        relative_path: shot.relative_path.clone(),
+                        captured: shot
+                            .captured
+                            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string()),
+                        sharpness: shot.sharpness,
+                    }
+                })
+                .collect(),
+            best,
+        });
+    }
+
+    Ok(bursts)
+}
@@ -0,0 +1,272 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbImage};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+use super::formats;
+use crate::file_system::commands::scan_media_files;
+use crate::jobs::manager::Job;
+
+/// Bins of the luminance histogram, each covering 8 levels
+const HISTOGRAM_BINS: usize = 32;
+
+/// Images are scaled to fit this size before counting colors, which is plenty for
+/// proportions and keeps large photos fast
+const SAMPLE_SIZE: u32 = 128;
+
+/// Colors in a palette
+const PALETTE_SIZE: usize = 5;
+
+/// Bits kept of each channel when grouping similar colors
+const QUANTIZE_BITS: u8 = 4;
+
+/// Mean luminance (0-255) below which an image counts as dark, and above which as
+/// bright
+const DARK_LUMINANCE: f64 = 60.0;
+const BRIGHT_LUMINANCE: f64 = 200.0;
+
+/// Share of a dataset past which dark or bright images, or one background, make a
+/// bias worth a warning
+const BIAS_SHARE: f64 = 0.5;
+
+/// A color of a palette
+#[derive(Debug, Clone, Serialize)]
+pub struct PaletteColor {
+    /// `#rrggbb`
+    pub hex: String,
+    /// Share of the pixels (or, for backgrounds, of the images), from 0 to 1
+    pub share: f64,
+}
+
+/// Brightness and colors of an image
+#[derive(Debug, Serialize)]
+pub struct ImageColors {
+    /// Share of the pixels in each of 32 luminance bins, darkest first
+    pub histogram: Vec<f64>,
+    /// Mean luminance from 0 to 255
+    pub mean_luminance: f64,
+    /// Dominant colors, most common first
+    pub palette: Vec<PaletteColor>,
+    /// Most common color along the edges, which is usually the background
+    pub background: Option<PaletteColor>,
+}
+
+/// Brightness and colors of the images of a project, to catch color bias before
+/// training
+#[derive(Debug, Serialize)]
+pub struct DatasetColors {
+    pub images: usize,
+    /// Mean of the images' luminance histograms
+    pub histogram: Vec<f64>,
+    pub mean_luminance: f64,
+    /// Share of the images that are mostly dark, and mostly bright
+    pub dark_share: f64,
+    pub bright_share: f64,
+    /// Dominant colors over all images, each weighted the same
+    pub palette: Vec<PaletteColor>,
+    /// The most common background, with the share of the images that have it
+    pub common_background: Option<PaletteColor>,
+    /// Biases found, in words
+    pub warnings: Vec<String>,
+}
+
+/// Counts of similar colors: quantized color to pixel count and channel sums
+#[derive(Default)]
+struct ColorCounts(HashMap<[u8; 3], (f64, [f64; 3])>);
+
+impl ColorCounts {
+    fn add(&mut self, pixel: [u8; 3], weight: f64) {
+        let key = pixel.map(|channel| channel >> (8 - QUANTIZE_BITS));
+        let entry = self.0.entry(key).or_default();
+        entry.0 += weight;
+        for (sum, channel) in entry.1.iter_mut().zip(pixel) {
+            *sum += channel as f64 * weight;
+        }
+    }
+
+    fn merge(&mut self, other: &ColorCounts, weight: f64) {
+        for (key, (count, sums)) in &other.0 {
+            let entry = self.0.entry(*key).or_default();
+            entry.0 += count * weight;
+            for (sum, other) in entry.1.iter_mut().zip(sums) {
+                *sum += other * weight;
+            }
+        }
+    }
+
+    /// The mean color of the most common group
+    fn dominant(&self) -> Option<[u8; 3]> {
+        self.0
+            .values()
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .filter(|(count, _)| *count > 0.0)
+            .map(|(count, sums)| sums.map(|sum| (sum / count).round() as u8))
+    }
+
+    /// The `limit` most common colors, each the mean of its group
+    fn top(&self, limit: usize) -> Vec<PaletteColor> {
+        let total: f64 = self.0.values().map(|(count, _)| count).sum();
+        let mut groups: Vec<_> = self.0.values().filter(|(count, _)| *count > 0.0).collect();
+        groups.sort_by(|a, b| b.0.total_cmp(&a.0));
+        groups
+            .into_iter()
+            .take(limit)
+            .map(|(count, sums)| PaletteColor {
+                hex: hex(sums.map(|sum| (sum / count).round() as u8)),
+                share: round(count / total),
+            })
+            .collect()
+    }
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn round(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+/// Rec. 601 luma of a pixel
+fn luminance([r, g, b]: [u8; 3]) -> f64 {
+    0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64
+}
+
+/// An image scaled down to sample its colors
+fn sample(image: &DynamicImage) -> RgbImage {
+    image
+        .resize(SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle)
+        .to_rgb8()
+}
+
+/// Histogram, color counts and edge color counts of an image
+fn measure(image: &DynamicImage) -> (Vec<f64>, f64, ColorCounts, ColorCounts) {
+    let sampled = sample(image);
+    let (width, height) = sampled.dimensions();
+    let mut histogram = vec![0.0; HISTOGRAM_BINS];
+    let mut colors = ColorCounts::default();
+    let mut edges = ColorCounts::default();
+    let mut luminance_sum = 0.0;
+
+    for (x, y, pixel) in sampled.enumerate_pixels() {
+        let luma = luminance(pixel.0);
+        luminance_sum += luma;
+        histogram[(luma as usize * HISTOGRAM_BINS / 256).min(HISTOGRAM_BINS - 1)] += 1.0;
+        colors.add(pixel.0, 1.0);
+        if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+            edges.add(pixel.0, 1.0);
+        }
+    }
+
+    let pixels = (width * height).max(1) as f64;
+    for bin in &mut histogram {
+        *bin /= pixels;
+    }
+    (histogram, luminance_sum / pixels, colors, edges)
+}
+
+/// Get the luminance histogram, dominant colors and background color of an image
+pub fn image_colors(path: &Path) -> Result<ImageColors, String> {
+    let image = formats::open_image(path).map_err(|e| format!("Failed to open image: {}", e))?;
+    let (histogram, mean_luminance, colors, edges) = measure(&image);
+
+    Ok(ImageColors {
+        histogram: histogram.into_iter().map(round).collect(),
+        mean_luminance: round(mean_luminance),
+        palette: colors.top(PALETTE_SIZE),
+        background: edges.top(1).pop(),
+    })
+}
+
+/// Aggregate the brightness and colors of the images of a project and warn about
+/// bias: most images dark or bright, or most sharing one background
+///
+/// Videos are skipped.
+pub fn dataset_colors(job: &Job, directory: &Path) -> Result<DatasetColors, String> {
+    let images: Vec<_> = scan_media_files(directory)?
+        .into_iter()
+        .filter(|file| file.file_type == "image")
+        .collect();
+
+    let total = images.len() as u64;
+    let mut histogram = vec![0.0; HISTOGRAM_BINS];
+    let mut luminance_sum = 0.0;
+    let mut dark = 0;
+    let mut bright = 0;
+    let mut palette = ColorCounts::default();
+    let mut backgrounds = ColorCounts::default();
+    let mut measured = 0;
+    for (index, file) in images.into_iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some("Measuring colors".to_string()));
+
+        let image = match formats::open_image(Path::new(&file.path)) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!("Failed to analyze {}: {}", file.path, e);
+                continue;
+            }
+        };
+        let (image_histogram, mean_luminance, colors, edges) = measure(&image);
+
+        for (sum, share) in histogram.iter_mut().zip(image_histogram) {
+            *sum += share;
+        }
+        luminance_sum += mean_luminance;
+        if mean_luminance < DARK_LUMINANCE {
+            dark += 1;
+        } else if mean_luminance > BRIGHT_LUMINANCE {
+            bright += 1;
+        }
+        // Weigh every image the same, whatever its size
+        let pixels: f64 = colors.0.values().map(|(count, _)| count).sum();
+        palette.merge(&colors, 1.0 / pixels.max(1.0));
+        if let Some(background) = edges.dominant() {
+            backgrounds.add(background, 1.0);
+        }
+        measured += 1;
+    }
+    job.progress(total, total, None);
+
+    let count = measured.max(1) as f64;
+    let dark_share = round(dark as f64 / count);
+    let bright_share = round(bright as f64 / count);
+    let common_background = backgrounds.top(1).pop().filter(|_| measured > 1);
+
+    let mut warnings = Vec::new();
+    if dark_share > BIAS_SHARE {
+        warnings.push(format!("{:.0}% of the images are dark", dark_share * 100.0));
+    }
+    if bright_share > BIAS_SHARE {
+        warnings.push(format!(
+            "{:.0}% of the images are bright",
+            bright_share * 100.0
+        ));
+    }
+    if let Some(background) = common_background
+        .as_ref()
+        .filter(|background| background.share > BIAS_SHARE)
+    {
+        warnings.push(format!(
+            "{:.0}% of the images share the background {}",
+            background.share * 100.0,
+            background.hex
+        ));
+    }
+
+    Ok(DatasetColors {
+        images: measured,
+        histogram: histogram
+            .into_iter()
+            .map(|sum| round(sum / count))
+            .collect(),
+        mean_luminance: round(luminance_sum / count),
+        dark_share,
+        bright_share,
+        palette: palette.top(PALETTE_SIZE),
+        common_background,
+        warnings,
+    })
+}
@@ -1,20 +1,57 @@
 use base64::{engine::general_purpose, Engine as _};
 use image::{self, imageops::FilterType, GenericImageView, ImageOutputFormat};
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io::{Cursor, Read};
+use std::io::{self, Cursor, Read};
 use std::path::Path;
 use std::path::PathBuf;
-use std::process::Command;
-use std::sync::Mutex;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
 use tempfile::tempdir;
+use tokio::sync::watch;
+use tracing::{debug, error, info, instrument, warn};
+
+use super::animation;
+use super::borders::{self, BorderReport};
+use super::bursts::{self, Burst};
+use super::colors::{self, DatasetColors, ImageColors};
+use super::convert::{self, ImageConversion, TargetFormat};
+use super::crop::{self, CropPreset, CropSummary, Gravity};
+use super::downscale::{self, DownscaleSummary};
+use super::ffmpeg::{self, FfmpegCapabilities};
+use super::formats;
+use super::jpeg_quality::{self, JpegQualityReport};
+use super::keyframes;
+use super::metadata::{self, EmbedSummary};
+use super::pdf::{self, PdfConversion, PdfPage};
+use super::raw::{self, RawConversion};
+use super::tiles::{self, TileSummary};
+use super::upscale::{self, UpscaleModel, UpscaleSummary};
+use crate::captions::format::CaptionStore;
+use crate::file_system::commands::{
+    scan_media_files, validate_media_file, validate_project_directory,
+};
+use crate::file_system::leftovers::{BACKUP_SUFFIX, TEMP_SUFFIX};
+use crate::file_system::{in_use, paths};
+use crate::jobs::manager::{self as jobs, Job, JobKind, JobStatus};
+use crate::project::settings;
+use crate::undo::journal::{Operation, OperationKind};
+
+/// An encoded JPEG thumbnail, shared by the cache and its readers
+type Thumbnail = Arc<[u8]>;
+
+/// Identifies a thumbnail: the path, the size, and for videos the position of the frame
+/// in milliseconds (`None` for the first frame)
+type ThumbnailKey = (String, u32, Option<u64>);
 
 // Define a simple cache for thumbnails
 struct ThumbnailCache {
-    // Map of path and size to base64 thumbnail
-    cache: HashMap<(String, u32), (String, u64)>, // (path, size) -> (thumbnail, timestamp)
+    // Map of path, size and position to JPEG thumbnail
+    cache: HashMap<ThumbnailKey, (Thumbnail, u64)>, // key -> (thumbnail, timestamp)
     max_entries: usize,
 }
 
@@ -26,10 +63,10 @@ impl ThumbnailCache {
         }
     }
 
-    fn get(&self, path: &str, size: u32) -> Option<String> {
-        let key = (path.to_string(), size);
+    fn get(&self, key: &ThumbnailKey) -> Option<Thumbnail> {
+        let path = &key.0;
         // Get the entry and check if it's still valid (file hasn't been modified)
-        if let Some((thumbnail, cached_time)) = self.cache.get(&key) {
+        if let Some((thumbnail, cached_time)) = self.cache.get(key) {
             // Check if the file has been modified since caching
             if let Ok(metadata) = fs::metadata(path) {
                 if let Ok(modified) = metadata.modified() {
@@ -48,15 +85,13 @@ impl ThumbnailCache {
         }
     }
 
-    fn set(&mut self, path: &str, size: u32, thumbnail: String) {
+    fn set(&mut self, key: ThumbnailKey, thumbnail: Thumbnail) {
         // Get current timestamp
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
 
-        let key = (path.to_string(), size);
-
         // If cache is at capacity, remove oldest entry
         if self.cache.len() >= self.max_entries {
             // Simple eviction: remove a random entry
@@ -74,24 +109,142 @@ static THUMBNAIL_CACHE: Lazy<Mutex<ThumbnailCache>> = Lazy::new(|| {
     Mutex::new(ThumbnailCache::new(500)) // Cache up to 500 thumbnails
 });
 
+type ThumbnailResult = Result<Thumbnail, String>;
+
+/// Receives the result of a thumbnail generation once it finishes
+type ThumbnailWaiter = watch::Receiver<Option<ThumbnailResult>>;
+
+/// Thumbnails being generated, so concurrent requests for the same thumbnail wait for
+/// one generation instead of each decoding the file
+static THUMBNAILS_IN_FLIGHT: Lazy<Mutex<HashMap<ThumbnailKey, ThumbnailWaiter>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Marks a thumbnail as in flight until dropped, even if its request is abandoned, so
+/// later requests don't wait for a generation that never finishes
+struct InFlightThumbnail {
+    key: ThumbnailKey,
+    sender: watch::Sender<Option<ThumbnailResult>>,
+}
+
+impl Drop for InFlightThumbnail {
+    fn drop(&mut self) {
+        let mut in_flight = THUMBNAILS_IN_FLIGHT
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        in_flight.remove(&self.key);
+    }
+}
+
+/// Either generate a thumbnail, or wait for the request already generating it
+enum ThumbnailRequest {
+    Generate(InFlightThumbnail),
+    Wait(ThumbnailWaiter),
+}
+
+/// Mark a thumbnail as in flight, unless another request already generates it
+fn request_thumbnail(key: &ThumbnailKey) -> ThumbnailRequest {
+    let mut in_flight = THUMBNAILS_IN_FLIGHT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    if let Some(receiver) = in_flight.get(key) {
+        return ThumbnailRequest::Wait(receiver.clone());
+    }
+    let (sender, receiver) = watch::channel(None);
+    in_flight.insert(key.clone(), receiver);
+    ThumbnailRequest::Generate(InFlightThumbnail {
+        key: key.clone(),
+        sender,
+    })
+}
+
 /// Generate a thumbnail for an image or video file and return as base64
+///
+/// For videos, `time_sec` picks the frame instead of the first one; thumbnails at
+/// different positions are cached separately. The grid loads thumbnails through the
+/// [`super::protocol`] instead, which skips the base64 round trip.
 #[tauri::command]
-pub async fn get_media_thumbnail(path: String, max_size: u32) -> Result<String, String> {
+pub async fn get_media_thumbnail(
+    path: String,
+    max_size: u32,
+    time_sec: Option<f64>,
+) -> Result<String, String> {
+    let thumbnail = thumbnail_bytes_at(&path, max_size, time_sec.map(position_ms)).await?;
+
+    // Return as JPEG data URL
+    Ok(format!(
+        "data:image/jpeg;base64,{}",
+        general_purpose::STANDARD.encode(&thumbnail)
+    ))
+}
+
+/// Position of a video frame in whole milliseconds, as thumbnails are keyed by
+pub fn position_ms(time_sec: f64) -> u64 {
+    (time_sec.max(0.0) * 1000.0).round() as u64
+}
+
+/// The JPEG thumbnail of an image or video file, from the cache when it is up to date
+pub async fn thumbnail_bytes(path: &str, max_size: u32) -> ThumbnailResult {
+    thumbnail_bytes_at(path, max_size, None).await
+}
+
+/// The JPEG thumbnail of an image, or of the frame of a video at `time_ms` (the first
+/// frame if `None`), from the cache when it is up to date
+pub async fn thumbnail_bytes_at(
+    path: &str,
+    max_size: u32,
+    time_ms: Option<u64>,
+) -> ThumbnailResult {
     // Strip any timestamp query parameter from the path
-    let clean_path = if path.contains('?') {
-        path.split('?').next().unwrap_or(&path).to_string()
-    } else {
-        path.clone()
-    };
+    let clean_path = path.split('?').next().unwrap_or(path).to_string();
+    let key = (clean_path.clone(), max_size, time_ms);
 
     // Check cache first
     if let Ok(cache) = THUMBNAIL_CACHE.lock() {
-        if let Some(cached) = cache.get(&clean_path, max_size) {
+        if let Some(cached) = cache.get(&key) {
             return Ok(cached);
         }
     }
 
-    let path_obj = Path::new(&clean_path);
+    // Share the result of a generation that is already running
+    let in_flight = match request_thumbnail(&key) {
+        ThumbnailRequest::Generate(in_flight) => in_flight,
+        ThumbnailRequest::Wait(mut receiver) => {
+            let shared = receiver
+                .wait_for(Option::is_some)
+                .await
+                .ok()
+                .and_then(|result| result.clone());
+            return match shared {
+                Some(result) => result,
+                // The other request was abandoned before it finished
+                None => Box::pin(thumbnail_bytes_at(path, max_size, time_ms)).await,
+            };
+        }
+    };
+
+    let result: ThumbnailResult = generate_thumbnail(&clean_path, max_size, time_ms)
+        .await
+        .map(Arc::from);
+
+    // If successful, cache the result, before other requests stop waiting for it
+    if let Ok(ref thumbnail) = &result {
+        if let Ok(mut cache) = THUMBNAIL_CACHE.lock() {
+            cache.set(key, thumbnail.clone());
+        }
+    }
+    in_flight.sender.send_replace(Some(result.clone()));
+
+    result
+}
+
+/// Generate a JPEG thumbnail for a media file, by its type
+async fn generate_thumbnail(
+    clean_path: &str,
+    max_size: u32,
+    time_ms: Option<u64>,
+) -> Result<Vec<u8>, String> {
+    let path_obj = &paths::long_path(Path::new(clean_path));
 
     // Check if the file exists
     if !path_obj.exists() {
@@ -106,44 +259,50 @@ pub async fn get_media_thumbnail(path: String, max_size: u32) -> Result<String,
 
     // No debug logging
 
-    // Process based on file type (lowercase extensions only)
-    let result = if ["jpg", "jpeg", "png", "gif", "webp"].contains(&ext_str.as_str()) {
-        // Handle image files
-        generate_image_thumbnail(path_obj, max_size)
-    } else if ["mp4", "webm", "mov", "avi"].contains(&ext_str.as_str()) {
-        // Handle video files
-        generate_video_thumbnail(path_obj, max_size).await
-    } else {
-        // If not recognized, try to detect by examining the file
-        if let Ok(file) = std::fs::File::open(path_obj) {
-            let mut buffer = [0; 8]; // Read first 8 bytes for magic numbers
-            if file.take(8).read(&mut buffer).is_ok() {
-                // Check PNG signature (89 50 4E 47 0D 0A 1A 0A)
-                if buffer == [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A] {
-                    return generate_image_thumbnail(path_obj, max_size);
-                }
-                // Check JPEG signature (FF D8)
-                if buffer[0] == 0xFF && buffer[1] == 0xD8 {
-                    return generate_image_thumbnail(path_obj, max_size);
-                }
-            }
-        }
-
-        Err(format!("Unsupported file type: {}", ext_str))
-    };
+    // Process based on file type (unknown extensions are identified by their contents)
+    match formats::media_type(path_obj) {
+        Some("image") => generate_image_thumbnail(path_obj, max_size),
+        Some("video") => generate_video_thumbnail(path_obj, max_size, time_ms).await,
+        _ => Err(format!("Unsupported file type: {}", ext_str)),
+    }
+}
 
-    // If successful, cache the result
-    if let Ok(ref thumbnail) = &result {
-        if let Ok(mut cache) = THUMBNAIL_CACHE.lock() {
-            cache.set(&clean_path, max_size, thumbnail.clone());
+/// Generate and cache thumbnails for every media file in a directory ahead of time
+///
+/// Runs as a thumbnails job and returns how many thumbnails were generated; files
+/// whose thumbnail fails are logged and skipped.
+#[tauri::command]
+pub async fn pregenerate_thumbnails(
+    app: AppHandle,
+    directory: String,
+    max_size: u32,
+) -> Result<usize, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    let files = scan_media_files(&dir_path)?;
+    let description = format!("Generate thumbnails for {}", directory);
+
+    jobs::run_async(&app, JobKind::Thumbnails, description, |job| async move {
+        let total = files.len() as u64;
+        let mut generated = 0;
+
+        for (index, file) in files.into_iter().enumerate() {
+            job.check_cancelled()?;
+            job.progress(index as u64, total, Some(file.name));
+
+            match thumbnail_bytes(&file.path, max_size).await {
+                Ok(_) => generated += 1,
+                Err(e) => warn!("Failed to generate thumbnail for {}: {}", file.path, e),
+            }
         }
-    }
 
-    result
+        job.progress(total, total, None);
+        Ok(generated)
+    })
+    .await
 }
 
-/// Generate a thumbnail for an image file
-fn generate_image_thumbnail(path: &Path, max_size: u32) -> Result<String, String> {
+/// Generate a JPEG thumbnail for an image file
+fn generate_image_thumbnail(path: &Path, max_size: u32) -> Result<Vec<u8>, String> {
     // Get file size to determine processing approach
     let file_size = match fs::metadata(path) {
         Ok(metadata) => metadata.len(),
@@ -154,7 +313,7 @@ fn generate_image_thumbnail(path: &Path, max_size: u32) -> Result<String, String
     let large_threshold = 10 * 1024 * 1024; // 10MB threshold
 
     // Try to open the image
-    let img = match image::open(path) {
+    let img = match formats::open_image(path) {
         Ok(img) => img,
         Err(e) => {
             // Special handling for large images that may cause memory issues
@@ -192,7 +351,6 @@ fn generate_image_thumbnail(path: &Path, max_size: u32) -> Result<String, String
         img.thumbnail(max_size, max_size)
     };
 
-    // Convert to base64 with appropriate format
     let mut buffer = Vec::new();
     let mut cursor = Cursor::new(&mut buffer);
 
@@ -201,15 +359,35 @@ fn generate_image_thumbnail(path: &Path, max_size: u32) -> Result<String, String
         return Err(format!("Failed to encode JPEG thumbnail: {}", e));
     }
 
-    // Encode as base64
-    let base64_string = general_purpose::STANDARD.encode(&buffer);
+    Ok(buffer)
+}
 
-    // Return as JPEG data URL
-    Ok(format!("data:image/jpeg;base64,{}", base64_string))
+/// Get what the installed FFmpeg can do: its version, encoders, hardware acceleration
+/// methods and whether ffprobe is available
+///
+/// The result is cached after the first probe; `refresh` probes again, e.g. after
+/// installing FFmpeg.
+#[tauri::command]
+pub async fn get_ffmpeg_capabilities(refresh: Option<bool>) -> Result<FfmpegCapabilities, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let capabilities = if refresh.unwrap_or(false) {
+            ffmpeg::refresh()
+        } else {
+            ffmpeg::capabilities()
+        };
+        (*capabilities).clone()
+    })
+    .await
+    .map_err(|e| e.to_string())
 }
 
-/// Generate a thumbnail for a video file by extracting the first frame
-async fn generate_video_thumbnail(path: &Path, max_size: u32) -> Result<String, String> {
+/// Generate a JPEG thumbnail for a video file by extracting the frame at `time_ms`, or
+/// the first frame
+async fn generate_video_thumbnail(
+    path: &Path,
+    max_size: u32,
+    time_ms: Option<u64>,
+) -> Result<Vec<u8>, String> {
     // Create a temporary directory to store the extracted frame
     let temp_dir = match tempdir() {
         Ok(dir) => dir,
@@ -218,25 +396,26 @@ async fn generate_video_thumbnail(path: &Path, max_size: u32) -> Result<String,
 
     // Create a path for the extracted frame
     let frame_path = temp_dir.path().join("frame.jpg");
-    let frame_path_str = frame_path.to_string_lossy().to_string();
 
-    // Use ffmpeg to extract the first frame
-    // Check if ffmpeg is available
-    let ffmpeg_result = Command::new("ffmpeg").arg("-version").output();
+    // Use ffmpeg to extract the frame
+    ffmpeg::require("video thumbnails")?;
 
-    if ffmpeg_result.is_err() {
-        return Err("FFmpeg is not installed or not in PATH. Please install FFmpeg to enable video thumbnails.".to_string());
+    // Extract the frame using ffmpeg, seeking before the input so only the frames
+    // around the position are decoded
+    let mut command = Command::new("ffmpeg");
+    if let Some(time_ms) = time_ms {
+        command
+            .arg("-ss")
+            .arg(format!("{:.3}", time_ms as f64 / 1000.0));
     }
-
-    // Extract the first frame using ffmpeg
-    let output = Command::new("ffmpeg")
+    let output = command
         .arg("-i")
-        .arg(path.to_string_lossy().to_string())
+        .arg(path)
         .arg("-vframes")
         .arg("1")
         .arg("-q:v")
         .arg("2")
-        .arg(&frame_path_str)
+        .arg(&frame_path)
         .output();
 
     match output {
@@ -263,30 +442,81 @@ async fn generate_video_thumbnail(path: &Path, max_size: u32) -> Result<String,
     result
 }
 
+/// Wait for an FFmpeg process to exit, killing it if the job is cancelled
+fn wait_for_exit(child: &mut Child, job: &Job) -> io::Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if job.is_cancelled() {
+            let _ = child.kill();
+            return child.wait();
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Collect the output of an FFmpeg process like `Command::output`, killing it if the
+/// job is cancelled
+pub(crate) fn wait_for_ffmpeg(mut child: Child, job: &Job) -> io::Result<Output> {
+    // Drain stderr while waiting so FFmpeg never blocks on a full pipe
+    let stderr = child.stderr.take().map(|mut pipe| {
+        thread::spawn(move || {
+            let mut buffer = Vec::new();
+            let _ = pipe.read_to_end(&mut buffer);
+            buffer
+        })
+    });
+
+    let status = wait_for_exit(&mut child, job)?;
+    let stderr = stderr
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+
+    Ok(Output {
+        status,
+        stdout: Vec::new(),
+        stderr,
+    })
+}
+
 /// Generate a file name with a suffix for modified files
-fn generate_modified_filename(path: &Path, suffix: &str) -> PathBuf {
-    let stem = path
+pub(crate) fn generate_modified_filename(path: &Path, suffix: &str) -> PathBuf {
+    // Build the name from OS strings so names that aren't valid Unicode survive
+    let mut new_name = path
         .file_stem()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| "file".to_string());
-
-    let extension = path
-        .extension()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| "".to_string());
+        .map(|s| s.to_os_string())
+        .unwrap_or_else(|| "file".into());
+    new_name.push(suffix);
 
-    let new_name = if extension.is_empty() {
-        format!("{}{}", stem, suffix)
-    } else {
-        format!("{}{}.{}", stem, suffix, extension)
-    };
+    if let Some(extension) = path.extension() {
+        new_name.push(".");
+        new_name.push(extension);
+    }
 
     path.with_file_name(new_name)
 }
 
+/// Move the pre-edit backup of a media file into the project's undo journal
+fn journal_media_edit(path: &Path, backup_path: &Path, action: &str) -> Result<(), String> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut operation =
+        Operation::begin(path, OperationKind::MediaEdit, format!("{} {}", action, name))?;
+    operation.adopt_backup(path, backup_path)?;
+    operation.commit()?;
+
+    Ok(())
+}
+
 /// Save a cropped image from the provided data URL, overwriting the original file
 #[tauri::command]
 pub async fn save_cropped_image(path: String, data_url: String) -> Result<String, String> {
+    settings::ensure_writable(Path::new(&path))?;
+
     // Parse the data URL
     if !data_url.starts_with("data:image/") {
         return Err("Invalid data URL format".to_string());
@@ -305,32 +535,521 @@ pub async fn save_cropped_image(path: String, data_url: String) -> Result<String
     };
 
     // Use the original path
-    let path_obj = Path::new(&path);
+    let path_obj = &paths::long_path(Path::new(&path));
 
     // Create a backup of the original file (just in case)
-    let backup_path = generate_modified_filename(path_obj, "_backup");
-    if let Err(e) = fs::copy(path_obj, &backup_path) {
-        return Err(format!("Failed to create backup of original image: {}", e));
+    let backup_path = generate_modified_filename(path_obj, BACKUP_SUFFIX);
+    if let Err(e) = in_use::retry(|| fs::copy(path_obj, &backup_path)) {
+        return Err(in_use::error_message(e, "crop", Path::new(&path), |e| {
+            format!("Failed to create backup of original image: {}", e)
+        }));
     }
 
+    // Remove the file first so a hard link or symlink to an imported original is
+    // replaced rather than written through
+    let _ = in_use::retry(|| fs::remove_file(path_obj));
+
     // Save the image data, overwriting the original file
-    if let Err(e) = fs::write(path_obj, image_data) {
+    if let Err(e) = in_use::retry(|| fs::write(path_obj, &image_data)) {
         // If writing fails, try to restore from backup
         let _ = fs::copy(&backup_path, path_obj); // Best effort restore
         let _ = fs::remove_file(&backup_path); // Clean up backup
-        return Err(format!("Failed to write cropped image: {}", e));
+        return Err(in_use::error_message(e, "crop", Path::new(&path), |e| {
+            format!("Failed to write cropped image: {}", e)
+        }));
     }
 
-    // Clean up the backup file
-    let _ = fs::remove_file(&backup_path);
+    // Keep the original in the undo journal instead of discarding the backup
+    if let Err(e) = journal_media_edit(path_obj, &backup_path, "Crop") {
+        warn!("Failed to record crop in undo journal: {}", e);
+        let _ = fs::remove_file(&backup_path);
+    }
 
     // Return the original path (for consistency with the existing interface)
     Ok(path)
 }
 
+/// Convert the camera RAW files of a project to JPEG using their embedded previews
+///
+/// Meant as an import step so RAW datasets can be captioned and exported like any
+/// other images. Files that already have a JPEG next to them are skipped.
+#[tauri::command]
+pub async fn convert_raw_images(
+    app: AppHandle,
+    directory: String,
+    remove_originals: bool,
+) -> Result<RawConversion, String> {
+    let dir_path = validate_project_directory(&app, &directory)?;
+    raw::convert_directory(&dir_path, remove_originals)
+}
+
+/// Rasterize the pages of the PDF documents in a project to PNG images
+///
+/// Meant as an import step for artwork delivered as PDFs; needs Poppler. `dpi`
+/// defaults to 150. Each page image is linked to its document and page number, see
+/// `get_pdf_pages`. Runs as an import job and is recorded as one undoable operation.
+#[tauri::command]
+pub async fn convert_pdf_documents(
+    app: AppHandle,
+    directory: String,
+    dpi: Option<u32>,
+    remove_originals: Option<bool>,
+) -> Result<PdfConversion, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let description = format!("Convert PDFs in {}", directory);
+
+    jobs::run(&app, JobKind::Import, description, move |job| {
+        pdf::convert_directory(job, &project, dpi, remove_originals.unwrap_or(false))
+    })
+    .await
+}
+
+/// Get the document and page number each page image of a project was rasterized from,
+/// by relative path
+#[tauri::command]
+pub async fn get_pdf_pages(
+    app: AppHandle,
+    directory: String,
+) -> Result<BTreeMap<String, PdfPage>, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    pdf::load_pages(&project)
+}
+
+/// Convert images to JPEG, PNG or WebP, so a mixed-format dump becomes one format
+///
+/// HEIC and AVIF sources are decoded through FFmpeg. `quality` (1-100, default 90)
+/// applies to JPEG; WebP is written lossless. Captions follow the converted files,
+/// and with `remove_originals` the originals are deleted. Runs as an import job and
+/// is recorded as one undoable operation.
+#[tauri::command]
+pub async fn convert_images(
+    app: AppHandle,
+    paths: Vec<String>,
+    target_format: TargetFormat,
+    quality: Option<u8>,
+    remove_originals: Option<bool>,
+) -> Result<ImageConversion, String> {
+    let paths = paths
+        .iter()
+        .map(|path| validate_media_file(&app, path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let description = format!("Convert {} images", paths.len());
+
+    jobs::run(&app, JobKind::Import, description, move |job| {
+        convert::convert_images(
+            job,
+            &paths,
+            target_format,
+            quality,
+            remove_originals.unwrap_or(false),
+        )
+    })
+    .await
+}
+
+/// Split ultra-wide and ultra-tall images, such as panoramas and manga pages, into
+/// overlapping square tiles next to them
+///
+/// Images with a long side at least `min_aspect` (default 2) times their short side
+/// are tiled, each tile overlapping the next by `overlap` (default 0.25). Tiles get a
+/// copy of the image's caption, and with `remove_originals` tiled images are deleted.
+/// Runs as an import job and is recorded as one undoable operation.
+#[tauri::command]
+pub async fn tile_images(
+    app: AppHandle,
+    paths: Vec<String>,
+    min_aspect: Option<f64>,
+    overlap: Option<f64>,
+    remove_originals: Option<bool>,
+) -> Result<TileSummary, String> {
+    let paths = paths
+        .iter()
+        .map(|path| validate_media_file(&app, path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let description = format!("Tile {} images", paths.len());
+
+    jobs::run(&app, JobKind::Import, description, move |job| {
+        tiles::tile_images(
+            job,
+            &paths,
+            min_aspect,
+            overlap,
+            remove_originals.unwrap_or(false),
+        )
+    })
+    .await
+}
+
+/// List the built-in crop presets: common aspect ratios and the SDXL buckets
+#[tauri::command]
+pub fn get_crop_presets() -> Vec<CropPreset> {
+    crop::presets()
+}
+
+/// Crop images in place to an aspect ratio, keeping the side given by `gravity`
+/// (default: the center)
+///
+/// `aspect` is a preset name, `sdxl` for the nearest SDXL bucket of each image, or a
+/// ratio like `"5:4"`. Runs as a crop job; videos and images that can't be written
+/// back are skipped. The crops are recorded as one undoable operation.
+#[tauri::command]
+pub async fn crop_to_aspect(
+    app: AppHandle,
+    paths: Vec<String>,
+    aspect: String,
+    gravity: Option<Gravity>,
+) -> Result<CropSummary, String> {
+    let paths = paths
+        .iter()
+        .map(|path| validate_media_file(&app, path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let description = format!("Crop {} images to {}", paths.len(), aspect);
+
+    jobs::run(&app, JobKind::Crop, description, move |job| {
+        crop::crop_to_aspect(job, &paths, &aspect, gravity.unwrap_or_default())
+    })
+    .await
+}
+
+/// Find the images of a project with solid borders, letterboxing or a likely corner
+/// watermark
+///
+/// Runs as an indexing job, since it reads every image. Returns only the images with a
+/// finding.
+#[tauri::command]
+pub async fn detect_image_borders(
+    app: AppHandle,
+    directory: String,
+) -> Result<Vec<BorderReport>, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let description = format!("Find borders in {}", directory);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        borders::analyze_directory(job, &project)
+    })
+    .await
+}
+
+/// Get the luminance histogram, dominant colors and background color of an image
+#[tauri::command]
+pub async fn get_image_colors(app: AppHandle, path: String) -> Result<ImageColors, String> {
+    let path = validate_media_file(&app, &path)?;
+    tauri::async_runtime::spawn_blocking(move || colors::image_colors(&path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Aggregate the brightness and colors of a project's images, with warnings about
+/// color bias such as most images being dark or sharing one background
+///
+/// Runs as an indexing job, since it reads every image.
+#[tauri::command]
+pub async fn get_dataset_colors(
+    app: AppHandle,
+    directory: String,
+) -> Result<DatasetColors, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let description = format!("Measure colors in {}", directory);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        colors::dataset_colors(job, &project)
+    })
+    .await
+}
+
+/// Group a project's burst shots and near-duplicates so only the best frame of each
+/// burst is kept
+///
+/// Images shot within `window_seconds` (default 2) of each other by their EXIF time,
+/// or whose perceptual hashes differ by at most `max_distance` bits (default 6), are
+/// grouped, and the sharpest of each group is named as the one to keep. Runs as an
+/// indexing job. Returns only groups of more than one image.
+#[tauri::command]
+pub async fn find_bursts(
+    app: AppHandle,
+    directory: String,
+    window_seconds: Option<f64>,
+    max_distance: Option<u32>,
+) -> Result<Vec<Burst>, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let description = format!("Find bursts in {}", directory);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        bursts::find_bursts(job, &project, window_seconds, max_distance)
+    })
+    .await
+}
+
+/// Find the JPEGs of a project compressed hard enough to show artifacts, to exclude
+/// them from training
+///
+/// Quality is estimated from the quantization tables and blocking measured on the 8x8
+/// grid; files below `min_quality` (default 75) or with visible blocking are flagged.
+/// Runs as an indexing job. Returns only the flagged files, worst first.
+#[tauri::command]
+pub async fn audit_jpeg_quality(
+    app: AppHandle,
+    directory: String,
+    min_quality: Option<u8>,
+) -> Result<Vec<JpegQualityReport>, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let description = format!("Check JPEG quality in {}", directory);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        jpeg_quality::analyze_directory(job, &project, min_quality)
+    })
+    .await
+}
+
+/// Crop the solid borders and letterboxing off images, in place
+///
+/// Runs as a crop job; images without borders are skipped. The crops are recorded as
+/// one undoable operation.
+#[tauri::command]
+pub async fn trim_image_borders(app: AppHandle, paths: Vec<String>) -> Result<CropSummary, String> {
+    let paths = paths
+        .iter()
+        .map(|path| validate_media_file(&app, path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let description = format!("Trim borders of {} images", paths.len());
+
+    jobs::run(&app, JobKind::Crop, description, move |job| {
+        borders::trim_borders(job, &paths)
+    })
+    .await
+}
+
+/// Upscale images in place with Real-ESRGAN, by `scale` (2, 3 or 4, default 4)
+///
+/// Needs the `realesrgan-ncnn-vulkan` executable and its models, bundled next to the
+/// app or in PATH. Runs as a crop job, reporting progress per image; videos and
+/// formats that can't be written back are skipped. The upscales are recorded as one
+/// undoable operation.
+#[tauri::command]
+pub async fn upscale_images(
+    app: AppHandle,
+    paths: Vec<String>,
+    scale: Option<u32>,
+    model: Option<UpscaleModel>,
+) -> Result<UpscaleSummary, String> {
+    let paths = paths
+        .iter()
+        .map(|path| validate_media_file(&app, path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let description = format!("Upscale {} images", paths.len());
+
+    jobs::run(&app, JobKind::Crop, description, move |job| {
+        upscale::upscale_images(job, &paths, scale.unwrap_or(4), model.unwrap_or_default())
+    })
+    .await
+}
+
+/// Convert an animated GIF or WebP to an H.264 MP4 next to it, keeping each frame's
+/// delay
+///
+/// Runs as an import job; cancelling it stops FFmpeg. The caption moves to the video,
+/// and with `remove_original` the animation is deleted. The conversion is recorded as
+/// one undoable operation. Returns the path of the video.
+#[tauri::command]
+pub async fn convert_animation_to_mp4(
+    app: AppHandle,
+    path: String,
+    remove_original: Option<bool>,
+) -> Result<String, String> {
+    let source = validate_media_file(&app, &path)?;
+    let description = format!("Convert {} to MP4", path);
+
+    jobs::run(&app, JobKind::Import, description, move |job| {
+        animation_to_mp4_job(job, &source, remove_original.unwrap_or(false))
+    })
+    .await
+}
+
+fn animation_to_mp4_job(job: &Job, source: &Path, remove_original: bool) -> Result<String, String> {
+    let capabilities = ffmpeg::require("MP4 conversion")?;
+    if !capabilities.has_encoder("libx264") {
+        return Err(
+            "FFmpeg was built without the libx264 encoder, which MP4 conversion needs.".to_string(),
+        );
+    }
+
+    let video = source.with_extension("mp4");
+    if video.exists() {
+        return Err(format!("{} already exists", paths::display_path(&video)));
+    }
+
+    // FFmpeg can't decode animated WebPs, so every animation goes in as its frames
+    let frames_dir =
+        tempdir().map_err(|e| format!("Failed to create temporary directory: {}", e))?;
+    let list = animation::write_frames(job, source, frames_dir.path())?;
+
+    let name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut operation = Operation::begin(
+        source,
+        OperationKind::MediaEdit,
+        format!("Convert {} to MP4", name),
+    )?;
+    operation.snapshot(&video)?;
+
+    // H.264 players expect yuv420p, which needs even dimensions
+    let child = Command::new("ffmpeg")
+        .arg("-f")
+        .arg("concat")
+        .arg("-i")
+        .arg(&list)
+        .arg("-vsync")
+        .arg("vfr")
+        .arg("-vf")
+        .arg("scale=trunc(iw/2)*2:trunc(ih/2)*2")
+        .arg("-pix_fmt")
+        .arg("yuv420p")
+        .arg("-c:v")
+        .arg("libx264")
+        .arg("-crf")
+        .arg("18")
+        .arg("-movflags")
+        .arg("+faststart")
+        .arg(&video)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+    let output = child.and_then(|child| wait_for_ffmpeg(child, job));
+
+    if job.is_cancelled() {
+        let _ = fs::remove_file(&video);
+        return Err(jobs::CANCELLED.to_string());
+    }
+
+    match output {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            let _ = fs::remove_file(&video);
+            let error = String::from_utf8_lossy(&output.stderr);
+            error!("FFmpeg error: {}", error);
+            return Err(format!("FFmpeg failed to convert the animation: {}", error));
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&video);
+            return Err(format!("Failed to run ffmpeg: {}", e));
+        }
+    }
+
+    let mut store = CaptionStore::for_media(source);
+    convert::move_caption(&mut store, &mut operation, source, &video, remove_original)?;
+    operation.commit()?;
+
+    Ok(paths::display_path(&video))
+}
+
+/// Turn a video into images for an image dataset by extracting the frames that differ
+/// from each other (see [`keyframes::extract_keyframes`])
+///
+/// Keeps at most `max_frames` (20 by default) frames differing by at least
+/// `min_difference` (0.1 by default, on a 0 to 1 scale). Runs as an import job and
+/// returns the paths of the written images.
+#[tauri::command]
+pub async fn extract_keyframes(
+    app: AppHandle,
+    path: String,
+    max_frames: Option<usize>,
+    min_difference: Option<f64>,
+) -> Result<Vec<String>, String> {
+    let video = validate_media_file(&app, &path)?;
+    if formats::media_type(&video) != Some("video") {
+        return Err(format!("Not a video: {}", path));
+    }
+    let max_frames = max_frames.unwrap_or(20).max(1);
+    let min_difference = min_difference.unwrap_or(0.1);
+    let description = format!("Extract key frames of {}", path);
+
+    jobs::run(&app, JobKind::Import, description, move |job| {
+        let images = keyframes::extract_keyframes(job, &video, max_frames, min_difference)?;
+        Ok(images
+            .iter()
+            .map(|image| paths::display_path(image))
+            .collect())
+    })
+    .await
+}
+
+/// Write each image's caption into its own XMP and IPTC metadata
+///
+/// Lets captions travel with the files into tools that ignore sidecars. Runs as an
+/// export job; files that can't be rewritten are listed in the summary. Refused while
+/// the project is read-only or in use elsewhere. The rewrites are recorded as one
+/// undoable operation, also when the job is cancelled partway, so undoing it restores
+/// the original bytes of every image.
+#[tauri::command]
+pub async fn embed_caption_metadata(
+    app: AppHandle,
+    directory: String,
+) -> Result<EmbedSummary, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let description = format!("Embed captions in {}", directory);
+
+    jobs::run(&app, JobKind::Export, description, move |job| {
+        let mut operation = Operation::begin(
+            &project,
+            OperationKind::MediaEdit,
+            "Embed captions in images",
+        )?;
+        let result = metadata::embed_directory(job, &project, Some(&mut operation));
+
+        // Keep what was embedded so far undoable, even when cancelled
+        operation.commit()?;
+        result
+    })
+    .await
+}
+
+/// Downscale the videos of a project whose shorter side is above `max_resolution`
+/// (default 1080), in place
+///
+/// Meant as an import step, so 4K sources are re-encoded once. Runs as a crop job;
+/// cancelling it stops FFmpeg and keeps the videos downscaled so far. The downscales
+/// are recorded as one undoable operation.
+#[tauri::command]
+pub async fn downscale_videos(
+    app: AppHandle,
+    directory: String,
+    max_resolution: Option<u32>,
+) -> Result<DownscaleSummary, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let description = format!("Downscale videos in {}", directory);
+
+    jobs::run(&app, JobKind::Crop, description, move |job| {
+        downscale::downscale_videos(job, &project, max_resolution)
+    })
+    .await
+}
+
 /// Crop a video using FFmpeg, overwriting the original file
+///
+/// Runs as a crop job; cancelling it stops FFmpeg and leaves the video untouched.
 #[tauri::command]
-pub async fn crop_video(path: String, crop_params: serde_json::Value) -> Result<String, String> {
+pub async fn crop_video(
+    app: AppHandle,
+    path: String,
+    crop_params: serde_json::Value,
+) -> Result<String, String> {
+    settings::ensure_writable(Path::new(&path))?;
+    let description = format!("Crop {}", path);
+
+    jobs::run(&app, JobKind::Crop, description, move |job| {
+        crop_video_job(job, path, crop_params)
+    })
+    .await
+}
+
+#[instrument(skip_all, fields(video = %path))]
+fn crop_video_job(
+    job: &Job,
+    path: String,
+    crop_params: serde_json::Value,
+) -> Result<String, String> {
     // Parse crop parameters
     let x = crop_params
         .get("x")
@@ -367,21 +1086,23 @@ pub async fn crop_video(path: String, crop_params: serde_json::Value) -> Result<
         .and_then(|v| v.as_bool())
         .unwrap_or(false);
 
-    // Check if ffmpeg is available
-    let ffmpeg_result = Command::new("ffmpeg").arg("-version").output();
-
-    if ffmpeg_result.is_err() {
-        return Err("FFmpeg is not installed or not in PATH. Please install FFmpeg to enable video cropping.".to_string());
+    let capabilities = ffmpeg::require("video cropping")?;
+    if !capabilities.has_encoder("libx264") {
+        return Err(
+            "FFmpeg was built without the libx264 encoder, which video cropping needs.".to_string(),
+        );
     }
 
     // Create a temporary path for the cropped video
-    let path_obj = Path::new(&path);
-    let temp_path = generate_modified_filename(path_obj, "_temp");
+    let path_obj = &paths::long_path(Path::new(&path));
+    let temp_path = generate_modified_filename(path_obj, TEMP_SUFFIX);
 
     // Create a backup of the original file
-    let backup_path = generate_modified_filename(path_obj, "_backup");
-    if let Err(e) = fs::copy(path_obj, &backup_path) {
-        return Err(format!("Failed to create backup of original video: {}", e));
+    let backup_path = generate_modified_filename(path_obj, BACKUP_SUFFIX);
+    if let Err(e) = in_use::retry(|| fs::copy(path_obj, &backup_path)) {
+        return Err(in_use::error_message(e, "crop", Path::new(&path), |e| {
+            format!("Failed to create backup of original video: {}", e)
+        }));
     }
 
     // Build FFmpeg filter chain
@@ -416,9 +1137,9 @@ pub async fn crop_video(path: String, crop_params: serde_json::Value) -> Result<
     let filter_chain = filters.join(",");
 
     // Execute FFmpeg with the filter chain
-    let output = Command::new("ffmpeg")
+    let child = Command::new("ffmpeg")
         .arg("-i")
-        .arg(&path)
+        .arg(path_obj)
         .arg("-vf")
         .arg(filter_chain)
         .arg("-c:a")
@@ -430,14 +1151,23 @@ pub async fn crop_video(path: String, crop_params: serde_json::Value) -> Result<
         .arg("-crf")
         .arg("23") // Reasonable quality
         .arg(&temp_path)
-        .output();
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+    let output = child.and_then(|child| wait_for_ffmpeg(child, job));
+
+    if job.is_cancelled() {
+        let _ = fs::remove_file(&temp_path);
+        let _ = fs::remove_file(&backup_path);
+        return Err(jobs::CANCELLED.to_string());
+    }
 
     match output {
         Ok(output) => {
             if !output.status.success() {
                 let error = String::from_utf8_lossy(&output.stderr);
                 // Log the full error to console
-                eprintln!("FFmpeg error (crop): {}", error);
+                error!("FFmpeg error (crop): {}", error);
 
                 // Try to remove the temporary file if it exists
                 let _ = fs::remove_file(&temp_path);
@@ -450,7 +1180,7 @@ pub async fn crop_video(path: String, crop_params: serde_json::Value) -> Result<
         }
         Err(e) => {
             // Log the full error to console
-            eprintln!("Failed to run ffmpeg (crop): {}", e);
+            error!("Failed to run ffmpeg (crop): {}", e);
 
             // Try to remove the temporary file if it exists
             let _ = fs::remove_file(&temp_path);
@@ -470,18 +1200,23 @@ pub async fn crop_video(path: String, crop_params: serde_json::Value) -> Result<
     }
 
     // Move the temp file to overwrite the original
-    if let Err(e) = fs::rename(&temp_path, path_obj) {
+    if let Err(e) = in_use::retry(|| fs::rename(&temp_path, path_obj)) {
         // If rename fails, try to restore from backup
         let _ = fs::copy(&backup_path, path_obj);
         // Try to remove the temporary file
         let _ = fs::remove_file(&temp_path);
         // Try to remove the backup file
         let _ = fs::remove_file(&backup_path);
-        return Err(format!("Failed to replace original video: {}", e));
+        return Err(in_use::error_message(e, "crop", Path::new(&path), |e| {
+            format!("Failed to replace original video: {}", e)
+        }));
     }
 
-    // Remove the backup file
-    let _ = fs::remove_file(&backup_path);
+    // Keep the original in the undo journal instead of discarding the backup
+    if let Err(e) = journal_media_edit(path_obj, &backup_path, "Crop") {
+        warn!("Failed to record crop in undo journal: {}", e);
+        let _ = fs::remove_file(&backup_path);
+    }
 
     // Return the original path
     Ok(path)
@@ -489,22 +1224,23 @@ pub async fn crop_video(path: String, crop_params: serde_json::Value) -> Result<
 
 /// Get the current progress of a video trim operation
 /// Used to poll progress from the frontend
+///
+/// Reads the most recent trim job: its percentage while it runs, 100 once it has
+/// completed and -1 if it failed.
 #[tauri::command]
 pub fn get_trim_progress() -> Result<i32, String> {
-    // Get the global progress value
-    let progress = match TRIM_PROGRESS.lock() {
-        Ok(progress) => *progress,
-        Err(_) => 0,
+    let progress = match jobs::latest(JobKind::Trim) {
+        Some(job) => match job.status {
+            JobStatus::Completed => 100,
+            JobStatus::Failed => -1,
+            _ => job.progress.current as i32,
+        },
+        None => 0,
     };
 
     Ok(progress)
 }
 
-// Global variable to track trim progress
-static TRIM_PROGRESS: Lazy<Mutex<i32>> = Lazy::new(|| {
-    Mutex::new(0) // Initialize with 0% progress
-});
-
 /// Extract a frame from a video at a specific timestamp and return it as a data URL
 #[tauri::command]
 pub async fn extract_video_frame(path: String, time_sec: Option<f64>) -> Result<String, String> {
@@ -516,23 +1252,18 @@ pub async fn extract_video_frame(path: String, time_sec: Option<f64>) -> Result<
 
     // Create a path for the extracted frame
     let frame_path = temp_dir.path().join("frame.jpg");
-    let frame_path_str = frame_path.to_string_lossy().to_string();
 
     // Check if the file exists
-    let path_obj = Path::new(&path);
+    let path_obj = &paths::long_path(Path::new(&path));
     if !path_obj.exists() {
         return Err(format!("File not found: {}", path_obj.display()));
     }
 
-    // Check if ffmpeg is available
-    let ffmpeg_result = Command::new("ffmpeg").arg("-version").output();
-    if ffmpeg_result.is_err() {
-        return Err("FFmpeg is not installed or not in PATH. Please install FFmpeg to enable frame extraction.".to_string());
-    }
+    ffmpeg::require("frame extraction")?;
 
     // Build command to extract the frame at the specified time, or the first frame if time_sec is None
     let mut cmd = Command::new("ffmpeg");
-    cmd.arg("-i").arg(path_obj.to_string_lossy().to_string());
+    cmd.arg("-i").arg(path_obj);
     
     // If time_sec is provided, seek to that position
     if let Some(time) = time_sec {
@@ -544,7 +1275,7 @@ pub async fn extract_video_frame(path: String, time_sec: Option<f64>) -> Result<
        .arg("1")
        .arg("-q:v")
        .arg("2") // High quality
-       .arg(&frame_path_str);
+       .arg(&frame_path);
 
     // Execute the command
     let output = cmd.output();
@@ -592,32 +1323,40 @@ pub async fn extract_video_frame(path: String, time_sec: Option<f64>) -> Result<
 }
 
 /// Reset the trim progress (called when starting a new trim)
+///
+/// Kept for compatibility: every trim is a new job, so progress starts at 0 by itself.
 #[tauri::command]
 pub fn reset_trim_progress() -> Result<(), String> {
-    match TRIM_PROGRESS.lock() {
-        Ok(mut progress) => {
-            *progress = 0;
-            Ok(())
-        }
-        Err(_) => Err("Failed to reset progress".to_string()),
-    }
+    Ok(())
 }
 
 /// Trim a video using FFmpeg, overwriting the original file
 /// Now with frame-accurate re-encoding for exact trimming
+///
+/// Runs as a trim job; cancelling it stops FFmpeg and leaves the video untouched.
 #[tauri::command]
 pub async fn trim_video(
-    _app: tauri::AppHandle,
+    app: AppHandle,
     path: String,
     start_time: f64,
     end_time: f64,
 ) -> Result<String, String> {
-    // Reset progress at the beginning
-    match TRIM_PROGRESS.lock() {
-        Ok(mut progress) => *progress = 0,
-        Err(_) => return Err("Failed to initialize progress tracking".to_string()),
-    }
+    settings::ensure_writable(Path::new(&path))?;
+    let description = format!("Trim {}", path);
 
+    jobs::run(&app, JobKind::Trim, description, move |job| {
+        trim_video_job(job, path, start_time, end_time)
+    })
+    .await
+}
+
+#[instrument(skip_all, fields(video = %path))]
+fn trim_video_job(
+    job: &Job,
+    path: String,
+    start_time: f64,
+    end_time: f64,
+) -> Result<String, String> {
     // Validate time parameters
     if start_time < 0.0 {
         return Err("Start time cannot be negative".to_string());
@@ -627,21 +1366,18 @@ pub async fn trim_video(
         return Err("End time must be greater than start time".to_string());
     }
 
-    // Check if ffmpeg is available
-    let ffmpeg_result = Command::new("ffmpeg").arg("-version").output();
-
-    if ffmpeg_result.is_err() {
-        return Err("FFmpeg is not installed or not in PATH. Please install FFmpeg to enable video trimming.".to_string());
-    }
+    let capabilities = ffmpeg::require("video trimming")?;
 
     // Create temporary path for the trimmed video
-    let path_obj = Path::new(&path);
-    let temp_path = generate_modified_filename(path_obj, "_temp");
+    let path_obj = &paths::long_path(Path::new(&path));
+    let temp_path = generate_modified_filename(path_obj, TEMP_SUFFIX);
 
     // Create a backup of the original file
-    let backup_path = generate_modified_filename(path_obj, "_backup");
-    if let Err(e) = fs::copy(path_obj, &backup_path) {
-        return Err(format!("Failed to create backup of original video: {}", e));
+    let backup_path = generate_modified_filename(path_obj, BACKUP_SUFFIX);
+    if let Err(e) = in_use::retry(|| fs::copy(path_obj, &backup_path)) {
+        return Err(in_use::error_message(e, "trim", Path::new(&path), |e| {
+            format!("Failed to create backup of original video: {}", e)
+        }));
     }
 
     // Calculate duration
@@ -657,7 +1393,7 @@ pub async fn trim_video(
         .arg("stream=codec_name,width,height,r_frame_rate,bit_rate")
         .arg("-of")
         .arg("csv=p=0")
-        .arg(&path)
+        .arg(path_obj)
         .output();
 
     // Variables to store video info
@@ -695,7 +1431,7 @@ pub async fn trim_video(
                     }
 
                     // Log what we're using
-                    eprintln!(
+                    debug!(
                         "Original codec: {}, using encoder: {} with CRF: {}",
                         original_codec, video_codec, crf_value
                     );
@@ -703,13 +1439,26 @@ pub async fn trim_video(
             }
         }
         Err(e) => {
-            eprintln!("Failed to probe video details: {}", e);
+            warn!("Failed to probe video details: {}", e);
             // Continue with defaults
         }
     }
 
+    // Not every FFmpeg build has every encoder, so fall back to H.264
+    if !capabilities.has_encoder(&video_codec) {
+        warn!("FFmpeg has no {} encoder, using libx264", video_codec);
+        video_codec = "libx264".to_string();
+        crf_value = "18".to_string();
+        preset = "medium".to_string();
+    }
+    if !capabilities.has_encoder(&video_codec) {
+        return Err(
+            "FFmpeg was built without the libx264 encoder, which video trimming needs.".to_string(),
+        );
+    }
+
     // Log the command we're about to run
-    eprintln!(
+    info!(
         "Trimming video from {} to {} (duration: {})",
         start_time, end_time, duration
     );
@@ -728,19 +1477,18 @@ pub async fn trim_video(
         path, start_time, duration, video_codec, crf_value, preset, progress_file.display(), temp_path.display()
     );
     
-    // Print detailed diagnostic info to console
-    println!("=== FFMPEG TRIM OPERATION ===");
-    println!("Command: {}", cmd_string);
-    println!("Input file: {}", path);
-    println!("Start time: {}s", start_time);
-    println!("End time: {}s", end_time);
-    println!("Duration: {}s", duration);
-    println!("Codec: {}", video_codec);
-    println!("CRF: {}", crf_value);
-    println!("Preset: {}", preset);
-    println!("Output file: {}", temp_path.display());
-    println!("Time: {}", chrono::Local::now().to_rfc3339());
-    println!("============================");
+    debug!(
+        command = %cmd_string,
+        input = %path,
+        start_time,
+        end_time,
+        duration,
+        codec = %video_codec,
+        crf = %crf_value,
+        preset = %preset,
+        output = %temp_path.display(),
+        "Running ffmpeg trim"
+    );
 
     // Create a pipe for stderr to capture output while still allowing the process to run
     let stderr_file = tempfile::tempfile()
@@ -753,7 +1501,7 @@ pub async fn trim_video(
         .arg("-v") // Verbose mode for more detailed output
         .arg("verbose")
         .arg("-i")
-        .arg(&path)
+        .arg(path_obj)
         .arg("-ss")
         .arg(start_time.to_string())
         .arg("-t")
@@ -789,6 +1537,7 @@ pub async fn trim_video(
 
             // Create a handle to child.id() that we can use from multiple places
             let child_id = child.id();
+            let job_progress = job.clone();
 
             // Spawn a thread that just monitors the progress
             std::thread::spawn(move || {
@@ -853,9 +1602,7 @@ pub async fn trim_video(
 
                                     // Only update if progress changed significantly
                                     if progress - last_progress >= 1.0 {
-                                        if let Ok(mut global_progress) = TRIM_PROGRESS.lock() {
-                                            *global_progress = progress as i32;
-                                        }
+                                        job_progress.progress(progress as u64, 100, None);
                                         last_progress = progress;
                                     }
                                 }
@@ -866,10 +1613,15 @@ pub async fn trim_video(
             });
 
             // Meanwhile, wait for the process to complete in the main thread
-            let status = child
-                .wait()
+            let status = wait_for_exit(&mut child, job)
                 .map_err(|e| format!("FFmpeg process error: {}", e))?;
 
+            if job.is_cancelled() {
+                let _ = fs::remove_file(&temp_path);
+                let _ = fs::remove_file(&backup_path);
+                return Err(jobs::CANCELLED.to_string());
+            }
+
             if !status.success() {
                 // Get exit code for more detailed error info
                 let exit_code = status.code().unwrap_or(-1);
@@ -900,7 +1652,7 @@ pub async fn trim_video(
                         .arg(duration.to_string())
                         .output()
                         .unwrap_or_else(|e| {
-                            eprintln!("Failed to get ffmpeg error details: {}", e);
+                            warn!("Failed to get ffmpeg error details: {}", e);
                             std::process::Command::new("echo")
                                 .arg("Failed to get ffmpeg error details")
                                 .output()
@@ -910,22 +1662,12 @@ pub async fn trim_video(
                     stderr_content = String::from_utf8_lossy(&output.stderr).to_string();
                 }
                 
-                // Print the full error to console
-                println!("=== FFMPEG ERROR ===");
-                println!("Exit code: {}", exit_code);
-                println!("Error details:");
-                println!("{}", stderr_content);
-                println!("==================");
+                error!(exit_code, "FFmpeg trim failed:\n{}", stderr_content);
 
                 // Clean up temporary files
                 let _ = fs::remove_file(&temp_path);
                 let _ = fs::remove_file(&backup_path);
 
-                // Set progress to error state (-1)
-                if let Ok(mut progress) = TRIM_PROGRESS.lock() {
-                    *progress = -1;
-                }
-
                 // Try to extract a meaningful error message from ffmpeg output
                 let user_message = if stderr_content.contains("Invalid data found when processing input") {
                     "Failed to trim video: The video file might be corrupted or in an unsupported format."
@@ -942,26 +1684,16 @@ pub async fn trim_video(
                 };
 
                 return Err(user_message.to_string());
-            } else {
-                // Success - set progress to 100%
-                if let Ok(mut progress) = TRIM_PROGRESS.lock() {
-                    *progress = 100;
-                }
             }
         }
         Err(e) => {
             // Log the full error to console
-            eprintln!("Failed to run ffmpeg: {}", e);
+            error!("Failed to run ffmpeg: {}", e);
 
             // Clean up temporary files
             let _ = fs::remove_file(&temp_path);
             let _ = fs::remove_file(&backup_path);
 
-            // Set progress to error state (-1)
-            if let Ok(mut progress) = TRIM_PROGRESS.lock() {
-                *progress = -1;
-            }
-
             // Return a more concise error message
             return Err("Failed to run FFmpeg. Check logs for details.".to_string());
         }
@@ -974,18 +1706,23 @@ pub async fn trim_video(
     }
 
     // Move the temporary file to overwrite the original
-    if let Err(e) = fs::rename(&temp_path, path_obj) {
+    if let Err(e) = in_use::retry(|| fs::rename(&temp_path, path_obj)) {
         // If rename fails, try to restore from backup
         let _ = fs::copy(&backup_path, path_obj);
         // Try to remove the temporary file
         let _ = fs::remove_file(&temp_path);
         // Try to remove the backup file
         let _ = fs::remove_file(&backup_path);
-        return Err(format!("Failed to replace original video: {}", e));
+        return Err(in_use::error_message(e, "trim", Path::new(&path), |e| {
+            format!("Failed to replace original video: {}", e)
+        }));
     }
 
-    // Remove the backup file
-    let _ = fs::remove_file(&backup_path);
+    // Keep the original in the undo journal instead of discarding the backup
+    if let Err(e) = journal_media_edit(path_obj, &backup_path, "Trim") {
+        warn!("Failed to record trim in undo journal: {}", e);
+        let _ = fs::remove_file(&backup_path);
+    }
 
     // Return the original path
     Ok(path)
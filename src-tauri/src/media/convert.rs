@@ -0,0 +1,264 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ImageEncoder};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::tempdir;
+use tracing::warn;
+
+use super::ffmpeg;
+use super::formats;
+use crate::captions::format::CaptionStore;
+use crate::jobs::manager::Job;
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Extensions (lowercase) the image decoder can't read, decoded through FFmpeg instead
+const FFMPEG_EXTENSIONS: &[&str] = &["heic", "heif", "avif"];
+
+/// JPEG quality when none is given
+pub const DEFAULT_QUALITY: u8 = 90;
+
+/// Format images are converted to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl TargetFormat {
+    /// The format of a file extension (lowercase), if it is one of the targets
+    pub fn for_extension(extension: &str) -> Option<Self> {
+        [TargetFormat::Jpeg, TargetFormat::Png, TargetFormat::Webp]
+            .into_iter()
+            .find(|target| target.matches(extension))
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            TargetFormat::Jpeg => "jpg",
+            TargetFormat::Png => "png",
+            TargetFormat::Webp => "webp",
+        }
+    }
+
+    /// Whether a file extension (lowercase) already is this format
+    fn matches(self, extension: &str) -> bool {
+        match self {
+            TargetFormat::Jpeg => matches!(extension, "jpg" | "jpeg" | "jfif"),
+            TargetFormat::Png => extension == "png",
+            TargetFormat::Webp => extension == "webp",
+        }
+    }
+}
+
+/// Result of converting images to one format, with file names
+#[derive(Debug, Default, Serialize)]
+pub struct ImageConversion {
+    pub converted: Vec<String>,
+    /// Images already in the format, or with a converted file next to them
+    pub skipped: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Decode an image, going through FFmpeg for HEIC and AVIF files
+fn decode(path: &Path) -> Result<DynamicImage, String> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !FFMPEG_EXTENSIONS.contains(&extension.as_str()) {
+        return formats::open_image(path).map_err(|e| format!("Failed to open image: {}", e));
+    }
+
+    ffmpeg::require("HEIC and AVIF conversion")?;
+    let temp_dir = tempdir().map_err(|e| format!("Failed to create temporary directory: {}", e))?;
+    let frame_path = temp_dir.path().join("frame.png");
+
+    let output = Command::new("ffmpeg")
+        .arg("-i")
+        .arg(path)
+        .arg("-frames:v")
+        .arg("1")
+        .arg(&frame_path)
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() || !frame_path.exists() {
+        return Err(format!(
+            "Failed to decode image: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    image::open(&frame_path).map_err(|e| format!("Failed to open decoded image: {}", e))
+}
+
+/// Encode an image to a file
+///
+/// WebP is written lossless, since the image encoder has no lossy mode; the quality
+/// only applies to JPEG.
+pub fn encode(
+    image: &DynamicImage,
+    target: TargetFormat,
+    quality: u8,
+    path: &Path,
+) -> Result<(), String> {
+    let file = fs::File::create(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+
+    let result = match target {
+        TargetFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let rgb = image.to_rgb8();
+            JpegEncoder::new_with_quality(&mut writer, quality).write_image(
+                &rgb,
+                rgb.width(),
+                rgb.height(),
+                image::ColorType::Rgb8,
+            )
+        }
+        TargetFormat::Png => {
+            let rgba = image.to_rgba8();
+            PngEncoder::new(&mut writer).write_image(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                image::ColorType::Rgba8,
+            )
+        }
+        TargetFormat::Webp => {
+            let rgba = image.to_rgba8();
+            WebPEncoder::new_lossless(&mut writer).write_image(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                image::ColorType::Rgba8,
+            )
+        }
+    };
+
+    result.map_err(|e| format!("Failed to encode {}: {}", path.display(), e))?;
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Give a converted file the caption of its original, and with `remove_original`
+/// delete the original, recording both in an operation
+pub fn move_caption(
+    store: &mut CaptionStore,
+    operation: &mut Operation,
+    source: &Path,
+    converted: &Path,
+    remove_original: bool,
+) -> Result<(), String> {
+    // Sidecars are shared through the file stem; manifest entries are per file name
+    if store.format().is_manifest() {
+        if let Some(caption) = store.read(source)? {
+            operation.snapshot(&store.caption_path(converted))?;
+            store.write(converted, &caption)?;
+            if remove_original {
+                store.remove(source)?;
+            }
+        }
+    }
+
+    if remove_original {
+        operation.snapshot_and_remove(source)?;
+    }
+
+    Ok(())
+}
+
+/// Convert images to one format, writing each next to its original
+///
+/// Captions move to the converted file, and with `remove_originals` the originals are
+/// deleted. The conversion is recorded as one undoable operation on the project of
+/// the first image.
+pub fn convert_images(
+    job: &Job,
+    paths: &[PathBuf],
+    target: TargetFormat,
+    quality: Option<u8>,
+    remove_originals: bool,
+) -> Result<ImageConversion, String> {
+    let mut report = ImageConversion::default();
+    let Some(first) = paths.first() else {
+        return Ok(report);
+    };
+
+    let quality = quality.unwrap_or(DEFAULT_QUALITY).clamp(1, 100);
+    let mut store = CaptionStore::for_media(first);
+    let mut operation = Operation::begin(
+        first,
+        OperationKind::MediaEdit,
+        format!("Convert images to {}", target.extension().to_uppercase()),
+    )?;
+
+    job.progress(0, paths.len() as u64, Some("Converting images".to_string()));
+    for source in paths {
+        if let Err(e) = job.check_cancelled() {
+            // Keep what was converted so far undoable
+            operation.commit()?;
+            return Err(e);
+        }
+
+        let name = file_name(source);
+        let extension = source
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let converted = source.with_extension(target.extension());
+        if target.matches(&extension) || converted.exists() {
+            report.skipped.push(name);
+            job.advance(1);
+            continue;
+        }
+
+        let image = match decode(source) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!("Failed to convert {}: {}", source.display(), e);
+                report.failed.push(name);
+                job.advance(1);
+                continue;
+            }
+        };
+
+        operation.snapshot(&converted)?;
+        if let Err(e) = encode(&image, target, quality, &converted) {
+            warn!("Failed to convert {}: {}", source.display(), e);
+            let _ = fs::remove_file(&converted);
+            report.failed.push(name);
+            job.advance(1);
+            continue;
+        }
+
+        move_caption(
+            &mut store,
+            &mut operation,
+            source,
+            &converted,
+            remove_originals,
+        )?;
+
+        report.converted.push(name);
+        job.advance(1);
+    }
+
+    operation.commit()?;
+
+    Ok(report)
+}
@@ -0,0 +1,284 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use super::convert::{self, TargetFormat};
+use super::formats;
+use crate::jobs::manager::Job;
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Name of the preset that picks the nearest SDXL bucket for each image
+pub const SDXL_AUTO: &str = "sdxl";
+
+/// A named aspect ratio images can be cropped to
+#[derive(Debug, Clone, Serialize)]
+pub struct CropPreset {
+    pub name: String,
+    pub label: String,
+    /// Aspect ratio as width and height, e.g. 16 and 9
+    pub width: u32,
+    pub height: u32,
+    /// Whether the cropped image is also resized to exactly `width` by `height`, as for
+    /// training buckets
+    pub resize: bool,
+}
+
+/// Resolutions SDXL was trained on, as width and height
+const SDXL_BUCKETS: &[(u32, u32)] = &[
+    (1024, 1024),
+    (1152, 896),
+    (896, 1152),
+    (1216, 832),
+    (832, 1216),
+    (1344, 768),
+    (768, 1344),
+    (1536, 640),
+    (640, 1536),
+];
+
+fn ratio(name: &str, width: u32, height: u32) -> CropPreset {
+    CropPreset {
+        name: name.to_string(),
+        label: name.to_string(),
+        width,
+        height,
+        resize: false,
+    }
+}
+
+/// The built-in crop presets: common aspect ratios, then the SDXL buckets
+pub fn presets() -> Vec<CropPreset> {
+    let mut presets = vec![
+        ratio("1:1", 1, 1),
+        ratio("2:3", 2, 3),
+        ratio("3:2", 3, 2),
+        ratio("4:5", 4, 5),
+        ratio("9:16", 9, 16),
+        ratio("16:9", 16, 9),
+    ];
+    presets.extend(SDXL_BUCKETS.iter().map(|&(width, height)| CropPreset {
+        name: format!("sdxl-{}x{}", width, height),
+        label: format!("SDXL {}×{}", width, height),
+        width,
+        height,
+        resize: true,
+    }));
+    presets
+}
+
+/// Side of an image that is kept when cropping
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Gravity {
+    #[default]
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// Result of cropping images to an aspect ratio, with file names
+#[derive(Debug, Default, Serialize)]
+pub struct CropSummary {
+    pub cropped: Vec<String>,
+    /// Videos, formats that can't be written back, and images already at the aspect
+    pub skipped: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// What an image is cropped to
+enum Target {
+    Preset(CropPreset),
+    /// The SDXL bucket nearest to each image's own aspect ratio
+    SdxlAuto,
+}
+
+/// Parse an aspect given as a preset name, `sdxl`, or a ratio like `"5:4"`
+fn parse_target(aspect: &str) -> Result<Target, String> {
+    let aspect = aspect.trim();
+    if aspect.eq_ignore_ascii_case(SDXL_AUTO) {
+        return Ok(Target::SdxlAuto);
+    }
+    if let Some(preset) = presets().into_iter().find(|p| p.name == aspect) {
+        return Ok(Target::Preset(preset));
+    }
+
+    let (width, height) = aspect
+        .split_once([':', 'x'])
+        .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)))
+        .filter(|&(w, h): &(u32, u32)| w > 0 && h > 0)
+        .ok_or_else(|| format!("Invalid aspect ratio: {}", aspect))?;
+    Ok(Target::Preset(ratio(aspect, width, height)))
+}
+
+/// The SDXL bucket whose aspect ratio is nearest to an image's
+fn nearest_sdxl_bucket(width: u32, height: u32) -> (u32, u32) {
+    let aspect = (width as f64 / height as f64).ln();
+    SDXL_BUCKETS
+        .iter()
+        .copied()
+        .min_by(|a, b| {
+            let distance = |(w, h): (u32, u32)| ((w as f64 / h as f64).ln() - aspect).abs();
+            distance(*a).total_cmp(&distance(*b))
+        })
+        .unwrap_or((1024, 1024))
+}
+
+/// Region of a `width` by `height` image with the aspect of `aspect_width` by
+/// `aspect_height`, as large as possible and placed by `gravity`
+fn crop_region(
+    width: u32,
+    height: u32,
+    aspect_width: u32,
+    aspect_height: u32,
+    gravity: Gravity,
+) -> (u32, u32, u32, u32) {
+    let aspect = aspect_width as f64 / aspect_height as f64;
+    if width as f64 / height as f64 > aspect {
+        let new_width = ((height as f64 * aspect).round() as u32).clamp(1, width);
+        let x = match gravity {
+            Gravity::Left => 0,
+            Gravity::Right => width - new_width,
+            _ => (width - new_width) / 2,
+        };
+        (x, 0, new_width, height)
+    } else {
+        let new_height = ((width as f64 / aspect).round() as u32).clamp(1, height);
+        let y = match gravity {
+            Gravity::Top => 0,
+            Gravity::Bottom => height - new_height,
+            _ => (height - new_height) / 2,
+        };
+        (0, y, width, new_height)
+    }
+}
+
+/// Crop an image to the target, or `None` if it already matches it
+fn crop_image(image: &DynamicImage, target: &Target, gravity: Gravity) -> Option<DynamicImage> {
+    let (width, height) = image.dimensions();
+    let (aspect_width, aspect_height, resize) = match target {
+        Target::Preset(preset) => (preset.width, preset.height, preset.resize),
+        Target::SdxlAuto => {
+            let (w, h) = nearest_sdxl_bucket(width, height);
+            (w, h, true)
+        }
+    };
+
+    let (x, y, crop_width, crop_height) =
+        crop_region(width, height, aspect_width, aspect_height, gravity);
+    let unchanged = (crop_width, crop_height) == (width, height);
+    if unchanged && (!resize || (width, height) == (aspect_width, aspect_height)) {
+        return None;
+    }
+
+    let cropped = image.crop_imm(x, y, crop_width, crop_height);
+    Some(if resize {
+        cropped.resize_exact(aspect_width, aspect_height, FilterType::Lanczos3)
+    } else {
+        cropped
+    })
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Crop images in place with `crop`, which returns `None` for images to leave as they
+/// are
+///
+/// Only JPEG, PNG and WebP files are cropped, since other formats can't be written
+/// back. The crops are recorded as one undoable operation on the project of the first
+/// image, described by `description`.
+pub fn crop_in_place(
+    job: &Job,
+    paths: &[PathBuf],
+    description: String,
+    crop: impl Fn(&DynamicImage) -> Option<DynamicImage>,
+) -> Result<CropSummary, String> {
+    let mut summary = CropSummary::default();
+    let Some(first) = paths.first() else {
+        return Ok(summary);
+    };
+
+    let mut operation = Operation::begin(first, OperationKind::MediaEdit, description)?;
+
+    job.progress(0, paths.len() as u64, Some("Cropping images".to_string()));
+    for path in paths {
+        if let Err(e) = job.check_cancelled() {
+            // Keep what was cropped so far undoable
+            operation.commit()?;
+            return Err(e);
+        }
+
+        let name = file_name(path);
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let format = TargetFormat::for_extension(&extension);
+        let (Some(format), Some("image")) = (format, formats::media_type(path)) else {
+            summary.skipped.push(name);
+            job.advance(1);
+            continue;
+        };
+
+        let cropped = match formats::open_image(path) {
+            Ok(image) => crop(&image),
+            Err(e) => {
+                warn!("Failed to crop {}: {}", path.display(), e);
+                summary.failed.push(name);
+                job.advance(1);
+                continue;
+            }
+        };
+        let Some(cropped) = cropped else {
+            summary.skipped.push(name);
+            job.advance(1);
+            continue;
+        };
+
+        // Replace the file rather than writing through a hard link to an imported
+        // original
+        operation.snapshot(path)?;
+        let _ = fs::remove_file(path);
+        if let Err(e) = convert::encode(&cropped, format, convert::DEFAULT_QUALITY, path) {
+            warn!("Failed to crop {}: {}", path.display(), e);
+            summary.failed.push(name);
+            job.advance(1);
+            continue;
+        }
+
+        summary.cropped.push(name);
+        job.advance(1);
+    }
+
+    operation.commit()?;
+
+    Ok(summary)
+}
+
+/// Crop images in place to an aspect ratio
+///
+/// `aspect` is a preset name (see [`presets`]), `sdxl` for the nearest SDXL bucket of
+/// each image, or a ratio like `"5:4"`. The largest region with that aspect is kept,
+/// placed by `gravity`; bucket presets also resize to the bucket's resolution.
+pub fn crop_to_aspect(
+    job: &Job,
+    paths: &[PathBuf],
+    aspect: &str,
+    gravity: Gravity,
+) -> Result<CropSummary, String> {
+    let target = parse_target(aspect)?;
+    crop_in_place(
+        job,
+        paths,
+        format!("Crop images to {}", aspect.trim()),
+        |image| crop_image(image, &target, gravity),
+    )
+}
@@ -0,0 +1,175 @@
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tracing::{error, warn};
+
+use super::commands::{generate_modified_filename, wait_for_ffmpeg};
+use super::ffmpeg;
+use crate::file_system::commands::scan_media_files;
+use crate::file_system::leftovers::TEMP_SUFFIX;
+use crate::jobs::manager::{self as jobs, Job};
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Shorter side videos are downscaled to when no limit is given, i.e. 1080p
+pub const DEFAULT_MAX_RESOLUTION: u32 = 1080;
+
+/// Result of downscaling the videos of a project, with file names
+#[derive(Debug, Default, Serialize)]
+pub struct DownscaleSummary {
+    pub downscaled: Vec<String>,
+    /// Videos in containers that can't be re-encoded in place, e.g. MPEG
+    pub skipped: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// Video codec arguments for re-encoding into a container, by its extension
+/// (lowercase); the sound is copied as it is
+fn codec_args(extension: &str) -> Option<&'static [&'static str]> {
+    match extension {
+        "mp4" | "m4v" | "mov" | "mkv" | "avi" => Some(&[
+            "-c:v", "libx264", "-preset", "medium", "-crf", "18", "-pix_fmt", "yuv420p",
+        ]),
+        "webm" => Some(&["-c:v", "libvpx-vp9", "-crf", "30", "-b:v", "0"]),
+        _ => None,
+    }
+}
+
+/// Scale filter bringing the shorter side of a video down to `max_resolution`, keeping
+/// the aspect ratio and even dimensions
+fn scale_filter(width: u32, height: u32, max_resolution: u32) -> String {
+    if width < height {
+        format!("scale={}:-2", max_resolution)
+    } else {
+        format!("scale=-2:{}", max_resolution)
+    }
+}
+
+/// Re-encode one video at a lower resolution into `target`
+fn downscale(
+    job: &Job,
+    source: &Path,
+    target: &Path,
+    filter: &str,
+    codec: &[&str],
+) -> Result<(), String> {
+    let child = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-i")
+        .arg(source)
+        .arg("-vf")
+        .arg(filter)
+        .args(codec)
+        .arg("-c:a")
+        .arg("copy")
+        .arg(target)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn();
+    match child.and_then(|child| wait_for_ffmpeg(child, job)) {
+        Ok(output) if output.status.success() && target.exists() => Ok(()),
+        Ok(output) => {
+            let error = String::from_utf8_lossy(&output.stderr);
+            error!("FFmpeg error (downscale): {}", error);
+            Err("Failed to downscale video. Check logs for details.".to_string())
+        }
+        Err(e) => Err(format!("Failed to run ffmpeg: {}", e)),
+    }
+}
+
+/// Downscale the videos of a project whose shorter side is above `max_resolution`
+/// (1080 by default), since video training pipelines choke on 4K sources
+///
+/// Meant as an import step, so the re-encode happens once. Videos keep their container
+/// and their sound; MPEG files are skipped. The downscales are recorded as one
+/// undoable operation.
+pub fn downscale_videos(
+    job: &Job,
+    project: &Path,
+    max_resolution: Option<u32>,
+) -> Result<DownscaleSummary, String> {
+    let max_resolution = max_resolution.unwrap_or(DEFAULT_MAX_RESOLUTION).max(144);
+    let videos: Vec<_> = scan_media_files(project)?
+        .into_iter()
+        .filter(|file| file.file_type == "video")
+        .filter(|file| match (file.width, file.height) {
+            (Some(width), Some(height)) => width.min(height) > max_resolution,
+            _ => false,
+        })
+        .collect();
+
+    let mut summary = DownscaleSummary::default();
+    if videos.is_empty() {
+        return Ok(summary);
+    }
+
+    let capabilities = ffmpeg::require("video downscaling")?;
+    let mut operation = Operation::begin(
+        project,
+        OperationKind::MediaEdit,
+        format!("Downscale videos to {}p", max_resolution),
+    )?;
+
+    job.progress(
+        0,
+        videos.len() as u64,
+        Some("Downscaling videos".to_string()),
+    );
+    for file in videos {
+        if let Err(e) = job.check_cancelled() {
+            // Keep what was downscaled so far undoable
+            operation.commit()?;
+            return Err(e);
+        }
+
+        let path = Path::new(&file.path);
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        // The encoder is the second codec argument
+        let codec = codec_args(&extension).filter(|codec| capabilities.has_encoder(codec[1]));
+        let Some(codec) = codec else {
+            summary.skipped.push(file.name);
+            job.advance(1);
+            continue;
+        };
+
+        let filter = scale_filter(
+            file.width.unwrap_or_default(),
+            file.height.unwrap_or_default(),
+            max_resolution,
+        );
+        let temp_path = generate_modified_filename(path, TEMP_SUFFIX);
+        let result = downscale(job, path, &temp_path, &filter, codec);
+
+        if job.is_cancelled() {
+            let _ = fs::remove_file(&temp_path);
+            operation.commit()?;
+            return Err(jobs::CANCELLED.to_string());
+        }
+        if let Err(e) = result {
+            warn!("Failed to downscale {}: {}", file.path, e);
+            let _ = fs::remove_file(&temp_path);
+            summary.failed.push(file.name);
+            job.advance(1);
+            continue;
+        }
+
+        operation.snapshot(path)?;
+        if let Err(e) = fs::rename(&temp_path, path) {
+            warn!("Failed to replace {}: {}", file.path, e);
+            let _ = fs::remove_file(&temp_path);
+            summary.failed.push(file.name);
+            job.advance(1);
+            continue;
+        }
+
+        summary.downscaled.push(file.name);
+        job.advance(1);
+    }
+
+    operation.commit()?;
+
+    Ok(summary)
+}
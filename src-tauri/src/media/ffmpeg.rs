@@ -0,0 +1,129 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::process::{Command, Output};
+use std::sync::{Arc, RwLock};
+use tracing::{debug, warn};
+
+/// What the installed FFmpeg can do
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FfmpegCapabilities {
+    /// Whether `ffmpeg` runs at all
+    pub available: bool,
+    /// Version string reported by `ffmpeg -version`, e.g. `6.1.1`
+    pub version: Option<String>,
+    /// Names of the available encoders, e.g. `libx264`
+    pub encoders: Vec<String>,
+    /// Names of the hardware acceleration methods, e.g. `videotoolbox`
+    pub hwaccels: Vec<String>,
+    /// Whether `ffprobe` runs, which reading video dimensions needs
+    pub ffprobe: bool,
+}
+
+impl FfmpegCapabilities {
+    /// Whether FFmpeg has an encoder, assumed when its encoders couldn't be listed
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.is_empty() || self.encoders.iter().any(|encoder| encoder == name)
+    }
+}
+
+/// Capabilities found by the last probe; probing runs several processes, so it only
+/// happens on first use and when asked to
+static CAPABILITIES: Lazy<RwLock<Option<Arc<FfmpegCapabilities>>>> =
+    Lazy::new(|| RwLock::new(None));
+
+fn run(program: &str, args: &[&str]) -> Option<Output> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+}
+
+/// Names listed by `ffmpeg -encoders`, after the line of dashes ending the legend
+fn parse_encoders(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Names listed by `ffmpeg -hwaccels`, after its heading
+fn parse_hwaccels(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .skip_while(|line| !line.ends_with(':'))
+        .skip(1)
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn probe() -> FfmpegCapabilities {
+    let Some(version) = run("ffmpeg", &["-version"]) else {
+        warn!("FFmpeg is not installed or not in PATH");
+        return FfmpegCapabilities {
+            ffprobe: run("ffprobe", &["-version"]).is_some(),
+            ..Default::default()
+        };
+    };
+
+    let capabilities = FfmpegCapabilities {
+        available: true,
+        // The first line reads "ffmpeg version <version> Copyright ..."
+        version: String::from_utf8_lossy(&version.stdout)
+            .split_whitespace()
+            .nth(2)
+            .map(str::to_string),
+        encoders: run("ffmpeg", &["-hide_banner", "-encoders"])
+            .map(|output| parse_encoders(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or_default(),
+        hwaccels: run("ffmpeg", &["-hide_banner", "-hwaccels"])
+            .map(|output| parse_hwaccels(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or_default(),
+        ffprobe: run("ffprobe", &["-version"]).is_some(),
+    };
+    debug!(
+        version = ?capabilities.version,
+        encoders = capabilities.encoders.len(),
+        hwaccels = ?capabilities.hwaccels,
+        ffprobe = capabilities.ffprobe,
+        "Probed FFmpeg"
+    );
+    capabilities
+}
+
+/// Probe FFmpeg again, e.g. after it was installed while the app was running
+pub fn refresh() -> Arc<FfmpegCapabilities> {
+    let capabilities = Arc::new(probe());
+    if let Ok(mut cached) = CAPABILITIES.write() {
+        *cached = Some(capabilities.clone());
+    }
+    capabilities
+}
+
+/// The capabilities of FFmpeg, probed on first use
+pub fn capabilities() -> Arc<FfmpegCapabilities> {
+    if let Ok(cached) = CAPABILITIES.read() {
+        if let Some(capabilities) = cached.as_ref() {
+            return capabilities.clone();
+        }
+    }
+    refresh()
+}
+
+/// The capabilities of FFmpeg, or an error naming what can't be done without it
+pub fn require(feature: &str) -> Result<Arc<FfmpegCapabilities>, String> {
+    let capabilities = capabilities();
+    if capabilities.available {
+        Ok(capabilities)
+    } else {
+        Err(format!(
+            "FFmpeg is not installed or not in PATH. Please install FFmpeg to enable {}.",
+            feature
+        ))
+    }
+}
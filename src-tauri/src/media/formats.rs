@@ -0,0 +1,139 @@
+use image::io::Reader as ImageReader;
+use image::{DynamicImage, ImageError, ImageFormat, ImageResult};
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+use super::raw;
+
+/// Extensions listed and thumbnailed as images (lowercase)
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "jfif", "png", "gif", "webp", "bmp", "tif", "tiff",
+];
+
+/// Extensions listed and thumbnailed as videos (lowercase)
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "webm", "mov", "avi", "mkv", "mpg", "mpeg"];
+
+/// Extensions that are never media, so their contents aren't sniffed
+const NON_MEDIA_EXTENSIONS: &[&str] = &[
+    "txt", "caption", "json", "md", "csv", "toml", "yaml", "yml", "xml", "log",
+];
+
+/// Media type ("image" or "video") for a file extension; camera RAW files are images
+pub fn type_for_extension(extension: &str) -> Option<&'static str> {
+    let extension = extension.to_lowercase();
+    if IMAGE_EXTENSIONS.contains(&extension.as_str())
+        || raw::RAW_EXTENSIONS.contains(&extension.as_str())
+    {
+        Some("image")
+    } else if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        Some("video")
+    } else {
+        None
+    }
+}
+
+/// Media type of a file from the signature at the start of its contents
+fn sniff_type(path: &Path) -> Option<&'static str> {
+    let mut header = [0u8; 16];
+    let file = fs::File::open(path).ok()?;
+    let read = file.take(16).read(&mut header).ok()?;
+    let header = &header[..read];
+
+    let image = header.starts_with(&[0x89, b'P', b'N', b'G'])
+        || header.starts_with(&[0xFF, 0xD8, 0xFF])
+        || header.starts_with(b"GIF8")
+        || header.starts_with(b"BM")
+        || header.starts_with(b"II*\0")
+        || header.starts_with(b"MM\0*")
+        || (header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP"));
+    if image {
+        return Some("image");
+    }
+
+    let video = header.get(4..8) == Some(b"ftyp")
+        || header.starts_with(&[0x1A, 0x45, 0xDF, 0xA3])
+        || header.starts_with(&[0x00, 0x00, 0x01, 0xBA])
+        || header.starts_with(&[0x00, 0x00, 0x01, 0xB3])
+        || (header.starts_with(b"RIFF") && header.get(8..12) == Some(b"AVI "));
+    if video {
+        return Some("video");
+    }
+
+    None
+}
+
+/// Media type ("image" or "video") of a file, or None if it isn't supported media
+///
+/// Known extensions decide without touching the file. Files with an unknown (or no)
+/// extension are identified by their contents, except for known text formats like
+/// caption sidecars.
+pub fn media_type(path: &Path) -> Option<&'static str> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if let Some(file_type) = type_for_extension(&extension) {
+        return Some(file_type);
+    }
+
+    if NON_MEDIA_EXTENSIONS.contains(&extension.as_str()) || !path.is_file() {
+        return None;
+    }
+
+    sniff_type(path)
+}
+
+/// MIME type of a supported media file, based on its extension
+pub fn mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    let mime = match extension.as_str() {
+        "jpg" | "jpeg" | "jfif" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "mp4" => "video/mp4",
+        "m4v" => "video/x-m4v",
+        "mov" => "video/quicktime",
+        "avi" => "video/x-msvideo",
+        "webm" => "video/webm",
+        "mkv" => "video/x-matroska",
+        "mpg" | "mpeg" => "video/mpeg",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// Reader for the embedded JPEG preview of a camera RAW file
+fn raw_preview_reader(path: &Path) -> ImageResult<ImageReader<Cursor<Vec<u8>>>> {
+    let preview = raw::extract_preview(path)
+        .map_err(|e| ImageError::IoError(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+    Ok(ImageReader::with_format(Cursor::new(preview), ImageFormat::Jpeg))
+}
+
+/// Open an image, detecting its format from the contents rather than the extension
+///
+/// Camera RAW files open as their embedded preview.
+pub fn open_image(path: &Path) -> ImageResult<DynamicImage> {
+    if raw::is_raw(path) {
+        return raw_preview_reader(path)?.decode();
+    }
+
+    ImageReader::open(path)?.with_guessed_format()?.decode()
+}
+
+/// Read the dimensions of an image from its header, whatever its extension
+///
+/// For camera RAW files these are the dimensions of the embedded preview.
+pub fn image_dimensions(path: &Path) -> ImageResult<(u32, u32)> {
+    if raw::is_raw(path) {
+        return raw_preview_reader(path)?.into_dimensions();
+    }
+
+    ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_dimensions()
+}
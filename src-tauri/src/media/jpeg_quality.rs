@@ -0,0 +1,212 @@
+use image::GrayImage;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use crate::file_system::commands::scan_media_files;
+use crate::jobs::manager::Job;
+
+/// Estimated quality below which a JPEG is flagged when no threshold is given
+pub const DEFAULT_MIN_QUALITY: u8 = 75;
+
+/// Blockiness above which a JPEG is flagged whatever its quantization tables say,
+/// e.g. for an image saved at high quality after being compressed hard before
+const MAX_BLOCKINESS: f64 = 1.35;
+
+/// Luminance quantization table of the IJG reference encoder at quality 50, which
+/// most encoders scale for other qualities
+const STANDARD_LUMINANCE: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+/// Compression found in a JPEG
+#[derive(Debug, Serialize)]
+pub struct JpegQualityReport {
+    pub path: String,
+    pub relative_path: String,
+    pub width: u32,
+    pub height: u32,
+    /// Quality (1-100) the file was saved at, estimated from its luminance
+    /// quantization table; None when it has none
+    pub quality: Option<u8>,
+    /// How much stronger edges are on the 8x8 block grid than elsewhere; about 1 for
+    /// clean images
+    pub blockiness: f64,
+}
+
+/// The luminance quantization table of a JPEG, read from its DQT segments before the
+/// image data
+fn luminance_table(data: &[u8]) -> Option<Vec<u16>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut i = 2;
+    while i + 4 <= data.len() && data[i] == 0xFF {
+        let marker = data[i + 1];
+        // Fill bytes before a marker
+        if marker == 0xFF {
+            i += 1;
+            continue;
+        }
+        // Start of scan: the tables all come before it
+        if marker == 0xDA {
+            return None;
+        }
+
+        let length = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+        let segment = data.get(i + 4..i + 2 + length)?;
+        if marker == 0xDB {
+            // A segment may hold several tables, each with its precision and id first
+            let mut j = 0;
+            while j < segment.len() {
+                let precision = segment[j] >> 4;
+                let id = segment[j] & 0x0F;
+                let size = if precision == 0 { 64 } else { 128 };
+                let table = segment.get(j + 1..j + 1 + size)?;
+                if id == 0 {
+                    return Some(if precision == 0 {
+                        table.iter().map(|&q| q as u16).collect()
+                    } else {
+                        table
+                            .chunks_exact(2)
+                            .map(|q| u16::from_be_bytes([q[0], q[1]]))
+                            .collect()
+                    });
+                }
+                j += 1 + size;
+            }
+        }
+        i += 2 + length;
+    }
+
+    None
+}
+
+/// Estimate the quality a JPEG was saved at by comparing its luminance table to the
+/// reference one, inverting the IJG quality scaling
+fn estimate_quality(table: &[u16]) -> u8 {
+    let sum: f64 = table.iter().map(|&q| q as f64).sum();
+    let standard: f64 = STANDARD_LUMINANCE.iter().map(|&q| q as f64).sum();
+    let scale = sum * 100.0 / standard;
+    let quality = if scale <= 100.0 {
+        (200.0 - scale) / 2.0
+    } else {
+        5000.0 / scale
+    };
+    quality.round().clamp(1.0, 100.0) as u8
+}
+
+/// Mean brightness step between neighboring pixels across 8x8 block boundaries,
+/// divided by the mean step inside blocks
+///
+/// Heavy compression leaves a visible grid, so the ratio grows with it.
+fn blockiness(luma: &GrayImage) -> f64 {
+    let (width, height) = luma.dimensions();
+    let mut boundary = (0.0, 0u64);
+    let mut inside = (0.0, 0u64);
+    let mut add = |position: u32, a: u8, b: u8| {
+        let step = (a as f64 - b as f64).abs();
+        let sum = if position % 8 == 7 {
+            &mut boundary
+        } else {
+            &mut inside
+        };
+        sum.0 += step;
+        sum.1 += 1;
+    };
+
+    for y in 0..height {
+        for x in 0..width.saturating_sub(1) {
+            add(x, luma.get_pixel(x, y)[0], luma.get_pixel(x + 1, y)[0]);
+        }
+    }
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..width {
+            add(y, luma.get_pixel(x, y)[0], luma.get_pixel(x, y + 1)[0]);
+        }
+    }
+
+    let boundary = boundary.0 / boundary.1.max(1) as f64;
+    let inside = inside.0 / inside.1.max(1) as f64;
+    if inside < 1.0 {
+        // Flat images have no steps to compare
+        return 1.0;
+    }
+    (boundary / inside * 100.0).round() / 100.0
+}
+
+/// Find the JPEGs of a project that were compressed hard enough to show artifacts
+///
+/// A JPEG is flagged when its estimated quality is below `min_quality` (75 by
+/// default) or its 8x8 block grid shows. Returns only the flagged files, worst first.
+pub fn analyze_directory(
+    job: &Job,
+    directory: &Path,
+    min_quality: Option<u8>,
+) -> Result<Vec<JpegQualityReport>, String> {
+    let min_quality = min_quality.unwrap_or(DEFAULT_MIN_QUALITY);
+    let images: Vec<_> = scan_media_files(directory)?
+        .into_iter()
+        .filter(|file| file.file_type == "image")
+        .collect();
+
+    let total = images.len() as u64;
+    let mut reports = Vec::new();
+    for (index, file) in images.into_iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(
+            index as u64,
+            total,
+            Some("Checking JPEG quality".to_string()),
+        );
+
+        let data = match fs::read(&file.path) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Failed to analyze {}: {}", file.path, e);
+                continue;
+            }
+        };
+        // Other formats have no quantization to audit
+        if !data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            continue;
+        }
+        let quality = luminance_table(&data).map(|table| estimate_quality(&table));
+
+        let luma = match image::load_from_memory(&data) {
+            Ok(image) => image.to_luma8(),
+            Err(e) => {
+                warn!("Failed to analyze {}: {}", file.path, e);
+                continue;
+            }
+        };
+        let blockiness = blockiness(&luma);
+        let low_quality = quality.is_some_and(|quality| quality < min_quality);
+        if !low_quality && blockiness <= MAX_BLOCKINESS {
+            continue;
+        }
+
+        let (width, height) = luma.dimensions();
+        reports.push(JpegQualityReport {
+            path: file.path,
+            relative_path: file.relative_path,
+            width,
+            height,
+            quality,
+            blockiness,
+        });
+    }
+
+    reports.sort_by(|a, b| {
+        a.quality
+            .unwrap_or(100)
+            .cmp(&b.quality.unwrap_or(100))
+            .then(b.blockiness.total_cmp(&a.blockiness))
+    });
+    job.progress(total, total, None);
+    Ok(reports)
+}
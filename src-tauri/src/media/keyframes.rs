@@ -0,0 +1,197 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::ffmpeg;
+use super::probe;
+use crate::captions::format::CaptionStore;
+use crate::jobs::manager::{self as jobs, Job};
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Frames per second looked at when comparing frames
+const SAMPLE_FPS: f64 = 2.0;
+
+/// Side of the grayscale thumbnails frames are compared by
+const COMPARE_SIZE: usize = 32;
+
+/// A sampled frame that differs enough from the previous one kept
+struct Candidate {
+    /// Position in the sampled frames
+    index: usize,
+    /// Difference from the previous frame kept; the first frame counts as fully
+    /// different
+    difference: f64,
+}
+
+/// Mean absolute difference of two grayscale thumbnails, from 0 (identical) to 1
+fn difference(a: &[u8], b: &[u8]) -> f64 {
+    let total: u64 = a
+        .iter()
+        .zip(b)
+        .map(|(&x, &y)| u64::from(x.abs_diff(y)))
+        .sum();
+    total as f64 / (a.len().max(1) as f64 * 255.0)
+}
+
+/// Walk the frames of a video and collect those differing from the previous frame
+/// kept by at least `min_difference`
+fn scan(job: &Job, video: &Path, min_difference: f64) -> Result<Vec<Candidate>, String> {
+    let expected = probe::probe_media(video, "video")
+        .duration
+        .map(|duration| (duration * SAMPLE_FPS).ceil() as u64)
+        .unwrap_or(0);
+
+    let mut child = Command::new("ffmpeg")
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(video)
+        .arg("-vf")
+        .arg(format!(
+            "fps={},scale={size}:{size},format=gray",
+            SAMPLE_FPS,
+            size = COMPARE_SIZE
+        ))
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("-")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    let Some(mut stdout) = child.stdout.take() else {
+        let _ = child.kill();
+        return Err("Failed to read ffmpeg output".to_string());
+    };
+
+    let mut candidates = Vec::new();
+    let mut last_kept: Option<Vec<u8>> = None;
+    let mut frame = vec![0u8; COMPARE_SIZE * COMPARE_SIZE];
+    let mut index = 0;
+    while stdout.read_exact(&mut frame).is_ok() {
+        if job.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(jobs::CANCELLED.to_string());
+        }
+        job.progress(
+            index as u64,
+            expected.max(index as u64 + 1),
+            Some("Comparing frames".to_string()),
+        );
+
+        let change = last_kept
+            .as_deref()
+            .map(|last| difference(last, &frame))
+            .unwrap_or(1.0);
+        if change >= min_difference {
+            candidates.push(Candidate {
+                index,
+                difference: change,
+            });
+            last_kept = Some(frame.clone());
+        }
+        index += 1;
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !status.success() && candidates.is_empty() {
+        return Err(format!("FFmpeg failed to decode {}", video.display()));
+    }
+    Ok(candidates)
+}
+
+/// Write one frame of a video to an image
+fn write_frame(video: &Path, time: f64, destination: &Path) -> Result<(), String> {
+    let output = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-ss")
+        .arg(format!("{:.3}", time))
+        .arg("-i")
+        .arg(video)
+        .arg("-frames:v")
+        .arg("1")
+        .arg(destination)
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() || !destination.exists() {
+        return Err(format!(
+            "Failed to extract frame at {:.1}s: {}",
+            time,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+/// Extract the frames of a video that differ from each other, turning it into images
+/// for an image dataset
+///
+/// Frames are compared a few times a second by the mean difference of small grayscale
+/// versions; one is kept when it differs from the last kept frame by at least
+/// `min_difference` (0 to 1). With more than `max_frames` candidates, the most
+/// different ones are kept. The frames are written as PNGs next to the video, named
+/// `<video>_keyframe_<n>.png`, each with a copy of the video's caption (empty if it
+/// has none). The extraction is one undoable operation. Returns the written images.
+pub fn extract_keyframes(
+    job: &Job,
+    video: &Path,
+    max_frames: usize,
+    min_difference: f64,
+) -> Result<Vec<PathBuf>, String> {
+    ffmpeg::require("key frame extraction")?;
+    let min_difference = min_difference.clamp(0.0, 1.0);
+
+    let mut candidates = scan(job, video, min_difference)?;
+    if candidates.len() > max_frames {
+        candidates.sort_by(|a, b| b.difference.total_cmp(&a.difference));
+        candidates.truncate(max_frames);
+        candidates.sort_by_key(|candidate| candidate.index);
+    }
+
+    let stem = video
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "video".to_string());
+    let directory = video.parent().unwrap_or(Path::new("."));
+
+    let mut store = CaptionStore::for_media(video);
+    let caption = store.read(video)?.unwrap_or_default();
+    let mut operation = Operation::begin(
+        video,
+        OperationKind::MediaEdit,
+        format!("Extract key frames of {}", stem),
+    )?;
+
+    let total = candidates.len() as u64;
+    let mut written = Vec::new();
+    let mut write_all = || -> Result<(), String> {
+        for (number, candidate) in candidates.iter().enumerate() {
+            job.check_cancelled()?;
+            job.progress(
+                number as u64,
+                total,
+                Some(format!("Writing frame {} of {}", number + 1, total)),
+            );
+
+            let image = directory.join(format!("{}_keyframe_{:03}.png", stem, number + 1));
+            operation.snapshot(&image)?;
+            write_frame(video, candidate.index as f64 / SAMPLE_FPS, &image)?;
+
+            operation.snapshot(&store.caption_path(&image))?;
+            store.write(&image, &caption)?;
+            written.push(image);
+        }
+        Ok(())
+    };
+    let result = write_all();
+
+    // Frames written before a failure or cancellation stay undoable
+    operation.commit()?;
+    result?;
+
+    job.progress(total, total, None);
+    Ok(written)
+}
@@ -0,0 +1,955 @@
+use chrono::NaiveDateTime;
+use img_parts::jpeg::{markers, Jpeg, JpegSegment};
+use img_parts::png::{Png, PngChunk};
+use img_parts::riff::{RiffChunk, RiffContent};
+use img_parts::webp::{WebP, CHUNK_EXIF, CHUNK_VP8, CHUNK_VP8L, CHUNK_VP8X, CHUNK_XMP};
+use img_parts::Bytes;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use super::raw;
+use crate::captions::batch::CaptionChange;
+use crate::captions::format::CaptionStore;
+use crate::file_system::atomic;
+use crate::file_system::commands::scan_media_files;
+use crate::jobs::manager::Job;
+use crate::undo::journal::Operation;
+
+/// Formats captions can be embedded in (lowercase extensions)
+const SUPPORTED_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// Starts the XMP packet in a JPEG APP1 segment
+const XMP_JPEG_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Starts the Photoshop image resources, which hold IPTC data, in a JPEG APP13 segment
+const PHOTOSHOP_PREFIX: &[u8] = b"Photoshop 3.0\0";
+
+/// Largest payload of a JPEG segment
+const JPEG_SEGMENT_MAX: usize = 65533;
+
+/// Photoshop resources holding the IPTC data and a digest of it
+const RESOURCE_IPTC: u16 = 0x0404;
+const RESOURCE_IPTC_DIGEST: u16 = 0x0425;
+
+/// IPTC Caption-Abstract (record 2, dataset 120), limited to 2000 bytes
+const IPTC_CAPTION: (u8, u8) = (2, 120);
+const IPTC_CAPTION_MAX: usize = 2000;
+
+/// IPTC Keywords (record 2, dataset 25), repeated once per keyword
+const IPTC_KEYWORDS: (u8, u8) = (2, 25);
+
+/// IPTC CodedCharacterSet (record 1, dataset 90), with the value declaring UTF-8
+const IPTC_CHARSET: (u8, u8) = (1, 90);
+const IPTC_UTF8: &[u8] = b"\x1b%G";
+
+/// PNG text chunk holding the XMP packet, identified by its keyword
+const PNG_ITXT: [u8; 4] = *b"iTXt";
+const PNG_TEXT: [u8; 4] = *b"tEXt";
+const PNG_EXIF: [u8; 4] = *b"eXIf";
+const PNG_IDAT: [u8; 4] = *b"IDAT";
+const XMP_PNG_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+const PNG_DESCRIPTION_KEYWORD: &[u8] = b"Description";
+
+/// Starts the EXIF data in a JPEG APP1 segment or, optionally, a WebP EXIF chunk
+const EXIF_PREFIX: &[u8] = b"Exif\0\0";
+
+/// EXIF ImageDescription tag and its ASCII type
+const EXIF_IMAGE_DESCRIPTION: u16 = 0x010e;
+const EXIF_ASCII: u16 = 2;
+
+/// EXIF tags of when a photo was last changed and when it was taken, the latter in the
+/// EXIF IFD the pointer tag leads to
+const EXIF_DATE_TIME: u16 = 0x0132;
+const EXIF_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const EXIF_IFD_POINTER: u16 = 0x8769;
+
+/// Format of EXIF dates, e.g. `2024:05:01 14:03:22`
+const EXIF_DATE_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+
+/// Descriptions cameras fill in by default, which say nothing about the image
+const CAMERA_PLACEHOLDERS: &[&str] = &[
+    "OLYMPUS DIGITAL CAMERA",
+    "SONY DSC",
+    "DIGITAL CAMERA",
+    "KODAK Digital Still Camera",
+    "SAMSUNG CAMERA PICTURES",
+    "Default",
+];
+
+/// VP8X flags announcing XMP metadata and transparency
+const WEBP_XMP_FLAG: u8 = 0x04;
+const WEBP_ALPHA_FLAG: u8 = 0x10;
+
+static DESCRIPTION_ELEMENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<dc:description\b[^>]*?(?:/>|>.*?</dc:description>)").unwrap());
+static DESCRIPTION_ATTRIBUTE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"\sdc:description\s*=\s*("[^"]*"|'[^']*')"#).unwrap());
+static DESCRIPTION_OPEN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<rdf:Description\b[^>]*>").unwrap());
+static SUBJECT_ELEMENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<dc:subject\b[^>]*>(.*?)</dc:subject>").unwrap());
+static TITLE_ELEMENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<dc:title\b[^>]*>(.*?)</dc:title>").unwrap());
+static CREATOR_ELEMENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<dc:creator\b[^>]*>(.*?)</dc:creator>").unwrap());
+static RIGHTS_ELEMENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<dc:rights\b[^>]*>(.*?)</dc:rights>").unwrap());
+static LIST_ITEM: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<rdf:li\b([^>]*)>(.*?)</rdf:li>").unwrap());
+static XML_ENTITY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"&(#x[0-9a-fA-F]+|#[0-9]+|amp|lt|gt|quot|apos);").unwrap());
+
+/// Result of embedding the captions of a project in its images
+#[derive(Debug, Default, Serialize)]
+pub struct EmbedSummary {
+    pub embedded: usize,
+    /// Videos, images in other formats and files without a caption
+    pub skipped: usize,
+    /// Files that couldn't be rewritten, with the reason
+    pub failed: Vec<(String, String)>,
+}
+
+/// Check whether a caption can be embedded in a file, judging by its extension
+pub fn supports(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| SUPPORTED_EXTENSIONS.contains(&ext.as_str()))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// An XMP packet holding only a description
+fn new_packet(description: &str) -> String {
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+            " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+            "  <rdf:Description rdf:about=\"\" ",
+            "xmlns:dc=\"http://purl.org/dc/elements/1.1/\">{}</rdf:Description>\n",
+            " </rdf:RDF>\n",
+            "</x:xmpmeta>\n",
+            "<?xpacket end=\"w\"?>"
+        ),
+        description
+    )
+}
+
+/// Set `dc:description` in an XMP packet, keeping everything else in it
+///
+/// Any previous description is replaced; without an existing packet to update, a new
+/// one is created.
+fn xmp_with_description(existing: Option<&str>, caption: &str) -> String {
+    let description = format!(
+        "<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>",
+        escape_xml(caption)
+    );
+    let Some(existing) = existing else {
+        return new_packet(&description);
+    };
+
+    let xmp = DESCRIPTION_ELEMENT.replace_all(existing, "");
+    let xmp = DESCRIPTION_ATTRIBUTE.replace_all(&xmp, "");
+    let Some(open) = DESCRIPTION_OPEN.find(&xmp) else {
+        return new_packet(&description);
+    };
+
+    let self_closing = open.as_str().ends_with("/>");
+    let mut tag = open
+        .as_str()
+        .trim_end_matches('>')
+        .trim_end_matches('/')
+        .to_string();
+    if !tag.contains("xmlns:dc=") {
+        tag.push_str(" xmlns:dc=\"http://purl.org/dc/elements/1.1/\"");
+    }
+    tag.push('>');
+
+    let mut updated = String::with_capacity(xmp.len() + description.len() + 64);
+    updated.push_str(&xmp[..open.start()]);
+    updated.push_str(&tag);
+    updated.push_str(&description);
+    if self_closing {
+        updated.push_str("</rdf:Description>");
+    }
+    updated.push_str(&xmp[open.end()..]);
+    updated
+}
+
+/// Cut a caption to at most `max` bytes without splitting a character
+fn truncate(text: &str, max: usize) -> &str {
+    if text.len() <= max {
+        return text;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// IPTC datasets as (record, dataset, value)
+type Datasets = Vec<(u8, u8, Vec<u8>)>;
+
+fn parse_iptc(data: &[u8]) -> Datasets {
+    let mut datasets = Vec::new();
+    let mut pos = 0;
+    while pos + 5 <= data.len() && data[pos] == 0x1c {
+        let (record, dataset) = (data[pos + 1], data[pos + 2]);
+        let size = u16::from_be_bytes([data[pos + 3], data[pos + 4]]) as usize;
+        // Extended sizes (high bit set) only occur for binary data, never for text
+        if size & 0x8000 != 0 || pos + 5 + size > data.len() {
+            break;
+        }
+        datasets.push((record, dataset, data[pos + 5..pos + 5 + size].to_vec()));
+        pos += 5 + size;
+    }
+    datasets
+}
+
+/// IPTC data with the Caption-Abstract replaced, declared as UTF-8
+fn iptc_with_caption(existing: &[u8], caption: &str) -> Vec<u8> {
+    let mut datasets: Datasets = parse_iptc(existing)
+        .into_iter()
+        .filter(|(record, dataset, _)| (*record, *dataset) != IPTC_CAPTION)
+        .collect();
+    if !datasets
+        .iter()
+        .any(|(record, dataset, _)| (*record, *dataset) == IPTC_CHARSET)
+    {
+        datasets.push((IPTC_CHARSET.0, IPTC_CHARSET.1, IPTC_UTF8.to_vec()));
+    }
+    let caption = truncate(caption, IPTC_CAPTION_MAX).as_bytes().to_vec();
+    datasets.push((IPTC_CAPTION.0, IPTC_CAPTION.1, caption));
+
+    // Datasets have to be in record order
+    datasets.sort_by_key(|(record, _, _)| *record);
+
+    let mut data = Vec::new();
+    for (record, dataset, value) in datasets {
+        data.extend_from_slice(&[0x1c, record, dataset]);
+        data.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        data.extend_from_slice(&value);
+    }
+    data
+}
+
+/// Photoshop image resources as (id, name, data)
+type Resources = Vec<(u16, Vec<u8>, Vec<u8>)>;
+
+fn parse_resources(data: &[u8]) -> Resources {
+    let mut resources = Vec::new();
+    let mut pos = 0;
+    while pos + 7 <= data.len() && &data[pos..pos + 4] == b"8BIM" {
+        let id = u16::from_be_bytes([data[pos + 4], data[pos + 5]]);
+        // Pascal string, padded to an even length
+        let name_len = data[pos + 6] as usize;
+        let name_end = pos + 6 + ((name_len + 2) & !1);
+        if name_end + 4 > data.len() {
+            break;
+        }
+        let name = data[pos + 6..name_end].to_vec();
+        let size = u32::from_be_bytes([
+            data[name_end],
+            data[name_end + 1],
+            data[name_end + 2],
+            data[name_end + 3],
+        ]) as usize;
+        let start = name_end + 4;
+        if start + size > data.len() {
+            break;
+        }
+        resources.push((id, name, data[start..start + size].to_vec()));
+        pos = start + size + (size & 1);
+    }
+    resources
+}
+
+/// Photoshop resources with the IPTC caption set; the IPTC digest is dropped since it
+/// no longer matches
+fn resources_with_caption(existing: &[u8], caption: &str) -> Vec<u8> {
+    let mut resources = parse_resources(existing);
+    resources.retain(|(id, _, _)| *id != RESOURCE_IPTC_DIGEST);
+
+    let iptc = match resources.iter_mut().find(|(id, _, _)| *id == RESOURCE_IPTC) {
+        Some(resource) => &mut resource.2,
+        None => {
+            resources.push((RESOURCE_IPTC, vec![0, 0], Vec::new()));
+            &mut resources.last_mut().unwrap().2
+        }
+    };
+    *iptc = iptc_with_caption(iptc, caption);
+
+    let mut data = Vec::new();
+    for (id, name, value) in resources {
+        data.extend_from_slice(b"8BIM");
+        data.extend_from_slice(&id.to_be_bytes());
+        data.extend_from_slice(&name);
+        data.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        data.extend_from_slice(&value);
+        if value.len() % 2 == 1 {
+            data.push(0);
+        }
+    }
+    data
+}
+
+/// Put a segment in place of the one at `existing`, or after the leading APPn segments
+fn set_jpeg_segment(
+    jpeg: &mut Jpeg,
+    existing: Option<usize>,
+    marker: u8,
+    prefix: &[u8],
+    payload: &[u8],
+) -> Result<(), String> {
+    if prefix.len() + payload.len() > JPEG_SEGMENT_MAX {
+        return Err("The caption is too long to embed in a JPEG".to_string());
+    }
+    let mut contents = prefix.to_vec();
+    contents.extend_from_slice(payload);
+    let segment = JpegSegment::new_with_contents(marker, Bytes::from(contents));
+
+    match existing {
+        Some(index) => jpeg.segments_mut()[index] = segment,
+        None => {
+            let index = jpeg
+                .segments()
+                .iter()
+                .take_while(|s| (markers::APP0..=markers::APP15).contains(&s.marker()))
+                .count();
+            jpeg.segments_mut().insert(index, segment);
+        }
+    }
+    Ok(())
+}
+
+fn find_jpeg_segment(jpeg: &Jpeg, marker: u8, prefix: &[u8]) -> Option<usize> {
+    jpeg.segments()
+        .iter()
+        .position(|s| s.marker() == marker && s.contents().starts_with(prefix))
+}
+
+/// The contents of the first JPEG segment with the given marker and prefix, without it
+fn jpeg_segment(jpeg: &Jpeg, marker: u8, prefix: &[u8]) -> Option<Bytes> {
+    find_jpeg_segment(jpeg, marker, prefix)
+        .map(|index| jpeg.segments()[index].contents().slice(prefix.len()..))
+}
+
+fn jpeg_xmp(jpeg: &Jpeg) -> Option<String> {
+    jpeg_segment(jpeg, markers::APP1, XMP_JPEG_PREFIX)
+        .map(|xmp| String::from_utf8_lossy(&xmp).to_string())
+}
+
+/// Set the XMP description and the IPTC Caption-Abstract of a JPEG
+fn embed_jpeg(data: Bytes, caption: &str) -> Result<Bytes, String> {
+    let mut jpeg = Jpeg::from_bytes(data).map_err(|e| format!("Invalid JPEG: {}", e))?;
+
+    let xmp_index = find_jpeg_segment(&jpeg, markers::APP1, XMP_JPEG_PREFIX);
+    let xmp = xmp_with_description(jpeg_xmp(&jpeg).as_deref(), caption);
+    set_jpeg_segment(
+        &mut jpeg,
+        xmp_index,
+        markers::APP1,
+        XMP_JPEG_PREFIX,
+        xmp.as_bytes(),
+    )?;
+
+    let iptc_index = find_jpeg_segment(&jpeg, markers::APP13, PHOTOSHOP_PREFIX);
+    let existing = iptc_index
+        .map(|index| jpeg.segments()[index].contents()[PHOTOSHOP_PREFIX.len()..].to_vec())
+        .unwrap_or_default();
+    let resources = resources_with_caption(&existing, caption);
+    set_jpeg_segment(
+        &mut jpeg,
+        iptc_index,
+        markers::APP13,
+        PHOTOSHOP_PREFIX,
+        &resources,
+    )?;
+
+    Ok(jpeg.encoder().bytes())
+}
+
+/// The text of an uncompressed PNG iTXt chunk with the given keyword
+fn png_itxt_text(chunk: &PngChunk, keyword: &[u8]) -> Option<String> {
+    let contents = chunk.contents();
+    let rest = contents.strip_prefix(keyword)?.strip_prefix(b"\0")?;
+    // Compression flag and method, then the language tag and translated keyword
+    let (&compressed, rest) = rest.split_first()?;
+    if compressed != 0 {
+        return None;
+    }
+    let rest = &rest[1..];
+    let language_end = rest.iter().position(|&b| b == 0)?;
+    let rest = &rest[language_end + 1..];
+    let translated_end = rest.iter().position(|&b| b == 0)?;
+    Some(String::from_utf8_lossy(&rest[translated_end + 1..]).to_string())
+}
+
+/// The text of the first PNG text chunk (iTXt or tEXt) with the given keyword
+fn png_text(png: &Png, keyword: &[u8]) -> Option<String> {
+    png.chunks().iter().find_map(|chunk| match chunk.kind() {
+        PNG_ITXT => png_itxt_text(chunk, keyword),
+        PNG_TEXT => chunk
+            .contents()
+            .strip_prefix(keyword)?
+            .strip_prefix(b"\0")
+            .map(latin1),
+        _ => None,
+    })
+}
+
+/// Set the XMP description of a PNG, stored in an iTXt chunk before the image data
+fn embed_png(data: Bytes, caption: &str) -> Result<Bytes, String> {
+    let mut png = Png::from_bytes(data).map_err(|e| format!("Invalid PNG: {}", e))?;
+
+    let xmp = xmp_with_description(png_text(&png, XMP_PNG_KEYWORD).as_deref(), caption);
+
+    let mut contents = XMP_PNG_KEYWORD.to_vec();
+    // Keyword terminator, no compression, empty language tag and translated keyword
+    contents.extend_from_slice(&[0, 0, 0, 0, 0]);
+    contents.extend_from_slice(xmp.as_bytes());
+    let chunk = PngChunk::new(PNG_ITXT, Bytes::from(contents));
+
+    png.chunks_mut()
+        .retain(|chunk| chunk.kind() != PNG_ITXT || !chunk.contents().starts_with(XMP_PNG_KEYWORD));
+    let index = png
+        .chunks()
+        .iter()
+        .position(|chunk| chunk.kind() == PNG_IDAT)
+        .ok_or_else(|| "Invalid PNG: no image data".to_string())?;
+    png.chunks_mut().insert(index, chunk);
+
+    Ok(png.encoder().bytes())
+}
+
+/// The data of a WebP chunk as text
+fn webp_chunk(webp: &WebP, id: [u8; 4]) -> Option<String> {
+    webp.chunk_by_id(id)
+        .and_then(|chunk| chunk.content().data())
+        .map(|data| String::from_utf8_lossy(data).to_string())
+}
+
+/// The VP8X chunk a simple (lossy or lossless) WebP needs before it can hold metadata
+fn vp8x_chunk(webp: &WebP) -> Result<RiffChunk, String> {
+    let (width, height) = webp
+        .dimensions()
+        .ok_or_else(|| "Invalid WebP: unknown dimensions".to_string())?;
+
+    // Lossless images say in their header whether they use transparency
+    let alpha = webp
+        .chunk_by_id(CHUNK_VP8L)
+        .and_then(|chunk| chunk.content().data().cloned())
+        .and_then(|data| data.get(1..5).map(|b| [b[0], b[1], b[2], b[3]]))
+        .map(|header| u32::from_le_bytes(header) >> 28 & 1 == 1)
+        .unwrap_or(false);
+
+    let mut content = vec![if alpha { WEBP_ALPHA_FLAG } else { 0 }, 0, 0, 0];
+    content.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    content.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+    Ok(RiffChunk::new(
+        CHUNK_VP8X,
+        RiffContent::Data(Bytes::from(content)),
+    ))
+}
+
+/// Set the XMP description of a WebP, converting it to the extended format if needed
+fn embed_webp(data: Bytes, caption: &str) -> Result<Bytes, String> {
+    let mut webp = WebP::from_bytes(data).map_err(|e| format!("Invalid WebP: {}", e))?;
+
+    let xmp = xmp_with_description(webp_chunk(&webp, CHUNK_XMP).as_deref(), caption);
+
+    if !webp.has_chunk(CHUNK_VP8X) {
+        if !webp.has_chunk(CHUNK_VP8) && !webp.has_chunk(CHUNK_VP8L) {
+            return Err("Invalid WebP: no image data".to_string());
+        }
+        let chunk = vp8x_chunk(&webp)?;
+        webp.chunks_mut().insert(0, chunk);
+    }
+
+    // Set the XMP flag of the VP8X chunk
+    let vp8x = webp
+        .chunks_mut()
+        .iter_mut()
+        .find(|chunk| chunk.id() == CHUNK_VP8X)
+        .ok_or_else(|| "Invalid WebP: no VP8X chunk".to_string())?;
+    if let RiffContent::Data(data) = vp8x.content_mut() {
+        let mut flags = data.to_vec();
+        if let Some(first) = flags.first_mut() {
+            *first |= WEBP_XMP_FLAG;
+        }
+        *data = Bytes::from(flags);
+    }
+
+    webp.remove_chunks_by_id(CHUNK_XMP);
+    webp.chunks_mut().push(RiffChunk::new(
+        CHUNK_XMP,
+        RiffContent::Data(Bytes::from(xmp.into_bytes())),
+    ));
+
+    Ok(webp.encoder().bytes())
+}
+
+/// Write a caption into an image's own metadata: XMP `dc:description` for JPEG, PNG and
+/// WebP, plus IPTC Caption-Abstract for JPEG
+///
+/// Other metadata and the pixel data are kept as they are; the file is replaced
+/// atomically.
+pub fn embed_caption(path: &Path, caption: &str) -> Result<(), String> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let data = Bytes::from(
+        fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+    );
+    let caption = caption.trim();
+
+    let updated = match extension.as_str() {
+        "jpg" | "jpeg" => embed_jpeg(data, caption)?,
+        "png" => embed_png(data, caption)?,
+        "webp" => embed_webp(data, caption)?,
+        _ => return Err(format!("Can't embed captions in .{} files", extension)),
+    };
+
+    atomic::write_atomic(path, &updated, false)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Embed the caption of every supported image of a directory in the image itself
+///
+/// Failures are collected rather than stopping the rest; the job's progress advances
+/// by one per file. With an `operation` each image is snapshotted before it is
+/// rewritten, so the embedding can be undone.
+pub fn embed_directory(
+    job: &Job,
+    directory: &Path,
+    mut operation: Option<&mut Operation>,
+) -> Result<EmbedSummary, String> {
+    let files = scan_media_files(directory)?;
+    let mut store = CaptionStore::open(directory);
+    let mut summary = EmbedSummary::default();
+
+    job.progress(
+        0,
+        files.len() as u64,
+        Some("Embedding captions".to_string()),
+    );
+    for file in files {
+        job.check_cancelled()?;
+        let path = Path::new(&file.path);
+
+        let caption = match store.read(path) {
+            Ok(Some(caption)) if supports(path) && !caption.trim().is_empty() => caption,
+            Ok(_) => {
+                summary.skipped += 1;
+                job.advance(1);
+                continue;
+            }
+            Err(e) => {
+                summary.failed.push((file.relative_path, e));
+                job.advance(1);
+                continue;
+            }
+        };
+
+        if let Some(operation) = operation.as_deref_mut() {
+            operation.snapshot(path)?;
+        }
+        match embed_caption(path, &caption) {
+            Ok(()) => summary.embedded += 1,
+            Err(e) => summary.failed.push((file.relative_path, e)),
+        }
+        job.advance(1);
+    }
+
+    Ok(summary)
+}
+
+/// Description and keywords found in an image's own metadata
+#[derive(Debug, Default, Serialize)]
+pub struct EmbeddedText {
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+}
+
+impl EmbeddedText {
+    /// The caption to seed from this text: the description, optionally followed by the
+    /// keywords as comma-separated tags
+    pub fn caption(&self, include_keywords: bool) -> Option<String> {
+        let mut parts: Vec<&str> = self.description.iter().map(String::as_str).collect();
+        if include_keywords {
+            parts.extend(self.keywords.iter().map(String::as_str));
+        }
+        let caption = parts.join(", ");
+        (!caption.is_empty()).then_some(caption)
+    }
+}
+
+fn unescape_xml(text: &str) -> String {
+    XML_ENTITY
+        .replace_all(text, |caps: &regex::Captures| {
+            let entity = &caps[1];
+            let code = match entity {
+                "amp" => Some('&' as u32),
+                "lt" => Some('<' as u32),
+                "gt" => Some('>' as u32),
+                "quot" => Some('"' as u32),
+                "apos" => Some('\'' as u32),
+                _ => match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity[1..].parse().ok(),
+                },
+            };
+            code.and_then(char::from_u32)
+                .map(String::from)
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .to_string()
+}
+
+/// Text of unknown encoding: UTF-8 if it's valid, otherwise Latin-1
+fn latin1(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Keep a description unless it's empty or a camera's placeholder
+fn meaningful(text: String) -> Option<String> {
+    let text = text.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    let placeholder = CAMERA_PLACEHOLDERS
+        .iter()
+        .any(|p| p.eq_ignore_ascii_case(text));
+    (!text.is_empty() && !placeholder).then(|| text.to_string())
+}
+
+/// `dc:description` of an XMP packet, preferring the default language
+fn xmp_description(xmp: &str) -> Option<String> {
+    if let Some(element) = DESCRIPTION_ELEMENT.find(xmp) {
+        let items: Vec<_> = LIST_ITEM.captures_iter(element.as_str()).collect();
+        let item = items
+            .iter()
+            .find(|caps| caps[1].contains("x-default"))
+            .or(items.first())?;
+        return meaningful(unescape_xml(&item[2]));
+    }
+
+    let value = DESCRIPTION_ATTRIBUTE.captures(xmp)?;
+    let quoted = value.get(1)?.as_str();
+    meaningful(unescape_xml(&quoted[1..quoted.len() - 1]))
+}
+
+/// `dc:subject` keywords of an XMP packet
+fn xmp_keywords(xmp: &str) -> Vec<String> {
+    SUBJECT_ELEMENT
+        .captures(xmp)
+        .map(|subject| {
+            LIST_ITEM
+                .captures_iter(&subject[1])
+                .filter_map(|item| meaningful(unescape_xml(&item[2])))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// First item of an XMP list element, preferring the default language of alternatives
+fn xmp_item(element: &Regex, xmp: &str) -> Option<String> {
+    let list = element.captures(xmp)?;
+    let items: Vec<_> = LIST_ITEM.captures_iter(&list[1]).collect();
+    let item = items
+        .iter()
+        .find(|caps| caps[1].contains("x-default"))
+        .or(items.first())?;
+    meaningful(unescape_xml(&item[2]))
+}
+
+/// An ASCII tag of EXIF (TIFF) data, from its first IFD or else from the EXIF IFD
+fn exif_ascii(tiff: &[u8], tag: u16) -> Option<String> {
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |pos: usize| {
+        let b = tiff.get(pos..pos + 2)?;
+        Some(if big_endian {
+            u16::from_be_bytes([b[0], b[1]])
+        } else {
+            u16::from_le_bytes([b[0], b[1]])
+        })
+    };
+    let u32_at = |pos: usize| {
+        let b = tiff.get(pos..pos + 4)?;
+        Some(if big_endian {
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        })
+    };
+
+    // The offset of the entry of a tag in an IFD
+    let find = |ifd: usize, wanted: u16| {
+        (0..u16_at(ifd)? as usize)
+            .map(|i| ifd + 2 + i * 12)
+            .find(|&entry| u16_at(entry) == Some(wanted))
+    };
+
+    let first = u32_at(4)? as usize;
+    let entry = find(first, tag).or_else(|| {
+        let exif_ifd = u32_at(find(first, EXIF_IFD_POINTER)? + 8)? as usize;
+        find(exif_ifd, tag)
+    })?;
+    if u16_at(entry + 2)? != EXIF_ASCII {
+        return None;
+    }
+    // Values of up to four bytes are stored in the entry itself
+    let len = u32_at(entry + 4)? as usize;
+    let start = if len <= 4 {
+        entry + 8
+    } else {
+        u32_at(entry + 8)? as usize
+    };
+    Some(latin1(tiff.get(start..start.checked_add(len)?)?))
+}
+
+/// ImageDescription from the first IFD of EXIF (TIFF) data
+fn exif_description(tiff: &[u8]) -> Option<String> {
+    exif_ascii(tiff, EXIF_IMAGE_DESCRIPTION).and_then(meaningful)
+}
+
+/// Caption and keywords from the IPTC data in a JPEG's Photoshop resources
+fn iptc_text(resources: &[u8]) -> EmbeddedText {
+    let mut text = EmbeddedText::default();
+    let Some((_, _, iptc)) = parse_resources(resources)
+        .into_iter()
+        .find(|(id, _, _)| *id == RESOURCE_IPTC)
+    else {
+        return text;
+    };
+
+    for (record, dataset, value) in parse_iptc(&iptc) {
+        match (record, dataset) {
+            IPTC_CAPTION => text.description = meaningful(latin1(&value)),
+            IPTC_KEYWORDS => text.keywords.extend(meaningful(latin1(&value))),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Read the description and keywords embedded in an image
+///
+/// XMP is preferred, then IPTC (JPEG only), then the EXIF ImageDescription or, for PNG,
+/// the `Description` text chunk. Formats that can't hold them give an empty result.
+pub fn read_embedded(path: &Path) -> Result<EmbeddedText, String> {
+    if !supports(path) {
+        return Ok(EmbeddedText::default());
+    }
+    let data = Bytes::from(
+        fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+    );
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    // XMP, IPTC and EXIF text, in order of preference
+    let (xmp, iptc, fallback) = match extension.as_str() {
+        "png" => {
+            let png = Png::from_bytes(data).map_err(|e| format!("Invalid PNG: {}", e))?;
+            let exif = png
+                .chunks()
+                .iter()
+                .find(|chunk| chunk.kind() == PNG_EXIF)
+                .and_then(|chunk| exif_description(chunk.contents()));
+            let text =
+                exif.or_else(|| png_text(&png, PNG_DESCRIPTION_KEYWORD).and_then(meaningful));
+            (
+                png_text(&png, XMP_PNG_KEYWORD),
+                EmbeddedText::default(),
+                text,
+            )
+        }
+        "webp" => {
+            let webp = WebP::from_bytes(data).map_err(|e| format!("Invalid WebP: {}", e))?;
+            let exif = webp
+                .chunk_by_id(CHUNK_EXIF)
+                .and_then(|chunk| chunk.content().data())
+                .and_then(|data| exif_description(data.strip_prefix(EXIF_PREFIX).unwrap_or(data)));
+            (webp_chunk(&webp, CHUNK_XMP), EmbeddedText::default(), exif)
+        }
+        _ => {
+            let jpeg = Jpeg::from_bytes(data).map_err(|e| format!("Invalid JPEG: {}", e))?;
+            let iptc = jpeg_segment(&jpeg, markers::APP13, PHOTOSHOP_PREFIX)
+                .map(|resources| iptc_text(&resources))
+                .unwrap_or_default();
+            let exif = jpeg_segment(&jpeg, markers::APP1, EXIF_PREFIX)
+                .and_then(|tiff| exif_description(&tiff));
+            (jpeg_xmp(&jpeg), iptc, exif)
+        }
+    };
+
+    let description = xmp
+        .as_deref()
+        .and_then(xmp_description)
+        .or(iptc.description)
+        .or(fallback);
+    let mut keywords = xmp.as_deref().map(xmp_keywords).unwrap_or_default();
+    if keywords.is_empty() {
+        keywords = iptc.keywords;
+    }
+
+    Ok(EmbeddedText {
+        description,
+        keywords,
+    })
+}
+
+/// When a photo was taken, from its EXIF DateTimeOriginal or else DateTime
+///
+/// JPEG, PNG and WebP files carry EXIF in a segment or chunk; camera RAW files are
+/// TIFF, so EXIF themselves. Returns None for other files and files without a date.
+pub fn read_capture_time(path: &Path) -> Option<NaiveDateTime> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !supports(path) && !raw::is_raw(path) {
+        return None;
+    }
+    let data = Bytes::from(fs::read(path).ok()?);
+
+    let tiff = match extension.as_str() {
+        "png" => Png::from_bytes(data)
+            .ok()?
+            .chunks()
+            .iter()
+            .find(|chunk| chunk.kind() == PNG_EXIF)?
+            .contents()
+            .clone(),
+        "webp" => {
+            let data = WebP::from_bytes(data)
+                .ok()?
+                .chunk_by_id(CHUNK_EXIF)?
+                .content()
+                .data()?
+                .clone();
+            if data.starts_with(EXIF_PREFIX) {
+                data.slice(EXIF_PREFIX.len()..)
+            } else {
+                data
+            }
+        }
+        "jpg" | "jpeg" => jpeg_segment(&Jpeg::from_bytes(data).ok()?, markers::APP1, EXIF_PREFIX)?,
+        _ => data,
+    };
+
+    [EXIF_DATE_TIME_ORIGINAL, EXIF_DATE_TIME]
+        .into_iter()
+        .filter_map(|tag| exif_ascii(&tiff, tag))
+        .find_map(|date| {
+            NaiveDateTime::parse_from_str(date.trim_end_matches('\0').trim(), EXIF_DATE_FORMAT).ok()
+        })
+}
+
+/// Text of an XMP sidecar, as written next to exported images by Lightroom, Capture
+/// One and others
+#[derive(Debug, Default)]
+pub struct SidecarText {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub creator: Option<String>,
+    /// Copyright or license notice
+    pub rights: Option<String>,
+}
+
+impl SidecarText {
+    /// The description and keywords, with the title standing in for a missing
+    /// description
+    pub fn embedded(&self) -> EmbeddedText {
+        EmbeddedText {
+            description: self.description.clone().or_else(|| self.title.clone()),
+            keywords: self.keywords.clone(),
+        }
+    }
+}
+
+/// Read an XMP sidecar file
+pub fn read_xmp_sidecar(path: &Path) -> Result<SidecarText, String> {
+    let xmp = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let xmp = String::from_utf8_lossy(&xmp);
+
+    Ok(SidecarText {
+        title: xmp_item(&TITLE_ELEMENT, &xmp),
+        description: xmp_description(&xmp),
+        keywords: xmp_keywords(&xmp),
+        creator: xmp_item(&CREATOR_ELEMENT, &xmp),
+        rights: xmp_item(&RIGHTS_ELEMENT, &xmp),
+    })
+}
+
+/// Compute the captions to seed from the metadata embedded in a project's images
+///
+/// Files that already have a caption are left alone unless `overwrite` is set. Files
+/// whose metadata can't be read are skipped, since most libraries hold a few broken
+/// files; the job's progress advances by one per file.
+pub fn plan_seed(
+    job: &Job,
+    directory: &Path,
+    include_keywords: bool,
+    overwrite: bool,
+) -> Result<Vec<CaptionChange>, String> {
+    let files = scan_media_files(directory)?;
+    let mut store = CaptionStore::open(directory);
+    let mut changes = Vec::new();
+
+    job.progress(
+        0,
+        files.len() as u64,
+        Some("Reading embedded metadata".to_string()),
+    );
+    for file in files {
+        job.check_cancelled()?;
+        job.advance(1);
+        let path = Path::new(&file.path);
+        if !supports(path) {
+            continue;
+        }
+
+        let before = store.read(path)?.unwrap_or_default();
+        if !before.trim().is_empty() && !overwrite {
+            continue;
+        }
+
+        let after = match read_embedded(path) {
+            Ok(text) => text.caption(include_keywords),
+            Err(e) => {
+                warn!("Failed to read the metadata of {}: {}", file.path, e);
+                None
+            }
+        };
+        if let Some(after) = after.filter(|after| *after != before) {
+            changes.push(CaptionChange {
+                path: file.path,
+                name: file.name,
+                before,
+                after,
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(changes)
+}
@@ -1 +1,19 @@
+pub mod animation;
+pub mod borders;
+pub mod bursts;
+pub mod colors;
 pub mod commands;
+pub mod convert;
+pub mod crop;
+pub mod downscale;
+pub mod ffmpeg;
+pub mod formats;
+pub mod jpeg_quality;
+pub mod keyframes;
+pub mod metadata;
+pub mod pdf;
+pub mod probe;
+pub mod protocol;
+pub mod raw;
+pub mod tiles;
+pub mod upscale;
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::warn;
+
+use crate::file_system::atomic;
+use crate::jobs::manager::Job;
+use crate::project::{metadata_dir, METADATA_DIR};
+use crate::undo::journal::{Operation, OperationKind};
+
+/// File in the project metadata linking page images to the documents they came from
+const PAGES_FILE: &str = "pdf_pages.json";
+
+/// Resolution pages are rasterized at when none is given
+pub const DEFAULT_DPI: u32 = 150;
+
+/// Where a page image comes from
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PdfPage {
+    /// Relative path of the document
+    pub document: String,
+    /// Page number, starting at 1
+    pub page: u32,
+}
+
+/// Result of rasterizing the PDF documents of a project, with file names
+#[derive(Debug, Default, Serialize)]
+pub struct PdfConversion {
+    /// Documents whose pages became images
+    pub converted: Vec<String>,
+    /// Page images written
+    pub pages: Vec<String>,
+    /// Documents whose page images already exist
+    pub skipped: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case("pdf"))
+}
+
+/// The PDF documents of a folder and its subfolders, app data aside
+fn find_documents(dir: &Path, documents: &mut Vec<PathBuf>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        if path.is_dir() {
+            if entry.file_name() != METADATA_DIR {
+                find_documents(&path, documents)?;
+            }
+        } else if is_pdf(&path) {
+            documents.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The rasterizer of Poppler, or an error saying it is needed
+fn require_poppler() -> Result<(), String> {
+    let available = Command::new("pdftoppm")
+        .arg("-v")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if available {
+        Ok(())
+    } else {
+        Err(
+            "Poppler is not installed or not in PATH. Please install Poppler (pdftoppm and \
+             pdfinfo) to enable PDF import."
+                .to_string(),
+        )
+    }
+}
+
+/// Number of pages of a document, as reported by `pdfinfo`
+fn page_count(document: &Path) -> Result<u32, String> {
+    let output = Command::new("pdfinfo")
+        .arg(document)
+        .output()
+        .map_err(|e| format!("Failed to run pdfinfo: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to read document: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("Pages:"))
+        .and_then(|count| count.trim().parse().ok())
+        .ok_or_else(|| "Failed to read the page count of the document".to_string())
+}
+
+/// Image a page of a document is rasterized to: `scan.pdf` page 3 becomes
+/// `scan_p003.png` next to it
+fn page_path(document: &Path, page: u32) -> PathBuf {
+    let stem = document
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    document.with_file_name(format!("{}_p{:03}.png", stem, page))
+}
+
+/// Rasterize one page of a document to a PNG
+fn rasterize_page(document: &Path, page: u32, dpi: u32, target: &Path) -> Result<(), String> {
+    // pdftoppm adds the extension to the name it is given
+    let prefix = target.with_extension("");
+    let output = Command::new("pdftoppm")
+        .arg("-png")
+        .arg("-r")
+        .arg(dpi.to_string())
+        .arg("-f")
+        .arg(page.to_string())
+        .arg("-l")
+        .arg(page.to_string())
+        .arg("-singlefile")
+        .arg(document)
+        .arg(&prefix)
+        .output()
+        .map_err(|e| format!("Failed to run pdftoppm: {}", e))?;
+    if !output.status.success() || !target.exists() {
+        return Err(format!(
+            "Failed to rasterize page {}: {}",
+            page,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+fn relative(project: &Path, path: &Path) -> String {
+    path.strip_prefix(project)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// The documents and pages the page images of a project come from, by relative path
+pub fn load_pages(project: &Path) -> Result<BTreeMap<String, PdfPage>, String> {
+    let path = project.join(METADATA_DIR).join(PAGES_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read PDF pages: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse PDF pages: {}", e))
+}
+
+fn save_pages(project: &Path, pages: &BTreeMap<String, PdfPage>) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(pages)
+        .map_err(|e| format!("Failed to serialize PDF pages: {}", e))?;
+    atomic::write_atomic(&metadata_dir(project)?.join(PAGES_FILE), content, false)
+        .map_err(|e| format!("Failed to write PDF pages: {}", e))
+}
+
+/// Rasterize every page of the PDF documents in a project to a PNG next to the
+/// document, e.g. for artwork delivered as scans
+///
+/// Each page image is linked to its document and page number in the project metadata.
+/// Documents whose first page image already exists are skipped, and with
+/// `remove_originals` converted documents are deleted. The conversion is recorded as
+/// one undoable operation.
+pub fn convert_directory(
+    job: &Job,
+    project: &Path,
+    dpi: Option<u32>,
+    remove_originals: bool,
+) -> Result<PdfConversion, String> {
+    let mut documents = Vec::new();
+    find_documents(project, &mut documents)?;
+    documents.sort();
+
+    let mut report = PdfConversion::default();
+    if documents.is_empty() {
+        return Ok(report);
+    }
+    require_poppler()?;
+
+    let dpi = dpi.unwrap_or(DEFAULT_DPI).clamp(36, 600);
+    let mut links = load_pages(project)?;
+    let mut operation = Operation::begin(
+        project,
+        OperationKind::MediaEdit,
+        "Convert PDF pages to images",
+    )?;
+
+    job.progress(
+        0,
+        documents.len() as u64,
+        Some("Converting PDFs".to_string()),
+    );
+    for document in &documents {
+        if let Err(e) = job.check_cancelled() {
+            // Keep what was converted so far undoable and linked
+            save_pages(project, &links)?;
+            operation.commit()?;
+            return Err(e);
+        }
+
+        let name = relative(project, document);
+        if page_path(document, 1).exists() {
+            report.skipped.push(name);
+            job.advance(1);
+            continue;
+        }
+
+        let pages = match page_count(document) {
+            Ok(pages) => pages,
+            Err(e) => {
+                warn!("Failed to convert {}: {}", document.display(), e);
+                report.failed.push(name);
+                job.advance(1);
+                continue;
+            }
+        };
+
+        let mut written = Vec::new();
+        let mut failed = None;
+        for page in 1..=pages {
+            let target = page_path(document, page);
+            operation.snapshot(&target)?;
+            if let Err(e) = rasterize_page(document, page, dpi, &target) {
+                failed = Some(e);
+                break;
+            }
+            written.push((target, page));
+        }
+
+        if let Some(e) = failed {
+            warn!("Failed to convert {}: {}", document.display(), e);
+            for (target, _) in &written {
+                let _ = fs::remove_file(target);
+            }
+            report.failed.push(name);
+            job.advance(1);
+            continue;
+        }
+
+        for (target, page) in written {
+            let page_name = relative(project, &target);
+            links.insert(
+                page_name.clone(),
+                PdfPage {
+                    document: name.clone(),
+                    page,
+                },
+            );
+            report.pages.push(page_name);
+        }
+        if remove_originals {
+            operation.snapshot_and_remove(document)?;
+        }
+
+        report.converted.push(name);
+        job.advance(1);
+    }
+
+    save_pages(project, &links)?;
+    operation.commit()?;
+
+    Ok(report)
+}
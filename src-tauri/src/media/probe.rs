@@ -0,0 +1,116 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use super::animation;
+use super::ffmpeg;
+use super::formats;
+
+/// Dimensions and duration of a media file
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MediaInfo {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Duration in seconds (videos and animated images)
+    pub duration: Option<f64>,
+    /// Number of frames (animated images only)
+    pub frame_count: Option<u32>,
+}
+
+/// A probe result with the size and mtime of the file when it was probed
+type CacheEntry = (u64, Option<SystemTime>, MediaInfo);
+
+/// Probe results keyed by path, valid while the file's size and mtime are unchanged
+static PROBE_CACHE: Lazy<Mutex<HashMap<PathBuf, CacheEntry>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Read the dimensions of an image from its header, and the frames of animated GIFs
+/// and WebPs
+fn probe_image(path: &Path) -> MediaInfo {
+    let (width, height) = match formats::image_dimensions(path) {
+        Ok(dimensions) => dimensions,
+        Err(_) => return MediaInfo::default(),
+    };
+    let animation = animation::animation_info(path);
+
+    MediaInfo {
+        width: Some(width),
+        height: Some(height),
+        duration: animation.map(|a| a.duration),
+        frame_count: animation.map(|a| a.frame_count),
+    }
+}
+
+/// Read the dimensions and duration of a video with ffprobe
+fn probe_video(path: &Path) -> MediaInfo {
+    if !ffmpeg::capabilities().ffprobe {
+        return MediaInfo::default();
+    }
+
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("v:0")
+        .arg("-show_entries")
+        .arg("stream=width,height:format=duration")
+        .arg("-of")
+        .arg("json")
+        .arg(path)
+        .output();
+
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        _ => return MediaInfo::default(),
+    };
+
+    let value: serde_json::Value = match serde_json::from_slice(&output.stdout) {
+        Ok(value) => value,
+        Err(_) => return MediaInfo::default(),
+    };
+
+    let stream = &value["streams"][0];
+    MediaInfo {
+        width: stream["width"].as_u64().map(|w| w as u32),
+        height: stream["height"].as_u64().map(|h| h as u32),
+        // ffprobe reports the duration as a string
+        duration: value["format"]["duration"]
+            .as_str()
+            .and_then(|d| d.parse().ok()),
+        frame_count: None,
+    }
+}
+
+/// Get the dimensions (and duration for videos and animations) of a media file
+///
+/// Results are cached until the file changes, so listing a project again doesn't run
+/// ffprobe for every video.
+pub fn probe_media(path: &Path, file_type: &str) -> MediaInfo {
+    let metadata = fs::metadata(path).ok();
+    let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
+    let modified = metadata.and_then(|m| m.modified().ok());
+
+    if let Ok(cache) = PROBE_CACHE.lock() {
+        if let Some((cached_size, cached_modified, info)) = cache.get(path) {
+            if *cached_size == size && *cached_modified == modified {
+                return *info;
+            }
+        }
+    }
+
+    let info = match file_type {
+        "image" => probe_image(path),
+        "video" => probe_video(path),
+        _ => MediaInfo::default(),
+    };
+
+    if let Ok(mut cache) = PROBE_CACHE.lock() {
+        cache.insert(path.to_path_buf(), (size, modified, info));
+    }
+
+    info
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, UriSchemeResponder};
+
+use super::commands::{position_ms, thumbnail_bytes_at};
+use crate::file_system::commands::validate_media_file;
+use crate::file_system::paths;
+
+/// URI scheme thumbnails are served from, as
+/// `spacecat-thumb://localhost/<percent-encoded path>?size=<pixels>`, or
+/// `http://spacecat-thumb.localhost/...` on Windows; videos take a `time=<seconds>`
+/// parameter for a frame other than the first
+pub const SCHEME: &str = "spacecat-thumb";
+
+/// Thumbnail size when the URL doesn't give one, matching the grid
+const DEFAULT_SIZE: u32 = 100;
+
+/// Largest thumbnail served, so a bad URL can't make every image decode at full size
+const MAX_SIZE: u32 = 2048;
+
+/// Answer a thumbnail request without blocking the webview
+pub fn handle(app: AppHandle, request: Request<Vec<u8>>, responder: UriSchemeResponder) {
+    tauri::async_runtime::spawn(async move {
+        responder.respond(respond(&app, &request).await);
+    });
+}
+
+async fn respond(app: &AppHandle, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let uri = request.uri();
+    let Some(path) = percent_decode(uri.path().trim_start_matches('/')) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "Invalid thumbnail path".to_string(),
+        );
+    };
+    // Only media of the projects is served, so a page can't read other files through it
+    if let Err(e) = validate_media_file(app, &path) {
+        return error(StatusCode::FORBIDDEN, e);
+    }
+    let query = |name: &str| {
+        uri.query()
+            .unwrap_or_default()
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+            .map(str::to_string)
+    };
+    let size = query("size")
+        .and_then(|size| size.parse::<u32>().ok())
+        .unwrap_or(DEFAULT_SIZE)
+        .clamp(1, MAX_SIZE);
+    let time_ms = query("time")
+        .and_then(|time| time.parse::<f64>().ok())
+        .filter(|time| time.is_finite())
+        .map(position_ms);
+
+    // The file's modification time and length identify the thumbnail, so the webview
+    // can keep its copy until the file changes
+    let Some(tag) = entity_tag(&path, size, time_ms) else {
+        return error(StatusCode::NOT_FOUND, format!("File not found: {}", path));
+    };
+    let cached = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|value| value.as_bytes() == tag.as_bytes());
+    if cached {
+        return Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, &tag)
+            .body(Vec::new())
+            .unwrap_or_default();
+    }
+
+    match thumbnail_bytes_at(&path, size, time_ms).await {
+        Ok(thumbnail) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "image/jpeg")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .header(header::ETAG, &tag)
+            .body(thumbnail.to_vec())
+            .unwrap_or_default(),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+fn error(status: StatusCode, message: String) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(message.into_bytes())
+        .unwrap_or_default()
+}
+
+/// ETag of the thumbnail of a file at a size and position, or `None` if the file
+/// doesn't exist
+fn entity_tag(path: &str, size: u32, time_ms: Option<u64>) -> Option<String> {
+    let metadata = fs::metadata(paths::long_path(Path::new(path))).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_millis())
+        .unwrap_or_default();
+    let position = time_ms.map(|time| format!("@{}", time)).unwrap_or_default();
+    Some(format!(
+        "\"{:x}-{:x}-{}{}\"",
+        modified,
+        metadata.len(),
+        size,
+        position
+    ))
+}
+
+/// Decode the `%XX` escapes of a URL path, as written by `encodeURIComponent`
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = value.get(index + 1..index + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
@@ -0,0 +1,272 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use crate::captions::format::CaptionStore;
+use crate::file_system::commands::scan_media_files;
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Camera RAW extensions (lowercase) whose embedded JPEG preview can be extracted;
+/// all of them are TIFF-based
+pub const RAW_EXTENSIONS: &[&str] = &["cr2", "nef", "nrw", "arw", "dng", "pef"];
+
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014A;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+const TAG_EXIF_IFD: u16 = 0x8769;
+
+/// Old-style JPEG and JPEG compression, used both for previews and for lossless raw data
+const COMPRESSION_OLD_JPEG: u32 = 6;
+const COMPRESSION_JPEG: u32 = 7;
+
+/// Upper bound on the IFDs visited, in case of corrupt offsets
+const MAX_IFDS: usize = 64;
+
+/// Check whether a file is a supported camera RAW by its extension
+pub fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .map(|e| RAW_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Minimal reader for the TIFF structure RAW files are built on
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl Tiff<'_> {
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// Values of a SHORT or LONG entry, read inline or from the offset it points to
+    fn values(&self, entry: usize) -> Vec<u32> {
+        let field_type = self.u16_at(entry + 2).unwrap_or(0);
+        let count = self.u32_at(entry + 4).unwrap_or(0) as usize;
+        let size = match field_type {
+            3 => 2,
+            4 | 13 => 4,
+            _ => return Vec::new(),
+        };
+
+        let start = if size * count <= 4 {
+            entry + 8
+        } else {
+            match self.u32_at(entry + 8) {
+                Some(offset) => offset as usize,
+                None => return Vec::new(),
+            }
+        };
+
+        (0..count.min(256))
+            .filter_map(|i| match size {
+                2 => self.u16_at(start + i * 2).map(u32::from),
+                _ => self.u32_at(start + i * 4),
+            })
+            .collect()
+    }
+
+    fn first_value(&self, entry: usize) -> Option<u32> {
+        self.values(entry).first().copied()
+    }
+}
+
+/// Check that JPEG data is baseline or progressive, which image decoders support,
+/// rather than the lossless JPEG RAW files store sensor data in
+fn is_viewable_jpeg(jpeg: &[u8]) -> bool {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return false;
+    }
+
+    let mut i = 2;
+    while i + 4 <= jpeg.len() && jpeg[i] == 0xFF {
+        match jpeg[i + 1] {
+            0xC0..=0xC2 => return true,
+            // Other start-of-frame markers, or the image data without a frame first
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF | 0xDA => return false,
+            // Fill bytes before a marker
+            0xFF => i += 1,
+            _ => {
+                let length = u16::from_be_bytes([jpeg[i + 2], jpeg[i + 3]]) as usize;
+                i += 2 + length;
+            }
+        }
+    }
+
+    false
+}
+
+/// Find the largest JPEG preview embedded in a TIFF-based RAW file
+///
+/// Walks IFD0 and its chain, SubIFDs and the EXIF IFD, collecting JPEG thumbnails
+/// (`JPEGInterchangeFormat`) and JPEG-compressed images that can be decoded.
+pub fn extract_preview(path: &Path) -> Result<Vec<u8>, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read RAW file: {}", e))?;
+    let little_endian = match data.get(0..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return Err(format!("Unsupported RAW file: {}", path.display())),
+    };
+    let tiff = Tiff {
+        data: &data,
+        little_endian,
+    };
+
+    let mut pending = vec![tiff.u32_at(4).unwrap_or(0) as usize];
+    let mut visited = HashSet::new();
+    let mut best: Option<(usize, usize)> = None;
+
+    while let Some(ifd) = pending.pop() {
+        if ifd == 0 || !visited.insert(ifd) || visited.len() > MAX_IFDS {
+            continue;
+        }
+
+        let count = match tiff.u16_at(ifd) {
+            Some(count) => count as usize,
+            None => continue,
+        };
+
+        let mut jpeg_offset = None;
+        let mut jpeg_length = None;
+        let mut strip_offset = None;
+        let mut strip_length = None;
+        let mut compression = None;
+
+        for i in 0..count {
+            let entry = ifd + 2 + i * 12;
+            match tiff.u16_at(entry) {
+                Some(TAG_COMPRESSION) => compression = tiff.first_value(entry),
+                Some(TAG_STRIP_OFFSETS) => strip_offset = tiff.first_value(entry),
+                Some(TAG_STRIP_BYTE_COUNTS) => strip_length = tiff.first_value(entry),
+                Some(TAG_JPEG_OFFSET) => jpeg_offset = tiff.first_value(entry),
+                Some(TAG_JPEG_LENGTH) => jpeg_length = tiff.first_value(entry),
+                Some(TAG_SUB_IFDS) => {
+                    pending.extend(tiff.values(entry).into_iter().map(|o| o as usize))
+                }
+                Some(TAG_EXIF_IFD) => pending.extend(tiff.first_value(entry).map(|o| o as usize)),
+                _ => {}
+            }
+        }
+
+        let mut candidates = vec![(jpeg_offset, jpeg_length)];
+        if matches!(compression, Some(COMPRESSION_OLD_JPEG | COMPRESSION_JPEG)) {
+            candidates.push((strip_offset, strip_length));
+        }
+
+        for (offset, length) in candidates {
+            if let (Some(offset), Some(length)) = (offset, length) {
+                let (offset, length) = (offset as usize, length as usize);
+                let viewable = data
+                    .get(offset..offset.saturating_add(length))
+                    .map(is_viewable_jpeg)
+                    .unwrap_or(false);
+                if viewable && best.map(|(_, l)| length > l).unwrap_or(true) {
+                    best = Some((offset, length));
+                }
+            }
+        }
+
+        if let Some(next) = tiff.u32_at(ifd + 2 + count * 12) {
+            pending.push(next as usize);
+        }
+    }
+
+    match best {
+        Some((offset, length)) => Ok(data[offset..offset + length].to_vec()),
+        None => Err(format!("No embedded preview found in {}", path.display())),
+    }
+}
+
+/// Result of converting the RAW files of a project to JPEG, with file names
+#[derive(Debug, Default, Serialize)]
+pub struct RawConversion {
+    pub converted: Vec<String>,
+    /// RAW files that already have a JPEG next to them
+    pub skipped: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// Save the embedded preview of every RAW file in a project as a JPEG next to it
+///
+/// The preview is written as-is, without re-encoding. Captions move to the JPEG, and
+/// with `remove_originals` the RAW files are deleted. The conversion is recorded as one
+/// undoable operation.
+pub fn convert_directory(
+    directory: &Path,
+    remove_originals: bool,
+) -> Result<RawConversion, String> {
+    let mut store = CaptionStore::open(directory);
+    let mut report = RawConversion::default();
+    let mut operation = Operation::begin(
+        directory,
+        OperationKind::MediaEdit,
+        "Convert RAW files to JPEG",
+    )?;
+
+    for file in scan_media_files(directory)? {
+        let raw = Path::new(&file.path);
+        if !is_raw(raw) {
+            continue;
+        }
+
+        let jpeg = raw.with_extension("jpg");
+        if jpeg.exists() {
+            report.skipped.push(file.name);
+            continue;
+        }
+
+        let preview = match extract_preview(raw) {
+            Ok(preview) => preview,
+            Err(e) => {
+                warn!("Failed to convert {}: {}", file.path, e);
+                report.failed.push(file.name);
+                continue;
+            }
+        };
+
+        operation.snapshot(&jpeg)?;
+        fs::write(&jpeg, preview)
+            .map_err(|e| format!("Failed to write {}: {}", jpeg.display(), e))?;
+
+        // Sidecars are shared through the file stem; manifest entries are per file name
+        if store.format().is_manifest() {
+            if let Some(caption) = store.read(raw)? {
+                operation.snapshot(&store.caption_path(&jpeg))?;
+                store.write(&jpeg, &caption)?;
+                if remove_originals {
+                    store.remove(raw)?;
+                }
+            }
+        }
+
+        if remove_originals {
+            operation.snapshot_and_remove(raw)?;
+        }
+
+        report.converted.push(file.name);
+    }
+
+    operation.commit()?;
+
+    Ok(report)
+}
@@ -0,0 +1,174 @@
+use image::GenericImageView;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use super::convert::{self, TargetFormat};
+use super::formats;
+use crate::captions::format::CaptionStore;
+use crate::jobs::manager::Job;
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Ratio of the long side to the short side past which an image is tiled when none
+/// is given
+pub const DEFAULT_MIN_ASPECT: f64 = 2.0;
+
+/// Share of each tile overlapping the next when none is given
+pub const DEFAULT_OVERLAP: f64 = 0.25;
+
+/// Result of tiling images, with file names
+#[derive(Debug, Default, Serialize)]
+pub struct TileSummary {
+    /// Images split into tiles
+    pub tiled: Vec<String>,
+    /// Tiles written
+    pub tiles: Vec<String>,
+    /// Videos and images that aren't wide or tall enough, or already tiled
+    pub skipped: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Offsets along the long side of square tiles covering it, spread evenly so each
+/// overlaps the next by at least `overlap`
+fn tile_offsets(long: u32, short: u32, overlap: f64) -> Vec<u32> {
+    let step = short as f64 * (1.0 - overlap);
+    let count = (((long - short) as f64 / step).ceil() as u32 + 1).max(2);
+    (0..count)
+        .map(|i| ((long - short) as f64 * i as f64 / (count - 1) as f64).round() as u32)
+        .collect()
+}
+
+/// Path of a tile next to its image: tile 2 of `pano.jpg` is `pano_tile02.jpg`
+fn tile_path(path: &Path, index: usize, extension: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}_tile{:02}.{}", stem, index + 1, extension))
+}
+
+/// Split ultra-wide and ultra-tall images, such as panoramas and manga pages, into
+/// overlapping square tiles next to them, which square cropping would otherwise ruin
+///
+/// Images whose long side is at least `min_aspect` (2 by default) times their short
+/// side are tiled along it, each tile overlapping the next by `overlap` (a quarter by
+/// default). Tiles keep the format of their image where it can be written, or become
+/// PNGs, and get a copy of its caption. With `remove_originals` tiled images are
+/// deleted. The tiling is recorded as one undoable operation on the project of the
+/// first image.
+pub fn tile_images(
+    job: &Job,
+    paths: &[PathBuf],
+    min_aspect: Option<f64>,
+    overlap: Option<f64>,
+    remove_originals: bool,
+) -> Result<TileSummary, String> {
+    let mut summary = TileSummary::default();
+    let Some(first) = paths.first() else {
+        return Ok(summary);
+    };
+
+    let min_aspect = min_aspect.unwrap_or(DEFAULT_MIN_ASPECT).max(1.2);
+    let overlap = overlap.unwrap_or(DEFAULT_OVERLAP).clamp(0.0, 0.75);
+    let mut store = CaptionStore::for_media(first);
+    let mut operation = Operation::begin(first, OperationKind::MediaEdit, "Tile images")?;
+
+    job.progress(0, paths.len() as u64, Some("Tiling images".to_string()));
+    for path in paths {
+        if let Err(e) = job.check_cancelled() {
+            // Keep what was tiled so far undoable
+            operation.commit()?;
+            return Err(e);
+        }
+
+        let name = file_name(path);
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let (format, tile_extension) = match TargetFormat::for_extension(&extension) {
+            Some(format) => (format, extension.as_str()),
+            None => (TargetFormat::Png, "png"),
+        };
+        if formats::media_type(path) != Some("image") || tile_path(path, 0, tile_extension).exists()
+        {
+            summary.skipped.push(name);
+            job.advance(1);
+            continue;
+        }
+
+        let image = match formats::open_image(path) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!("Failed to tile {}: {}", path.display(), e);
+                summary.failed.push(name);
+                job.advance(1);
+                continue;
+            }
+        };
+        let (width, height) = image.dimensions();
+        let (long, short) = (width.max(height), width.min(height));
+        if short == 0 || (long as f64) < short as f64 * min_aspect {
+            summary.skipped.push(name);
+            job.advance(1);
+            continue;
+        }
+
+        let caption = store.read(path)?;
+        let mut written = Vec::new();
+        let mut failed = None;
+        for (index, offset) in tile_offsets(long, short, overlap).into_iter().enumerate() {
+            let tile = if width > height {
+                image.crop_imm(offset, 0, short, short)
+            } else {
+                image.crop_imm(0, offset, short, short)
+            };
+            let target = tile_path(path, index, tile_extension);
+            operation.snapshot(&target)?;
+            if let Err(e) = convert::encode(&tile, format, convert::DEFAULT_QUALITY, &target) {
+                failed = Some(e);
+                break;
+            }
+            written.push(target);
+        }
+
+        if let Some(e) = failed {
+            warn!("Failed to tile {}: {}", path.display(), e);
+            for target in &written {
+                let _ = fs::remove_file(target);
+            }
+            summary.failed.push(name);
+            job.advance(1);
+            continue;
+        }
+
+        for target in written {
+            if let Some(caption) = &caption {
+                operation.snapshot(&store.caption_path(&target))?;
+                store.write(&target, caption)?;
+            }
+            summary.tiles.push(file_name(&target));
+        }
+        if remove_originals {
+            if caption.is_some() {
+                operation.snapshot(&store.caption_path(path))?;
+                store.remove(path)?;
+            }
+            operation.snapshot_and_remove(path)?;
+        }
+
+        summary.tiled.push(name);
+        job.advance(1);
+    }
+
+    operation.commit()?;
+
+    Ok(summary)
+}
@@ -0,0 +1,224 @@
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::tempdir;
+use tracing::{debug, warn};
+
+use super::convert::{self, TargetFormat};
+use super::formats;
+use crate::jobs::manager::Job;
+use crate::undo::journal::{Operation, OperationKind};
+
+/// Name of the Real-ESRGAN executable, bundled next to the app or found in PATH
+const BINARY: &str = "realesrgan-ncnn-vulkan";
+
+/// Real-ESRGAN model images are upscaled with
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UpscaleModel {
+    /// Photos and other real-world images (default)
+    #[default]
+    General,
+    /// Illustrations and anime art
+    Anime,
+    /// Lighter anime model, which also upscales 2x and 3x natively
+    AnimeFast,
+}
+
+impl UpscaleModel {
+    fn name(self) -> &'static str {
+        match self {
+            UpscaleModel::General => "realesrgan-x4plus",
+            UpscaleModel::Anime => "realesrgan-x4plus-anime",
+            UpscaleModel::AnimeFast => "realesr-animevideov3",
+        }
+    }
+
+    /// Scale the model upscales to for a requested one; the x4 models only do 4x
+    fn native_scale(self, scale: u32) -> u32 {
+        match self {
+            UpscaleModel::AnimeFast => scale,
+            _ => 4,
+        }
+    }
+}
+
+/// Result of upscaling images, with file names
+#[derive(Debug, Default, Serialize)]
+pub struct UpscaleSummary {
+    pub upscaled: Vec<String>,
+    /// Videos and formats that can't be written back
+    pub skipped: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// The Real-ESRGAN executable: the one bundled next to the app, or else the first in
+/// PATH
+fn find_binary() -> Result<PathBuf, String> {
+    let file_name = format!("{}{}", BINARY, env::consts::EXE_SUFFIX);
+    let bundled = env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(&file_name)));
+    let in_path = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|dir| dir.join(&file_name));
+
+    bundled
+        .into_iter()
+        .chain(in_path)
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| {
+            format!(
+                "Real-ESRGAN is not installed. Please put {} and its models folder next to the \
+                 app or in PATH to enable upscaling.",
+                BINARY
+            )
+        })
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Run Real-ESRGAN on one image, writing a PNG to `target`
+fn run_upscaler(
+    binary: &Path,
+    source: &Path,
+    target: &Path,
+    model: UpscaleModel,
+    scale: u32,
+) -> Result<(), String> {
+    let mut command = Command::new(binary);
+    command
+        .arg("-i")
+        .arg(source)
+        .arg("-o")
+        .arg(target)
+        .arg("-n")
+        .arg(model.name())
+        .arg("-s")
+        .arg(scale.to_string())
+        .arg("-f")
+        .arg("png");
+    // The models are looked up relative to the working directory by default
+    if let Some(models) = binary.parent().map(|dir| dir.join("models")) {
+        if models.is_dir() {
+            command.arg("-m").arg(models);
+        }
+    }
+
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", BINARY, e))?;
+    if !output.status.success() || !target.exists() {
+        return Err(format!(
+            "Failed to upscale image: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+/// Upscale images in place by `scale` (2, 3 or 4) with Real-ESRGAN, e.g. to bring
+/// low-resolution finds up to the training resolution
+///
+/// Models that only upscale 4x are scaled down to the requested size afterwards. Only
+/// JPEG, PNG and WebP files are upscaled, since other formats can't be written back.
+/// The upscales are recorded as one undoable operation on the project of the first
+/// image.
+pub fn upscale_images(
+    job: &Job,
+    paths: &[PathBuf],
+    scale: u32,
+    model: UpscaleModel,
+) -> Result<UpscaleSummary, String> {
+    let mut summary = UpscaleSummary::default();
+    let Some(first) = paths.first() else {
+        return Ok(summary);
+    };
+    if !(2..=4).contains(&scale) {
+        return Err(format!("Unsupported scale {}; use 2, 3 or 4", scale));
+    }
+
+    let binary = find_binary()?;
+    debug!("Upscaling with {}", binary.display());
+    let temp_dir = tempdir().map_err(|e| format!("Failed to create temporary directory: {}", e))?;
+    let upscaled_path = temp_dir.path().join("upscaled.png");
+    let native_scale = model.native_scale(scale);
+    let mut operation = Operation::begin(
+        first,
+        OperationKind::MediaEdit,
+        format!("Upscale images {}x", scale),
+    )?;
+
+    job.progress(0, paths.len() as u64, Some("Upscaling images".to_string()));
+    for path in paths {
+        if let Err(e) = job.check_cancelled() {
+            // Keep what was upscaled so far undoable
+            operation.commit()?;
+            return Err(e);
+        }
+
+        let name = file_name(path);
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let format = TargetFormat::for_extension(&extension);
+        let (Some(format), Some("image")) = (format, formats::media_type(path)) else {
+            summary.skipped.push(name);
+            job.advance(1);
+            continue;
+        };
+
+        let upscaled = run_upscaler(&binary, path, &upscaled_path, model, native_scale)
+            .and_then(|_| {
+                image::open(&upscaled_path)
+                    .map_err(|e| format!("Failed to open upscaled image: {}", e))
+            })
+            .and_then(|upscaled| {
+                let (width, height) = image::image_dimensions(path)
+                    .map_err(|e| format!("Failed to read image size: {}", e))?;
+                Ok(if native_scale == scale {
+                    upscaled
+                } else {
+                    upscaled.resize_exact(width * scale, height * scale, FilterType::Lanczos3)
+                })
+            });
+        let _ = fs::remove_file(&upscaled_path);
+        let upscaled = match upscaled {
+            Ok(upscaled) => upscaled,
+            Err(e) => {
+                warn!("Failed to upscale {}: {}", path.display(), e);
+                summary.failed.push(name);
+                job.advance(1);
+                continue;
+            }
+        };
+
+        // Replace the file rather than writing through a hard link to an imported
+        // original
+        operation.snapshot(path)?;
+        let _ = fs::remove_file(path);
+        if let Err(e) = convert::encode(&upscaled, format, convert::DEFAULT_QUALITY, path) {
+            warn!("Failed to upscale {}: {}", path.display(), e);
+            summary.failed.push(name);
+            job.advance(1);
+            continue;
+        }
+
+        summary.upscaled.push(name);
+        job.advance(1);
+    }
+
+    operation.commit()?;
+
+    Ok(summary)
+}
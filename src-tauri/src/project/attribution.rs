@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// License, source and creator of a file or of a whole project, to keep track of what
+/// redistributing a dataset requires
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Attribution {
+    /// License name or SPDX identifier, e.g. `CC-BY-4.0`
+    pub license: Option<String>,
+    /// Where the file comes from, e.g. a URL
+    pub source: Option<String>,
+    pub creator: Option<String>,
+}
+
+/// Trim a field, treating blank values as unset
+fn cleaned(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+impl Attribution {
+    pub fn is_empty(&self) -> bool {
+        self.license.is_none() && self.source.is_none() && self.creator.is_none()
+    }
+
+    /// Take the fields set in `changes`, leaving the others as they are; an empty
+    /// string clears a field
+    pub fn update(&mut self, changes: &Attribution) {
+        if changes.license.is_some() {
+            self.license = cleaned(&changes.license);
+        }
+        if changes.source.is_some() {
+            self.source = cleaned(&changes.source);
+        }
+        if changes.creator.is_some() {
+            self.creator = cleaned(&changes.creator);
+        }
+    }
+
+    /// This attribution with its unset fields taken from `defaults`
+    pub fn or(&self, defaults: &Attribution) -> Attribution {
+        Attribution {
+            license: self.license.clone().or_else(|| defaults.license.clone()),
+            source: self.source.clone().or_else(|| defaults.source.clone()),
+            creator: self.creator.clone().or_else(|| defaults.creator.clone()),
+        }
+    }
+}
+
+/// Attribution of a project and of its files
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DatasetAttribution {
+    /// Defaults for every file of the project
+    pub project: Attribution,
+    /// Files with attribution of their own, by relative path
+    pub files: HashMap<String, Attribution>,
+}
+
+impl DatasetAttribution {
+    pub fn is_empty(&self) -> bool {
+        self.project.is_empty() && self.files.is_empty()
+    }
+
+    /// The attribution of a file, falling back to the project's for unset fields
+    pub fn of(&self, relative_path: &str) -> Attribution {
+        match self.files.get(relative_path) {
+            Some(attribution) => attribution.or(&self.project),
+            None => self.project.clone(),
+        }
+    }
+}
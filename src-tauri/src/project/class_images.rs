@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use super::index;
+use crate::file_system::commands::scan_media_files;
+use crate::file_system::copy::{copy_file, ImportMode};
+use crate::jobs::manager::Job;
+use crate::media::probe;
+
+/// Repeats of the class folder when none are given; regularization images are
+/// usually seen once per epoch
+const DEFAULT_REPEATS: u32 = 1;
+
+/// Share of the dataset's median short side below which a candidate is too small,
+/// since training would upscale it
+const MIN_SIZE_SHARE: f64 = 0.75;
+
+/// Options of a class image selection; anything left unset is derived from the dataset
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ClassImageOptions {
+    /// Images to copy (default: as many as the project has)
+    pub count: Option<usize>,
+    pub repeats: Option<u32>,
+    /// Caption written for every image (default: `a photo of a <class>`)
+    pub caption: Option<String>,
+    /// Seed of the random pick among equally fitting images, for a reproducible
+    /// selection
+    pub seed: Option<u64>,
+}
+
+/// Result of a class image selection
+#[derive(Debug, Serialize)]
+pub struct ClassImages {
+    pub class_dir: String,
+    pub copied: usize,
+    /// Candidates left out for being smaller than the dataset's images
+    pub too_small: usize,
+    pub caption: String,
+    pub seed: u64,
+}
+
+/// An image of the pool, with the log of its aspect ratio so wide and tall images
+/// compare evenly
+struct Candidate {
+    path: String,
+    relative_path: String,
+    aspect: f64,
+}
+
+fn log_aspect(width: u32, height: u32) -> f64 {
+    (width as f64 / height as f64).ln()
+}
+
+/// Copy regularization images for DreamBooth-style training from a pool of generic
+/// images into a `<repeats>_<class>` folder of `output`, each with a generic caption
+///
+/// The images are picked to match the aspect ratios of the project's images, one for
+/// each project image in turn, so the buckets are filled in the same proportions.
+/// Pool images much smaller than the project's are left out. Nested pool images are
+/// flattened into the folder.
+pub fn select(
+    job: &Job,
+    project: &Path,
+    pool: &Path,
+    output: &Path,
+    class_name: &str,
+    options: &ClassImageOptions,
+) -> Result<ClassImages, String> {
+    let class_name = class_name.trim();
+    if class_name.is_empty() {
+        return Err("A class name is required, e.g. \"woman\" or \"dog\"".to_string());
+    }
+    if output.starts_with(project) || output.starts_with(pool) {
+        return Err(
+            "The output directory can't be inside the project or the image pool".to_string(),
+        );
+    }
+
+    let sizes: Vec<(u32, u32)> = index::list_files(project)?
+        .iter()
+        .filter(|file| file.file_type == "image")
+        .filter_map(|file| file.width.zip(file.height))
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .collect();
+    if sizes.is_empty() {
+        return Err("The project has no images to match".to_string());
+    }
+    let mut short_sides: Vec<u32> = sizes.iter().map(|&(w, h)| w.min(h)).collect();
+    short_sides.sort_unstable();
+    let min_short_side = short_sides[short_sides.len() / 2] as f64 * MIN_SIZE_SHARE;
+
+    let files: Vec<_> = scan_media_files(pool)?
+        .into_iter()
+        .filter(|file| file.file_type == "image")
+        .collect();
+    let total = files.len() as u64;
+    let mut candidates = Vec::new();
+    let mut too_small = 0;
+    for (index, file) in files.into_iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some("Measuring images".to_string()));
+
+        let info = probe::probe_media(Path::new(&file.path), &file.file_type);
+        let Some((width, height)) = info.width.zip(info.height) else {
+            continue;
+        };
+        if width == 0 || height == 0 {
+            continue;
+        }
+        if (width.min(height) as f64) < min_short_side {
+            too_small += 1;
+            continue;
+        }
+        candidates.push(Candidate {
+            path: file.path,
+            relative_path: file.relative_path,
+            aspect: log_aspect(width, height),
+        });
+    }
+
+    let seed = options.seed.unwrap_or_else(|| u64::from(fastrand::u32(..)));
+    fastrand::Rng::with_seed(seed).shuffle(&mut candidates);
+
+    // Cycle through the project's aspect ratios, taking the closest unused candidate
+    // for each; shuffling first picks at random among equally close ones
+    let count = options.count.unwrap_or(sizes.len()).min(candidates.len());
+    let targets = sizes.iter().map(|&(w, h)| log_aspect(w, h)).cycle();
+    let mut picked = Vec::with_capacity(count);
+    for target in targets.take(count) {
+        let closest = candidates
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                (a.aspect - target)
+                    .abs()
+                    .total_cmp(&(b.aspect - target).abs())
+            })
+            .map(|(index, _)| index);
+        if let Some(index) = closest {
+            picked.push(candidates.swap_remove(index));
+        }
+    }
+
+    let repeats = options.repeats.unwrap_or(DEFAULT_REPEATS).max(1);
+    let caption = options
+        .caption
+        .clone()
+        .filter(|caption| !caption.trim().is_empty())
+        .unwrap_or_else(|| format!("a photo of a {}", class_name));
+    let folder = output.join(format!("{}_{}", repeats, class_name));
+    fs::create_dir_all(&folder)
+        .map_err(|e| format!("Failed to create {}: {}", folder.display(), e))?;
+
+    let total = picked.len() as u64;
+    for (index, candidate) in picked.iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some(candidate.relative_path.clone()));
+
+        let name = candidate.relative_path.replace(['/', '\\'], "_");
+        let target = folder.join(&name);
+        copy_file(ImportMode::Copy, Path::new(&candidate.path), &target)?;
+
+        let caption_path = target.with_extension("txt");
+        fs::write(&caption_path, caption.trim())
+            .map_err(|e| format!("Failed to write {}: {}", caption_path.display(), e))?;
+    }
+    job.progress(total, total, None);
+
+    Ok(ClassImages {
+        class_dir: folder.to_string_lossy().to_string(),
+        copied: picked.len(),
+        too_small,
+        caption,
+        seed,
+    })
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::AppHandle;
+
+use super::attribution::{Attribution, DatasetAttribution};
+use super::class_images::{self, ClassImageOptions, ClassImages};
+use super::index::{self, IndexSummary};
+use super::kohya::{self, KohyaExport, KohyaOptions};
+use super::marks::{self, ColorFlag, FileMark};
+use super::overview::{self, ProjectOverview};
+use super::report::{self, ReportFormat};
+use super::settings::{self, now_timestamp, ProjectSettings, ProjectStats};
+use crate::file_system::commands::{list_directory_files, validate_project_directory};
+use crate::file_system::copy;
+use crate::jobs::journal;
+use crate::jobs::manager::{self as jobs, JobKind};
+
+/// Recount the media files of a project for its settings snapshot
+async fn refresh_stats(project: &Path) -> Result<ProjectStats, String> {
+    let files = list_directory_files(project.to_string_lossy().to_string(), None, None).await?;
+
+    Ok(ProjectStats {
+        file_count: files.len(),
+        image_count: files.iter().filter(|f| f.file_type == "image").count(),
+        video_count: files.iter().filter(|f| f.file_type == "video").count(),
+        captioned_count: files.iter().filter(|f| f.has_caption).count(),
+        updated: Some(now_timestamp()),
+    })
+}
+
+/// Get the settings stored in a project's `project.json`
+#[tauri::command]
+pub async fn get_project_settings(project: String) -> Result<ProjectSettings, String> {
+    let project_path = Path::new(&project);
+    if !project_path.is_dir() {
+        return Err(format!("Directory does not exist: {}", project));
+    }
+
+    let mut project_settings = settings::load_settings(project_path)?;
+    project_settings.stats = refresh_stats(project_path).await?;
+    settings::save_settings(project_path, &project_settings)?;
+
+    Ok(project_settings)
+}
+
+/// Update a project's settings with the provided fields, keeping all others
+#[tauri::command]
+pub async fn update_project_settings(
+    app: AppHandle,
+    project: String,
+    settings: serde_json::Value,
+) -> Result<ProjectSettings, String> {
+    let project_path = validate_project_directory(&app, &project)?;
+
+    let current = settings::load_settings(&project_path)?;
+    let updated = settings::merge_settings(&current, settings)?;
+    settings::save_settings(&project_path, &updated)?;
+
+    Ok(updated)
+}
+
+/// Bring a project's index up to date, including the hashes of new or changed files
+///
+/// Listings keep the index fresh on their own but leave hashing to this command, which
+/// runs as a cancellable indexing job.
+#[tauri::command]
+pub async fn refresh_project_index(
+    app: AppHandle,
+    project: String,
+) -> Result<IndexSummary, String> {
+    let project_path = validate_project_directory(&app, &project)?;
+    let description = format!("Index {}", project);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        let result = index::refresh_with_hashes(&project_path, job.cancel_flag(), |done, total| {
+            // Reporting every file would flood the frontend with events
+            if done % 25 == 0 || done == total {
+                job.progress(done as u64, total as u64, None);
+            }
+        });
+
+        match result {
+            Err(e) if e == copy::CANCELLED => Err(jobs::CANCELLED.to_string()),
+            result => result,
+        }
+    })
+    .await
+}
+
+/// Export a project for kohya's sd-scripts into `destination`, with a dataset config
+/// (and optionally a training config) derived from its images and captions
+#[tauri::command]
+pub async fn export_kohya_dataset(
+    app: AppHandle,
+    directory: String,
+    destination: String,
+    options: Option<KohyaOptions>,
+) -> Result<KohyaExport, String> {
+    let project_path = validate_project_directory(&app, &directory)?;
+    let description = format!("Export {} for kohya", directory);
+    let (project, exported_to) = (project_path.clone(), destination.clone());
+
+    let export = jobs::run(&app, JobKind::Export, description, move |job| {
+        kohya::export(
+            job,
+            &project_path,
+            Path::new(&destination),
+            &options.unwrap_or_default(),
+        )
+    })
+    .await?;
+    settings::record_export(&project, &exported_to);
+    Ok(export)
+}
+
+/// Copy regularization images for DreamBooth-style training from `pool` into a
+/// `<repeats>_<class_name>` folder of `destination`, with generic captions
+///
+/// The images are picked to match the aspect ratios and resolution of the project's
+/// images; `destination` is typically the folder of a kohya export. Runs as an export
+/// job.
+#[tauri::command]
+pub async fn generate_class_images(
+    app: AppHandle,
+    directory: String,
+    pool: String,
+    destination: String,
+    class_name: String,
+    options: Option<ClassImageOptions>,
+) -> Result<ClassImages, String> {
+    let project_path = validate_project_directory(&app, &directory)?;
+    let pool_path = Path::new(&pool).to_path_buf();
+    if !pool_path.is_dir() {
+        return Err(format!("Directory does not exist: {}", pool));
+    }
+    let description = format!("Select {} class images for {}", class_name, directory);
+
+    jobs::run(&app, JobKind::Export, description, move |job| {
+        class_images::select(
+            job,
+            &project_path,
+            &pool_path,
+            Path::new(&destination),
+            &class_name,
+            &options.unwrap_or_default(),
+        )
+    })
+    .await
+}
+
+/// Generate a dataset quality report: file counts, resolution and aspect ratio
+/// histograms, caption coverage and lengths, duplicates and questionable files
+///
+/// Written as HTML (default) or JSON to `output`, or into the project's metadata
+/// directory; returns the path of the report. Runs as an indexing job since it hashes
+/// new files to find duplicates.
+#[tauri::command]
+pub async fn generate_report(
+    app: AppHandle,
+    directory: String,
+    format: Option<ReportFormat>,
+    output: Option<String>,
+) -> Result<String, String> {
+    let project_path = validate_project_directory(&app, &directory)?;
+    let description = format!("Report on {}", directory);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        let report = report::build_report(job, &project_path)?;
+        let path = report::write_report(
+            &project_path,
+            &report,
+            format.unwrap_or_default(),
+            output.as_deref().map(Path::new),
+        )?;
+        Ok(path.to_string_lossy().to_string())
+    })
+    .await
+}
+
+/// Get what the projects screen shows about a project in one call: file counts by
+/// type and extension, total size, caption coverage, the latest operations, jobs
+/// working on it and when it was last exported
+#[tauri::command]
+pub async fn get_project_overview(app: AppHandle, path: String) -> Result<ProjectOverview, String> {
+    let project_path = validate_project_directory(&app, &path)?;
+    let interrupted = journal::list_interrupted(&app).unwrap_or_default();
+    overview::build_overview(&project_path, &interrupted)
+}
+
+/// Get the marks (rating, flag, favorite) of a project's files, by relative path
+#[tauri::command]
+pub async fn get_file_marks(
+    app: AppHandle,
+    directory: String,
+) -> Result<HashMap<String, FileMark>, String> {
+    let project_path = validate_project_directory(&app, &directory)?;
+    index::marks(&project_path)
+}
+
+/// Rate files (given by relative path) from 1 to 5 stars, or clear their rating
+#[tauri::command]
+pub async fn set_file_rating(
+    app: AppHandle,
+    directory: String,
+    files: Vec<String>,
+    rating: Option<u8>,
+) -> Result<(), String> {
+    let project_path = validate_project_directory(&app, &directory)?;
+    marks::validate_rating(rating)?;
+    index::update_marks(&project_path, &files, |mark| mark.rating = rating)
+}
+
+/// Set or clear the color flag of files, given by relative path
+#[tauri::command]
+pub async fn set_file_flag(
+    app: AppHandle,
+    directory: String,
+    files: Vec<String>,
+    flag: Option<ColorFlag>,
+) -> Result<(), String> {
+    let project_path = validate_project_directory(&app, &directory)?;
+    index::update_marks(&project_path, &files, |mark| mark.flag = flag)
+}
+
+/// Mark files, given by relative path, as favorites or not
+#[tauri::command]
+pub async fn set_file_favorite(
+    app: AppHandle,
+    directory: String,
+    files: Vec<String>,
+    favorite: bool,
+) -> Result<(), String> {
+    let project_path = validate_project_directory(&app, &directory)?;
+    index::update_marks(&project_path, &files, |mark| mark.favorite = favorite)
+}
+
+/// Get the license, source and creator of a project and of its files
+#[tauri::command]
+pub async fn get_attribution(
+    app: AppHandle,
+    directory: String,
+) -> Result<DatasetAttribution, String> {
+    let project_path = validate_project_directory(&app, &directory)?;
+    index::attribution(&project_path)
+}
+
+/// Set the license, source or creator of a whole project, the default for its files
+///
+/// Only the fields given are changed; an empty string clears a field.
+#[tauri::command]
+pub async fn set_project_attribution(
+    app: AppHandle,
+    directory: String,
+    attribution: Attribution,
+) -> Result<(), String> {
+    let project_path = validate_project_directory(&app, &directory)?;
+    index::update_attribution(&project_path, None, &attribution)
+}
+
+/// Set the license, source or creator of files, given by relative path, overriding the
+/// project's
+///
+/// Only the fields given are changed; an empty string clears a field, so that the file
+/// falls back to the project's.
+#[tauri::command]
+pub async fn set_file_attribution(
+    app: AppHandle,
+    directory: String,
+    files: Vec<String>,
+    attribution: Attribution,
+) -> Result<(), String> {
+    let project_path = validate_project_directory(&app, &directory)?;
+    if files.iter().any(|file| file.trim().is_empty()) {
+        return Err("File paths can't be empty".to_string());
+    }
+    index::update_attribution(&project_path, Some(&files), &attribution)
+}
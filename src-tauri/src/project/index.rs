@@ -0,0 +1,929 @@
+use rayon::prelude::*;
+use rusqlite::{params, Connection, TransactionBehavior};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::attribution::{Attribution, DatasetAttribution};
+use super::marks::{ColorFlag, FileMark};
+use super::metadata_dir;
+use crate::captions::format::CaptionStore;
+use crate::captions::stats::split_tags;
+use crate::file_system::commands::{caption_preview, scan_media_files, MediaFile};
+use crate::file_system::{dedup, paths};
+use crate::jobs::pool;
+use crate::media::probe;
+use crate::semantic::subjects::SubjectCount;
+
+/// File in the metadata directory holding the project index
+const INDEX_FILE: &str = "index.sqlite";
+
+/// Bumped whenever the schema changes; an index with another version is rebuilt
+const SCHEMA_VERSION: i32 = 4;
+
+const SCHEMA: &str = "
+    CREATE TABLE files (
+        relative_path TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        file_type TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        mtime INTEGER NOT NULL,
+        modified TEXT,
+        width INTEGER,
+        height INTEGER,
+        duration REAL,
+        frame_count INTEGER,
+        hash TEXT,
+        caption TEXT,
+        caption_signature TEXT NOT NULL
+    );
+    CREATE TABLE tags (
+        relative_path TEXT NOT NULL,
+        tag TEXT NOT NULL
+    );
+    CREATE INDEX tags_by_tag ON tags (tag);
+    CREATE INDEX tags_by_path ON tags (relative_path);
+    CREATE TABLE embeddings (
+        relative_path TEXT PRIMARY KEY,
+        model TEXT NOT NULL,
+        mtime INTEGER NOT NULL,
+        vector BLOB NOT NULL
+    );
+    CREATE TABLE subjects (
+        relative_path TEXT PRIMARY KEY,
+        model TEXT NOT NULL,
+        mtime INTEGER NOT NULL,
+        faces INTEGER,
+        persons INTEGER NOT NULL
+    );
+";
+
+/// Curation marks and attribution are user data rather than a cache, so they are kept
+/// out of [`SCHEMA`] and survive the index being rebuilt
+const USER_DATA_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS marks (
+        relative_path TEXT PRIMARY KEY,
+        rating INTEGER,
+        flag TEXT,
+        favorite INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS attribution (
+        relative_path TEXT PRIMARY KEY,
+        license TEXT,
+        source TEXT,
+        creator TEXT
+    );
+";
+
+/// Key of the project-wide attribution in the attribution table, which no file has
+const PROJECT_ATTRIBUTION: &str = "";
+
+/// Columns read back into a [`MediaFile`], in the order [`row_to_file`] expects
+const FILE_COLUMNS: &str = "files.relative_path, files.name, files.file_type, files.size,
+    files.modified, files.width, files.height, files.duration, files.caption, files.frame_count";
+
+/// Outcome of bringing an index up to date
+#[derive(Debug, Default, Serialize)]
+pub struct IndexSummary {
+    pub files: usize,
+    pub captioned: usize,
+    /// Files added or changed since the index was last refreshed
+    pub updated: usize,
+    pub removed: usize,
+    /// Files hashed by this refresh
+    pub hashed: usize,
+}
+
+/// Relative paths of the files a refresh added, updated or removed
+#[derive(Debug, Default)]
+struct RefreshedPaths {
+    added: HashSet<String>,
+    /// Files whose media or caption changed
+    changed: HashSet<String>,
+    removed: Vec<String>,
+}
+
+/// Files added, changed or removed since the index was last refreshed, e.g. by a listing
+#[derive(Debug, Default, Serialize)]
+pub struct DirectoryChanges {
+    pub added: Vec<MediaFile>,
+    /// Files whose media or caption changed
+    pub changed: Vec<MediaFile>,
+    /// Relative paths of the files that are gone
+    pub removed: Vec<String>,
+}
+
+/// How often a tag occurs: total occurrences and number of files containing it
+#[derive(Debug)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+    pub files: usize,
+}
+
+fn db_error(e: rusqlite::Error) -> String {
+    format!("Project index error: {}", e)
+}
+
+/// Open the index of a project, creating or rebuilding it when its schema is outdated
+fn open(project: &Path) -> Result<Connection, String> {
+    let path = metadata_dir(project)?.join(INDEX_FILE);
+    let mut conn = Connection::open(&path).map_err(db_error)?;
+
+    // Listings and batch operations may refresh the index at the same time
+    conn.busy_timeout(Duration::from_secs(10))
+        .map_err(db_error)?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+        .map_err(db_error)?;
+
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(db_error)?;
+    let version: i32 = tx
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(db_error)?;
+    if version != SCHEMA_VERSION {
+        tx.execute_batch(
+            "DROP TABLE IF EXISTS subjects; DROP TABLE IF EXISTS embeddings;
+             DROP TABLE IF EXISTS tags; DROP TABLE IF EXISTS files;",
+        )
+        .map_err(db_error)?;
+        tx.execute_batch(SCHEMA).map_err(db_error)?;
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)
+            .map_err(db_error)?;
+    }
+    tx.execute_batch(USER_DATA_SCHEMA).map_err(db_error)?;
+    tx.commit().map_err(db_error)?;
+
+    Ok(conn)
+}
+
+/// Modification time of a file in nanoseconds, 0 if unavailable
+fn mtime_nanos(metadata: &fs::Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos() as i64)
+        .unwrap_or(0)
+}
+
+/// Identifies the state of the file holding a media file's caption, so captions are
+/// only read again after that file changed (or the caption format did)
+fn caption_signature(store: &CaptionStore, media: &Path) -> String {
+    match fs::metadata(store.caption_path(media)) {
+        Ok(metadata) => format!(
+            "{:?}:{}:{}",
+            store.format(),
+            mtime_nanos(&metadata),
+            metadata.len()
+        ),
+        Err(_) => format!("{:?}:none", store.format()),
+    }
+}
+
+/// Replace the indexed tags of a file with those of its caption
+fn write_tags(
+    tx: &rusqlite::Transaction,
+    relative_path: &str,
+    caption: Option<&str>,
+) -> Result<(), String> {
+    tx.execute("DELETE FROM tags WHERE relative_path = ?1", [relative_path])
+        .map_err(db_error)?;
+
+    if let Some(caption) = caption {
+        let mut insert = tx
+            .prepare_cached("INSERT INTO tags (relative_path, tag) VALUES (?1, ?2)")
+            .map_err(db_error)?;
+        for tag in split_tags(caption) {
+            insert
+                .execute(params![relative_path, tag.to_lowercase()])
+                .map_err(db_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Bring the index in line with the files on disk
+///
+/// Listing the directory is the only full pass; media files are probed again only
+/// when their size or modification time changed, and captions only when the file
+/// holding them did. Hashes of changed files are cleared, to be filled in by
+/// [`refresh_with_hashes`].
+fn refresh(conn: &mut Connection, project: &Path) -> Result<IndexSummary, String> {
+    refresh_tracked(conn, project).map(|(summary, _)| summary)
+}
+
+/// [`refresh`], also returning which files it found added, changed or removed
+fn refresh_tracked(
+    conn: &mut Connection,
+    project: &Path,
+) -> Result<(IndexSummary, RefreshedPaths), String> {
+    let files = scan_media_files(project)?;
+    let mut store = CaptionStore::open(project);
+    let mut summary = IndexSummary {
+        files: files.len(),
+        ..Default::default()
+    };
+
+    let mut known: HashMap<String, (i64, i64, String)> = HashMap::new();
+    {
+        let mut query = conn
+            .prepare("SELECT relative_path, size, mtime, caption_signature FROM files")
+            .map_err(db_error)?;
+        let rows = query
+            .query_map([], |row| {
+                Ok((row.get(0)?, (row.get(1)?, row.get(2)?, row.get(3)?)))
+            })
+            .map_err(db_error)?;
+        for row in rows {
+            let (relative_path, state) = row.map_err(db_error)?;
+            known.insert(relative_path, state);
+        }
+    }
+
+    // Work out what changed before probing, which is the slow part
+    let mut paths = RefreshedPaths::default();
+    let mut changed_media = Vec::new();
+    let mut changed_captions = Vec::new();
+    let mut seen = HashSet::new();
+    for file in files {
+        let media = Path::new(&file.path);
+        let mtime = fs::metadata(media).map(|m| mtime_nanos(&m)).unwrap_or(0);
+        let signature = caption_signature(&store, media);
+        seen.insert(file.relative_path.clone());
+
+        match known.get(&file.relative_path) {
+            Some((size, known_mtime, known_signature))
+                if *size == file.size as i64 && *known_mtime == mtime =>
+            {
+                if *known_signature != signature {
+                    paths.changed.insert(file.relative_path.clone());
+                    changed_captions.push((file, signature));
+                }
+            }
+            Some(_) => {
+                paths.changed.insert(file.relative_path.clone());
+                changed_media.push((file, mtime, signature));
+            }
+            None => {
+                paths.added.insert(file.relative_path.clone());
+                changed_media.push((file, mtime, signature));
+            }
+        }
+    }
+
+    let probed: Vec<_> = pool::install(|| {
+        changed_media
+            .into_par_iter()
+            .map(|(file, mtime, signature)| {
+                let info = probe::probe_media(Path::new(&file.path), &file.file_type);
+                (file, mtime, signature, info)
+            })
+            .collect()
+    });
+
+    let tx = conn.transaction().map_err(db_error)?;
+
+    for (file, mtime, signature, info) in &probed {
+        let caption = store.read(Path::new(&file.path)).unwrap_or(None);
+        tx.execute(
+            "INSERT OR REPLACE INTO files (relative_path, name, file_type, size, mtime, modified,
+                width, height, duration, frame_count, hash, caption, caption_signature)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, NULL, ?11, ?12)",
+            params![
+                file.relative_path,
+                file.name,
+                file.file_type,
+                file.size as i64,
+                mtime,
+                file.modified,
+                info.width,
+                info.height,
+                info.duration,
+                info.frame_count,
+                caption,
+                signature,
+            ],
+        )
+        .map_err(db_error)?;
+        write_tags(&tx, &file.relative_path, caption.as_deref())?;
+    }
+
+    for (file, signature) in &changed_captions {
+        let caption = store.read(Path::new(&file.path)).unwrap_or(None);
+        tx.execute(
+            "UPDATE files SET caption = ?2, caption_signature = ?3 WHERE relative_path = ?1",
+            params![file.relative_path, caption, signature],
+        )
+        .map_err(db_error)?;
+        write_tags(&tx, &file.relative_path, caption.as_deref())?;
+    }
+
+    for relative_path in known.keys().filter(|p| !seen.contains(*p)) {
+        tx.execute(
+            "DELETE FROM files WHERE relative_path = ?1",
+            [relative_path],
+        )
+        .map_err(db_error)?;
+        tx.execute(
+            "DELETE FROM embeddings WHERE relative_path = ?1",
+            [relative_path],
+        )
+        .map_err(db_error)?;
+        tx.execute(
+            "DELETE FROM subjects WHERE relative_path = ?1",
+            [relative_path],
+        )
+        .map_err(db_error)?;
+        tx.execute(
+            "DELETE FROM marks WHERE relative_path = ?1",
+            [relative_path],
+        )
+        .map_err(db_error)?;
+        tx.execute(
+            "DELETE FROM attribution WHERE relative_path = ?1",
+            [relative_path],
+        )
+        .map_err(db_error)?;
+        write_tags(&tx, relative_path, None)?;
+        paths.removed.push(relative_path.clone());
+    }
+    summary.removed = paths.removed.len();
+
+    let captioned: i64 = tx
+        .query_row(
+            "SELECT COUNT(*) FROM files WHERE caption IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_error)?;
+    summary.captioned = captioned as usize;
+    tx.commit().map_err(db_error)?;
+
+    summary.updated = probed.len() + changed_captions.len();
+    Ok((summary, paths))
+}
+
+/// Build a media file from a row selected with [`FILE_COLUMNS`], along with its caption
+fn row_to_file(
+    read_path: &Path,
+    row: &rusqlite::Row,
+) -> rusqlite::Result<(MediaFile, Option<String>)> {
+    let relative_path: String = row.get(0)?;
+    let name: String = row.get(1)?;
+    let file_type: String = row.get(2)?;
+    let caption: Option<String> = row.get(8)?;
+
+    let file = MediaFile {
+        id: format!("{}-{}", file_type, name),
+        name,
+        path: paths::display_path(&read_path.join(&relative_path)),
+        relative_path,
+        file_type,
+        has_caption: caption.is_some(),
+        size: row.get::<_, i64>(3)? as u64,
+        width: row.get(5)?,
+        height: row.get(6)?,
+        duration: row.get(7)?,
+        frame_count: row.get(9)?,
+        modified: row.get(4)?,
+        caption_preview: None,
+        mark: None,
+        subjects: None,
+    };
+
+    Ok((file, caption))
+}
+
+/// Query indexed files along with their captions, ordered by name
+fn query_files(
+    conn: &Connection,
+    project: &Path,
+    filter: &str,
+) -> Result<Vec<(MediaFile, Option<String>)>, String> {
+    // Paths are built the way directory listings build them
+    let read_path: PathBuf = paths::extended_path(project);
+    let sql = format!(
+        "SELECT {} FROM files {} ORDER BY name",
+        FILE_COLUMNS, filter
+    );
+
+    let mut query = conn.prepare(&sql).map_err(db_error)?;
+    let rows = query
+        .query_map([], |row| row_to_file(&read_path, row))
+        .map_err(db_error)?;
+
+    rows.collect::<rusqlite::Result<Vec<_>>>().map_err(db_error)
+}
+
+/// List the media files of a project with their dimensions, caption previews, marks
+/// and detected subjects
+pub fn list_files(project: &Path) -> Result<Vec<MediaFile>, String> {
+    let mut conn = open(project)?;
+    refresh(&mut conn, project)?;
+    let mut marks = read_marks(&conn)?;
+    let mut subjects = read_subjects(&conn)?;
+
+    Ok(query_files(&conn, project, "")?
+        .into_iter()
+        .map(|(mut file, caption)| {
+            file.caption_preview = caption.as_deref().map(caption_preview);
+            file.mark = marks.remove(&file.relative_path);
+            file.subjects = subjects.remove(&file.relative_path);
+            file
+        })
+        .collect())
+}
+
+/// Bring the index up to date and return only the files that changed since it was
+/// last refreshed, with caption previews, marks and subjects like [`list_files`]
+pub fn changed_files(project: &Path) -> Result<DirectoryChanges, String> {
+    let mut conn = open(project)?;
+    let (_, paths) = refresh_tracked(&mut conn, project)?;
+    let mut changes = DirectoryChanges {
+        removed: paths.removed,
+        ..Default::default()
+    };
+    if paths.added.is_empty() && paths.changed.is_empty() {
+        return Ok(changes);
+    }
+
+    let mut marks = read_marks(&conn)?;
+    let mut subjects = read_subjects(&conn)?;
+    for (mut file, caption) in query_files(&conn, project, "")? {
+        let added = paths.added.contains(&file.relative_path);
+        if !added && !paths.changed.contains(&file.relative_path) {
+            continue;
+        }
+        file.caption_preview = caption.as_deref().map(caption_preview);
+        file.mark = marks.remove(&file.relative_path);
+        file.subjects = subjects.remove(&file.relative_path);
+        if added {
+            changes.added.push(file);
+        } else {
+            changes.changed.push(file);
+        }
+    }
+
+    Ok(changes)
+}
+
+fn read_marks(conn: &Connection) -> Result<HashMap<String, FileMark>, String> {
+    let mut query = conn
+        .prepare("SELECT relative_path, rating, flag, favorite FROM marks")
+        .map_err(db_error)?;
+    let rows = query
+        .query_map([], |row| {
+            let flag: Option<String> = row.get(2)?;
+            Ok((
+                row.get(0)?,
+                FileMark {
+                    rating: row.get(1)?,
+                    flag: flag.as_deref().and_then(ColorFlag::parse),
+                    favorite: row.get(3)?,
+                },
+            ))
+        })
+        .map_err(db_error)?;
+
+    rows.collect::<rusqlite::Result<HashMap<_, _>>>()
+        .map_err(db_error)
+}
+
+/// Subjects detected in files that haven't changed since, by relative path
+fn read_subjects(conn: &Connection) -> Result<HashMap<String, SubjectCount>, String> {
+    let mut query = conn
+        .prepare(
+            "SELECT subjects.relative_path, subjects.faces, subjects.persons FROM subjects
+             JOIN files ON files.relative_path = subjects.relative_path
+             WHERE subjects.mtime = files.mtime",
+        )
+        .map_err(db_error)?;
+    let rows = query
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                SubjectCount {
+                    faces: row.get(1)?,
+                    persons: row.get(2)?,
+                },
+            ))
+        })
+        .map_err(db_error)?;
+
+    rows.collect::<rusqlite::Result<HashMap<_, _>>>()
+        .map_err(db_error)
+}
+
+/// Get the curation marks of a project's files, by relative path
+pub fn marks(project: &Path) -> Result<HashMap<String, FileMark>, String> {
+    read_marks(&open(project)?)
+}
+
+/// Change the marks of some files of a project, given by relative path
+///
+/// Files whose marks end up empty are dropped from the table.
+pub fn update_marks(
+    project: &Path,
+    files: &[String],
+    update: impl Fn(&mut FileMark),
+) -> Result<(), String> {
+    let mut conn = open(project)?;
+    let mut marks = read_marks(&conn)?;
+
+    let tx = conn.transaction().map_err(db_error)?;
+    for relative_path in files {
+        let mut mark = marks.remove(relative_path).unwrap_or_default();
+        update(&mut mark);
+        if mark.is_empty() {
+            tx.execute(
+                "DELETE FROM marks WHERE relative_path = ?1",
+                [relative_path],
+            )
+            .map_err(db_error)?;
+        } else {
+            tx.execute(
+                "INSERT OR REPLACE INTO marks (relative_path, rating, flag, favorite)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    relative_path,
+                    mark.rating,
+                    mark.flag.map(ColorFlag::as_str),
+                    mark.favorite,
+                ],
+            )
+            .map_err(db_error)?;
+        }
+    }
+    tx.commit().map_err(db_error)
+}
+
+/// Get the attribution of a project and of its files
+pub fn attribution(project: &Path) -> Result<DatasetAttribution, String> {
+    let conn = open(project)?;
+    let mut query = conn
+        .prepare("SELECT relative_path, license, source, creator FROM attribution")
+        .map_err(db_error)?;
+    let rows = query
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                Attribution {
+                    license: row.get(1)?,
+                    source: row.get(2)?,
+                    creator: row.get(3)?,
+                },
+            ))
+        })
+        .map_err(db_error)?;
+
+    let mut attribution = DatasetAttribution::default();
+    for row in rows {
+        let (relative_path, file) = row.map_err(db_error)?;
+        if relative_path == PROJECT_ATTRIBUTION {
+            attribution.project = file;
+        } else {
+            attribution.files.insert(relative_path, file);
+        }
+    }
+    Ok(attribution)
+}
+
+/// Change the attribution of some files of a project, given by relative path, or of
+/// the project itself when `files` is `None`
+///
+/// Only the fields set in `changes` are changed (see [`Attribution::update`]).
+pub fn update_attribution(
+    project: &Path,
+    files: Option<&[String]>,
+    changes: &Attribution,
+) -> Result<(), String> {
+    let current = attribution(project)?;
+    let project_key = [PROJECT_ATTRIBUTION.to_string()];
+    let keys = files.unwrap_or(&project_key);
+
+    let mut conn = open(project)?;
+    let tx = conn.transaction().map_err(db_error)?;
+    for relative_path in keys {
+        let mut attribution = if relative_path == PROJECT_ATTRIBUTION {
+            current.project.clone()
+        } else {
+            current
+                .files
+                .get(relative_path)
+                .cloned()
+                .unwrap_or_default()
+        };
+        attribution.update(changes);
+        if attribution.is_empty() {
+            tx.execute(
+                "DELETE FROM attribution WHERE relative_path = ?1",
+                [relative_path],
+            )
+            .map_err(db_error)?;
+        } else {
+            tx.execute(
+                "INSERT OR REPLACE INTO attribution (relative_path, license, source, creator)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    relative_path,
+                    attribution.license,
+                    attribution.source,
+                    attribution.creator,
+                ],
+            )
+            .map_err(db_error)?;
+        }
+    }
+    tx.commit().map_err(db_error)
+}
+
+/// Set the attribution of many files at once, replacing what they had, e.g. for files
+/// just imported
+pub fn set_file_attributions(
+    project: &Path,
+    attributions: &[(String, Attribution)],
+) -> Result<(), String> {
+    let mut conn = open(project)?;
+    let tx = conn.transaction().map_err(db_error)?;
+    for (relative_path, attribution) in attributions {
+        if attribution.is_empty() {
+            continue;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO attribution (relative_path, license, source, creator)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                relative_path,
+                attribution.license,
+                attribution.source,
+                attribution.creator,
+            ],
+        )
+        .map_err(db_error)?;
+    }
+    tx.commit().map_err(db_error)
+}
+
+/// Get the captioned media files of a project with their captions
+pub fn captions(project: &Path) -> Result<Vec<(MediaFile, String)>, String> {
+    let mut conn = open(project)?;
+    refresh(&mut conn, project)?;
+
+    Ok(query_files(&conn, project, "WHERE caption IS NOT NULL")?
+        .into_iter()
+        .filter_map(|(file, caption)| caption.map(|caption| (file, caption)))
+        .collect())
+}
+
+/// Count every (lowercase) tag of a project, along with the number of captioned files
+pub fn tag_counts(project: &Path) -> Result<(Vec<TagCount>, usize), String> {
+    let mut conn = open(project)?;
+    let summary = refresh(&mut conn, project)?;
+
+    let mut query = conn
+        .prepare("SELECT tag, COUNT(*), COUNT(DISTINCT relative_path) FROM tags GROUP BY tag")
+        .map_err(db_error)?;
+    let counts = query
+        .query_map([], |row| {
+            Ok(TagCount {
+                tag: row.get(0)?,
+                count: row.get::<_, i64>(1)? as usize,
+                files: row.get::<_, i64>(2)? as usize,
+            })
+        })
+        .map_err(db_error)?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(db_error)?;
+
+    Ok((counts, summary.captioned))
+}
+
+/// Refresh the index of a project and hash the files that have no hash yet
+///
+/// Hashing reads every new file in full, so it only happens here rather than on
+/// every listing. Stops with the copy module's cancellation error when `cancel` is set.
+pub fn refresh_with_hashes(
+    project: &Path,
+    cancel: &AtomicBool,
+    progress: impl Fn(usize, usize) + Sync,
+) -> Result<IndexSummary, String> {
+    let mut conn = open(project)?;
+    let mut summary = refresh(&mut conn, project)?;
+
+    let unhashed: Vec<String> = {
+        let mut query = conn
+            .prepare("SELECT relative_path FROM files WHERE hash IS NULL")
+            .map_err(db_error)?;
+        let rows = query.query_map([], |row| row.get(0)).map_err(db_error)?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(db_error)?
+    };
+
+    let read_path = paths::extended_path(project);
+    let total = unhashed.len();
+    let done = AtomicUsize::new(0);
+    let hashes = pool::install(|| {
+        unhashed
+            .into_par_iter()
+            .map(|relative_path| {
+                let hash = dedup::hash_file(&read_path.join(&relative_path), cancel)?;
+                let count = done.fetch_add(1, Ordering::SeqCst) + 1;
+                progress(count, total);
+                Ok((relative_path, hash))
+            })
+            .collect::<Result<Vec<(String, String)>, String>>()
+    })?;
+
+    let tx = conn.transaction().map_err(db_error)?;
+    for (relative_path, hash) in &hashes {
+        tx.execute(
+            "UPDATE files SET hash = ?2 WHERE relative_path = ?1",
+            params![relative_path, hash],
+        )
+        .map_err(db_error)?;
+    }
+    tx.commit().map_err(db_error)?;
+
+    summary.hashed = hashes.len();
+    Ok(summary)
+}
+
+/// Groups of byte-identical files, as relative paths, using the hashes stored by
+/// [`refresh_with_hashes`]; files not hashed yet are left out
+pub fn duplicate_groups(project: &Path) -> Result<Vec<Vec<String>>, String> {
+    let conn = open(project)?;
+    let mut query = conn
+        .prepare(
+            "SELECT hash, relative_path FROM files WHERE hash IN
+                (SELECT hash FROM files WHERE hash IS NOT NULL GROUP BY hash HAVING COUNT(*) > 1)
+            ORDER BY hash, relative_path",
+        )
+        .map_err(db_error)?;
+    let rows = query
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))
+        .map_err(db_error)?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()
+        .map_err(db_error)?;
+
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    let mut last_hash = None;
+    for (hash, relative_path) in rows {
+        if last_hash.as_ref() != Some(&hash) {
+            groups.push(Vec::new());
+            last_hash = Some(hash);
+        }
+        if let Some(group) = groups.last_mut() {
+            group.push(relative_path);
+        }
+    }
+
+    Ok(groups)
+}
+
+/// An image whose embedding is missing or out of date
+#[derive(Debug)]
+pub struct PendingImage {
+    pub relative_path: String,
+    pub path: String,
+    pub mtime: i64,
+}
+
+/// Images of a project without an up-to-date embedding from `model`
+pub fn images_without_embeddings(project: &Path, model: &str) -> Result<Vec<PendingImage>, String> {
+    let mut conn = open(project)?;
+    refresh(&mut conn, project)?;
+
+    let read_path = paths::extended_path(project);
+    let mut query = conn
+        .prepare(
+            "SELECT files.relative_path, files.mtime FROM files
+             LEFT JOIN embeddings ON embeddings.relative_path = files.relative_path
+             WHERE files.file_type = 'image' AND (embeddings.relative_path IS NULL
+                OR embeddings.model != ?1 OR embeddings.mtime != files.mtime)
+             ORDER BY files.name",
+        )
+        .map_err(db_error)?;
+    let rows = query
+        .query_map([model], |row| {
+            let relative_path: String = row.get(0)?;
+            Ok(PendingImage {
+                path: paths::display_path(&read_path.join(&relative_path)),
+                relative_path,
+                mtime: row.get(1)?,
+            })
+        })
+        .map_err(db_error)?;
+
+    rows.collect::<rusqlite::Result<Vec<_>>>().map_err(db_error)
+}
+
+/// Save embeddings computed by `model`, replacing older ones of the same images
+pub fn store_embeddings(
+    project: &Path,
+    model: &str,
+    embeddings: &[(PendingImage, Vec<f32>)],
+) -> Result<(), String> {
+    let mut conn = open(project)?;
+    let tx = conn.transaction().map_err(db_error)?;
+
+    for (image, vector) in embeddings {
+        let bytes: Vec<u8> = vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+        tx.execute(
+            "INSERT OR REPLACE INTO embeddings (relative_path, model, mtime, vector)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![image.relative_path, model, image.mtime, bytes],
+        )
+        .map_err(db_error)?;
+    }
+
+    tx.commit().map_err(db_error)
+}
+
+/// Get the images of a project that have an up-to-date embedding from `model`
+pub fn embeddings(project: &Path, model: &str) -> Result<Vec<(MediaFile, Vec<f32>)>, String> {
+    let conn = open(project)?;
+    let read_path = paths::extended_path(project);
+    let sql = format!(
+        "SELECT {}, embeddings.vector FROM files
+         JOIN embeddings ON embeddings.relative_path = files.relative_path
+         WHERE embeddings.model = ?1 AND embeddings.mtime = files.mtime",
+        FILE_COLUMNS
+    );
+
+    let mut query = conn.prepare(&sql).map_err(db_error)?;
+    let rows = query
+        .query_map([model], |row| {
+            let (file, _) = row_to_file(&read_path, row)?;
+            let bytes: Vec<u8> = row.get(10)?;
+            let vector = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            Ok((file, vector))
+        })
+        .map_err(db_error)?;
+
+    rows.collect::<rusqlite::Result<Vec<_>>>().map_err(db_error)
+}
+
+/// Images of a project without up-to-date subject counts from `model`
+pub fn images_without_subjects(project: &Path, model: &str) -> Result<Vec<PendingImage>, String> {
+    let mut conn = open(project)?;
+    refresh(&mut conn, project)?;
+
+    let read_path = paths::extended_path(project);
+    let mut query = conn
+        .prepare(
+            "SELECT files.relative_path, files.mtime FROM files
+             LEFT JOIN subjects ON subjects.relative_path = files.relative_path
+             WHERE files.file_type = 'image' AND (subjects.relative_path IS NULL
+                OR subjects.model != ?1 OR subjects.mtime != files.mtime)
+             ORDER BY files.name",
+        )
+        .map_err(db_error)?;
+    let rows = query
+        .query_map([model], |row| {
+            let relative_path: String = row.get(0)?;
+            Ok(PendingImage {
+                path: paths::display_path(&read_path.join(&relative_path)),
+                relative_path,
+                mtime: row.get(1)?,
+            })
+        })
+        .map_err(db_error)?;
+
+    rows.collect::<rusqlite::Result<Vec<_>>>().map_err(db_error)
+}
+
+/// Save subject counts made by `model`, replacing older ones of the same images
+pub fn store_subjects(
+    project: &Path,
+    model: &str,
+    counts: &[(PendingImage, SubjectCount)],
+) -> Result<(), String> {
+    let mut conn = open(project)?;
+    let tx = conn.transaction().map_err(db_error)?;
+
+    for (image, count) in counts {
+        tx.execute(
+            "INSERT OR REPLACE INTO subjects (relative_path, model, mtime, faces, persons)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                image.relative_path,
+                model,
+                image.mtime,
+                count.faces,
+                count.persons
+            ],
+        )
+        .map_err(db_error)?;
+    }
+
+    tx.commit().map_err(db_error)
+}
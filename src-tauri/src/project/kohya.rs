@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::index;
+use super::settings;
+use crate::captions::stats::count_tags;
+use crate::file_system::atomic;
+use crate::file_system::commands::MediaFile;
+use crate::file_system::copy::{copy_file, ImportMode};
+use crate::jobs::manager::Job;
+
+/// Names of the config files written next to the image folder
+const DATASET_CONFIG_FILE: &str = "dataset.toml";
+const TRAINING_CONFIG_FILE: &str = "training.toml";
+
+/// Steps per epoch the derived repeat count aims for, at batch size 1
+const TARGET_STEPS_PER_EPOCH: usize = 200;
+const MAX_REPEATS: usize = 50;
+
+/// Captions with at least this many comma-separated tags on average are treated as
+/// tag lists, which are shuffled during training
+const TAG_STYLE_MIN_TAGS: f64 = 4.0;
+
+const BUCKET_RESO_STEPS: u32 = 64;
+
+/// Options of a kohya export; anything left unset is derived from the dataset
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct KohyaOptions {
+    pub repeats: Option<u32>,
+    /// Folder name and class token (default: the project's trigger word, or its name)
+    pub concept: Option<String>,
+    pub resolution: Option<u32>,
+    /// Also write a LoRA training config skeleton for sd-scripts' `--config_file`
+    pub training_config: bool,
+    /// Model the training config starts from
+    pub base_model: Option<String>,
+}
+
+/// Dataset settings derived from the project's files and captions
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetSettings {
+    pub resolution: u32,
+    pub repeats: u32,
+    pub enable_bucket: bool,
+    pub min_bucket_reso: u32,
+    pub max_bucket_reso: u32,
+    /// Set when many images are smaller than the resolution, so they aren't upscaled
+    pub bucket_no_upscale: bool,
+    pub shuffle_caption: bool,
+    /// Leading tags kept in place while shuffling, e.g. the trigger word
+    pub keep_tokens: u32,
+}
+
+/// Result of a kohya export
+#[derive(Debug, Serialize)]
+pub struct KohyaExport {
+    pub files: usize,
+    pub image_dir: String,
+    pub dataset_config: String,
+    pub training_config: Option<String>,
+    pub settings: DatasetSettings,
+}
+
+#[derive(Serialize)]
+struct DatasetConfig {
+    general: GeneralSection,
+    datasets: Vec<DatasetSection>,
+}
+
+#[derive(Serialize)]
+struct GeneralSection {
+    shuffle_caption: bool,
+    caption_extension: &'static str,
+    keep_tokens: u32,
+}
+
+#[derive(Serialize)]
+struct DatasetSection {
+    resolution: u32,
+    batch_size: u32,
+    enable_bucket: bool,
+    min_bucket_reso: u32,
+    max_bucket_reso: u32,
+    bucket_reso_steps: u32,
+    bucket_no_upscale: bool,
+    subsets: Vec<SubsetSection>,
+}
+
+#[derive(Serialize)]
+struct SubsetSection {
+    image_dir: String,
+    class_tokens: String,
+    num_repeats: u32,
+}
+
+#[derive(Serialize)]
+struct TrainingConfig {
+    pretrained_model_name_or_path: String,
+    dataset_config: String,
+    output_dir: String,
+    output_name: String,
+    save_model_as: &'static str,
+    network_module: &'static str,
+    network_dim: u32,
+    network_alpha: u32,
+    learning_rate: f64,
+    optimizer_type: &'static str,
+    lr_scheduler: &'static str,
+    max_train_epochs: u32,
+    save_every_n_epochs: u32,
+    mixed_precision: &'static str,
+    save_precision: &'static str,
+    cache_latents: bool,
+    gradient_checkpointing: bool,
+    sdpa: bool,
+}
+
+fn round_to_steps(value: u32) -> u32 {
+    (value / BUCKET_RESO_STEPS).max(1) * BUCKET_RESO_STEPS
+}
+
+/// Derive the dataset settings from the image sizes and captions of a project
+fn derive_settings(
+    media: &[MediaFile],
+    captions: &HashMap<String, String>,
+    concept: &str,
+    options: &KohyaOptions,
+) -> DatasetSettings {
+    let sizes: Vec<(u32, u32)> = media
+        .iter()
+        .filter_map(|f| f.width.zip(f.height))
+        .filter(|&(w, h)| w > 0 && h > 0)
+        .collect();
+    let mut short_sides: Vec<u32> = sizes.iter().map(|&(w, h)| w.min(h)).collect();
+    short_sides.sort_unstable();
+    let median = short_sides.get(short_sides.len() / 2).copied().unwrap_or(0);
+
+    // The resolutions SD 1.5, SD 2 and SDXL were trained at
+    let resolution = options.resolution.unwrap_or(match median {
+        1024.. => 1024,
+        768.. => 768,
+        _ => 512,
+    });
+    let repeats = options.repeats.unwrap_or_else(|| {
+        TARGET_STEPS_PER_EPOCH
+            .div_ceil(media.len().max(1))
+            .clamp(1, MAX_REPEATS) as u32
+    });
+
+    let square = |&(w, h): &(u32, u32)| (w as f64 / h as f64 - 1.0).abs() < 0.05;
+    let smaller = short_sides
+        .iter()
+        .filter(|&&side| side < resolution)
+        .count();
+    let longest = sizes
+        .iter()
+        .map(|&(w, h)| w.max(h))
+        .max()
+        .unwrap_or(resolution);
+
+    let tags: Vec<usize> = captions.values().map(|c| count_tags(c)).collect();
+    let average_tags = tags.iter().sum::<usize>() as f64 / tags.len().max(1) as f64;
+    let shuffle_caption = average_tags >= TAG_STYLE_MIN_TAGS;
+    let led_by_concept = captions
+        .values()
+        .filter(|c| c.trim_start().starts_with(concept))
+        .count();
+
+    DatasetSettings {
+        resolution,
+        repeats,
+        enable_bucket: !sizes.iter().all(square),
+        min_bucket_reso: round_to_steps((resolution / 2).max(256)),
+        max_bucket_reso: round_to_steps(longest.clamp(resolution, resolution * 2)),
+        bucket_no_upscale: smaller * 4 > short_sides.len(),
+        shuffle_caption,
+        keep_tokens: u32::from(shuffle_caption && led_by_concept * 2 > captions.len()),
+    }
+}
+
+fn write_toml(path: &Path, header: &str, value: &impl Serialize) -> Result<(), String> {
+    let body = toml::to_string_pretty(value).map_err(|e| e.to_string())?;
+    atomic::write_atomic(path, format!("{}\n\n{}", header, body), false)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Export a project for kohya's sd-scripts
+///
+/// The media files are copied into a `<repeats>_<concept>` folder of `output`, each with
+/// a `.txt` caption beside it (nested files are flattened into the folder), along with a
+/// dataset config whose resolution, repeats, buckets and caption shuffling are derived
+/// from the images and captions. With `training_config` set, a LoRA training config
+/// pointing at it is written too.
+pub fn export(
+    job: &Job,
+    project: &Path,
+    output: &Path,
+    options: &KohyaOptions,
+) -> Result<KohyaExport, String> {
+    if output.starts_with(project) {
+        return Err("The output directory can't be inside the project".to_string());
+    }
+
+    let concept = options
+        .concept
+        .clone()
+        .or_else(|| {
+            settings::load_settings(project)
+                .ok()
+                .and_then(|s| s.trigger_word)
+                .filter(|t| !t.trim().is_empty())
+        })
+        .or_else(|| project.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "dataset".to_string());
+
+    let media = index::list_files(project)?;
+    let captions: HashMap<String, String> = index::captions(project)?
+        .into_iter()
+        .map(|(file, caption)| (file.relative_path, caption))
+        .collect();
+    let settings = derive_settings(&media, &captions, &concept, options);
+
+    let folder = output.join(format!("{}_{}", settings.repeats, concept));
+    fs::create_dir_all(&folder)
+        .map_err(|e| format!("Failed to create {}: {}", folder.display(), e))?;
+
+    let total = media.len() as u64;
+    for (index, file) in media.iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress(index as u64, total, Some(file.relative_path.clone()));
+
+        let name = file.relative_path.replace(['/', '\\'], "_");
+        let target = folder.join(&name);
+        copy_file(ImportMode::Copy, Path::new(&file.path), &target)?;
+
+        if let Some(caption) = captions.get(&file.relative_path) {
+            let caption_path = target.with_extension("txt");
+            fs::write(&caption_path, caption.trim())
+                .map_err(|e| format!("Failed to write {}: {}", caption_path.display(), e))?;
+        }
+    }
+    job.progress(total, total, None);
+
+    let image_dir = folder.to_string_lossy().to_string();
+    let dataset_config = output.join(DATASET_CONFIG_FILE);
+    write_toml(
+        &dataset_config,
+        "# sd-scripts dataset config, pass it with --dataset_config",
+        &DatasetConfig {
+            general: GeneralSection {
+                shuffle_caption: settings.shuffle_caption,
+                caption_extension: ".txt",
+                keep_tokens: settings.keep_tokens,
+            },
+            datasets: vec![DatasetSection {
+                resolution: settings.resolution,
+                batch_size: 1,
+                enable_bucket: settings.enable_bucket,
+                min_bucket_reso: settings.min_bucket_reso,
+                max_bucket_reso: settings.max_bucket_reso,
+                bucket_reso_steps: BUCKET_RESO_STEPS,
+                bucket_no_upscale: settings.bucket_no_upscale,
+                subsets: vec![SubsetSection {
+                    image_dir: image_dir.clone(),
+                    class_tokens: concept.clone(),
+                    num_repeats: settings.repeats,
+                }],
+            }],
+        },
+    )?;
+
+    let training_config = if options.training_config {
+        let path = output.join(TRAINING_CONFIG_FILE);
+        let script = if settings.resolution >= 1024 {
+            "sdxl_train_network.py"
+        } else {
+            "train_network.py"
+        };
+        write_toml(
+            &path,
+            &format!(
+                "# LoRA training config skeleton, run with:\n# accelerate launch {} --config_file {}",
+                script,
+                path.display()
+            ),
+            &TrainingConfig {
+                pretrained_model_name_or_path: options
+                    .base_model
+                    .clone()
+                    .unwrap_or_else(|| "path/to/base_model.safetensors".to_string()),
+                dataset_config: dataset_config.to_string_lossy().to_string(),
+                output_dir: output.join("output").to_string_lossy().to_string(),
+                output_name: concept.clone(),
+                save_model_as: "safetensors",
+                network_module: "networks.lora",
+                network_dim: 16,
+                network_alpha: 8,
+                learning_rate: 1e-4,
+                optimizer_type: "AdamW8bit",
+                lr_scheduler: "cosine",
+                max_train_epochs: 10,
+                save_every_n_epochs: 1,
+                mixed_precision: "bf16",
+                save_precision: "bf16",
+                cache_latents: true,
+                gradient_checkpointing: true,
+                sdpa: true,
+            },
+        )?;
+        Some(path.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    Ok(KohyaExport {
+        files: media.len(),
+        image_dir,
+        dataset_config: dataset_config.to_string_lossy().to_string(),
+        training_config,
+        settings,
+    })
+}
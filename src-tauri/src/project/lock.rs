@@ -0,0 +1,222 @@
+use fs2::FileExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+use super::{metadata_dir, settings, METADATA_DIR};
+use crate::file_system::paths;
+
+/// File in the project metadata the holder of a project keeps locked
+const LOCK_FILE: &str = "lock";
+
+/// File next to the lock naming its holder; the lock itself can't be read on every
+/// platform while it is held
+const OWNER_FILE: &str = "lock.json";
+
+/// Attempts at taking a lock, a moment apart; [`ensure_not_in_use`] in other processes
+/// holds it shared for an instant
+const LOCK_ATTEMPTS: u32 = 5;
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(20);
+
+/// Who holds a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    /// `app window` or `command line run`
+    pub holder: String,
+    pub since: String,
+}
+
+/// A lock this process holds, with the open lock file keeping it
+struct Held {
+    file: File,
+    /// Label of the app window holding it, None for a command line run
+    window: Option<String>,
+}
+
+/// Locks this process holds
+///
+/// The operating system releases a lock when its process exits, however it exits, so
+/// a lock file left behind by a crash is stale as soon as nobody holds it. The windows
+/// of the app share a process, so each lock also records its window and the windows
+/// shut each other out here rather than through the lock file.
+static HELD: Lazy<Mutex<HashMap<PathBuf, Held>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn open_lock(project: &Path) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(metadata_dir(project)?.join(LOCK_FILE))
+        .map_err(|e| format!("Failed to open project lock: {}", e))
+}
+
+fn in_use_error(project: &Path) -> String {
+    let owner = fs::read_to_string(project.join(METADATA_DIR).join(OWNER_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str::<LockOwner>(&content).ok());
+    match owner {
+        Some(owner) => format!(
+            "Project in use by another {} (process {}) since {}. Close it there first.",
+            owner.holder, owner.pid, owner.since
+        ),
+        None => "Project in use by another window or command line run. Close it there first."
+            .to_string(),
+    }
+}
+
+/// Key of a project in [`HELD`]; a second handle on a held lock would fail to lock on
+/// Windows, so the same project must always map to the same key
+fn key(project: &Path) -> PathBuf {
+    PathBuf::from(paths::display_path(project))
+}
+
+fn is_held(project: &Path) -> bool {
+    HELD.lock()
+        .map(|held| held.contains_key(&key(project)))
+        .unwrap_or(false)
+}
+
+/// Lock a project for a window of this process (None for a command line run), so
+/// other app windows and command line runs refuse to change it until it is released
+///
+/// `holder` describes this process in the message other processes get. Locking a
+/// project the same window already holds does nothing; another window holding it is
+/// an error.
+pub fn acquire(project: &Path, holder: &str, window: Option<&str>) -> Result<(), String> {
+    let owner = HELD
+        .lock()
+        .map_err(|e| e.to_string())?
+        .get(&key(project))
+        .map(|held| held.window.clone());
+    match owner {
+        Some(owner) if owner.as_deref() == window => return Ok(()),
+        Some(_) => {
+            return Err("Project is open in another window. Close it there first.".to_string())
+        }
+        None => {}
+    }
+
+    let file = open_lock(project)?;
+    let mut attempt = 1;
+    while file.try_lock_exclusive().is_err() {
+        if attempt == LOCK_ATTEMPTS {
+            return Err(in_use_error(project));
+        }
+        attempt += 1;
+        thread::sleep(LOCK_RETRY_DELAY);
+    }
+
+    let owner = LockOwner {
+        pid: std::process::id(),
+        holder: holder.to_string(),
+        since: settings::now_timestamp(),
+    };
+    let content = serde_json::to_string_pretty(&owner).map_err(|e| e.to_string())?;
+    if let Err(e) = fs::write(project.join(METADATA_DIR).join(OWNER_FILE), content) {
+        warn!(
+            "Failed to record the holder of {}: {}",
+            project.display(),
+            e
+        );
+    }
+
+    HELD.lock().map_err(|e| e.to_string())?.insert(
+        key(project),
+        Held {
+            file,
+            window: window.map(str::to_string),
+        },
+    );
+    Ok(())
+}
+
+/// Release the lock this process holds on a project, if any, whichever window holds it
+pub fn release(project: &Path) {
+    let held = HELD
+        .lock()
+        .ok()
+        .and_then(|mut held| held.remove(&key(project)));
+    if let Some(held) = held {
+        let _ = fs::remove_file(project.join(METADATA_DIR).join(OWNER_FILE));
+        let _ = FileExt::unlock(&held.file);
+    }
+}
+
+/// Release the locks of projects matching `filter`
+fn release_where(filter: impl Fn(&Path, &Held) -> bool) {
+    let projects: Vec<PathBuf> = HELD
+        .lock()
+        .map(|held| {
+            held.iter()
+                .filter(|(project, held)| filter(project, held))
+                .map(|(project, _)| project.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    for project in projects {
+        release(&project);
+    }
+}
+
+/// Release every lock a window holds except the one on `keep`, if given
+pub fn release_others(window: &str, keep: Option<&Path>) {
+    let keep = keep.map(key);
+    release_where(|project, held| {
+        held.window.as_deref() == Some(window) && keep.as_deref() != Some(project)
+    });
+}
+
+/// Release every lock this process holds, in every window
+pub fn release_all() {
+    release_where(|_, _| true);
+}
+
+/// Refuse to change a project another process holds
+///
+/// A lock file nobody holds, e.g. after a crash, doesn't count. The lock is probed
+/// shared, so checks don't shut each other out the way an exclusive probe would.
+pub fn ensure_not_in_use(project: &Path) -> Result<(), String> {
+    if is_held(project) || !project.join(METADATA_DIR).join(LOCK_FILE).is_file() {
+        return Ok(());
+    }
+
+    let file = open_lock(project)?;
+    match file.try_lock_shared() {
+        Ok(()) => {
+            let _ = FileExt::unlock(&file);
+            Ok(())
+        }
+        Err(_) => Err(in_use_error(project)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_hold_projects_apart() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+
+        acquire(first.path(), "app window", Some("main")).unwrap();
+        acquire(first.path(), "app window", Some("main")).unwrap();
+        assert!(acquire(first.path(), "app window", Some("other")).is_err());
+
+        // Opening a project in one window leaves the other windows' projects locked
+        acquire(second.path(), "app window", Some("other")).unwrap();
+        release_others("main", None);
+        assert!(acquire(second.path(), "app window", Some("main")).is_err());
+        acquire(first.path(), "app window", Some("other")).unwrap();
+
+        release_all();
+        assert!(!is_held(first.path()) && !is_held(second.path()));
+    }
+}
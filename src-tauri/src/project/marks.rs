@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use crate::semantic::subjects::SubjectCount;
+
+/// Color label of a file, as in photo managers
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorFlag {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Gray,
+}
+
+impl ColorFlag {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ColorFlag::Red => "red",
+            ColorFlag::Orange => "orange",
+            ColorFlag::Yellow => "yellow",
+            ColorFlag::Green => "green",
+            ColorFlag::Blue => "blue",
+            ColorFlag::Purple => "purple",
+            ColorFlag::Gray => "gray",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [
+            ColorFlag::Red,
+            ColorFlag::Orange,
+            ColorFlag::Yellow,
+            ColorFlag::Green,
+            ColorFlag::Blue,
+            ColorFlag::Purple,
+            ColorFlag::Gray,
+        ]
+        .into_iter()
+        .find(|flag| flag.as_str() == value)
+    }
+}
+
+/// Curation marks of a file: a rating, a color flag and a favorite bit
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileMark {
+    /// 1-5 stars
+    pub rating: Option<u8>,
+    pub flag: Option<ColorFlag>,
+    pub favorite: bool,
+}
+
+impl FileMark {
+    pub fn is_empty(&self) -> bool {
+        *self == FileMark::default()
+    }
+}
+
+/// Which files a listing or export includes, by their marks and detected subjects;
+/// unset criteria match every file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarkFilter {
+    pub min_rating: Option<u8>,
+    /// Files with any of these flags
+    pub flags: Option<Vec<ColorFlag>>,
+    pub favorite: Option<bool>,
+    /// Files with exactly this many faces, or persons, e.g. 1 for a character dataset;
+    /// files whose subjects weren't detected don't match
+    pub faces: Option<u32>,
+    pub persons: Option<u32>,
+}
+
+impl MarkFilter {
+    pub fn matches(&self, mark: Option<&FileMark>, subjects: Option<&SubjectCount>) -> bool {
+        let default = FileMark::default();
+        let mark = mark.unwrap_or(&default);
+
+        let rating = self
+            .min_rating
+            .is_none_or(|min| mark.rating.is_some_and(|r| r >= min));
+        let flag = self
+            .flags
+            .as_ref()
+            .is_none_or(|flags| mark.flag.is_some_and(|f| flags.contains(&f)));
+        let favorite = self
+            .favorite
+            .is_none_or(|favorite| mark.favorite == favorite);
+
+        let faces = self
+            .faces
+            .is_none_or(|faces| subjects.is_some_and(|s| s.faces == Some(faces)));
+        let persons = self
+            .persons
+            .is_none_or(|persons| subjects.is_some_and(|s| s.persons == persons));
+
+        rating && flag && favorite && faces && persons
+    }
+}
+
+/// Check that a rating is within 1-5
+pub fn validate_rating(rating: Option<u8>) -> Result<(), String> {
+    match rating {
+        Some(rating) if !(1..=5).contains(&rating) => {
+            Err(format!("Ratings go from 1 to 5, not {}", rating))
+        }
+        _ => Ok(()),
+    }
+}
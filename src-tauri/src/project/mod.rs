@@ -0,0 +1,50 @@
+pub mod attribution;
+pub mod class_images;
+pub mod commands;
+pub mod index;
+pub mod kohya;
+pub mod lock;
+pub mod marks;
+pub mod overview;
+pub mod report;
+pub mod settings;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the hidden directory inside each project that holds app-managed state
+/// (operation journal, undo backups, ...). It is skipped by listings and exports.
+pub const METADATA_DIR: &str = ".spacecat";
+
+/// Name of the per-project settings file stored in the project root
+pub const PROJECT_FILE: &str = "project.json";
+
+/// Resolve the project root that owns a media or caption file
+///
+/// Walks up from the file looking for an existing metadata directory or project file
+/// and falls back to the file's parent directory, since projects are flat folders of media.
+pub fn project_root_for(path: &Path) -> PathBuf {
+    let start = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+
+    for ancestor in start.ancestors() {
+        if ancestor.join(METADATA_DIR).is_dir() || ancestor.join(PROJECT_FILE).is_file() {
+            return ancestor.to_path_buf();
+        }
+    }
+
+    start.to_path_buf()
+}
+
+/// Get the metadata directory of a project, creating it if needed
+pub fn metadata_dir(project: &Path) -> Result<PathBuf, String> {
+    let dir = project.join(METADATA_DIR);
+    if !dir.exists() {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create project metadata directory: {}", e))?;
+    }
+    Ok(dir)
+}
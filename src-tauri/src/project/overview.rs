@@ -0,0 +1,130 @@
+use serde::Serialize;
+use std::path::Path;
+
+use super::index;
+use super::report::FileCounts;
+use super::settings::{self, ExportRecord};
+use crate::jobs::journal::InterruptedJob;
+use crate::jobs::manager::{self as jobs, JobInfo};
+use crate::undo::journal::{self as undo, OperationKind};
+
+/// Number of recent operations listed in an overview
+const RECENT_ACTIVITY: usize = 5;
+
+/// An operation recently applied to a project's files
+#[derive(Debug, Serialize)]
+pub struct Activity {
+    pub kind: OperationKind,
+    pub description: String,
+    pub timestamp: String,
+    /// Number of files the operation touched
+    pub files: usize,
+}
+
+/// What the projects screen shows about a project
+#[derive(Debug, Serialize)]
+pub struct ProjectOverview {
+    pub name: String,
+    pub files: FileCounts,
+    pub captioned: usize,
+    /// Share of the files with a caption, from 0 to 100
+    pub caption_coverage: f64,
+    /// Latest operations first
+    pub recent_activity: Vec<Activity>,
+    /// Queued and running jobs mentioning the project
+    pub active_jobs: Vec<JobInfo>,
+    /// Interrupted jobs working on the project's files, waiting to be resumed
+    pub interrupted_jobs: usize,
+    pub last_export: Option<ExportRecord>,
+    pub created: Option<String>,
+    pub last_opened: Option<String>,
+}
+
+/// Whether an interrupted job worked on a project, by its arguments or its items
+fn works_on(job: &InterruptedJob, project: &Path) -> bool {
+    let mentions = |value: &serde_json::Value| {
+        value
+            .as_str()
+            .is_some_and(|text| Path::new(text).starts_with(project))
+    };
+    job.params
+        .as_object()
+        .is_some_and(|params| params.values().any(mentions))
+        || job
+            .remaining
+            .iter()
+            .any(|item| Path::new(item).starts_with(project))
+}
+
+/// Gather the overview of a project from its index, settings, operation journal and
+/// jobs
+///
+/// `interrupted` are the journals of jobs cut short by the app exiting, of any
+/// project.
+pub fn build_overview(
+    project: &Path,
+    interrupted: &[InterruptedJob],
+) -> Result<ProjectOverview, String> {
+    let project_settings = settings::load_settings(project)?;
+    let media = index::list_files(project)?;
+
+    let mut files = FileCounts::default();
+    let mut captioned = 0;
+    for file in &media {
+        files.total += 1;
+        files.total_bytes += file.size;
+        match file.file_type.as_str() {
+            "image" => files.images += 1,
+            "video" => files.videos += 1,
+            _ => {}
+        }
+        let extension = Path::new(&file.name)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        *files.by_extension.entry(extension).or_default() += 1;
+        if file.has_caption {
+            captioned += 1;
+        }
+    }
+    let caption_coverage = if files.total == 0 {
+        0.0
+    } else {
+        captioned as f64 * 100.0 / files.total as f64
+    };
+
+    let recent_activity = undo::load_journal(project)?
+        .into_iter()
+        .rev()
+        .take(RECENT_ACTIVITY)
+        .map(|entry| Activity {
+            kind: entry.kind,
+            description: entry.description,
+            timestamp: entry.timestamp,
+            files: entry.files.len(),
+        })
+        .collect();
+
+    let path = project.to_string_lossy().to_string();
+    let active_jobs = jobs::list()
+        .into_iter()
+        .filter(|job| job.finished.is_none() && job.description.contains(&path))
+        .collect();
+    let interrupted_jobs = interrupted
+        .iter()
+        .filter(|job| works_on(job, project))
+        .count();
+
+    Ok(ProjectOverview {
+        name: project_settings.name,
+        files,
+        captioned,
+        caption_coverage,
+        recent_activity,
+        active_jobs,
+        interrupted_jobs,
+        last_export: project_settings.last_export,
+        created: project_settings.created,
+        last_opened: project_settings.last_opened,
+    })
+}
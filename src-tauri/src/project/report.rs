@@ -0,0 +1,496 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::attribution::Attribution;
+use super::index;
+use super::metadata_dir;
+use super::settings::now_timestamp;
+use crate::captions::stats::{self, CaptionStats, LengthStats};
+use crate::file_system::{atomic, copy};
+use crate::jobs::manager::{self as jobs, Job};
+
+/// Images whose shorter side is below this are flagged as low resolution
+const MIN_SHORT_SIDE: u32 = 512;
+
+/// Files more elongated than this (long side / short side) are flagged
+const MAX_ASPECT: f64 = 2.5;
+
+/// Videos shorter than this, in seconds, are flagged
+const MIN_VIDEO_SECS: f64 = 1.0;
+
+/// Upper bounds of the resolution histogram, by shorter side
+const RESOLUTION_BUCKETS: &[u32] = &[256, 512, 768, 1024, 1536, 2048];
+
+/// Aspect ratios files are grouped by, as (label, width / height)
+const ASPECT_RATIOS: &[(&str, f64)] = &[
+    ("9:16", 9.0 / 16.0),
+    ("2:3", 2.0 / 3.0),
+    ("3:4", 3.0 / 4.0),
+    ("1:1", 1.0),
+    ("4:3", 4.0 / 3.0),
+    ("3:2", 3.0 / 2.0),
+    ("16:9", 16.0 / 9.0),
+];
+
+/// Format a dataset report is written in
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Html,
+    Json,
+}
+
+impl ReportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Html => "html",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+/// Number of files of each kind
+#[derive(Debug, Default, Serialize)]
+pub struct FileCounts {
+    pub total: usize,
+    pub images: usize,
+    pub videos: usize,
+    pub total_bytes: u64,
+    /// Files per lowercase extension
+    pub by_extension: BTreeMap<String, usize>,
+}
+
+/// A bar of a histogram
+#[derive(Debug, Serialize)]
+pub struct Bucket {
+    pub label: String,
+    pub count: usize,
+}
+
+/// A file that may hurt training, with the reasons
+#[derive(Debug, Serialize)]
+pub struct FlaggedFile {
+    pub relative_path: String,
+    pub reasons: Vec<String>,
+}
+
+/// Licensing of a project's files, for checking what redistributing it requires
+#[derive(Debug, Serialize)]
+pub struct LicensingSummary {
+    /// Defaults for every file of the project
+    pub project: Attribution,
+    /// Files per license, `unknown` for files without one
+    pub licenses: Vec<Bucket>,
+    /// Files without a license, as relative paths
+    pub unlicensed: Vec<String>,
+    /// Files with neither a creator nor a source, as relative paths
+    pub uncredited: Vec<String>,
+}
+
+/// Pre-training sanity check of a project
+#[derive(Debug, Serialize)]
+pub struct DatasetReport {
+    pub project: String,
+    pub generated: String,
+    pub files: FileCounts,
+    /// Files by shorter side
+    pub resolutions: Vec<Bucket>,
+    /// Files by nearest common aspect ratio
+    pub aspect_ratios: Vec<Bucket>,
+    pub captions: CaptionStats,
+    /// Sets of byte-identical files, as relative paths
+    pub duplicates: Vec<Vec<String>>,
+    pub flagged: Vec<FlaggedFile>,
+    pub licensing: LicensingSummary,
+}
+
+fn resolution_label(short_side: Option<u32>) -> String {
+    let Some(short_side) = short_side else {
+        return "unknown".to_string();
+    };
+    let mut lower = 0;
+    for &upper in RESOLUTION_BUCKETS {
+        if short_side < upper {
+            return match lower {
+                0 => format!("< {}", upper),
+                _ => format!("{}–{}", lower, upper - 1),
+            };
+        }
+        lower = upper;
+    }
+    format!("≥ {}", lower)
+}
+
+fn aspect_label(width: u32, height: u32) -> String {
+    let ratio = width as f64 / height as f64;
+    let (tallest, widest) = (ASPECT_RATIOS[0], ASPECT_RATIOS[ASPECT_RATIOS.len() - 1]);
+    // A little past the extremes counts as more elongated than any common ratio
+    if ratio < tallest.1 * 0.9 {
+        return format!("taller than {}", tallest.0);
+    }
+    if ratio > widest.1 * 1.1 {
+        return format!("wider than {}", widest.0);
+    }
+    ASPECT_RATIOS
+        .iter()
+        .min_by(|a, b| {
+            (a.1.ln() - ratio.ln())
+                .abs()
+                .total_cmp(&(b.1.ln() - ratio.ln()).abs())
+        })
+        .map(|(label, _)| label.to_string())
+        .unwrap_or_default()
+}
+
+/// Count files per label, keeping the order labels appear in `order`
+fn histogram(labels: Vec<String>, order: &[String]) -> Vec<Bucket> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for label in labels {
+        *counts.entry(label).or_default() += 1;
+    }
+    let mut buckets: Vec<Bucket> = order
+        .iter()
+        .filter_map(|label| {
+            counts.remove(label).map(|count| Bucket {
+                label: label.clone(),
+                count,
+            })
+        })
+        .collect();
+    buckets.extend(
+        counts
+            .into_iter()
+            .map(|(label, count)| Bucket { label, count }),
+    );
+    buckets
+}
+
+/// Analyze a project: files, resolutions, captions, duplicates and questionable files
+///
+/// Hashes new files for duplicate detection first, which is the slow part, reporting
+/// progress through the job.
+pub fn build_report(job: &Job, project: &Path) -> Result<DatasetReport, String> {
+    let hashed = index::refresh_with_hashes(project, job.cancel_flag(), |done, total| {
+        if done % 25 == 0 || done == total {
+            job.progress(done as u64, total as u64, Some("Hashing files".to_string()));
+        }
+    });
+    match hashed {
+        Err(e) if e == copy::CANCELLED => return Err(jobs::CANCELLED.to_string()),
+        result => result?,
+    };
+    job.progress(0, 0, Some("Analyzing".to_string()));
+
+    let media = index::list_files(project)?;
+    let attribution = index::attribution(project)?;
+    let mut licenses = Vec::new();
+    let mut unlicensed = Vec::new();
+    let mut uncredited = Vec::new();
+    let mut files = FileCounts::default();
+    let mut resolutions = Vec::new();
+    let mut aspect_ratios = Vec::new();
+    let mut flagged = Vec::new();
+
+    for file in &media {
+        files.total += 1;
+        files.total_bytes += file.size;
+        match file.file_type.as_str() {
+            "image" => files.images += 1,
+            "video" => files.videos += 1,
+            _ => {}
+        }
+        let extension = Path::new(&file.name)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        *files.by_extension.entry(extension).or_default() += 1;
+
+        let mut reasons = Vec::new();
+        let dimensions = file.width.zip(file.height).filter(|&(w, h)| w > 0 && h > 0);
+        resolutions.push(resolution_label(dimensions.map(|(w, h)| w.min(h))));
+        match dimensions {
+            Some((width, height)) => {
+                aspect_ratios.push(aspect_label(width, height));
+                if width.min(height) < MIN_SHORT_SIDE {
+                    reasons.push(format!("Low resolution ({}×{})", width, height));
+                }
+                if width.max(height) as f64 / width.min(height) as f64 > MAX_ASPECT {
+                    reasons.push(format!("Extreme aspect ratio ({}×{})", width, height));
+                }
+            }
+            None if file.file_type == "video" => {
+                reasons.push("Unknown dimensions (unreadable, or ffprobe is missing)".to_string())
+            }
+            None => reasons.push("Unreadable or corrupt (no dimensions)".to_string()),
+        }
+        if let Some(duration) = file.duration.filter(|&d| d < MIN_VIDEO_SECS) {
+            reasons.push(format!("Very short video ({:.1}s)", duration));
+        }
+
+        let file_attribution = attribution.of(&file.relative_path);
+        match &file_attribution.license {
+            Some(license) => licenses.push(license.clone()),
+            None => {
+                licenses.push("unknown".to_string());
+                unlicensed.push(file.relative_path.clone());
+            }
+        }
+        if file_attribution.creator.is_none() && file_attribution.source.is_none() {
+            uncredited.push(file.relative_path.clone());
+        }
+
+        if !reasons.is_empty() {
+            flagged.push(FlaggedFile {
+                relative_path: file.relative_path.clone(),
+                reasons,
+            });
+        }
+    }
+
+    let mut resolution_order: Vec<String> = std::iter::once(0)
+        .chain(RESOLUTION_BUCKETS.iter().copied())
+        .map(|side| resolution_label(Some(side)))
+        .collect();
+    resolution_order.push(resolution_label(None));
+    let mut aspect_order = vec![format!("taller than {}", ASPECT_RATIOS[0].0)];
+    aspect_order.extend(ASPECT_RATIOS.iter().map(|(label, _)| label.to_string()));
+    aspect_order.push(format!(
+        "wider than {}",
+        ASPECT_RATIOS[ASPECT_RATIOS.len() - 1].0
+    ));
+
+    Ok(DatasetReport {
+        project: project.to_string_lossy().to_string(),
+        generated: now_timestamp(),
+        files,
+        resolutions: histogram(resolutions, &resolution_order),
+        aspect_ratios: histogram(aspect_ratios, &aspect_order),
+        captions: stats::collect_stats(project)?,
+        duplicates: index::duplicate_groups(project)?,
+        flagged,
+        licensing: LicensingSummary {
+            project: attribution.project,
+            licenses: histogram(licenses, &[]),
+            unlicensed,
+            uncredited,
+        },
+    })
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, units[unit])
+}
+
+fn percent(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+/// A histogram as rows of proportional bars
+fn html_histogram(title: &str, buckets: &[Bucket]) -> String {
+    let max = buckets.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+    let rows: String = buckets
+        .iter()
+        .map(|b| {
+            format!(
+                "<tr><td>{}</td><td class=\"bar\"><div style=\"width:{:.1}%\"></div></td><td>{}</td></tr>",
+                escape_html(&b.label),
+                percent(b.count, max),
+                b.count
+            )
+        })
+        .collect();
+    format!("<h2>{}</h2><table>{}</table>", title, rows)
+}
+
+fn html_lengths(name: &str, lengths: &LengthStats) -> String {
+    format!(
+        "<tr><td>{}</td><td>{}</td><td>{:.1}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+        name, lengths.min, lengths.mean, lengths.median, lengths.p90, lengths.max
+    )
+}
+
+/// Files without some attribution, as a collapsed list
+fn html_file_list(title: &str, files: &[String]) -> String {
+    if files.is_empty() {
+        return String::new();
+    }
+    let items: String = files
+        .iter()
+        .map(|file| format!("<li>{}</li>", escape_html(file)))
+        .collect();
+    format!(
+        "<details><summary>{} ({})</summary><ul>{}</ul></details>",
+        title,
+        files.len(),
+        items
+    )
+}
+
+fn html_licensing(licensing: &LicensingSummary) -> String {
+    let project = &licensing.project;
+    let field = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(escape_html)
+            .unwrap_or_else(|| "<span class=\"muted\">not set</span>".to_string())
+    };
+    let mut html = format!(
+        "<h2>Licensing</h2><table><tr><td>Project license</td><td>{}</td></tr>\
+         <tr><td>Project source</td><td>{}</td></tr>\
+         <tr><td>Project creator</td><td>{}</td></tr></table>",
+        field(&project.license),
+        field(&project.source),
+        field(&project.creator)
+    );
+    html.push_str(&html_histogram("Files by license", &licensing.licenses));
+    html.push_str(&html_file_list(
+        "Files without a license",
+        &licensing.unlicensed,
+    ));
+    html.push_str(&html_file_list(
+        "Files without a creator or source",
+        &licensing.uncredited,
+    ));
+    html
+}
+
+/// Render a report as a self-contained HTML page
+pub fn render_html(report: &DatasetReport) -> String {
+    let files = &report.files;
+    let captions = &report.captions;
+    let mut html = String::new();
+
+    html.push_str(&format!(
+        "<h1>Dataset report</h1><p class=\"muted\">{} · {}</p>",
+        escape_html(&report.project),
+        escape_html(&report.generated)
+    ));
+
+    let extensions: Vec<String> = files
+        .by_extension
+        .iter()
+        .map(|(ext, count)| format!("{} .{}", count, escape_html(ext)))
+        .collect();
+    html.push_str(&format!(
+        "<h2>Files</h2><table><tr><td>Total</td><td>{} ({})</td></tr>\
+         <tr><td>Images</td><td>{}</td></tr><tr><td>Videos</td><td>{}</td></tr>\
+         <tr><td>Formats</td><td>{}</td></tr></table>",
+        files.total,
+        format_bytes(files.total_bytes),
+        files.images,
+        files.videos,
+        extensions.join(", ")
+    ));
+
+    html.push_str(&html_histogram(
+        "Resolution (shorter side)",
+        &report.resolutions,
+    ));
+    html.push_str(&html_histogram("Aspect ratio", &report.aspect_ratios));
+
+    html.push_str(&format!(
+        "<h2>Captions</h2><table><tr><td>Captioned</td><td>{} of {} ({:.0}%)</td></tr>\
+         <tr><td>Empty captions</td><td>{}</td></tr>\
+         <tr><td>Over the CLIP token limit</td><td>{}</td></tr>\
+         <tr><td>Average tags</td><td>{:.1}</td></tr></table>\
+         <table><tr><th></th><th>Min</th><th>Mean</th><th>Median</th><th>P90</th><th>Max</th></tr>{}{}{}</table>",
+        captions.captioned,
+        captions.total_files,
+        percent(captions.captioned, captions.total_files),
+        captions.empty_captions.len(),
+        captions.over_token_limit,
+        captions.average_tags,
+        html_lengths("Characters", &captions.chars),
+        html_lengths("Words", &captions.words),
+        html_lengths("CLIP tokens (est.)", &captions.clip_tokens)
+    ));
+
+    html.push_str(&format!(
+        "<h2>Duplicates ({})</h2>",
+        report.duplicates.len()
+    ));
+    if report.duplicates.is_empty() {
+        html.push_str("<p class=\"muted\">No byte-identical files.</p>");
+    } else {
+        html.push_str("<ul>");
+        for group in &report.duplicates {
+            let paths: Vec<String> = group.iter().map(|p| escape_html(p)).collect();
+            html.push_str(&format!("<li>{}</li>", paths.join(" = ")));
+        }
+        html.push_str("</ul>");
+    }
+
+    html.push_str(&format!(
+        "<h2>Flagged files ({})</h2>",
+        report.flagged.len()
+    ));
+    if report.flagged.is_empty() {
+        html.push_str("<p class=\"muted\">Nothing to flag.</p>");
+    } else {
+        html.push_str("<table>");
+        for file in &report.flagged {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape_html(&file.relative_path),
+                escape_html(&file.reasons.join("; "))
+            ));
+        }
+        html.push_str("</table>");
+    }
+
+    html.push_str(&html_licensing(&report.licensing));
+
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Dataset report</title>\
+         <style>body{{font-family:system-ui,sans-serif;max-width:60rem;margin:2rem auto;padding:0 1rem}}\
+         table{{border-collapse:collapse;margin-bottom:1rem}}td,th{{padding:.2rem .8rem .2rem 0;text-align:left}}\
+         .bar{{width:20rem}}.bar div{{background:#6366f1;height:.8rem}}.muted{{color:#777}}</style>\
+         </head><body>{}</body></html>",
+        html
+    )
+}
+
+/// Write a report, by default into the project's metadata directory, returning its path
+pub fn write_report(
+    project: &Path,
+    report: &DatasetReport,
+    format: ReportFormat,
+    output: Option<&Path>,
+) -> Result<PathBuf, String> {
+    let path = match output {
+        Some(path) => path.to_path_buf(),
+        None => metadata_dir(project)?.join(format!("report.{}", format.extension())),
+    };
+    let contents = match format {
+        ReportFormat::Html => render_html(report),
+        ReportFormat::Json => serde_json::to_string_pretty(report).map_err(|e| e.to_string())?,
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    atomic::write_atomic(&path, contents, false)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
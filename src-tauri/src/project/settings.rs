@@ -0,0 +1,229 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use super::{lock, project_root_for, PROJECT_FILE};
+use crate::captions::format::CaptionFormat;
+use crate::captions::trigger::TriggerRules;
+use crate::file_system::atomic;
+use crate::file_system::copy::ImportMode;
+
+/// Snapshot of a project's dataset, refreshed whenever the settings are read
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectStats {
+    pub file_count: usize,
+    pub image_count: usize,
+    pub video_count: usize,
+    pub captioned_count: usize,
+    pub updated: Option<String>,
+}
+
+/// When a project is backed up automatically
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    /// Hours between scheduled backups, or None to only back up on demand
+    pub interval_hours: Option<u32>,
+    /// Back up captions before batch edits and caption format conversions
+    pub before_batch: bool,
+    /// Include the media files, not just captions and settings
+    pub include_media: bool,
+    /// Number of backups kept; older ones are pruned after each automatic backup
+    pub keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            interval_hours: None,
+            before_batch: true,
+            include_media: false,
+            keep: 10,
+        }
+    }
+}
+
+/// When and where a project was last exported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRecord {
+    pub timestamp: String,
+    /// Directory, archive or remote location the project was exported to
+    pub destination: String,
+}
+
+/// How batch captioning commands pace their provider requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchSettings {
+    /// Files captioned at the same time
+    pub parallelism: usize,
+    /// Requests per minute across the whole batch, or None for no limit
+    pub requests_per_minute: Option<u32>,
+    /// Times a failed request is tried again before the file is given up on
+    pub retries: u32,
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        Self {
+            parallelism: 1,
+            requests_per_minute: None,
+            retries: 0,
+        }
+    }
+}
+
+/// Settings stored in a project's `project.json`
+///
+/// Every field has a default so older files keep loading as new settings are added,
+/// and keys this version doesn't know about are preserved on save.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectSettings {
+    pub name: String,
+    pub source_directory: Option<String>,
+    pub import_mode: ImportMode,
+    pub caption_prompt: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub system_instruction: Option<String>,
+    pub temperature: Option<f32>,
+    pub trigger_word: Option<String>,
+    /// How the trigger word is injected into captions, or None to leave them alone
+    pub trigger_rules: Option<TriggerRules>,
+    pub caption_format: CaptionFormat,
+    /// Versions kept per caption in the history store (default 20, 0 disables it)
+    pub caption_history_limit: Option<usize>,
+    /// Keep the caption files in a git repository, committed after every batch operation
+    pub caption_git: bool,
+    pub backup: BackupSettings,
+    pub batch: BatchSettings,
+    pub stats: ProjectStats,
+    pub created: Option<String>,
+    pub last_opened: Option<String>,
+    pub last_export: Option<ExportRecord>,
+    /// Refuse every change to the project's files, e.g. once the dataset is finished
+    pub read_only: bool,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Current local time in the format used by project metadata
+pub fn now_timestamp() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Load the settings of a project, returning defaults if it has no `project.json` yet
+pub fn load_settings(project: &Path) -> Result<ProjectSettings, String> {
+    let path = project.join(PROJECT_FILE);
+
+    if !path.exists() {
+        let name = project
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        return Ok(ProjectSettings {
+            name,
+            created: Some(now_timestamp()),
+            ..Default::default()
+        });
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read project settings: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse project settings: {}", e))
+}
+
+/// Save the settings of a project to its `project.json`
+pub fn save_settings(project: &Path, settings: &ProjectSettings) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize project settings: {}", e))?;
+    atomic::write_atomic(&project.join(PROJECT_FILE), content, false)
+        .map_err(|e| format!("Failed to write project settings: {}", e))
+}
+
+/// Apply a partial update (a JSON object of changed fields) to a project's settings
+pub fn merge_settings(
+    settings: &ProjectSettings,
+    changes: serde_json::Value,
+) -> Result<ProjectSettings, String> {
+    let changes = match changes {
+        serde_json::Value::Object(map) => map,
+        _ => return Err("Project settings update must be an object".to_string()),
+    };
+
+    let mut merged = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    if let serde_json::Value::Object(ref mut map) = merged {
+        for (key, value) in changes {
+            map.insert(key, value);
+        }
+    }
+
+    serde_json::from_value(merged).map_err(|e| format!("Invalid project settings: {}", e))
+}
+
+/// Record that a project was opened
+pub fn touch_last_opened(project: &Path) -> Result<(), String> {
+    let mut settings = load_settings(project)?;
+    settings.last_opened = Some(now_timestamp());
+    save_settings(project, &settings)
+}
+
+/// Record that a project was exported to `destination`
+///
+/// Exports have already succeeded when this is called, so a failure to record them is
+/// only logged.
+pub fn record_export(project: &Path, destination: &str) {
+    let result = load_settings(project).and_then(|mut settings| {
+        settings.last_export = Some(ExportRecord {
+            timestamp: now_timestamp(),
+            destination: destination.to_string(),
+        });
+        save_settings(project, &settings)
+    });
+    if let Err(e) = result {
+        warn!(
+            "Failed to record the export of {}: {}",
+            project.display(),
+            e
+        );
+    }
+}
+
+/// Record the folder a project was imported from and how its files were brought in
+pub fn record_source_directory(
+    project: &Path,
+    source: &str,
+    import_mode: ImportMode,
+) -> Result<(), String> {
+    let mut settings = load_settings(project)?;
+    settings.source_directory = Some(source.to_string());
+    settings.import_mode = import_mode;
+    save_settings(project, &settings)
+}
+
+/// Refuse to change a project, or a file in a project, while the project is read-only
+/// or another process (app window, command line run) holds it
+///
+/// Deleting, cropping, trimming, renaming and overwriting captions all go through this
+/// check, most of them by starting an undoable operation.
+pub fn ensure_writable(path: &Path) -> Result<(), String> {
+    let project = project_root_for(path);
+    lock::ensure_not_in_use(&project)?;
+    if !project.join(PROJECT_FILE).is_file() {
+        return Ok(());
+    }
+
+    let settings = load_settings(&project)?;
+    if settings.read_only {
+        return Err(format!(
+            "{} is read-only. Turn off read-only mode in the project settings to change it.",
+            settings.name
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,501 @@
+use chrono::Local;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tempfile::{tempdir, TempDir};
+use tracing::info;
+
+use super::hub::{HubClient, HubFile, UploadMode};
+use super::s3::{S3Client, S3Target};
+use super::sftp::{self, SftpTarget};
+use crate::file_system::commands::{validate_project_directory, zip_directory, MediaFile};
+use crate::file_system::dedup::hash_file;
+use crate::file_system::ignore_rules::IgnoreRules;
+use crate::file_system::merge::collect_files;
+use crate::file_system::space;
+use crate::jobs::manager::{self as jobs, Job, JobKind};
+use crate::jobs::pool;
+use crate::media::formats;
+use crate::project::{index, settings};
+
+/// Metadata file of the `imagefolder` layout, pairing each file with its caption
+pub(crate) const METADATA_FILE: &str = "metadata.jsonl";
+
+/// Result of uploading a project to the Hugging Face Hub
+#[derive(Debug, Serialize)]
+pub struct HubUploadSummary {
+    pub repo_url: String,
+    pub commit_url: Option<String>,
+    /// Whether the repository was created by this upload
+    pub created: bool,
+    pub uploaded: usize,
+    /// Files the Hub already had, e.g. from an earlier or interrupted upload
+    pub skipped: usize,
+    /// Media files removed from the repository because they left the project
+    pub deleted: usize,
+    pub bytes_uploaded: u64,
+}
+
+/// Result of exporting a project to an S3 bucket
+#[derive(Debug, Serialize)]
+pub struct S3ExportSummary {
+    /// Where the export was uploaded, as `s3://bucket/prefix`
+    pub location: String,
+    pub uploaded: usize,
+    /// Files already in the bucket with the same contents
+    pub skipped: usize,
+    pub bytes_uploaded: u64,
+}
+
+/// Result of syncing a project to a server over SFTP
+#[derive(Debug, Serialize)]
+pub struct SftpExportSummary {
+    /// Where the export was synced to, as `user@host:path`
+    pub location: String,
+    pub uploaded: usize,
+    /// Files whose remote copy has the same size and modification time
+    pub unchanged: usize,
+    /// Remote files removed because they are no longer in the project
+    pub deleted: usize,
+    pub bytes_uploaded: u64,
+}
+
+/// A local file to export, with its path relative to the export and its hash
+pub(crate) struct ExportFile {
+    pub relative_path: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub hash: String,
+}
+
+/// List and hash the files of a project as an export would include them, leaving out
+/// app metadata and ignored files
+pub(crate) fn collect_export_files(job: &Job, project: &Path) -> Result<Vec<ExportFile>, String> {
+    let mut relative_paths = Vec::new();
+    collect_files(
+        project,
+        project,
+        &IgnoreRules::load(project),
+        &mut relative_paths,
+    )?;
+
+    job.progress(
+        0,
+        relative_paths.len() as u64,
+        Some("Hashing files".to_string()),
+    );
+    pool::map(job, relative_paths, |relative| {
+        let path = project.join(&relative);
+        Ok(ExportFile {
+            relative_path: relative.to_string_lossy().replace('\\', "/"),
+            size: path.metadata().map(|m| m.len()).unwrap_or(0),
+            hash: hash_file(&path, job.cancel_flag())?,
+            path,
+        })
+    })
+}
+
+/// Zip a project into a temporary directory, as a single file to export
+fn zip_export(job: &Job, project: &Path) -> Result<(TempDir, ExportFile), String> {
+    let name = project
+        .file_name()
+        .ok_or_else(|| "Invalid source directory".to_string())?
+        .to_string_lossy()
+        .to_string();
+    let zip_name = format!(
+        "spacecat_export_{}_{}.zip",
+        name,
+        Local::now().format("%Y%m%d_%H%M%S")
+    );
+
+    let staging = tempdir().map_err(|e| format!("Failed to create staging directory: {}", e))?;
+    let zip_path = staging.path().join(&zip_name);
+    job.progress(
+        0,
+        space::dataset_size(project),
+        Some("Creating ZIP file".to_string()),
+    );
+    zip_directory(&project.to_string_lossy(), &zip_path.to_string_lossy(), job)?;
+
+    let file = ExportFile {
+        relative_path: zip_name,
+        size: zip_path.metadata().map(|m| m.len()).unwrap_or(0),
+        hash: hash_file(&zip_path, job.cancel_flag())?,
+        path: zip_path,
+    };
+    Ok((staging, file))
+}
+
+/// Package a project as an `imagefolder` dataset: its media files at their relative
+/// paths, plus a `metadata.jsonl` with the caption of each (empty if uncaptioned)
+fn package_dataset(job: &Job, project: &Path) -> Result<Vec<HubFile>, String> {
+    let media = index::list_files(project)?;
+    let total = media.len() as u64;
+    job.progress(0, total, Some("Hashing files".to_string()));
+    let mut files = pool::map(job, media.iter().collect(), |file| {
+        let repo_path = file.relative_path.replace('\\', "/");
+        HubFile::from_path(repo_path, PathBuf::from(&file.path))
+            .map_err(|e| format!("Failed to read {}: {}", file.path, e))
+    })?;
+
+    files.push(HubFile::from_bytes(
+        METADATA_FILE.to_string(),
+        dataset_metadata(project, &media)?.into_bytes(),
+    ));
+
+    Ok(files)
+}
+
+/// The `metadata.jsonl` of an `imagefolder` dataset of media files of a project
+///
+/// Projects with attribution get `license`, `source` and `creator` columns as well,
+/// with the project's values for files without their own.
+pub(crate) fn dataset_metadata(project: &Path, media: &[MediaFile]) -> Result<String, String> {
+    let captions: HashMap<String, String> = index::captions(project)?
+        .into_iter()
+        .map(|(file, caption)| (file.relative_path, caption))
+        .collect();
+    let attribution = index::attribution(project)?;
+
+    let mut metadata = String::new();
+    for file in media {
+        let caption = captions.get(&file.relative_path).map(|c| c.trim());
+        let mut line = json!({
+            "file_name": file.relative_path.replace('\\', "/"),
+            "text": caption.unwrap_or_default(),
+        });
+        if !attribution.is_empty() {
+            let file_attribution = attribution.of(&file.relative_path);
+            line["license"] = json!(file_attribution.license);
+            line["source"] = json!(file_attribution.source);
+            line["creator"] = json!(file_attribution.creator);
+        }
+        metadata.push_str(&line.to_string());
+        metadata.push('\n');
+    }
+    Ok(metadata)
+}
+
+/// Upload a project to a dataset repository on the Hugging Face Hub
+///
+/// The project is packaged as an `imagefolder` dataset with a `metadata.jsonl` holding
+/// the captions. The repository is created if needed, otherwise its visibility is
+/// updated and media files no longer in the project are deleted from it. Large files
+/// go to LFS storage in parts, and files the Hub already has are skipped, so running an
+/// interrupted upload again picks up where it stopped. Runs as a cancellable export job
+/// reporting the bytes sent; the token is never recorded.
+#[tauri::command]
+pub async fn upload_to_hub(
+    app: AppHandle,
+    directory: String,
+    repo_id: String,
+    token: String,
+    private: bool,
+) -> Result<HubUploadSummary, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let client = HubClient::new(&repo_id, &token)?;
+    let description = format!("Upload {} to {}", directory, repo_id);
+
+    jobs::run_async(&app, JobKind::Export, description, |job| async move {
+        // Hashing reads every file, so it runs off the async runtime
+        let hashing = job.clone();
+        let files =
+            tauri::async_runtime::spawn_blocking(move || package_dataset(&hashing, &project))
+                .await
+                .map_err(|e| e.to_string())??;
+
+        let items: Vec<String> = files.iter().map(|f| f.path.clone()).collect();
+        job.make_resumable(
+            json!({ "directory": directory, "repo_id": repo_id, "private": private }),
+            &items,
+        );
+
+        job.progress(0, 0, Some("Preparing repository".to_string()));
+        let created = client.ensure_repo(private).await?;
+        let modes = client.upload_modes(&files).await?;
+        let mode_of = |file: &HubFile| modes.get(&file.path).copied().unwrap_or(UploadMode::Lfs);
+
+        // Identical files share one LFS object
+        let mut seen = HashSet::new();
+        let lfs_files: Vec<&HubFile> = files
+            .iter()
+            .filter(|f| mode_of(f) == UploadMode::Lfs && seen.insert(f.sha256.as_str()))
+            .collect();
+        let objects = client.lfs_batch(&lfs_files).await?;
+        let by_oid: HashMap<&str, &HubFile> =
+            lfs_files.iter().map(|f| (f.sha256.as_str(), *f)).collect();
+
+        let total_bytes = lfs_files.iter().map(|f| f.size).sum();
+        job.progress(0, total_bytes, Some("Uploading files".to_string()));
+        let mut uploaded = 0;
+        let mut skipped = 0;
+        let mut bytes_uploaded = 0;
+        for object in &objects {
+            let Some(file) = by_oid.get(object.oid.as_str()) else {
+                continue;
+            };
+            if client.upload_lfs(&job, file, object).await? {
+                uploaded += 1;
+                bytes_uploaded += file.size;
+                job.complete_item(&file.path, "uploaded");
+            } else {
+                skipped += 1;
+                job.advance(file.size);
+                job.complete_item(&file.path, "skipped");
+            }
+        }
+        job.check_cancelled()?;
+
+        // Only media is deleted, so files added to the repository by hand survive
+        let uploading: HashSet<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        let deleted: Vec<String> = if created {
+            Vec::new()
+        } else {
+            client
+                .list_files()
+                .await?
+                .into_iter()
+                .filter(|path| !uploading.contains(path.as_str()))
+                .filter(|path| {
+                    Path::new(path)
+                        .extension()
+                        .and_then(|e| {
+                            formats::type_for_extension(&e.to_string_lossy().to_lowercase())
+                        })
+                        .is_some()
+                })
+                .collect()
+        };
+
+        job.progress(total_bytes, total_bytes, Some("Committing".to_string()));
+        let committed: Vec<(&HubFile, UploadMode)> =
+            files.iter().map(|f| (f, mode_of(f))).collect();
+        uploaded += committed
+            .iter()
+            .filter(|(_, mode)| *mode == UploadMode::Regular)
+            .count();
+        let commit_url = client
+            .commit("Upload dataset from SpaceCat", &committed, &deleted)
+            .await?;
+
+        info!(
+            "Uploaded {} to {}: {} files sent, {} already there, {} deleted",
+            directory,
+            repo_id,
+            uploaded,
+            skipped,
+            deleted.len()
+        );
+
+        settings::record_export(Path::new(&directory), &client.repo_url());
+        Ok(HubUploadSummary {
+            repo_url: client.repo_url(),
+            commit_url,
+            created,
+            uploaded,
+            skipped,
+            deleted: deleted.len(),
+            bytes_uploaded,
+        })
+    })
+    .await
+}
+
+/// Export a project to an S3 bucket or a compatible service (R2, MinIO, ...)
+///
+/// Uploads the files of the project below the target's prefix, or a ZIP of it when
+/// `as_zip` is set. Large files are sent as multipart uploads. Every object records the
+/// hash of its file, so files already in the bucket with the same contents are skipped
+/// and an interrupted export can simply be run again. Runs as a cancellable export job
+/// reporting the bytes sent; credentials are never recorded.
+#[tauri::command]
+pub async fn export_to_s3(
+    app: AppHandle,
+    directory: String,
+    target: S3Target,
+    as_zip: bool,
+) -> Result<S3ExportSummary, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let params = json!({
+        "directory": directory,
+        "endpoint": target.endpoint,
+        "region": target.region,
+        "bucket": target.bucket,
+        "prefix": target.prefix,
+        "path_style": target.path_style,
+        "as_zip": as_zip,
+    });
+    let client = S3Client::new(target.clone())?;
+    let description = format!("Export {} to {}", directory, target.location());
+
+    jobs::run_async(&app, JobKind::Export, description, |job| async move {
+        // Hashing and zipping read every file, so they run off the async runtime
+        let preparing = job.clone();
+        let (_staging, files) = tauri::async_runtime::spawn_blocking(move || {
+            if as_zip {
+                zip_export(&preparing, &project).map(|(staging, file)| (Some(staging), vec![file]))
+            } else {
+                collect_export_files(&preparing, &project).map(|files| (None, files))
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        let items: Vec<String> = files.iter().map(|f| f.relative_path.clone()).collect();
+        job.make_resumable(params, &items);
+
+        let total_bytes = files.iter().map(|f| f.size).sum();
+        job.progress(0, total_bytes, Some("Uploading files".to_string()));
+        let mut uploaded = 0;
+        let mut skipped = 0;
+        let mut bytes_uploaded = 0;
+        for file in &files {
+            job.check_cancelled()?;
+            let key = target.key(&file.relative_path);
+
+            if client.uploaded_hash(&key).await?.as_deref() == Some(file.hash.as_str()) {
+                skipped += 1;
+                job.advance(file.size);
+                job.complete_item(&file.relative_path, "skipped");
+                continue;
+            }
+
+            client
+                .upload_file(&job, &key, &file.path, file.size, &file.hash)
+                .await?;
+            uploaded += 1;
+            bytes_uploaded += file.size;
+            job.complete_item(&file.relative_path, "uploaded");
+        }
+
+        info!(
+            "Exported {} to {}: {} files uploaded, {} unchanged",
+            directory,
+            target.location(),
+            uploaded,
+            skipped
+        );
+
+        settings::record_export(Path::new(&directory), &target.location());
+        Ok(S3ExportSummary {
+            location: target.location(),
+            uploaded,
+            skipped,
+            bytes_uploaded,
+        })
+    })
+    .await
+}
+
+/// Sync a project to a directory on a server over SFTP
+///
+/// Like rsync, files whose remote copy has the same size and modification time are
+/// skipped, so later exports only transfer what changed; with `delete_removed`, remote
+/// files no longer in the project are deleted too. Authenticates with a private key,
+/// or the SSH agent when none is given, and only connects to servers in the user's
+/// `known_hosts` or whose fingerprint was confirmed. Files are uploaded under a
+/// temporary name and renamed into place, so an interrupted export leaves no
+/// half-written files behind. Runs as a cancellable export job reporting the bytes sent.
+#[tauri::command]
+pub async fn export_to_sftp(
+    app: AppHandle,
+    directory: String,
+    target: SftpTarget,
+    delete_removed: bool,
+) -> Result<SftpExportSummary, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let known_hosts = app
+        .path()
+        .home_dir()
+        .map_err(|e| e.to_string())?
+        .join(".ssh")
+        .join("known_hosts");
+    let description = format!("Export {} to {}", directory, target.location());
+
+    jobs::run(&app, JobKind::Export, description, move |job| {
+        let mut relative_paths = Vec::new();
+        collect_files(
+            &project,
+            &project,
+            &IgnoreRules::load(&project),
+            &mut relative_paths,
+        )?;
+
+        job.progress(0, 0, Some(format!("Connecting to {}", target.host)));
+        let sftp = sftp::connect(&target, &known_hosts)?;
+        let remote = sftp::list_remote(&sftp, &target.remote_path)?;
+
+        let mut changed = Vec::new();
+        let mut local = HashSet::new();
+        let mut unchanged = 0;
+        for relative in &relative_paths {
+            let relative_path = relative.to_string_lossy().replace('\\', "/");
+            let path = project.join(relative);
+            let Some(stamp) = sftp::local_stamp(&path) else {
+                continue;
+            };
+
+            if remote.get(&relative_path) == Some(&stamp) {
+                unchanged += 1;
+            } else {
+                changed.push((relative_path.clone(), path, stamp));
+            }
+            local.insert(relative_path);
+        }
+
+        let items: Vec<String> = changed
+            .iter()
+            .map(|(relative, _, _)| relative.clone())
+            .collect();
+        job.make_resumable(
+            json!({
+                "directory": directory,
+                "host": target.host,
+                "port": target.port,
+                "username": target.username,
+                "remote_path": target.remote_path,
+                "delete_removed": delete_removed,
+            }),
+            &items,
+        );
+
+        let total_bytes = changed.iter().map(|(_, _, (size, _))| size).sum();
+        job.progress(0, total_bytes, Some("Uploading files".to_string()));
+        for (relative, path, stamp) in &changed {
+            job.check_cancelled()?;
+            sftp::upload_file(job, &sftp, path, &target.remote_file(relative), *stamp)?;
+            job.complete_item(relative, "uploaded");
+        }
+
+        // Leftovers of interrupted uploads are never part of the project
+        let mut deleted = 0;
+        if delete_removed {
+            for relative in remote.keys().filter(|r| !local.contains(*r)) {
+                job.check_cancelled()?;
+                sftp::remove_file(&sftp, &target.remote_file(relative))?;
+                deleted += 1;
+            }
+        }
+
+        info!(
+            "Exported {} to {}: {} files uploaded, {} unchanged, {} deleted",
+            directory,
+            target.location(),
+            changed.len(),
+            unchanged,
+            deleted
+        );
+
+        settings::record_export(&project, &target.location());
+        Ok(SftpExportSummary {
+            location: target.location(),
+            uploaded: changed.len(),
+            unchanged,
+            deleted,
+            bytes_uploaded: total_bytes,
+        })
+    })
+    .await
+}
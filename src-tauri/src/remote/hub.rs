@@ -0,0 +1,604 @@
+use base64::{engine::general_purpose, Engine as _};
+use reqwest::header::{ACCEPT, CONTENT_TYPE, ETAG};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::jobs::manager::Job;
+
+/// Base URL of the Hugging Face Hub
+pub const HUB_ENDPOINT: &str = "https://huggingface.co";
+
+/// Files described per pre-upload request
+const PREUPLOAD_BATCH: usize = 250;
+
+/// Objects requested per LFS batch request
+const LFS_BATCH: usize = 100;
+
+/// Bytes of each file sent with the pre-upload request, to tell text from binary
+const SAMPLE_BYTES: u64 = 512;
+
+/// Attempts per upload request before giving up on a file
+const MAX_ATTEMPTS: u32 = 3;
+
+const LFS_MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
+
+/// Contents of a file to upload: a file on disk or generated data
+pub enum HubContent {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+/// A file to upload, with the size and hash the Hub identifies it by
+pub struct HubFile {
+    /// Path in the repository, with forward slashes
+    pub path: String,
+    pub content: HubContent,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl HubFile {
+    /// Describe a file on disk, hashing its contents
+    pub fn from_path(repo_path: String, path: PathBuf) -> io::Result<Self> {
+        let mut file = File::open(&path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 1024 * 1024];
+        let mut size = 0;
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+
+        Ok(Self {
+            path: repo_path,
+            content: HubContent::Path(path),
+            size,
+            sha256: hex(&hasher.finalize()),
+        })
+    }
+
+    pub fn from_bytes(repo_path: String, bytes: Vec<u8>) -> Self {
+        Self {
+            path: repo_path,
+            size: bytes.len() as u64,
+            sha256: hex(&Sha256::digest(&bytes)),
+            content: HubContent::Bytes(bytes),
+        }
+    }
+
+    /// Read `len` bytes from `offset`; read again for every attempt rather than kept
+    /// in memory, since parts of large videos are big
+    fn read_range(&self, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        match &self.content {
+            HubContent::Path(path) => {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                let mut buffer = Vec::new();
+                file.take(len).read_to_end(&mut buffer)?;
+                Ok(buffer)
+            }
+            HubContent::Bytes(bytes) => {
+                let start = (offset as usize).min(bytes.len());
+                let end = (start + len as usize).min(bytes.len());
+                Ok(bytes[start..end].to_vec())
+            }
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// How the Hub wants a file committed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadMode {
+    /// Sent inline with the commit
+    Regular,
+    /// Uploaded to LFS storage first, then committed as a pointer
+    Lfs,
+    /// Matched by the repository's ignore rules
+    Ignored,
+}
+
+#[derive(Deserialize)]
+struct PreuploadResponse {
+    files: Vec<PreuploadFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PreuploadFile {
+    path: String,
+    upload_mode: String,
+    #[serde(default)]
+    should_ignore: bool,
+}
+
+#[derive(Deserialize)]
+struct LfsBatchResponse {
+    objects: Vec<LfsObject>,
+}
+
+/// Where and how to upload one LFS object; no actions means the Hub already has it
+#[derive(Deserialize)]
+pub struct LfsObject {
+    pub oid: String,
+    #[serde(default)]
+    actions: Option<LfsActions>,
+    #[serde(default)]
+    error: Option<LfsError>,
+}
+
+#[derive(Deserialize)]
+struct LfsActions {
+    upload: Option<LfsAction>,
+    verify: Option<LfsAction>,
+}
+
+#[derive(Deserialize)]
+struct LfsAction {
+    href: String,
+    #[serde(default)]
+    header: HashMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct LfsError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct TreeEntry {
+    #[serde(rename = "type")]
+    entry_type: String,
+    path: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommitResponse {
+    commit_url: Option<String>,
+}
+
+fn request_error(e: reqwest::Error) -> String {
+    format!("Hugging Face request failed: {}", e)
+}
+
+/// Turn an unsuccessful response into an error describing `action`
+async fn check(response: Response, action: &str) -> Result<Response, String> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+
+    let status = response.status();
+    let error_text = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    Err(format!(
+        "{} failed with status {}: {}",
+        action, status, error_text
+    ))
+}
+
+/// Header values of LFS actions may be strings or numbers
+fn header_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Check that a repository id has the `owner/name` form the Hub expects
+pub fn validate_repo_id(repo_id: &str) -> Result<(), String> {
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && !part.starts_with('.')
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+
+    match repo_id.split_once('/') {
+        Some((owner, name)) if valid_part(owner) && valid_part(name) => Ok(()),
+        _ => Err(format!(
+            "Invalid repository id: {} (expected owner/name)",
+            repo_id
+        )),
+    }
+}
+
+/// Client for one dataset repository on the Hub
+pub struct HubClient {
+    client: Client,
+    repo_id: String,
+    token: String,
+}
+
+impl HubClient {
+    pub fn new(repo_id: &str, token: &str) -> Result<Self, String> {
+        validate_repo_id(repo_id)?;
+        if token.trim().is_empty() {
+            return Err("A Hugging Face access token is required".to_string());
+        }
+
+        // Generous, since a single part of a large video can take a while
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(30))
+            .timeout(Duration::from_secs(600))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        Ok(Self {
+            client,
+            repo_id: repo_id.to_string(),
+            token: token.trim().to_string(),
+        })
+    }
+
+    pub fn repo_url(&self) -> String {
+        format!("{}/datasets/{}", HUB_ENDPOINT, self.repo_id)
+    }
+
+    fn api_url(&self, path: &str) -> String {
+        format!("{}/api/datasets/{}/{}", HUB_ENDPOINT, self.repo_id, path)
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        request.bearer_auth(&self.token)
+    }
+
+    /// Create the dataset repository, or update its visibility if it already exists;
+    /// returns whether it was created
+    pub async fn ensure_repo(&self, private: bool) -> Result<bool, String> {
+        let (organization, name) = self.repo_id.split_once('/').unwrap_or(("", &self.repo_id));
+        let response = self
+            .authorized(
+                self.client
+                    .post(format!("{}/api/repos/create", HUB_ENDPOINT)),
+            )
+            .json(&json!({
+                "type": "dataset",
+                "name": name,
+                "organization": organization,
+                "private": private,
+            }))
+            .send()
+            .await
+            .map_err(request_error)?;
+
+        if response.status() != StatusCode::CONFLICT {
+            check(response, "Creating the repository").await?;
+            return Ok(true);
+        }
+
+        let response = self
+            .authorized(self.client.put(self.api_url("settings")))
+            .json(&json!({ "private": private }))
+            .send()
+            .await
+            .map_err(request_error)?;
+        check(response, "Updating the repository").await?;
+
+        Ok(false)
+    }
+
+    /// Ask the Hub how each file has to be uploaded
+    pub async fn upload_modes(
+        &self,
+        files: &[HubFile],
+    ) -> Result<HashMap<String, UploadMode>, String> {
+        let mut modes = HashMap::new();
+
+        for batch in files.chunks(PREUPLOAD_BATCH) {
+            let mut described = Vec::new();
+            for file in batch {
+                let sample = file
+                    .read_range(0, SAMPLE_BYTES)
+                    .map_err(|e| format!("Failed to read {}: {}", file.path, e))?;
+                described.push(json!({
+                    "path": file.path,
+                    "size": file.size,
+                    "sample": general_purpose::STANDARD.encode(sample),
+                }));
+            }
+
+            let response = self
+                .authorized(self.client.post(self.api_url("preupload/main")))
+                .json(&json!({ "files": described }))
+                .send()
+                .await
+                .map_err(request_error)?;
+            let response: PreuploadResponse = check(response, "Preparing the upload")
+                .await?
+                .json()
+                .await
+                .map_err(request_error)?;
+
+            for file in response.files {
+                let mode = if file.should_ignore {
+                    UploadMode::Ignored
+                } else if file.upload_mode == "lfs" {
+                    UploadMode::Lfs
+                } else {
+                    UploadMode::Regular
+                };
+                modes.insert(file.path, mode);
+            }
+        }
+
+        Ok(modes)
+    }
+
+    /// Request upload instructions for LFS files, in batches
+    pub async fn lfs_batch(&self, files: &[&HubFile]) -> Result<Vec<LfsObject>, String> {
+        let url = format!(
+            "{}/datasets/{}.git/info/lfs/objects/batch",
+            HUB_ENDPOINT, self.repo_id
+        );
+        let mut objects = Vec::new();
+
+        for batch in files.chunks(LFS_BATCH) {
+            let body = json!({
+                "operation": "upload",
+                "transfers": ["basic", "multipart"],
+                "hash_algo": "sha256",
+                "objects": batch
+                    .iter()
+                    .map(|file| json!({ "oid": file.sha256, "size": file.size }))
+                    .collect::<Vec<_>>(),
+            });
+
+            let response = self
+                .authorized(self.client.post(&url))
+                .header(ACCEPT, LFS_MEDIA_TYPE)
+                .header(CONTENT_TYPE, LFS_MEDIA_TYPE)
+                .body(body.to_string())
+                .send()
+                .await
+                .map_err(request_error)?;
+            let response: LfsBatchResponse = check(response, "Requesting LFS uploads")
+                .await?
+                .json()
+                .await
+                .map_err(request_error)?;
+
+            objects.extend(response.objects);
+        }
+
+        Ok(objects)
+    }
+
+    /// PUT a byte range of a file, retrying failed attempts with a growing delay
+    async fn put_range(
+        &self,
+        url: &str,
+        headers: &HashMap<String, Value>,
+        file: &HubFile,
+        offset: u64,
+        len: u64,
+    ) -> Result<Response, String> {
+        let mut attempt = 1;
+        loop {
+            let body = file
+                .read_range(offset, len)
+                .map_err(|e| format!("Failed to read {}: {}", file.path, e))?;
+            let mut request = self.client.put(url).body(body);
+            for (name, value) in headers {
+                request = request.header(name.as_str(), header_value(value));
+            }
+
+            let error = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => check(response, "Uploading").await.err().unwrap_or_default(),
+                Err(e) => request_error(e),
+            };
+            if attempt >= MAX_ATTEMPTS {
+                return Err(format!("Failed to upload {}: {}", file.path, error));
+            }
+
+            warn!(
+                "Upload of {} failed (attempt {}), retrying: {}",
+                file.path, attempt, error
+            );
+            tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+            attempt += 1;
+        }
+    }
+
+    /// Upload one LFS object, in parts if the Hub asks for it
+    ///
+    /// Returns false if the Hub already had the file, which is what makes an upload
+    /// resumable: files sent by an earlier, interrupted run are skipped. The job's
+    /// progress advances by the bytes sent.
+    pub async fn upload_lfs(
+        &self,
+        job: &Job,
+        file: &HubFile,
+        object: &LfsObject,
+    ) -> Result<bool, String> {
+        if let Some(error) = &object.error {
+            return Err(format!(
+                "Hub refused {} ({}): {}",
+                file.path, error.code, error.message
+            ));
+        }
+        let Some(upload) = object.actions.as_ref().and_then(|a| a.upload.as_ref()) else {
+            return Ok(false);
+        };
+
+        match upload.header.get("chunk_size") {
+            Some(chunk_size) => {
+                let chunk_size: u64 = header_value(chunk_size)
+                    .parse()
+                    .map_err(|_| "Invalid chunk size from the Hub".to_string())?;
+                let mut parts: Vec<(u32, String)> = upload
+                    .header
+                    .iter()
+                    .filter_map(|(key, url)| Some((key.parse().ok()?, header_value(url))))
+                    .collect();
+                parts.sort_by_key(|(number, _)| *number);
+
+                let mut etags = Vec::new();
+                for (number, url) in parts {
+                    job.check_cancelled()?;
+                    let offset = u64::from(number.saturating_sub(1)) * chunk_size;
+                    let len = chunk_size.min(file.size.saturating_sub(offset));
+                    let response = self
+                        .put_range(&url, &HashMap::new(), file, offset, len)
+                        .await?;
+                    let etag = response
+                        .headers()
+                        .get(ETAG)
+                        .and_then(|v| v.to_str().ok())
+                        .ok_or_else(|| format!("No ETag for part {} of {}", number, file.path))?;
+                    etags.push(json!({ "partNumber": number, "etag": etag }));
+                    job.advance(len);
+                }
+
+                let response = self
+                    .client
+                    .post(&upload.href)
+                    .header(ACCEPT, LFS_MEDIA_TYPE)
+                    .header(CONTENT_TYPE, LFS_MEDIA_TYPE)
+                    .body(json!({ "oid": object.oid, "parts": etags }).to_string())
+                    .send()
+                    .await
+                    .map_err(request_error)?;
+                check(response, "Completing the upload").await?;
+            }
+            None => {
+                job.check_cancelled()?;
+                self.put_range(&upload.href, &upload.header, file, 0, file.size)
+                    .await?;
+                job.advance(file.size);
+            }
+        }
+
+        if let Some(verify) = object.actions.as_ref().and_then(|a| a.verify.as_ref()) {
+            let response = self
+                .client
+                .post(&verify.href)
+                .basic_auth("USER", Some(&self.token))
+                .json(&json!({ "oid": object.oid, "size": file.size }))
+                .send()
+                .await
+                .map_err(request_error)?;
+            check(response, "Verifying the upload").await?;
+        }
+
+        Ok(true)
+    }
+
+    /// List the paths of every file in the repository
+    pub async fn list_files(&self) -> Result<Vec<String>, String> {
+        let mut paths = Vec::new();
+        let mut next = Some(self.api_url("tree/main?recursive=true&expand=false"));
+
+        while let Some(url) = next.take() {
+            let response = self
+                .authorized(self.client.get(&url))
+                .send()
+                .await
+                .map_err(request_error)?;
+            // A new repository has no main branch until its first commit
+            if response.status() == StatusCode::NOT_FOUND {
+                break;
+            }
+            let response = check(response, "Listing the repository").await?;
+
+            // Long listings are paginated with a `Link: <url>; rel="next"` header
+            next = response
+                .headers()
+                .get("link")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|link| {
+                    link.split(',')
+                        .find(|l| l.contains("rel=\"next\""))
+                        .and_then(|l| Some(l[l.find('<')? + 1..l.find('>')?].to_string()))
+                });
+
+            let entries: Vec<TreeEntry> = response.json().await.map_err(request_error)?;
+            paths.extend(
+                entries
+                    .into_iter()
+                    .filter(|e| e.entry_type == "file")
+                    .map(|e| e.path),
+            );
+        }
+
+        Ok(paths)
+    }
+
+    /// Commit uploaded files to the main branch, returning the commit's URL
+    ///
+    /// Regular files are sent inline; LFS files must have been uploaded already.
+    pub async fn commit(
+        &self,
+        summary: &str,
+        files: &[(&HubFile, UploadMode)],
+        deleted: &[String],
+    ) -> Result<Option<String>, String> {
+        let mut lines = vec![json!({
+            "key": "header",
+            "value": { "summary": summary, "description": "" },
+        })];
+
+        for (file, mode) in files {
+            let line = match mode {
+                UploadMode::Ignored => continue,
+                UploadMode::Lfs => json!({
+                    "key": "lfsFile",
+                    "value": { "path": file.path, "algo": "sha256", "oid": file.sha256 },
+                }),
+                UploadMode::Regular => {
+                    let content = file
+                        .read_range(0, file.size)
+                        .map_err(|e| format!("Failed to read {}: {}", file.path, e))?;
+                    json!({
+                        "key": "file",
+                        "value": {
+                            "path": file.path,
+                            "encoding": "base64",
+                            "content": general_purpose::STANDARD.encode(content),
+                        },
+                    })
+                }
+            };
+            lines.push(line);
+        }
+        for path in deleted {
+            lines.push(json!({ "key": "deletedFile", "value": { "path": path } }));
+        }
+
+        let body: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        let response = self
+            .authorized(self.client.post(self.api_url("commit/main")))
+            .header(CONTENT_TYPE, "application/x-ndjson")
+            .body(body)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let response: CommitResponse = check(response, "Committing the upload")
+            .await?
+            .json()
+            .await
+            .map_err(request_error)?;
+
+        Ok(response.commit_url)
+    }
+}
@@ -0,0 +1,4 @@
+pub mod commands;
+pub mod hub;
+pub mod s3;
+pub mod sftp;
@@ -0,0 +1,602 @@
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use reqwest::header::ETAG;
+use reqwest::{Client, Method, Response, StatusCode, Url};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::jobs::manager::Job;
+use crate::media::formats;
+
+/// Files up to this size are sent in a single request, larger ones in parts
+const PART_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Most parts S3 accepts for one object
+const MAX_PARTS: u64 = 10_000;
+
+/// Attempts per request before giving up
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Object metadata holding the BLAKE3 hash of the uploaded file, to skip unchanged
+/// files on the next export
+const HASH_HEADER: &str = "x-amz-meta-blake3";
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+/// A bucket on S3 or a compatible service (Cloudflare R2, MinIO, ...)
+#[derive(Debug, Clone, Deserialize)]
+pub struct S3Target {
+    /// Service URL, e.g. `https://s3.eu-west-1.amazonaws.com`,
+    /// `https://<account>.r2.cloudflarestorage.com` or `http://localhost:9000`
+    pub endpoint: String,
+    /// Region requests are signed for; `auto` for R2
+    #[serde(default = "default_region")]
+    pub region: String,
+    pub bucket: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Needed with temporary credentials
+    #[serde(default)]
+    pub session_token: Option<String>,
+    /// Key prefix the export is uploaded under
+    #[serde(default)]
+    pub prefix: String,
+    /// Put the bucket in the path instead of the host name, as MinIO needs by default
+    #[serde(default)]
+    pub path_style: bool,
+}
+
+impl S3Target {
+    /// Object key of a path relative to the export, below the prefix
+    pub fn key(&self, relative: &str) -> String {
+        let relative = relative.replace('\\', "/");
+        match self.prefix.trim_matches('/') {
+            "" => relative,
+            prefix => format!("{}/{}", prefix, relative),
+        }
+    }
+
+    /// Where the export ends up, as `s3://bucket/prefix`
+    pub fn location(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix.trim_matches('/'))
+    }
+}
+
+/// Percent-encode a string as SigV4 expects, keeping slashes in object keys
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Query string of a request as SigV4 signs it: encoded names and values, sorted
+fn canonical_query(query: &[(&str, &str)]) -> String {
+    let mut query: Vec<(String, String)> = query
+        .iter()
+        .map(|(k, v)| (uri_encode(k, false), uri_encode(v, false)))
+        .collect();
+    query.sort();
+    query
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Header value as SigV4 signs it: trimmed, with runs of spaces collapsed
+fn canonical_header_value(value: &str) -> String {
+    value
+        .split(' ')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim()
+        .to_string()
+}
+
+/// A request as SigV4 signs it
+struct CanonicalRequest<'a> {
+    method: &'a str,
+    /// URI-encoded path
+    path: &'a str,
+    /// From [`canonical_query`]
+    query: &'a str,
+    /// Lowercase names with values from [`canonical_header_value`], sorted by name
+    headers: &'a [(String, String)],
+    payload_hash: &'a str,
+}
+
+impl CanonicalRequest<'_> {
+    fn signed_headers(&self) -> String {
+        self.headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+
+    fn text(&self) -> String {
+        let headers: String = self
+            .headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect();
+        format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            self.method,
+            self.path,
+            self.query,
+            headers,
+            self.signed_headers(),
+            self.payload_hash
+        )
+    }
+
+    /// Authorization header signing the request for a service of the target's region,
+    /// at `amz_date` (`20150830T123600Z`)
+    fn authorization(&self, target: &S3Target, service: &str, amz_date: &str) -> String {
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/{}/aws4_request", date, target.region, service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256_hex(self.text().as_bytes())
+        );
+        let secret = format!("AWS4{}", target.secret_access_key);
+        let signing_key = [service, "aws4_request"].iter().fold(
+            hmac(&hmac(secret.as_bytes(), date), &target.region),
+            |key, part| hmac(&key, part),
+        );
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            target.access_key_id.trim(),
+            scope,
+            self.signed_headers(),
+            hex(&hmac(&signing_key, &string_to_sign))
+        )
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    // HMAC takes keys of any length, so this can't fail
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Text between the first `<tag>` and `</tag>` of an XML response
+fn xml_value(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml[start..end].to_string())
+}
+
+/// Read `len` bytes of a file from `offset`
+fn read_range(path: &Path, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buffer = Vec::new();
+    file.take(len).read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Client for one bucket, signing requests with AWS Signature Version 4
+pub struct S3Client {
+    client: Client,
+    target: S3Target,
+    /// URL of the bucket, without a trailing slash
+    bucket_url: Url,
+}
+
+impl S3Client {
+    pub fn new(target: S3Target) -> Result<Self, String> {
+        if target.bucket.trim().is_empty() {
+            return Err("A bucket name is required".to_string());
+        }
+        if target.access_key_id.trim().is_empty() || target.secret_access_key.is_empty() {
+            return Err("S3 credentials are required".to_string());
+        }
+
+        let endpoint = Url::parse(target.endpoint.trim().trim_end_matches('/'))
+            .map_err(|e| format!("Invalid S3 endpoint {}: {}", target.endpoint, e))?;
+        let bucket_url = if target.path_style {
+            format!(
+                "{}/{}",
+                endpoint.as_str().trim_end_matches('/'),
+                target.bucket
+            )
+        } else {
+            let host = endpoint
+                .host_str()
+                .ok_or_else(|| format!("Invalid S3 endpoint: {}", target.endpoint))?;
+            let port = endpoint
+                .port()
+                .map(|p| format!(":{}", p))
+                .unwrap_or_default();
+            format!("{}://{}.{}{}", endpoint.scheme(), target.bucket, host, port)
+        };
+        let bucket_url =
+            Url::parse(&bucket_url).map_err(|e| format!("Invalid S3 bucket URL: {}", e))?;
+
+        // Generous, since a single part can take a while on a slow uplink
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(30))
+            .timeout(Duration::from_secs(600))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        Ok(Self {
+            client,
+            target,
+            bucket_url,
+        })
+    }
+
+    /// Sign and send a request for an object, retrying network errors and server
+    /// errors with a growing delay
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, String)],
+        body: Vec<u8>,
+    ) -> Result<Response, String> {
+        let path = format!(
+            "{}/{}",
+            self.bucket_url.path().trim_end_matches('/'),
+            uri_encode(key, true)
+        );
+        let query = canonical_query(query);
+
+        let mut url = self.bucket_url.clone();
+        url.set_path(&path);
+        url.set_query(if query.is_empty() { None } else { Some(&query) });
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let payload_hash = sha256_hex(&body);
+
+        let mut attempt = 1;
+        loop {
+            let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+            let mut signed: Vec<(String, String)> = vec![
+                ("host".to_string(), host.clone()),
+                ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+                ("x-amz-date".to_string(), amz_date.clone()),
+            ];
+            if let Some(token) = &self.target.session_token {
+                signed.push(("x-amz-security-token".to_string(), token.clone()));
+            }
+            for (name, value) in headers {
+                signed.push((name.to_lowercase(), canonical_header_value(value)));
+            }
+            signed.sort();
+
+            let authorization = CanonicalRequest {
+                method: method.as_str(),
+                path: &path,
+                query: &query,
+                headers: &signed,
+                payload_hash: &payload_hash,
+            }
+            .authorization(&self.target, "s3", &amz_date);
+
+            // reqwest sets the host header itself, from the same URL
+            let mut request = self
+                .client
+                .request(method.clone(), url.clone())
+                .header("authorization", authorization)
+                .body(body.clone());
+            for (name, value) in signed.iter().filter(|(name, _)| name != "host") {
+                request = request.header(name.as_str(), value.as_str());
+            }
+
+            let error = match request.send().await {
+                Ok(response) if !response.status().is_server_error() => return Ok(response),
+                Ok(response) => format!("status {}", response.status()),
+                Err(e) => e.to_string(),
+            };
+            if attempt >= MAX_ATTEMPTS {
+                return Err(format!("S3 request failed: {}", error));
+            }
+
+            warn!(
+                "S3 request for {} failed (attempt {}), retrying: {}",
+                key, attempt, error
+            );
+            tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+            attempt += 1;
+        }
+    }
+
+    /// Turn an unsuccessful response into an error describing `action`
+    async fn check(response: Response, action: &str) -> Result<Response, String> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        let message = xml_value(&body, "Message").unwrap_or(body);
+        Err(format!(
+            "{} failed with status {}: {}",
+            action, status, message
+        ))
+    }
+
+    /// Hash recorded on an object by an earlier export, or None if it doesn't exist
+    pub async fn uploaded_hash(&self, key: &str) -> Result<Option<String>, String> {
+        let response = self.send(Method::HEAD, key, &[], &[], Vec::new()).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let response = Self::check(response, "Checking an object").await?;
+        Ok(response
+            .headers()
+            .get(HASH_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string()))
+    }
+
+    /// Upload a file, in parts if it's large; the job's progress advances by the
+    /// bytes sent
+    pub async fn upload_file(
+        &self,
+        job: &Job,
+        key: &str,
+        path: &Path,
+        size: u64,
+        hash: &str,
+    ) -> Result<(), String> {
+        let content_type = formats::mime_type(path).unwrap_or("application/octet-stream");
+        let headers = [
+            ("content-type", content_type.to_string()),
+            (HASH_HEADER, hash.to_string()),
+        ];
+        let read_error = |e: io::Error| format!("Failed to read {}: {}", path.display(), e);
+
+        if size <= PART_SIZE {
+            let body = read_range(path, 0, size).map_err(read_error)?;
+            let response = self.send(Method::PUT, key, &[], &headers, body).await?;
+            Self::check(response, "Uploading").await?;
+            job.advance(size);
+            return Ok(());
+        }
+
+        let response = self
+            .send(Method::POST, key, &[("uploads", "")], &headers, Vec::new())
+            .await?;
+        let response = Self::check(response, "Starting a multipart upload").await?;
+        let body = response.text().await.map_err(|e| e.to_string())?;
+        let upload_id = xml_value(&body, "UploadId")
+            .ok_or_else(|| "No upload id in the S3 response".to_string())?;
+
+        let result = self.upload_parts(job, key, path, size, &upload_id).await;
+        if result.is_err() {
+            // Parts of an abandoned upload are stored (and billed) until it's aborted
+            let aborted = self
+                .send(
+                    Method::DELETE,
+                    key,
+                    &[("uploadId", &upload_id)],
+                    &[],
+                    Vec::new(),
+                )
+                .await;
+            if let Err(e) = aborted {
+                warn!("Failed to abort multipart upload of {}: {}", key, e);
+            }
+        }
+        result
+    }
+
+    async fn upload_parts(
+        &self,
+        job: &Job,
+        key: &str,
+        path: &Path,
+        size: u64,
+        upload_id: &str,
+    ) -> Result<(), String> {
+        let part_size = PART_SIZE.max(size.div_ceil(MAX_PARTS));
+        let mut completed = String::from("<CompleteMultipartUpload>");
+
+        let mut offset = 0;
+        let mut number = 1;
+        while offset < size {
+            job.check_cancelled()?;
+            let len = part_size.min(size - offset);
+            let body = read_range(path, offset, len)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let part = number.to_string();
+            let query = [("partNumber", part.as_str()), ("uploadId", upload_id)];
+            let response = self.send(Method::PUT, key, &query, &[], body).await?;
+            let response = Self::check(response, "Uploading a part").await?;
+            let etag = response
+                .headers()
+                .get(ETAG)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| format!("No ETag for part {} of {}", number, key))?;
+            completed.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                number, etag
+            ));
+
+            job.advance(len);
+            offset += len;
+            number += 1;
+        }
+        completed.push_str("</CompleteMultipartUpload>");
+
+        let response = self
+            .send(
+                Method::POST,
+                key,
+                &[("uploadId", upload_id)],
+                &[],
+                completed.into_bytes(),
+            )
+            .await?;
+        let response = Self::check(response, "Completing a multipart upload").await?;
+
+        // Completion can fail after the response started, with an error in the body
+        let body = response.text().await.map_err(|e| e.to_string())?;
+        if body.contains("<Error>") {
+            return Err(format!(
+                "Completing a multipart upload failed: {}",
+                xml_value(&body, "Message").unwrap_or(body)
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Requests of the AWS Signature Version 4 test suite, signed for its example
+    // credentials and service
+
+    const AMZ_DATE: &str = "20150830T123600Z";
+
+    fn example_target() -> S3Target {
+        S3Target {
+            endpoint: "https://example.amazonaws.com".to_string(),
+            region: "us-east-1".to_string(),
+            bucket: "example".to_string(),
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+            prefix: String::new(),
+            path_style: false,
+        }
+    }
+
+    fn signature(path: &str, query: &[(&str, &str)], headers: &[(&str, &str)]) -> String {
+        let mut headers: Vec<(String, String)> = headers
+            .iter()
+            .chain(&[("Host", "example.amazonaws.com"), ("X-Amz-Date", AMZ_DATE)])
+            .map(|(name, value)| (name.to_lowercase(), canonical_header_value(value)))
+            .collect();
+        headers.sort();
+        let authorization = CanonicalRequest {
+            method: "GET",
+            path: &uri_encode(path, true),
+            query: &canonical_query(query),
+            headers: &headers,
+            payload_hash: &sha256_hex(b""),
+        }
+        .authorization(&example_target(), "service", AMZ_DATE);
+        authorization
+            .rsplit("Signature=")
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    #[test]
+    fn signs_get_vanilla() {
+        let mut headers = vec![
+            ("host".to_string(), "example.amazonaws.com".to_string()),
+            ("x-amz-date".to_string(), AMZ_DATE.to_string()),
+        ];
+        headers.sort();
+        let request = CanonicalRequest {
+            method: "GET",
+            path: "/",
+            query: "",
+            headers: &headers,
+            payload_hash: &sha256_hex(b""),
+        };
+        assert_eq!(
+            sha256_hex(request.text().as_bytes()),
+            "bb579772317eb040ac9ed261061d46c1f17a8133879d6129b6e1c25292927e63"
+        );
+        assert_eq!(
+            request.authorization(&example_target(), "service", AMZ_DATE),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn sorts_and_encodes_the_query() {
+        // get-vanilla-query-order-key-case
+        assert_eq!(
+            canonical_query(&[("Param2", "value2"), ("Param1", "value1")]),
+            "Param1=value1&Param2=value2"
+        );
+        assert_eq!(
+            signature("/", &[("Param2", "value2"), ("Param1", "value1")], &[]),
+            "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"
+        );
+
+        // get-vanilla-utf8-query
+        assert_eq!(canonical_query(&[("\u{1234}", "bar")]), "%E1%88%B4=bar");
+        assert_eq!(
+            signature("/", &[("\u{1234}", "bar")], &[]),
+            "2cdec8eed098649ff3a119c94853b13c643bcf08f8b0a1d91e12c9027818dd04"
+        );
+    }
+
+    #[test]
+    fn encodes_the_path() {
+        // get-utf8
+        assert_eq!(uri_encode("/\u{1234}", true), "/%E1%88%B4");
+        assert_eq!(
+            signature("/\u{1234}", &[], &[]),
+            "8318018e0b0f223aa2bbf98705b62bb787dc9c0e678f255a891fd03141be5d85"
+        );
+
+        // get-space
+        assert_eq!(
+            signature("/example space/", &[], &[]),
+            "652487583200325589f1fba4c7e578f72c47cb61beeca81406b39ddec1366741"
+        );
+    }
+
+    #[test]
+    fn trims_header_values() {
+        // get-header-value-trim
+        assert_eq!(canonical_header_value(" \"a   b   c\" "), "\"a b c\"");
+        assert_eq!(
+            signature(
+                "/",
+                &[],
+                &[("My-Header1", " value1"), ("My-Header2", " \"a   b   c\"")]
+            ),
+            "acc3ed3afb60bb290fc8d2dd0098b9911fcaa05412b367055dee359757a9c736"
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde::Deserialize;
+use ssh2::{CheckResult, FileStat, HashType, KnownHostFileKind, RenameFlags, Session, Sftp};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::jobs::manager::{Job, CANCELLED};
+
+/// Suffix of files being uploaded; they are renamed into place once complete
+const PARTIAL_SUFFIX: &str = ".spacecat-partial";
+
+/// Bytes copied between cancellation checks and progress updates
+const CHUNK_SIZE: usize = 256 * 1024;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+fn default_port() -> u16 {
+    22
+}
+
+/// A directory on a server reachable over SSH
+#[derive(Debug, Clone, Deserialize)]
+pub struct SftpTarget {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub username: String,
+    /// Private key file; the SSH agent is used when none is given
+    #[serde(default)]
+    pub private_key: Option<String>,
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    /// Directory on the server the project is synced into
+    pub remote_path: String,
+    /// Accepted host key of a server missing from `known_hosts`, as the `SHA256:...`
+    /// fingerprint shown by the first connection attempt
+    #[serde(default)]
+    pub host_fingerprint: Option<String>,
+}
+
+impl SftpTarget {
+    /// Where the export ends up, as `user@host:path`
+    pub fn location(&self) -> String {
+        format!("{}@{}:{}", self.username, self.host, self.remote_path)
+    }
+
+    /// Remote path of a file relative to the export
+    pub fn remote_file(&self, relative: &str) -> String {
+        let root = self.remote_path.trim_end_matches('/');
+        format!("{}/{}", root, relative.replace('\\', "/"))
+    }
+}
+
+/// Size and modification time (seconds since the epoch) of a file, compared like
+/// rsync's quick check to tell changed files
+pub type FileStamp = (u64, u64);
+
+fn ssh_error(e: ssh2::Error) -> String {
+    format!("SSH error: {}", e)
+}
+
+/// The `SHA256:...` fingerprint of the server's host key, as OpenSSH shows it
+fn host_fingerprint(session: &Session) -> Option<String> {
+    let hash = session.host_key_hash(HashType::Sha256)?;
+    Some(format!(
+        "SHA256:{}",
+        general_purpose::STANDARD_NO_PAD.encode(hash)
+    ))
+}
+
+/// Check the server's host key against `known_hosts`, or the fingerprint the user
+/// accepted for a server that isn't in it
+fn verify_host(session: &Session, target: &SftpTarget, known_hosts: &Path) -> Result<(), String> {
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| "The server sent no host key".to_string())?;
+    let fingerprint = host_fingerprint(session).unwrap_or_default();
+
+    let mut hosts = session.known_hosts().map_err(ssh_error)?;
+    if known_hosts.is_file() {
+        hosts
+            .read_file(known_hosts, KnownHostFileKind::OpenSSH)
+            .map_err(ssh_error)?;
+    }
+
+    match hosts.check_port(&target.host, target.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(format!(
+            "Host key of {} changed ({}); refusing to connect",
+            target.host, fingerprint
+        )),
+        _ if target.host_fingerprint.as_deref() == Some(fingerprint.as_str()) => Ok(()),
+        _ => Err(format!(
+            "Unknown host {} with key fingerprint {}; confirm it to connect",
+            target.host, fingerprint
+        )),
+    }
+}
+
+/// Connect and authenticate to the server of a target, returning its SFTP channel
+pub fn connect(target: &SftpTarget, known_hosts: &Path) -> Result<Sftp, String> {
+    let address = (target.host.as_str(), target.port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", target.host, e))?
+        .next()
+        .ok_or_else(|| format!("Failed to resolve {}", target.host))?;
+    let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+        .map_err(|e| format!("Failed to connect to {}: {}", target.host, e))?;
+
+    let mut session = Session::new().map_err(ssh_error)?;
+    session.set_tcp_stream(stream);
+    session.handshake().map_err(ssh_error)?;
+    verify_host(&session, target, known_hosts)?;
+
+    match &target.private_key {
+        Some(key) => session.userauth_pubkey_file(
+            &target.username,
+            None,
+            Path::new(key),
+            target.passphrase.as_deref(),
+        ),
+        None => session.userauth_agent(&target.username),
+    }
+    .map_err(|e| format!("SSH authentication failed: {}", e))?;
+    if !session.authenticated() {
+        return Err("SSH authentication failed".to_string());
+    }
+
+    session.sftp().map_err(ssh_error)
+}
+
+/// List the files below a remote directory with their stamps, by path relative to it;
+/// empty if the directory doesn't exist yet
+pub fn list_remote(sftp: &Sftp, root: &str) -> Result<HashMap<String, FileStamp>, String> {
+    let mut files = HashMap::new();
+    let mut pending = vec![String::new()];
+
+    while let Some(relative) = pending.pop() {
+        let dir = match relative.as_str() {
+            "" => root.trim_end_matches('/').to_string(),
+            _ => format!("{}/{}", root.trim_end_matches('/'), relative),
+        };
+        let entries = match sftp.readdir(Path::new(&dir)) {
+            Ok(entries) => entries,
+            Err(_) if relative.is_empty() => return Ok(files),
+            Err(e) => return Err(format!("Failed to list {}: {}", dir, e)),
+        };
+
+        for (path, stat) in entries {
+            let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let child = match relative.as_str() {
+                "" => name,
+                _ => format!("{}/{}", relative, name),
+            };
+            if stat.is_dir() {
+                pending.push(child);
+            } else if stat.is_file() {
+                files.insert(child, (stat.size.unwrap_or(0), stat.mtime.unwrap_or(0)));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Create a remote directory and its missing parents
+fn create_dirs(sftp: &Sftp, dir: &str) -> Result<(), String> {
+    let mut current = String::new();
+    for part in dir.split('/') {
+        if part.is_empty() {
+            if current.is_empty() {
+                current.push('/');
+            }
+            continue;
+        }
+        if !current.is_empty() && !current.ends_with('/') {
+            current.push('/');
+        }
+        current.push_str(part);
+
+        if sftp.stat(Path::new(&current)).is_err() {
+            sftp.mkdir(Path::new(&current), 0o755)
+                .map_err(|e| format!("Failed to create {}: {}", current, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Local file stamp, to compare with the remote copy
+pub fn local_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = path.metadata().ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some((metadata.len(), modified))
+}
+
+/// Upload a file, replacing the remote copy only once it is complete
+///
+/// The remote file gets the local modification time, so the next export can tell it
+/// is unchanged. The job's progress advances by the bytes sent.
+pub fn upload_file(
+    job: &Job,
+    sftp: &Sftp,
+    local: &Path,
+    remote: &str,
+    stamp: FileStamp,
+) -> Result<(), String> {
+    if let Some((dir, _)) = remote.rsplit_once('/') {
+        if !dir.is_empty() {
+            create_dirs(sftp, dir)?;
+        }
+    }
+
+    let partial = format!("{}{}", remote, PARTIAL_SUFFIX);
+    let mut source =
+        File::open(local).map_err(|e| format!("Failed to open {}: {}", local.display(), e))?;
+    let mut target = sftp
+        .create(Path::new(&partial))
+        .map_err(|e| format!("Failed to create {}: {}", partial, e))?;
+
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let copied = loop {
+        if job.is_cancelled() {
+            break Err(CANCELLED.to_string());
+        }
+        let read = match source.read(&mut buffer) {
+            Ok(0) => break Ok(()),
+            Ok(read) => read,
+            Err(e) => break Err(format!("Failed to read {}: {}", local.display(), e)),
+        };
+        if let Err(e) = target.write_all(&buffer[..read]) {
+            break Err(format!("Failed to write {}: {}", partial, e));
+        }
+        job.advance(read as u64);
+    };
+    drop(target);
+    if let Err(e) = copied {
+        let _ = sftp.unlink(Path::new(&partial));
+        return Err(e);
+    }
+
+    let stat = FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: None,
+        atime: Some(stamp.1),
+        mtime: Some(stamp.1),
+    };
+    sftp.setstat(Path::new(&partial), stat)
+        .map_err(|e| format!("Failed to set the time of {}: {}", partial, e))?;
+
+    // Servers speaking SFTP v3 can't rename over an existing file
+    let flags = RenameFlags::OVERWRITE | RenameFlags::ATOMIC | RenameFlags::NATIVE;
+    if sftp
+        .rename(Path::new(&partial), Path::new(remote), Some(flags))
+        .is_err()
+    {
+        let _ = sftp.unlink(Path::new(remote));
+        sftp.rename(Path::new(&partial), Path::new(remote), None)
+            .map_err(|e| format!("Failed to move {} into place: {}", remote, e))?;
+    }
+
+    Ok(())
+}
+
+/// Delete a remote file
+pub fn remove_file(sftp: &Sftp, remote: &str) -> Result<(), String> {
+    sftp.unlink(Path::new(remote))
+        .map_err(|e| format!("Failed to delete {}: {}", remote, e))
+}
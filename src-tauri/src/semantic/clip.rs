@@ -0,0 +1,228 @@
+use image::imageops::FilterType;
+use once_cell::sync::Lazy;
+use ort::session::Session;
+use ort::value::Tensor;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tokenizers::Tokenizer;
+
+use crate::media::formats;
+
+/// Files a CLIP model directory must contain, as exported by Hugging Face Optimum
+pub const TEXT_MODEL_FILE: &str = "text_model.onnx";
+pub const VISION_MODEL_FILE: &str = "vision_model.onnx";
+pub const TOKENIZER_FILE: &str = "tokenizer.json";
+
+/// Side of the square images the vision model takes
+const IMAGE_SIZE: u32 = 224;
+
+/// Tokens in CLIP's text context, including the start and end tokens
+const CONTEXT_LENGTH: usize = 77;
+
+/// Per-channel normalization CLIP was trained with
+const MEAN: [f32; 3] = [0.481_454_66, 0.457_827_5, 0.408_210_73];
+const STD: [f32; 3] = [0.268_629_54, 0.261_302_6, 0.275_777_1];
+
+/// Loaded once and kept for the whole session, since loading takes seconds
+static MODEL: Lazy<Mutex<Option<ClipModel>>> = Lazy::new(|| Mutex::new(None));
+
+/// A CLIP text and vision model pair with its tokenizer
+pub struct ClipModel {
+    dir: PathBuf,
+    signature: String,
+    text: Session,
+    vision: Session,
+    tokenizer: Tokenizer,
+}
+
+fn model_error(e: impl std::fmt::Display) -> String {
+    format!("CLIP model error: {}", e)
+}
+
+/// Identifies the installed model, so embeddings from another model are recomputed
+fn model_signature(dir: &Path) -> Result<String, String> {
+    let metadata = fs::metadata(dir.join(VISION_MODEL_FILE)).map_err(model_error)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    Ok(format!("clip:{}:{}", metadata.len(), modified))
+}
+
+/// Check that a directory holds every file of a CLIP model
+pub fn is_installed(dir: &Path) -> bool {
+    [TEXT_MODEL_FILE, VISION_MODEL_FILE, TOKENIZER_FILE]
+        .iter()
+        .all(|file| dir.join(file).is_file())
+}
+
+impl ClipModel {
+    fn load(dir: &Path) -> Result<Self, String> {
+        if !is_installed(dir) {
+            return Err(format!(
+                "CLIP model not found: {} needs {}, {} and {}",
+                dir.display(),
+                TEXT_MODEL_FILE,
+                VISION_MODEL_FILE,
+                TOKENIZER_FILE
+            ));
+        }
+
+        let session = |file: &str| -> Result<Session, String> {
+            Session::builder()
+                .map_err(model_error)?
+                .commit_from_file(dir.join(file))
+                .map_err(model_error)
+        };
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            signature: model_signature(dir)?,
+            text: session(TEXT_MODEL_FILE)?,
+            vision: session(VISION_MODEL_FILE)?,
+            tokenizer: Tokenizer::from_file(dir.join(TOKENIZER_FILE)).map_err(model_error)?,
+        })
+    }
+
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// Embed a text query as a unit vector
+    pub fn embed_text(&mut self, text: &str) -> Result<Vec<f32>, String> {
+        let encoding = self.tokenizer.encode(text, true).map_err(model_error)?;
+        let mut ids: Vec<i64> = encoding.get_ids().iter().map(|&id| i64::from(id)).collect();
+        let end_token = ids.last().copied().unwrap_or(0);
+
+        // Long queries are cut off, keeping the end token the model pools on
+        if ids.len() > CONTEXT_LENGTH {
+            ids.truncate(CONTEXT_LENGTH);
+            ids[CONTEXT_LENGTH - 1] = end_token;
+        }
+        let mut mask = vec![1i64; ids.len()];
+        ids.resize(CONTEXT_LENGTH, end_token);
+        mask.resize(CONTEXT_LENGTH, 0);
+
+        // Some exports take no attention mask
+        let wants_mask = self
+            .text
+            .inputs()
+            .iter()
+            .any(|input| input.name() == "attention_mask");
+        let input_ids = Tensor::from_array(([1usize, CONTEXT_LENGTH], ids)).map_err(model_error)?;
+        let outputs = if wants_mask {
+            let attention_mask =
+                Tensor::from_array(([1usize, CONTEXT_LENGTH], mask)).map_err(model_error)?;
+            self.text.run(ort::inputs![
+                "input_ids" => input_ids,
+                "attention_mask" => attention_mask,
+            ])
+        } else {
+            self.text.run(ort::inputs!["input_ids" => input_ids])
+        }
+        .map_err(model_error)?;
+
+        let embedding = outputs
+            .get("text_embeds")
+            .unwrap_or(&outputs[0])
+            .try_extract_tensor::<f32>()
+            .map_err(model_error)?
+            .1
+            .to_vec();
+
+        Ok(normalize(embedding))
+    }
+
+    /// Embed preprocessed pixels (see [`preprocess_image`]) as a unit vector
+    pub fn embed_pixels(&mut self, pixels: Vec<f32>) -> Result<Vec<f32>, String> {
+        let size = IMAGE_SIZE as usize;
+        let pixel_values =
+            Tensor::from_array(([1usize, 3, size, size], pixels)).map_err(model_error)?;
+        let outputs = self
+            .vision
+            .run(ort::inputs!["pixel_values" => pixel_values])
+            .map_err(model_error)?;
+
+        let embedding = outputs
+            .get("image_embeds")
+            .unwrap_or(&outputs[0])
+            .try_extract_tensor::<f32>()
+            .map_err(model_error)?
+            .1
+            .to_vec();
+
+        Ok(normalize(embedding))
+    }
+}
+
+/// Scale a vector to unit length, so cosine similarity is a dot product
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+/// Cosine similarity of two unit vectors
+pub fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Load an image as CLIP expects it: the shorter side scaled to 224 pixels, center
+/// cropped to a square and normalized, in channel-first order
+///
+/// Doesn't need the model, so images can be prepared in parallel.
+pub fn preprocess_image(path: &Path) -> Result<Vec<f32>, String> {
+    let image = formats::open_image(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    let (width, height) = (image.width().max(1), image.height().max(1));
+    let scale = IMAGE_SIZE as f32 / width.min(height) as f32;
+    let resized = image.resize_exact(
+        ((width as f32 * scale).round() as u32).max(IMAGE_SIZE),
+        ((height as f32 * scale).round() as u32).max(IMAGE_SIZE),
+        FilterType::CatmullRom,
+    );
+    let x = (resized.width() - IMAGE_SIZE) / 2;
+    let y = (resized.height() - IMAGE_SIZE) / 2;
+    let rgb = resized.crop_imm(x, y, IMAGE_SIZE, IMAGE_SIZE).to_rgb8();
+
+    let plane = (IMAGE_SIZE * IMAGE_SIZE) as usize;
+    let mut pixels = vec![0f32; 3 * plane];
+    for (i, pixel) in rgb.pixels().enumerate() {
+        for channel in 0..3 {
+            let value = f32::from(pixel[channel]) / 255.0;
+            pixels[channel * plane + i] = (value - MEAN[channel]) / STD[channel];
+        }
+    }
+
+    Ok(pixels)
+}
+
+/// Run a function with the CLIP model from `dir`, loading it on first use
+pub fn with_model<T>(
+    dir: &Path,
+    run: impl FnOnce(&mut ClipModel) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut model = MODEL.lock().map_err(|e| e.to_string())?;
+
+    let stale = match model.as_ref() {
+        Some(loaded) => loaded.dir != dir || loaded.signature != model_signature(dir)?,
+        None => true,
+    };
+    if stale {
+        *model = None;
+        *model = Some(ClipModel::load(dir)?);
+    }
+
+    match model.as_mut() {
+        Some(model) => run(model),
+        None => Err("CLIP model is not loaded".to_string()),
+    }
+}
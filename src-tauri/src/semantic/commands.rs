@@ -0,0 +1,444 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use super::clip;
+use super::subjects;
+use super::text;
+use crate::file_system::commands::{validate_project_directory, MediaFile};
+use crate::jobs::manager::{self as jobs, Job, JobKind};
+use crate::jobs::pool;
+use crate::project::index::{self, PendingImage};
+
+/// Images embedded per batch; each batch is saved, so an interrupted run keeps its work
+const BATCH_SIZE: usize = 32;
+
+/// Share of an image covered by text above which it is flagged; captions and
+/// watermarks stay well below it, memes and screenshots well above
+const DEFAULT_TEXT_THRESHOLD: f32 = 0.05;
+
+/// Number of matches returned when no limit is given
+const DEFAULT_LIMIT: usize = 50;
+
+/// CLIP similarity below which a caption is flagged as probably not describing its
+/// image; matching pairs typically score around 0.25 to 0.35
+const DEFAULT_ALIGNMENT_THRESHOLD: f32 = 0.2;
+
+/// What to search a project for: a text description or an example image
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SemanticQuery {
+    Text(String),
+    Image(String),
+}
+
+/// An image matching a semantic search, with its cosine similarity to the query
+#[derive(Debug, Serialize)]
+pub struct SemanticMatch {
+    pub file: MediaFile,
+    pub score: f32,
+}
+
+/// CLIP similarity between an image and its caption
+#[derive(Debug, Serialize)]
+pub struct AlignmentScore {
+    pub file: MediaFile,
+    pub score: f32,
+    /// Scored below the threshold, so the caption may not fit the image
+    pub flagged: bool,
+}
+
+/// Caption alignment of a project, worst-scoring images first
+#[derive(Debug, Serialize)]
+pub struct AlignmentReport {
+    pub threshold: f32,
+    pub mean_score: f32,
+    pub flagged: usize,
+    pub scores: Vec<AlignmentScore>,
+}
+
+/// Where a model is expected, and whether it is there
+#[derive(Debug, Serialize)]
+pub struct ModelInfo {
+    pub directory: String,
+    pub installed: bool,
+}
+
+/// Share of an image covered by text
+#[derive(Debug, Serialize)]
+pub struct TextCoverage {
+    pub file: MediaFile,
+    pub coverage: f32,
+    /// Covered beyond the threshold, e.g. a meme or a screenshot
+    pub flagged: bool,
+}
+
+/// Text coverage of a project's images, most text first
+#[derive(Debug, Serialize)]
+pub struct TextReport {
+    pub threshold: f32,
+    pub flagged: usize,
+    pub scores: Vec<TextCoverage>,
+}
+
+/// Get the directory the CLIP model is loaded from
+pub(crate) fn clip_model_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join("spacecat-models").join("clip"))
+}
+
+/// Get the directory the text detection model is loaded from
+fn text_model_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join("spacecat-models").join("text"))
+}
+
+/// Get the directory the subject detection models are loaded from
+fn subject_model_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join("spacecat-models").join("subjects"))
+}
+
+/// Embed every image of a project that has no up-to-date embedding, returning how
+/// many were embedded
+fn update_embeddings(job: &Job, project: &Path, model_dir: &Path) -> Result<usize, String> {
+    let signature = clip::with_model(model_dir, |model| Ok(model.signature().to_string()))?;
+    let pending = index::images_without_embeddings(project, &signature)?;
+    let total = pending.len() as u64;
+    let mut processed = 0;
+    let mut embedded = 0;
+
+    let mut pending = pending.into_iter().peekable();
+    while pending.peek().is_some() {
+        job.check_cancelled()?;
+        let batch: Vec<PendingImage> = pending.by_ref().take(BATCH_SIZE).collect();
+        processed += batch.len() as u64;
+
+        // Decoding and resizing dominate, and don't need the model
+        let pixels: Vec<_> = pool::install(|| {
+            batch
+                .par_iter()
+                .map(|image| clip::preprocess_image(Path::new(&image.path)))
+                .collect()
+        });
+
+        let vectors = clip::with_model(model_dir, |model| {
+            let mut vectors = Vec::new();
+            for (image, pixels) in batch.into_iter().zip(pixels) {
+                match pixels.and_then(|pixels| model.embed_pixels(pixels)) {
+                    Ok(vector) => vectors.push((image, vector)),
+                    Err(e) => warn!("Skipping {} for semantic search: {}", image.path, e),
+                }
+            }
+            Ok(vectors)
+        })?;
+
+        index::store_embeddings(project, &signature, &vectors)?;
+        embedded += vectors.len();
+        job.progress(processed, total, Some("Embedding images".to_string()));
+    }
+
+    Ok(embedded)
+}
+
+/// Get where the CLIP model used by semantic search is expected
+///
+/// The directory must hold `text_model.onnx`, `vision_model.onnx` and `tokenizer.json`,
+/// as exported from a Hugging Face CLIP model.
+#[tauri::command]
+pub async fn get_clip_model_info(app: AppHandle) -> Result<ModelInfo, String> {
+    let dir = clip_model_dir(&app)?;
+
+    Ok(ModelInfo {
+        directory: dir.to_string_lossy().to_string(),
+        installed: clip::is_installed(&dir),
+    })
+}
+
+/// Compute CLIP embeddings for the images of a project that don't have one yet
+///
+/// Runs as an indexing job and returns the number of images embedded. Embeddings are
+/// kept in the project index, so only new or changed images are embedded again.
+#[tauri::command]
+pub async fn index_embeddings(app: AppHandle, directory: String) -> Result<usize, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let model_dir = clip_model_dir(&app)?;
+    let description = format!("Embed images of {}", directory);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        update_embeddings(job, &project, &model_dir)
+    })
+    .await
+}
+
+/// Find the images of a project closest to a text description or an example image
+///
+/// Images without an up-to-date embedding are embedded first, so the first search of
+/// a large project takes a while; it runs as a cancellable indexing job. Matches are
+/// ordered by similarity, best first. An example image is left out of its own results.
+#[tauri::command]
+pub async fn semantic_search(
+    app: AppHandle,
+    directory: String,
+    query: SemanticQuery,
+    limit: Option<usize>,
+) -> Result<Vec<SemanticMatch>, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let model_dir = clip_model_dir(&app)?;
+    let description = format!("Semantic search in {}", directory);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        update_embeddings(job, &project, &model_dir)?;
+        job.check_cancelled()?;
+
+        let (target, example) = match &query {
+            SemanticQuery::Text(text) => (
+                clip::with_model(&model_dir, |model| model.embed_text(text))?,
+                None,
+            ),
+            SemanticQuery::Image(path) => {
+                let pixels = clip::preprocess_image(Path::new(path))?;
+                let vector = clip::with_model(&model_dir, |model| model.embed_pixels(pixels))?;
+                (vector, Some(path.as_str()))
+            }
+        };
+
+        let signature = clip::with_model(&model_dir, |model| Ok(model.signature().to_string()))?;
+        let mut matches: Vec<SemanticMatch> = index::embeddings(&project, &signature)?
+            .into_iter()
+            .filter(|(file, _)| Some(file.path.as_str()) != example)
+            .map(|(file, vector)| SemanticMatch {
+                score: clip::similarity(&target, &vector),
+                file,
+            })
+            .collect();
+
+        matches.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+        matches.truncate(limit.unwrap_or(DEFAULT_LIMIT));
+
+        Ok(matches)
+    })
+    .await
+}
+
+/// Score how well each captioned image matches its caption with CLIP
+///
+/// Pairs scoring below `threshold` (0.2 by default) are flagged, to surface files
+/// that were mis-captioned, e.g. after a big batch run. CLIP only sees the first 77
+/// tokens of a caption. Runs as a cancellable indexing job, embedding images first
+/// like `semantic_search`.
+#[tauri::command]
+pub async fn score_caption_alignment(
+    app: AppHandle,
+    directory: String,
+    threshold: Option<f32>,
+) -> Result<AlignmentReport, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let model_dir = clip_model_dir(&app)?;
+    let threshold = threshold.unwrap_or(DEFAULT_ALIGNMENT_THRESHOLD);
+    let description = format!("Score captions of {}", directory);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        update_embeddings(job, &project, &model_dir)?;
+
+        let signature = clip::with_model(&model_dir, |model| Ok(model.signature().to_string()))?;
+        let images: HashMap<String, Vec<f32>> = index::embeddings(&project, &signature)?
+            .into_iter()
+            .map(|(file, vector)| (file.relative_path, vector))
+            .collect();
+        let captioned: Vec<_> = index::captions(&project)?
+            .into_iter()
+            .filter(|(file, caption)| {
+                images.contains_key(&file.relative_path) && !caption.trim().is_empty()
+            })
+            .collect();
+
+        let total = captioned.len() as u64;
+        let mut scores = Vec::new();
+        for (done, (file, caption)) in captioned.into_iter().enumerate() {
+            job.check_cancelled()?;
+            if done % 25 == 0 {
+                job.progress(done as u64, total, Some("Scoring captions".to_string()));
+            }
+
+            let text = clip::with_model(&model_dir, |model| model.embed_text(&caption))?;
+            let score = clip::similarity(&images[&file.relative_path], &text);
+            scores.push(AlignmentScore {
+                file,
+                score,
+                flagged: score < threshold,
+            });
+        }
+
+        scores.sort_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(Ordering::Equal));
+        let mean_score = if scores.is_empty() {
+            0.0
+        } else {
+            scores.iter().map(|s| s.score).sum::<f32>() / scores.len() as f32
+        };
+
+        Ok(AlignmentReport {
+            threshold,
+            mean_score,
+            flagged: scores.iter().filter(|s| s.flagged).count(),
+            scores,
+        })
+    })
+    .await
+}
+
+/// Get where the text detection model is expected
+///
+/// The directory must hold `text_detection.onnx`, an EAST or DB text detector such as
+/// the detection model of PaddleOCR.
+#[tauri::command]
+pub async fn get_text_model_info(app: AppHandle) -> Result<ModelInfo, String> {
+    let dir = text_model_dir(&app)?;
+
+    Ok(ModelInfo {
+        directory: dir.to_string_lossy().to_string(),
+        installed: text::is_installed(&dir),
+    })
+}
+
+/// Score how much of each image of a project is covered by text
+///
+/// Images covered beyond `threshold` (0.05 by default) are flagged, so memes and
+/// screenshots can be filtered out of a photo dataset or captioned with a prompt that
+/// transcribes text. Runs as a cancellable indexing job.
+#[tauri::command]
+pub async fn detect_text_regions(
+    app: AppHandle,
+    directory: String,
+    threshold: Option<f32>,
+) -> Result<TextReport, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let model_dir = text_model_dir(&app)?;
+    let threshold = threshold.unwrap_or(DEFAULT_TEXT_THRESHOLD);
+    let description = format!("Detect text in {}", directory);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        let images: Vec<MediaFile> = index::list_files(&project)?
+            .into_iter()
+            .filter(|file| file.file_type == "image")
+            .collect();
+
+        let total = images.len() as u64;
+        let mut processed = 0;
+        let mut scores = Vec::new();
+        let mut images = images.into_iter().peekable();
+        while images.peek().is_some() {
+            job.check_cancelled()?;
+            let batch: Vec<MediaFile> = images.by_ref().take(BATCH_SIZE).collect();
+            processed += batch.len() as u64;
+
+            let inputs: Vec<_> = pool::install(|| {
+                batch
+                    .par_iter()
+                    .map(|file| text::preprocess_image(Path::new(&file.path)))
+                    .collect()
+            });
+
+            text::with_model(&model_dir, |model| {
+                for (file, input) in batch.into_iter().zip(inputs) {
+                    match input.and_then(|input| model.text_coverage(input)) {
+                        Ok(coverage) => scores.push(TextCoverage {
+                            file,
+                            coverage,
+                            flagged: coverage > threshold,
+                        }),
+                        Err(e) => warn!("Skipping {} for text detection: {}", file.path, e),
+                    }
+                }
+                Ok(())
+            })?;
+            job.progress(processed, total, Some("Detecting text".to_string()));
+        }
+
+        scores.sort_by(|a, b| {
+            b.coverage
+                .partial_cmp(&a.coverage)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        Ok(TextReport {
+            threshold,
+            flagged: scores.iter().filter(|s| s.flagged).count(),
+            scores,
+        })
+    })
+    .await
+}
+
+/// Get where the subject detection models are expected
+///
+/// The directory must hold `person_detection.onnx`, a YOLO detector whose first class
+/// is a person such as YOLOv8 trained on COCO, and may hold `face_detection.onnx`, a
+/// face detector such as YOLOv8-face.
+#[tauri::command]
+pub async fn get_subject_model_info(app: AppHandle) -> Result<ModelInfo, String> {
+    let dir = subject_model_dir(&app)?;
+
+    Ok(ModelInfo {
+        directory: dir.to_string_lossy().to_string(),
+        installed: subjects::is_installed(&dir),
+    })
+}
+
+/// Count the persons, and faces when a face model is installed, in the images of a
+/// project that don't have up-to-date counts
+///
+/// Runs as an indexing job and returns the number of images counted. Counts are kept
+/// in the project index, listed with each file and filterable, e.g. to keep only the
+/// images with exactly one subject for a character dataset.
+#[tauri::command]
+pub async fn detect_subjects(app: AppHandle, directory: String) -> Result<usize, String> {
+    let project = validate_project_directory(&app, &directory)?;
+    let model_dir = subject_model_dir(&app)?;
+    let description = format!("Detect subjects in {}", directory);
+
+    jobs::run(&app, JobKind::Indexing, description, move |job| {
+        let signature =
+            subjects::with_model(&model_dir, |model| Ok(model.signature().to_string()))?;
+        let pending = index::images_without_subjects(&project, &signature)?;
+        let total = pending.len() as u64;
+        let mut processed = 0;
+        let mut detected = 0;
+
+        let mut pending = pending.into_iter().peekable();
+        while pending.peek().is_some() {
+            job.check_cancelled()?;
+            let batch: Vec<PendingImage> = pending.by_ref().take(BATCH_SIZE).collect();
+            processed += batch.len() as u64;
+
+            let inputs: Vec<_> = pool::install(|| {
+                batch
+                    .par_iter()
+                    .map(|image| subjects::preprocess_image(Path::new(&image.path)))
+                    .collect()
+            });
+
+            let counts = subjects::with_model(&model_dir, |model| {
+                let mut counts = Vec::new();
+                for (image, input) in batch.into_iter().zip(inputs) {
+                    match input.and_then(|input| model.count(input)) {
+                        Ok(count) => counts.push((image, count)),
+                        Err(e) => warn!("Skipping {} for subject detection: {}", image.path, e),
+                    }
+                }
+                Ok(counts)
+            })?;
+
+            index::store_subjects(&project, &signature, &counts)?;
+            detected += counts.len();
+            job.progress(processed, total, Some("Detecting subjects".to_string()));
+        }
+
+        Ok(detected)
+    })
+    .await
+}
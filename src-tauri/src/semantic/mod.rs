@@ -0,0 +1,4 @@
+pub mod clip;
+pub mod commands;
+pub mod subjects;
+pub mod text;
@@ -0,0 +1,271 @@
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+use once_cell::sync::Lazy;
+use ort::session::Session;
+use ort::value::Tensor;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::media::formats;
+
+/// Files a subject detection model directory holds: YOLO detectors (v8 or later
+/// exports) whose first class is a person, such as a COCO model, and a face, such as
+/// YOLOv8-face; the face model is optional
+pub const PERSON_MODEL_FILE: &str = "person_detection.onnx";
+pub const FACE_MODEL_FILE: &str = "face_detection.onnx";
+
+/// Side of the square images the detectors take
+const IMAGE_SIZE: u32 = 640;
+
+/// Gray the letterboxing is filled with, as in YOLO training
+const PADDING: u8 = 114;
+
+/// Score above which a box counts as a detection
+const MIN_SCORE: f32 = 0.4;
+
+/// Overlap above which two boxes are taken for the same subject
+const MAX_OVERLAP: f32 = 0.5;
+
+/// Channel of the first class score in YOLO outputs, after the box
+const FIRST_CLASS: usize = 4;
+
+/// Loaded once and kept for the whole session, like the CLIP model
+static MODEL: Lazy<Mutex<Option<SubjectModel>>> = Lazy::new(|| Mutex::new(None));
+
+/// Faces and persons detected in an image
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SubjectCount {
+    /// None when no face model is installed
+    pub faces: Option<u32>,
+    pub persons: u32,
+}
+
+/// A person detection model, with a face detection model when there is one
+pub struct SubjectModel {
+    dir: PathBuf,
+    signature: String,
+    persons: Session,
+    faces: Option<Session>,
+}
+
+/// An image letterboxed for the detectors, in channel-first order
+pub struct DetectorInput {
+    pixels: Vec<f32>,
+}
+
+/// A detected box: center, size and score
+struct Detection {
+    center: (f32, f32),
+    size: (f32, f32),
+    score: f32,
+}
+
+impl Detection {
+    fn overlap(&self, other: &Detection) -> f32 {
+        let axis = |a: f32, a_size: f32, b: f32, b_size: f32| {
+            let start = (a - a_size / 2.0).max(b - b_size / 2.0);
+            let end = (a + a_size / 2.0).min(b + b_size / 2.0);
+            (end - start).max(0.0)
+        };
+        let intersection = axis(self.center.0, self.size.0, other.center.0, other.size.0)
+            * axis(self.center.1, self.size.1, other.center.1, other.size.1);
+        let union = self.size.0 * self.size.1 + other.size.0 * other.size.1 - intersection;
+        if union > 0.0 {
+            intersection / union
+        } else {
+            0.0
+        }
+    }
+}
+
+fn model_error(e: impl std::fmt::Display) -> String {
+    format!("Subject detection model error: {}", e)
+}
+
+/// Identifies the installed models, so replaced models are loaded again
+fn model_signature(dir: &Path) -> Result<String, String> {
+    let file_signature = |file: &str| {
+        fs::metadata(dir.join(file)).ok().map(|metadata| {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0);
+            format!("{}:{}", metadata.len(), modified)
+        })
+    };
+    let persons = file_signature(PERSON_MODEL_FILE)
+        .ok_or_else(|| model_error(format!("{} is missing", PERSON_MODEL_FILE)))?;
+    let faces = file_signature(FACE_MODEL_FILE).unwrap_or_else(|| "none".to_string());
+
+    Ok(format!("subjects:{}:{}", persons, faces))
+}
+
+/// Check that a directory holds a person detection model
+pub fn is_installed(dir: &Path) -> bool {
+    dir.join(PERSON_MODEL_FILE).is_file()
+}
+
+fn load_session(path: &Path) -> Result<Session, String> {
+    Session::builder()
+        .map_err(model_error)?
+        .commit_from_file(path)
+        .map_err(model_error)
+}
+
+/// Number of distinct subjects of a YOLO output: boxes whose first class scores above
+/// [`MIN_SCORE`], with overlapping boxes merged
+///
+/// Outputs are `[1, 4 + classes, boxes]`, or transposed in some exports.
+fn count_subjects(shape: &[i64], scores: &[f32]) -> Result<u32, String> {
+    let [_, a, b] = shape else {
+        return Err(model_error("unexpected output shape"));
+    };
+    let (channels, boxes, transposed) = if a <= b {
+        (*a as usize, *b as usize, false)
+    } else {
+        (*b as usize, *a as usize, true)
+    };
+    if channels <= FIRST_CLASS || scores.len() < channels * boxes {
+        return Err(model_error("unexpected output shape"));
+    }
+    let value = |channel: usize, index: usize| {
+        if transposed {
+            scores[index * channels + channel]
+        } else {
+            scores[channel * boxes + index]
+        }
+    };
+
+    let mut detections: Vec<Detection> = (0..boxes)
+        .map(|index| Detection {
+            center: (value(0, index), value(1, index)),
+            size: (value(2, index), value(3, index)),
+            score: value(FIRST_CLASS, index),
+        })
+        .filter(|detection| detection.score > MIN_SCORE)
+        .collect();
+    detections.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut kept: Vec<Detection> = Vec::new();
+    for detection in detections {
+        if kept
+            .iter()
+            .all(|other| detection.overlap(other) <= MAX_OVERLAP)
+        {
+            kept.push(detection);
+        }
+    }
+    Ok(kept.len() as u32)
+}
+
+fn detect(session: &mut Session, input: &DetectorInput) -> Result<u32, String> {
+    let input_name = session
+        .inputs()
+        .first()
+        .map(|input| input.name().to_string())
+        .ok_or_else(|| model_error("the model has no input"))?;
+    let size = IMAGE_SIZE as usize;
+    let tensor =
+        Tensor::from_array(([1usize, 3, size, size], input.pixels.clone())).map_err(model_error)?;
+    let outputs = session
+        .run(ort::inputs![input_name => tensor])
+        .map_err(model_error)?;
+
+    let (_, output) = outputs
+        .iter()
+        .next()
+        .ok_or_else(|| model_error("the model has no output"))?;
+    let (shape, scores) = output.try_extract_tensor::<f32>().map_err(model_error)?;
+    count_subjects(shape, scores)
+}
+
+impl SubjectModel {
+    fn load(dir: &Path) -> Result<Self, String> {
+        if !is_installed(dir) {
+            return Err(format!(
+                "Subject detection model not found: {} needs {}",
+                dir.display(),
+                PERSON_MODEL_FILE
+            ));
+        }
+
+        let faces = dir.join(FACE_MODEL_FILE);
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            signature: model_signature(dir)?,
+            persons: load_session(&dir.join(PERSON_MODEL_FILE))?,
+            faces: faces.is_file().then(|| load_session(&faces)).transpose()?,
+        })
+    }
+
+    /// Identifies the loaded models, to tell counts they made from older ones
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// Count the persons, and the faces when a face model is installed, in an image
+    pub fn count(&mut self, input: DetectorInput) -> Result<SubjectCount, String> {
+        let persons = detect(&mut self.persons, &input)?;
+        let faces = match self.faces.as_mut() {
+            Some(session) => Some(detect(session, &input)?),
+            None => None,
+        };
+
+        Ok(SubjectCount { faces, persons })
+    }
+}
+
+/// Load an image as the detectors expect it: scaled to fit 640 pixels, letterboxed to
+/// a square and scaled to 0-1
+///
+/// Doesn't need the model, so images can be prepared in parallel.
+pub fn preprocess_image(path: &Path) -> Result<DetectorInput, String> {
+    let image = formats::open_image(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    let fitted = image
+        .resize(IMAGE_SIZE, IMAGE_SIZE, FilterType::Triangle)
+        .to_rgb8();
+    let mut square = RgbImage::from_pixel(IMAGE_SIZE, IMAGE_SIZE, Rgb([PADDING; 3]));
+    let x = (IMAGE_SIZE - fitted.width()) / 2;
+    let y = (IMAGE_SIZE - fitted.height()) / 2;
+    imageops::replace(&mut square, &fitted, x as i64, y as i64);
+
+    let plane = (IMAGE_SIZE * IMAGE_SIZE) as usize;
+    let mut pixels = vec![0f32; 3 * plane];
+    for (i, pixel) in square.pixels().enumerate() {
+        for channel in 0..3 {
+            pixels[channel * plane + i] = f32::from(pixel[channel]) / 255.0;
+        }
+    }
+
+    Ok(DetectorInput { pixels })
+}
+
+/// Run a function with the subject detection models from `dir`, loading them on
+/// first use
+pub fn with_model<T>(
+    dir: &Path,
+    run: impl FnOnce(&mut SubjectModel) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut model = MODEL.lock().map_err(|e| e.to_string())?;
+
+    let stale = match model.as_ref() {
+        Some(loaded) => loaded.dir != dir || loaded.signature != model_signature(dir)?,
+        None => true,
+    };
+    if stale {
+        *model = None;
+        *model = Some(SubjectModel::load(dir)?);
+    }
+
+    match model.as_mut() {
+        Some(model) => run(model),
+        None => Err("Subject detection model is not loaded".to_string()),
+    }
+}
@@ -0,0 +1,176 @@
+use image::imageops::FilterType;
+use once_cell::sync::Lazy;
+use ort::session::Session;
+use ort::value::Tensor;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::media::formats;
+
+/// File a text detection model directory must contain: an EAST or DB detector that
+/// takes one normalized RGB image and outputs a text score map
+pub const MODEL_FILE: &str = "text_detection.onnx";
+
+/// Longer side images are scaled to before detection
+const MAX_SIDE: u32 = 640;
+
+/// The detectors downsample by up to 32, so both sides must be multiples of it
+const SIDE_MULTIPLE: u32 = 32;
+
+/// Score above which a point of the score map counts as text
+const TEXT_SCORE: f32 = 0.3;
+
+/// Per-channel ImageNet normalization the common DB and EAST exports expect
+const MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+/// Loaded once and kept for the whole session, like the CLIP model
+static MODEL: Lazy<Mutex<Option<TextModel>>> = Lazy::new(|| Mutex::new(None));
+
+/// A text detection model
+pub struct TextModel {
+    dir: PathBuf,
+    signature: String,
+    session: Session,
+}
+
+/// An image prepared for the detector, in channel-first order
+pub struct DetectorInput {
+    width: usize,
+    height: usize,
+    pixels: Vec<f32>,
+}
+
+fn model_error(e: impl std::fmt::Display) -> String {
+    format!("Text detection model error: {}", e)
+}
+
+/// Identifies the installed model, so a replaced model is loaded again
+fn model_signature(dir: &Path) -> Result<String, String> {
+    let metadata = fs::metadata(dir.join(MODEL_FILE)).map_err(model_error)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    Ok(format!("text:{}:{}", metadata.len(), modified))
+}
+
+/// Check that a directory holds a text detection model
+pub fn is_installed(dir: &Path) -> bool {
+    dir.join(MODEL_FILE).is_file()
+}
+
+impl TextModel {
+    fn load(dir: &Path) -> Result<Self, String> {
+        if !is_installed(dir) {
+            return Err(format!(
+                "Text detection model not found: {} needs {}",
+                dir.display(),
+                MODEL_FILE
+            ));
+        }
+
+        let session = Session::builder()
+            .map_err(model_error)?
+            .commit_from_file(dir.join(MODEL_FILE))
+            .map_err(model_error)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            signature: model_signature(dir)?,
+            session,
+        })
+    }
+
+    /// Share of an image covered by text, from 0 to 1
+    ///
+    /// Counts the points of the detector's score map above the text score, so both
+    /// DB probability maps and EAST score maps work. With several outputs (EAST also
+    /// outputs geometry), the first single-channel one is the score map.
+    pub fn text_coverage(&mut self, input: DetectorInput) -> Result<f32, String> {
+        let input_name = self
+            .session
+            .inputs()
+            .first()
+            .map(|input| input.name().to_string())
+            .ok_or_else(|| model_error("the model has no input"))?;
+        let tensor = Tensor::from_array(([1usize, 3, input.height, input.width], input.pixels))
+            .map_err(model_error)?;
+        let outputs = self
+            .session
+            .run(ort::inputs![input_name => tensor])
+            .map_err(model_error)?;
+
+        for (_, output) in outputs.iter() {
+            let (shape, scores) = output.try_extract_tensor::<f32>().map_err(model_error)?;
+            let single_channel = shape.len() < 4 || shape[1] == 1;
+            if single_channel && !scores.is_empty() {
+                let text = scores.iter().filter(|&&score| score > TEXT_SCORE).count();
+                return Ok(text as f32 / scores.len() as f32);
+            }
+        }
+        Err(model_error("the model has no score map output"))
+    }
+}
+
+/// Load an image as the detector expects it: the longer side scaled to 640 pixels,
+/// both sides rounded to multiples of 32, and normalized
+///
+/// Doesn't need the model, so images can be prepared in parallel.
+pub fn preprocess_image(path: &Path) -> Result<DetectorInput, String> {
+    let image = formats::open_image(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    let (width, height) = (image.width().max(1), image.height().max(1));
+    let scale = MAX_SIDE as f32 / width.max(height) as f32;
+    let side = |length: u32| {
+        let scaled = (length as f32 * scale / SIDE_MULTIPLE as f32).round() as u32;
+        scaled.max(1) * SIDE_MULTIPLE
+    };
+    let rgb = image
+        .resize_exact(side(width), side(height), FilterType::Triangle)
+        .to_rgb8();
+
+    let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+    let plane = width * height;
+    let mut pixels = vec![0f32; 3 * plane];
+    for (i, pixel) in rgb.pixels().enumerate() {
+        for channel in 0..3 {
+            let value = f32::from(pixel[channel]) / 255.0;
+            pixels[channel * plane + i] = (value - MEAN[channel]) / STD[channel];
+        }
+    }
+
+    Ok(DetectorInput {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Run a function with the text detection model from `dir`, loading it on first use
+pub fn with_model<T>(
+    dir: &Path,
+    run: impl FnOnce(&mut TextModel) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut model = MODEL.lock().map_err(|e| e.to_string())?;
+
+    let stale = match model.as_ref() {
+        Some(loaded) => loaded.dir != dir || loaded.signature != model_signature(dir)?,
+        None => true,
+    };
+    if stale {
+        *model = None;
+        *model = Some(TextModel::load(dir)?);
+    }
+
+    match model.as_mut() {
+        Some(model) => run(model),
+        None => Err("Text detection model is not loaded".to_string()),
+    }
+}
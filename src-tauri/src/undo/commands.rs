@@ -0,0 +1,31 @@
+use tauri::AppHandle;
+use tracing::info;
+
+use super::journal::{self, JournalEntry};
+use crate::file_system::commands::validate_project_directory;
+
+/// Undo the most recent destructive operation (delete, rename, caption overwrite,
+/// media edit) in a project, returning the operation that was reverted
+#[tauri::command]
+pub async fn undo_last_operation(app: AppHandle, project: String) -> Result<JournalEntry, String> {
+    let project_path = validate_project_directory(&app, &project)?;
+
+    let entry = journal::undo_last(&project_path)?;
+    info!("Undid operation {}: {}", entry.id, entry.description);
+
+    Ok(entry)
+}
+
+/// List the recorded operations of a project, most recent first
+#[tauri::command]
+pub async fn list_operation_history(
+    app: AppHandle,
+    project: String,
+) -> Result<Vec<JournalEntry>, String> {
+    let project_path = validate_project_directory(&app, &project)?;
+
+    let mut entries = journal::load_journal(&project_path)?;
+    entries.reverse();
+
+    Ok(entries)
+}
@@ -0,0 +1,377 @@
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::file_system::{atomic, in_use};
+use crate::project::{metadata_dir, project_root_for, settings};
+
+const JOURNAL_FILE: &str = "journal.json";
+const UNDO_DIR: &str = "undo";
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Maximum number of operations kept in a project's journal
+const MAX_OPERATIONS: usize = 50;
+
+/// Repeated saves of the same caption within this window are folded into one entry
+const COALESCE_WINDOW_SECS: i64 = 120;
+
+/// Kind of destructive operation recorded in the journal
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    Delete,
+    Rename,
+    CaptionOverwrite,
+    MediaEdit,
+    Import,
+    Restore,
+}
+
+/// State of a single file before an operation touched it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSnapshot {
+    pub path: String,
+    /// Copy of the previous contents, or None if the file did not exist before
+    pub backup: Option<String>,
+    /// Where the operation moved the file, when it was renamed rather than changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_to: Option<String>,
+}
+
+/// A recorded operation that can be undone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: String,
+    pub kind: OperationKind,
+    pub description: String,
+    pub timestamp: String,
+    pub files: Vec<FileSnapshot>,
+}
+
+/// An operation in progress
+///
+/// Snapshot every file before modifying it, then `commit` once the operation has
+/// succeeded. Dropping an uncommitted operation puts back the files it removed and
+/// discards its backups.
+pub struct Operation {
+    project: PathBuf,
+    backup_dir: PathBuf,
+    entry: JournalEntry,
+    /// Snapshots of files moved into the backup area, whose backup is the only copy,
+    /// or renamed
+    moved: Vec<usize>,
+    /// Set once the backups are in the journal, or must stay because files couldn't
+    /// be put back
+    keep_backups: bool,
+}
+
+impl Operation {
+    /// Start recording an operation on the project that owns `path`
+    pub fn begin(
+        path: &Path,
+        kind: OperationKind,
+        description: impl Into<String>,
+    ) -> Result<Self, String> {
+        settings::ensure_writable(path)?;
+        let project = project_root_for(path);
+        // Operations can start within the same millisecond, e.g. from a batch job and
+        // the command line, so the time alone doesn't tell their backups apart
+        let id = format!(
+            "{}-{:08x}",
+            Local::now().format("%Y%m%d%H%M%S%3f"),
+            fastrand::u32(..)
+        );
+        let backup_dir = metadata_dir(&project)?.join(UNDO_DIR).join(&id);
+
+        Ok(Self {
+            project,
+            backup_dir,
+            entry: JournalEntry {
+                id,
+                kind,
+                description: description.into(),
+                timestamp: Local::now().format(TIMESTAMP_FORMAT).to_string(),
+                files: Vec::new(),
+            },
+            moved: Vec::new(),
+            keep_backups: false,
+        })
+    }
+
+    /// Record the current contents of a file by copying it into the backup area
+    pub fn snapshot(&mut self, path: &Path) -> Result<(), String> {
+        self.record(path, path, false)
+    }
+
+    /// Record a file that is about to be deleted by moving it into the backup area
+    pub fn snapshot_and_remove(&mut self, path: &Path) -> Result<(), String> {
+        self.record(path, path, true)
+    }
+
+    /// Record `path` using an existing backup copy of its previous contents, moving
+    /// the backup into the undo area instead of copying the file again
+    pub fn adopt_backup(&mut self, path: &Path, backup: &Path) -> Result<(), String> {
+        self.record(path, backup, true)
+    }
+
+    fn record(&mut self, path: &Path, source: &Path, remove: bool) -> Result<(), String> {
+        let path_str = path.to_string_lossy().to_string();
+
+        // Only the first snapshot of a file reflects its state before the operation
+        if self.entry.files.iter().any(|f| f.path == path_str) {
+            return Ok(());
+        }
+
+        let moved_away = remove && source == path;
+        let backup = if source.is_file() {
+            if !self.backup_dir.exists() {
+                fs::create_dir_all(&self.backup_dir)
+                    .map_err(|e| format!("Failed to create undo directory: {}", e))?;
+            }
+
+            let file_name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "file".to_string());
+            let backup_path =
+                self.backup_dir
+                    .join(format!("{}_{}", self.entry.files.len(), file_name));
+
+            if remove {
+                // Moving is cheap within the project; fall back to copy + delete
+                if fs::rename(source, &backup_path).is_err() {
+                    fs::copy(source, &backup_path)
+                        .map_err(|e| format!("Failed to back up {}: {}", path.display(), e))?;
+                    fs::remove_file(source)
+                        .map_err(|e| format!("Failed to remove {}: {}", source.display(), e))?;
+                }
+            } else {
+                fs::copy(source, &backup_path)
+                    .map_err(|e| format!("Failed to back up {}: {}", path.display(), e))?;
+            }
+
+            Some(backup_path.to_string_lossy().to_string())
+        } else {
+            None
+        };
+
+        if moved_away && backup.is_some() {
+            self.moved.push(self.entry.files.len());
+        }
+        self.entry.files.push(FileSnapshot {
+            path: path_str,
+            backup,
+            renamed_to: None,
+        });
+
+        Ok(())
+    }
+
+    /// Rename a file, recording the move so undoing it moves the file back
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<(), String> {
+        if to.exists() {
+            return Err(format!("{} already exists", to.display()));
+        }
+        in_use::retry(|| fs::rename(from, to))
+            .map_err(|e| format!("Failed to rename {}: {}", from.display(), e))?;
+
+        self.moved.push(self.entry.files.len());
+        self.entry.files.push(FileSnapshot {
+            path: from.to_string_lossy().to_string(),
+            backup: None,
+            renamed_to: Some(to.to_string_lossy().to_string()),
+        });
+        Ok(())
+    }
+
+    /// Append the operation to the project's journal
+    pub fn commit(mut self) -> Result<JournalEntry, String> {
+        let mut journal = load_journal(&self.project)?;
+        journal.push(self.entry.clone());
+
+        // Drop the oldest operations (and their backups) beyond the limit
+        while journal.len() > MAX_OPERATIONS {
+            let oldest = journal.remove(0);
+            let _ = fs::remove_dir_all(undo_dir(&self.project)?.join(&oldest.id));
+        }
+
+        save_journal(&self.project, &journal)?;
+        self.keep_backups = true;
+
+        Ok(self.entry.clone())
+    }
+
+    /// Put every file recorded so far back the way it was and discard the operation,
+    /// for operations that must happen entirely or not at all
+    ///
+    /// When some file can't be put back, the backups are kept so nothing is lost.
+    pub fn rollback(mut self) -> Result<(), String> {
+        match restore(&self.entry.files) {
+            Ok(()) => {
+                self.moved.clear();
+                Ok(())
+            }
+            Err(e) => {
+                self.keep_backups = true;
+                Err(format!(
+                    "{}; the previous files are kept in {}",
+                    e,
+                    self.backup_dir.display()
+                ))
+            }
+        }
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        if self.keep_backups || !self.backup_dir.exists() {
+            return;
+        }
+
+        // Files moved into the backup area would be gone with it, and renamed files
+        // go back to their names too
+        let moved: Vec<FileSnapshot> = self
+            .moved
+            .iter()
+            .map(|&index| self.entry.files[index].clone())
+            .collect();
+        if let Err(e) = restore(&moved) {
+            warn!(
+                "Keeping the backups of an unfinished operation in {}: {}",
+                self.backup_dir.display(),
+                e
+            );
+            return;
+        }
+        let _ = fs::remove_dir_all(&self.backup_dir);
+    }
+}
+
+fn undo_dir(project: &Path) -> Result<PathBuf, String> {
+    Ok(metadata_dir(project)?.join(UNDO_DIR))
+}
+
+fn journal_path(project: &Path) -> Result<PathBuf, String> {
+    Ok(metadata_dir(project)?.join(JOURNAL_FILE))
+}
+
+/// Load the operation journal of a project (oldest first)
+pub fn load_journal(project: &Path) -> Result<Vec<JournalEntry>, String> {
+    let path = journal_path(project)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read operation journal: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse operation journal: {}", e))
+}
+
+fn save_journal(project: &Path, journal: &[JournalEntry]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(journal)
+        .map_err(|e| format!("Failed to serialize operation journal: {}", e))?;
+    atomic::write_atomic(&journal_path(project)?, content, false)
+        .map_err(|e| format!("Failed to write operation journal: {}", e))
+}
+
+/// Point a project's journal at its new location after the project was copied from
+/// `old_project`, since snapshots name files and backups by absolute path
+pub fn relocate(project: &Path, old_project: &Path) -> Result<(), String> {
+    let rebase = |path: &mut String| {
+        if let Ok(relative) = Path::new(path.as_str()).strip_prefix(old_project) {
+            *path = project.join(relative).to_string_lossy().to_string();
+        }
+    };
+
+    let mut journal = load_journal(project)?;
+    if journal.is_empty() {
+        return Ok(());
+    }
+    for snapshot in journal.iter_mut().flat_map(|entry| entry.files.iter_mut()) {
+        rebase(&mut snapshot.path);
+        if let Some(backup) = snapshot.backup.as_mut() {
+            rebase(backup);
+        }
+        if let Some(renamed_to) = snapshot.renamed_to.as_mut() {
+            rebase(renamed_to);
+        }
+    }
+    save_journal(project, &journal)
+}
+
+/// Check whether the latest journal entry already covers a recent save of this caption
+///
+/// Auto-save writes the caption on every pause in typing; only the state before the
+/// first save of an editing burst is worth restoring.
+pub fn recently_recorded_caption(path: &Path) -> bool {
+    let project = project_root_for(path);
+    let path_str = path.to_string_lossy();
+
+    let last = match load_journal(&project) {
+        Ok(mut journal) => journal.pop(),
+        Err(_) => None,
+    };
+
+    match last {
+        Some(entry) => {
+            let recent = NaiveDateTime::parse_from_str(&entry.timestamp, TIMESTAMP_FORMAT)
+                .map(|t| (Local::now().naive_local() - t).num_seconds() < COALESCE_WINDOW_SECS)
+                .unwrap_or(false);
+
+            recent
+                && entry.kind == OperationKind::CaptionOverwrite
+                && entry.files.len() == 1
+                && entry.files[0].path == path_str
+        }
+        None => false,
+    }
+}
+
+/// Revert the most recent operation of a project and remove it from the journal
+pub fn undo_last(project: &Path) -> Result<JournalEntry, String> {
+    settings::ensure_writable(project)?;
+    let mut journal = load_journal(project)?;
+    let entry = journal.pop().ok_or_else(|| "Nothing to undo".to_string())?;
+
+    restore(&entry.files)?;
+
+    let _ = fs::remove_dir_all(undo_dir(project)?.join(&entry.id));
+    save_journal(project, &journal)?;
+
+    Ok(entry)
+}
+
+/// Put files back into the state recorded in their snapshots
+fn restore(files: &[FileSnapshot]) -> Result<(), String> {
+    // Restore in reverse order so files touched twice end up in their original state
+    for snapshot in files.iter().rev() {
+        let path = Path::new(&snapshot.path);
+        if let Some(renamed_to) = &snapshot.renamed_to {
+            in_use::retry(|| fs::rename(renamed_to, path))
+                .map_err(|e| format!("Failed to rename {} back: {}", renamed_to, e))?;
+            continue;
+        }
+        match &snapshot.backup {
+            Some(backup) => {
+                if let Some(parent) = path.parent() {
+                    if !parent.exists() {
+                        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                    }
+                }
+                fs::copy(backup, path)
+                    .map_err(|e| format!("Failed to restore {}: {}", snapshot.path, e))?;
+            }
+            None => {
+                // The operation created this file, so undoing removes it
+                if path.exists() {
+                    fs::remove_file(path)
+                        .map_err(|e| format!("Failed to remove {}: {}", snapshot.path, e))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,13 +1,18 @@
-import { useState, useEffect, useCallback } from 'react';
+import { useState, useEffect, useCallback, useRef } from 'react';
 import { Toaster } from "@/components/ui/sonner";
 import { toast } from "sonner";
 import { useFileSystem } from "@/hooks/useFileSystem";
 import { useSettings } from "@/hooks/useSettings";
-import { MediaFile } from "@/lib/fs";
+import { MediaFile, onHotFolderFiles, setHotFolder } from "@/lib/fs";
+import { parseCaptionModified } from "@/lib/captions";
 import { 
+  generateAltTexts,
   generateCaptions, 
+  generateGeminiAltTexts,
   generateGeminiCaptions,
-  generateCaptionWithPreferredProvider
+  generateScriptCaptions,
+  generateCaptionWithPreferredProvider,
+  scriptArgs
 } from "@/lib/api";
 
 // Import components
@@ -16,6 +21,13 @@ import { FileSidebar } from "@/components/FileSidebar";
 import { MediaViewer } from "@/components/MediaViewer";
 import { CaptionEditor } from "@/components/CaptionEditor";
 import { EmptyState } from "@/components/EmptyState";
+import { EmbeddedCaptionsDialog } from "@/components/EmbeddedCaptionsDialog";
+
+/** Most hot folder files captioned in one request batch */
+const HOT_FOLDER_BATCH_SIZE = 10;
+
+/** Pause before each hot folder batch, so files arriving together share a batch */
+const HOT_FOLDER_BATCH_DELAY_MS = 2000;
 
 function App() {
   // Initialize hooks
@@ -33,7 +45,13 @@ function App() {
     updateFileSelection,
     exportWorkingDirectory,
     removeFile,
-    duplicateFile
+    removeFiles,
+    duplicateFile,
+    embeddedCaptions,
+    applyEmbeddedCaptions,
+    dismissEmbeddedCaptions,
+    addMediaFiles,
+    refreshMediaFiles
   } = useFileSystem();
   
   const {
@@ -47,6 +65,8 @@ function App() {
   const [isProcessing, setIsProcessing] = useState<boolean>(false);
   const [isSaving, setIsSaving] = useState<boolean>(false);
   const [captionModified, setCaptionModified] = useState<boolean>(false);
+  // Caption of the current file as last read from or written to disk
+  const diskCaption = useRef<string>('');
   const [loadingThumbnails, setLoadingThumbnails] = useState<boolean>(false);
 
   // Auto-save caption when modified (with debounce)
@@ -110,7 +130,9 @@ function App() {
     setIsSaving(true);
     
     try {
-      await writeCaption(currentFile, caption);
+      if (await writeCaption(currentFile, caption, diskCaption.current)) {
+        diskCaption.current = caption;
+      }
       
       // Only show toast for manual saves
       if (captionModified === false) {
@@ -119,9 +141,33 @@ function App() {
         });
       }
     } catch (err) {
-      toast("Error", {
-        description: `Failed to save caption: ${err}`
-      });
+      const modified = parseCaptionModified(err);
+      if (modified) {
+        // Keep the edits; the user decides which version wins
+        toast.error(modified.message, {
+          id: 'caption-modified',
+          duration: Infinity,
+          action: {
+            label: 'Reload',
+            onClick: () => {
+              diskCaption.current = modified.current;
+              setCaption(modified.current);
+              setCaptionModified(false);
+            },
+          },
+          cancel: {
+            label: 'Keep mine',
+            onClick: () => {
+              diskCaption.current = modified.current;
+              setCaptionModified(true);
+            },
+          },
+        });
+      } else {
+        toast("Error", {
+          description: `Failed to save caption: ${err}`
+        });
+      }
     } finally {
       setIsSaving(false);
     }
@@ -141,8 +187,10 @@ function App() {
     // Load caption if it exists
     if (file.has_caption) {
       const captionText = await readCaption(file);
+      diskCaption.current = captionText;
       setCaption(captionText);
     } else {
+      diskCaption.current = '';
       setCaption('');
     }
   };
@@ -163,22 +211,50 @@ function App() {
       return;
     }
     
+    captionFiles(selectedFiles);
+  };
+
+  // Generate and save captions for a batch of files
+  const captionFiles = (selectedFiles: MediaFile[]) => {
     setIsProcessing(true);
     
     // Get the paths of the selected files
     const mediaPaths = selectedFiles.map(file => file.path);
     
     // Determine which API to use based on settings
+    const useScript = settings.preferredProvider === 'script';
     const useGemini = settings.preferredProvider === 'gemini';
-    const modelName = useGemini ? settings.geminiModel : settings.model;
+    const modelName = useScript
+      ? settings.scriptCommand
+      : useGemini ? settings.geminiModel : settings.model;
     
-    // Call the appropriate API to generate captions
-    const captionPromise = useGemini
+    // Call the appropriate API to generate captions, or alt text in alt text mode
+    const altText = settings.captionMode === 'alt_text' && !useScript;
+    const captionPromise = altText
+      ? useGemini
+        ? generateGeminiAltTexts(settings.geminiApiKey, mediaPaths, settings.altTextMaxLength)
+        : generateAltTexts(
+            settings.apiUrl,
+            settings.apiKey,
+            mediaPaths,
+            settings.model,
+            settings.altTextMaxLength
+          )
+      : useScript
+      ? generateScriptCaptions(
+          settings.scriptCommand,
+          scriptArgs(settings),
+          settings.captionPrompt,
+          mediaPaths
+        )
+      : useGemini
       ? generateGeminiCaptions(
           settings.geminiApiKey,
           settings.captionPrompt,
           mediaPaths,
-          settings.geminiSystemInstruction
+          settings.geminiSystemInstruction,
+          undefined,
+          settings.includeVideoAudio
         )
       : generateCaptions(
           settings.apiUrl,
@@ -187,7 +263,8 @@ function App() {
           mediaPaths,
           settings.model,
           settings.imageDetail,
-          settings.useDetailParameter
+          settings.useDetailParameter,
+          settings.includeVideoAudio
         );
     
     toast.promise(
@@ -197,7 +274,7 @@ function App() {
         success: (results) => {
           // Update the captions for each file
           results.forEach(([path, caption]) => {
-            const file = mediaFiles.find(f => f.path === path);
+            const file = selectedFiles.find(f => f.path === path);
             if (file) {
               writeCaption(file, caption)
                 .catch(err => console.error(`Failed to save caption for ${file.name}:`, err));
@@ -224,6 +301,67 @@ function App() {
     );
   };
 
+  // Caption files dropped into the project folder while automatic captioning is on
+  const captionFilesRef = useRef(captionFiles);
+  captionFilesRef.current = captionFiles;
+  const isProcessingRef = useRef(isProcessing);
+  isProcessingRef.current = isProcessing;
+
+  // New files wait here and are captioned one batch at a time, never while another
+  // batch is running, so a large drop doesn't send every file to the provider at once
+  const hotFolderQueue = useRef<MediaFile[]>([]);
+  const hotFolderTimer = useRef<ReturnType<typeof setTimeout>>();
+
+  /**
+   * Caption the next batch of queued hot folder files after a pause, unless a batch
+   * is running; the queue is drained again once it finishes
+   */
+  const drainHotFolder = useCallback(() => {
+    if (hotFolderTimer.current || hotFolderQueue.current.length === 0) return;
+    if (isProcessingRef.current) return;
+
+    hotFolderTimer.current = setTimeout(() => {
+      hotFolderTimer.current = undefined;
+      if (isProcessingRef.current) return;
+      const batch = hotFolderQueue.current.splice(0, HOT_FOLDER_BATCH_SIZE);
+      if (batch.length > 0) {
+        captionFilesRef.current(batch);
+      }
+    }, HOT_FOLDER_BATCH_DELAY_MS);
+  }, []);
+
+  useEffect(() => {
+    if (!isProcessing) drainHotFolder();
+  }, [isProcessing, drainHotFolder]);
+  
+  useEffect(() => {
+    if (!workingDirectory || !settings.autoCaptionNewFiles) return;
+    
+    setHotFolder(workingDirectory, true)
+      .catch(err => toast.error(`Failed to watch the project folder: ${err}`));
+    return () => {
+      setHotFolder(workingDirectory, false)
+        .catch(err => console.error('Failed to stop watching the project folder:', err));
+      // Files still queued are left uncaptioned, like any other file
+      hotFolderQueue.current = [];
+      clearTimeout(hotFolderTimer.current);
+      hotFolderTimer.current = undefined;
+    };
+  }, [workingDirectory, settings.autoCaptionNewFiles]);
+  
+  useEffect(() => {
+    const unlisten = onHotFolderFiles(({ project, files }) => {
+      if (project !== workingDirectory) return;
+      addMediaFiles(files);
+      const queued = new Set(hotFolderQueue.current.map(file => file.path));
+      hotFolderQueue.current.push(...files.filter(file => !queued.has(file.path)));
+      drainHotFolder();
+    });
+    return () => {
+      unlisten.then(stop => stop());
+    };
+  }, [workingDirectory, addMediaFiles, drainHotFolder]);
+
   // Handle navigation
   const handleNavigate = async (direction: 'prev' | 'next') => {
     if (!currentFile || mediaFiles.length === 0) return;
@@ -249,8 +387,10 @@ function App() {
     // Load caption if it exists
     if (newFile.has_caption) {
       const captionText = await readCaption(newFile);
+      diskCaption.current = captionText;
       setCaption(captionText);
     } else {
+      diskCaption.current = '';
       setCaption('');
     }
   };
@@ -341,6 +481,36 @@ function App() {
     }
   }, [currentFile, mediaFiles, removeFile, handleFileSelect]);
 
+  // Handle removal of the selected files, after confirming
+  const handleRemoveSelectedFiles = useCallback(() => {
+    const selectedFiles = mediaFiles.filter(f => f.selected);
+    if (selectedFiles.length === 0) return;
+    
+    const confirmed = confirm(
+      `Delete ${selectedFiles.length} selected file${selectedFiles.length === 1 ? '' : 's'} and their captions? You can undo this from the operation history.`
+    );
+    if (!confirmed) return;
+    
+    toast.promise(
+      (async () => {
+        const summary = await removeFiles(selectedFiles);
+        
+        // If the current file was deleted, clear it
+        if (currentFile && selectedFiles.some(f => f.id === currentFile.id)) {
+          setCurrentFile(null);
+          setCaption('');
+        }
+        
+        return summary;
+      })(),
+      {
+        loading: `Deleting ${selectedFiles.length} files...`,
+        success: (summary) => `Deleted ${summary.deleted} files and ${summary.captions} captions`,
+        error: (error) => `Failed to delete files: ${error}`
+      }
+    );
+  }, [currentFile, mediaFiles, removeFiles]);
+
   // Generate caption for current file
   const handleGenerateCurrentCaption = (currentVideoTime?: number) => {
     if (!currentFile) return;
@@ -352,7 +522,8 @@ function App() {
       const isVideo = currentFile.type === 'video' || currentFile.file_type === 'video';
       
       // If it's a video and we're using OpenAI, extract the current frame
-      if (isVideo && (!settings.useGeminiForVideos || settings.preferredProvider === 'openai')) {
+      if (isVideo && settings.preferredProvider !== 'script' &&
+          (!settings.useGeminiForVideos || settings.preferredProvider === 'openai')) {
         try {
           // If a specific time was provided, use that, otherwise use the default first frame
           const time = typeof currentVideoTime === 'number' ? currentVideoTime : undefined;
@@ -365,17 +536,21 @@ function App() {
         }
       }
       
-      // Call the API to generate a caption using the preferred provider
+      // Call the API to generate a caption using the preferred provider, showing
+      // the caption in the editor as it streams in
       return generateCaptionWithPreferredProvider(
         currentFile.path,
         settings,
         isVideo,
-        videoFrameUrl
+        videoFrameUrl,
+        setCaption
       );
     };
     
     // Determine which model to show in the loading message
-    const modelName = settings.preferredProvider === 'gemini' || 
+    const modelName = settings.preferredProvider === 'script'
+                     ? settings.scriptCommand
+                     : settings.preferredProvider === 'gemini' || 
                      (settings.useGeminiForVideos && 
                       (currentFile.type === 'video' || currentFile.file_type === 'video'))
                      ? settings.geminiModel
@@ -451,7 +626,9 @@ function App() {
           handleFileSelect={handleFileSelect}
           updateFileSelection={updateFileSelection}
           handleGenerateCaptions={handleGenerateCaptions}
+          handleRemoveSelectedFiles={handleRemoveSelectedFiles}
           duplicateFile={duplicateFile}
+          refreshMediaFiles={refreshMediaFiles}
         />
         
         {/* Main content area */}
@@ -486,6 +663,12 @@ function App() {
         </div>
       </div>
       
+      <EmbeddedCaptionsDialog
+        embeddedCaptions={embeddedCaptions}
+        applyEmbeddedCaptions={applyEmbeddedCaptions}
+        dismissEmbeddedCaptions={dismissEmbeddedCaptions}
+      />
+      
       <Toaster />
     </div>
   );
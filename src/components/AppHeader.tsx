@@ -7,7 +7,7 @@ interface AppHeaderProps {
   settings: AppSettings;
   updateSingleSetting: <K extends keyof AppSettings>(key: K, value: AppSettings[K]) => Promise<boolean>;
   workingDirectory: string | null;
-  exportWorkingDirectory: (asZip: boolean) => Promise<string | null>;
+  exportWorkingDirectory: (asZip: boolean, embedCaptions?: boolean) => Promise<string | null>;
 }
 
 export function AppHeader({
@@ -0,0 +1,89 @@
+import { useState } from 'react';
+import { Button } from "@/components/ui/button";
+import { Checkbox } from "@/components/ui/checkbox";
+import { Dialog, DialogContent, DialogDescription, DialogFooter, DialogHeader, DialogTitle } from "@/components/ui/dialog";
+import { Label } from "@/components/ui/label";
+import { toast } from "sonner";
+import { BatchEditSummary } from "@/lib/captions";
+
+interface EmbeddedCaptionsDialogProps {
+  embeddedCaptions: BatchEditSummary | null;
+  applyEmbeddedCaptions: (includeKeywords: boolean) => Promise<number>;
+  dismissEmbeddedCaptions: () => void;
+}
+
+/**
+ * Offers to seed captions from the descriptions embedded in freshly imported images
+ */
+export function EmbeddedCaptionsDialog({
+  embeddedCaptions,
+  applyEmbeddedCaptions,
+  dismissEmbeddedCaptions
+}: EmbeddedCaptionsDialogProps) {
+  const [includeKeywords, setIncludeKeywords] = useState<boolean>(true);
+  const [isApplying, setIsApplying] = useState<boolean>(false);
+
+  const handleApply = async () => {
+    try {
+      setIsApplying(true);
+      await applyEmbeddedCaptions(includeKeywords);
+    } catch (error) {
+      const errorMessage = error instanceof Error ? error.message : String(error);
+      toast.error("Failed to use embedded captions", {
+        description: errorMessage
+      });
+    } finally {
+      setIsApplying(false);
+    }
+  };
+
+  const count = embeddedCaptions?.changes.length ?? 0;
+  const examples = embeddedCaptions?.changes.slice(0, 3) ?? [];
+
+  return (
+    <Dialog
+      open={embeddedCaptions !== null}
+      onOpenChange={(open) => !open && !isApplying && dismissEmbeddedCaptions()}
+    >
+      <DialogContent className="sm:max-w-[500px]">
+        <DialogHeader>
+          <DialogTitle>Embedded Captions Found</DialogTitle>
+          <DialogDescription>
+            {count} {count === 1 ? "image has" : "images have"} a description or keywords in
+            its metadata (XMP, IPTC or EXIF) but no caption yet. Use them as starting captions?
+          </DialogDescription>
+        </DialogHeader>
+        <div className="py-2 space-y-4">
+          <ul className="space-y-2 text-sm">
+            {examples.map((change) => (
+              <li key={change.path} className="truncate">
+                <span className="font-medium">{change.name}</span>
+                <span className="text-muted-foreground"> — {change.after}</span>
+              </li>
+            ))}
+          </ul>
+          <div className="flex items-center space-x-2">
+            <Checkbox
+              id="embedded-include-keywords"
+              checked={includeKeywords}
+              onCheckedChange={(checked) => setIncludeKeywords(!!checked)}
+            />
+            <Label htmlFor="embedded-include-keywords">Add keywords as tags</Label>
+          </div>
+        </div>
+        <DialogFooter>
+          <Button
+            variant="outline"
+            onClick={dismissEmbeddedCaptions}
+            disabled={isApplying}
+          >
+            Skip
+          </Button>
+          <Button onClick={handleApply} disabled={isApplying}>
+            {isApplying ? "Writing captions..." : `Use ${count} captions`}
+          </Button>
+        </DialogFooter>
+      </DialogContent>
+    </Dialog>
+  );
+}
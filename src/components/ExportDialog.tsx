@@ -7,12 +7,13 @@ import { toast } from "sonner";
 
 interface ExportDialogProps {
   workingDirectory: string | null;
-  exportWorkingDirectory: (asZip: boolean) => Promise<string | null>;
+  exportWorkingDirectory: (asZip: boolean, embedCaptions?: boolean) => Promise<string | null>;
 }
 
 export function ExportDialog({ workingDirectory, exportWorkingDirectory }: ExportDialogProps) {
   const [exportDialogOpen, setExportDialogOpen] = useState<boolean>(false);
   const [exportAsZip, setExportAsZip] = useState<boolean>(true);
+  const [embedCaptions, setEmbedCaptions] = useState<boolean>(false);
   const [isExporting, setIsExporting] = useState<boolean>(false);
 
   const handleExport = async () => {
@@ -25,7 +26,7 @@ export function ExportDialog({ workingDirectory, exportWorkingDirectory }: Expor
     
     try {
       setIsExporting(true);
-      const exportPath = await exportWorkingDirectory(exportAsZip);
+      const exportPath = await exportWorkingDirectory(exportAsZip, embedCaptions);
       setExportDialogOpen(false);
       
       if (exportPath) {
@@ -71,6 +72,17 @@ export function ExportDialog({ workingDirectory, exportWorkingDirectory }: Expor
               ? "All files will be compressed into a single ZIP file" 
               : "Files will be exported to a folder"}
           </p>
+          <div className="flex items-center space-x-2">
+            <Checkbox 
+              id="export-embed-captions" 
+              checked={embedCaptions} 
+              onCheckedChange={(checked) => setEmbedCaptions(!!checked)}
+            />
+            <Label htmlFor="export-embed-captions">Embed captions in image metadata</Label>
+          </div>
+          <p className="text-sm text-muted-foreground">
+            Writes each caption into the image's XMP description (and IPTC caption for JPEG) so it survives without the caption files
+          </p>
         </div>
         <DialogFooter>
           <Button 
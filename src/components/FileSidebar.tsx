@@ -9,8 +9,9 @@ import {
   ContextMenuItem,
   ContextMenuTrigger,
 } from "@/components/ui/context-menu";
-import { FolderOpen, FileText, RefreshCw, ImageIcon, Video, Wand2, Clock, ArrowRight, Copy, CheckSquare, Square } from "lucide-react";
-import { MediaFile, ProjectDirectory } from "@/lib/fs";
+import { FolderOpen, FileText, RefreshCw, ImageIcon, Video, Wand2, Clock, ArrowRight, Copy, CheckSquare, Square, Trash2, ExternalLink, FolderSearch, Film } from "lucide-react";
+import { MediaFile, ProjectDirectory, openWithDefaultApp, revealMediaFile } from "@/lib/fs";
+import { convertAnimationToMp4 } from "@/lib/media";
 import { toast } from "sonner";
 import { useProjectManagement } from "@/hooks/useProjectManagement";
 
@@ -34,7 +35,9 @@ interface FileSidebarProps {
   handleFileSelect: (file: MediaFile) => void;
   updateFileSelection: (fileId: string, selected: boolean) => void;
   handleGenerateCaptions: () => void;
+  handleRemoveSelectedFiles?: () => void;
   duplicateFile?: (file: MediaFile) => Promise<MediaFile | null>;
+  refreshMediaFiles?: () => Promise<number>;
 }
 
 export function FileSidebar({
@@ -49,7 +52,9 @@ export function FileSidebar({
   handleFileSelect,
   updateFileSelection,
   handleGenerateCaptions,
-  duplicateFile
+  handleRemoveSelectedFiles,
+  duplicateFile,
+  refreshMediaFiles
 }: FileSidebarProps) {
   const [fileFilter, setFileFilter] = useState<'all' | 'captioned' | 'uncaptioned'>('all');
 
@@ -186,6 +191,16 @@ export function FileSidebar({
                             toast.error(`Error duplicating file: ${err}`);
                           }
                         };
+
+                        // Animations become a video next to them, picked up by the refresh
+                        const handleConvertToMp4 = async () => {
+                          try {
+                            await convertAnimationToMp4(file.path);
+                            await refreshMediaFiles?.();
+                          } catch (err) {
+                            toast.error(`Error converting to MP4: ${err}`);
+                          }
+                        };
                         
                         return (
                           <ContextMenu key={file.id}>
@@ -209,8 +224,9 @@ export function FileSidebar({
                                 <div className="h-12 w-12 rounded overflow-hidden flex-shrink-0 bg-white flex items-center justify-center">
                                   {file.thumbnail ? (
                                     <img 
-                                      src={`${file.thumbnail}${file.refreshToken ? `?t=${file.refreshToken}` : ''}`}
+                                      src={`${file.thumbnail}${file.refreshToken ? `&t=${file.refreshToken}` : ''}`}
                                       alt={file.name} 
+                                      loading="lazy"
                                       className="h-full w-full object-cover"
                                       key={`thumb-${file.id}-${file.refreshToken || 'default'}`}
                                       onError={(e) => {
@@ -246,6 +262,11 @@ export function FileSidebar({
                                       Has caption
                                     </p>
                                   )}
+                                  {file.frame_count != null && (
+                                    <p className="text-xs text-muted-foreground truncate">
+                                      {file.frame_count} frames{file.duration != null && ` · ${file.duration.toFixed(1)}s`}
+                                    </p>
+                                  )}
                                 </div>
                               </div>
                             </ContextMenuTrigger>
@@ -258,6 +279,29 @@ export function FileSidebar({
                                 <Copy className="h-4 w-4" />
                                 <span>Duplicate</span>
                               </ContextMenuItem>
+                              {file.frame_count != null && (
+                                <ContextMenuItem 
+                                  onClick={handleConvertToMp4}
+                                  className="flex items-center gap-2"
+                                >
+                                  <Film className="h-4 w-4" />
+                                  <span>Convert to MP4</span>
+                                </ContextMenuItem>
+                              )}
+                              <ContextMenuItem 
+                                onClick={() => openWithDefaultApp(file.path).catch(err => toast.error(`Error opening file: ${err}`))}
+                                className="flex items-center gap-2"
+                              >
+                                <ExternalLink className="h-4 w-4" />
+                                <span>Open with Default App</span>
+                              </ContextMenuItem>
+                              <ContextMenuItem 
+                                onClick={() => revealMediaFile(file.path).catch(err => toast.error(`Error revealing file: ${err}`))}
+                                className="flex items-center gap-2"
+                              >
+                                <FolderSearch className="h-4 w-4" />
+                                <span>Show in Folder</span>
+                              </ContextMenuItem>
                             </ContextMenuContent>
                           </ContextMenu>
                         );
@@ -295,6 +339,17 @@ export function FileSidebar({
                       <CheckSquare className="h-4 w-4" />
                     )}
                   </Button>
+                  {handleRemoveSelectedFiles && (
+                    <Button
+                      variant="outline"
+                      size="icon"
+                      onClick={handleRemoveSelectedFiles}
+                      disabled={isProcessing || mediaFiles.filter(f => f.selected).length === 0}
+                      title="Delete Selected"
+                    >
+                      <Trash2 className="h-4 w-4" />
+                    </Button>
+                  )}
                 </div>
               </div>
             </div>
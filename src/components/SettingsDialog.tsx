@@ -14,10 +14,10 @@ import {
   ContextMenuItem,
   ContextMenuTrigger,
 } from "@/components/ui/context-menu";
-import { Settings as SettingsIcon, Info, Trash2, RefreshCw, FolderOpen, Sliders, Keyboard, FolderCog } from "lucide-react";
+import { Settings as SettingsIcon, Info, Trash2, RefreshCw, FolderOpen, Sliders, Keyboard, FolderCog, Terminal } from "lucide-react";
 import { toast } from "sonner";
 import { AppSettings } from "@/lib/settings";
-import { ImageDetailLevel } from "@/lib/settings";
+import { ApiProvider, CaptionMode, ImageDetailLevel } from "@/lib/settings";
 import { useProjectManagement } from "@/hooks/useProjectManagement";
 import { useState } from "react";
 import {
@@ -102,6 +102,14 @@ export function SettingsDialog({ settings, updateSingleSetting }: SettingsDialog
                 </svg>
                 Gemini
               </Button>
+              <Button
+                variant={activeSection === "script" ? "default" : "ghost"} 
+                className="w-full justify-start mb-1"
+                onClick={() => setActiveSection("script")}
+              >
+                <Terminal className="h-4 w-4 mr-2" />
+                Script
+              </Button>
               <Button
                 variant={activeSection === "shortcuts" ? "default" : "ghost"} 
                 className="w-full justify-start mb-1"
@@ -145,6 +153,38 @@ export function SettingsDialog({ settings, updateSingleSetting }: SettingsDialog
                   />
                 </div>
                 
+                <div className="grid gap-2">
+                  <Label htmlFor="captionMode">Caption Mode</Label>
+                  <Select 
+                    value={settings.captionMode} 
+                    onValueChange={(value: string) => updateSingleSetting('captionMode', value as CaptionMode)}
+                  >
+                    <SelectTrigger>
+                      <SelectValue placeholder="Select mode" />
+                    </SelectTrigger>
+                    <SelectContent>
+                      <SelectItem value="caption">Training captions</SelectItem>
+                      <SelectItem value="alt_text">Web alt text</SelectItem>
+                    </SelectContent>
+                  </Select>
+                  <p className="text-xs text-muted-foreground">
+                    Alt text is concise and factual and ignores the caption prompt (OpenAI and Gemini only)
+                  </p>
+                </div>
+                
+                {settings.captionMode === 'alt_text' && (
+                  <div className="grid gap-2">
+                    <Label htmlFor="altTextMaxLength">Alt Text Length Cap (characters)</Label>
+                    <Input 
+                      id="altTextMaxLength" 
+                      type="number" 
+                      min={20}
+                      value={settings.altTextMaxLength} 
+                      onChange={(e) => updateSingleSetting('altTextMaxLength', Number(e.target.value) || 125)}
+                    />
+                  </div>
+                )}
+                
                 <div className="grid gap-4">
                   <h3 className="text-sm font-medium">API Provider Settings</h3>
                   
@@ -152,7 +192,7 @@ export function SettingsDialog({ settings, updateSingleSetting }: SettingsDialog
                     <Label htmlFor="preferredProvider">Preferred API Provider</Label>
                     <Select 
                       value={settings.preferredProvider} 
-                      onValueChange={(value: string) => updateSingleSetting('preferredProvider', value as 'openai' | 'gemini')}
+                      onValueChange={(value: string) => updateSingleSetting('preferredProvider', value as ApiProvider)}
                     >
                       <SelectTrigger>
                         <SelectValue placeholder="Select provider" />
@@ -160,6 +200,7 @@ export function SettingsDialog({ settings, updateSingleSetting }: SettingsDialog
                       <SelectContent>
                         <SelectItem value="openai">OpenAI</SelectItem>
                         <SelectItem value="gemini">Google Gemini</SelectItem>
+                        <SelectItem value="script">Caption script</SelectItem>
                       </SelectContent>
                     </Select>
                   </div>
@@ -179,6 +220,63 @@ export function SettingsDialog({ settings, updateSingleSetting }: SettingsDialog
                       Use Gemini for videos
                     </Label>
                   </div>
+                  
+                  <div className="grid gap-1">
+                    <div className="flex items-center space-x-2">
+                      <Checkbox 
+                        id="includeVideoAudio" 
+                        checked={settings.includeVideoAudio}
+                        onCheckedChange={(checked) => 
+                          updateSingleSetting('includeVideoAudio', checked === true)
+                        }
+                      />
+                      <Label 
+                        htmlFor="includeVideoAudio" 
+                        className="text-sm font-normal"
+                      >
+                        Include the sound of videos
+                      </Label>
+                    </div>
+                    <p className="text-xs text-muted-foreground">
+                      Gemini listens to the video; OpenAI gets several frames and a Whisper transcript
+                    </p>
+                  </div>
+                  
+                  <div className="grid gap-1">
+                    <div className="flex items-center space-x-2">
+                      <Checkbox 
+                        id="autoCaptionNewFiles" 
+                        checked={settings.autoCaptionNewFiles}
+                        onCheckedChange={(checked) => 
+                          updateSingleSetting('autoCaptionNewFiles', checked === true)
+                        }
+                      />
+                      <Label 
+                        htmlFor="autoCaptionNewFiles" 
+                        className="text-sm font-normal"
+                      >
+                        Caption new files automatically
+                      </Label>
+                    </div>
+                    <p className="text-xs text-muted-foreground">
+                      Files dropped into the open project folder are captioned once they finish copying
+                    </p>
+                  </div>
+                  
+                  <div className="grid gap-2">
+                    <Label htmlFor="maxVideoResolution">Downscale imported videos above (pixels)</Label>
+                    <Input 
+                      id="maxVideoResolution" 
+                      type="number" 
+                      min={0}
+                      placeholder="e.g. 1080"
+                      value={settings.maxVideoResolution || ''} 
+                      onChange={(e) => updateSingleSetting('maxVideoResolution', Math.max(0, Number(e.target.value) || 0))}
+                    />
+                    <p className="text-xs text-muted-foreground">
+                      Videos whose shorter side is larger are re-encoded once at import; leave empty to keep them as they are
+                    </p>
+                  </div>
                 </div>
               </div>
             )}
@@ -319,6 +417,48 @@ export function SettingsDialog({ settings, updateSingleSetting }: SettingsDialog
               </div>
             )}
             
+            {/* Caption Script Settings */}
+            {activeSection === "script" && (
+              <div className="grid gap-4 pb-4">
+                <div className="grid gap-2">
+                  <Label htmlFor="scriptCommand">Command</Label>
+                  <Input 
+                    id="scriptCommand" 
+                    value={settings.scriptCommand} 
+                    placeholder="python3"
+                    onChange={(e) => updateSingleSetting('scriptCommand', e.target.value)}
+                  />
+                  <p className="text-xs text-muted-foreground">
+                    Executable run once for every file, e.g. python3 or the path of your own program
+                  </p>
+                </div>
+                
+                <div className="grid gap-2">
+                  <Label htmlFor="scriptArgs">Arguments</Label>
+                  <Input 
+                    id="scriptArgs" 
+                    value={settings.scriptArgs} 
+                    placeholder="/path/to/caption.py"
+                    onChange={(e) => updateSingleSetting('scriptArgs', e.target.value)}
+                  />
+                  <p className="text-xs text-muted-foreground">
+                    Separated by spaces
+                  </p>
+                </div>
+                
+                <div className="grid gap-2">
+                  <h3 className="text-sm font-medium">Protocol</h3>
+                  <p className="text-xs text-muted-foreground">
+                    The script gets a JSON request on stdin and answers with a JSON object on the
+                    last line of stdout. Anything printed before that line is ignored; write logs to
+                    stderr, which is shown when the script fails.
+                  </p>
+                  <pre className="text-xs bg-muted rounded-md p-3 overflow-x-auto">{`stdin:  {"media_path": "/path/to/image.png", "media_type": "image", "prompt": "..."}
+stdout: {"caption": "..."}  or  {"error": "..."}`}</pre>
+                </div>
+              </div>
+            )}
+            
             {/* Keyboard Shortcuts */}
             {activeSection === "shortcuts" && (
               <div className="space-y-6 pb-4">
@@ -8,6 +8,7 @@ import 'react-advanced-cropper/dist/themes/corners.css';
 import { RotateCcw, FlipHorizontal, FlipVertical, Check, X, Lock, Unlock } from 'lucide-react';
 import { toast } from 'sonner';
 import { getMediaThumbnail, saveCroppedImage, cropVideo } from '../../lib/media';
+import { parseFileInUse } from '../../lib/fs';
 
 interface CropEditorProps {
   src: string;
@@ -120,6 +121,16 @@ const CropEditor: React.FC<CropEditorProps> = ({
         setOpen(false);
       }
     } catch (error) {
+      const inUse = parseFileInUse(error);
+      if (inUse) {
+        toast.error(`Failed to crop ${fileType}`, {
+          description: inUse.message,
+          action: { label: 'Retry', onClick: () => handleSave() },
+          duration: 10000,
+        });
+        return;
+      }
+      
       // Display a more user-friendly error message
       toast.error(`Failed to crop ${fileType}`, {
         description: "An error occurred during the crop operation. Check the application logs for details.",
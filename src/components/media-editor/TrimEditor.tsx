@@ -4,6 +4,7 @@ import { Button } from "../ui/button";
 import { Scissors, Check, X, GripHorizontal } from "lucide-react";
 import { invoke } from '@tauri-apps/api/core';
 import { toast } from 'sonner';
+import { parseFileInUse } from '../../lib/fs';
 
 interface TrimEditorProps {
   src: string;
@@ -216,6 +217,18 @@ export function TrimEditor({ src, filePath, onSave, disabled = false }: TrimEdit
       setOpen(false);
       
     } catch (error) {
+      const inUse = parseFileInUse(error);
+      if (inUse) {
+        toast.error("Failed to trim video", {
+          description: inUse.message,
+          action: { label: 'Retry', onClick: () => handleTrim() },
+          duration: 10000,
+        });
+        setProgressPercent(0);
+        setIsProcessing(false);
+        return;
+      }
+      
       // Get the error message from the error object
       const errorMessage = error instanceof Error 
         ? error.message 
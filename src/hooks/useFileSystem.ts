@@ -1,22 +1,31 @@
 import { useState, useCallback } from 'react';
-import { join } from '@tauri-apps/api/path';
-import { appDataDir } from '@tauri-apps/api/path';
+import { dirname, join } from '@tauri-apps/api/path';
+import { toast } from 'sonner';
 import { 
   selectDirectory, 
   duplicateDirectory, 
   registerWorkingDirectory,
   listDirectoryFiles,
-  readCaptionFile,
-  writeCaptionFile,
+  refreshDirectory,
   MediaFile,
   getAssetUrl,
-  getCaptionPath,
   selectExportDirectory,
   exportDirectory,
   deleteMediaFile,
-  duplicateMediaFile
+  deleteMediaFiles,
+  DeleteSummary,
+  duplicateMediaFile,
+  getWorkingRoot
 } from '../lib/fs';
-import { getMediaThumbnail } from '../lib/media';
+import { getThumbnailUrl, convertPdfDocuments, downscaleVideos } from '../lib/media';
+import { loadSettings } from '../lib/settings';
+import {
+  readMediaCaption,
+  writeMediaCaption,
+  parseCaptionModified,
+  seedCaptionsFromMetadata,
+  BatchEditSummary
+} from '../lib/captions';
 
 export interface UseFileSystemProps {
   workingDirName?: string;
@@ -28,6 +37,8 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
   const [mediaFiles, setMediaFiles] = useState<MediaFile[]>([]);
   const [isLoading, setIsLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  // Captions that could be seeded from the metadata embedded in freshly imported images
+  const [embeddedCaptions, setEmbeddedCaptions] = useState<BatchEditSummary | null>(null);
 
   /**
    * Select a source directory and create a working copy
@@ -42,8 +53,9 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
       setSourceDirectory(selectedDir);
       
       // Create the working directory path
-      const appData = await appDataDir();
-      const workingDir = await join(appData, workingDirName);
+      // The working directory can be moved off the app data directory
+      const workingRoot = await getWorkingRoot();
+      const workingDir = await join(await dirname(workingRoot.path), workingDirName);
       
       // Create a duplicate of the source directory
       const duplicatedDir = await duplicateDirectory(selectedDir, workingDir);
@@ -52,6 +64,19 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
       // Register the working directory as an asset scope
       await registerWorkingDirectory(duplicatedDir);
       
+      // Turn the pages of PDF documents into images; the import goes on without them
+      await convertPdfDocuments(duplicatedDir).catch(err =>
+        toast.error(`Failed to convert PDF documents: ${err}`)
+      );
+      
+      // Re-encode oversized videos once, here, rather than in every training run
+      const { maxVideoResolution } = await loadSettings();
+      if (maxVideoResolution > 0) {
+        await downscaleVideos(duplicatedDir, maxVideoResolution).catch(err =>
+          toast.error(`Failed to downscale videos: ${err}`)
+        );
+      }
+      
       // Load the media files first without thumbnails
       const files = await listDirectoryFiles(duplicatedDir);
       
@@ -64,6 +89,11 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
       // Start thumbnail generation in the background
       generateThumbnails(sortedFiles);
       
+      // Look for descriptions already embedded in the images, to offer seeding captions from them
+      seedCaptionsFromMetadata(duplicatedDir, { dryRun: true })
+        .then(summary => setEmbeddedCaptions(summary.changes.length > 0 ? summary : null))
+        .catch(err => console.error('Failed to read embedded captions:', err));
+      
       return { sourceDirectory: selectedDir, workingDirectory: duplicatedDir, files };
     } catch (err) {
       const errorMessage = err instanceof Error ? err.message : String(err);
@@ -75,43 +105,27 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
   }, [workingDirName]);
 
   /**
-   * Generate thumbnails for both image and video files in the background
+   * Point image and video files at their thumbnails, which the webview loads from the
+   * thumbnail protocol as they come into view
    */
   const generateThumbnails = useCallback(async (files: MediaFile[]) => {
     // Process both image and video files
-    const mediaFiles = files.filter(file => 
-      (file.type === 'video' || file.file_type === 'video') ||
-      (file.type === 'image' || file.file_type === 'image')
+    const mediaIds = new Set(
+      files
+        .filter(file => 
+          (file.type === 'video' || file.file_type === 'video') ||
+          (file.type === 'image' || file.file_type === 'image')
+        )
+        .map(file => file.id)
     );
     
-    if (mediaFiles.length === 0) return;
+    if (mediaIds.size === 0) return;
     
-    // Process thumbnails in smaller batches
-    const batchSize = 5;
-    const total = mediaFiles.length;
-    
-    for (let i = 0; i < total; i += batchSize) {
-      const batch = mediaFiles.slice(i, i + batchSize);
-      
-      // Process each batch concurrently
-      await Promise.all(
-        batch.map(async (file) => {
-          try {
-            // Generate thumbnail for both images and videos
-            const thumbnail = await getMediaThumbnail(file.path, 100);
-            
-            // Update just this file in the state
-            setMediaFiles(prevFiles => 
-              prevFiles.map(f => 
-                f.id === file.id ? { ...f, thumbnail } : f
-              )
-            );
-          } catch (err) {
-            // Silent fail - no logging needed
-          }
-        })
-      );
-    }
+    setMediaFiles(prevFiles => 
+      prevFiles.map(f => 
+        mediaIds.has(f.id) ? { ...f, thumbnail: getThumbnailUrl(f.path, 100) } : f
+      )
+    );
   }, []);
 
   /**
@@ -153,8 +167,7 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
    */
   const readCaption = useCallback(async (mediaFile: MediaFile) => {
     try {
-      const captionPath = getCaptionPath(mediaFile.path);
-      return await readCaptionFile(captionPath);
+      return await readMediaCaption(mediaFile.path);
     } catch (err) {
       // If the file doesn't exist, return an empty string
       return '';
@@ -163,11 +176,13 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
 
   /**
    * Write a caption file for a media file
+   *
+   * With `expected`, the caption as it was last read, a caption changed outside the app
+   * since is kept and the `caption_modified` error is thrown for the caller to resolve.
    */
-  const writeCaption = useCallback(async (mediaFile: MediaFile, content: string) => {
+  const writeCaption = useCallback(async (mediaFile: MediaFile, content: string, expected?: string) => {
     try {
-      const captionPath = getCaptionPath(mediaFile.path);
-      await writeCaptionFile(captionPath, content);
+      await writeMediaCaption(mediaFile.path, content, false, undefined, expected);
       
       // Update the media file in the state
       setMediaFiles(prev => 
@@ -176,6 +191,7 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
       
       return true;
     } catch (err) {
+      if (parseCaptionModified(err)) throw err;
       const errorMessage = err instanceof Error ? err.message : String(err);
       setError(errorMessage);
       return false;
@@ -196,11 +212,10 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
    */
   const getThumbnail = useCallback(async (mediaFile: MediaFile, maxSize: number = 100) => {
     try {
-      // Add a cache-busting timestamp to the path to ensure we get a fresh thumbnail
+      // Add a cache-busting timestamp to the URL to ensure we get a fresh thumbnail
       const timestamp = mediaFile.refreshToken || Date.now();
-      const pathWithTimestamp = `${mediaFile.path}?t=${timestamp}`;
       
-      return await getMediaThumbnail(pathWithTimestamp, maxSize);
+      return getThumbnailUrl(mediaFile.path, maxSize, timestamp);
     } catch (err) {
       console.error('Failed to generate thumbnail:', err);
       return null;
@@ -222,9 +237,10 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
   /**
    * Export the current working directory
    * @param asZip Whether to export as a ZIP file
+   * @param embedCaptions Whether to write captions into the exported images' metadata
    * @returns Promise with the path to the exported directory or ZIP file
    */
-  const exportWorkingDirectory = useCallback(async (asZip: boolean): Promise<string | null> => {
+  const exportWorkingDirectory = useCallback(async (asZip: boolean, embedCaptions = false): Promise<string | null> => {
     if (!workingDirectory) {
       setError('No working directory to export');
       return null;
@@ -238,7 +254,7 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
       const exportDestination = await selectExportDirectory();
       
       // Export the working directory
-      const exportedPath = await exportDirectory(workingDirectory, exportDestination, asZip);
+      const exportedPath = await exportDirectory(workingDirectory, exportDestination, asZip, undefined, embedCaptions);
       
       return exportedPath;
     } catch (err) {
@@ -271,6 +287,21 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
     }
   }, []);
 
+  /**
+   * Remove several media files and their captions in one undoable operation
+   * @param files The media files to remove
+   * @returns Promise with what was deleted
+   */
+  const removeFiles = useCallback(async (files: MediaFile[]): Promise<DeleteSummary> => {
+    const summary = await deleteMediaFiles(files.map(f => f.path));
+    
+    // Update the state to remove the deleted files
+    const deletedIds = new Set(files.map(f => f.id));
+    setMediaFiles(prev => prev.filter(f => !deletedIds.has(f.id)));
+    
+    return summary;
+  }, []);
+
   /**
    * Load an existing project directory directly without selecting a source directory
    * @param projectPath The path to the existing project directory
@@ -314,6 +345,73 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
     }
   }, [generateThumbnails]);
 
+  /**
+   * Pick up files added, changed or removed in the working directory without
+   * listing it again
+   * @returns Promise with the number of files that changed
+   */
+  const refreshMediaFiles = useCallback(async (): Promise<number> => {
+    if (!workingDirectory) return 0;
+    
+    const { added, changed, removed } = await refreshDirectory(workingDirectory);
+    if (added.length === 0 && changed.length === 0 && removed.length === 0) return 0;
+    
+    const gone = new Set([...removed, ...added.map(f => f.relative_path)]);
+    const updates = new Map(changed.map(f => [f.relative_path, f]));
+    const refreshToken = Date.now();
+    setMediaFiles(prev => {
+      const kept = prev
+        .filter(f => !gone.has(f.relative_path))
+        .map(f => {
+          const update = updates.get(f.relative_path);
+          // Changed files get a new thumbnail URL so the grid doesn't keep the old image
+          return update
+            ? { ...update, selected: f.selected, refreshToken, thumbnail: getThumbnailUrl(update.path, 100, refreshToken) }
+            : f;
+        });
+      const fresh = added.map(f => ({ ...f, thumbnail: getThumbnailUrl(f.path, 100) }));
+      return [...kept, ...fresh].sort((a, b) => a.name.localeCompare(b.name));
+    });
+    
+    return added.length + changed.length + removed.length;
+  }, [workingDirectory]);
+
+  /**
+   * Add files that appeared in the working directory, e.g. in a hot folder, to the list
+   * @param files The new files
+   */
+  const addMediaFiles = useCallback((files: MediaFile[]) => {
+    const paths = new Set(files.map(f => f.relative_path));
+    const fresh = files.map(f => ({ ...f, thumbnail: getThumbnailUrl(f.path, 100) }));
+    setMediaFiles(prev =>
+      [...prev.filter(f => !paths.has(f.relative_path)), ...fresh]
+        .sort((a, b) => a.name.localeCompare(b.name))
+    );
+  }, []);
+
+  /**
+   * Write the captions found in the imported images' metadata and reload the files
+   * @param includeKeywords Whether to append the embedded keywords as tags
+   * @returns Promise with the number of captions written
+   */
+  const applyEmbeddedCaptions = useCallback(async (includeKeywords: boolean): Promise<number> => {
+    if (!workingDirectory) return 0;
+    
+    const summary = await seedCaptionsFromMetadata(workingDirectory, { includeKeywords });
+    setEmbeddedCaptions(null);
+    
+    await refreshMediaFiles();
+    
+    return summary.changes.length;
+  }, [workingDirectory, refreshMediaFiles]);
+
+  /**
+   * Decline seeding captions from the imported images' metadata
+   */
+  const dismissEmbeddedCaptions = useCallback(() => {
+    setEmbeddedCaptions(null);
+  }, []);
+
   /**
    * Duplicate a media file and its caption
    * @param mediaFile The media file to duplicate
@@ -335,7 +433,7 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
       // Generate thumbnail for the new file before adding to state
       let thumbnail: string | undefined = undefined;
       try {
-        thumbnail = await getMediaThumbnail(enhancedFile.path, 100);
+        thumbnail = getThumbnailUrl(enhancedFile.path, 100);
         console.log("Generated thumbnail for duplicated file:", enhancedFile.name);
       } catch (err) {
         // Log error instead of silent fail
@@ -372,6 +470,12 @@ export function useFileSystem({ workingDirName = 'spacecat-working' }: UseFileSy
     exportWorkingDirectory,
     generateThumbnails,
     removeFile,
-    duplicateFile
+    removeFiles,
+    duplicateFile,
+    refreshMediaFiles,
+    addMediaFiles,
+    embeddedCaptions,
+    applyEmbeddedCaptions,
+    dismissEmbeddedCaptions
   };
 }
@@ -1,5 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
-import { ImageDetailLevel } from './settings';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
+import { AppSettings, ImageDetailLevel } from './settings';
 
 /**
  * Generate a caption for an image or video frame using OpenAI
@@ -11,6 +12,7 @@ import { ImageDetailLevel } from './settings';
  * @param imageDetail The image detail level
  * @param useDetailParameter Whether to include the detail parameter
  * @param videoFrameUrl Optional data URL of a video frame to use instead of the media path
+ * @param includeAudio Caption a video from sampled frames and a Whisper transcript of its sound
  * @returns Promise with the generated caption
  */
 export async function generateCaption(
@@ -21,7 +23,8 @@ export async function generateCaption(
   model: string,
   imageDetail: ImageDetailLevel,
   useDetailParameter: boolean,
-  videoFrameUrl?: string
+  videoFrameUrl?: string,
+  includeAudio?: boolean
 ): Promise<string> {
   return invoke('generate_caption', { 
     apiUrl, 
@@ -31,7 +34,8 @@ export async function generateCaption(
     model,
     imageDetail,
     useDetailParameter,
-    videoFrameUrl
+    videoFrameUrl,
+    includeAudio
   });
 }
 
@@ -42,6 +46,7 @@ export async function generateCaption(
  * @param mediaPath The path to the media file (image or video)
  * @param systemInstruction Optional system instruction
  * @param temperature Optional temperature parameter
+ * @param includeAudio Ask for a video's sound to be described as well as its picture
  * @returns Promise with the generated caption
  */
 export async function generateGeminiCaption(
@@ -49,14 +54,157 @@ export async function generateGeminiCaption(
   prompt: string,
   mediaPath: string,
   systemInstruction?: string,
-  temperature?: number
+  temperature?: number,
+  includeAudio?: boolean
 ): Promise<string> {
   return invoke('generate_gemini_caption', {
     apiKey,
     prompt,
     mediaPath,
     systemInstruction,
-    temperature
+    temperature,
+    includeAudio
+  });
+}
+
+/**
+ * Partial text of a caption being streamed
+ */
+export interface CaptionChunk {
+  stream_id: string;
+  /** The caption received so far, not just the latest chunk */
+  text: string;
+}
+
+/**
+ * Listen for the partial text of streamed captions
+ * @param callback Called with the caption received so far
+ * @returns Promise with a function that stops listening
+ */
+export async function onCaptionStream(
+  callback: (chunk: CaptionChunk) => void
+): Promise<UnlistenFn> {
+  return listen<CaptionChunk>('caption-stream', (event) => callback(event.payload));
+}
+
+/**
+ * Generate a caption using OpenAI, streaming its text as caption-stream events
+ * @param streamId Identifies the events of this caption
+ * @param apiUrl The API URL
+ * @param apiKey The API key
+ * @param prompt The caption prompt
+ * @param mediaPath The path to the media file (image or video)
+ * @param model The model to use
+ * @param imageDetail The image detail level
+ * @param useDetailParameter Whether to include the detail parameter
+ * @param videoFrameUrl Optional data URL of a video frame to use instead of the media path
+ * @param includeAudio Caption a video from sampled frames and a Whisper transcript of its sound
+ * @returns Promise with the complete caption
+ */
+export async function streamCaption(
+  streamId: string,
+  apiUrl: string,
+  apiKey: string,
+  prompt: string,
+  mediaPath: string,
+  model: string,
+  imageDetail: ImageDetailLevel,
+  useDetailParameter: boolean,
+  videoFrameUrl?: string,
+  includeAudio?: boolean
+): Promise<string> {
+  return invoke('stream_caption', {
+    streamId,
+    apiUrl,
+    apiKey,
+    prompt,
+    imagePath: mediaPath,
+    model,
+    imageDetail,
+    useDetailParameter,
+    videoFrameUrl,
+    includeAudio
+  });
+}
+
+/**
+ * Generate a caption using Gemini, streaming its text as caption-stream events
+ * @param streamId Identifies the events of this caption
+ * @param apiKey The Gemini API key
+ * @param prompt The caption prompt
+ * @param mediaPath The path to the media file (image or video)
+ * @param systemInstruction Optional system instruction
+ * @param temperature Optional temperature parameter
+ * @param includeAudio Ask for a video's sound to be described as well as its picture
+ * @returns Promise with the complete caption
+ */
+export async function streamGeminiCaption(
+  streamId: string,
+  apiKey: string,
+  prompt: string,
+  mediaPath: string,
+  systemInstruction?: string,
+  temperature?: number,
+  includeAudio?: boolean
+): Promise<string> {
+  return invoke('stream_gemini_caption', {
+    streamId,
+    apiKey,
+    prompt,
+    mediaPath,
+    systemInstruction,
+    temperature,
+    includeAudio
+  });
+}
+
+/**
+ * Run a streamed caption request, passing its partial text to onPartial
+ * @param request Starts the request with the given stream ID
+ * @param onPartial Called with the caption received so far
+ * @returns Promise with the complete caption
+ */
+async function withCaptionStream(
+  request: (streamId: string) => Promise<string>,
+  onPartial: (text: string) => void
+): Promise<string> {
+  const streamId = crypto.randomUUID();
+  const unlisten = await onCaptionStream((chunk) => {
+    if (chunk.stream_id === streamId) {
+      onPartial(chunk.text);
+    }
+  });
+  try {
+    return await request(streamId);
+  } finally {
+    unlisten();
+  }
+}
+
+/**
+ * Generate a caption with a user-provided script. The script gets a JSON request
+ * ({ media_path, media_type, prompt }) on stdin and prints { "caption": "..." } or
+ * { "error": "..." } as the last line of stdout.
+ * @param command The executable to run, e.g. python3
+ * @param args Arguments of the executable, e.g. the path of a Python script
+ * @param prompt The caption prompt
+ * @param mediaPath The path to the media file (image or video)
+ * @param timeoutSecs Optional time limit in seconds (default: 300)
+ * @returns Promise with the generated caption
+ */
+export async function generateScriptCaption(
+  command: string,
+  args: string[],
+  prompt: string,
+  mediaPath: string,
+  timeoutSecs?: number
+): Promise<string> {
+  return invoke('generate_script_caption', {
+    command,
+    args,
+    prompt,
+    mediaPath,
+    timeoutSecs
   });
 }
 
@@ -69,6 +217,7 @@ export async function generateGeminiCaption(
  * @param model The model to use
  * @param imageDetail The image detail level
  * @param useDetailParameter Whether to include the detail parameter
+ * @param includeAudio Caption videos from sampled frames and a Whisper transcript of their sound
  * @returns Promise with array of [path, caption] tuples
  */
 export async function generateCaptions(
@@ -78,7 +227,8 @@ export async function generateCaptions(
   imagePaths: string[],
   model: string,
   imageDetail: ImageDetailLevel,
-  useDetailParameter: boolean
+  useDetailParameter: boolean,
+  includeAudio?: boolean
 ): Promise<[string, string][]> {
   return invoke('generate_captions', { 
     apiUrl, 
@@ -87,7 +237,8 @@ export async function generateCaptions(
     imagePaths, 
     model,
     imageDetail,
-    useDetailParameter
+    useDetailParameter,
+    includeAudio
   });
 }
 
@@ -98,6 +249,7 @@ export async function generateCaptions(
  * @param mediaPaths Array of paths to media files
  * @param systemInstruction Optional system instruction
  * @param temperature Optional temperature parameter
+ * @param includeAudio Ask for the sound of videos to be described as well as their picture
  * @returns Promise with array of [path, caption] tuples
  */
 export async function generateGeminiCaptions(
@@ -105,42 +257,270 @@ export async function generateGeminiCaptions(
   prompt: string,
   mediaPaths: string[],
   systemInstruction?: string,
-  temperature?: number
+  temperature?: number,
+  includeAudio?: boolean
 ): Promise<[string, string][]> {
   return invoke('generate_gemini_captions', {
     apiKey,
     prompt,
     mediaPaths,
     systemInstruction,
-    temperature
+    temperature,
+    includeAudio
+  });
+}
+
+/**
+ * Generate captions for multiple media files with a user-provided script
+ * @param command The executable to run
+ * @param args Arguments of the executable
+ * @param prompt The caption prompt
+ * @param mediaPaths Array of paths to media files
+ * @param timeoutSecs Optional time limit per file in seconds
+ * @returns Promise with array of [path, caption] tuples
+ */
+export async function generateScriptCaptions(
+  command: string,
+  args: string[],
+  prompt: string,
+  mediaPaths: string[],
+  timeoutSecs?: number
+): Promise<[string, string][]> {
+  return invoke('generate_script_captions', {
+    command,
+    args,
+    prompt,
+    mediaPaths,
+    timeoutSecs
+  });
+}
+
+/**
+ * Generate web alt text for an image or video frame using OpenAI. Alt text is concise
+ * and factual, without openings like "image of", and is cut to the length cap.
+ * @param apiUrl The API URL
+ * @param apiKey The API key
+ * @param mediaPath The path to the media file (image or video)
+ * @param model The model to use
+ * @param maxLength Optional length cap in characters (default: 125)
+ * @param videoFrameUrl Optional data URL of a video frame to use instead of the media path
+ * @returns Promise with the generated alt text
+ */
+export async function generateAltText(
+  apiUrl: string,
+  apiKey: string,
+  mediaPath: string,
+  model: string,
+  maxLength?: number,
+  videoFrameUrl?: string
+): Promise<string> {
+  return invoke('generate_alt_text', {
+    apiUrl,
+    apiKey,
+    imagePath: mediaPath,
+    model,
+    maxLength,
+    videoFrameUrl
+  });
+}
+
+/**
+ * Generate web alt text for an image or video using Google's Gemini API
+ * @param apiKey The Gemini API key
+ * @param mediaPath The path to the media file (image or video)
+ * @param maxLength Optional length cap in characters (default: 125)
+ * @returns Promise with the generated alt text
+ */
+export async function generateGeminiAltText(
+  apiKey: string,
+  mediaPath: string,
+  maxLength?: number
+): Promise<string> {
+  return invoke('generate_gemini_alt_text', { apiKey, mediaPath, maxLength });
+}
+
+/**
+ * Generate web alt text for multiple media files using OpenAI
+ * @param apiUrl The API URL
+ * @param apiKey The API key
+ * @param imagePaths Array of paths to media files
+ * @param model The model to use
+ * @param maxLength Optional length cap in characters (default: 125)
+ * @returns Promise with array of [path, alt text] tuples
+ */
+export async function generateAltTexts(
+  apiUrl: string,
+  apiKey: string,
+  imagePaths: string[],
+  model: string,
+  maxLength?: number
+): Promise<[string, string][]> {
+  return invoke('generate_alt_texts', {
+    apiUrl,
+    apiKey,
+    imagePaths,
+    model,
+    maxLength
   });
 }
 
+/**
+ * Generate web alt text for multiple media files using Gemini
+ * @param apiKey The Gemini API key
+ * @param mediaPaths Array of paths to media files
+ * @param maxLength Optional length cap in characters (default: 125)
+ * @returns Promise with array of [path, alt text] tuples
+ */
+export async function generateGeminiAltTexts(
+  apiKey: string,
+  mediaPaths: string[],
+  maxLength?: number
+): Promise<[string, string][]> {
+  return invoke('generate_gemini_alt_texts', { apiKey, mediaPaths, maxLength });
+}
+
+export interface GeminiCleanup {
+  deleted: number;
+  kept: number;
+  foreign: number;
+  failed: number;
+}
+
+/**
+ * Delete files this app uploaded to the Gemini Files API that are older than a given age.
+ * Files uploaded by anything else are left alone.
+ * @param apiKey The Gemini API key
+ * @param maxAgeMinutes Optional age in minutes past which uploads are deleted (default: 60)
+ * @returns Promise with the number of uploads deleted, kept and failed, and of other files
+ */
+export async function cleanupGeminiFiles(apiKey: string, maxAgeMinutes?: number): Promise<GeminiCleanup> {
+  return invoke('cleanup_gemini_files', { apiKey, maxAgeMinutes });
+}
+
+/** Time, size and reply limits of the requests sent to one provider */
+export interface ProviderLimits {
+  /** Time limit of a caption request in seconds; streamed requests get at least 120 */
+  timeout_secs: number;
+  /** Largest media sent in one request, in megabytes */
+  max_upload_mb: number;
+  /** Largest reply accepted, in kilobytes */
+  max_response_kb: number;
+}
+
+/** Limits of the requests sent to each provider */
+export interface LimitSettings {
+  /** OpenAI-compatible APIs, including self-hosted servers (default: 30 s, 20 MB, 1024 KB) */
+  openai: ProviderLimits;
+  /** Gemini (default: 120 s, 2048 MB, 1024 KB) */
+  gemini: ProviderLimits;
+}
+
+/**
+ * Get the limits of the requests sent to each provider
+ * @returns Promise with the limits
+ */
+export async function getProviderLimits(): Promise<LimitSettings> {
+  return invoke('get_provider_limits');
+}
+
+/**
+ * Change the limits of the requests sent to each provider; they are kept across restarts
+ * @param settings New limits, all greater than zero
+ */
+export async function updateProviderLimits(settings: LimitSettings): Promise<void> {
+  return invoke('update_provider_limits', { settings });
+}
+
+/**
+ * Split the caption script arguments setting into separate arguments
+ * @param settings The application settings
+ * @returns The arguments
+ */
+export function scriptArgs(settings: AppSettings): string[] {
+  return settings.scriptArgs.split(/\s+/).filter(arg => arg.length > 0);
+}
+
 /**
  * Generate a caption for a media file using the preferred provider
  * @param mediaPath The path to the media file
  * @param settings The application settings
  * @param isVideo Whether the media is a video
  * @param videoFrameUrl Optional data URL of a video frame (for OpenAI)
+ * @param onPartial Optional callback that streams the caption as it is generated
  * @returns Promise with the generated caption
  */
 export async function generateCaptionWithPreferredProvider(
   mediaPath: string,
   settings: any,
   isVideo: boolean = false,
-  videoFrameUrl?: string
+  videoFrameUrl?: string,
+  onPartial?: (text: string) => void
 ): Promise<string> {
+  // A caption script handles every kind of media itself
+  if (settings.preferredProvider === 'script') {
+    return generateScriptCaption(
+      settings.scriptCommand,
+      scriptArgs(settings),
+      settings.captionPrompt,
+      mediaPath
+    );
+  }
+  
   // Determine which provider to use
   const useGemini = isVideo && settings.useGeminiForVideos 
     ? true 
     : settings.preferredProvider === 'gemini';
   
+  // Alt text mode asks for web alt text rather than a training caption
+  if (settings.captionMode === 'alt_text') {
+    return useGemini
+      ? generateGeminiAltText(settings.geminiApiKey, mediaPath, settings.altTextMaxLength)
+      : generateAltText(
+          settings.apiUrl,
+          settings.apiKey,
+          mediaPath,
+          settings.model,
+          settings.altTextMaxLength,
+          videoFrameUrl
+        );
+  }
+  
+  if (onPartial) {
+    return withCaptionStream(
+      (streamId) => useGemini
+        ? streamGeminiCaption(
+            streamId,
+            settings.geminiApiKey,
+            settings.captionPrompt,
+            mediaPath,
+            settings.geminiSystemInstruction,
+            undefined,
+            settings.includeVideoAudio
+          )
+        : streamCaption(
+            streamId,
+            settings.apiUrl,
+            settings.apiKey,
+            settings.captionPrompt,
+            mediaPath,
+            settings.model,
+            settings.imageDetail,
+            settings.useDetailParameter,
+            videoFrameUrl,
+            settings.includeVideoAudio
+          ),
+      onPartial
+    );
+  }
+  
   if (useGemini) {
     return generateGeminiCaption(
       settings.geminiApiKey,
       settings.captionPrompt,
       mediaPath,
-      settings.geminiSystemInstruction
+      settings.geminiSystemInstruction,
+      undefined,
+      settings.includeVideoAudio
     );
   } else {
     return generateCaption(
@@ -151,7 +531,8 @@ export async function generateCaptionWithPreferredProvider(
       settings.model,
       settings.imageDetail,
       settings.useDetailParameter,
-      videoFrameUrl
+      videoFrameUrl,
+      settings.includeVideoAudio
     );
   }
 }
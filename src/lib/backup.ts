@@ -0,0 +1,50 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export interface BackupInfo {
+  id: string;
+  project: string;
+  created: string;
+  reason: string;
+  include_media: boolean;
+  file_count: number;
+  size_bytes: number;
+}
+
+/**
+ * Back up a project now
+ * @param project Path to the project directory
+ * @param includeMedia Include media files, not just captions (defaults to the project's backup settings)
+ * @returns Promise with the new backup
+ */
+export async function createProjectBackup(project: string, includeMedia?: boolean): Promise<BackupInfo> {
+  return invoke('create_project_backup', { project, includeMedia });
+}
+
+/**
+ * List the backups of a project
+ * @param project Path to the project directory
+ * @returns Promise with the backups, most recent first
+ */
+export async function listProjectBackups(project: string): Promise<BackupInfo[]> {
+  return invoke('list_project_backups', { project });
+}
+
+/**
+ * Restore a backup into its project; the restore can be undone
+ * @param project Path to the project directory
+ * @param id ID of the backup to restore
+ * @returns Promise with the number of files restored
+ */
+export async function restoreProjectBackup(project: string, id: string): Promise<number> {
+  return invoke('restore_project_backup', { project, id });
+}
+
+/**
+ * Delete old backups of a project
+ * @param project Path to the project directory
+ * @param keep Number of most recent backups to keep (defaults to the project's backup settings)
+ * @returns Promise with the number of backups deleted
+ */
+export async function pruneProjectBackups(project: string, keep?: number): Promise<number> {
+  return invoke('prune_project_backups', { project, keep });
+}
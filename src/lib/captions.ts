@@ -0,0 +1,700 @@
+import { invoke } from '@tauri-apps/api/core';
+import type { MediaFile } from './fs';
+
+export type CaptionFormat = 'txt' | 'caption' | 'json' | 'manifest' | 'jsonl';
+
+export interface CaptionVersion {
+  version: number;
+  timestamp: string;
+  content: string;
+}
+
+export interface SnippetPart {
+  text: string;
+  highlight: boolean;
+}
+
+export interface CaptionMatch {
+  file: MediaFile;
+  match_count: number;
+  snippets: SnippetPart[][];
+}
+
+export interface CaptionChange {
+  path: string;
+  name: string;
+  before: string;
+  after: string;
+}
+
+export interface BatchEditSummary {
+  changes: CaptionChange[];
+  total_replacements: number;
+  applied: boolean;
+}
+
+export interface CaptionIssue {
+  kind: 'spelling' | 'repeated_word' | 'grammar';
+  /** Byte range of the flagged text in the caption */
+  start: number;
+  end: number;
+  text: string;
+  /** Replacement used when fixing, or null if there is no good guess */
+  suggestion: string | null;
+}
+
+export interface CaptionLint {
+  path: string;
+  name: string;
+  caption: string;
+  issues: CaptionIssue[];
+}
+
+export interface LintReport {
+  /** Captions with issues */
+  files: CaptionLint[];
+  checked: number;
+  /** Words in the dictionary used; 0 if only the project's own vocabulary was used */
+  dictionary_words: number;
+  /** Captions changed by fixing the issues that have a suggestion */
+  changes: CaptionChange[];
+  applied: boolean;
+}
+
+/** OpenAI-compatible model for the optional grammar pass of lintCaptions */
+export interface GrammarModel {
+  api_url: string;
+  api_key: string;
+  model: string;
+}
+
+export interface LengthStats {
+  min: number;
+  max: number;
+  mean: number;
+  median: number;
+  p90: number;
+}
+
+export interface CaptionStats {
+  total_files: number;
+  captioned: number;
+  uncaptioned: number;
+  empty_captions: string[];
+  chars: LengthStats;
+  words: LengthStats;
+  clip_tokens: LengthStats;
+  over_token_limit: number;
+  average_tags: number;
+  /** Coverage of the captions in other languages */
+  languages: LanguageCoverage[];
+}
+
+export interface LanguageCoverage {
+  language: string;
+  captioned: number;
+  ratio: number;
+}
+
+export interface TagFrequency {
+  tag: string;
+  count: number;
+  files: number;
+  ratio: number;
+}
+
+/** Tag aliases ("girl" → "woman") and implications ("golden retriever" → ["dog"]) */
+export interface TagDictionary {
+  aliases: Record<string, string>;
+  implications: Record<string, string[]>;
+}
+
+export interface UnknownTag {
+  tag: string;
+  files: number;
+}
+
+export interface BooruImport {
+  changes: CaptionChange[];
+  applied: boolean;
+  /** Tags the project's tag dictionary doesn't know, most frequent first */
+  unknown_tags: UnknownTag[];
+}
+
+/** What to do with an imported caption when the media file already has one */
+export type CaptionConflict = 'skip' | 'overwrite' | 'append';
+
+export interface CaptionImport {
+  changes: CaptionChange[];
+  applied: boolean;
+  /** Media files that kept their caption because of the conflict policy */
+  skipped: string[];
+  /** Imported captions that matched no media file of the project, or several */
+  unmatched: string[];
+}
+
+export interface CaptionDiffEntry {
+  path: string;
+  name: string;
+  status: 'only_ours' | 'only_theirs' | 'changed';
+  ours: string | null;
+  theirs: string | null;
+}
+
+export interface CaptionDiff {
+  entries: CaptionDiffEntry[];
+  /** Media files with the same caption on both sides */
+  unchanged: number;
+  /** Captions of the other set that matched no media file of the project, or several */
+  unmatched: string[];
+}
+
+export interface CaptionMerge {
+  changes: CaptionChange[];
+  applied: boolean;
+  /** Media files whose caption now holds conflict markers, to be resolved by hand */
+  conflicts: string[];
+  unmatched: string[];
+}
+
+export interface ConversionSummary {
+  converted: number;
+  skipped: number;
+}
+
+/**
+ * Read the caption of a media file in its project's caption format
+ * @param mediaPath Path to the media file
+ * @param language Optional language code (e.g. "ja") to read `image.ja.txt` instead
+ * @returns Promise with the caption (empty if the file has none)
+ */
+export async function readMediaCaption(mediaPath: string, language?: string): Promise<string> {
+  return invoke('read_media_caption', { mediaPath, language });
+}
+
+/**
+ * Write the caption of a media file in its project's caption format
+ * @param mediaPath Path to the media file
+ * @param content Caption content
+ * @param sync Flush the write to disk before resolving
+ * @param language Optional language code (e.g. "ja") to write `image.ja.txt` instead
+ * @param expected Optional caption as it was last read; a caption changed on disk since
+ *   is not overwritten and the write fails with a {@link CaptionModifiedError}
+ * @returns Promise that resolves when the caption is written
+ */
+export async function writeMediaCaption(
+  mediaPath: string,
+  content: string,
+  sync = false,
+  language?: string,
+  expected?: string
+): Promise<void> {
+  return invoke('write_media_caption', { mediaPath, content, sync, language, expected });
+}
+
+/** Error of a caption write refused because the caption changed outside the app */
+export interface CaptionModifiedError {
+  kind: 'caption_modified';
+  path: string;
+  /** The caption on disk, empty if it was deleted */
+  current: string;
+  message: string;
+}
+
+/**
+ * Recognize the error of a caption write refused because the caption changed on disk
+ * since it was read
+ * @param error Error thrown by a command
+ * @returns The details, or null for any other error
+ */
+export function parseCaptionModified(error: unknown): CaptionModifiedError | null {
+  if (typeof error !== 'string' || !error.startsWith('{')) return null;
+  try {
+    const parsed = JSON.parse(error);
+    return parsed?.kind === 'caption_modified' ? parsed as CaptionModifiedError : null;
+  } catch {
+    return null;
+  }
+}
+
+/**
+ * List the languages that have captions in a project
+ * @param directory Project directory
+ * @returns Promise with the language codes, e.g. ["en", "ja"]
+ */
+export async function listCaptionLanguages(directory: string): Promise<string[]> {
+  return invoke('list_caption_languages', { directory });
+}
+
+/**
+ * Convert all captions of a project to another format
+ * @param directory Project directory
+ * @param targetFormat Format to convert to
+ * @param removeOld Whether to delete the captions in the previous format
+ * @returns Promise with the number of converted and skipped files
+ */
+export async function convertCaptionFormat(
+  directory: string,
+  targetFormat: CaptionFormat,
+  removeOld: boolean
+): Promise<ConversionSummary> {
+  return invoke('convert_caption_format', { directory, targetFormat, removeOld });
+}
+
+/**
+ * Get the previous versions of a caption
+ * @param path Path to the media file or its caption file
+ * @returns Promise with the stored versions, most recent first
+ */
+export async function getCaptionHistory(path: string): Promise<CaptionVersion[]> {
+  return invoke('get_caption_history', { path });
+}
+
+/**
+ * Restore a previous version of a caption
+ * @param path Path to the media file or its caption file
+ * @param version Version number to restore
+ * @returns Promise with the restored caption content
+ */
+export async function restoreCaptionVersion(path: string, version: number): Promise<string> {
+  return invoke('restore_caption_version', { path, version });
+}
+
+export interface CaptionCommit {
+  id: string;
+  summary: string;
+  timestamp: string;
+  files_changed: number;
+}
+
+export interface CaptionFileDiff {
+  path: string;
+  status: 'added' | 'modified' | 'deleted';
+  /** Unified diff of the caption file */
+  patch: string;
+}
+
+/**
+ * Keep a project's caption files in a git repository from now on
+ * @param directory Project directory
+ * @returns Promise with the first commit, or null if nothing changed
+ */
+export async function enableCaptionGit(directory: string): Promise<CaptionCommit | null> {
+  return invoke('enable_caption_git', { directory });
+}
+
+/**
+ * Commit the current captions of a project
+ * @param directory Project directory
+ * @param message Commit message
+ * @returns Promise with the commit, or null if nothing changed
+ */
+export async function commitCaptions(
+  directory: string,
+  message: string
+): Promise<CaptionCommit | null> {
+  return invoke('commit_captions', { directory, message });
+}
+
+/**
+ * List the caption commits of a project
+ * @param directory Project directory
+ * @param limit Maximum number of commits (default: 100)
+ * @returns Promise with the commits, most recent first
+ */
+export async function getCaptionGitLog(
+  directory: string,
+  limit?: number
+): Promise<CaptionCommit[]> {
+  return invoke('get_caption_git_log', { directory, limit });
+}
+
+/**
+ * Get the caption changes of a commit
+ * @param directory Project directory
+ * @param commit Commit id, or undefined for the uncommitted changes
+ * @returns Promise with the diff of each changed caption file
+ */
+export async function getCaptionGitDiff(
+  directory: string,
+  commit?: string
+): Promise<CaptionFileDiff[]> {
+  return invoke('get_caption_git_diff', { directory, commit });
+}
+
+/**
+ * Undo the caption changes of a commit, keeping later changes, and commit the result
+ * @param directory Project directory
+ * @param commit Commit id to revert
+ * @returns Promise with the revert commit, or null if nothing changed
+ */
+export async function revertCaptionCommit(
+  directory: string,
+  commit: string
+): Promise<CaptionCommit | null> {
+  return invoke('revert_caption_commit', { directory, commit });
+}
+
+/**
+ * Search the captions of every media file in a project (case-insensitive)
+ * @param directory Project directory
+ * @param query Text to find, or a regular expression when regex is set
+ * @param regex Whether the query is a regular expression
+ * @returns Promise with the matching files and highlighted snippets
+ */
+export async function searchCaptions(
+  directory: string,
+  query: string,
+  regex = false
+): Promise<CaptionMatch[]> {
+  return invoke('search_captions', { directory, query, regex });
+}
+
+/**
+ * Find and replace text across all captions of a project
+ * @param directory Project directory
+ * @param find Text to find, or a regular expression when regex is set
+ * @param replace Replacement text ($1-style references are expanded in regex mode)
+ * @param options Matching options; dryRun previews the changes without writing them
+ * @returns Promise with the affected captions before and after the replacement
+ */
+export async function replaceInCaptions(
+  directory: string,
+  find: string,
+  replace: string,
+  options: { regex?: boolean; caseSensitive?: boolean; dryRun?: boolean } = {}
+): Promise<BatchEditSummary> {
+  return invoke('replace_in_captions', {
+    directory,
+    find,
+    replace,
+    regex: options.regex ?? false,
+    caseSensitive: options.caseSensitive ?? false,
+    dryRun: options.dryRun ?? false,
+  });
+}
+
+/**
+ * Add text before and/or after captions, skipping captions that already contain it
+ * @param directory Project directory
+ * @param prepend Text to add at the start, including its separator (e.g. "ohwx, ")
+ * @param append Text to add at the end, including its separator (e.g. ", film grain")
+ * @param files Optional media paths to limit the change to
+ * @returns Promise with the modified captions
+ */
+export async function modifyCaptions(
+  directory: string,
+  prepend?: string,
+  append?: string,
+  files?: string[]
+): Promise<BatchEditSummary> {
+  return invoke('modify_captions', {
+    directory,
+    prepend: prepend ?? null,
+    append: append ?? null,
+    files: files ?? null,
+  });
+}
+
+/**
+ * Copy one file's caption to other files, e.g. burst shots or frame sequences
+ * @param directory Project directory
+ * @param source Media file whose caption is copied
+ * @param targets Media files that receive the caption
+ * @param options template fills in {name}, {stem}, {index} and {frame} per file;
+ *   overwrite replaces existing captions; dryRun only previews the changes
+ * @returns Promise with the captions that change (or would change)
+ */
+export async function propagateCaption(
+  directory: string,
+  source: string,
+  targets: string[],
+  options: { template?: boolean; overwrite?: boolean; dryRun?: boolean } = {}
+): Promise<BatchEditSummary> {
+  return invoke('propagate_caption', {
+    directory,
+    source,
+    targets,
+    template: options.template ?? false,
+    overwrite: options.overwrite ?? false,
+    dryRun: options.dryRun ?? false,
+  });
+}
+
+/**
+ * Where a caption merge reads a part from: the project's caption, the caption in a
+ * language, or a sidecar with the given extension (e.g. "tags" for `image.tags`)
+ */
+export type MergeSource = 'caption' | { language: string } | { sidecar: string };
+
+/**
+ * Merge two caption sources (e.g. tagger output and a prose caption) into the captions
+ * of a project, or of selected files
+ * @param directory Project directory
+ * @param tags Source of the `{tags}` part
+ * @param prose Source of the `{prose}` part
+ * @param options template places the parts (default "{tags}, {prose}"); files limits
+ *   the merge to those media paths; dryRun only previews the changes
+ * @returns Promise with the captions that change (or would change)
+ */
+export async function mergeCaptions(
+  directory: string,
+  tags: MergeSource,
+  prose: MergeSource,
+  options: { template?: string; files?: string[]; dryRun?: boolean } = {}
+): Promise<BatchEditSummary> {
+  return invoke('merge_captions', {
+    directory,
+    tags,
+    prose,
+    template: options.template,
+    files: options.files,
+    dryRun: options.dryRun ?? false,
+  });
+}
+
+/**
+ * Seed captions from the XMP/IPTC/EXIF descriptions and keywords embedded in a project's images
+ * @param directory Project directory
+ * @param options includeKeywords appends the keywords as tags; overwrite replaces
+ *   existing captions; dryRun only previews the changes
+ * @returns Promise with the captions that change (or would change)
+ */
+export async function seedCaptionsFromMetadata(
+  directory: string,
+  options: { includeKeywords?: boolean; overwrite?: boolean; dryRun?: boolean } = {}
+): Promise<BatchEditSummary> {
+  return invoke('seed_captions_from_metadata', {
+    directory,
+    includeKeywords: options.includeKeywords ?? true,
+    overwrite: options.overwrite ?? false,
+    dryRun: options.dryRun ?? false,
+  });
+}
+
+/**
+ * A caption sidecar without a media file
+ */
+export interface OrphanCaption {
+  path: string;
+  name: string;
+  /** Uncaptioned media file whose name is close enough to be the caption's */
+  suggested_media: string | null;
+}
+
+/**
+ * Captions and media of a project that lost their pair
+ */
+export interface OrphanReport {
+  orphan_captions: OrphanCaption[];
+  uncaptioned_media: string[];
+}
+
+/**
+ * What is done with orphaned captions: deleted, moved into the project metadata, or
+ * renamed after their suggested media file
+ */
+export type OrphanAction = 'delete' | 'archive' | 'repair';
+
+/**
+ * Outcome of dealing with orphaned captions
+ */
+export interface OrphanCleanup {
+  deleted: string[];
+  archived: string[];
+  /** Captions given to a media file, as `caption -> media` */
+  repaired: string[];
+  /** Captions that couldn't be re-paired */
+  skipped: string[];
+}
+
+/**
+ * Find the captions of a project without a media file, and the media without a caption
+ * @param directory Project directory
+ * @returns Promise with the orphaned captions and uncaptioned media
+ */
+export async function findOrphanCaptions(directory: string): Promise<OrphanReport> {
+  return invoke('find_orphan_captions', { directory });
+}
+
+/**
+ * Delete, archive or re-pair orphaned captions as one undoable operation
+ * @param directory Project directory
+ * @param action What to do with the orphans
+ * @param captions File names of the orphans to handle (default: all)
+ * @returns Promise with what was done to each caption
+ */
+export async function cleanUpOrphanCaptions(
+  directory: string,
+  action: OrphanAction,
+  captions?: string[]
+): Promise<OrphanCleanup> {
+  return invoke('clean_up_orphan_captions', { directory, action, captions });
+}
+
+/**
+ * Get caption statistics of a project (coverage, lengths, estimated CLIP tokens, tags)
+ * @param directory Project directory
+ * @returns Promise with the caption statistics
+ */
+export async function getCaptionStats(directory: string): Promise<CaptionStats> {
+  return invoke('get_caption_stats', { directory });
+}
+
+/**
+ * Get how often each comma-separated tag occurs across a project's captions
+ * @param directory Project directory
+ * @returns Promise with the tag table, most frequent first
+ */
+export async function getTagFrequencies(directory: string): Promise<TagFrequency[]> {
+  return invoke('get_tag_frequencies', { directory });
+}
+
+/**
+ * Get the tag aliases and implications of a project
+ * @param directory Project directory
+ * @returns Promise with the project's tag dictionary
+ */
+export async function getTagDictionary(directory: string): Promise<TagDictionary> {
+  return invoke('get_tag_dictionary', { directory });
+}
+
+/**
+ * Save the tag aliases and implications of a project; generated captions get them applied
+ * @param directory Project directory
+ * @param dictionary The new tag dictionary
+ * @returns Promise with the dictionary as stored
+ */
+export async function saveTagDictionary(
+  directory: string,
+  dictionary: TagDictionary
+): Promise<TagDictionary> {
+  return invoke('save_tag_dictionary', { directory, dictionary });
+}
+
+/**
+ * Apply a project's tag dictionary to all of its existing captions
+ * @param directory Project directory
+ * @param dryRun Only preview the changes
+ * @returns Promise with the captions that change (or would change)
+ */
+export async function normalizeTags(directory: string, dryRun = false): Promise<BatchEditSummary> {
+  return invoke('normalize_tags', { directory, dryRun });
+}
+
+/**
+ * Check the spelling of all captions of a project
+ * @param directory Project directory
+ * @param options dictionary is a word list or Hunspell .dic (default: the system word
+ *   list); grammar adds a language model pass over prose captions; fix writes the
+ *   suggested corrections
+ * @returns Promise with the issues found and the captions their fixes change
+ */
+export async function lintCaptions(
+  directory: string,
+  options: { dictionary?: string; grammar?: GrammarModel; fix?: boolean } = {}
+): Promise<LintReport> {
+  return invoke('lint_captions', {
+    directory,
+    dictionary: options.dictionary,
+    grammar: options.grammar,
+    fix: options.fix ?? false,
+  });
+}
+
+/**
+ * Add words the spell checker should accept for a project, such as names
+ * @param directory Project directory
+ * @param words Words to accept
+ * @returns Promise with the project's whole word list
+ */
+export async function addSpellingWords(directory: string, words: string[]): Promise<string[]> {
+  return invoke('add_spelling_words', { directory, words });
+}
+
+/**
+ * Normalize the booru-style tags of an imported dataset (separators, underscores,
+ * duplicates) and apply the project's tag dictionary
+ * @param directory Project directory
+ * @param dryRun Only preview the changes
+ * @returns Promise with the changed captions and the tags the dictionary doesn't know
+ */
+export async function importBooruTags(directory: string, dryRun = false): Promise<BooruImport> {
+  return invoke('import_booru_tags', { directory, dryRun });
+}
+
+/**
+ * Import the caption files of a folder or ZIP archive into a project, e.g. captions
+ * reviewed by a collaborator, matching them to media files by relative path or file name
+ * @param directory Project directory
+ * @param source Folder or ZIP archive with the caption files
+ * @param policy What to do with media files that already have a caption (default: skip)
+ * @param dryRun Only preview the changes
+ * @returns Promise with the changed captions, the skipped media files and the unmatched captions
+ */
+export async function importCaptionFiles(
+  directory: string,
+  source: string,
+  policy?: CaptionConflict,
+  dryRun = false
+): Promise<CaptionImport> {
+  return invoke('import_caption_files', { directory, source, policy, dryRun });
+}
+
+/**
+ * Compare the captions of a project with those of another project, or of a folder or
+ * ZIP archive of caption files
+ * @param directory Project directory
+ * @param other Other project, folder or ZIP archive
+ * @returns Promise with the media files whose captions differ
+ */
+export async function diffCaptions(directory: string, other: string): Promise<CaptionDiff> {
+  return invoke('diff_captions', { directory, other });
+}
+
+/**
+ * Three-way merge the captions of another project, folder or ZIP archive into a project;
+ * captions changed on both sides get conflict markers to resolve by hand
+ * @param directory Project directory
+ * @param theirs Other project, folder or ZIP archive with the captions to merge in
+ * @param base Optional caption set both sides started from, e.g. an earlier captions-only export
+ * @param dryRun Only preview the changes
+ * @returns Promise with the changed captions and the conflicts
+ */
+export async function mergeCaptionSets(
+  directory: string,
+  theirs: string,
+  base?: string,
+  dryRun = false
+): Promise<CaptionMerge> {
+  return invoke('merge_caption_sets', { directory, theirs, base, dryRun });
+}
+
+/**
+ * Re-apply a project's trigger rules to all of its captions, e.g. after changing them
+ * @param directory Project directory
+ * @param options previousTokens are replaced by the current token (e.g. an old trigger
+ *   word); dryRun only previews the changes
+ * @returns Promise with the captions that change (or would change)
+ */
+export async function reapplyTriggerRules(
+  directory: string,
+  options: { previousTokens?: string[]; dryRun?: boolean } = {}
+): Promise<BatchEditSummary> {
+  return invoke('reapply_trigger_rules', {
+    directory,
+    previousTokens: options.previousTokens,
+    dryRun: options.dryRun ?? false,
+  });
+}
+
+/**
+ * Export the captions of a project as web alt text to an alt-text.csv, with a
+ * file,alt_text row per captioned file
+ * @param directory Project directory
+ * @param destination Optional directory to write the file to (default: the project)
+ * @returns Promise with the path of the written file
+ */
+export async function exportAltText(directory: string, destination?: string): Promise<string> {
+  return invoke('export_alt_text', { directory, destination });
+}
@@ -0,0 +1,90 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export interface PromptVariant {
+  name: string;
+  prompt: string;
+}
+
+export type ExperimentProvider =
+  | {
+      provider: 'openai';
+      api_url: string;
+      model: string;
+      image_detail: string;
+      use_detail_parameter: boolean;
+    }
+  | {
+      provider: 'gemini';
+      system_instruction: string | null;
+      temperature: number | null;
+    };
+
+export interface ExperimentSample {
+  path: string;
+  relative_path: string;
+  /** Captions in the order of the variants; shorter than the variants if the run was cancelled */
+  captions: string[];
+}
+
+export interface Experiment {
+  id: string;
+  name: string;
+  created: string;
+  provider: ExperimentProvider;
+  variants: PromptVariant[];
+  seed: number;
+  samples: ExperimentSample[];
+  incomplete: boolean;
+}
+
+/**
+ * Caption a random sample of a project's files with each of several prompts, as a captioning job
+ * @param directory Path to the project directory
+ * @param name Name of the experiment
+ * @param variants Prompts to compare, at least two
+ * @param sampleSize Number of files to sample
+ * @param provider Provider and model to caption with
+ * @param apiKey API key for the provider; it is not stored with the experiment
+ * @param seed Seed to draw the sample with, to repeat an earlier experiment's sample
+ * @returns Promise with the saved experiment
+ */
+export async function runExperiment(
+  directory: string,
+  name: string,
+  variants: PromptVariant[],
+  sampleSize: number,
+  provider: ExperimentProvider,
+  apiKey: string,
+  seed?: number
+): Promise<Experiment> {
+  return invoke('run_experiment', { directory, name, variants, sampleSize, seed, provider, apiKey });
+}
+
+/**
+ * List the prompt experiments of a project
+ * @param directory Path to the project directory
+ * @returns Promise with the experiments, newest first
+ */
+export async function listExperiments(directory: string): Promise<Experiment[]> {
+  return invoke('list_experiments', { directory });
+}
+
+/**
+ * Delete a prompt experiment
+ * @param directory Path to the project directory
+ * @param id ID of the experiment
+ */
+export async function deleteExperiment(directory: string, id: string): Promise<void> {
+  return invoke('delete_experiment', { directory, id });
+}
+
+/**
+ * Export a prompt experiment as a CSV comparison sheet
+ * @param directory Path to the project directory
+ * @param id ID of the experiment
+ * @param destination Directory to write the sheet to (defaults to the project directory)
+ * @returns Promise with the path of the written file
+ */
+export async function exportExperiment(directory: string, id: string, destination?: string): Promise<string> {
+  return invoke('export_experiment', { directory, id, destination });
+}
@@ -1,5 +1,39 @@
 import { invoke } from '@tauri-apps/api/core';
 import { convertFileSrc } from '@tauri-apps/api/core';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
+import { CaptionFormat } from './captions';
+
+/** How exported media files are named; sidecar files are renamed along with them */
+export type ExportNaming = 'original' | 'sequential' | 'hash' | 'sanitized';
+
+export type ColorFlag = 'red' | 'orange' | 'yellow' | 'green' | 'blue' | 'purple' | 'gray';
+
+/** Curation marks of a file */
+export interface FileMark {
+  /** 1-5 stars */
+  rating: number | null;
+  flag: ColorFlag | null;
+  favorite: boolean;
+}
+
+/** Faces and persons found by subject detection */
+export interface SubjectCount {
+  /** Null when no face model is installed */
+  faces: number | null;
+  persons: number;
+}
+
+/** Which files a listing or export includes; unset criteria match every file */
+export interface MarkFilter {
+  min_rating?: number;
+  /** Files with any of these flags */
+  flags?: ColorFlag[];
+  favorite?: boolean;
+  /** Files with exactly this many faces, e.g. 1 for a character dataset; undetected files don't match */
+  faces?: number;
+  /** Files with exactly this many persons; undetected files don't match */
+  persons?: number;
+}
 
 export interface MediaFile {
   id: string;
@@ -8,6 +42,20 @@ export interface MediaFile {
   relative_path: string;
   file_type: string;
   has_caption: boolean;
+  size: number;
+  width: number | null;
+  height: number | null;
+  /** Duration in seconds (videos and animated images) */
+  duration: number | null;
+  /** Number of frames (animated GIFs and WebPs only) */
+  frame_count: number | null;
+  modified: string | null;
+  /** Start of the caption, only filled in by listDirectoryFiles */
+  caption_preview: string | null;
+  /** Rating, flag and favorite bit, only filled in by listDirectoryFiles */
+  mark: FileMark | null;
+  /** Faces and persons, only filled in by listDirectoryFiles once detectSubjects ran */
+  subjects: SubjectCount | null;
   // Additional frontend properties
   type?: 'image' | 'video';
   selected?: boolean;
@@ -16,6 +64,86 @@ export interface MediaFile {
   refreshToken?: number;
 }
 
+/** Error of a crop, trim or rename that failed because another program has the file open */
+export interface FileInUseError {
+  kind: 'file_in_use';
+  /** What was being done, e.g. "crop" */
+  operation: string;
+  path: string;
+  message: string;
+}
+
+/** What changed in a directory since it was last listed */
+export interface DirectoryChanges {
+  added: MediaFile[];
+  changed: MediaFile[];
+  /** Relative paths of the files that are gone */
+  removed: string[];
+}
+
+export type ImportMode = 'copy' | 'hardlink' | 'reflink' | 'link_in_place';
+
+export type ConflictPolicy = 'skip' | 'overwrite' | 'rename';
+
+export interface MergeReport {
+  added: string[];
+  skipped: string[];
+  overwritten: string[];
+  renamed: { from: string; to: string }[];
+}
+
+export type SyncChangeKind = 'added' | 'modified' | 'removed' | 'conflict';
+
+export interface SyncReport {
+  source: string;
+  changes: { path: string; kind: SyncChangeKind }[];
+  applied: boolean;
+}
+
+export interface ArchiveImport {
+  project: string;
+  name: string;
+  media_count: number;
+  has_project_file: boolean;
+  uncaptioned: string[];
+  orphan_captions: string[];
+}
+
+export interface CatalogImport {
+  project: string;
+  name: string;
+  media_count: number;
+  /** Files given an initial caption from their metadata */
+  captioned: number;
+  /** Files renamed because another folder had a file of the same name */
+  renamed: { from: string; to: string }[];
+}
+
+export type DedupMode = 'off' | 'report' | 'skip';
+
+export interface DuplicateGroup {
+  hash: string;
+  size: number;
+  kept: string;
+  duplicates: string[];
+}
+
+export interface DedupReport {
+  source: string;
+  mode: DedupMode;
+  groups: DuplicateGroup[];
+  duplicate_files: number;
+  duplicate_bytes: number;
+}
+
+export interface CopyProgress {
+  copied_bytes: number;
+  total_bytes: number;
+  copied_files: number;
+  total_files: number;
+  current_file: string;
+}
+
 export interface ProjectDirectory {
   id: string;
   name: string;
@@ -39,10 +167,119 @@ export async function selectDirectory(): Promise<string> {
  * Duplicate a directory to create a working copy
  * @param source Source directory path
  * @param destination Destination directory path
+ * @param mode How files are brought in (copy, hard links, reflinks or links to the originals)
+ * @param dedup Whether to report byte-identical files, or skip all but one copy
  * @returns Promise with the destination path
  */
-export async function duplicateDirectory(source: string, destination: string): Promise<string> {
-  return invoke('duplicate_directory', { source, destination });
+export async function duplicateDirectory(
+  source: string,
+  destination: string,
+  mode: ImportMode = 'copy',
+  dedup: DedupMode = 'off'
+): Promise<string> {
+  return invoke('duplicate_directory', { source, destination, mode, dedup });
+}
+
+/**
+ * Get the duplicate report written when a project was imported
+ * @param path Project directory
+ * @returns Promise with the report, or null if the import didn't check for duplicates
+ */
+export async function getDedupReport(path: string): Promise<DedupReport | null> {
+  return invoke('get_dedup_report', { path });
+}
+
+/**
+ * Import the files of a folder into an existing project instead of replacing it
+ * @param source Folder to import from
+ * @param project Project directory to import into
+ * @param policy What to do with files whose name is already taken
+ * @param mode How files are brought in (copy, hard links, reflinks or links to the originals)
+ * @returns Promise with the files that were added, renamed, overwritten or skipped
+ */
+export async function mergeImportDirectory(
+  source: string,
+  project: string,
+  policy: ConflictPolicy = 'skip',
+  mode: ImportMode = 'copy'
+): Promise<MergeReport> {
+  return invoke('merge_import_directory', { source, project, policy, mode });
+}
+
+/**
+ * Compare a project with the folder it was imported from
+ * @param path Project directory
+ * @param apply Whether to bring the differences into the project or only report them
+ * @returns Promise with the added, modified, removed and conflicting files
+ */
+export async function syncProject(path: string, apply = false): Promise<SyncReport> {
+  return invoke('sync_project', { path, apply });
+}
+
+/**
+ * What failed crops and trims left behind in a project
+ */
+export interface CleanupReport {
+  /** Temporary files, and backups identical to their original, that were deleted */
+  removed: string[];
+  /** Backups put back in place of an original that had gone missing */
+  restored: string[];
+  /** Backups that differ from their original, moved into the undo journal */
+  kept_for_undo: string[];
+  failed: string[];
+}
+
+/**
+ * Remove or restore the `_backup` and `_temp` files failed crops and trims left in a project
+ * @param path Path of the project
+ * @returns Promise with what was cleaned up
+ */
+export async function cleanUpEditLeftovers(path: string): Promise<CleanupReport> {
+  return invoke('clean_up_edit_leftovers', { path });
+}
+
+/**
+ * Recreate a project from a ZIP archive made by export
+ * @param zipPath Path of the archive
+ * @returns Promise with the new project and any media or captions missing their pair
+ */
+export async function importExportArchive(zipPath: string): Promise<ArchiveImport> {
+  return invoke('import_export_archive', { zipPath });
+}
+
+/**
+ * Create a project from the folders a photo catalog (Lightroom, Capture One) exported,
+ * flattening subfolders and seeding captions from the XMP sidecars
+ * @param source Folder the catalog exported to
+ * @param mode How media files are brought into the project
+ * @param includeKeywords Whether keywords are appended to the captions as tags
+ * @returns Promise with the new project and the files renamed to avoid clashes
+ */
+export async function importCatalogExport(
+  source: string,
+  mode?: ImportMode,
+  includeKeywords?: boolean
+): Promise<CatalogImport> {
+  return invoke('import_catalog_export', { source, mode, includeKeywords });
+}
+
+/**
+ * Cancel the running directory duplication
+ * @returns Promise that resolves once cancellation has been requested
+ */
+export async function cancelDuplicateDirectory(): Promise<void> {
+  return invoke('cancel_duplicate_directory');
+}
+
+/**
+ * Listen for progress updates while a directory is being duplicated
+ * @param callback Called with the current copy progress
+ * @returns Promise with a function that stops listening
+ */
+export async function onDuplicateProgress(
+  callback: (progress: CopyProgress) => void
+): Promise<UnlistenFn> {
+  return listen<CopyProgress>('duplicate-progress', (event) => callback(event.payload));
 }
 
 /**
@@ -67,19 +304,42 @@ export async function readCaptionFile(path: string): Promise<string> {
  * Write content to a caption file
  * @param path Path to the caption file
  * @param content Content to write
+ * @param sync Flush the write to disk before resolving
+ * @param expected Optional content as it was last read; a file changed on disk since is
+ *   not overwritten and the write fails with a `caption_modified` error
  * @returns Promise that resolves when the file is written
  */
-export async function writeCaptionFile(path: string, content: string): Promise<void> {
-  return invoke('write_caption_file', { path, content });
+export async function writeCaptionFile(
+  path: string,
+  content: string,
+  sync = false,
+  expected?: string
+): Promise<void> {
+  return invoke('write_caption_file', { path, content, sync, expected });
+}
+
+/**
+ * Write several caption files as a single undoable operation
+ * @param captions Array of [caption path, content] tuples
+ * @returns Promise that resolves when all files are written
+ */
+export async function writeCaptionFiles(captions: [string, string][]): Promise<void> {
+  return invoke('write_caption_files', { captions });
 }
 
 /**
  * List all media files in a directory
  * @param directory Directory path to list
+ * @param filter Optional filter to only list files with matching marks
+ * @param language Optional language code whose captions are previewed
  * @returns Promise with an array of media files
  */
-export async function listDirectoryFiles(directory: string): Promise<MediaFile[]> {
-  const files: MediaFile[] = await invoke('list_directory_files', { directory });
+export async function listDirectoryFiles(
+  directory: string,
+  filter?: MarkFilter,
+  language?: string
+): Promise<MediaFile[]> {
+  const files: MediaFile[] = await invoke('list_directory_files', { directory, filter, language });
   
   // Add frontend-specific properties
   return files.map(file => ({
@@ -90,6 +350,31 @@ export async function listDirectoryFiles(directory: string): Promise<MediaFile[]
   }));
 }
 
+/**
+ * List only the files added, changed or removed since a directory was last listed
+ * @param directory The directory to refresh
+ * @param language Caption language to preview instead of the default captions
+ * @returns Promise with the changes
+ */
+export async function refreshDirectory(
+  directory: string,
+  language?: string
+): Promise<DirectoryChanges> {
+  const changes: DirectoryChanges = await invoke('refresh_directory', { directory, language });
+  const withFrontendProperties = (file: MediaFile): MediaFile => ({
+    ...file,
+    type: file.file_type === 'image' ? 'image' : 'video',
+    selected: false,
+    thumbnail: undefined
+  });
+
+  return {
+    added: changes.added.map(withFrontendProperties),
+    changed: changes.changed.map(withFrontendProperties),
+    removed: changes.removed
+  };
+}
+
 /**
  * Get the asset URL for a media file
  * @param relativePath Relative path to the media file
@@ -121,14 +406,70 @@ export async function selectExportDirectory(): Promise<string> {
  * @param sourceDir Source directory path
  * @param destinationDir Destination directory path
  * @param asZip Whether to export as a ZIP file
+ * @param captionFormat Optional caption format to convert captions to in the export
+ * @param embedCaptions Whether to also write captions into the exported images' XMP/IPTC metadata
+ * @param filter Optional filter to only export files with matching marks
+ * @param captionLanguage Optional language whose captions are exported as the captions;
+ *   by default the captions of every language are exported
+ * @param naming Optional naming of the exported files; renamed exports include a
+ *   `renamed.json` mapping the original names to the new ones
  * @returns Promise with the path to the exported directory or ZIP file
  */
 export async function exportDirectory(
   sourceDir: string,
   destinationDir: string,
-  asZip: boolean
+  asZip: boolean,
+  captionFormat?: CaptionFormat,
+  embedCaptions?: boolean,
+  filter?: MarkFilter,
+  captionLanguage?: string,
+  naming?: ExportNaming
 ): Promise<string> {
-  return invoke('export_directory', { sourceDir, destinationDir, asZip });
+  return invoke('export_directory', {
+    sourceDir,
+    destinationDir,
+    asZip,
+    captionFormat,
+    embedCaptions,
+    filter,
+    captionLanguage,
+    naming,
+  });
+}
+
+/**
+ * An archive of a project's captions, without the media
+ */
+export interface CaptionExport {
+  path: string;
+  /** Number of captions in the archive */
+  captions: number;
+}
+
+/**
+ * Export only the captions of the working directory as a small ZIP file, keeping the
+ * relative paths of their media
+ * @param sourceDir Source directory path
+ * @param destinationDir Directory the ZIP file is written to
+ * @param captionFormat Optional caption format to convert captions to
+ * @param filter Optional filter to only export the captions of files with matching marks
+ * @param captionLanguage Optional language whose captions are exported as the captions
+ * @returns Promise with the path to the ZIP file and the number of captions in it
+ */
+export async function exportCaptionFiles(
+  sourceDir: string,
+  destinationDir: string,
+  captionFormat?: CaptionFormat,
+  filter?: MarkFilter,
+  captionLanguage?: string
+): Promise<CaptionExport> {
+  return invoke('export_caption_files', {
+    sourceDir,
+    destinationDir,
+    captionFormat,
+    filter,
+    captionLanguage,
+  });
 }
 
 /**
@@ -146,7 +487,46 @@ export function formatFileSize(bytes: number): string {
 }
 
 /**
- * List all project directories in the app data directory
+ * The directory that holds all project working copies
+ */
+export interface WorkingRootInfo {
+  path: string;
+  default_path: string;
+  is_default: boolean;
+  /** Free space on the drive, if the platform reports it */
+  available_bytes: number | null;
+}
+
+/**
+ * Outcome of moving the working directory
+ */
+export interface WorkingRootMove {
+  path: string;
+  projects: number;
+  bytes: number;
+  /** Old project directories that couldn't be removed after copying */
+  leftovers: string[];
+}
+
+/**
+ * Get the directory that holds all project working copies
+ * @returns Promise with the working directory and its free space
+ */
+export async function getWorkingRoot(): Promise<WorkingRootInfo> {
+  return invoke('get_working_root');
+}
+
+/**
+ * Move the working directory, with every project, to another location (e.g. a larger drive)
+ * @param destination Directory to create the `spacecat-working` directory in
+ * @returns Promise with the new location and what was moved
+ */
+export async function moveWorkingRoot(destination: string): Promise<WorkingRootMove> {
+  return invoke('move_working_root', { destination });
+}
+
+/**
+ * List all project directories in the working directory
  * @returns Promise with an array of project directories
  */
 export async function listProjectDirectories(): Promise<ProjectDirectory[]> {
@@ -177,6 +557,24 @@ export async function openProjectDirectory(path: string): Promise<void> {
   return invoke('open_project_directory', { path });
 }
 
+/**
+ * Show a media file selected in the system's file explorer
+ * @param path Path to the media file
+ * @returns Promise that resolves when the file explorer is opened
+ */
+export async function revealMediaFile(path: string): Promise<void> {
+  return invoke('reveal_media_file', { path });
+}
+
+/**
+ * Open a media file in the system's default application for its type
+ * @param path Path to the media file
+ * @returns Promise that resolves when the application is launched
+ */
+export async function openWithDefaultApp(path: string): Promise<void> {
+  return invoke('open_with_default_app', { path });
+}
+
 /**
  * Duplicate a media file and its associated caption file
  * @param path Path to the media file
@@ -194,3 +592,103 @@ export async function duplicateMediaFile(path: string): Promise<MediaFile> {
 export async function deleteMediaFile(path: string): Promise<void> {
   return invoke('delete_media_file', { path });
 }
+
+/** Result of deleting several media files at once */
+export interface DeleteSummary {
+  deleted: number;
+  /** Captions deleted along with the media, counting each language */
+  captions: number;
+  /** Size of the deleted media files */
+  bytes: number;
+}
+
+/**
+ * Delete several media files of a project with their captions, as one undoable
+ * operation. Nothing is deleted unless every file can be.
+ * @param paths Paths to the media files, all inside the same project
+ * @returns Promise with what was deleted
+ */
+export async function deleteMediaFiles(paths: string[]): Promise<DeleteSummary> {
+  return invoke('delete_media_files', { paths });
+}
+
+/**
+ * Rename a media file with its captions in every language, as one undoable operation
+ * @param path Path to the media file
+ * @param newName New file name, keeping a media extension
+ * @returns Promise with the new path of the file
+ */
+export async function renameMediaFile(path: string, newName: string): Promise<string> {
+  return invoke('rename_media_file', { path, newName });
+}
+
+/**
+ * Rename a project directory
+ * @param path Path to the project directory
+ * @param newName New name for the project
+ * @returns Promise with the new project path
+ */
+export async function renameProject(path: string, newName: string): Promise<string> {
+  return invoke('rename_project', { path, newName });
+}
+
+/**
+ * Clone a project directory under a new unique name
+ * @param path Path to the project directory
+ * @returns Promise with the path of the cloned project
+ */
+export async function cloneProject(path: string): Promise<string> {
+  return invoke('clone_project', { path });
+}
+
+/**
+ * Recognize the error of a file operation that failed because the file is in use
+ * @param error Error thrown by a command
+ * @returns The details, or null for any other error
+ */
+export function parseFileInUse(error: unknown): FileInUseError | null {
+  if (typeof error !== 'string' || !error.startsWith('{')) return null;
+  try {
+    const parsed = JSON.parse(error);
+    return parsed?.kind === 'file_in_use' ? parsed as FileInUseError : null;
+  } catch {
+    return null;
+  }
+}
+
+/** New files of a hot folder, ready to be captioned */
+export interface HotFolderFiles {
+  project: string;
+  files: MediaFile[];
+}
+
+/**
+ * Turn a project into a hot folder whose new media files are announced for captioning,
+ * or back into a plain one
+ * @param path Path to the project directory
+ * @param enabled Whether to watch the project
+ */
+export async function setHotFolder(path: string, enabled: boolean): Promise<void> {
+  return invoke('set_hot_folder', { path, enabled });
+}
+
+/**
+ * Listen for new files of hot folders, announced once they finish copying
+ * @param callback Called with the project and its new files
+ * @returns Promise with a function that stops listening
+ */
+export async function onHotFolderFiles(
+  callback: (event: HotFolderFiles) => void
+): Promise<UnlistenFn> {
+  return listen<HotFolderFiles>('hot-folder-files', event => {
+    callback({
+      ...event.payload,
+      files: event.payload.files.map(file => ({
+        ...file,
+        type: file.file_type === 'image' ? 'image' : 'video',
+        selected: false,
+        thumbnail: undefined
+      }))
+    });
+  });
+}
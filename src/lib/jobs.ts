@@ -0,0 +1,120 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/** Event emitted with a JobInfo whenever a job is queued, makes progress or finishes */
+export const JOB_EVENT = 'job-progress';
+
+export type JobKind = 'import' | 'export' | 'trim' | 'crop' | 'captioning' | 'thumbnails' | 'indexing';
+
+export type JobStatus = 'queued' | 'running' | 'completed' | 'failed' | 'cancelled';
+
+export interface JobProgress {
+  current: number;
+  /** 0 while the total is unknown */
+  total: number;
+  message: string | null;
+}
+
+export interface JobInfo {
+  id: string;
+  kind: JobKind;
+  description: string;
+  status: JobStatus;
+  progress: JobProgress;
+  error: string | null;
+  created: string;
+  finished: string | null;
+}
+
+/**
+ * List queued, running and recently finished jobs
+ * @returns Promise with the jobs, oldest first
+ */
+export async function listJobs(): Promise<JobInfo[]> {
+  return invoke('list_jobs');
+}
+
+/**
+ * Cancel a queued or running job; it stops at its next checkpoint
+ * @param id ID of the job to cancel
+ */
+export async function cancelJob(id: string): Promise<void> {
+  return invoke('cancel_job', { id });
+}
+
+/** A job that was still running when the app last exited or crashed, or a finished job with failed items */
+export interface InterruptedJob {
+  id: string;
+  kind: JobKind;
+  description: string;
+  /** Arguments to start the job again (snake_case, never including API keys) */
+  params: Record<string, unknown>;
+  /** Items finished before the interruption, as [item, latest result] pairs */
+  completed: [string, string][];
+  /** Items still to process, in their original order */
+  remaining: string[];
+  started: string;
+}
+
+/**
+ * List jobs interrupted by a crash or by quitting the app, to offer resuming them
+ * @returns Promise with the interrupted jobs, oldest first
+ */
+export async function listInterruptedJobs(): Promise<InterruptedJob[]> {
+  return invoke('list_interrupted_jobs');
+}
+
+/**
+ * Forget an interrupted job once it was resumed, or when it won't be
+ * @param id ID of the interrupted job
+ */
+export async function discardInterruptedJob(id: string): Promise<void> {
+  return invoke('discard_interrupted_job', { id });
+}
+
+/**
+ * List finished caption jobs with failed files, whose failures retryFailed can caption again
+ * @returns Promise with the jobs and their latest results, oldest first
+ */
+export async function listFailedJobs(): Promise<InterruptedJob[]> {
+  return invoke('list_failed_jobs');
+}
+
+/**
+ * Caption the failed files of a finished job again (runs as a job), merging the new results
+ * into the job's record
+ * @param jobId ID of the finished job
+ * @param apiKey API key for OpenAI and Gemini jobs, since keys are never recorded
+ * @returns Promise with every result of the job, the retried files with their new result
+ */
+export async function retryFailed(jobId: string, apiKey?: string): Promise<[string, string][]> {
+  return invoke('retry_failed', { jobId, apiKey });
+}
+
+/** How finished jobs are announced */
+export interface NotificationSettings {
+  /** Show a desktop notification (default: true) */
+  desktop: boolean;
+  /**
+   * URL a JSON summary of each finished job is POSTed to, e.g. a Discord or Slack
+   * webhook; the summary has text and content fields those services display
+   */
+  webhook_url: string | null;
+  /** Jobs that finish sooner are not announced (default: 30) */
+  min_duration_secs: number;
+}
+
+/**
+ * Get how finished jobs are announced
+ * @returns Promise with the notification settings
+ */
+export async function getNotificationSettings(): Promise<NotificationSettings> {
+  return invoke('get_notification_settings');
+}
+
+/**
+ * Change how finished jobs are announced; cancelled jobs are never announced
+ * @param settings New notification settings
+ */
+export async function updateNotificationSettings(settings: NotificationSettings): Promise<void> {
+  return invoke('update_notification_settings', { settings });
+}
@@ -1,3 +1,4 @@
+import { invoke } from '@tauri-apps/api/core';
 import { attachConsole } from '@tauri-apps/plugin-log';
 
 /**
@@ -54,3 +55,39 @@ function setupConsoleForwarding() {
     originalConsole.error(...args);
   };
 }
+
+/**
+ * How much is logged
+ */
+export type LogLevel = 'off' | 'error' | 'warn' | 'info' | 'debug' | 'trace';
+
+/**
+ * Log levels of the app and of single modules
+ */
+export interface LogSettings {
+  /** Level of the app's own modules without a level of their own */
+  level: LogLevel;
+  /** Levels by module path, e.g. "media" or "api::commands", or dependency crate */
+  modules: Record<string, LogLevel>;
+}
+
+/**
+ * Get the log level of the app and the levels of single modules
+ * @returns Promise with the log settings
+ */
+export async function getLogSettings(): Promise<LogSettings> {
+  return invoke('get_log_settings');
+}
+
+/**
+ * Change how much is logged, from now on and after restarts
+ * @param level The new level, or null to remove the level of a module
+ * @param module Optional module to set the level of, e.g. "media"; the whole app otherwise
+ * @returns Promise with the updated log settings
+ */
+export async function setLogLevel(
+  level: LogLevel | null,
+  module?: string
+): Promise<LogSettings> {
+  return invoke('set_log_level', { level, module });
+}
@@ -1,13 +1,238 @@
-import { invoke } from '@tauri-apps/api/core';
+import { convertFileSrc, invoke } from '@tauri-apps/api/core';
+
+export interface RawConversion {
+  converted: string[];
+  skipped: string[];
+  failed: string[];
+}
+
+export interface PdfConversion {
+  /** Documents whose pages became images, by relative path */
+  converted: string[];
+  /** Page images written, by relative path */
+  pages: string[];
+  /** Documents whose page images already exist */
+  skipped: string[];
+  failed: string[];
+}
+
+/** The document and page a page image was rasterized from */
+export interface PdfPage {
+  /** Relative path of the document */
+  document: string;
+  /** Page number, starting at 1 */
+  page: number;
+}
+
+/** Real-ESRGAN model: photos, anime art, or a lighter anime model */
+export type UpscaleModel = 'general' | 'anime' | 'anime_fast';
+
+export interface UpscaleSummary {
+  upscaled: string[];
+  /** Videos and formats that can't be written back */
+  skipped: string[];
+  failed: string[];
+}
+
+export interface DownscaleSummary {
+  downscaled: string[];
+  /** Videos in containers that can't be re-encoded in place, e.g. MPEG */
+  skipped: string[];
+  failed: string[];
+}
+
+export interface TileSummary {
+  /** Images split into tiles */
+  tiled: string[];
+  /** Tiles written */
+  tiles: string[];
+  /** Videos and images that aren't wide or tall enough, or already tiled */
+  skipped: string[];
+  failed: string[];
+}
+
+export type ImageTargetFormat = 'jpeg' | 'png' | 'webp';
+
+export interface ImageConversion {
+  converted: string[];
+  /** Images already in the target format, or with a converted file next to them */
+  skipped: string[];
+  failed: string[];
+}
+
+/** A named aspect ratio images can be cropped to */
+export interface CropPreset {
+  /** Name passed as the aspect to cropToAspect, e.g. "16:9" or "sdxl-1216x832" */
+  name: string;
+  label: string;
+  width: number;
+  height: number;
+  /** Whether cropped images are also resized to exactly width by height (SDXL buckets) */
+  resize: boolean;
+}
+
+/** Side of an image kept when cropping */
+export type CropGravity = 'center' | 'top' | 'bottom' | 'left' | 'right';
+
+export interface CropSummary {
+  cropped: string[];
+  /** Videos, formats that can't be written back, and images already at the aspect */
+  skipped: string[];
+  failed: string[];
+}
+
+/** Width of solid borders on each side of an image, in pixels */
+export interface Borders {
+  top: number;
+  bottom: number;
+  left: number;
+  right: number;
+}
+
+export type ImageCorner = 'top_left' | 'top_right' | 'bottom_left' | 'bottom_right';
+
+/** An image with solid borders or a likely watermark */
+export interface BorderReport {
+  path: string;
+  relative_path: string;
+  width: number;
+  height: number;
+  borders: Borders;
+  /** Dark bars on opposite sides, as left by letterboxed video frames */
+  letterboxed: boolean;
+  /** Corner with far more fine detail than the rest of the image, as text and logos have */
+  watermark: ImageCorner | null;
+}
+
+export interface PaletteColor {
+  /** `#rrggbb` */
+  hex: string;
+  /** Share of the pixels (or, for backgrounds, of the images), from 0 to 1 */
+  share: number;
+}
+
+export interface ImageColors {
+  /** Share of the pixels in each of 32 luminance bins, darkest first */
+  histogram: number[];
+  /** Mean luminance from 0 to 255 */
+  mean_luminance: number;
+  /** Dominant colors, most common first */
+  palette: PaletteColor[];
+  /** Most common color along the edges, which is usually the background */
+  background: PaletteColor | null;
+}
+
+export interface DatasetColors {
+  images: number;
+  /** Mean of the images' luminance histograms */
+  histogram: number[];
+  mean_luminance: number;
+  /** Share of the images that are mostly dark */
+  dark_share: number;
+  /** Share of the images that are mostly bright */
+  bright_share: number;
+  /** Dominant colors over all images, each weighted the same */
+  palette: PaletteColor[];
+  /** The most common background, with the share of the images that have it */
+  common_background: PaletteColor | null;
+  /** Biases found, in words */
+  warnings: string[];
+}
+
+export interface JpegQualityReport {
+  path: string;
+  relative_path: string;
+  width: number;
+  height: number;
+  /** Quality (1-100) estimated from the quantization tables, or null without them */
+  quality: number | null;
+  /** How much stronger edges are on the 8x8 block grid than elsewhere; about 1 for clean images */
+  blockiness: number;
+}
+
+/** Why files were grouped into a burst */
+export type BurstReason = 'time' | 'similar' | 'both';
+
+export interface BurstMember {
+  path: string;
+  relative_path: string;
+  /** When it was shot, from its EXIF data */
+  captured: string | null;
+  /** Variance of the Laplacian of the image; higher is sharper */
+  sharpness: number;
+}
+
+export interface Burst {
+  reason: BurstReason;
+  /** In order of capture */
+  members: BurstMember[];
+  /** Path of the sharpest member, the one to keep */
+  best: string;
+}
+
+export interface EmbedSummary {
+  embedded: number;
+  /** Videos, other image formats and files without a caption */
+  skipped: number;
+  /** Relative path and reason of each file that couldn't be rewritten */
+  failed: [string, string][];
+}
+
+export interface FfmpegCapabilities {
+  /** Whether ffmpeg runs at all */
+  available: boolean;
+  /** Version reported by ffmpeg, e.g. "6.1.1" */
+  version: string | null;
+  /** Available encoders, e.g. "libx264" */
+  encoders: string[];
+  /** Hardware acceleration methods, e.g. "videotoolbox" */
+  hwaccels: string[];
+  /** Whether ffprobe runs, which reading video dimensions needs */
+  ffprobe: boolean;
+}
+
+/**
+ * Get what the installed FFmpeg can do, probed once and cached
+ * @param refresh Probe again, e.g. after installing FFmpeg
+ * @returns Promise with the capabilities
+ */
+export async function getFfmpegCapabilities(refresh: boolean = false): Promise<FfmpegCapabilities> {
+  return invoke('get_ffmpeg_capabilities', { refresh });
+}
 
 /**
  * Generate a thumbnail for an image or video file
  * @param path Path to the media file (image or video)
  * @param maxSize Maximum size of the thumbnail (width or height)
+ * @param timeSec Optional position in seconds of the video frame to show (default: the first frame)
  * @returns Promise with the thumbnail as a data URL
  */
-export async function getMediaThumbnail(path: string, maxSize: number = 100): Promise<string> {
-  return invoke('get_media_thumbnail', { path, maxSize });
+export async function getMediaThumbnail(
+  path: string,
+  maxSize: number = 100,
+  timeSec?: number
+): Promise<string> {
+  return invoke('get_media_thumbnail', { path, maxSize, timeSec });
+}
+
+/**
+ * URL of the thumbnail of an image or video file, served from the thumbnail cache by the
+ * spacecat-thumb protocol without a base64 round trip
+ * @param path Path to the media file (image or video)
+ * @param maxSize Maximum size of the thumbnail (width or height)
+ * @param refreshToken Optional token that changes the URL after the file was edited
+ * @param timeSec Optional position in seconds of the video frame to show (default: the first frame)
+ * @returns The thumbnail URL, for an img src
+ */
+export function getThumbnailUrl(
+  path: string,
+  maxSize: number = 100,
+  refreshToken?: number,
+  timeSec?: number
+): string {
+  let url = `${convertFileSrc(path, 'spacecat-thumb')}?size=${maxSize}`;
+  if (timeSec !== undefined) url += `&time=${timeSec}`;
+  return refreshToken ? `${url}&t=${refreshToken}` : url;
 }
 
 /**
@@ -50,6 +275,86 @@ export async function cropVideo(
   return invoke('crop_video', { path, cropParams });
 }
 
+/**
+ * List the built-in crop presets: common aspect ratios and the SDXL buckets
+ * @returns Promise with the presets
+ */
+export async function getCropPresets(): Promise<CropPreset[]> {
+  return invoke('get_crop_presets');
+}
+
+/**
+ * Crop JPEG, PNG and WebP images in place to an aspect ratio, as one undoable operation
+ * @param paths Images to crop
+ * @param aspect A preset name, "sdxl" for the nearest SDXL bucket of each image, or a ratio like "5:4"
+ * @param gravity Side of each image to keep (default: the center)
+ * @returns Promise with the cropped, skipped and failed file names
+ */
+export async function cropToAspect(
+  paths: string[],
+  aspect: string,
+  gravity?: CropGravity
+): Promise<CropSummary> {
+  return invoke('crop_to_aspect', { paths, aspect, gravity });
+}
+
+/**
+ * Find the images of a project with solid borders, letterboxing or a likely corner watermark
+ * @param directory Project directory
+ * @returns Promise with the images that have a finding
+ */
+export async function detectImageBorders(directory: string): Promise<BorderReport[]> {
+  return invoke('detect_image_borders', { directory });
+}
+
+/**
+ * Get the luminance histogram, dominant colors and background color of an image
+ * @param path Path of the image
+ * @returns Promise with the colors of the image
+ */
+export async function getImageColors(path: string): Promise<ImageColors> {
+  return invoke('get_image_colors', { path });
+}
+
+/**
+ * Aggregate the brightness and colors of a project's images to catch color bias before training
+ * @param directory Project directory
+ * @returns Promise with the dataset's colors and warnings about bias
+ */
+export async function getDatasetColors(directory: string): Promise<DatasetColors> {
+  return invoke('get_dataset_colors', { directory });
+}
+
+/**
+ * Find the JPEGs of a project compressed hard enough to show artifacts
+ * @param directory Project directory
+ * @param minQuality Estimated quality below which a JPEG is flagged (default 75)
+ * @returns Promise with the flagged JPEGs, worst first
+ */
+export async function auditJpegQuality(directory: string, minQuality?: number): Promise<JpegQualityReport[]> {
+  return invoke('audit_jpeg_quality', { directory, minQuality });
+}
+
+/**
+ * Group burst shots and near-duplicates of a project, naming the sharpest of each burst to keep
+ * @param directory Project directory
+ * @param windowSeconds Seconds between shots of one burst, by EXIF capture time (default 2)
+ * @param maxDistance Differing bits of the perceptual hashes of near-duplicates, out of 64 (default 6)
+ * @returns Promise with the groups of more than one image
+ */
+export async function findBursts(directory: string, windowSeconds?: number, maxDistance?: number): Promise<Burst[]> {
+  return invoke('find_bursts', { directory, windowSeconds, maxDistance });
+}
+
+/**
+ * Crop the solid borders and letterboxing off images, in place, as one undoable operation
+ * @param paths Images to trim
+ * @returns Promise with the trimmed, skipped and failed file names
+ */
+export async function trimImageBorders(paths: string[]): Promise<CropSummary> {
+  return invoke('trim_image_borders', { paths });
+}
+
 /**
  * Extract a frame from a video at a specific timestamp
  * @param path Path to the video file
@@ -58,4 +363,143 @@ export async function cropVideo(
  */
 export async function extractVideoFrame(path: string, timeSeconds?: number): Promise<string> {
   return invoke('extract_video_frame', { path, time_sec: timeSeconds });
-}
\ No newline at end of file
+}
+
+/**
+ * Convert the camera RAW files (CR2, NEF, ARW, DNG, ...) of a project to JPEG
+ * @param directory Project directory
+ * @param removeOriginals Whether to delete the RAW files after converting them
+ * @returns Promise with the converted, skipped and failed file names
+ */
+export async function convertRawImages(directory: string, removeOriginals = false): Promise<RawConversion> {
+  return invoke('convert_raw_images', { directory, removeOriginals });
+}
+
+/**
+ * Rasterize the pages of the PDF documents in a project to PNG images next to them
+ * @param directory Project directory
+ * @param dpi Resolution of the page images (default 150)
+ * @param removeOriginals Whether to delete the documents after converting them
+ * @returns Promise with the converted documents, the page images and the skipped and failed documents
+ */
+export async function convertPdfDocuments(
+  directory: string,
+  dpi?: number,
+  removeOriginals = false
+): Promise<PdfConversion> {
+  return invoke('convert_pdf_documents', { directory, dpi, removeOriginals });
+}
+
+/**
+ * Get the document and page each page image of a project was rasterized from
+ * @param directory Project directory
+ * @returns Promise with the documents and pages by relative path of the page image
+ */
+export async function getPdfPages(directory: string): Promise<Record<string, PdfPage>> {
+  return invoke('get_pdf_pages', { directory });
+}
+
+/**
+ * Convert images (including HEIC and AVIF) to one format, next to the originals
+ * @param paths Images to convert
+ * @param targetFormat Format to convert to; WebP is written lossless
+ * @param quality JPEG quality from 1 to 100 (default 90)
+ * @param removeOriginals Whether to delete the originals after converting them
+ * @returns Promise with the converted, skipped and failed file names
+ */
+export async function convertImages(
+  paths: string[],
+  targetFormat: ImageTargetFormat,
+  quality?: number,
+  removeOriginals = false
+): Promise<ImageConversion> {
+  return invoke('convert_images', { paths, targetFormat, quality, removeOriginals });
+}
+
+/**
+ * Upscale images in place with Real-ESRGAN, as one undoable operation
+ * @param paths Images to upscale
+ * @param scale Upscaling factor: 2, 3 or 4 (default 4)
+ * @param model Model to upscale with (default 'general')
+ * @returns Promise with the upscaled, skipped and failed file names
+ */
+export async function upscaleImages(
+  paths: string[],
+  scale?: number,
+  model?: UpscaleModel
+): Promise<UpscaleSummary> {
+  return invoke('upscale_images', { paths, scale, model });
+}
+
+/**
+ * Downscale the videos of a project whose shorter side is above a resolution, in place, as one undoable operation
+ * @param directory Project directory
+ * @param maxResolution Shorter side to downscale to, e.g. 1080 (default 1080)
+ * @returns Promise with the downscaled, skipped and failed file names
+ */
+export async function downscaleVideos(directory: string, maxResolution?: number): Promise<DownscaleSummary> {
+  return invoke('downscale_videos', { directory, maxResolution });
+}
+
+/**
+ * Split ultra-wide and ultra-tall images into overlapping square tiles that inherit their captions
+ * @param paths Images to tile
+ * @param minAspect Long side to short side ratio from which an image is tiled (default 2)
+ * @param overlap Share of each tile overlapping the next, from 0 to 0.75 (default 0.25)
+ * @param removeOriginals Whether to delete the tiled images
+ * @returns Promise with the tiled images, the tiles written and the skipped and failed file names
+ */
+export async function tileImages(
+  paths: string[],
+  minAspect?: number,
+  overlap?: number,
+  removeOriginals = false
+): Promise<TileSummary> {
+  return invoke('tile_images', { paths, minAspect, overlap, removeOriginals });
+}
+
+/**
+ * Convert an animated GIF or WebP to an MP4 video next to it
+ * @param path Path of the animation
+ * @param removeOriginal Whether to delete the animation after converting it
+ * @returns Promise with the path of the video
+ */
+export async function convertAnimationToMp4(path: string, removeOriginal = false): Promise<string> {
+  return invoke('convert_animation_to_mp4', { path, removeOriginal });
+}
+
+/**
+ * Generate and cache thumbnails for every media file in a directory in the background
+ * @param directory Project directory
+ * @param maxSize Maximum thumbnail dimension, matching what the grid requests
+ * @returns Promise with the number of thumbnails generated
+ */
+export async function pregenerateThumbnails(directory: string, maxSize: number): Promise<number> {
+  return invoke('pregenerate_thumbnails', { directory, maxSize });
+}
+
+/**
+ * Write each image's caption into its XMP dc:description (and IPTC Caption-Abstract for JPEG),
+ * in place, as one undoable operation
+ * @param directory Project directory
+ * @returns Promise with the number of embedded and skipped files, and the failures
+ */
+export async function embedCaptionMetadata(directory: string): Promise<EmbedSummary> {
+  return invoke('embed_caption_metadata', { directory });
+}
+
+/**
+ * Extract the frames of a video that differ from each other as PNGs next to it, each with a
+ * copy of the video's caption, to turn a video into images for a dataset
+ * @param path Path of the video
+ * @param maxFrames Most frames to keep (default: 20)
+ * @param minDifference Least difference from the last kept frame, from 0 to 1 (default: 0.1)
+ * @returns Promise with the paths of the written images
+ */
+export async function extractKeyframes(
+  path: string,
+  maxFrames?: number,
+  minDifference?: number
+): Promise<string[]> {
+  return invoke('extract_keyframes', { path, maxFrames, minDifference });
+}
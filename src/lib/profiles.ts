@@ -0,0 +1,120 @@
+import { invoke } from '@tauri-apps/api/core';
+import { ImageDetailLevel } from './settings';
+
+/** Service a profile captions with */
+export type ProfileProvider = 'openai' | 'gemini' | 'script';
+
+/** Where the API key of a profile comes from: the key itself, or an environment variable */
+export type ApiKeyRef = { value: string } | { env: string };
+
+/** A named provider configuration that caption commands refer to by id */
+export interface ProviderProfile {
+  /** Empty for a profile that was never saved; assigned on save */
+  id: string;
+  name: string;
+  provider: ProfileProvider;
+  /** Endpoint of OpenAI-compatible APIs */
+  api_url: string;
+  api_key: ApiKeyRef | null;
+  /** Model name; Gemini uses its default model when empty */
+  model: string;
+  /** Default prompt; {name}, {stem}, {index} and {frame} are filled in per file */
+  prompt: string;
+  system_instruction: string | null;
+  image_detail: ImageDetailLevel;
+  use_detail_parameter: boolean;
+  temperature: number | null;
+  /** Caption videos from their sound as well as their picture */
+  include_audio: boolean;
+  /** Requests are spaced out to stay under this rate, across all batches using the profile */
+  requests_per_minute: number | null;
+  script_command: string;
+  script_args: string[];
+  script_timeout_secs: number | null;
+}
+
+/**
+ * List the saved provider profiles
+ * @returns Promise with the profiles, in the order they were created
+ */
+export async function listProviderProfiles(): Promise<ProviderProfile[]> {
+  return invoke('list_provider_profiles');
+}
+
+/**
+ * Save a provider profile, adding it if it has no id yet
+ * @param profile The profile to save
+ * @returns Promise with the saved profile, including its id
+ */
+export async function saveProviderProfile(profile: ProviderProfile): Promise<ProviderProfile> {
+  return invoke('save_provider_profile', { profile });
+}
+
+/**
+ * Delete a saved provider profile
+ * @param id ID of the profile
+ */
+export async function deleteProviderProfile(id: string): Promise<void> {
+  return invoke('delete_provider_profile', { id });
+}
+
+/**
+ * Generate a caption for a media file with a saved provider profile
+ * @param profileId ID of the profile
+ * @param mediaPath The path to the media file (image or video)
+ * @param prompt Optional prompt replacing the profile's default prompt
+ * @returns Promise with the generated caption
+ */
+export async function captionWithProfile(
+  profileId: string,
+  mediaPath: string,
+  prompt?: string
+): Promise<string> {
+  return invoke('caption_with_profile', { profileId, mediaPath, prompt });
+}
+
+/**
+ * Generate captions for multiple media files with a saved provider profile, as a resumable
+ * captioning job that keeps to the profile's rate limit
+ * @param profileId ID of the profile
+ * @param mediaPaths Array of paths to media files
+ * @param prompt Optional prompt replacing the profile's default prompt
+ * @returns Promise with array of [path, caption] tuples
+ */
+export async function captionFilesWithProfile(
+  profileId: string,
+  mediaPaths: string[],
+  prompt?: string
+): Promise<[string, string][]> {
+  return invoke('caption_files_with_profile', { profileId, mediaPaths, prompt });
+}
+
+/** Captions of a sample of a project's uncaptioned files, to approve before captioning the rest */
+export interface CaptionSample {
+  /** Sampled files and their captions, or "Error: ..." for failed requests */
+  results: [string, string][];
+  /** The other uncaptioned files, to caption once the sample is approved */
+  remaining: string[];
+  /** Seed the sample was drawn with */
+  seed: number;
+}
+
+/**
+ * Caption a random sample of a project's uncaptioned files without saving the captions, so
+ * a prompt can be checked before the whole batch is run with captionFilesWithProfile
+ * @param directory Path to the project directory
+ * @param profileId ID of the profile
+ * @param sampleSize Number of files to sample
+ * @param prompt Optional prompt replacing the profile's default prompt
+ * @param seed Optional seed to draw the same sample again
+ * @returns Promise with the sample's captions and the remaining uncaptioned files
+ */
+export async function sampleUncaptionedFiles(
+  directory: string,
+  profileId: string,
+  sampleSize: number,
+  prompt?: string,
+  seed?: number
+): Promise<CaptionSample> {
+  return invoke('sample_uncaptioned_files', { directory, profileId, sampleSize, prompt, seed });
+}
@@ -0,0 +1,370 @@
+import { invoke } from '@tauri-apps/api/core';
+import { CaptionFormat } from './captions';
+import { ColorFlag, FileMark, ImportMode } from './fs';
+import { JobInfo } from './jobs';
+import { OperationKind } from './undo';
+
+export interface ProjectStats {
+  file_count: number;
+  image_count: number;
+  video_count: number;
+  captioned_count: number;
+  updated: string | null;
+}
+
+export interface IndexSummary {
+  files: number;
+  captioned: number;
+  /** Files added or changed since the index was last refreshed */
+  updated: number;
+  removed: number;
+  /** Files hashed by this refresh */
+  hashed: number;
+}
+
+export type ReportFormat = 'html' | 'json';
+
+/** Options of a kohya export; anything left unset is derived from the dataset */
+export interface KohyaOptions {
+  repeats?: number;
+  /** Folder name and class token (default: the trigger word, or the project name) */
+  concept?: string;
+  resolution?: number;
+  /** Also write a LoRA training config skeleton */
+  training_config?: boolean;
+  base_model?: string;
+}
+
+export interface KohyaDatasetSettings {
+  resolution: number;
+  repeats: number;
+  enable_bucket: boolean;
+  min_bucket_reso: number;
+  max_bucket_reso: number;
+  bucket_no_upscale: boolean;
+  shuffle_caption: boolean;
+  keep_tokens: number;
+}
+
+export interface KohyaExport {
+  files: number;
+  image_dir: string;
+  dataset_config: string;
+  training_config: string | null;
+  settings: KohyaDatasetSettings;
+}
+
+export interface ClassImageOptions {
+  /** Images to copy (default: as many as the project has) */
+  count?: number;
+  /** Repeats in the folder name (default: 1) */
+  repeats?: number;
+  /** Caption written for every image (default: "a photo of a <class>") */
+  caption?: string;
+  /** Seed of the random pick, for a reproducible selection */
+  seed?: number;
+}
+
+export interface ClassImages {
+  class_dir: string;
+  copied: number;
+  /** Pool images left out for being smaller than the project's images */
+  too_small: number;
+  caption: string;
+  seed: number;
+}
+
+export interface BackupSettings {
+  interval_hours: number | null;
+  before_batch: boolean;
+  include_media: boolean;
+  keep: number;
+}
+
+/** When and where a project was last exported */
+export interface ExportRecord {
+  timestamp: string;
+  /** Directory, archive or remote location the project was exported to */
+  destination: string;
+}
+
+/** How batch captioning commands pace their provider requests */
+export interface BatchSettings {
+  /** Files captioned at the same time (default 1) */
+  parallelism: number;
+  /** Requests per minute across the whole batch, or null for no limit */
+  requests_per_minute: number | null;
+  /** Times a failed request is tried again before the file is given up on (default 0) */
+  retries: number;
+}
+
+export type TriggerPlacement = 'first' | 'after_style_tags' | 'replace_subject';
+
+/** How the trigger word (followed by the class token) is injected into captions */
+export interface TriggerRules {
+  class_token: string | null;
+  placement: TriggerPlacement;
+  /** Tags the token follows with 'after_style_tags' */
+  style_tags: string[];
+  /** Nouns the token replaces with 'replace_subject', e.g. "a woman" */
+  subject_nouns: string[];
+}
+
+/** License, source and creator of a file or a whole project */
+export interface Attribution {
+  /** License name or SPDX identifier, e.g. CC-BY-4.0 */
+  license?: string | null;
+  /** Where the file comes from, e.g. a URL */
+  source?: string | null;
+  creator?: string | null;
+}
+
+export interface DatasetAttribution {
+  /** Defaults for every file of the project */
+  project: Attribution;
+  /** Files with attribution of their own, by relative path */
+  files: Record<string, Attribution>;
+}
+
+export interface ProjectSettings {
+  name: string;
+  source_directory: string | null;
+  import_mode: ImportMode;
+  caption_prompt: string | null;
+  provider: string | null;
+  model: string | null;
+  system_instruction: string | null;
+  temperature: number | null;
+  trigger_word: string | null;
+  /** How the trigger word is injected into captions, or null to leave them alone */
+  trigger_rules: TriggerRules | null;
+  caption_format: CaptionFormat;
+  caption_history_limit: number | null;
+  /** Keep caption files in a git repository, committed after every batch operation */
+  caption_git: boolean;
+  backup: BackupSettings;
+  batch: BatchSettings;
+  stats: ProjectStats;
+  created: string | null;
+  last_opened: string | null;
+  last_export: ExportRecord | null;
+  /** Refuse every change to the project's files, e.g. once the dataset is finished */
+  read_only: boolean;
+  [key: string]: unknown;
+}
+
+/**
+ * Get the settings stored in a project's project.json
+ * @param project Path to the project directory
+ * @returns Promise with the project settings (stats refreshed)
+ */
+export async function getProjectSettings(project: string): Promise<ProjectSettings> {
+  return invoke('get_project_settings', { project });
+}
+
+/** Number of files of each kind */
+export interface FileCounts {
+  total: number;
+  images: number;
+  videos: number;
+  total_bytes: number;
+  /** Files per lowercase extension */
+  by_extension: Record<string, number>;
+}
+
+/** An operation recently applied to a project's files */
+export interface Activity {
+  kind: OperationKind;
+  description: string;
+  timestamp: string;
+  /** Number of files the operation touched */
+  files: number;
+}
+
+/** What the projects screen shows about a project */
+export interface ProjectOverview {
+  name: string;
+  files: FileCounts;
+  captioned: number;
+  /** Share of the files with a caption, from 0 to 100 */
+  caption_coverage: number;
+  /** Latest operations first */
+  recent_activity: Activity[];
+  /** Queued and running jobs mentioning the project */
+  active_jobs: JobInfo[];
+  /** Interrupted jobs working on the project's files, waiting to be resumed */
+  interrupted_jobs: number;
+  last_export: ExportRecord | null;
+  created: string | null;
+  last_opened: string | null;
+}
+
+/**
+ * Get file counts, size, caption coverage, recent activity, pending jobs and the last export
+ * of a project in one call, for the projects screen
+ * @param path Path to the project directory
+ * @returns Promise with the overview
+ */
+export async function getProjectOverview(path: string): Promise<ProjectOverview> {
+  return invoke('get_project_overview', { path });
+}
+
+/**
+ * Update a project's settings, keeping fields that are not provided
+ * @param project Path to the project directory
+ * @param settings Fields to change
+ * @returns Promise with the updated project settings
+ */
+export async function updateProjectSettings(
+  project: string,
+  settings: Partial<ProjectSettings>
+): Promise<ProjectSettings> {
+  return invoke('update_project_settings', { project, settings });
+}
+
+/**
+ * Bring a project's index up to date, hashing new and changed files (runs as a job)
+ * @param project Path to the project directory
+ * @returns Promise with what the refresh changed
+ */
+export async function refreshProjectIndex(project: string): Promise<IndexSummary> {
+  return invoke('refresh_project_index', { project });
+}
+
+/**
+ * Generate a dataset quality report (file counts, resolution and aspect ratio histograms,
+ * caption coverage and lengths, duplicates, flagged files, licensing)
+ * @param directory Project directory
+ * @param format 'html' (default) or 'json'
+ * @param output Where to write the report; defaults to the project's .spacecat directory
+ * @returns Promise with the path of the written report
+ */
+export async function generateReport(
+  directory: string,
+  format: ReportFormat = 'html',
+  output?: string
+): Promise<string> {
+  return invoke('generate_report', { directory, format, output });
+}
+
+/**
+ * Export a project for kohya's sd-scripts with a derived dataset.toml (runs as a job)
+ * @param directory Project directory
+ * @param destination Directory to export into (outside the project)
+ * @param options Repeats, concept, resolution and training config options
+ * @returns Promise with the written paths and the settings used
+ */
+export async function exportKohyaDataset(
+  directory: string,
+  destination: string,
+  options: KohyaOptions = {}
+): Promise<KohyaExport> {
+  return invoke('export_kohya_dataset', { directory, destination, options });
+}
+
+/**
+ * Copy regularization images matching the project's aspect ratios and resolution into a class folder (runs as a job)
+ * @param directory Project directory
+ * @param pool Directory of generic images to pick from
+ * @param destination Directory the class folder is created in, e.g. a kohya export
+ * @param className Class word, e.g. "woman", naming the folder and the default caption
+ * @param options Count, repeats, caption and seed options
+ * @returns Promise with the class folder, the number of images copied and the caption used
+ */
+export async function generateClassImages(
+  directory: string,
+  pool: string,
+  destination: string,
+  className: string,
+  options: ClassImageOptions = {}
+): Promise<ClassImages> {
+  return invoke('generate_class_images', { directory, pool, destination, className, options });
+}
+
+/**
+ * Get the marks (rating, flag, favorite) of a project's files
+ * @param directory Project directory
+ * @returns Promise with the marks by relative path; unmarked files are left out
+ */
+export async function getFileMarks(directory: string): Promise<Record<string, FileMark>> {
+  return invoke('get_file_marks', { directory });
+}
+
+/**
+ * Rate files from 1 to 5 stars
+ * @param directory Project directory
+ * @param files Relative paths of the files
+ * @param rating The rating, or null to clear it
+ */
+export async function setFileRating(
+  directory: string,
+  files: string[],
+  rating: number | null
+): Promise<void> {
+  return invoke('set_file_rating', { directory, files, rating });
+}
+
+/**
+ * Set the color flag of files
+ * @param directory Project directory
+ * @param files Relative paths of the files
+ * @param flag The flag, or null to clear it
+ */
+export async function setFileFlag(
+  directory: string,
+  files: string[],
+  flag: ColorFlag | null
+): Promise<void> {
+  return invoke('set_file_flag', { directory, files, flag });
+}
+
+/**
+ * Mark files as favorites or not
+ * @param directory Project directory
+ * @param files Relative paths of the files
+ * @param favorite Whether they are favorites
+ */
+export async function setFileFavorite(
+  directory: string,
+  files: string[],
+  favorite: boolean
+): Promise<void> {
+  return invoke('set_file_favorite', { directory, files, favorite });
+}
+
+/**
+ * Get the license, source and creator of a project and of its files
+ * @param directory Project directory
+ * @returns Promise with the project's attribution and the files' own
+ */
+export async function getAttribution(directory: string): Promise<DatasetAttribution> {
+  return invoke('get_attribution', { directory });
+}
+
+/**
+ * Set the license, source or creator of a project, the default for its files. Only the
+ * fields given are changed; an empty string clears a field.
+ * @param directory Project directory
+ * @param attribution The fields to change
+ */
+export async function setProjectAttribution(
+  directory: string,
+  attribution: Attribution
+): Promise<void> {
+  return invoke('set_project_attribution', { directory, attribution });
+}
+
+/**
+ * Set the license, source or creator of files, overriding the project's. Only the
+ * fields given are changed; an empty string clears a field.
+ * @param directory Project directory
+ * @param files Relative paths of the files
+ * @param attribution The fields to change
+ */
+export async function setFileAttribution(
+  directory: string,
+  files: string[],
+  attribution: Attribution
+): Promise<void> {
+  return invoke('set_file_attribution', { directory, files, attribution });
+}
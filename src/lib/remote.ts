@@ -0,0 +1,118 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export interface HubUploadSummary {
+  repo_url: string;
+  commit_url: string | null;
+  /** Whether the repository was created by this upload */
+  created: boolean;
+  uploaded: number;
+  /** Files the Hub already had, e.g. from an earlier or interrupted upload */
+  skipped: number;
+  /** Media files removed from the repository because they left the project */
+  deleted: number;
+  bytes_uploaded: number;
+}
+
+/**
+ * Upload a project to a Hugging Face Hub dataset repository as an imagefolder dataset
+ * (runs as an export job; progress is reported in bytes)
+ * @param directory Project directory
+ * @param repoId Repository id, as owner/name
+ * @param token Hugging Face access token with write access
+ * @param isPrivate Whether the repository is private
+ * @returns Promise with what was uploaded
+ */
+export async function uploadToHub(
+  directory: string,
+  repoId: string,
+  token: string,
+  isPrivate: boolean
+): Promise<HubUploadSummary> {
+  return invoke('upload_to_hub', { directory, repoId, token, private: isPrivate });
+}
+
+/** A bucket on S3 or a compatible service (Cloudflare R2, MinIO, ...) */
+export interface S3Target {
+  /** Service URL, e.g. https://s3.eu-west-1.amazonaws.com or http://localhost:9000 */
+  endpoint: string;
+  /** Region requests are signed for (default: us-east-1; auto for R2) */
+  region?: string;
+  bucket: string;
+  access_key_id: string;
+  secret_access_key: string;
+  session_token?: string;
+  /** Key prefix the export is uploaded under */
+  prefix?: string;
+  /** Put the bucket in the path instead of the host name, as MinIO needs by default */
+  path_style?: boolean;
+}
+
+export interface S3ExportSummary {
+  /** Where the export was uploaded, as s3://bucket/prefix */
+  location: string;
+  uploaded: number;
+  /** Files already in the bucket with the same contents */
+  skipped: number;
+  bytes_uploaded: number;
+}
+
+/**
+ * Export a project to an S3-compatible bucket (runs as an export job; progress is
+ * reported in bytes). Unchanged files are skipped, so an interrupted export can be rerun.
+ * @param directory Project directory
+ * @param target Bucket, credentials and key prefix
+ * @param asZip Upload a single ZIP of the project instead of its files
+ * @returns Promise with what was uploaded
+ */
+export async function exportToS3(
+  directory: string,
+  target: S3Target,
+  asZip: boolean
+): Promise<S3ExportSummary> {
+  return invoke('export_to_s3', { directory, target, asZip });
+}
+
+/** A directory on a server reachable over SSH */
+export interface SftpTarget {
+  host: string;
+  /** Default: 22 */
+  port?: number;
+  username: string;
+  /** Private key file; the SSH agent is used when none is given */
+  private_key?: string;
+  passphrase?: string;
+  /** Directory on the server the project is synced into */
+  remote_path: string;
+  /**
+   * Accepted SHA256:... fingerprint of a server missing from known_hosts, as shown
+   * in the error of the first connection attempt
+   */
+  host_fingerprint?: string;
+}
+
+export interface SftpExportSummary {
+  /** Where the export was synced to, as user@host:path */
+  location: string;
+  uploaded: number;
+  /** Files whose remote copy has the same size and modification time */
+  unchanged: number;
+  /** Remote files removed because they are no longer in the project */
+  deleted: number;
+  bytes_uploaded: number;
+}
+
+/**
+ * Sync a project to a server over SFTP, transferring only changed files (runs as an
+ * export job; progress is reported in bytes)
+ * @param directory Project directory
+ * @param target Server, credentials and remote directory
+ * @param deleteRemoved Also delete remote files that are no longer in the project
+ * @returns Promise with what was transferred
+ */
+export async function exportToSftp(
+  directory: string,
+  target: SftpTarget,
+  deleteRemoved: boolean
+): Promise<SftpExportSummary> {
+  return invoke('export_to_sftp', { directory, target, deleteRemoved });
+}
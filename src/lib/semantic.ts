@@ -0,0 +1,131 @@
+import { invoke } from '@tauri-apps/api/core';
+import { MediaFile } from './fs';
+
+/** A text description, or the path of an example image */
+export type SemanticQuery = { text: string } | { image: string };
+
+export interface SemanticMatch {
+  file: MediaFile;
+  /** Cosine similarity to the query; higher is closer */
+  score: number;
+}
+
+export interface ClipModelInfo {
+  /** Directory that must hold text_model.onnx, vision_model.onnx and tokenizer.json */
+  directory: string;
+  installed: boolean;
+}
+
+/**
+ * Get where the CLIP model used by semantic search is expected, and whether it is there
+ * @returns Promise with the model directory and its state
+ */
+export async function getClipModelInfo(): Promise<ClipModelInfo> {
+  return invoke('get_clip_model_info');
+}
+
+/**
+ * Compute CLIP embeddings for the images of a project that don't have one yet (runs as a job)
+ * @param directory Project directory
+ * @returns Promise with the number of images embedded
+ */
+export async function indexEmbeddings(directory: string): Promise<number> {
+  return invoke('index_embeddings', { directory });
+}
+
+/**
+ * Find the images of a project closest to a text description or an example image
+ * @param directory Project directory
+ * @param query Text to describe the images, or an example image
+ * @param limit Maximum number of matches (default: 50)
+ * @returns Promise with the matches, best first
+ */
+export async function semanticSearch(
+  directory: string,
+  query: SemanticQuery,
+  limit?: number
+): Promise<SemanticMatch[]> {
+  return invoke('semantic_search', { directory, query, limit });
+}
+
+export interface AlignmentScore {
+  file: MediaFile;
+  /** CLIP similarity between the image and its caption */
+  score: number;
+  /** Scored below the threshold, so the caption may not fit the image */
+  flagged: boolean;
+}
+
+export interface AlignmentReport {
+  threshold: number;
+  mean_score: number;
+  flagged: number;
+  /** Captioned images, worst-scoring first */
+  scores: AlignmentScore[];
+}
+
+/**
+ * Score how well each captioned image matches its caption with CLIP (runs as a job)
+ * @param directory Project directory
+ * @param threshold Score below which a pair is flagged (default: 0.2)
+ * @returns Promise with the scores, worst first
+ */
+export async function scoreCaptionAlignment(
+  directory: string,
+  threshold?: number
+): Promise<AlignmentReport> {
+  return invoke('score_caption_alignment', { directory, threshold });
+}
+
+/**
+ * Get where the text detection model is expected, and whether it is there
+ * @returns Promise with the model directory (holding text_detection.onnx) and its state
+ */
+export async function getTextModelInfo(): Promise<ClipModelInfo> {
+  return invoke('get_text_model_info');
+}
+
+export interface TextCoverage {
+  file: MediaFile;
+  /** Share of the image covered by text, from 0 to 1 */
+  coverage: number;
+  /** Covered beyond the threshold, e.g. a meme or a screenshot */
+  flagged: boolean;
+}
+
+export interface TextReport {
+  threshold: number;
+  flagged: number;
+  /** Images, most text first */
+  scores: TextCoverage[];
+}
+
+/**
+ * Score how much of each image of a project is covered by text with an EAST or DB detector
+ * (runs as a job), to filter out memes and screenshots or caption them with an OCR prompt
+ * @param directory Project directory
+ * @param threshold Coverage above which an image is flagged (default: 0.05)
+ * @returns Promise with the coverage of each image, most text first
+ */
+export async function detectTextRegions(directory: string, threshold?: number): Promise<TextReport> {
+  return invoke('detect_text_regions', { directory, threshold });
+}
+
+/**
+ * Get where the subject detection models are expected, and whether they are there
+ * @returns Promise with the model directory (holding person_detection.onnx and optionally face_detection.onnx) and its state
+ */
+export async function getSubjectModelInfo(): Promise<ClipModelInfo> {
+  return invoke('get_subject_model_info');
+}
+
+/**
+ * Count the persons and faces in the images of a project that don't have up-to-date counts (runs as a job)
+ *
+ * Counts are listed with each file and can be filtered on with MarkFilter.
+ * @param directory Project directory
+ * @returns Promise with the number of images counted
+ */
+export async function detectSubjects(directory: string): Promise<number> {
+  return invoke('detect_subjects', { directory });
+}
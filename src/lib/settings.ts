@@ -1,7 +1,9 @@
 import { LazyStore } from '@tauri-apps/plugin-store';
 
 export type ImageDetailLevel = 'auto' | 'low' | 'high';
-export type ApiProvider = 'openai' | 'gemini';
+export type ApiProvider = 'openai' | 'gemini' | 'script';
+/** Captions for model training, or concise alt text for web publishing */
+export type CaptionMode = 'caption' | 'alt_text';
 
 export interface AppSettings {
   // OpenAI settings
@@ -17,9 +19,27 @@ export interface AppSettings {
   geminiModel: string;
   geminiSystemInstruction: string;
   
+  // Caption script settings
+  scriptCommand: string;
+  /** Arguments of the script, separated by spaces */
+  scriptArgs: string;
+  
   // Provider selection
   preferredProvider: ApiProvider;
   useGeminiForVideos: boolean;
+  /** Caption videos from their sound as well as their picture */
+  includeVideoAudio: boolean;
+  
+  // Caption mode
+  captionMode: CaptionMode;
+  /** Length cap of alt text in characters */
+  altTextMaxLength: number;
+  
+  /** Caption media files dropped into the open project automatically */
+  autoCaptionNewFiles: boolean;
+  
+  /** Shorter side imported videos are downscaled to when above it; 0 keeps them as they are */
+  maxVideoResolution: number;
 }
 
 // Default settings
@@ -37,9 +57,24 @@ export const DEFAULT_SETTINGS: AppSettings = {
   geminiModel: 'gemini-2.0-flash',
   geminiSystemInstruction: 'You are an image and video captioner. Do not mention the medium (e.g. image, video) in the caption itself, simply describe it visually. Return only the caption in json format.',
   
+  // Caption script defaults
+  scriptCommand: '',
+  scriptArgs: '',
+  
   // Provider selection defaults
   preferredProvider: 'openai',
-  useGeminiForVideos: true
+  useGeminiForVideos: true,
+  includeVideoAudio: false,
+  
+  // Caption mode defaults
+  captionMode: 'caption',
+  altTextMaxLength: 125,
+  
+  // Hot folder defaults
+  autoCaptionNewFiles: false,
+  
+  // Import defaults
+  maxVideoResolution: 0
 };
 
 // Create a lazy store for settings
@@ -94,6 +129,13 @@ export async function loadSettings(): Promise<AppSettings> {
       needsUpdate = true;
     }
     
+    // Add caption script settings if they don't exist
+    if (settings && !('scriptCommand' in settings)) {
+      (settings as AppSettings).scriptCommand = DEFAULT_SETTINGS.scriptCommand;
+      (settings as AppSettings).scriptArgs = DEFAULT_SETTINGS.scriptArgs;
+      needsUpdate = true;
+    }
+    
     if (settings && !('preferredProvider' in settings)) {
       (settings as AppSettings).preferredProvider = DEFAULT_SETTINGS.preferredProvider;
       needsUpdate = true;
@@ -104,6 +146,27 @@ export async function loadSettings(): Promise<AppSettings> {
       needsUpdate = true;
     }
     
+    if (settings && !('includeVideoAudio' in settings)) {
+      (settings as AppSettings).includeVideoAudio = DEFAULT_SETTINGS.includeVideoAudio;
+      needsUpdate = true;
+    }
+    
+    if (settings && !('captionMode' in settings)) {
+      (settings as AppSettings).captionMode = DEFAULT_SETTINGS.captionMode;
+      (settings as AppSettings).altTextMaxLength = DEFAULT_SETTINGS.altTextMaxLength;
+      needsUpdate = true;
+    }
+    
+    if (settings && !('autoCaptionNewFiles' in settings)) {
+      (settings as AppSettings).autoCaptionNewFiles = DEFAULT_SETTINGS.autoCaptionNewFiles;
+      needsUpdate = true;
+    }
+    
+    if (settings && !('maxVideoResolution' in settings)) {
+      (settings as AppSettings).maxVideoResolution = DEFAULT_SETTINGS.maxVideoResolution;
+      needsUpdate = true;
+    }
+    
     if (needsUpdate) {
       await settingsStore.set('settings', settings);
       await settingsStore.save();
@@ -0,0 +1,36 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export type OperationKind = 'delete' | 'rename' | 'caption_overwrite' | 'media_edit' | 'import' | 'restore';
+
+export interface FileSnapshot {
+  path: string;
+  backup: string | null;
+  /** Where the file was moved, when the operation renamed it */
+  renamed_to?: string;
+}
+
+export interface JournalEntry {
+  id: string;
+  kind: OperationKind;
+  description: string;
+  timestamp: string;
+  files: FileSnapshot[];
+}
+
+/**
+ * Undo the most recent destructive operation in a project
+ * @param project Path to the project directory
+ * @returns Promise with the operation that was reverted
+ */
+export async function undoLastOperation(project: string): Promise<JournalEntry> {
+  return invoke('undo_last_operation', { project });
+}
+
+/**
+ * List the recorded operations of a project
+ * @param project Path to the project directory
+ * @returns Promise with the operations, most recent first
+ */
+export async function listOperationHistory(project: string): Promise<JournalEntry[]> {
+  return invoke('list_operation_history', { project });
+}